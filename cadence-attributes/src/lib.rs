@@ -0,0 +1,114 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Attribute macros for Cadence, an extensible Statsd client for Rust!
+//!
+//! This crate is not meant to be used directly. Enable the `attributes` feature
+//! of the `cadence` crate and use the macros it re-exports instead.
+
+use proc_macro::TokenStream;
+use quote::quote;
+use syn::{parse_macro_input, ItemFn, LitStr};
+
+/// Time each call of a function and send it as a timer using the global
+/// default `StatsdClient`.
+///
+/// The timer is named after the function unless a key is given with `key`,
+/// and key-value tags can be given with `tags`. Timers are sent when the
+/// function returns, including early returns and panics. For `async` functions,
+/// the timer covers the time from when the returned future is first polled
+/// until it completes or is dropped.
+///
+/// If the global default client has not been set with
+/// `cadence::set_global_default`, no timer is sent.
+///
+/// This attribute is only available when the `attributes` feature of `cadence`
+/// is enabled, which requires Rust 1.71 or newer.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{timed, NopMetricSink, StatsdClient};
+///
+/// // Sends the timer "my.prefix.load_config"
+/// #[timed]
+/// fn load_config() {
+///     // ...
+/// }
+///
+/// // Sends the timer "my.prefix.db.query" with the tag "table:users"
+/// #[timed(key = "db.query", tags(table = "users"))]
+/// async fn find_user(id: u64) -> Option<String> {
+///     // ...
+///     # None
+/// }
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// cadence::set_global_default(client).unwrap();
+///
+/// load_config();
+/// ```
+#[proc_macro_attribute]
+pub fn timed(args: TokenStream, input: TokenStream) -> TokenStream {
+    let mut key: Option<LitStr> = None;
+    let mut tags: Vec<(LitStr, LitStr)> = Vec::new();
+
+    let parser = syn::meta::parser(|meta| {
+        if meta.path.is_ident("key") {
+            key = Some(meta.value()?.parse()?);
+            Ok(())
+        } else if meta.path.is_ident("tags") {
+            meta.parse_nested_meta(|tag| {
+                let name = match tag.path.get_ident() {
+                    Some(ident) => LitStr::new(&ident.to_string(), ident.span()),
+                    None => return Err(tag.error("expected a tag name")),
+                };
+
+                tags.push((name, tag.value()?.parse()?));
+                Ok(())
+            })
+        } else {
+            Err(meta.error("unsupported timed property, expected `key` or `tags`"))
+        }
+    });
+
+    parse_macro_input!(args with parser);
+    let ItemFn { attrs, vis, sig, block } = parse_macro_input!(input as ItemFn);
+
+    let key = key.unwrap_or_else(|| LitStr::new(&sig.ident.to_string(), sig.ident.span()));
+    let tag_keys = tags.iter().map(|(k, _)| k);
+    let tag_values = tags.iter().map(|(_, v)| v);
+    let stmts = &block.stmts;
+
+    // The timer is sent when a guard created at the start of the function is
+    // dropped, which covers early returns and, for async functions, happens
+    // when the returned future completes.
+    let expanded = quote! {
+        #(#attrs)*
+        #vis #sig {
+            struct __CadenceTimedGuard(::std::time::Instant);
+
+            impl ::std::ops::Drop for __CadenceTimedGuard {
+                fn drop(&mut self) {
+                    if let ::std::option::Option::Some(client) = ::cadence::get_global_default() {
+                        ::cadence::Timed::time_with_tags(&*client, #key, self.0.elapsed())
+                            #(.with_tag(#tag_keys, #tag_values))*
+                            .send();
+                    }
+                }
+            }
+
+            let __cadence_timed_guard = __CadenceTimedGuard(::std::time::Instant::now());
+            #(#stmts)*
+        }
+    };
+
+    expanded.into()
+}
@@ -0,0 +1,105 @@
+use cadence::{timed, ParsedMetric, SpyMetricSink, StatsdClient};
+use crossbeam_channel::Receiver;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll, Wake, Waker};
+use std::thread::{self, Thread};
+
+#[timed]
+fn load_config() -> u32 {
+    42
+}
+
+#[timed(key = "db.query", tags(table = "users", op = "select"))]
+fn find_user(id: u64) -> Result<String, String> {
+    if id == 0 {
+        return Err("no such user".to_owned());
+    }
+
+    Ok(format!("user{}", id))
+}
+
+struct Cache;
+
+impl Cache {
+    #[timed(key = "cache.get")]
+    async fn get(&self, key: &str) -> Option<usize> {
+        YieldNow(false).await;
+        Some(key.len())
+    }
+}
+
+// Future that is pending the first time it is polled, so that timed async
+// functions are tested across more than one poll.
+struct YieldNow(bool);
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.0 {
+            Poll::Ready(())
+        } else {
+            self.0 = true;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+}
+
+struct ThreadWaker(Thread);
+
+impl Wake for ThreadWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.unpark();
+    }
+}
+
+// Run a future to completion on the current thread without needing a runtime
+fn block_on<F: Future>(fut: F) -> F::Output {
+    let mut fut = Box::pin(fut);
+    let waker = Waker::from(Arc::new(ThreadWaker(thread::current())));
+    let mut cx = Context::from_waker(&waker);
+
+    loop {
+        match fut.as_mut().poll(&mut cx) {
+            Poll::Ready(v) => return v,
+            Poll::Pending => thread::park(),
+        }
+    }
+}
+
+fn read_all_metrics(rx: &Receiver<Vec<u8>>) -> Vec<String> {
+    rx.try_iter().map(|v| String::from_utf8(v).unwrap()).collect()
+}
+
+fn assert_timer(metric: &str, name: &str, tags: &[(Option<&str>, &str)]) {
+    let parsed = ParsedMetric::parse(metric).unwrap();
+    assert_eq!(name, parsed.name());
+    assert_eq!(cadence::MetricType::Timer, parsed.metric_type());
+    assert_eq!(tags, parsed.tags());
+}
+
+#[test]
+fn test_timed() {
+    // NOTE: We're testing everything as part of a single #[test] block since
+    // the global default client can only be set once per process.
+    assert_eq!(42, load_config());
+
+    let (rx, sink) = SpyMetricSink::new();
+    cadence::set_global_default(StatsdClient::from_sink("my.prefix", sink)).unwrap();
+
+    assert_eq!(42, load_config());
+    assert_eq!(Ok("user3".to_owned()), find_user(3));
+    assert!(find_user(0).is_err());
+    assert_eq!(Some(3), block_on(Cache.get("abc")));
+
+    let metrics = read_all_metrics(&rx);
+    let tags = [(Some("table"), "users"), (Some("op"), "select")];
+    assert_eq!(4, metrics.len());
+    assert_timer(&metrics[0], "my.prefix.load_config", &[]);
+    assert_timer(&metrics[1], "my.prefix.db.query", &tags);
+    assert_timer(&metrics[2], "my.prefix.db.query", &tags);
+    assert_timer(&metrics[3], "my.prefix.cache.get", &[]);
+}
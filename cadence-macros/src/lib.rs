@@ -93,6 +93,31 @@
 //! statsd_set!("some.set", 123, "tag" => "val", "another" => "thing");
 //! ```
 //!
+//! ### Formatted Keys
+//!
+//! The `counted!`, `timed!`, and `gauged!` macros build the key of a metric with
+//! `format!`-style interpolation. The value is given after `=>` and tags are given
+//! after a `;`. They use the global default client unless a client is passed as
+//! the first argument, which is useful for libraries or tests that don't set a
+//! global default.
+//!
+//! ```rust,no_run
+//! use cadence::{StatsdClient, NopMetricSink};
+//! use cadence_macros::{counted, timed, gauged};
+//!
+//! let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+//! let route = "users";
+//!
+//! counted!(client, "requests.{}", route);
+//! counted!(client, "requests.{}", route => 2; "method" => "GET");
+//! timed!(client, "requests.{}.latency", route => 123);
+//! gauged!(client, "requests.{}.in_flight", route => 4; "method" => "GET");
+//!
+//! cadence_macros::set_global_default(client);
+//!
+//! counted!("requests.{}", route; "method" => "POST");
+//! ```
+//!
 //! ## Limitations
 //!
 //! Some limitations with the current implemenation of Cadence macros are described below
@@ -101,9 +126,10 @@
 //!   set when using macros: `client.count_with_tags("some.counter", 123).with_tag_value("beta").send()`
 //!
 
-pub use crate::state::{
-    get_global_default, is_global_default_set, set_global_default, GlobalDefaultNotSet, SingletonHolder,
-};
+pub use crate::state::{get_global_default, is_global_default_set, set_global_default, GlobalDefaultNotSet};
+
+#[doc(hidden)]
+pub use cadence::SingletonHolder;
 
 mod macros;
 mod state;
@@ -361,6 +361,169 @@ macro_rules! statsd_set {
     }
 }
 
+/// Increment a counter with a formatted key, optionally using a specific client
+/// and with tags
+///
+/// The key is a format string followed by any arguments for it, the same as
+/// `format!`. The counter is incremented by one unless a value is given after
+/// `=>`. Tags are given after a `;` as key-value pairs. The default global
+/// client is used unless a client is given as the first argument.
+///
+/// Any errors encountered sending metrics will be handled by the error handler
+/// registered with the client.
+///
+/// # Panics
+///
+/// This macro will panic if no client is given and the default global client
+/// has not been set when it is invoked (via `cadence_macros::set_global_default`).
+///
+/// # Examples
+///
+/// ```
+/// use cadence::{StatsdClient, NopMetricSink};
+/// use cadence_macros::counted;
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// let route = "users";
+///
+/// // "my.prefix.requests.users:1|c"
+/// counted!(client, "requests.{}", route);
+/// // "my.prefix.requests.users:3|c"
+/// counted!(client, "requests.{}", route => 3);
+/// // "my.prefix.requests.users:1|c|#method:GET"
+/// counted!(client, "requests.{}", route; "method" => "GET");
+///
+/// cadence_macros::set_global_default(client);
+///
+/// // "my.prefix.requests:3|c|#method:GET,status:200"
+/// counted!("requests" => 3; "method" => "GET", "status" => "200");
+/// ```
+///
+/// # Limitations
+///
+/// Only key-value style tags are supported. Value style tags are not
+/// supported, e.g. `builder.with_tag_value("val")`.
+#[macro_export]
+macro_rules! counted {
+    ($fmt:literal $(, $arg:expr)* $(=> $val:expr)? $(; $($tag_key:expr => $tag_val:expr),*)?) => {
+        $crate::counted!($crate::get_global_default().unwrap(), $fmt $(, $arg)* $(=> $val)? $(; $($tag_key => $tag_val),*)?)
+    };
+
+    ($client:expr, $fmt:literal $(, $arg:expr)* => $val:expr $(; $($tag_key:expr => $tag_val:expr),*)?) => {
+        $crate::_generate_formatted_impl!($client, count_with_tags, ($fmt $(, $arg)*), $val $(, $($tag_key => $tag_val),*)?)
+    };
+
+    ($client:expr, $fmt:literal $(, $arg:expr)* $(; $($tag_key:expr => $tag_val:expr),*)?) => {
+        $crate::_generate_formatted_impl!($client, count_with_tags, ($fmt $(, $arg)*), 1 $(, $($tag_key => $tag_val),*)?)
+    };
+}
+
+/// Emit a timer with a formatted key, optionally using a specific client and
+/// with tags
+///
+/// The key is a format string followed by any arguments for it, the same as
+/// `format!`, and the value of the timer is given after `=>`. Tags are given
+/// after a `;` as key-value pairs. The default global client is used unless a
+/// client is given as the first argument.
+///
+/// Any errors encountered sending metrics will be handled by the error handler
+/// registered with the client.
+///
+/// # Panics
+///
+/// This macro will panic if no client is given and the default global client
+/// has not been set when it is invoked (via `cadence_macros::set_global_default`).
+///
+/// # Examples
+///
+/// ```
+/// use std::time::Duration;
+/// use cadence::{StatsdClient, NopMetricSink};
+/// use cadence_macros::timed;
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// let table = "users";
+///
+/// // "my.prefix.db.users.query:34|ms"
+/// timed!(client, "db.{}.query", table => 34);
+/// // "my.prefix.db.users.query:34|ms|#op:select"
+/// timed!(client, "db.{}.query", table => Duration::from_millis(34); "op" => "select");
+/// ```
+///
+/// # Limitations
+///
+/// Only key-value style tags are supported. Value style tags are not
+/// supported, e.g. `builder.with_tag_value("val")`.
+#[macro_export]
+macro_rules! timed {
+    ($fmt:literal $(, $arg:expr)* => $val:expr $(; $($tag_key:expr => $tag_val:expr),*)?) => {
+        $crate::timed!($crate::get_global_default().unwrap(), $fmt $(, $arg)* => $val $(; $($tag_key => $tag_val),*)?)
+    };
+
+    ($client:expr, $fmt:literal $(, $arg:expr)* => $val:expr $(; $($tag_key:expr => $tag_val:expr),*)?) => {
+        $crate::_generate_formatted_impl!($client, time_with_tags, ($fmt $(, $arg)*), $val $(, $($tag_key => $tag_val),*)?)
+    };
+}
+
+/// Emit a gauge with a formatted key, optionally using a specific client and
+/// with tags
+///
+/// The key is a format string followed by any arguments for it, the same as
+/// `format!`, and the value of the gauge is given after `=>`. Tags are given
+/// after a `;` as key-value pairs. The default global client is used unless a
+/// client is given as the first argument.
+///
+/// Any errors encountered sending metrics will be handled by the error handler
+/// registered with the client.
+///
+/// # Panics
+///
+/// This macro will panic if no client is given and the default global client
+/// has not been set when it is invoked (via `cadence_macros::set_global_default`).
+///
+/// # Examples
+///
+/// ```
+/// use cadence::{StatsdClient, NopMetricSink};
+/// use cadence_macros::gauged;
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// let pool = "db";
+///
+/// // "my.prefix.pool.db.size:12|g"
+/// gauged!(client, "pool.{}.size", pool => 12);
+/// // "my.prefix.pool.db.size:12|g|#region:us-east"
+/// gauged!(client, "pool.{}.size", pool => 12; "region" => "us-east");
+/// ```
+///
+/// # Limitations
+///
+/// Only key-value style tags are supported. Value style tags are not
+/// supported, e.g. `builder.with_tag_value("val")`.
+#[macro_export]
+macro_rules! gauged {
+    ($fmt:literal $(, $arg:expr)* => $val:expr $(; $($tag_key:expr => $tag_val:expr),*)?) => {
+        $crate::gauged!($crate::get_global_default().unwrap(), $fmt $(, $arg)* => $val $(; $($tag_key => $tag_val),*)?)
+    };
+
+    ($client:expr, $fmt:literal $(, $arg:expr)* => $val:expr $(; $($tag_key:expr => $tag_val:expr),*)?) => {
+        $crate::_generate_formatted_impl!($client, gauge_with_tags, ($fmt $(, $arg)*), $val $(, $($tag_key => $tag_val),*)?)
+    };
+}
+
+#[macro_export]
+#[doc(hidden)]
+macro_rules! _generate_formatted_impl {
+    ($client:expr, $method:ident, ($($fmt:tt)*), $val:expr $(, $tag_key:expr => $tag_val:expr)*) => {{
+        use cadence::prelude::*;
+        let client = &$client;
+        let key = format!($($fmt)*);
+        let builder = client.$method(&key, $val);
+        $(let builder = builder.with_tag($tag_key, $tag_val);)*
+        builder.send()
+    }};
+}
+
 #[macro_export]
 #[doc(hidden)]
 macro_rules! _generate_impl {
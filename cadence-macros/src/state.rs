@@ -9,85 +9,10 @@
 // except according to those terms.
 
 use cadence::StatsdClient;
-use std::cell::UnsafeCell;
 use std::error::Error;
 use std::fmt::{self, Display, Formatter};
-use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::Arc;
 
-const UNSET: usize = 0;
-const LOADING: usize = 1;
-const COMPLETE: usize = 2;
-
-/// Global default StatsdClient to be used by macros
-static HOLDER: SingletonHolder<StatsdClient> = SingletonHolder::new();
-
-/// Holder to allow global reads of a value from multiple threads while
-/// allowing the value to be written (set) a single time.
-///
-/// This type is public to allow it to be used in integration tests for
-/// this crate but it is not part of the public API and may change at any
-/// time.
-#[doc(hidden)]
-#[derive(Debug, Default)]
-pub struct SingletonHolder<T> {
-    value: UnsafeCell<Option<Arc<T>>>,
-    state: AtomicUsize,
-}
-
-impl<T> SingletonHolder<T> {
-    /// Create a new empty holder
-    pub const fn new() -> Self {
-        SingletonHolder {
-            value: UnsafeCell::new(None),
-            state: AtomicUsize::new(UNSET),
-        }
-    }
-}
-
-impl<T> SingletonHolder<T> {
-    /// Get a pointer to the contained value if set, None otherwise
-    pub fn get(&self) -> Option<Arc<T>> {
-        if !self.is_set() {
-            return None;
-        }
-
-        // SAFETY: We've ensured that the state is "complete" and the
-        // set method has completed and set a value for the UnsafeCell.
-        unsafe { &*self.value.get() }.clone()
-    }
-
-    pub fn is_set(&self) -> bool {
-        COMPLETE == self.state.load(Ordering::Acquire)
-    }
-
-    /// Set the value if it has not already been set, otherwise this is a no-op
-    pub fn set(&self, val: T) {
-        if self
-            .state
-            .compare_exchange(UNSET, LOADING, Ordering::AcqRel, Ordering::Relaxed)
-            .is_err()
-        {
-            return;
-        }
-
-        // SAFETY: There are no readers at this point since we've guaranteed the
-        // state could not have been "complete". There are no other writers since
-        // we've ensured that the state was previously "unset" and we've been able
-        // to compare-and-swap it to "loading".
-        let ptr = self.value.get();
-        unsafe {
-            *ptr = Some(Arc::new(val));
-        }
-
-        self.state.store(COMPLETE, Ordering::Release);
-    }
-}
-
-unsafe impl<T: Send> Send for SingletonHolder<T> {}
-
-unsafe impl<T: Sync> Sync for SingletonHolder<T> {}
-
 /// Error indicating that a global default `StatsdClient` was not set
 /// when a call to `get_global_default` was made.
 #[derive(Debug)]
@@ -103,7 +28,9 @@ impl Error for GlobalDefaultNotSet {}
 
 /// Set the global default `StatsdClient` instance
 ///
-/// If the global default client has already been set, this method does nothing.
+/// This is the same global default client used by `cadence::set_global_default`
+/// and the functions in the `cadence::global` module. If the global default client
+/// has already been set, this method does nothing.
 ///
 /// # Example
 ///
@@ -114,7 +41,7 @@ impl Error for GlobalDefaultNotSet {}
 /// cadence_macros::set_global_default(client);
 /// ```
 pub fn set_global_default(client: StatsdClient) {
-    HOLDER.set(client);
+    let _ = cadence::set_global_default(client);
 }
 
 /// Get a reference to the global default `StatsdClient` instance
@@ -139,7 +66,7 @@ pub fn set_global_default(client: StatsdClient) {
 /// assert!(global_client.is_ok());
 /// ```
 pub fn get_global_default() -> Result<Arc<StatsdClient>, GlobalDefaultNotSet> {
-    HOLDER.get().ok_or(GlobalDefaultNotSet)
+    cadence::get_global_default().ok_or(GlobalDefaultNotSet)
 }
 
 /// Return true if the global default `StatsdClient` is set, false otherwise
@@ -157,5 +84,5 @@ pub fn get_global_default() -> Result<Arc<StatsdClient>, GlobalDefaultNotSet> {
 /// assert!(cadence_macros::is_global_default_set());
 /// ```
 pub fn is_global_default_set() -> bool {
-    HOLDER.is_set()
+    cadence::is_global_default_set()
 }
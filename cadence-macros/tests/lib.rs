@@ -1,7 +1,7 @@
 use cadence::{SpyMetricSink, StatsdClient};
 use cadence_macros::{
-    statsd_count, statsd_distribution, statsd_gauge, statsd_histogram, statsd_meter, statsd_set, statsd_time,
-    SingletonHolder,
+    counted, gauged, statsd_count, statsd_distribution, statsd_gauge, statsd_histogram, statsd_meter, statsd_set,
+    statsd_time, timed, SingletonHolder,
 };
 use crossbeam_channel::Receiver;
 use std::collections::HashSet;
@@ -114,6 +114,20 @@ fn test_macros() {
         assert!(metrics.contains("my.prefix.some.set:348|s|#service:user,host:app01.example.com"));
     }
 
+    fn test_formatted_macros() {
+        let route = "users";
+        counted!("requests.{}", route);
+        counted!("requests.{}", route => 3; "method" => "GET");
+        timed!("requests.{}.latency", route => Duration::from_millis(12); "method" => "GET", "status" => "200");
+        gauged!("requests.{}.in_flight", route => 2);
+
+        let metrics = read_all_metrics();
+        assert!(metrics.contains("my.prefix.requests.users:1|c"));
+        assert!(metrics.contains("my.prefix.requests.users:3|c|#method:GET"));
+        assert!(metrics.contains("my.prefix.requests.users.latency:12|ms|#method:GET,status:200"));
+        assert!(metrics.contains("my.prefix.requests.users.in_flight:2|g"));
+    }
+
     test_counter_macros();
     test_timer_macros();
     test_gauge_macros();
@@ -121,4 +135,32 @@ fn test_macros() {
     test_histogram_macros();
     test_distribution_macros();
     test_set_macros();
+    test_formatted_macros();
+}
+
+#[test]
+fn test_formatted_macros_explicit_client() {
+    let (rx, sink) = SpyMetricSink::new();
+    let client = StatsdClient::from_sink("other.prefix", sink);
+    let shard = 4;
+
+    counted!(client, "jobs.done");
+    counted!(client, "jobs.shard.{}.done", shard);
+    counted!(client, "jobs.shard.{}.done", shard => 5);
+    counted!(client, "jobs.shard.{}.{}", shard, "failed"; "reason" => "timeout");
+    timed!(client, "jobs.{}.duration", "import" => 250);
+    gauged!(client, "jobs.queue.{}", "pending" => 1.5; "host" => "worker01", "pool" => "a");
+
+    let metrics: Vec<String> = rx.try_iter().map(|v| String::from_utf8(v).unwrap()).collect();
+    assert_eq!(
+        vec![
+            "other.prefix.jobs.done:1|c",
+            "other.prefix.jobs.shard.4.done:1|c",
+            "other.prefix.jobs.shard.4.done:5|c",
+            "other.prefix.jobs.shard.4.failed:1|c|#reason:timeout",
+            "other.prefix.jobs.import.duration:250|ms",
+            "other.prefix.jobs.queue.pending:1.5|g|#host:worker01,pool:a",
+        ],
+        metrics
+    );
 }
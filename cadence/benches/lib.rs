@@ -1,13 +1,17 @@
 use cadence::prelude::*;
 use cadence::{
-    BufferedUdpMetricSink, Counter, Distribution, Gauge, Histogram, Meter, NopMetricSink, QueuingMetricSink, Set,
-    StatsdClient, Timer, UdpMetricSink, DEFAULT_PORT,
+    BufferedUdpMetricSink, Counter, Distribution, Gauge, Histogram, Meter, MetricSink, NopMetricSink,
+    QueuingMetricSink, QueuingMetricSinkBuilder, Set, StatsdClient, Timer, UdpMetricSink, DEFAULT_PORT,
 };
 use criterion::{criterion_group, criterion_main, Criterion};
 use std::net::UdpSocket;
+use std::sync::{Arc, Barrier};
+use std::thread;
+use std::time::{Duration, Instant};
 
 const TARGET_HOST: (&str, u16) = ("127.0.0.1", DEFAULT_PORT);
 const QUEUE_SIZE: usize = 512 * 1024;
+const PRODUCER_THREADS: u64 = 8;
 
 fn new_nop_client() -> StatsdClient {
     StatsdClient::from_sink("client.bench", NopMetricSink)
@@ -46,6 +50,50 @@ fn new_queuing_buffered_udp_client(bound: Option<usize>) -> StatsdClient {
     StatsdClient::from_sink("client.bench", queuing)
 }
 
+// Time how long it takes for several threads to submit `iters` metrics in total
+// to the same queuing sink at once.
+fn emit_contended(sink: &Arc<QueuingMetricSink>, iters: u64) -> Duration {
+    let barrier = Arc::new(Barrier::new(PRODUCER_THREADS as usize + 1));
+    let threads: Vec<_> = (0..PRODUCER_THREADS)
+        .map(|_| {
+            let sink = sink.clone();
+            let barrier = barrier.clone();
+            thread::spawn(move || {
+                barrier.wait();
+                for _ in 0..iters / PRODUCER_THREADS {
+                    let _ = sink.emit("some.counter:4|c");
+                }
+            })
+        })
+        .collect();
+
+    barrier.wait();
+    let start = Instant::now();
+    for t in threads {
+        t.join().unwrap();
+    }
+
+    start.elapsed()
+}
+
+fn benchmark_queuing_contention(c: &mut Criterion) {
+    c.bench_function("queuing_nop_channel_contended", |b| {
+        let sink = Arc::new(QueuingMetricSinkBuilder::new().with_capacity(QUEUE_SIZE).build(NopMetricSink));
+        b.iter_custom(|iters| emit_contended(&sink, iters));
+    });
+
+    #[cfg(feature = "crossbeam-queue")]
+    c.bench_function("queuing_nop_array_queue_contended", |b| {
+        let sink = Arc::new(
+            QueuingMetricSinkBuilder::new()
+                .with_capacity(QUEUE_SIZE)
+                .with_array_queue()
+                .build(NopMetricSink),
+        );
+        b.iter_custom(|iters| emit_contended(&sink, iters));
+    });
+}
+
 fn benchmark_statsdclient_nop(c: &mut Criterion) {
     c.bench_function("statsdclient_nop", |b| {
         let client = new_nop_client();
@@ -196,6 +244,7 @@ criterion_group!(
     benchmark_statsdclient_udp,
     benchmark_statsdclient_buffered_udp,
     benchmark_statsdclient_queuing,
+    benchmark_queuing_contention,
     benchmark_new_metric_obj
 );
 
@@ -0,0 +1,663 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::panic::RefUnwindSafe;
+use std::sync::Arc;
+
+use crate::builder::{AsyncMetricBuilder, MetricBuilder, MetricType, NonFinitePolicy, SanitizePolicy, TagFormat};
+use crate::client::{
+    Counted, CountedExt, Distributed, Gauged, Histogrammed, Metered, Setted, StatsdClient, StatsdClientBuilder, Timed,
+    ToCounterValue, ToCustomValue, ToDistributionValue, ToGaugeValue, ToHistogramValue, ToMeterValue, ToSetValue,
+    ToTimerValue,
+};
+use crate::sample::Rng;
+use crate::sinks::{AsyncMetricSink, NopMetricSink};
+use crate::timing::TimeUnit;
+use crate::types::{
+    Counter, CustomMetric, Distribution, Gauge, Histogram, Meter, Metric, MetricError, MetricResult, Set, Timer,
+};
+
+/// Builder for creating and customizing `AsyncStatsdClient` instances.
+///
+/// Instances of the builder should be created by calling the `::builder()`
+/// method on the `AsyncStatsdClient` struct. The builder supports the same
+/// options as the `StatsdClientBuilder`.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{AsyncStatsdClient, NopMetricSink};
+///
+/// let client = AsyncStatsdClient::builder("prefix", NopMetricSink)
+///     .with_error_handler(|e| eprintln!("metric error: {}", e))
+///     .with_tag("environment", "production")
+///     .build();
+/// ```
+pub struct AsyncStatsdClientBuilder {
+    inner: StatsdClientBuilder,
+    sink: Arc<dyn AsyncMetricSink + Send + Sync>,
+}
+
+impl AsyncStatsdClientBuilder {
+    fn new<T>(prefix: &str, sink: T) -> Self
+    where
+        T: AsyncMetricSink + Send + Sync + 'static,
+    {
+        AsyncStatsdClientBuilder {
+            // Metrics are formatted by a regular client that never sends anything
+            // itself. They are sent by the async sink instead.
+            inner: StatsdClient::builder(prefix, NopMetricSink),
+            sink: Arc::new(sink),
+        }
+    }
+
+    /// Set an error handler to use for metrics sent via `AsyncMetricBuilder::send()`
+    ///
+    /// See `StatsdClientBuilder::with_error_handler()` for more information.
+    pub fn with_error_handler<F>(mut self, errors: F) -> Self
+    where
+        F: Fn(MetricError) + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.inner = self.inner.with_error_handler(errors);
+        self
+    }
+
+    /// Set an error handler that is given the metric that couldn't be sent
+    /// along with the error, for metrics sent via `AsyncMetricBuilder::send()`
+    ///
+    /// See `StatsdClientBuilder::with_metric_error_handler()` for more information.
+    pub fn with_metric_error_handler<F>(mut self, errors: F) -> Self
+    where
+        F: Fn(&str, MetricError) + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.inner = self.inner.with_metric_error_handler(errors);
+        self
+    }
+
+    /// Add a default tag with key and value to every metric published by the
+    /// built [AsyncStatsdClient].
+    pub fn with_tag<K, V>(mut self, key: K, value: V) -> Self
+    where
+        K: ToString,
+        V: ToString,
+    {
+        self.inner = self.inner.with_tag(key, value);
+        self
+    }
+
+    /// Add each of the given key-value tags as default tags to every metric
+    /// published by the built [AsyncStatsdClient].
+    pub fn with_tags<I, K, V>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: ToString,
+        V: ToString,
+    {
+        self.inner = self.inner.with_tags(tags);
+        self
+    }
+
+    /// Add a default tag with only a value to every metric published by the
+    /// built [AsyncStatsdClient].
+    pub fn with_tag_value<K>(mut self, value: K) -> Self
+    where
+        K: ToString,
+    {
+        self.inner = self.inner.with_tag_value(value);
+        self
+    }
+
+    /// Set the format used for the tags of every metric published by the built
+    /// [AsyncStatsdClient], Datadog style tags by default.
+    pub fn with_tag_format(mut self, tag_format: TagFormat) -> Self {
+        self.inner = self.inner.with_tag_format(tag_format);
+        self
+    }
+
+    /// Add a default container ID to every metric published by the built
+    /// [AsyncStatsdClient].
+    pub fn with_container_id<K>(mut self, container_id: K) -> Self
+    where
+        K: ToString,
+    {
+        self.inner = self.inner.with_container_id(container_id);
+        self
+    }
+
+    /// Set how keys and tags of metrics published by the built
+    /// [AsyncStatsdClient] are validated, such as those that contain characters
+    /// used to separate parts of a metric.
+    ///
+    /// See `StatsdClientBuilder::with_sanitize_policy()` for more information.
+    pub fn with_sanitize_policy(mut self, policy: SanitizePolicy) -> Self {
+        self.inner = self.inner.with_sanitize_policy(policy);
+        self
+    }
+
+    /// Set how gauge, histogram, and distribution values published by the
+    /// built [AsyncStatsdClient] that are `NaN` or infinite are handled.
+    ///
+    /// See `StatsdClientBuilder::with_non_finite_policy()` for more information.
+    pub fn with_non_finite_policy(mut self, policy: NonFinitePolicy) -> Self {
+        self.inner = self.inner.with_non_finite_policy(policy);
+        self
+    }
+
+    /// Set a default client-side sample rate for every metric published by
+    /// the built [AsyncStatsdClient].
+    ///
+    /// See `StatsdClientBuilder::with_sample_rate()` for more information.
+    pub fn with_sample_rate(mut self, rate: f64) -> Self {
+        self.inner = self.inner.with_sample_rate(rate);
+        self
+    }
+
+    /// Set a default client-side sample rate for metrics of the given type
+    /// published by the built [AsyncStatsdClient].
+    ///
+    /// See `StatsdClientBuilder::with_type_sample_rate()` for more information.
+    pub fn with_type_sample_rate(mut self, metric_type: MetricType<'_>, rate: f64) -> Self {
+        self.inner = self.inner.with_type_sample_rate(metric_type, rate);
+        self
+    }
+
+    /// Set the random number generator used to decide which sampled metrics
+    /// are sent by the built [AsyncStatsdClient].
+    ///
+    /// See `StatsdClientBuilder::with_rng()` for more information.
+    pub fn with_rng<R>(mut self, rng: R) -> Self
+    where
+        R: Rng + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.inner = self.inner.with_rng(rng);
+        self
+    }
+
+    /// Set a clock used to add a UNIX timestamp in seconds to every metric
+    /// published by the built [AsyncStatsdClient].
+    ///
+    /// See `StatsdClientBuilder::with_clock()` for more information.
+    pub fn with_clock<F>(mut self, clock: F) -> Self
+    where
+        F: Fn() -> u64 + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.inner = self.inner.with_clock(clock);
+        self
+    }
+
+    /// Set the unit that `Duration` values are converted to for timers
+    /// emitted by the built [AsyncStatsdClient].
+    ///
+    /// See `StatsdClientBuilder::with_timer_unit()` for more information.
+    pub fn with_timer_unit(mut self, unit: TimeUnit) -> Self {
+        self.inner = self.inner.with_timer_unit(unit);
+        self
+    }
+
+    /// Set the unit that `Duration` values are converted to for histograms
+    /// emitted by the built [AsyncStatsdClient].
+    ///
+    /// See `StatsdClientBuilder::with_histogram_unit()` for more information.
+    pub fn with_histogram_unit(mut self, unit: TimeUnit) -> Self {
+        self.inner = self.inner.with_histogram_unit(unit);
+        self
+    }
+
+    /// Set the unit that `Duration` values are converted to for distributions
+    /// emitted by the built [AsyncStatsdClient].
+    ///
+    /// See `StatsdClientBuilder::with_distribution_unit()` for more information.
+    pub fn with_distribution_unit(mut self, unit: TimeUnit) -> Self {
+        self.inner = self.inner.with_distribution_unit(unit);
+        self
+    }
+
+    /// Disable the built `AsyncStatsdClient` so that it never sends metrics.
+    ///
+    /// See `StatsdClientBuilder::with_disabled()` for more information.
+    pub fn with_disabled(mut self, disabled: bool) -> Self {
+        self.inner = self.inner.with_disabled(disabled);
+        self
+    }
+
+    /// Construct a new `AsyncStatsdClient` instance based on current settings.
+    pub fn build(self) -> AsyncStatsdClient {
+        AsyncStatsdClient {
+            client: self.inner.build(),
+            sink: self.sink,
+        }
+    }
+
+    /// Construct a new `AsyncStatsdClient` instance based on current settings
+    /// after making sure they are valid.
+    ///
+    /// See `StatsdClientBuilder::try_build()` for more information.
+    pub fn try_build(self) -> MetricResult<AsyncStatsdClient> {
+        Ok(AsyncStatsdClient {
+            client: self.inner.try_build()?,
+            sink: self.sink,
+        })
+    }
+}
+
+impl fmt::Debug for AsyncStatsdClientBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("AsyncStatsdClientBuilder { ... }")
+    }
+}
+
+/// Client for Statsd for use in async applications.
+///
+/// This client emits the same metrics as the `StatsdClient` but sends them via
+/// an `AsyncMetricSink`, returning futures that complete once the metric has
+/// been handed to the sink. This allows the whole emit path to be awaited inside
+/// Tokio, async-std, or other async applications without blocking the runtime
+/// or using `spawn_blocking`.
+///
+/// Methods for each type of metric are inherent methods of the client rather than
+/// traits. Methods ending with `_with_tags` return an `AsyncMetricBuilder` that
+/// can be used to add tags and other attributes before sending the metric.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{AsyncStatsdClient, NopMetricSink};
+///
+/// # async fn example() {
+/// let client = AsyncStatsdClient::from_sink("my.prefix", NopMetricSink);
+///
+/// client.count("some.counter", 1).await.unwrap();
+/// client.time("some.timer", 23).await.unwrap();
+/// client.gauge_with_tags("some.gauge", 5)
+///     .with_tag("region", "us-east-2")
+///     .send()
+///     .await;
+/// # }
+/// ```
+pub struct AsyncStatsdClient {
+    client: StatsdClient,
+    sink: Arc<dyn AsyncMetricSink + Send + Sync>,
+}
+
+impl AsyncStatsdClient {
+    /// Create a new client instance that will use the given prefix for
+    /// all metrics emitted to the given `AsyncMetricSink` implementation.
+    pub fn from_sink<T>(prefix: &str, sink: T) -> Self
+    where
+        T: AsyncMetricSink + Send + Sync + 'static,
+    {
+        Self::builder(prefix, sink).build()
+    }
+
+    /// Create a new builder with the provided prefix and async metric sink.
+    pub fn builder<T>(prefix: &str, sink: T) -> AsyncStatsdClientBuilder
+    where
+        T: AsyncMetricSink + Send + Sync + 'static,
+    {
+        AsyncStatsdClientBuilder::new(prefix, sink)
+    }
+
+    /// Flush the underlying async metric sink.
+    pub async fn flush(&self) -> MetricResult<()> {
+        if self.client.is_disabled() {
+            return Ok(());
+        }
+
+        Ok(self.sink.flush().await?)
+    }
+
+    /// Create a new client that appends the given name to the prefix of this
+    /// client and shares everything else with it.
+    ///
+    /// See `StatsdClient::scoped()` for more information.
+    pub fn scoped(&self, name: &str) -> AsyncStatsdClient {
+        AsyncStatsdClient {
+            client: self.client.scoped(name),
+            sink: self.sink.clone(),
+        }
+    }
+
+    /// Send a full formed `Metric` implementation via the underlying sink.
+    ///
+    /// See `MetricBackend::send_metric()` for more information.
+    pub async fn send_metric<M>(&self, metric: &M) -> MetricResult<()>
+    where
+        M: Metric,
+    {
+        if self.client.is_disabled() {
+            return Ok(());
+        }
+
+        self.sink.emit(metric.as_metric_str()).await?;
+        Ok(())
+    }
+
+    fn builder_for<'a, T>(&'a self, builder: MetricBuilder<'a, 'a, T>) -> AsyncMetricBuilder<'a, 'a, T>
+    where
+        T: Metric + From<String>,
+    {
+        AsyncMetricBuilder::new(builder, &*self.sink)
+    }
+
+    /// Increment or decrement the counter by the given amount.
+    pub async fn count<T>(&self, key: &str, count: T) -> MetricResult<Counter>
+    where
+        T: ToCounterValue,
+    {
+        self.count_with_tags(key, count).try_send().await
+    }
+
+    /// Increment or decrement the counter by the given amount and return
+    /// an `AsyncMetricBuilder` that can be used to add tags to the metric.
+    pub fn count_with_tags<'a, T>(&'a self, key: &'a str, count: T) -> AsyncMetricBuilder<'a, 'a, Counter>
+    where
+        T: ToCounterValue,
+    {
+        self.builder_for(self.client.count_with_tags(key, count))
+    }
+
+    /// Increment the counter by 1.
+    pub async fn incr(&self, key: &str) -> MetricResult<Counter> {
+        self.incr_with_tags(key).try_send().await
+    }
+
+    /// Increment the counter by 1 and return an `AsyncMetricBuilder` that can
+    /// be used to add tags to the metric.
+    pub fn incr_with_tags<'a>(&'a self, key: &'a str) -> AsyncMetricBuilder<'a, 'a, Counter> {
+        self.builder_for(self.client.incr_with_tags(key))
+    }
+
+    /// Decrement the counter by 1.
+    pub async fn decr(&self, key: &str) -> MetricResult<Counter> {
+        self.decr_with_tags(key).try_send().await
+    }
+
+    /// Decrement the counter by 1 and return an `AsyncMetricBuilder` that can
+    /// be used to add tags to the metric.
+    pub fn decr_with_tags<'a>(&'a self, key: &'a str) -> AsyncMetricBuilder<'a, 'a, Counter> {
+        self.builder_for(self.client.decr_with_tags(key))
+    }
+
+    /// Record a timing in milliseconds with the given key.
+    pub async fn time<T>(&self, key: &str, time: T) -> MetricResult<Timer>
+    where
+        T: ToTimerValue,
+    {
+        self.time_with_tags(key, time).try_send().await
+    }
+
+    /// Record a timing in milliseconds with the given key and return an
+    /// `AsyncMetricBuilder` that can be used to add tags to the metric.
+    pub fn time_with_tags<'a, T>(&'a self, key: &'a str, time: T) -> AsyncMetricBuilder<'a, 'a, Timer>
+    where
+        T: ToTimerValue,
+    {
+        self.builder_for(self.client.time_with_tags(key, time))
+    }
+
+    /// Record a gauge value with the given key.
+    pub async fn gauge<T>(&self, key: &str, value: T) -> MetricResult<Gauge>
+    where
+        T: ToGaugeValue,
+    {
+        self.gauge_with_tags(key, value).try_send().await
+    }
+
+    /// Record a gauge value with the given key and return an `AsyncMetricBuilder`
+    /// that can be used to add tags to the metric.
+    pub fn gauge_with_tags<'a, T>(&'a self, key: &'a str, value: T) -> AsyncMetricBuilder<'a, 'a, Gauge>
+    where
+        T: ToGaugeValue,
+    {
+        self.builder_for(self.client.gauge_with_tags(key, value))
+    }
+
+    /// Record a meter value with the given key.
+    pub async fn meter<T>(&self, key: &str, value: T) -> MetricResult<Meter>
+    where
+        T: ToMeterValue,
+    {
+        self.meter_with_tags(key, value).try_send().await
+    }
+
+    /// Record a meter value with the given key and return an `AsyncMetricBuilder`
+    /// that can be used to add tags to the metric.
+    pub fn meter_with_tags<'a, T>(&'a self, key: &'a str, value: T) -> AsyncMetricBuilder<'a, 'a, Meter>
+    where
+        T: ToMeterValue,
+    {
+        self.builder_for(self.client.meter_with_tags(key, value))
+    }
+
+    /// Record a single histogram value with the given key.
+    pub async fn histogram<T>(&self, key: &str, value: T) -> MetricResult<Histogram>
+    where
+        T: ToHistogramValue,
+    {
+        self.histogram_with_tags(key, value).try_send().await
+    }
+
+    /// Record a single histogram value with the given key and return an
+    /// `AsyncMetricBuilder` that can be used to add tags to the metric.
+    pub fn histogram_with_tags<'a, T>(&'a self, key: &'a str, value: T) -> AsyncMetricBuilder<'a, 'a, Histogram>
+    where
+        T: ToHistogramValue,
+    {
+        self.builder_for(self.client.histogram_with_tags(key, value))
+    }
+
+    /// Record a single distribution value with the given key.
+    pub async fn distribution<T>(&self, key: &str, value: T) -> MetricResult<Distribution>
+    where
+        T: ToDistributionValue,
+    {
+        self.distribution_with_tags(key, value).try_send().await
+    }
+
+    /// Record a single distribution value with the given key and return an
+    /// `AsyncMetricBuilder` that can be used to add tags to the metric.
+    pub fn distribution_with_tags<'a, T>(&'a self, key: &'a str, value: T) -> AsyncMetricBuilder<'a, 'a, Distribution>
+    where
+        T: ToDistributionValue,
+    {
+        self.builder_for(self.client.distribution_with_tags(key, value))
+    }
+
+    /// Record a single set value with the given key.
+    pub async fn set<T>(&self, key: &str, value: T) -> MetricResult<Set>
+    where
+        T: ToSetValue,
+    {
+        self.set_with_tags(key, value).try_send().await
+    }
+
+    /// Record a single set value with the given key and return an
+    /// `AsyncMetricBuilder` that can be used to add tags to the metric.
+    pub fn set_with_tags<'a, T>(&'a self, key: &'a str, value: T) -> AsyncMetricBuilder<'a, 'a, Set>
+    where
+        T: ToSetValue,
+    {
+        self.builder_for(self.client.set_with_tags(key, value))
+    }
+
+    /// Record a metric with a type that isn't part of the Statsd spec.
+    ///
+    /// See `StatsdClient::custom()` for more information.
+    pub async fn custom<T>(&self, key: &str, value: T, metric_type: &str) -> MetricResult<CustomMetric>
+    where
+        T: ToCustomValue,
+    {
+        self.custom_with_tags(key, value, metric_type).try_send().await
+    }
+
+    /// Record a metric with a type that isn't part of the Statsd spec and
+    /// return an `AsyncMetricBuilder` that can be used to add tags to the metric.
+    pub fn custom_with_tags<'a, T>(
+        &'a self,
+        key: &'a str,
+        value: T,
+        metric_type: &'a str,
+    ) -> AsyncMetricBuilder<'a, 'a, CustomMetric>
+    where
+        T: ToCustomValue,
+    {
+        self.builder_for(self.client.custom_with_tags(key, value, metric_type))
+    }
+}
+
+impl fmt::Debug for AsyncStatsdClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "AsyncStatsdClient {{ client: {:?}, sink: ... }}", self.client)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::AsyncStatsdClient;
+    use crate::executor::block_on;
+    use crate::sinks::{AsyncMetricSink, SinkFuture};
+    use crate::types::{ErrorKind, Metric, MetricError};
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[derive(Debug, Clone, Default)]
+    struct AsyncSpySink {
+        metrics: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl AsyncMetricSink for AsyncSpySink {
+        fn emit<'a>(&'a self, metric: &'a str) -> SinkFuture<'a, usize> {
+            Box::pin(async move {
+                self.metrics.lock().unwrap().push(metric.to_owned());
+                Ok(metric.len())
+            })
+        }
+    }
+
+    struct AsyncErrorSink;
+
+    impl AsyncMetricSink for AsyncErrorSink {
+        fn emit<'a>(&'a self, _metric: &'a str) -> SinkFuture<'a, usize> {
+            Box::pin(async { Err(io::Error::new(io::ErrorKind::Other, "foo")) })
+        }
+    }
+
+    #[test]
+    fn test_async_statsd_client_metrics() {
+        block_on(async {
+            let sink = AsyncSpySink::default();
+            let client = AsyncStatsdClient::from_sink("prefix", sink.clone());
+
+            client.count("some.counter", 3).await.unwrap();
+            client.incr("some.counter").await.unwrap();
+            client.decr("some.counter").await.unwrap();
+            client.time("some.timer", Duration::from_millis(25)).await.unwrap();
+            client.gauge("some.gauge", 5.5).await.unwrap();
+            client.meter("some.meter", 2).await.unwrap();
+            client.histogram("some.histogram", 4).await.unwrap();
+            client.distribution("some.distribution", 8).await.unwrap();
+            client.set("some.set", 9).await.unwrap();
+            client.custom("some.ratio", 0.5, "pct").await.unwrap();
+
+            assert_eq!(
+                vec![
+                    "prefix.some.counter:3|c",
+                    "prefix.some.counter:1|c",
+                    "prefix.some.counter:-1|c",
+                    "prefix.some.timer:25|ms",
+                    "prefix.some.gauge:5.5|g",
+                    "prefix.some.meter:2|m",
+                    "prefix.some.histogram:4|h",
+                    "prefix.some.distribution:8|d",
+                    "prefix.some.set:9|s",
+                    "prefix.some.ratio:0.5|pct",
+                ],
+                *sink.metrics.lock().unwrap()
+            );
+        });
+    }
+
+    #[test]
+    fn test_async_statsd_client_futures_are_send() {
+        fn assert_send<T: Send>(_: T) {}
+
+        let client = AsyncStatsdClient::from_sink("prefix", AsyncSpySink::default());
+        assert_send(client.count("some.counter", 1));
+        assert_send(client.count_with_tags("some.counter", 1).with_tag("foo", "bar").send());
+    }
+
+    #[test]
+    fn test_async_statsd_client_with_tags() {
+        block_on(async {
+            let sink = AsyncSpySink::default();
+            let client = AsyncStatsdClient::builder("prefix", sink.clone())
+                .with_tag("env", "prod")
+                .build();
+
+            let res = client
+                .count_with_tags("some.counter", 1)
+                .with_tag("region", "us-east-2")
+                .try_send()
+                .await
+                .unwrap();
+
+            assert_eq!(
+                "prefix.some.counter:1|c|#env:prod,region:us-east-2",
+                res.as_metric_str()
+            );
+            assert_eq!(vec![res.as_metric_str()], *sink.metrics.lock().unwrap());
+        });
+    }
+
+    #[test]
+    fn test_async_statsd_client_sampled_out() {
+        block_on(async {
+            let sink = AsyncSpySink::default();
+            let client = AsyncStatsdClient::from_sink("prefix", sink.clone());
+
+            let res = client
+                .count_with_tags("some.counter", 1)
+                .with_sample_rate(0.0)
+                .try_send()
+                .await;
+            assert_eq!("prefix.some.counter:1|c|@0", res.unwrap().as_metric_str());
+            client
+                .count_with_tags("some.counter", 1)
+                .with_sample_rate(0.0)
+                .send()
+                .await;
+
+            assert!(sink.metrics.lock().unwrap().is_empty());
+        });
+    }
+
+    #[test]
+    fn test_async_statsd_client_send_error_handler() {
+        block_on(async {
+            let count = Arc::new(AtomicUsize::new(0));
+            let count_ref = count.clone();
+
+            let client = AsyncStatsdClient::builder("prefix", AsyncErrorSink)
+                .with_error_handler(move |e: MetricError| {
+                    assert_eq!(ErrorKind::IoError, e.kind());
+                    count_ref.fetch_add(1, Ordering::Release);
+                })
+                .build();
+
+            assert!(client.count("some.counter", 1).await.is_err());
+            client.count_with_tags("some.counter", 1).send().await;
+
+            assert_eq!(1, count.load(Ordering::Acquire));
+        });
+    }
+}
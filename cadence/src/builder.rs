@@ -10,13 +10,22 @@
 // except according to those terms.
 
 use crate::client::{MetricBackend, StatsdClient};
-use crate::types::{Metric, MetricError, MetricResult};
+use crate::sample::{self, Rng};
+use crate::sinks::AsyncMetricSink;
+use crate::types::{
+    ErrorKind, Event, EventAlertType, EventPriority, Metric, MetricError, MetricResult, ServiceCheck,
+    ServiceCheckStatus, WriteMetric,
+};
+use std::borrow::Cow;
 use std::fmt::{self, Write};
 use std::marker::PhantomData;
 
-/// Type of metric that knows how to display itself
-#[derive(Debug, Clone, Copy)]
-enum MetricType {
+/// Type of a metric and the suffix used for it in the Statsd format
+///
+/// This type is used when parsing metrics with `ParsedMetric`. Types that
+/// Cadence doesn't know about are represented by `MetricType::Custom`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MetricType<'a> {
     Counter,
     Timer,
     Gauge,
@@ -24,22 +33,284 @@ enum MetricType {
     Histogram,
     Set,
     Distribution,
+    Custom(&'a str),
 }
 
-impl fmt::Display for MetricType {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+impl<'a> MetricType<'a> {
+    pub(crate) fn from_suffix(type_: &'a str) -> Self {
+        match type_ {
+            "c" => MetricType::Counter,
+            "ms" => MetricType::Timer,
+            "g" => MetricType::Gauge,
+            "m" => MetricType::Meter,
+            "h" => MetricType::Histogram,
+            "s" => MetricType::Set,
+            "d" => MetricType::Distribution,
+            t => MetricType::Custom(t),
+        }
+    }
+
+    /// Return the suffix used for this type of metric, such as "c" for counters.
+    pub fn as_str(&self) -> &'a str {
         match *self {
-            MetricType::Counter => "c".fmt(f),
-            MetricType::Timer => "ms".fmt(f),
-            MetricType::Gauge => "g".fmt(f),
-            MetricType::Meter => "m".fmt(f),
-            MetricType::Histogram => "h".fmt(f),
-            MetricType::Set => "s".fmt(f),
-            MetricType::Distribution => "d".fmt(f),
+            MetricType::Counter => "c",
+            MetricType::Timer => "ms",
+            MetricType::Gauge => "g",
+            MetricType::Meter => "m",
+            MetricType::Histogram => "h",
+            MetricType::Set => "s",
+            MetricType::Distribution => "d",
+            MetricType::Custom(t) => t,
+        }
+    }
+}
+
+impl<'a> fmt::Display for MetricType<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Conversion trait for the keys and values of tags added to a metric with
+/// `MetricBuilder::with_tags()`.
+///
+/// This trait allows tags to be given as string slices, or references to
+/// strings such as when iterating over a borrowed `HashMap`.
+pub trait ToTagStr<'a> {
+    fn to_tag_str(self) -> &'a str;
+}
+
+impl<'a> ToTagStr<'a> for &'a str {
+    fn to_tag_str(self) -> &'a str {
+        self
+    }
+}
+
+impl<'a> ToTagStr<'a> for &&'a str {
+    fn to_tag_str(self) -> &'a str {
+        self
+    }
+}
+
+impl<'a> ToTagStr<'a> for &'a String {
+    fn to_tag_str(self) -> &'a str {
+        self.as_str()
+    }
+}
+
+impl<'a> ToTagStr<'a> for &'a Cow<'_, str> {
+    fn to_tag_str(self) -> &'a str {
+        self.as_ref()
+    }
+}
+
+/// Conversion trait for key-value tags added to a metric with
+/// `MetricBuilder::with_tags()`.
+///
+/// This trait is implemented for tuples of keys and values, and references to
+/// them such as when iterating over a slice of tuples.
+pub trait ToTag<'a> {
+    fn to_tag(self) -> (&'a str, &'a str);
+}
+
+impl<'a, K, V> ToTag<'a> for (K, V)
+where
+    K: ToTagStr<'a>,
+    V: ToTagStr<'a>,
+{
+    fn to_tag(self) -> (&'a str, &'a str) {
+        (self.0.to_tag_str(), self.1.to_tag_str())
+    }
+}
+
+impl<'a, 'b, K, V> ToTag<'a> for &'b (K, V)
+where
+    &'b K: ToTagStr<'a>,
+    &'b V: ToTagStr<'a>,
+{
+    fn to_tag(self) -> (&'a str, &'a str) {
+        (self.0.to_tag_str(), self.1.to_tag_str())
+    }
+}
+
+/// Format used for the tags of metrics sent by a client.
+///
+/// By default, tags are written in the Datadog format after the type of the
+/// metric, e.g. `some.counter:1|c|#host:web01`. The Telegraf format writes them
+/// after the name of the metric instead, e.g. `some.counter,host=web01:1|c`,
+/// which is the format understood by the Telegraf statsd input plugin. The
+/// Graphite format also writes them after the name of the metric, using the
+/// tag syntax of Graphite 1.1, e.g. `some.counter;host=web01:1|c`.
+///
+/// Tags with only a value, added by `.with_tag_value()`, can't be represented in
+/// the Telegraf or Graphite formats and are not included in metrics that use
+/// them. Events and service checks are Datadog extensions and always use the
+/// Datadog format.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TagFormat {
+    Datadog,
+    Telegraf,
+    Graphite,
+}
+
+impl TagFormat {
+    // Separator written before each tag for formats that put tags after the
+    // name of the metric, or `None` for the Datadog format.
+    fn inline_separator(self) -> Option<char> {
+        match self {
+            TagFormat::Datadog => None,
+            TagFormat::Telegraf => Some(','),
+            TagFormat::Graphite => Some(';'),
+        }
+    }
+
+    // Characters reserved in the name of a metric in this format.
+    pub(crate) fn name_reserved(self) -> &'static [char] {
+        match self {
+            TagFormat::Datadog => NAME_RESERVED,
+            TagFormat::Telegraf => TELEGRAF_RESERVED,
+            TagFormat::Graphite => GRAPHITE_RESERVED,
+        }
+    }
+
+    // Characters reserved in tag keys in this format. Tags written after the
+    // name of a metric are part of the name, so the same characters are
+    // reserved in their keys and values.
+    pub(crate) fn tag_key_reserved(self) -> &'static [char] {
+        match self {
+            TagFormat::Datadog => TAG_KEY_RESERVED,
+            TagFormat::Telegraf => TELEGRAF_RESERVED,
+            TagFormat::Graphite => GRAPHITE_RESERVED,
+        }
+    }
+
+    // Characters reserved in tag values in this format.
+    pub(crate) fn tag_value_reserved(self) -> &'static [char] {
+        match self {
+            TagFormat::Datadog => TAG_VALUE_RESERVED,
+            TagFormat::Telegraf => TELEGRAF_RESERVED,
+            TagFormat::Graphite => GRAPHITE_RESERVED,
+        }
+    }
+}
+
+impl Default for TagFormat {
+    fn default() -> Self {
+        TagFormat::Datadog
+    }
+}
+
+// Characters that would change how the name, a tag key, or a tag value of a
+// metric is parsed if they were included in it. Telegraf and Graphite tags
+// are written after the name, separated by `,` or `;` and with `=` between keys
+// and values, which Telegraf ends at a space and Graphite doesn't allow `~` in.
+const NAME_RESERVED: &[char] = &[':', '|', '@', '#', '\n'];
+const TAG_KEY_RESERVED: &[char] = &[':', '|', ',', '\n'];
+const TAG_VALUE_RESERVED: &[char] = &['|', ',', '\n'];
+const TELEGRAF_RESERVED: &[char] = &[':', '|', '@', '#', '\n', ',', '=', ' '];
+const GRAPHITE_RESERVED: &[char] = &[':', '|', '@', '#', '\n', ';', '=', '~'];
+
+/// How a client validates keys and tags of metrics, such as those that contain
+/// characters used to separate parts of a metric.
+///
+/// Characters such as `:`, `|`, `@`, `#`, or newlines in a key or tag change
+/// how the metric is parsed by the Statsd server. A newline in a key taken from
+/// user input could even corrupt every metric sent in the same UDP packet. The
+/// `Telegraf` tag format also reserves `,`, `=`, and spaces in keys and tags,
+/// and the `Graphite` tag format reserves `;`, `=`, and `~`.
+/// Empty keys, tags with an empty key like `:value`, and empty tags without a
+/// key are malformed too.
+///
+/// By default, keys and tags are not checked, which is the fastest option when
+/// they never come from user input. The `Strict` policy returns an error with
+/// the kind `InvalidInput` for metrics with invalid keys or tags instead of
+/// sending them. The `Lenient` policy replaces each invalid character with `_`,
+/// leaves out malformed tags, and sends the metric. Metrics with empty keys
+/// can't be fixed and are sent as they are by the `Lenient` policy. Events and
+/// service checks are not affected by the policy since newlines in them are
+/// always escaped.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum SanitizePolicy {
+    Unchecked,
+    Strict,
+    Lenient,
+}
+
+impl Default for SanitizePolicy {
+    fn default() -> Self {
+        SanitizePolicy::Unchecked
+    }
+}
+
+// Write part of a metric, replacing any reserved characters in it with `_`
+// when the policy is lenient. Reserved characters are all ASCII so replacing
+// them doesn't change the length of the part.
+fn write_part<W: Write + ?Sized>(out: &mut W, val: &str, reserved: &[char], policy: SanitizePolicy) -> fmt::Result {
+    if policy != SanitizePolicy::Lenient || !val.contains(reserved) {
+        return out.write_str(val);
+    }
+
+    for c in val.chars() {
+        out.write_char(if reserved.contains(&c) { '_' } else { c })?;
+    }
+
+    Ok(())
+}
+
+/// How a client handles gauge, histogram, and distribution values that are
+/// `NaN` or infinite.
+///
+/// Values that aren't finite are written as `NaN`, `inf`, or `-inf`, which many
+/// Statsd servers fail to parse. By default, the `Error` policy returns an
+/// error with the kind `InvalidInput` for these metrics instead of sending
+/// them, the same as for counters and meters. The `Skip` policy doesn't send
+/// them and doesn't pass an error to the error handler, but methods that
+/// return the metric, like `try_send()`, return an `InvalidInput` error since
+/// there's no metric to return. The `Clamp` policy replaces infinite
+/// values with the largest or smallest finite `f64` and sends the metric, but
+/// still skips metrics with `NaN` values since there's no value to clamp them
+/// to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NonFinitePolicy {
+    Error,
+    Skip,
+    Clamp,
+}
+
+impl Default for NonFinitePolicy {
+    fn default() -> Self {
+        NonFinitePolicy::Error
+    }
+}
+
+impl NonFinitePolicy {
+    // Apply the policy to a value, clamping it if needed, and return false if
+    // the metric should be skipped instead of sent. This is used for values
+    // of metrics created by builders and handles.
+    pub(crate) fn apply(self, val: &mut MetricValue) -> MetricResult<bool> {
+        if self == NonFinitePolicy::Clamp {
+            val.clamp_infinite();
+        }
+
+        if val.is_finite() {
+            Ok(true)
+        } else if self == NonFinitePolicy::Error {
+            Err(MetricError::from((ErrorKind::InvalidInput, "value must be finite")))
+        } else {
+            Ok(false)
         }
     }
 }
 
+// A tag with an empty key, or without a key and with an empty value, can't be
+// parsed by the Statsd server.
+fn is_malformed_tag(key: Option<&str>, value: &str) -> bool {
+    match key {
+        Some(key) => key.is_empty(),
+        None => value.is_empty(),
+    }
+}
+
 /// Holder for primitive metric values that knows how to display itself
 ///
 /// This struct is internal to how various types that are valid for each type
@@ -47,7 +318,7 @@ impl fmt::Display for MetricType {
 /// implemented but is exposed for documentation purposes and advanced use cases.
 ///
 /// Typical use of Cadence shouldn't require interacting with this type.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub enum MetricValue {
     Signed(i64),
     PackedSigned(Vec<i64>),
@@ -58,7 +329,7 @@ pub enum MetricValue {
 }
 
 impl MetricValue {
-    fn count(&self) -> usize {
+    pub(crate) fn count(&self) -> usize {
         match self {
             Self::PackedSigned(x) => x.len(),
             Self::PackedUnsigned(x) => x.len(),
@@ -66,6 +337,81 @@ impl MetricValue {
             _ => 1,
         }
     }
+
+    fn is_finite(&self) -> bool {
+        match self {
+            Self::Float(x) => x.is_finite(),
+            Self::PackedFloat(x) => x.iter().all(|v| v.is_finite()),
+            _ => true,
+        }
+    }
+
+    // Replace infinite values with the largest or smallest finite value, leaving
+    // `NaN` values as they are.
+    fn clamp_infinite(&mut self) {
+        fn clamp(v: &mut f64) {
+            if v.is_infinite() {
+                *v = if *v > 0.0 { f64::MAX } else { f64::MIN };
+            }
+        }
+
+        match self {
+            Self::Float(x) => clamp(x),
+            Self::PackedFloat(x) => x.iter_mut().for_each(clamp),
+            _ => {}
+        }
+    }
+
+    // Write the value(s) using `itoa` and `ryu` instead of the `fmt::Display`
+    // implementations of each type, which are much slower. The output is the
+    // same as the `fmt::Display` implementation of `MetricValue`.
+    pub(crate) fn write_to<W: Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        match self {
+            MetricValue::Signed(v) => write_int(out, *v),
+            MetricValue::PackedSigned(v) => write_packed(out, v, write_int),
+            MetricValue::Unsigned(v) => write_int(out, *v),
+            MetricValue::PackedUnsigned(v) => write_packed(out, v, write_int),
+            MetricValue::Float(v) => write_float(out, *v),
+            MetricValue::PackedFloat(v) => write_packed(out, v, write_float),
+        }
+    }
+}
+
+fn write_int<W, I>(out: &mut W, val: I) -> fmt::Result
+where
+    W: Write + ?Sized,
+    I: itoa::Integer,
+{
+    out.write_str(itoa::Buffer::new().format(val))
+}
+
+fn write_float<W: Write + ?Sized>(out: &mut W, val: f64) -> fmt::Result {
+    let mut buf = ryu::Buffer::new();
+    let formatted = buf.format(val);
+
+    // `ryu` uses scientific notation for very large and very small values and
+    // always includes a fractional part. `fmt::Display` for floats does neither
+    // so fall back to it or trim the fractional part to keep the same output.
+    if formatted.contains('e') {
+        write!(out, "{}", val)
+    } else {
+        out.write_str(formatted.strip_suffix(".0").unwrap_or(formatted))
+    }
+}
+
+fn write_packed<W, T>(out: &mut W, vals: &[T], write: fn(&mut W, T) -> fmt::Result) -> fmt::Result
+where
+    W: Write + ?Sized,
+    T: Copy,
+{
+    for (i, value) in vals.iter().enumerate() {
+        if i > 0 {
+            out.write_char(':')?;
+        }
+        write(out, *value)?;
+    }
+
+    Ok(())
 }
 
 fn write_value<T>(f: &mut fmt::Formatter<'_>, vals: &[T]) -> fmt::Result
@@ -95,25 +441,154 @@ impl fmt::Display for MetricValue {
     }
 }
 
+/// Datadog style tags for a metric or event and the number of bytes they use
+#[derive(Debug, Clone, Default)]
+struct Tags<'a> {
+    tags: Vec<(Option<&'a str>, &'a str)>,
+    // number of tags at the start of `tags` that are client-wide defaults and
+    // may be overridden by tags with the same key added to this metric.
+    defaults: usize,
+    // keep track of the number of bytes we expect to use for the key-value part
+    // of the tags. incrementing this counter when tags are added saves us from
+    // having to loop through the tags to count the expected number of bytes.
+    kv_size: usize,
+}
+
+impl<'a> Tags<'a> {
+    const PREFIX: &'static str = "|#";
+
+    fn with_tag(&mut self, key: &'a str, value: &'a str) {
+        self.remove_default(key);
+        self.tags.push((Some(key), value));
+        self.kv_size += key.len() + 1 /* : */ + value.len();
+    }
+
+    fn with_tag_value(&mut self, value: &'a str) {
+        self.tags.push((None, value));
+        self.kv_size += value.len();
+    }
+
+    fn with_default(&mut self, key: Option<&'a str>, value: &'a str) {
+        match key {
+            Some(key) => {
+                self.tags.push((Some(key), value));
+                self.kv_size += key.len() + 1 /* : */ + value.len();
+            }
+            None => self.with_tag_value(value),
+        }
+
+        self.defaults += 1;
+    }
+
+    fn remove_default(&mut self, key: &str) {
+        let mut i = 0;
+        while i < self.defaults {
+            match self.tags[i] {
+                (Some(k), v) if k == key => {
+                    self.tags.remove(i);
+                    self.defaults -= 1;
+                    self.kv_size -= k.len() + 1 /* : */ + v.len();
+                }
+                _ => i += 1,
+            }
+        }
+    }
+
+    fn write<W: Write + ?Sized>(&self, out: &mut W, policy: SanitizePolicy) -> fmt::Result {
+        let mut first = true;
+        for &(key, value) in self.tags.iter() {
+            if policy == SanitizePolicy::Lenient && is_malformed_tag(key, value) {
+                continue;
+            }
+
+            out.write_str(if first { Self::PREFIX } else { "," })?;
+            first = false;
+            if let Some(key) = key {
+                write_part(out, key, TAG_KEY_RESERVED, policy)?;
+                out.write_char(':')?;
+            }
+            write_part(out, value, TAG_VALUE_RESERVED, policy)?;
+        }
+
+        Ok(())
+    }
+
+    // Return true if any key or value contains characters reserved for
+    // separating parts of a metric in the given format.
+    fn has_reserved(&self, format: TagFormat) -> bool {
+        self.tags.iter().any(|(key, value)| {
+            key.map(|k| k.contains(format.tag_key_reserved())).unwrap_or(false)
+                || value.contains(format.tag_value_reserved())
+        })
+    }
+
+    fn has_malformed(&self) -> bool {
+        self.tags.iter().any(|&(key, value)| is_malformed_tag(key, value))
+    }
+
+    fn size_hint(&self) -> usize {
+        if self.tags.is_empty() {
+            return 0;
+        }
+
+        // prefix, keys and values, commas
+        Self::PREFIX.len() + self.kv_size + self.tags.len() - 1
+    }
+
+    // Write key-value tags after the name of a metric, each preceded by the
+    // given separator, skipping any tags with only a value since the formats
+    // that do this don't support them.
+    fn write_inline<W: Write + ?Sized>(&self, out: &mut W, format: TagFormat, policy: SanitizePolicy) -> fmt::Result {
+        let separator = format.inline_separator().unwrap_or(',');
+        for &(key, value) in self.tags.iter() {
+            if policy == SanitizePolicy::Lenient && is_malformed_tag(key, value) {
+                continue;
+            }
+
+            if let Some(key) = key {
+                out.write_char(separator)?;
+                write_part(out, key, format.tag_key_reserved(), policy)?;
+                out.write_char('=')?;
+                write_part(out, value, format.tag_value_reserved(), policy)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn inline_len(&self) -> usize {
+        self.tags
+            .iter()
+            .map(|(key, value)| match key {
+                Some(key) => 1 /* separator */ + key.len() + 1 /* = */ + value.len(),
+                None => 0,
+            })
+            .sum()
+    }
+}
+
 #[derive(Debug, Clone)]
 pub(crate) struct MetricFormatter<'a> {
     prefix: &'a str,
     key: &'a str,
     val: MetricValue,
-    type_: MetricType,
-    tags: Vec<(Option<&'a str>, &'a str)>,
+    type_: MetricType<'a>,
+    tags: Tags<'a>,
     // Datadog extensions:
     // https://docs.datadoghq.com/developers/dogstatsd/datagram_shell/?tab=metrics#the-dogstatsd-protocol
     timestamp: Option<u64>,
+    // rate the metric was already sampled at by the caller
     sampling_rate: Option<f64>,
+    // rate to sample the metric at when sampling is done by the client
+    sample_rate: Option<f64>,
     container_id: Option<&'a str>,
+    tag_format: TagFormat,
+    sanitize: SanitizePolicy,
+    non_finite: NonFinitePolicy,
     base_size: usize,
-    kv_size: usize,
 }
 
 impl<'a> MetricFormatter<'a> {
-    const TAG_PREFIX: &'static str = "|#";
-
     pub(crate) fn counter(prefix: &'a str, key: &'a str, val: MetricValue) -> Self {
         Self::from_val(prefix, key, val, MetricType::Counter)
     }
@@ -142,36 +617,42 @@ impl<'a> MetricFormatter<'a> {
         Self::from_val(prefix, key, val, MetricType::Set)
     }
 
+    pub(crate) fn custom(prefix: &'a str, key: &'a str, val: MetricValue, metric_type: &'a str) -> Self {
+        Self::from_val(prefix, key, val, MetricType::Custom(metric_type))
+    }
+
     #[rustfmt::skip]
-    fn from_val(prefix: &'a str, key: &'a str, val: MetricValue, type_: MetricType) -> Self {
+    fn from_val(prefix: &'a str, key: &'a str, val: MetricValue, type_: MetricType<'a>) -> Self {
         let value_count = val.count();
         MetricFormatter {
             prefix,
             key,
             type_,
             val,
-            tags: Vec::new(),
-            // keep track of the number of bytes we expect to use for both the key-value
-            // part of the tags for this metric as well as the base metric (name, value,
-            // and type). incrementing these counters when tags are added saves us from
-            // having to loop through the tags to count the expected number of bytes to
-            // allocate.
-            kv_size: 0,
+            tags: Tags::default(),
+            // keep track of the number of bytes we expect to use for the base metric
+            // (name, value, and type) so that we can allocate the right size string.
             base_size: prefix.len() + key.len() + 1 /* : */ + 10 * value_count /* value(s) */ + 1 /* | */ + 2, /* type */
             timestamp: None,
             sampling_rate: None,
+            sample_rate: None,
             container_id: None,
+            tag_format: TagFormat::Datadog,
+            sanitize: SanitizePolicy::Unchecked,
+            non_finite: NonFinitePolicy::Error,
         }
     }
 
     fn with_tag(&mut self, key: &'a str, value: &'a str) {
-        self.tags.push((Some(key), value));
-        self.kv_size += key.len() + 1 /* : */ + value.len();
+        self.tags.with_tag(key, value);
+    }
+
+    fn with_default_tag(&mut self, key: Option<&'a str>, value: &'a str) {
+        self.tags.with_default(key, value);
     }
 
     fn with_tag_value(&mut self, value: &'a str) {
-        self.tags.push((None, value));
-        self.kv_size += value.len();
+        self.tags.with_tag_value(value);
     }
 
     fn with_timestamp(&mut self, timestamp: u64) {
@@ -182,58 +663,167 @@ impl<'a> MetricFormatter<'a> {
         self.container_id = Some(container_id);
     }
 
+    fn with_tag_format(&mut self, tag_format: TagFormat) {
+        self.tag_format = tag_format;
+    }
+
+    fn with_sanitize_policy(&mut self, policy: SanitizePolicy) {
+        self.sanitize = policy;
+    }
+
+    fn with_non_finite_policy(&mut self, policy: NonFinitePolicy) {
+        self.non_finite = policy;
+    }
+
+    // Apply the policy for values that aren't finite to the value of this
+    // metric, returning false if the metric should be skipped instead of sent.
+    fn apply_non_finite(&mut self) -> MetricResult<bool> {
+        self.non_finite
+            .apply(&mut self.val)
+            .map_err(|e| e.with_metric(self.prefix, self.key))
+    }
+
+    // Return an error if the sanitize policy is strict and the name or tags of
+    // this metric are empty or contain characters used to separate parts of a
+    // metric.
+    fn check(&self) -> MetricResult<()> {
+        if self.sanitize != SanitizePolicy::Strict {
+            return Ok(());
+        }
+
+        if self.key.is_empty() {
+            return Err(self.error(ErrorKind::Sanitization, "metric key must not be empty"));
+        }
+
+        let reserved = self.tag_format.name_reserved();
+        if self.prefix.contains(reserved) || self.key.contains(reserved) {
+            let desc = match self.tag_format {
+                TagFormat::Datadog => "metric key must not contain ':', '|', '@', '#', or newlines",
+                TagFormat::Telegraf => "metric key must not contain ':', '|', '@', '#', ',', '=', spaces, or newlines",
+                TagFormat::Graphite => "metric key must not contain ':', '|', '@', '#', ';', '=', '~', or newlines",
+            };
+            return Err(self.error(ErrorKind::Sanitization, desc));
+        }
+
+        if self.tags.has_reserved(self.tag_format) {
+            let desc = match self.tag_format {
+                TagFormat::Datadog => "metric tags must not contain '|', ',', or newlines, or ':' in keys",
+                TagFormat::Telegraf => "metric tags must not contain ':', '|', '@', '#', ',', '=', spaces, or newlines",
+                TagFormat::Graphite => "metric tags must not contain ':', '|', '@', '#', ';', '=', '~', or newlines",
+            };
+            return Err(self.error(ErrorKind::Sanitization, desc));
+        }
+
+        if self.tags.has_malformed() {
+            return Err(self.error(
+                ErrorKind::Sanitization,
+                "metric tags must not have empty keys or be empty",
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn error(&self, kind: ErrorKind, desc: &'static str) -> MetricError {
+        MetricError::from((kind, desc)).with_metric(self.prefix, self.key)
+    }
+
     fn with_sampling_rate(&mut self, rate: f64) {
         self.sampling_rate = Some(rate);
     }
 
-    fn write_base_metric(&self, out: &mut String) {
-        let _ = write!(out, "{}{}:{}|{}", self.prefix, self.key, self.val, self.type_);
+    fn with_sample_rate(&mut self, rate: f64) {
+        self.sample_rate = Some(rate);
     }
 
-    fn write_sampling_rate(&self, out: &mut String) {
-        if let Some(rate) = self.sampling_rate {
-            // See https://github.com/DataDog/datadog-go/blob/v5.5.0/statsd/format.go#L28
-            let _ = write!(out, "|@{}", rate);
+    // Rate sent to the server so that it can scale the values it receives. The
+    // rate that the caller sampled the metric at is multiplied by the rate the
+    // metric is sampled at by Cadence, since it's only sent if both select it.
+    // A client-side rate of 1 means every metric is sent so it isn't included.
+    fn rate(&self) -> Option<f64> {
+        match (self.sampling_rate, self.sample_rate) {
+            (Some(caller), Some(client)) => Some(caller * client),
+            (Some(caller), None) => Some(caller),
+            (None, Some(client)) if client < 1.0 => Some(client),
+            (None, _) => None,
         }
     }
 
-    fn write_tags(&self, out: &mut String) {
-        if !self.tags.is_empty() {
-            out.push_str(Self::TAG_PREFIX);
-            for (i, &(key, value)) in self.tags.iter().enumerate() {
-                if i > 0 {
-                    out.push(',');
-                }
-                if let Some(key) = key {
-                    out.push_str(key);
-                    out.push(':');
-                }
-                out.push_str(value);
-            }
+    pub(crate) fn metric_type(&self) -> MetricType<'a> {
+        self.type_
+    }
+
+    // Decide if this metric should be sent based on the client-side sample rate,
+    // if any. Note that each call to this method makes a new decision.
+    fn is_sampled(&self, rng: &dyn Rng) -> bool {
+        match self.sample_rate {
+            Some(rate) => sample::is_sampled(rate, rng),
+            None => true,
+        }
+    }
+
+    fn write_name<W: Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        let reserved = self.tag_format.name_reserved();
+        write_part(out, self.prefix, reserved, self.sanitize)?;
+        write_part(out, self.key, reserved, self.sanitize)
+    }
+
+    fn write_value_and_type<W: Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        out.write_char(':')?;
+        self.val.write_to(out)?;
+        out.write_char('|')?;
+        out.write_str(self.type_.as_str())
+    }
+
+    // Index of the value of this metric once formatted, right after the name,
+    // any tags written after the name, and ":"
+    fn value_index(&self) -> usize {
+        let tags = match self.tag_format.inline_separator() {
+            Some(_) => self.tags.inline_len(),
+            None => 0,
+        };
+
+        self.prefix.len() + self.key.len() + tags + 1
+    }
+
+    fn write_sampling_rate<W: Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        if let Some(rate) = self.rate() {
+            // See https://github.com/DataDog/datadog-go/blob/v5.5.0/statsd/format.go#L28
+            out.write_str("|@")?;
+            write_float(out, rate)?;
         }
+
+        Ok(())
+    }
+
+    fn write_tags<W: Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        self.tags.write(out, self.sanitize)
     }
 
-    fn write_timestamp(&self, out: &mut String) {
+    fn write_timestamp<W: Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
         if let Some(timestamp) = self.timestamp {
             // See https://github.com/DataDog/datadog-go/blob/v5.5.0/statsd/format.go#L276
-            let _ = write!(out, "|T{}", timestamp);
+            out.write_str("|T")?;
+            write_int(out, timestamp)?;
         }
+
+        Ok(())
     }
 
-    fn write_container_id(&self, out: &mut String) {
+    fn write_container_id<W: Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
         if let Some(container_id) = self.container_id {
             // See https://github.com/DataDog/datadog-go/blob/v5.5.0/statsd/format.go#L268
-            let _ = write!(out, "|c:{}", container_id);
+            write!(out, "|c:{}", container_id)?;
         }
+
+        Ok(())
     }
 
     fn tag_size_hint(&self) -> usize {
-        if self.tags.is_empty() {
-            return 0;
+        match self.tag_format.inline_separator() {
+            Some(_) => self.tags.inline_len(),
+            None => self.tags.size_hint(),
         }
-
-        // prefix, keys and values, commas
-        Self::TAG_PREFIX.len() + self.kv_size + self.tags.len() - 1
     }
 
     fn timestamp_size_hint(&self) -> usize {
@@ -246,7 +836,7 @@ impl<'a> MetricFormatter<'a> {
     }
 
     fn sampling_rate_size_hint(&self) -> usize {
-        if let Some(_rate) = self.sampling_rate {
+        if let Some(_rate) = self.rate() {
             /* |@ */
             2 + /* rate */ 17 /* MAX_SIG_DIGITS */
         } else {
@@ -272,26 +862,337 @@ impl<'a> MetricFormatter<'a> {
     }
 
     pub(crate) fn format(&self) -> String {
-        let size_hint = self.size_hint();
-        let mut metric_string = String::with_capacity(size_hint);
-        self.write_base_metric(&mut metric_string);
-        self.write_sampling_rate(&mut metric_string);
-        self.write_tags(&mut metric_string);
-        self.write_container_id(&mut metric_string);
-        self.write_timestamp(&mut metric_string);
+        let mut metric_string = String::new();
+        self.format_into(&mut metric_string);
         metric_string
     }
+
+    // Append the metric to the end of the given string, growing it at most once.
+    pub(crate) fn format_into(&self, out: &mut String) {
+        out.reserve(self.size_hint());
+        let _ = self.write_parts(out);
+    }
+
+    fn write_parts<W: Write + ?Sized>(&self, out: &mut W) -> fmt::Result {
+        self.write_name(out)?;
+        match self.tag_format.inline_separator() {
+            Some(_) => {
+                self.tags.write_inline(out, self.tag_format, self.sanitize)?;
+                self.write_value_and_type(out)?;
+                self.write_sampling_rate(out)?;
+            }
+            None => {
+                self.write_value_and_type(out)?;
+                self.write_sampling_rate(out)?;
+                self.write_tags(out)?;
+            }
+        }
+        self.write_container_id(out)?;
+        self.write_timestamp(out)
+    }
 }
 
-/// Internal state of a `MetricBuilder`
-///
-/// The builder can either be in the process of formatting a metric to send
-/// via a client or it can be simply holding on to an error that it will be
-/// dealt with when `.try_send()` or `.send()` is finally invoked.
-#[derive(Debug)]
-enum BuilderRepr<'m, 'c> {
-    Success(MetricFormatter<'m>, &'c StatsdClient),
-    Error(MetricError, &'c StatsdClient),
+impl<'a> WriteMetric for MetricFormatter<'a> {
+    fn write_to(&self, out: &mut dyn Write) -> fmt::Result {
+        self.write_parts(out)
+    }
+
+    fn size_hint(&self) -> usize {
+        MetricFormatter::size_hint(self)
+    }
+}
+
+// Newlines aren't allowed in the text of events or service checks since they would
+// end the datagram early so they are escaped, the same as other Datadog clients.
+fn escaped_len(val: &str) -> usize {
+    val.len() + val.matches('\n').count()
+}
+
+fn write_escaped(out: &mut String, val: &str) {
+    for (i, part) in val.split('\n').enumerate() {
+        if i > 0 {
+            out.push_str("\\n");
+        }
+        out.push_str(part);
+    }
+}
+
+fn write_optional<T: fmt::Display>(out: &mut String, field: &str, val: Option<T>) {
+    if let Some(val) = val {
+        let _ = write!(out, "|{}:{}", field, val);
+    }
+}
+
+// Size of an optional field of an event or service check, including the separator
+// and field name (`|x:`).
+fn optional_size_hint(val: Option<&str>) -> usize {
+    val.map(|v| 3 + v.len()).unwrap_or(0)
+}
+
+/// Formatter for Datadog events
+///
+/// Events use a different format than metrics. For more information see the
+/// [Datadog docs](https://docs.datadoghq.com/developers/dogstatsd/datagram_shell/?tab=events).
+#[derive(Debug, Clone)]
+pub(crate) struct EventFormatter<'a> {
+    title: &'a str,
+    text: &'a str,
+    timestamp: Option<u64>,
+    hostname: Option<&'a str>,
+    aggregation_key: Option<&'a str>,
+    priority: Option<EventPriority>,
+    source_type_name: Option<&'a str>,
+    alert_type: Option<EventAlertType>,
+    tags: Tags<'a>,
+    container_id: Option<&'a str>,
+}
+
+impl<'a> EventFormatter<'a> {
+    // _e{,}:| plus two lengths of up to five digits each
+    const HEADER_SIZE: usize = 17;
+
+    pub(crate) fn new(title: &'a str, text: &'a str) -> Self {
+        EventFormatter {
+            title,
+            text,
+            timestamp: None,
+            hostname: None,
+            aggregation_key: None,
+            priority: None,
+            source_type_name: None,
+            alert_type: None,
+            tags: Tags::default(),
+            container_id: None,
+        }
+    }
+
+    fn with_tag(&mut self, key: &'a str, value: &'a str) {
+        self.tags.with_tag(key, value);
+    }
+
+    fn with_default_tag(&mut self, key: Option<&'a str>, value: &'a str) {
+        self.tags.with_default(key, value);
+    }
+
+    fn with_tag_value(&mut self, value: &'a str) {
+        self.tags.with_tag_value(value);
+    }
+
+    fn with_timestamp(&mut self, timestamp: u64) {
+        self.timestamp = Some(timestamp);
+    }
+
+    fn with_hostname(&mut self, hostname: &'a str) {
+        self.hostname = Some(hostname);
+    }
+
+    fn with_aggregation_key(&mut self, key: &'a str) {
+        self.aggregation_key = Some(key);
+    }
+
+    fn with_priority(&mut self, priority: EventPriority) {
+        self.priority = Some(priority);
+    }
+
+    fn with_source_type_name(&mut self, name: &'a str) {
+        self.source_type_name = Some(name);
+    }
+
+    fn with_alert_type(&mut self, alert_type: EventAlertType) {
+        self.alert_type = Some(alert_type);
+    }
+
+    fn with_container_id(&mut self, container_id: &'a str) {
+        self.container_id = Some(container_id);
+    }
+
+    fn size_hint(&self) -> usize {
+        Self::HEADER_SIZE
+            + self.title.len()
+            + self.text.len()
+            + self.timestamp.map(|_| 3 /* |d: */ + 10 /* timestamp */).unwrap_or(0)
+            + optional_size_hint(self.hostname)
+            + optional_size_hint(self.aggregation_key)
+            + self.priority.map(|_| 3 /* |p: */ + 6 /* normal */).unwrap_or(0)
+            + optional_size_hint(self.source_type_name)
+            + self.alert_type.map(|_| 3 /* |t: */ + 7 /* warning */).unwrap_or(0)
+            + self.tags.size_hint()
+            + optional_size_hint(self.container_id)
+    }
+
+    pub(crate) fn format(&self) -> String {
+        let mut event_string = String::with_capacity(self.size_hint());
+        let _ = write!(
+            event_string,
+            "_e{{{},{}}}:",
+            escaped_len(self.title),
+            escaped_len(self.text)
+        );
+        write_escaped(&mut event_string, self.title);
+        event_string.push('|');
+        write_escaped(&mut event_string, self.text);
+        // See https://github.com/DataDog/datadog-go/blob/v5.5.0/statsd/format.go#L168
+        write_optional(&mut event_string, "d", self.timestamp);
+        write_optional(&mut event_string, "h", self.hostname);
+        write_optional(&mut event_string, "k", self.aggregation_key);
+        write_optional(&mut event_string, "p", self.priority);
+        write_optional(&mut event_string, "s", self.source_type_name);
+        write_optional(&mut event_string, "t", self.alert_type);
+        let _ = self.tags.write(&mut event_string, SanitizePolicy::Unchecked);
+        write_optional(&mut event_string, "c", self.container_id);
+        event_string
+    }
+}
+
+/// Formatter for Datadog service checks
+///
+/// For more information see the
+/// [Datadog docs](https://docs.datadoghq.com/developers/dogstatsd/datagram_shell/?tab=servicechecks).
+#[derive(Debug, Clone)]
+pub(crate) struct ServiceCheckFormatter<'a> {
+    prefix: &'a str,
+    name: &'a str,
+    status: ServiceCheckStatus,
+    timestamp: Option<u64>,
+    hostname: Option<&'a str>,
+    tags: Tags<'a>,
+    message: Option<&'a str>,
+    container_id: Option<&'a str>,
+}
+
+impl<'a> ServiceCheckFormatter<'a> {
+    // _sc|| and a single digit status
+    const HEADER_SIZE: usize = 6;
+
+    pub(crate) fn new(prefix: &'a str, name: &'a str, status: ServiceCheckStatus) -> Self {
+        ServiceCheckFormatter {
+            prefix,
+            name,
+            status,
+            timestamp: None,
+            hostname: None,
+            tags: Tags::default(),
+            message: None,
+            container_id: None,
+        }
+    }
+
+    fn with_tag(&mut self, key: &'a str, value: &'a str) {
+        self.tags.with_tag(key, value);
+    }
+
+    fn with_default_tag(&mut self, key: Option<&'a str>, value: &'a str) {
+        self.tags.with_default(key, value);
+    }
+
+    fn with_tag_value(&mut self, value: &'a str) {
+        self.tags.with_tag_value(value);
+    }
+
+    fn with_timestamp(&mut self, timestamp: u64) {
+        self.timestamp = Some(timestamp);
+    }
+
+    fn with_hostname(&mut self, hostname: &'a str) {
+        self.hostname = Some(hostname);
+    }
+
+    fn with_message(&mut self, message: &'a str) {
+        self.message = Some(message);
+    }
+
+    fn with_container_id(&mut self, container_id: &'a str) {
+        self.container_id = Some(container_id);
+    }
+
+    fn size_hint(&self) -> usize {
+        Self::HEADER_SIZE
+            + self.prefix.len()
+            + self.name.len()
+            + self.timestamp.map(|_| 3 /* |d: */ + 10 /* timestamp */).unwrap_or(0)
+            + optional_size_hint(self.hostname)
+            + self.tags.size_hint()
+            + self.message.map(|m| 3 /* |m: */ + escaped_len(m)).unwrap_or(0)
+            + optional_size_hint(self.container_id)
+    }
+
+    pub(crate) fn format(&self) -> String {
+        let mut check_string = String::with_capacity(self.size_hint());
+        let _ = write!(check_string, "_sc|{}{}|{}", self.prefix, self.name, self.status);
+        // See https://github.com/DataDog/datadog-go/blob/v5.5.0/statsd/format.go#L222
+        write_optional(&mut check_string, "d", self.timestamp);
+        write_optional(&mut check_string, "h", self.hostname);
+        let _ = self.tags.write(&mut check_string, SanitizePolicy::Unchecked);
+        write_optional(&mut check_string, "c", self.container_id);
+        if let Some(message) = self.message {
+            // The message is always last since it may contain the field separator
+            // but `m:` sequences are escaped, the same as other Datadog clients.
+            check_string.push_str("|m:");
+            write_escaped(&mut check_string, &message.replace("m:", "m\\:"));
+        }
+        check_string
+    }
+}
+
+/// Internal state of a `MetricBuilder`, `EventBuilder`, or `ServiceCheckBuilder`
+///
+/// The builder can either be in the process of formatting a metric to send
+/// via a client or it can be simply holding on to an error that it will be
+/// dealt with when `.try_send()` or `.send()` is finally invoked.
+#[derive(Debug)]
+enum BuilderRepr<'c, F> {
+    Success(F, &'c StatsdClient),
+    Error(MetricError, &'c StatsdClient),
+}
+
+impl<'c, F> BuilderRepr<'c, F> {
+    // True if the client of this builder never sends metrics, in which case
+    // nothing is formatted or reported by `.send()`
+    fn is_disabled(&self) -> bool {
+        match self {
+            BuilderRepr::Success(_, client) | BuilderRepr::Error(_, client) => client.is_disabled(),
+        }
+    }
+}
+
+type LazyTagValue<'a> = Box<dyn FnOnce() -> String + Send + 'a>;
+
+/// Key-value tags for a metric with values that are only computed when the
+/// metric is formatted.
+#[derive(Default)]
+struct LazyTags<'a> {
+    tags: Vec<(&'a str, LazyTagValue<'a>)>,
+}
+
+impl<'a> LazyTags<'a> {
+    fn push<F>(&mut self, key: &'a str, value: F)
+    where
+        F: FnOnce() -> String + Send + 'a,
+    {
+        self.tags.push((key, Box::new(value)));
+    }
+
+    // Compute the value of each tag and pass the formatter, with the tags added
+    // to it, to the given function. The formatter can't outlive the computed
+    // values so it's only available inside the function.
+    fn apply<F, R>(self, formatter: MetricFormatter<'a>, f: F) -> R
+    where
+        F: FnOnce(&MetricFormatter<'_>) -> R,
+    {
+        let values: Vec<(&str, String)> = self.tags.into_iter().map(|(key, value)| (key, value())).collect();
+        let mut formatter = formatter;
+        for (key, value) in values.iter() {
+            formatter.with_tag(key, value);
+        }
+
+        f(&formatter)
+    }
+}
+
+impl<'a> fmt::Debug for LazyTags<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_list().entries(self.tags.iter().map(|(key, _)| key)).finish()
+    }
 }
 
 /// Builder for adding tags to in-progress metrics.
@@ -303,9 +1204,11 @@ enum BuilderRepr<'m, 'c> {
 /// validating, or sending the metrics will be propagated and returned when those
 /// methods are finally invoked.
 ///
-/// Currently, only Datadog style tags are supported. For more information on the
+/// Datadog style tags are used by default. For more information on the
 /// exact format used, see the
 /// [Datadog docs](https://docs.datadoghq.com/developers/dogstatsd/#datagram-format).
+/// Clients can use Telegraf or Graphite style tags instead with
+/// `StatsdClientBuilder::with_tag_format()`.
 ///
 /// Adding tags to a metric via this builder will typically result in one or more
 /// extra heap allocations.
@@ -372,7 +1275,8 @@ pub struct MetricBuilder<'m, 'c, T>
 where
     T: Metric + From<String>,
 {
-    repr: BuilderRepr<'m, 'c>,
+    repr: BuilderRepr<'c, MetricFormatter<'m>>,
+    lazy_tags: LazyTags<'m>,
     type_: PhantomData<T>,
 }
 
@@ -383,6 +1287,7 @@ where
     pub(crate) fn from_fmt(formatter: MetricFormatter<'m>, client: &'c StatsdClient) -> Self {
         MetricBuilder {
             repr: BuilderRepr::Success(formatter, client),
+            lazy_tags: LazyTags::default(),
             type_: PhantomData,
         }
     }
@@ -390,6 +1295,7 @@ where
     pub(crate) fn from_error(err: MetricError, client: &'c StatsdClient) -> Self {
         MetricBuilder {
             repr: BuilderRepr::Error(err, client),
+            lazy_tags: LazyTags::default(),
             type_: PhantomData,
         }
     }
@@ -444,17 +1350,131 @@ where
         self
     }
 
-    /// Add tags to this metric.
-    pub(crate) fn with_tags<V>(mut self, tags: V) -> Self
+    /// Add a key-value tag to this metric if the value is present.
+    ///
+    /// If the value is `None`, no tag is added.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+    /// let user: Option<&str> = None;
+    /// let region = Some("us-east-1");
+    /// let res = client.count_with_tags("some.key", 1)
+    ///    .with_tag_opt("user", user)
+    ///    .with_tag_opt("region", region)
+    ///    .try_send();
+    ///
+    /// assert_eq!(
+    ///    "some.prefix.some.key:1|c|#region:us-east-1",
+    ///    res.unwrap().as_metric_str()
+    /// );
+    /// ```
+    pub fn with_tag_opt(self, key: &'m str, value: Option<&'m str>) -> Self {
+        match value {
+            Some(v) => self.with_tag(key, v),
+            None => self,
+        }
+    }
+
+    /// Add each of the given key-value tags to this metric.
+    ///
+    /// Tags can be given as any iterator of key-value pairs of string slices or
+    /// references to strings, such as an array or slice of tuples or a borrowed
+    /// `HashMap`.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::BTreeMap;
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+    /// let tags = [("method", "GET"), ("status", "200")];
+    /// let res = client.count_with_tags("some.key", 1)
+    ///    .with_tags(tags)
+    ///    .try_send();
+    ///
+    /// assert_eq!(
+    ///    "some.prefix.some.key:1|c|#method:GET,status:200",
+    ///    res.unwrap().as_metric_str()
+    /// );
+    ///
+    /// let mut tags = BTreeMap::new();
+    /// tags.insert("region".to_string(), "us-east-1".to_string());
+    /// let res = client.count_with_tags("some.key", 1)
+    ///    .with_tags(&tags)
+    ///    .try_send();
+    ///
+    /// assert_eq!(
+    ///    "some.prefix.some.key:1|c|#region:us-east-1",
+    ///    res.unwrap().as_metric_str()
+    /// );
+    /// ```
+    pub fn with_tags<I>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: ToTag<'m>,
+    {
+        if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
+            for (key, value) in tags.into_iter().map(ToTag::to_tag) {
+                formatter.with_tag(key, value);
+            }
+        }
+        self
+    }
+
+    /// Add a key-value tag to this metric with a value computed by the given
+    /// function.
+    ///
+    /// The function is only called if the metric is formatted. When `.send()`
+    /// is used, it isn't called for metrics that are not selected by sampling,
+    /// avoiding the cost of computing tag values that are never sent. Tags
+    /// added this way come after all other tags of the metric.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+    /// let shard_id = 7;
+    /// let res = client.count_with_tags("some.key", 1)
+    ///    .with_tag_lazy("shard", || shard_id.to_string())
+    ///    .with_tag("user", "authenticated")
+    ///    .try_send();
+    ///
+    /// assert_eq!(
+    ///    "some.prefix.some.key:1|c|#user:authenticated,shard:7",
+    ///    res.unwrap().as_metric_str()
+    /// );
+    /// ```
+    pub fn with_tag_lazy<F>(mut self, key: &'m str, value: F) -> Self
+    where
+        F: FnOnce() -> String + Send + 'm,
+    {
+        if let BuilderRepr::Success(_, _) = self.repr {
+            self.lazy_tags.push(key, value);
+        }
+        self
+    }
+
+    /// Add default tags from the client to this metric.
+    ///
+    /// Key-value tags added to the metric after these will replace any default
+    /// tags that use the same key.
+    pub(crate) fn with_default_tags<V>(mut self, tags: V) -> Self
     where
         V: IntoIterator<Item = (Option<&'m str>, &'m str)>,
     {
         if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
-            for tag in tags.into_iter() {
-                match tag {
-                    (Some(key), value) => formatter.with_tag(key, value),
-                    (None, value) => formatter.with_tag_value(value),
-                }
+            for (key, value) in tags.into_iter() {
+                formatter.with_default_tag(key, value);
             }
         }
 
@@ -478,6 +1498,27 @@ where
         self
     }
 
+    pub(crate) fn with_tag_format(mut self, tag_format: TagFormat) -> Self {
+        if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
+            formatter.with_tag_format(tag_format);
+        }
+        self
+    }
+
+    pub(crate) fn with_sanitize_policy(mut self, policy: SanitizePolicy) -> Self {
+        if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
+            formatter.with_sanitize_policy(policy);
+        }
+        self
+    }
+
+    pub(crate) fn with_non_finite_policy(mut self, policy: NonFinitePolicy) -> Self {
+        if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
+            formatter.with_non_finite_policy(policy);
+        }
+        self
+    }
+
     /// Add a UNIX timestamp in seconds to this metric.
     /// # Example
     ///
@@ -512,6 +1553,13 @@ where
     /// metric is sent 50% of the time. The sampling has to be done by the caller, cadence
     /// will simply forward it to the backend.
     ///
+    /// If the metric is also sampled by Cadence, because of `.with_sample_rate()` or
+    /// the default sample rate of the client, the two rates are multiplied and
+    /// only the result is sent since the metric is only sent when it's selected
+    /// by both. For example, a metric with a sampling rate of 0.5 and a sample
+    /// rate of 0.1 is sent with the rate `@0.05`, regardless of the order that
+    /// the methods are called in.
+    ///
     /// # Example
     /// ```
     /// use cadence::prelude::*;
@@ -534,11 +1582,63 @@ where
         self
     }
 
+    /// Sample this metric at the given rate, sending it only some of the time.
+    ///
+    /// The sample rate is a float between 0 and 1 that determines the rate at which
+    /// the metric is sent. For example, a sample rate of 0.1 would mean that the
+    /// metric is sent 10% of the time. Unlike `.with_sampling_rate()`, the sampling
+    /// is done by Cadence: metrics that are not selected are not sent to the sink.
+    /// The rate is included in metrics that are sent so that the server can scale
+    /// the values it receives. Calling this method again replaces the rate, and
+    /// the rate is multiplied by any rate given to `.with_sampling_rate()`.
+    ///
+    /// If the rate is not between 0 and 1, an `InvalidInput` error will be returned
+    /// when the metric is sent.
+    ///
+    /// The decision to send the metric is made when it is sent. When `.send()` is
+    /// used, metrics that are not selected are not formatted at all, avoiding the
+    /// cost of allocating a string for them. When `.try_send()` is used, the metric
+    /// is formatted and returned even if it was not selected to be sent, so the
+    /// result of `.try_send()` can't be used to tell if the metric was sent.
+    ///
+    /// # Example
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink, Metric};
+    ///
+    /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+    /// let res = client.count_with_tags("some.key", 1)
+    ///  .with_sample_rate(0.1)
+    ///  .try_send();
+    ///
+    /// assert_eq!(
+    ///  "some.prefix.some.key:1|c|@0.1",
+    ///  res.unwrap().as_metric_str()
+    /// );
+    /// ```
+    pub fn with_sample_rate(mut self, rate: f64) -> Self {
+        if !sample::is_valid_rate(rate) {
+            let err = MetricError::from((ErrorKind::InvalidInput, "sample rate must be between 0 and 1"));
+            self.repr = match self.repr {
+                BuilderRepr::Success(_, client) | BuilderRepr::Error(_, client) => BuilderRepr::Error(err, client),
+            };
+        } else if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
+            formatter.with_sample_rate(rate);
+        }
+
+        self
+    }
+
     /// Send a metric using the client that created this builder.
     ///
     /// Note that the builder is consumed by this method and thus `.try_send()`
     /// can only be called a single time per builder.
     ///
+    /// **The metric is returned even if it wasn't sent.** Metrics that are not
+    /// selected by client-side sampling (see `.with_sample_rate()`) or that are
+    /// created by a disabled client are formatted and returned as `Ok` but are
+    /// never passed to the sink.
+    ///
     /// # Example
     ///
     /// ```
@@ -558,9 +1658,20 @@ where
     pub fn try_send(self) -> MetricResult<T> {
         match self.repr {
             BuilderRepr::Error(err, _) => Err(err),
-            BuilderRepr::Success(ref formatter, client) => {
-                let metric = T::from(formatter.format());
-                client.send_metric(&metric)?;
+            BuilderRepr::Success(mut formatter, client) => {
+                // There's no metric to return when the value isn't finite and
+                // the policy is to skip it, only an error.
+                if !formatter.apply_non_finite()? {
+                    return Err(formatter.error(ErrorKind::InvalidInput, "value must be finite"));
+                }
+
+                let sampled = formatter.is_sampled(client.rng());
+                let metric = self.lazy_tags.apply(formatter, |formatter| {
+                    formatter.check().map(|_| T::from(formatter.format()))
+                })?;
+                if sampled {
+                    client.send_metric(&metric)?;
+                }
                 Ok(metric)
             }
         }
@@ -595,24 +1706,735 @@ where
     ///    .send();
     /// ```
     pub fn send(self) {
+        if self.repr.is_disabled() {
+            return;
+        }
+
         match self.repr {
             BuilderRepr::Error(err, client) => client.consume_error(err),
-            BuilderRepr::Success(_, client) => {
-                if let Err(e) = self.try_send() {
-                    client.consume_error(e);
+            BuilderRepr::Success(mut formatter, client) => {
+                match formatter.apply_non_finite() {
+                    Ok(true) => {}
+                    Ok(false) => return,
+                    Err(e) => return client.consume_error(e),
                 }
+
+                // Metrics that aren't selected by sampling are never formatted since
+                // nothing is returned to the caller that would require it.
+                if !formatter.is_sampled(client.rng()) {
+                    return;
+                }
+
+                // Nothing is returned to the caller either, so the sink is left to
+                // format the metric without allocating a string for it. The metric
+                // is only formatted here if the error handler needs it.
+                self.lazy_tags.apply(formatter, |formatter| {
+                    if let Err(e) = formatter.check() {
+                        client.consume_error(e);
+                    } else if let Err(e) = client.send_writable(formatter) {
+                        client.consume_metric_error(&formatter.format(), e);
+                    }
+                });
             }
         }
     }
-}
+
+    /// Send a metric using the client that created this builder, formatting it
+    /// into the given buffer instead of a newly allocated string.
+    ///
+    /// The buffer is cleared before the metric is written to it and contains
+    /// the formatted metric afterwards, even if it was not selected to be sent
+    /// due to sampling. The buffer is left empty if the metric is skipped
+    /// because its value isn't finite, see `NonFinitePolicy`. Reusing the same buffer for many metrics avoids
+    /// allocating memory for each of them. Note that `.send()` already reuses a
+    /// buffer for each thread, so this is only needed to find out if sending
+    /// the metric failed without allocating.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+    /// let mut buf = String::new();
+    ///
+    /// for i in 0..3 {
+    ///     client.count_with_tags("some.key", i).try_send_with_buffer(&mut buf).unwrap();
+    /// }
+    ///
+    /// assert_eq!("some.prefix.some.key:2|c", buf);
+    /// ```
+    pub fn try_send_with_buffer(self, buf: &mut String) -> MetricResult<()> {
+        buf.clear();
+
+        match self.repr {
+            BuilderRepr::Error(err, _) => Err(err),
+            BuilderRepr::Success(mut formatter, client) => {
+                if !formatter.apply_non_finite()? {
+                    return Ok(());
+                }
+
+                let sampled = formatter.is_sampled(client.rng());
+                self.lazy_tags.apply(formatter, |formatter| {
+                    formatter.check().map(|_| formatter.format_into(buf))
+                })?;
+                if sampled {
+                    client.send_formatted(buf)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    // Format the metric without sending it or making a sampling decision,
+    // returning the metric and the index of its value.
+    pub(crate) fn format_with_value_index(self) -> MetricResult<(String, usize)> {
+        match self.repr {
+            BuilderRepr::Error(err, _) => Err(err),
+            BuilderRepr::Success(formatter, _) => self.lazy_tags.apply(formatter, |formatter| {
+                formatter.check().map(|_| (formatter.format(), formatter.value_index()))
+            }),
+        }
+    }
+
+    // Format the metric without sending it, returning `None` if the metric
+    // is skipped because its value isn't finite or isn't selected by sampling.
+    fn try_format(self) -> MetricResult<Option<String>> {
+        match self.repr {
+            BuilderRepr::Error(err, _) => Err(err),
+            BuilderRepr::Success(mut formatter, client) => {
+                if formatter.apply_non_finite()? && formatter.is_sampled(client.rng()) {
+                    self.lazy_tags.apply(formatter, |formatter| {
+                        formatter.check().map(|_| Some(formatter.format()))
+                    })
+                } else {
+                    Ok(None)
+                }
+            }
+        }
+    }
+}
+
+/// Builder for sending several metrics to the sink of a client at once.
+///
+/// Metrics are added to the batch using the `MetricBuilder` returned by the
+/// `*_with_tags()` methods of `StatsdClient` and are all passed to the sink of
+/// the client in a single call when `MetricBatch::send()` or
+/// `MetricBatch::try_send()` is invoked. This allows sinks that buffer or queue
+/// metrics to handle the whole batch at once, such as `BufferedUdpMetricSink`
+/// taking its lock once or `QueuingMetricSink` using a single entry in its queue.
+///
+/// Metrics that aren't selected by sampling are left out of the batch. Errors
+/// creating any of the metrics are returned when the batch is sent, after the
+/// rest of the metrics in the batch are sent.
+///
+/// NOTE: The only way to instantiate an instance of this builder is via the
+/// `StatsdClient::batch()` method.
+///
+/// # Example
+///
+/// ```
+/// use cadence::prelude::*;
+/// use cadence::{StatsdClient, NopMetricSink};
+///
+/// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+/// let res = client
+///     .batch()
+///     .with_metric(client.count_with_tags("requests", 1).with_tag("status", "200"))
+///     .with_metric(client.time_with_tags("latency", 23))
+///     .try_send();
+///
+/// assert!(res.is_ok());
+/// ```
+#[must_use = "Did you forget to call .send() after adding metrics?"]
+#[derive(Debug)]
+pub struct MetricBatch<'c> {
+    client: &'c StatsdClient,
+    metrics: Vec<String>,
+    error: Option<MetricError>,
+}
+
+impl<'c> MetricBatch<'c> {
+    pub(crate) fn new(client: &'c StatsdClient) -> Self {
+        MetricBatch {
+            client,
+            metrics: Vec::new(),
+            error: None,
+        }
+    }
+
+    /// Add a metric to this batch.
+    pub fn with_metric<T>(mut self, metric: MetricBuilder<'_, '_, T>) -> Self
+    where
+        T: Metric + From<String>,
+    {
+        match metric.try_format() {
+            Ok(Some(m)) => self.metrics.push(m),
+            Ok(None) => {}
+            Err(e) => {
+                self.error.get_or_insert(e);
+            }
+        }
+
+        self
+    }
+
+    /// Return the number of metrics in this batch.
+    pub fn len(&self) -> usize {
+        self.metrics.len()
+    }
+
+    /// Return true if there are no metrics in this batch.
+    pub fn is_empty(&self) -> bool {
+        self.metrics.is_empty()
+    }
+
+    /// Send every metric in this batch using the client that created it,
+    /// returning the first error encountered creating or sending the metrics.
+    /// Errors creating metrics take precedence over an error from the sink
+    /// since they happened first.
+    pub fn try_send(self) -> MetricResult<()> {
+        let metrics: Vec<&str> = self.metrics.iter().map(|m| m.as_str()).collect();
+        let res = self.client.send_batch(&metrics);
+
+        match self.error {
+            Some(e) => Err(e),
+            None => res,
+        }
+    }
+
+    /// Send every metric in this batch using the client that created it,
+    /// invoking the error handler of the client for any errors.
+    pub fn send(self) {
+        let client = self.client;
+        if let Err(e) = self.try_send() {
+            client.consume_error(e);
+        }
+    }
+}
+
+/// Builder for adding tags to in-progress metrics sent by an `AsyncStatsdClient`.
+///
+/// This builder works the same way as the `MetricBuilder` except that the
+/// `.try_send()` and `.send()` methods return futures that must be awaited
+/// for the metric to be sent via the `AsyncMetricSink` of the client.
+///
+/// NOTE: The only way to instantiate an instance of this builder is via methods
+/// in the `AsyncStatsdClient` client.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{AsyncStatsdClient, Metric, NopMetricSink};
+///
+/// # async fn example() {
+/// let client = AsyncStatsdClient::from_sink("some.prefix", NopMetricSink);
+/// let res = client.count_with_tags("some.key", 1)
+///    .with_tag("host", "app11.example.com")
+///    .try_send()
+///    .await;
+///
+/// assert_eq!(
+///     "some.prefix.some.key:1|c|#host:app11.example.com",
+///     res.unwrap().as_metric_str()
+/// );
+/// # }
+/// ```
+#[must_use = "Did you forget to call .send().await after adding tags?"]
+pub struct AsyncMetricBuilder<'m, 'c, T>
+where
+    T: Metric + From<String>,
+{
+    builder: MetricBuilder<'m, 'c, T>,
+    sink: &'c (dyn AsyncMetricSink + Send + Sync),
+}
+
+impl<'m, 'c, T> AsyncMetricBuilder<'m, 'c, T>
+where
+    T: Metric + From<String>,
+{
+    pub(crate) fn new(builder: MetricBuilder<'m, 'c, T>, sink: &'c (dyn AsyncMetricSink + Send + Sync)) -> Self {
+        AsyncMetricBuilder { builder, sink }
+    }
+
+    /// Add a key-value tag to this metric.
+    pub fn with_tag(mut self, key: &'m str, value: &'m str) -> Self {
+        self.builder = self.builder.with_tag(key, value);
+        self
+    }
+
+    /// Add a value tag to this metric.
+    pub fn with_tag_value(mut self, value: &'m str) -> Self {
+        self.builder = self.builder.with_tag_value(value);
+        self
+    }
+
+    /// Add a key-value tag to this metric if the value is present.
+    pub fn with_tag_opt(mut self, key: &'m str, value: Option<&'m str>) -> Self {
+        self.builder = self.builder.with_tag_opt(key, value);
+        self
+    }
+
+    /// Add each of the given key-value tags to this metric.
+    ///
+    /// See `MetricBuilder::with_tags()` for more information.
+    pub fn with_tags<I>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator,
+        I::Item: ToTag<'m>,
+    {
+        self.builder = self.builder.with_tags(tags);
+        self
+    }
+
+    /// Add a key-value tag to this metric with a value computed by the given
+    /// function.
+    ///
+    /// See `MetricBuilder::with_tag_lazy()` for more information.
+    pub fn with_tag_lazy<F>(mut self, key: &'m str, value: F) -> Self
+    where
+        F: FnOnce() -> String + Send + 'm,
+    {
+        self.builder = self.builder.with_tag_lazy(key, value);
+        self
+    }
+
+    /// Add a container_id to this metric.
+    pub fn with_container_id(mut self, container_id: &'m str) -> Self {
+        self.builder = self.builder.with_container_id(container_id);
+        self
+    }
+
+    /// Add a UNIX timestamp in seconds to this metric.
+    pub fn with_timestamp(mut self, timestamp: u64) -> Self {
+        self.builder = self.builder.with_timestamp(timestamp);
+        self
+    }
+
+    /// Add a sampling rate to this metric.
+    ///
+    /// See `MetricBuilder::with_sampling_rate()` for more information.
+    pub fn with_sampling_rate(mut self, rate: f64) -> Self {
+        self.builder = self.builder.with_sampling_rate(rate);
+        self
+    }
+
+    /// Sample this metric at the given rate, sending it only some of the time.
+    ///
+    /// See `MetricBuilder::with_sample_rate()` for more information.
+    pub fn with_sample_rate(mut self, rate: f64) -> Self {
+        self.builder = self.builder.with_sample_rate(rate);
+        self
+    }
+
+    /// Send a metric using the client that created this builder.
+    ///
+    /// Note that the builder is consumed by this method and thus `.try_send()`
+    /// can only be called a single time per builder. Like
+    /// `MetricBuilder::try_send()`, the metric is returned even if it wasn't
+    /// sent because of sampling or because the client is disabled.
+    pub async fn try_send(self) -> MetricResult<T> {
+        match self.builder.repr {
+            BuilderRepr::Error(err, _) => Err(err),
+            BuilderRepr::Success(mut formatter, client) => {
+                if !formatter.apply_non_finite()? {
+                    return Err(formatter.error(ErrorKind::InvalidInput, "value must be finite"));
+                }
+
+                let sampled = formatter.is_sampled(client.rng());
+                let metric = self.builder.lazy_tags.apply(formatter, |formatter| {
+                    formatter.check().map(|_| T::from(formatter.format()))
+                })?;
+                if sampled && !client.is_disabled() {
+                    self.sink.emit(metric.as_metric_str()).await?;
+                }
+                Ok(metric)
+            }
+        }
+    }
+
+    /// Send a metric using the client that created this builder, discarding
+    /// successful results and invoking the error handler of the client for
+    /// error results.
+    ///
+    /// Note that the builder is consumed by this method and thus `.send()`
+    /// can only be called a single time per builder.
+    pub async fn send(self) {
+        if self.builder.repr.is_disabled() {
+            return;
+        }
+
+        match self.builder.repr {
+            BuilderRepr::Error(err, client) => client.consume_error(err),
+            BuilderRepr::Success(mut formatter, client) => {
+                match formatter.apply_non_finite() {
+                    Ok(true) => {}
+                    Ok(false) => return,
+                    Err(e) => return client.consume_error(e),
+                }
+
+                if !formatter.is_sampled(client.rng()) {
+                    return;
+                }
+
+                let metric = match self.builder.lazy_tags.apply(formatter, |formatter| {
+                    formatter.check().map(|_| T::from(formatter.format()))
+                }) {
+                    Ok(metric) => metric,
+                    Err(e) => return client.consume_error(e),
+                };
+                if let Err(e) = self.sink.emit(metric.as_metric_str()).await {
+                    client.consume_metric_error(metric.as_metric_str(), e.into());
+                }
+            }
+        }
+    }
+}
+
+impl<'m, 'c, T> fmt::Debug for AsyncMetricBuilder<'m, 'c, T>
+where
+    T: Metric + From<String>,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("AsyncMetricBuilder")
+            .field("repr", &self.builder.repr)
+            .field("sink", &"...")
+            .finish()
+    }
+}
+
+/// Builder for adding tags and other optional attributes to in-progress events.
+///
+/// This builder works the same way as the `MetricBuilder` but for Datadog
+/// events. Events are created by a call to a method on `StatsdClient` and
+/// are sent via the client when `EventBuilder::send()` or `EventBuilder::try_send()`
+/// is invoked. Any errors encountered constructing, validating, or sending the
+/// event will be propagated and returned when those methods are finally invoked.
+///
+/// For more information about the fields of an event, see the
+/// [Datadog docs](https://docs.datadoghq.com/developers/dogstatsd/datagram_shell/?tab=events).
+///
+/// NOTE: The only way to instantiate an instance of this builder is via methods in
+/// in the `StatsdClient` client.
+///
+/// # Example
+///
+/// ```
+/// use cadence::prelude::*;
+/// use cadence::{EventAlertType, EventPriority, StatsdClient, NopMetricSink, Metric};
+///
+/// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+/// let res = client.event_with_tags("Deploy failed", "Rolled back to v1.2.2")
+///    .with_alert_type(EventAlertType::Error)
+///    .with_priority(EventPriority::Normal)
+///    .with_aggregation_key("deploy")
+///    .with_tag("service", "api")
+///    .try_send();
+///
+/// assert_eq!(
+///     concat!(
+///         "_e{13,21}:Deploy failed|Rolled back to v1.2.2|",
+///         "k:deploy|p:normal|t:error|#service:api"
+///     ),
+///     res.unwrap().as_metric_str()
+/// );
+/// ```
+#[must_use = "Did you forget to call .send() after adding tags?"]
+#[derive(Debug)]
+pub struct EventBuilder<'m, 'c> {
+    repr: BuilderRepr<'c, EventFormatter<'m>>,
+}
+
+impl<'m, 'c> EventBuilder<'m, 'c> {
+    pub(crate) fn from_fmt(formatter: EventFormatter<'m>, client: &'c StatsdClient) -> Self {
+        EventBuilder {
+            repr: BuilderRepr::Success(formatter, client),
+        }
+    }
+
+    pub(crate) fn from_error(err: MetricError, client: &'c StatsdClient) -> Self {
+        EventBuilder {
+            repr: BuilderRepr::Error(err, client),
+        }
+    }
+
+    fn with_formatter<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut EventFormatter<'m>),
+    {
+        if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
+            f(formatter);
+        }
+        self
+    }
+
+    /// Add a key-value tag to this event.
+    pub fn with_tag(self, key: &'m str, value: &'m str) -> Self {
+        self.with_formatter(|f| f.with_tag(key, value))
+    }
+
+    /// Add a value tag to this event.
+    pub fn with_tag_value(self, value: &'m str) -> Self {
+        self.with_formatter(|f| f.with_tag_value(value))
+    }
+
+    /// Add default tags from the client to this event.
+    pub(crate) fn with_default_tags<V>(self, tags: V) -> Self
+    where
+        V: IntoIterator<Item = (Option<&'m str>, &'m str)>,
+    {
+        self.with_formatter(|f| {
+            for (key, value) in tags.into_iter() {
+                f.with_default_tag(key, value);
+            }
+        })
+    }
+
+    /// Add a container_id to this event.
+    pub fn with_container_id(self, container_id: &'m str) -> Self {
+        self.with_formatter(|f| f.with_container_id(container_id))
+    }
+
+    pub(crate) fn with_container_id_opt(self, container_id: Option<&'m str>) -> Self {
+        match container_id {
+            Some(container_id) => self.with_container_id(container_id),
+            None => self,
+        }
+    }
+
+    /// Set the UNIX timestamp in seconds of when this event happened.
+    ///
+    /// If not set, the server will use the time the event was received.
+    pub fn with_timestamp(self, timestamp: u64) -> Self {
+        self.with_formatter(|f| f.with_timestamp(timestamp))
+    }
+
+    /// Set the name of the host this event is for.
+    pub fn with_hostname(self, hostname: &'m str) -> Self {
+        self.with_formatter(|f| f.with_hostname(hostname))
+    }
+
+    /// Set a key used to group this event with other events in the Datadog UI.
+    pub fn with_aggregation_key(self, key: &'m str) -> Self {
+        self.with_formatter(|f| f.with_aggregation_key(key))
+    }
+
+    /// Set the priority of this event.
+    pub fn with_priority(self, priority: EventPriority) -> Self {
+        self.with_formatter(|f| f.with_priority(priority))
+    }
+
+    /// Set the type of source that generated this event.
+    pub fn with_source_type_name(self, name: &'m str) -> Self {
+        self.with_formatter(|f| f.with_source_type_name(name))
+    }
+
+    /// Set the type of alert this event represents.
+    pub fn with_alert_type(self, alert_type: EventAlertType) -> Self {
+        self.with_formatter(|f| f.with_alert_type(alert_type))
+    }
+
+    /// Send an event using the client that created this builder.
+    ///
+    /// Note that the builder is consumed by this method and thus `.try_send()`
+    /// can only be called a single time per builder.
+    pub fn try_send(self) -> MetricResult<Event> {
+        match self.repr {
+            BuilderRepr::Error(err, _) => Err(err),
+            BuilderRepr::Success(ref formatter, client) => {
+                let event = Event::from(formatter.format());
+                client.send_metric(&event)?;
+                Ok(event)
+            }
+        }
+    }
+
+    /// Send an event using the client that created this builder, discarding
+    /// successful results and invoking the client error handler for error
+    /// results.
+    ///
+    /// Note that the builder is consumed by this method and thus `.send()`
+    /// can only be called a single time per builder.
+    pub fn send(self) {
+        if self.repr.is_disabled() {
+            return;
+        }
+
+        match self.repr {
+            BuilderRepr::Error(err, client) => client.consume_error(err),
+            BuilderRepr::Success(ref formatter, client) => {
+                let event = Event::from(formatter.format());
+                if let Err(e) = client.send_metric(&event) {
+                    client.consume_metric_error(event.as_metric_str(), e);
+                }
+            }
+        }
+    }
+}
+
+/// Builder for adding tags and other optional attributes to in-progress service checks.
+///
+/// This builder works the same way as the `MetricBuilder` but for Datadog
+/// service checks. Service checks are created by a call to a method on
+/// `StatsdClient` and are sent via the client when `ServiceCheckBuilder::send()`
+/// or `ServiceCheckBuilder::try_send()` is invoked. Any errors encountered
+/// constructing, validating, or sending the service check will be propagated
+/// and returned when those methods are finally invoked.
+///
+/// For more information about the fields of a service check, see the
+/// [Datadog docs](https://docs.datadoghq.com/developers/dogstatsd/datagram_shell/?tab=servicechecks).
+///
+/// NOTE: The only way to instantiate an instance of this builder is via methods in
+/// in the `StatsdClient` client.
+///
+/// # Example
+///
+/// ```
+/// use cadence::prelude::*;
+/// use cadence::{ServiceCheckStatus, StatsdClient, NopMetricSink, Metric};
+///
+/// let client = StatsdClient::from_sink("some.prefix", NopMetricSink);
+/// let res = client.service_check_with_tags("database.up", ServiceCheckStatus::Critical)
+///    .with_tag("db", "users")
+///    .with_message("connection refused")
+///    .try_send();
+///
+/// assert_eq!(
+///     "_sc|some.prefix.database.up|2|#db:users|m:connection refused",
+///     res.unwrap().as_metric_str()
+/// );
+/// ```
+#[must_use = "Did you forget to call .send() after adding tags?"]
+#[derive(Debug)]
+pub struct ServiceCheckBuilder<'m, 'c> {
+    repr: BuilderRepr<'c, ServiceCheckFormatter<'m>>,
+}
+
+impl<'m, 'c> ServiceCheckBuilder<'m, 'c> {
+    pub(crate) fn from_fmt(formatter: ServiceCheckFormatter<'m>, client: &'c StatsdClient) -> Self {
+        ServiceCheckBuilder {
+            repr: BuilderRepr::Success(formatter, client),
+        }
+    }
+
+    pub(crate) fn from_error(err: MetricError, client: &'c StatsdClient) -> Self {
+        ServiceCheckBuilder {
+            repr: BuilderRepr::Error(err, client),
+        }
+    }
+
+    fn with_formatter<F>(mut self, f: F) -> Self
+    where
+        F: FnOnce(&mut ServiceCheckFormatter<'m>),
+    {
+        if let BuilderRepr::Success(ref mut formatter, _) = self.repr {
+            f(formatter);
+        }
+        self
+    }
+
+    /// Add a key-value tag to this service check.
+    pub fn with_tag(self, key: &'m str, value: &'m str) -> Self {
+        self.with_formatter(|f| f.with_tag(key, value))
+    }
+
+    /// Add a value tag to this service check.
+    pub fn with_tag_value(self, value: &'m str) -> Self {
+        self.with_formatter(|f| f.with_tag_value(value))
+    }
+
+    /// Add default tags from the client to this service check.
+    pub(crate) fn with_default_tags<V>(self, tags: V) -> Self
+    where
+        V: IntoIterator<Item = (Option<&'m str>, &'m str)>,
+    {
+        self.with_formatter(|f| {
+            for (key, value) in tags.into_iter() {
+                f.with_default_tag(key, value);
+            }
+        })
+    }
+
+    /// Add a container_id to this service check.
+    pub fn with_container_id(self, container_id: &'m str) -> Self {
+        self.with_formatter(|f| f.with_container_id(container_id))
+    }
+
+    pub(crate) fn with_container_id_opt(self, container_id: Option<&'m str>) -> Self {
+        match container_id {
+            Some(container_id) => self.with_container_id(container_id),
+            None => self,
+        }
+    }
+
+    /// Set the UNIX timestamp in seconds of when the check was run.
+    ///
+    /// If not set, the server will use the time the check was received.
+    pub fn with_timestamp(self, timestamp: u64) -> Self {
+        self.with_formatter(|f| f.with_timestamp(timestamp))
+    }
+
+    /// Set the name of the host this service check is for.
+    pub fn with_hostname(self, hostname: &'m str) -> Self {
+        self.with_formatter(|f| f.with_hostname(hostname))
+    }
+
+    /// Set a message describing the current state of the service.
+    pub fn with_message(self, message: &'m str) -> Self {
+        self.with_formatter(|f| f.with_message(message))
+    }
+
+    /// Send a service check using the client that created this builder.
+    ///
+    /// Note that the builder is consumed by this method and thus `.try_send()`
+    /// can only be called a single time per builder.
+    pub fn try_send(self) -> MetricResult<ServiceCheck> {
+        match self.repr {
+            BuilderRepr::Error(err, _) => Err(err),
+            BuilderRepr::Success(ref formatter, client) => {
+                let check = ServiceCheck::from(formatter.format());
+                client.send_metric(&check)?;
+                Ok(check)
+            }
+        }
+    }
+
+    /// Send a service check using the client that created this builder,
+    /// discarding successful results and invoking the client error handler
+    /// for error results.
+    ///
+    /// Note that the builder is consumed by this method and thus `.send()`
+    /// can only be called a single time per builder.
+    pub fn send(self) {
+        if self.repr.is_disabled() {
+            return;
+        }
+
+        match self.repr {
+            BuilderRepr::Error(err, client) => client.consume_error(err),
+            BuilderRepr::Success(ref formatter, client) => {
+                let check = ServiceCheck::from(formatter.format());
+                if let Err(e) = client.send_metric(&check) {
+                    client.consume_metric_error(check.as_metric_str(), e);
+                }
+            }
+        }
+    }
+}
 
 #[cfg(test)]
 mod tests {
-    use super::{MetricBuilder, MetricFormatter, MetricValue};
-    use crate::client::StatsdClient;
-    use crate::sinks::NopMetricSink;
+    use super::{
+        EventFormatter, MetricBuilder, MetricFormatter, MetricValue, NonFinitePolicy, SanitizePolicy,
+        ServiceCheckFormatter, TagFormat,
+    };
+    use crate::client::{Counted, Gauged, StatsdClient};
+    use crate::sample::ThreadLocalRng;
+    use crate::sinks::{NopMetricSink, SpyMetricSink};
     use crate::test::ErrorMetricSink;
-    use crate::types::Counter;
+    use crate::types::{Counter, ErrorKind, EventAlertType, EventPriority, Metric, ServiceCheckStatus};
+    use std::collections::HashMap;
     use std::sync::atomic::{AtomicU64, Ordering};
     use std::sync::Arc;
 
@@ -636,7 +2458,23 @@ mod tests {
         fmt.with_tag("host", "web");
         fmt.with_tag("user", "123");
 
-        assert_eq!(19, fmt.tag_size_hint());
+        assert_eq!(19, fmt.tag_size_hint());
+    }
+
+    #[test]
+    fn test_metric_formatter_tag_overrides_default_tag() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
+        fmt.with_default_tag(Some("env"), "prod");
+        fmt.with_default_tag(None, "beta");
+        fmt.with_default_tag(Some("host"), "web01");
+        fmt.with_tag("env", "staging");
+        fmt.with_tag("env", "dev");
+
+        assert_eq!(
+            "prefix.some.key:1|c|#beta,host:web01,env:staging,env:dev",
+            &fmt.format()
+        );
+        assert_eq!(37, fmt.tag_size_hint());
     }
 
     #[test]
@@ -659,6 +2497,46 @@ mod tests {
         assert_eq!(41, fmt.size_hint());
     }
 
+    #[test]
+    fn test_metric_formatter_telegraf_tags() {
+        let mut fmt = MetricFormatter::timer("prefix.", "some.key", MetricValue::Unsigned(34));
+        fmt.with_tag_format(TagFormat::Telegraf);
+        fmt.with_default_tag(Some("env"), "prod");
+        fmt.with_tag("host", "web01");
+        fmt.with_tag_value("beta");
+        fmt.with_sampling_rate(0.5);
+
+        let expected = "prefix.some.key,env=prod,host=web01:34|ms|@0.5";
+        assert_eq!(expected, &fmt.format());
+        assert_eq!(20, fmt.tag_size_hint());
+        assert_eq!(expected.find(':').unwrap() + 1, fmt.value_index());
+    }
+
+    #[test]
+    fn test_metric_formatter_graphite_tags() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(4));
+        fmt.with_tag_format(TagFormat::Graphite);
+        fmt.with_tag("host", "web01");
+        fmt.with_tag_value("beta");
+        fmt.with_tag("region", "us-east-1");
+        fmt.with_timestamp(1234567890);
+
+        let expected = "prefix.some.key;host=web01;region=us-east-1:4|c|T1234567890";
+        assert_eq!(expected, &fmt.format());
+        assert_eq!(28, fmt.tag_size_hint());
+        assert_eq!(expected.find(':').unwrap() + 1, fmt.value_index());
+    }
+
+    #[test]
+    fn test_metric_formatter_telegraf_no_tags() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(4));
+        fmt.with_tag_format(TagFormat::Telegraf);
+
+        assert_eq!("prefix.some.key:4|c", &fmt.format());
+        assert_eq!(0, fmt.tag_size_hint());
+        assert_eq!(16, fmt.value_index());
+    }
+
     #[test]
     fn test_metric_formatter_counter_no_tags() {
         let fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(4));
@@ -872,6 +2750,14 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_metric_formatter_custom_with_tags() {
+        let mut fmt = MetricFormatter::custom("prefix.", "cache.ratio", MetricValue::Float(0.75), "pct");
+        fmt.with_tag("host", "web01");
+
+        assert_eq!("prefix.cache.ratio:0.75|pct|#host:web01", &fmt.format());
+    }
+
     #[test]
     fn test_metric_builder_send_success() {
         let fmt = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(11));
@@ -925,4 +2811,608 @@ mod tests {
 
         assert!(res.is_err(), "expected Err result from try_send");
     }
+
+    #[test]
+    fn test_metric_builder_send_multiple() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix.", sink);
+
+        for i in 0..3 {
+            let fmt = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(i));
+            let builder: MetricBuilder<'_, '_, Counter> = MetricBuilder::from_fmt(fmt, &client);
+            builder.with_tag("id", "1").send();
+        }
+
+        assert_eq!(b"prefix.some.counter:0|c|#id:1".to_vec(), rx.try_recv().unwrap());
+        assert_eq!(b"prefix.some.counter:1|c|#id:1".to_vec(), rx.try_recv().unwrap());
+        assert_eq!(b"prefix.some.counter:2|c|#id:1".to_vec(), rx.try_recv().unwrap());
+    }
+
+    #[test]
+    fn test_metric_builder_try_send_with_buffer() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix.", sink);
+        let mut buf = String::from("leftover");
+
+        let fmt = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(11));
+        let builder: MetricBuilder<'_, '_, Counter> = MetricBuilder::from_fmt(fmt, &client);
+        builder.try_send_with_buffer(&mut buf).unwrap();
+
+        assert_eq!("prefix.some.counter:11|c", buf);
+        assert_eq!(b"prefix.some.counter:11|c".to_vec(), rx.try_recv().unwrap());
+    }
+
+    #[test]
+    fn test_metric_builder_try_send_with_buffer_error() {
+        let client = StatsdClient::from_sink("prefix.", ErrorMetricSink::always());
+        let mut buf = String::new();
+
+        let fmt = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(11));
+        let builder: MetricBuilder<'_, '_, Counter> = MetricBuilder::from_fmt(fmt, &client);
+        let res = builder.try_send_with_buffer(&mut buf);
+
+        assert_eq!(ErrorKind::Timeout, res.unwrap_err().kind());
+        assert_eq!("prefix.some.counter:11|c", buf);
+    }
+
+    #[test]
+    fn test_metric_value_write_to_matches_display() {
+        let values = vec![
+            MetricValue::Signed(0),
+            MetricValue::Signed(i64::MIN),
+            MetricValue::Unsigned(u64::MAX),
+            MetricValue::PackedSigned(vec![-1, 0, 1]),
+            MetricValue::PackedUnsigned(vec![]),
+            MetricValue::Float(0.0),
+            MetricValue::Float(-0.0),
+            MetricValue::Float(5.0),
+            MetricValue::Float(0.1 + 0.2),
+            MetricValue::Float(-123.456),
+            MetricValue::Float(1e21),
+            MetricValue::Float(1e-7),
+            MetricValue::Float(f64::MAX),
+            MetricValue::Float(f64::MIN_POSITIVE),
+            MetricValue::PackedFloat(vec![1.0, 2.5, 1e16]),
+        ];
+
+        for val in values {
+            let mut out = String::new();
+            val.write_to(&mut out).unwrap();
+            assert_eq!(val.to_string(), out);
+        }
+    }
+
+    #[test]
+    fn test_metric_formatter_float_values() {
+        let fmt = MetricFormatter::gauge(
+            "prefix.",
+            "some.gauge",
+            MetricValue::PackedFloat(vec![3.0, 0.25, 1e-10]),
+        );
+        assert_eq!("prefix.some.gauge:3:0.25:0.0000000001|g", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_sample_rate_always() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
+        fmt.with_sample_rate(1.0);
+
+        assert!(fmt.is_sampled(&ThreadLocalRng));
+        assert_eq!("prefix.some.key:1|c", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_sample_rate_never() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
+        fmt.with_sample_rate(0.0);
+
+        assert!(!fmt.is_sampled(&ThreadLocalRng));
+        assert_eq!("prefix.some.key:1|c|@0", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_sample_rate_and_sampling_rate() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
+        fmt.with_sample_rate(0.1);
+        fmt.with_sampling_rate(0.5);
+        assert_eq!("prefix.some.key:1|c|@0.05", &fmt.format());
+
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
+        fmt.with_sampling_rate(0.5);
+        fmt.with_sample_rate(0.1);
+        assert_eq!("prefix.some.key:1|c|@0.05", &fmt.format());
+
+        fmt.with_sample_rate(1.0);
+        assert_eq!("prefix.some.key:1|c|@0.5", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_builder_sample_rate_and_sampling_rate() {
+        let client = StatsdClient::builder("prefix.", NopMetricSink)
+            .with_sample_rate(0.5)
+            .build();
+
+        let res = client
+            .count_with_tags("some.counter", 1)
+            .with_sampling_rate(0.5)
+            .try_send();
+        assert_eq!("prefix.some.counter:1|c|@0.25", res.unwrap().as_metric_str());
+
+        let res = client
+            .count_with_tags("some.counter", 1)
+            .with_sampling_rate(0.5)
+            .with_sample_rate(1.0)
+            .try_send();
+        assert_eq!("prefix.some.counter:1|c|@0.5", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_metric_builder_sample_rate_not_sent() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix.", sink);
+        let fmt = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(11));
+
+        let builder: MetricBuilder<'_, '_, Counter> = MetricBuilder::from_fmt(fmt, &client);
+        let res = builder.with_sample_rate(0.0).try_send();
+
+        assert_eq!("prefix.some.counter:11|c|@0", res.unwrap().as_metric_str());
+        assert!(rx.try_recv().is_err(), "expected metric to be sampled out");
+    }
+
+    #[test]
+    fn test_metric_builder_sample_rate_send_skipped() {
+        let errors = Arc::new(AtomicU64::new(0));
+        let errors_ref = errors.clone();
+
+        let fmt = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(11));
+        let client = StatsdClient::builder("prefix.", ErrorMetricSink::always())
+            .with_error_handler(move |_e| {
+                errors_ref.fetch_add(1, Ordering::Release);
+            })
+            .build();
+
+        // the sink always fails so an error would be recorded if the metric was sent
+        let builder: MetricBuilder<'_, '_, Counter> = MetricBuilder::from_fmt(fmt, &client);
+        builder.with_sample_rate(0.0).send();
+
+        assert_eq!(0, errors.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn test_metric_formatter_sanitize_lenient() {
+        let mut fmt = MetricFormatter::counter("pre:fix.", "some|key\n", MetricValue::Signed(1));
+        fmt.with_sanitize_policy(SanitizePolicy::Lenient);
+        fmt.with_tag("ho:st", "a:b,c|d");
+        fmt.with_tag_value("x\ny");
+
+        assert_eq!("pre_fix.some_key_:1|c|#ho_st:a:b_c_d,x_y", &fmt.format());
+        assert!(fmt.check().is_ok());
+
+        fmt.with_tag_format(TagFormat::Telegraf);
+        assert_eq!("pre_fix.some_key_,ho_st=a_b_c_d:1|c", &fmt.format());
+        assert_eq!(fmt.value_index(), fmt.format().find(":1|c").unwrap() + 1);
+    }
+
+    #[test]
+    fn test_metric_formatter_sanitize_lenient_inline_tags() {
+        let mut fmt = MetricFormatter::counter("app.", "req;x=1,y", MetricValue::Signed(1));
+        fmt.with_sanitize_policy(SanitizePolicy::Lenient);
+        fmt.with_tag("k=a", "v=b;c d~e");
+
+        fmt.with_tag_format(TagFormat::Telegraf);
+        assert_eq!("app.req;x_1_y,k_a=v_b;c_d~e:1|c", &fmt.format());
+        assert!(fmt.check().is_ok());
+
+        fmt.with_tag_format(TagFormat::Graphite);
+        assert_eq!("app.req_x_1,y;k_a=v_b_c d_e:1|c", &fmt.format());
+        assert!(fmt.check().is_ok());
+    }
+
+    #[test]
+    fn test_metric_formatter_sanitize_strict_inline_tags() {
+        for format in [TagFormat::Telegraf, TagFormat::Graphite] {
+            let mut fmt = MetricFormatter::counter("app.", "req", MetricValue::Signed(1));
+            fmt.with_sanitize_policy(SanitizePolicy::Strict);
+            fmt.with_tag_format(format);
+            fmt.with_tag("k", "v");
+            assert!(fmt.check().is_ok());
+
+            let mut fmt = MetricFormatter::counter("app.", "req=x", MetricValue::Signed(1));
+            fmt.with_sanitize_policy(SanitizePolicy::Strict);
+            fmt.with_tag_format(format);
+            assert_eq!(ErrorKind::Sanitization, fmt.check().unwrap_err().kind());
+
+            let mut fmt = MetricFormatter::counter("app.", "req", MetricValue::Signed(1));
+            fmt.with_sanitize_policy(SanitizePolicy::Strict);
+            fmt.with_tag_format(format);
+            fmt.with_tag("k=a", "v");
+            assert_eq!(ErrorKind::Sanitization, fmt.check().unwrap_err().kind());
+
+            let mut fmt = MetricFormatter::counter("app.", "req", MetricValue::Signed(1));
+            fmt.with_sanitize_policy(SanitizePolicy::Strict);
+            fmt.with_tag_format(format);
+            fmt.with_tag("k", "a:b");
+            assert_eq!(ErrorKind::Sanitization, fmt.check().unwrap_err().kind());
+        }
+
+        let mut fmt = MetricFormatter::counter("app.", "req,x", MetricValue::Signed(1));
+        fmt.with_sanitize_policy(SanitizePolicy::Strict);
+        fmt.with_tag_format(TagFormat::Telegraf);
+        assert_eq!(ErrorKind::Sanitization, fmt.check().unwrap_err().kind());
+        fmt.with_tag_format(TagFormat::Graphite);
+        assert!(fmt.check().is_ok());
+
+        let mut fmt = MetricFormatter::counter("app.", "req", MetricValue::Signed(1));
+        fmt.with_sanitize_policy(SanitizePolicy::Strict);
+        fmt.with_tag("k", "b;c d");
+        fmt.with_tag_format(TagFormat::Telegraf);
+        assert_eq!(ErrorKind::Sanitization, fmt.check().unwrap_err().kind());
+        fmt.with_tag_format(TagFormat::Graphite);
+        assert_eq!(ErrorKind::Sanitization, fmt.check().unwrap_err().kind());
+        fmt.with_tag_format(TagFormat::Datadog);
+        assert!(fmt.check().is_ok());
+    }
+
+    #[test]
+    fn test_metric_formatter_sanitize_strict() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
+        fmt.with_sanitize_policy(SanitizePolicy::Strict);
+        fmt.with_tag("host", "a:b");
+        assert!(fmt.check().is_ok());
+
+        fmt.with_tag("bad", "x\ny");
+        assert_eq!(ErrorKind::Sanitization, fmt.check().unwrap_err().kind());
+
+        let mut fmt = MetricFormatter::counter("prefix.", "some@key", MetricValue::Signed(1));
+        fmt.with_sanitize_policy(SanitizePolicy::Strict);
+        assert_eq!(ErrorKind::Sanitization, fmt.check().unwrap_err().kind());
+
+        let mut fmt = MetricFormatter::counter("prefix.", "", MetricValue::Signed(1));
+        fmt.with_sanitize_policy(SanitizePolicy::Strict);
+        assert_eq!(ErrorKind::Sanitization, fmt.check().unwrap_err().kind());
+
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
+        fmt.with_sanitize_policy(SanitizePolicy::Strict);
+        fmt.with_tag("", "value");
+        assert_eq!(ErrorKind::Sanitization, fmt.check().unwrap_err().kind());
+
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
+        fmt.with_sanitize_policy(SanitizePolicy::Strict);
+        fmt.with_tag_value("");
+        assert_eq!(ErrorKind::Sanitization, fmt.check().unwrap_err().kind());
+
+        // Not checked by default
+        let fmt = MetricFormatter::counter("prefix.", "some@key", MetricValue::Signed(1));
+        assert!(fmt.check().is_ok());
+    }
+
+    #[test]
+    fn test_metric_formatter_sanitize_lenient_malformed_tags() {
+        let mut fmt = MetricFormatter::counter("prefix.", "some.key", MetricValue::Signed(1));
+        fmt.with_sanitize_policy(SanitizePolicy::Lenient);
+        fmt.with_tag("", "a");
+        fmt.with_tag_value("");
+        assert_eq!("prefix.some.key:1|c", &fmt.format());
+
+        fmt.with_tag("host", "");
+        fmt.with_tag_value("b");
+        assert_eq!("prefix.some.key:1|c|#host:,b", &fmt.format());
+
+        fmt.with_tag_format(TagFormat::Telegraf);
+        assert_eq!("prefix.some.key,host=:1|c", &fmt.format());
+    }
+
+    #[test]
+    fn test_metric_formatter_non_finite() {
+        let mut fmt = MetricFormatter::gauge("prefix.", "some.gauge", MetricValue::Float(f64::NAN));
+        let err = fmt.apply_non_finite().unwrap_err();
+        assert_eq!(ErrorKind::InvalidInput, err.kind());
+        assert_eq!(Some("prefix.some.gauge"), err.metric());
+
+        let mut fmt = MetricFormatter::gauge("prefix.", "some.gauge", MetricValue::Float(f64::NAN));
+        fmt.with_non_finite_policy(NonFinitePolicy::Skip);
+        assert!(!fmt.apply_non_finite().unwrap());
+
+        let mut fmt = MetricFormatter::histogram(
+            "prefix.",
+            "some.histogram",
+            MetricValue::PackedFloat(vec![1.5, f64::INFINITY, f64::NEG_INFINITY]),
+        );
+        fmt.with_non_finite_policy(NonFinitePolicy::Clamp);
+        assert!(fmt.apply_non_finite().unwrap());
+        assert!(fmt.check().is_ok());
+        assert_eq!(
+            format!("prefix.some.histogram:1.5:{}:{}|h", f64::MAX, f64::MIN),
+            fmt.format()
+        );
+    }
+
+    #[test]
+    fn test_non_finite_policy_apply() {
+        let mut val = MetricValue::Float(f64::INFINITY);
+        assert!(NonFinitePolicy::Error.apply(&mut val).is_err());
+        assert!(!NonFinitePolicy::Skip.apply(&mut val).unwrap());
+        assert!(NonFinitePolicy::Clamp.apply(&mut val).unwrap());
+        assert_eq!(MetricValue::Float(f64::MAX), val);
+
+        let mut val = MetricValue::PackedFloat(vec![1.0, f64::NAN]);
+        assert!(!NonFinitePolicy::Clamp.apply(&mut val).unwrap());
+
+        let mut val = MetricValue::Unsigned(1);
+        assert!(NonFinitePolicy::Error.apply(&mut val).unwrap());
+        assert_eq!(MetricValue::Unsigned(1), val);
+    }
+
+    #[test]
+    fn test_metric_builder_sanitize_inline_tags() {
+        let cases = [
+            (TagFormat::Telegraf, "app.req;x_1_y,k_a=v_b;c_d:1|c"),
+            (TagFormat::Graphite, "app.req_x_1,y;k_a=v_b_c d:1|c"),
+        ];
+
+        for (format, lenient) in cases {
+            let client = StatsdClient::builder("app", NopMetricSink)
+                .with_tag_format(format)
+                .with_sanitize_policy(SanitizePolicy::Strict)
+                .build();
+            let res = client
+                .count_with_tags("req;x=1,y", 1)
+                .with_tag("k=a", "v=b;c d")
+                .try_send();
+            assert_eq!(ErrorKind::Sanitization, res.unwrap_err().kind());
+
+            let client = StatsdClient::builder("app", NopMetricSink)
+                .with_tag_format(format)
+                .with_sanitize_policy(SanitizePolicy::Lenient)
+                .build();
+            let res = client
+                .count_with_tags("req;x=1,y", 1)
+                .with_tag("k=a", "v=b;c d")
+                .try_send();
+            assert_eq!(lenient, res.unwrap().as_metric_str());
+        }
+    }
+
+    #[test]
+    fn test_metric_builder_sanitize_strict() {
+        let errors = Arc::new(AtomicU64::new(0));
+        let errors_ref = errors.clone();
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix.", sink)
+            .with_sanitize_policy(SanitizePolicy::Strict)
+            .with_error_handler(move |_e| {
+                errors_ref.fetch_add(1, Ordering::Release);
+            })
+            .build();
+
+        let res = client
+            .count_with_tags("some.counter", 1)
+            .with_tag("a", "b|c")
+            .try_send();
+        let err = res.unwrap_err();
+        assert_eq!(ErrorKind::Sanitization, err.kind());
+        assert_eq!(Some("prefix.some.counter"), err.metric());
+        client
+            .count_with_tags("some.counter", 1)
+            .with_tag_lazy("a", || "b\nc".to_owned())
+            .send();
+        let mut buf = String::new();
+        let res = client.count_with_tags("some:counter", 1).try_send_with_buffer(&mut buf);
+        assert_eq!(ErrorKind::Sanitization, res.unwrap_err().kind());
+        let res = client.counter_handle("some|counter").incr();
+        assert_eq!(ErrorKind::Sanitization, res.unwrap_err().kind());
+
+        assert_eq!(1, errors.load(Ordering::Acquire));
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_metric_builder_sample_rate_invalid() {
+        let fmt = MetricFormatter::counter("prefix.", "some.counter", MetricValue::Signed(11));
+        let client = StatsdClient::from_sink("prefix.", NopMetricSink);
+
+        let builder: MetricBuilder<'_, '_, Counter> = MetricBuilder::from_fmt(fmt, &client);
+        let res = builder.with_sample_rate(1.5).try_send();
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_metric_builder_tag_opt() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix.", sink);
+        let user: Option<&str> = None;
+
+        client
+            .count_with_tags("some.counter", 1)
+            .with_tag_opt("user", user)
+            .with_tag_opt("region", Some("us-east-1"))
+            .send();
+
+        assert_eq!(
+            b"prefix.some.counter:1|c|#region:us-east-1".to_vec(),
+            rx.try_recv().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_metric_builder_tags() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix.", sink)
+            .with_tag("region", "us-west-2")
+            .build();
+        let tags = [("method", "GET"), ("region", "us-east-1")];
+
+        client
+            .count_with_tags("some.counter", 1)
+            .with_tags(&tags)
+            .with_tags(None::<(&str, &str)>)
+            .send();
+
+        assert_eq!(
+            b"prefix.some.counter:1|c|#method:GET,region:us-east-1".to_vec(),
+            rx.try_recv().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_metric_builder_tags_from_map() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix.", sink);
+        let mut borrowed = HashMap::new();
+        borrowed.insert("method", "GET");
+        let mut owned = HashMap::new();
+        owned.insert("status".to_string(), "200".to_string());
+
+        client
+            .count_with_tags("some.counter", 1)
+            .with_tags(&borrowed)
+            .with_tags(&owned)
+            .with_tags(&[("region", "us-east-1")][..])
+            .send();
+
+        assert_eq!(
+            b"prefix.some.counter:1|c|#method:GET,status:200,region:us-east-1".to_vec(),
+            rx.try_recv().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_metric_builder_tag_lazy_send() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix.", sink).with_tag("shard", "0").build();
+
+        client
+            .count_with_tags("some.counter", 1)
+            .with_tag_lazy("shard", || "3".to_string())
+            .with_tag("region", "us-east-1")
+            .send();
+
+        assert_eq!(
+            b"prefix.some.counter:1|c|#region:us-east-1,shard:3".to_vec(),
+            rx.try_recv().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_metric_builder_tag_lazy_sampled_out() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix.", sink);
+        let calls = AtomicU64::new(0);
+
+        for _ in 0..10 {
+            client
+                .count_with_tags("some.counter", 1)
+                .with_sample_rate(0.0)
+                .with_tag_lazy("shard", || {
+                    calls.fetch_add(1, Ordering::Relaxed);
+                    "3".to_string()
+                })
+                .send();
+        }
+
+        assert_eq!(0, calls.load(Ordering::Relaxed));
+        assert!(rx.try_recv().is_err(), "expected metric to be sampled out");
+    }
+
+    #[test]
+    fn test_metric_builder_tag_lazy_try_send_with_buffer() {
+        let client = StatsdClient::from_sink("prefix.", NopMetricSink);
+        let mut buf = String::new();
+
+        client
+            .gauge_with_tags("some.gauge", 4)
+            .with_tag_lazy("shard", || "3".to_string())
+            .try_send_with_buffer(&mut buf)
+            .unwrap();
+
+        assert_eq!("prefix.some.gauge:4|g|#shard:3", buf);
+    }
+
+    #[test]
+    fn test_metric_builder_tag_lazy_not_sync() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix.", sink);
+        let shard = std::cell::Cell::new(3);
+
+        client
+            .count_with_tags("some.counter", 1)
+            .with_tag_lazy("shard", move || shard.get().to_string())
+            .send();
+
+        assert_eq!(b"prefix.some.counter:1|c|#shard:3".to_vec(), rx.try_recv().unwrap());
+    }
+
+    #[test]
+    fn test_metric_builder_tag_lazy_error() {
+        let client = StatsdClient::from_sink("prefix.", NopMetricSink);
+        let res = client
+            .count_with_tags("some.counter", f64::NAN)
+            .with_tag_lazy("shard", || panic!("tag computed for invalid metric"))
+            .try_send();
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_event_formatter_no_fields() {
+        let fmt = EventFormatter::new("Deploy", "Deployed v1.2.3");
+        assert_eq!("_e{6,15}:Deploy|Deployed v1.2.3", &fmt.format());
+    }
+
+    #[test]
+    fn test_event_formatter_all_fields() {
+        let mut fmt = EventFormatter::new("Deploy", "Deployed v1.2.3");
+        fmt.with_default_tag(Some("env"), "prod");
+        fmt.with_tag("service", "api");
+        fmt.with_tag_value("canary");
+        fmt.with_timestamp(1234567890);
+        fmt.with_hostname("web01");
+        fmt.with_aggregation_key("deploys");
+        fmt.with_priority(EventPriority::Low);
+        fmt.with_source_type_name("jenkins");
+        fmt.with_alert_type(EventAlertType::Success);
+        fmt.with_container_id("1234");
+
+        let expected = concat!(
+            "_e{6,15}:Deploy|Deployed v1.2.3|d:1234567890|h:web01|k:deploys|p:low|",
+            "s:jenkins|t:success|#env:prod,service:api,canary|c:1234"
+        );
+        assert_eq!(expected, &fmt.format());
+        assert!(fmt.size_hint() >= expected.len());
+    }
+
+    #[test]
+    fn test_event_formatter_escapes_newlines() {
+        let fmt = EventFormatter::new("Deploy\nfailed", "line one\nline two");
+        assert_eq!(r"_e{14,18}:Deploy\nfailed|line one\nline two", &fmt.format());
+    }
+
+    #[test]
+    fn test_event_formatter_tag_overrides_default_tag() {
+        let mut fmt = EventFormatter::new("Deploy", "Deployed v1.2.3");
+        fmt.with_default_tag(Some("env"), "prod");
+        fmt.with_tag("env", "staging");
+        assert_eq!("_e{6,15}:Deploy|Deployed v1.2.3|#env:staging", &fmt.format());
+    }
+
+    #[test]
+    fn test_service_check_formatter_no_fields() {
+        let fmt = ServiceCheckFormatter::new("prefix.", "db.up", ServiceCheckStatus::Ok);
+        assert_eq!("_sc|prefix.db.up|0", &fmt.format());
+    }
+
+    #[test]
+    fn test_service_check_formatter_all_fields() {
+        let mut fmt = ServiceCheckFormatter::new("prefix.", "db.up", ServiceCheckStatus::Warning);
+        fmt.with_default_tag(Some("env"), "prod");
+        fmt.with_tag("db", "users");
+        fmt.with_timestamp(1234567890);
+        fmt.with_hostname("web01");
+        fmt.with_message("slow\nqueries from:web");
+        fmt.with_container_id("1234");
+
+        let expected = r"_sc|prefix.db.up|1|d:1234567890|h:web01|#env:prod,db:users|c:1234|m:slow\nqueries from\:web";
+        assert_eq!(expected, &fmt.format());
+    }
 }
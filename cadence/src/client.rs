@@ -8,22 +8,72 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use crate::builder::{MetricBuilder, MetricFormatter, MetricValue};
+use crate::builder::{
+    EventBuilder, EventFormatter, MetricBatch, MetricBuilder, MetricFormatter, MetricType, MetricValue,
+    NonFinitePolicy, SanitizePolicy, ServiceCheckBuilder, ServiceCheckFormatter, TagFormat,
+};
+use crate::errors::{ErrorStats, ErrorTracker};
+use crate::gauges::{GaugeRegistration, GaugeRegistry};
+use crate::handle::{MetricHandle, NewFormatter};
+use crate::sample::{self, Rng, ThreadLocalRng};
 use crate::sealed::Sealed;
 use crate::sinks::MetricSink;
+#[cfg(feature = "async-timing")]
+use crate::timing::TimedFuture;
+use crate::timing::{Clock, MonotonicClock, ScaledDuration, Stopwatch, TimeUnit};
 use crate::types::{
-    Counter, Distribution, ErrorKind, Gauge, Histogram, Meter, Metric, MetricError, MetricResult, Set, Timer,
+    Counter, CustomMetric, Distribution, ErrorKind, Event, Gauge, Histogram, Meter, Metric, MetricError, MetricResult,
+    ServiceCheck, ServiceCheckStatus, Set, Timer, WriteMetric,
 };
+use std::cell::Cell;
 use std::fmt;
-use std::panic::RefUnwindSafe;
+#[cfg(feature = "async-timing")]
+use std::future::Future;
+use std::io;
+use std::num::{NonZeroU32, NonZeroU64};
+use std::panic::{self, AssertUnwindSafe, RefUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::thread;
 use std::time::Duration;
 
+// How often the values of registered gauges are sent by default.
+const DEFAULT_GAUGE_INTERVAL: Duration = Duration::from_secs(10);
+
+// How long a panic hook waits for its counter to be sent before giving up.
+const PANIC_HOOK_TIMEOUT: Duration = Duration::from_millis(500);
+
+thread_local! {
+    // Set on threads sending the counter of a panic hook so that a panic while
+    // sending it doesn't cause another counter to be sent.
+    static IN_PANIC_HOOK: Cell<bool> = const { Cell::new(false) };
+}
+
+// Send the counter of a panic hook from a new thread, waiting a limited amount
+// of time for it to be sent, so that a sink lock held by the panicking thread
+// can't deadlock it and a panic in the sink can't abort the process.
+fn send_panic_counter(client: StatsdClient, key: String, name: String) {
+    let (tx, rx) = crossbeam_channel::bounded(1);
+    let res = thread::Builder::new().name("cadence-panic-hook".into()).spawn(move || {
+        IN_PANIC_HOOK.with(|h| h.set(true));
+        let _r = panic::catch_unwind(AssertUnwindSafe(|| {
+            client.incr_with_tags(&key).with_tag("thread", &name).send();
+            let _ = client.flush();
+        }));
+
+        let _ = tx.send(());
+    });
+
+    if res.is_ok() {
+        let _ = rx.recv_timeout(PANIC_HOOK_TIMEOUT);
+    }
+}
+
 /// Conversion trait for valid values for counters
 ///
 /// This trait must be implemented for any types that are used as counter
-/// values (currently `i64`, `i32`, `u64`, and `u32`). This trait is internal to how values are
-/// formatted as part of metrics but is exposed publicly for documentation
-/// purposes.
+/// values (currently `i64`, `i32`, `i128`, `u64`, `u32`, `u128`, and `f64`).
+/// This trait is internal to how values are formatted as part of metrics but
+/// is exposed publicly for documentation purposes.
 ///
 /// Typical use of Cadence shouldn't require interacting with this trait.
 pub trait ToCounterValue {
@@ -54,16 +104,73 @@ impl ToCounterValue for u32 {
     }
 }
 
+impl ToCounterValue for i128 {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        wide_signed(self)
+    }
+}
+
+impl ToCounterValue for u128 {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        wide_unsigned(self)
+    }
+}
+
+impl ToCounterValue for f64 {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        finite_float(self)
+    }
+}
+
+// Values of wider types are only accepted if they fit in a `u64`, or an `i64`
+// for negative values, since that's all that can be written.
+fn wide_unsigned(val: u128) -> MetricResult<MetricValue> {
+    u64::try_from(val)
+        .map(MetricValue::Unsigned)
+        .map_err(|_| MetricError::from((ErrorKind::InvalidInput, "u64 overflow")))
+}
+
+fn wide_signed(val: i128) -> MetricResult<MetricValue> {
+    if val >= 0 {
+        wide_unsigned(val as u128)
+    } else {
+        i64::try_from(val)
+            .map(MetricValue::Signed)
+            .map_err(|_| MetricError::from((ErrorKind::InvalidInput, "i64 overflow")))
+    }
+}
+
+// Fractional counts are only meaningful as real numbers so they are always
+// rejected, unlike gauges which are handled by the `NonFinitePolicy` of the
+// client.
+fn finite_float(val: f64) -> MetricResult<MetricValue> {
+    if val.is_finite() {
+        Ok(MetricValue::Float(val))
+    } else {
+        Err(MetricError::from((ErrorKind::InvalidInput, "value must be finite")))
+    }
+}
+
 /// Conversion trait for valid values for timers
 ///
 /// This trait must be implemented for any types that are used as timer
-/// values (currently `u64`, `Duration`, and `Vec`s of those types).
-/// This trait is internal to how values are formatted as part of metrics
-/// but is exposed publicly for documentation purposes.
+/// values (currently `u64`, `Duration`, `ScaledDuration`, and `Vec`s, slices,
+/// or arrays of `u64` and `Duration`). This trait is internal to how values
+/// are formatted as part of metrics but is exposed publicly for documentation
+/// purposes.
 ///
 /// Typical use of Cadence shouldn't require interacting with this trait.
 pub trait ToTimerValue {
     fn try_to_value(self) -> MetricResult<MetricValue>;
+
+    /// Convert to a metric value using the given unit for `Duration`s. Types
+    /// that aren't durations ignore the unit.
+    fn try_to_value_in(self, _unit: TimeUnit) -> MetricResult<MetricValue>
+    where
+        Self: Sized,
+    {
+        self.try_to_value()
+    }
 }
 
 impl ToTimerValue for u64 {
@@ -74,33 +181,106 @@ impl ToTimerValue for u64 {
 
 impl ToTimerValue for Vec<u64> {
     fn try_to_value(self) -> MetricResult<MetricValue> {
-        Ok(MetricValue::PackedUnsigned(self))
+        packed_unsigned(self)
+    }
+}
+
+impl ToTimerValue for &[u64] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_unsigned(self.to_vec())
+    }
+}
+
+impl<const N: usize> ToTimerValue for [u64; N] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_unsigned(self.to_vec())
     }
 }
 
 impl ToTimerValue for Duration {
     fn try_to_value(self) -> MetricResult<MetricValue> {
-        let as_millis = self.as_millis();
-        if as_millis > u64::MAX as u128 {
-            Err(MetricError::from((ErrorKind::InvalidInput, "u64 overflow")))
-        } else {
-            Ok(MetricValue::Unsigned(as_millis as u64))
-        }
+        duration_value(&self, TimeUnit::Milliseconds)
+    }
+
+    fn try_to_value_in(self, unit: TimeUnit) -> MetricResult<MetricValue> {
+        duration_value(&self, unit)
     }
 }
 
 impl ToTimerValue for Vec<Duration> {
     fn try_to_value(self) -> MetricResult<MetricValue> {
-        if self.iter().any(|x| x.as_millis() > u64::MAX as u128) {
-            Err(MetricError::from((ErrorKind::InvalidInput, "u64 overflow")))
-        } else {
-            Ok(MetricValue::PackedUnsigned(
-                self.iter().map(|x| x.as_millis() as u64).collect(),
-            ))
-        }
+        packed_durations(&self, TimeUnit::Milliseconds)
+    }
+
+    fn try_to_value_in(self, unit: TimeUnit) -> MetricResult<MetricValue> {
+        packed_durations(&self, unit)
+    }
+}
+
+impl ToTimerValue for &[Duration] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_durations(self, TimeUnit::Milliseconds)
+    }
+
+    fn try_to_value_in(self, unit: TimeUnit) -> MetricResult<MetricValue> {
+        packed_durations(self, unit)
+    }
+}
+
+impl<const N: usize> ToTimerValue for [Duration; N] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_durations(&self, TimeUnit::Milliseconds)
+    }
+
+    fn try_to_value_in(self, unit: TimeUnit) -> MetricResult<MetricValue> {
+        packed_durations(&self, unit)
     }
 }
 
+impl ToTimerValue for ScaledDuration {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        scaled_value(&self)
+    }
+}
+
+// Packed values are a Datadog extension that must include at least one value
+// to be a valid metric.
+fn packed_unsigned(vals: Vec<u64>) -> MetricResult<MetricValue> {
+    if vals.is_empty() {
+        Err(MetricError::from((ErrorKind::InvalidInput, "no values to pack")))
+    } else {
+        Ok(MetricValue::PackedUnsigned(vals))
+    }
+}
+
+fn packed_float(vals: Vec<f64>) -> MetricResult<MetricValue> {
+    if vals.is_empty() {
+        Err(MetricError::from((ErrorKind::InvalidInput, "no values to pack")))
+    } else {
+        Ok(MetricValue::PackedFloat(vals))
+    }
+}
+
+fn packed_durations(vals: &[Duration], unit: TimeUnit) -> MetricResult<MetricValue> {
+    if vals.iter().any(|x| unit.of(x) > u64::MAX as u128) {
+        Err(MetricError::from((ErrorKind::InvalidInput, "u64 overflow")))
+    } else {
+        packed_unsigned(vals.iter().map(|x| unit.of(x) as u64).collect())
+    }
+}
+
+fn scaled_value(val: &ScaledDuration) -> MetricResult<MetricValue> {
+    if val.is_fractional() {
+        Ok(MetricValue::Float(val.unit().fraction_of(val.duration())))
+    } else {
+        duration_value(val.duration(), val.unit())
+    }
+}
+
+fn duration_value(val: &Duration, unit: TimeUnit) -> MetricResult<MetricValue> {
+    wide_unsigned(unit.of(val))
+}
+
 /// Conversion trait for valid values for gauges
 ///
 /// This trait must be implemented for any types that are used as gauge
@@ -128,7 +308,7 @@ impl ToGaugeValue for f64 {
 /// Conversion trait for valid values for meters
 ///
 /// This trait must be implemented for any types that are used as meter
-/// values (currently only `u64`). This trait is internal to how values are
+/// values (currently `u64` and `f64`). This trait is internal to how values are
 /// formatted as part of metrics but is exposed publicly for documentation
 /// purposes.
 ///
@@ -143,16 +323,32 @@ impl ToMeterValue for u64 {
     }
 }
 
+impl ToMeterValue for f64 {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        finite_float(self)
+    }
+}
+
 /// Conversion trait for valid values for histograms
 ///
 /// This trait must be implemented for any types that are used as histogram
-/// values (currently `u64`, `f64`, `Duration`, and `Vec`s of those types).
-/// This trait is internal to how values are formatted as part of metrics
-/// but is exposed publicly for documentation purposes.
+/// values (currently `u64`, `f64`, `Duration`, `ScaledDuration`, and `Vec`s,
+/// slices, or arrays of `u64`, `f64`, and `Duration`). This trait is internal
+/// to how values are formatted as part of metrics but is exposed publicly for
+/// documentation purposes.
 ///
 /// Typical use of Cadence shouldn't require interacting with this trait.
 pub trait ToHistogramValue {
     fn try_to_value(self) -> MetricResult<MetricValue>;
+
+    /// Convert to a metric value using the given unit for `Duration`s. Types
+    /// that aren't durations ignore the unit.
+    fn try_to_value_in(self, _unit: TimeUnit) -> MetricResult<MetricValue>
+    where
+        Self: Sized,
+    {
+        self.try_to_value()
+    }
 }
 
 impl ToHistogramValue for u64 {
@@ -167,51 +363,108 @@ impl ToHistogramValue for f64 {
     }
 }
 
-impl ToHistogramValue for Duration {
+impl ToHistogramValue for Vec<u64> {
     fn try_to_value(self) -> MetricResult<MetricValue> {
-        let as_nanos = self.as_nanos();
-        if as_nanos > u64::MAX as u128 {
-            Err(MetricError::from((ErrorKind::InvalidInput, "u64 overflow")))
-        } else {
-            Ok(MetricValue::Unsigned(as_nanos as u64))
-        }
+        packed_unsigned(self)
     }
 }
 
-impl ToHistogramValue for Vec<u64> {
+impl ToHistogramValue for &[u64] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_unsigned(self.to_vec())
+    }
+}
+
+impl<const N: usize> ToHistogramValue for [u64; N] {
     fn try_to_value(self) -> MetricResult<MetricValue> {
-        Ok(MetricValue::PackedUnsigned(self))
+        packed_unsigned(self.to_vec())
     }
 }
 
 impl ToHistogramValue for Vec<f64> {
     fn try_to_value(self) -> MetricResult<MetricValue> {
-        Ok(MetricValue::PackedFloat(self))
+        packed_float(self)
+    }
+}
+
+impl ToHistogramValue for &[f64] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_float(self.to_vec())
+    }
+}
+
+impl<const N: usize> ToHistogramValue for [f64; N] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_float(self.to_vec())
+    }
+}
+
+impl ToHistogramValue for Duration {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        duration_value(&self, TimeUnit::Nanoseconds)
+    }
+
+    fn try_to_value_in(self, unit: TimeUnit) -> MetricResult<MetricValue> {
+        duration_value(&self, unit)
     }
 }
 
 impl ToHistogramValue for Vec<Duration> {
     fn try_to_value(self) -> MetricResult<MetricValue> {
-        if self.iter().any(|x| x.as_nanos() > u64::MAX as u128) {
-            Err(MetricError::from((ErrorKind::InvalidInput, "u64 overflow")))
-        } else {
-            Ok(MetricValue::PackedUnsigned(
-                self.iter().map(|x| x.as_nanos() as u64).collect(),
-            ))
-        }
+        packed_durations(&self, TimeUnit::Nanoseconds)
+    }
+
+    fn try_to_value_in(self, unit: TimeUnit) -> MetricResult<MetricValue> {
+        packed_durations(&self, unit)
+    }
+}
+
+impl ToHistogramValue for &[Duration] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_durations(self, TimeUnit::Nanoseconds)
+    }
+
+    fn try_to_value_in(self, unit: TimeUnit) -> MetricResult<MetricValue> {
+        packed_durations(self, unit)
+    }
+}
+
+impl<const N: usize> ToHistogramValue for [Duration; N] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_durations(&self, TimeUnit::Nanoseconds)
+    }
+
+    fn try_to_value_in(self, unit: TimeUnit) -> MetricResult<MetricValue> {
+        packed_durations(&self, unit)
+    }
+}
+
+impl ToHistogramValue for ScaledDuration {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        scaled_value(&self)
     }
 }
 
 /// Conversion trait for valid values for distributions
 ///
 /// This trait must be implemented for any types that are used as distribution
-/// values (currently `u64`, `f64`, and `Vec`s of those types). This trait is
-/// internal to how values are formatted as part of metrics but is exposed
-/// publicly for documentation purposes.
+/// values (currently `u64`, `f64`, `Duration`, `ScaledDuration`, and `Vec`s,
+/// slices, or arrays of `u64`, `f64`, and `Duration`). This trait is internal
+/// to how values are formatted as part of metrics but is exposed publicly for
+/// documentation purposes.
 ///
 /// Typical use of Cadence shouldn't require interacting with this trait.
 pub trait ToDistributionValue {
     fn try_to_value(self) -> MetricResult<MetricValue>;
+
+    /// Convert to a metric value using the given unit for `Duration`s. Types
+    /// that aren't durations ignore the unit.
+    fn try_to_value_in(self, _unit: TimeUnit) -> MetricResult<MetricValue>
+    where
+        Self: Sized,
+    {
+        self.try_to_value()
+    }
 }
 
 impl ToDistributionValue for u64 {
@@ -228,13 +481,83 @@ impl ToDistributionValue for f64 {
 
 impl ToDistributionValue for Vec<u64> {
     fn try_to_value(self) -> MetricResult<MetricValue> {
-        Ok(MetricValue::PackedUnsigned(self))
+        packed_unsigned(self)
+    }
+}
+
+impl ToDistributionValue for &[u64] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_unsigned(self.to_vec())
+    }
+}
+
+impl<const N: usize> ToDistributionValue for [u64; N] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_unsigned(self.to_vec())
     }
 }
 
 impl ToDistributionValue for Vec<f64> {
     fn try_to_value(self) -> MetricResult<MetricValue> {
-        Ok(MetricValue::PackedFloat(self))
+        packed_float(self)
+    }
+}
+
+impl ToDistributionValue for &[f64] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_float(self.to_vec())
+    }
+}
+
+impl<const N: usize> ToDistributionValue for [f64; N] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_float(self.to_vec())
+    }
+}
+
+impl ToDistributionValue for Duration {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        duration_value(&self, TimeUnit::Nanoseconds)
+    }
+
+    fn try_to_value_in(self, unit: TimeUnit) -> MetricResult<MetricValue> {
+        duration_value(&self, unit)
+    }
+}
+
+impl ToDistributionValue for Vec<Duration> {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_durations(&self, TimeUnit::Nanoseconds)
+    }
+
+    fn try_to_value_in(self, unit: TimeUnit) -> MetricResult<MetricValue> {
+        packed_durations(&self, unit)
+    }
+}
+
+impl ToDistributionValue for &[Duration] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_durations(self, TimeUnit::Nanoseconds)
+    }
+
+    fn try_to_value_in(self, unit: TimeUnit) -> MetricResult<MetricValue> {
+        packed_durations(self, unit)
+    }
+}
+
+impl<const N: usize> ToDistributionValue for [Duration; N] {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_durations(&self, TimeUnit::Nanoseconds)
+    }
+
+    fn try_to_value_in(self, unit: TimeUnit) -> MetricResult<MetricValue> {
+        packed_durations(&self, unit)
+    }
+}
+
+impl ToDistributionValue for ScaledDuration {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        scaled_value(&self)
     }
 }
 
@@ -256,73 +579,337 @@ impl ToSetValue for i64 {
     }
 }
 
-/// Trait for incrementing and decrementing counters.
-///
-/// Counters are simple values incremented or decremented by a client. The
-/// rates at which these events occur or average values will be determined
-/// by the server receiving them. Examples of counter uses include number
-/// of logins to a system or requests received.
+/// Conversion trait for valid values for custom metrics
 ///
-/// The following types are valid for counters:
-/// * `i64`
-///
-/// See the [Statsd spec](https://github.com/b/statsd_spec) for more
-/// information.
+/// This trait must be implemented for any types that are used as custom
+/// metric values (currently `i64`, `i32`, `i128`, `u64`, `u32`, `u128`, `f64`,
+/// and `Vec`s of `u64` or `f64`). This trait is internal to how values are
+/// formatted as part of metrics but is exposed publicly for documentation
+/// purposes.
 ///
-/// Note that tags are a [Datadog](https://docs.datadoghq.com/developers/dogstatsd/)
-/// extension to Statsd and may not be supported by your server.
-pub trait Counted<T>
-where
-    T: ToCounterValue,
-{
-    /// Increment or decrement the counter by the given amount
-    fn count(&self, key: &str, count: T) -> MetricResult<Counter> {
-        self.count_with_tags(key, count).try_send()
+/// Typical use of Cadence shouldn't require interacting with this trait.
+pub trait ToCustomValue {
+    fn try_to_value(self) -> MetricResult<MetricValue>;
+}
+
+impl ToCustomValue for i64 {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        Ok(MetricValue::Signed(self))
     }
+}
 
-    /// Increment or decrement the counter by the given amount and return
-    /// a `MetricBuilder` that can be used to add tags to the metric.
-    fn count_with_tags<'a>(&'a self, key: &'a str, count: T) -> MetricBuilder<'_, '_, Counter>;
+impl ToCustomValue for i32 {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        Ok(MetricValue::Signed(self.into()))
+    }
 }
 
-/// Trait for convenience methods for counters
-///
-/// This trait specifically implements increment and decrement convenience
-/// methods for counters with `i64` types.
-pub trait CountedExt: Counted<i64> {
-    /// Increment the counter by 1
-    fn incr(&self, key: &str) -> MetricResult<Counter> {
-        self.incr_with_tags(key).try_send()
+impl ToCustomValue for u64 {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        Ok(MetricValue::Unsigned(self))
     }
+}
 
-    /// Increment the counter by 1 and return a `MetricBuilder` that can
-    /// be used to add tags to the metric.
-    fn incr_with_tags<'a>(&'a self, key: &'a str) -> MetricBuilder<'_, '_, Counter> {
-        self.count_with_tags(key, 1)
+impl ToCustomValue for u32 {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        Ok(MetricValue::Unsigned(self.into()))
     }
+}
 
-    /// Decrement the counter by 1
-    fn decr(&self, key: &str) -> MetricResult<Counter> {
-        self.decr_with_tags(key).try_send()
+impl ToCustomValue for i128 {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        wide_signed(self)
     }
+}
 
-    /// Decrement the counter by 1 and return a `MetricBuilder` that can
-    /// be used to add tags to the metric.
-    fn decr_with_tags<'a>(&'a self, key: &'a str) -> MetricBuilder<'_, '_, Counter> {
-        self.count_with_tags(key, -1)
+impl ToCustomValue for u128 {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        wide_unsigned(self)
     }
 }
 
-/// Trait for recording timings in milliseconds.
+impl ToCustomValue for f64 {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        finite_float(self)
+    }
+}
+
+impl ToCustomValue for Vec<u64> {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_unsigned(self)
+    }
+}
+
+impl ToCustomValue for Vec<f64> {
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        packed_float(self)
+    }
+}
+
+/// Conversion trait for custom numeric types that are valid for any metric
 ///
-/// Timings are a positive number of milliseconds between a start and end
+/// Implementing this trait for a type, such as a newtype around a number,
+/// makes it usable as the value of counters, timers, gauges, meters,
+/// histograms, distributions, sets, and custom metrics without converting
+/// it at every call. It is implemented by Cadence for `NonZeroU64` and
+/// `NonZeroU32`.
+///
+/// Packed values (multiple values in a single metric) are only valid for
+/// timers, histograms, distributions, and custom metrics. Using them with
+/// any other type of metric results in an error.
+///
+/// # Example
+///
+/// ```
+/// use cadence::ext::{MetricValue, ToMetricValue};
+/// use cadence::prelude::*;
+/// use cadence::{MetricResult, NopMetricSink, StatsdClient};
+///
+/// struct Bytes(u64);
+///
+/// impl ToMetricValue for Bytes {
+///     fn try_to_metric_value(self) -> MetricResult<MetricValue> {
+///         Ok(MetricValue::Unsigned(self.0))
+///     }
+/// }
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// client.count("bytes.read", Bytes(4096)).unwrap();
+/// client.gauge("bytes.buffered", Bytes(512)).unwrap();
+/// client.histogram("bytes.response", Bytes(2048)).unwrap();
+/// ```
+pub trait ToMetricValue {
+    fn try_to_metric_value(self) -> MetricResult<MetricValue>;
+}
+
+// Other primitive integers, like `u128`, can't implement this trait since
+// another integer type being valid for a metric would break type inference
+// for integer literals such as `client.gauge("some.gauge", 1)`. Counters and
+// custom metrics accept `i32` so literals fall back to it, which is why they
+// can accept `i128` and `u128` directly.
+impl ToMetricValue for NonZeroU64 {
+    fn try_to_metric_value(self) -> MetricResult<MetricValue> {
+        Ok(MetricValue::Unsigned(self.get()))
+    }
+}
+
+impl ToMetricValue for NonZeroU32 {
+    fn try_to_metric_value(self) -> MetricResult<MetricValue> {
+        Ok(MetricValue::Unsigned(self.get().into()))
+    }
+}
+
+impl<T> ToCounterValue for T
+where
+    T: ToMetricValue,
+{
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        checked_value(self.try_to_metric_value()?, false)
+    }
+}
+
+impl<T> ToTimerValue for T
+where
+    T: ToMetricValue,
+{
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        checked_value(self.try_to_metric_value()?, true)
+    }
+}
+
+impl<T> ToGaugeValue for T
+where
+    T: ToMetricValue,
+{
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        checked_value(self.try_to_metric_value()?, false)
+    }
+}
+
+impl<T> ToMeterValue for T
+where
+    T: ToMetricValue,
+{
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        checked_value(self.try_to_metric_value()?, false)
+    }
+}
+
+impl<T> ToHistogramValue for T
+where
+    T: ToMetricValue,
+{
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        checked_value(self.try_to_metric_value()?, true)
+    }
+}
+
+impl<T> ToDistributionValue for T
+where
+    T: ToMetricValue,
+{
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        checked_value(self.try_to_metric_value()?, true)
+    }
+}
+
+impl<T> ToSetValue for T
+where
+    T: ToMetricValue,
+{
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        checked_value(self.try_to_metric_value()?, false)
+    }
+}
+
+impl<T> ToCustomValue for T
+where
+    T: ToMetricValue,
+{
+    fn try_to_value(self) -> MetricResult<MetricValue> {
+        checked_value(self.try_to_metric_value()?, true)
+    }
+}
+
+// Values from types outside of Cadence get the same checks as the built-in
+// types: only some metrics accept packed values and those need at least one.
+fn checked_value(value: MetricValue, packed: bool) -> MetricResult<MetricValue> {
+    let empty = match &value {
+        MetricValue::PackedSigned(v) => v.is_empty(),
+        MetricValue::PackedUnsigned(v) => v.is_empty(),
+        MetricValue::PackedFloat(v) => v.is_empty(),
+        _ => return Ok(value),
+    };
+
+    if !packed {
+        Err(MetricError::from((
+            ErrorKind::InvalidInput,
+            "metric does not support packed values",
+        )))
+    } else if empty {
+        Err(MetricError::from((ErrorKind::InvalidInput, "no values to pack")))
+    } else {
+        Ok(value)
+    }
+}
+
+// Custom types end up between the `|` after the value and any tags, sample
+// rate, or other fields so they can't contain anything that would change how
+// the rest of the metric is parsed.
+fn valid_metric_type(metric_type: &str) -> bool {
+    !metric_type.is_empty() && metric_type.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// Add the rate a metric was sampled at right after its type, where it would
+// be if the metric had been formatted by Cadence. Metrics that already have a
+// rate were sampled before this one so the rates are multiplied instead.
+fn with_sampling_rate(metric: &str, rate: f64) -> String {
+    let mut start = 0;
+    for (i, field) in metric.split('|').enumerate() {
+        // The name and value are first, then the type, then optional fields.
+        let existing = if i > 1 {
+            field.strip_prefix('@').and_then(|r| r.parse::<f64>().ok())
+        } else {
+            None
+        };
+
+        if let Some(existing) = existing {
+            let end = start + field.len();
+            return format!("{}@{}{}", &metric[..start], existing * rate, &metric[end..]);
+        }
+
+        start += field.len() + 1;
+    }
+
+    let end = metric
+        .find('|')
+        .map(|i| metric[i + 1..].find('|').map(|j| i + 1 + j).unwrap_or(metric.len()))
+        .unwrap_or(metric.len());
+
+    format!("{}|@{}{}", &metric[..end], rate, &metric[end..])
+}
+
+/// Trait for incrementing and decrementing counters.
+///
+/// Counters are simple values incremented or decremented by a client. The
+/// rates at which these events occur or average values will be determined
+/// by the server receiving them. Examples of counter uses include number
+/// of logins to a system or requests received.
+///
+/// The following types are valid for counters:
+/// * `i64`
+/// * `i32`
+/// * `i128`
+/// * `u64`
+/// * `u32`
+/// * `u128`
+/// * `f64`
+///
+/// Values of `i128` and `u128` that don't fit in an `i64` or `u64` are
+/// rejected with an error instead of being truncated.
+///
+/// Fractional counts (`f64`) are supported by some servers such as DogStatsD
+/// and statsite but may be rejected by others.
+///
+/// See the [Statsd spec](https://github.com/b/statsd_spec) for more
+/// information.
+///
+/// Note that tags are a [Datadog](https://docs.datadoghq.com/developers/dogstatsd/)
+/// extension to Statsd and may not be supported by your server.
+pub trait Counted<T>
+where
+    T: ToCounterValue,
+{
+    /// Increment or decrement the counter by the given amount
+    fn count(&self, key: &str, count: T) -> MetricResult<Counter> {
+        self.count_with_tags(key, count).try_send()
+    }
+
+    /// Increment or decrement the counter by the given amount and return
+    /// a `MetricBuilder` that can be used to add tags to the metric.
+    fn count_with_tags<'a>(&'a self, key: &'a str, count: T) -> MetricBuilder<'a, 'a, Counter>;
+}
+
+/// Trait for convenience methods for counters
+///
+/// This trait specifically implements increment and decrement convenience
+/// methods for counters with `i64` types.
+pub trait CountedExt: Counted<i64> {
+    /// Increment the counter by 1
+    fn incr(&self, key: &str) -> MetricResult<Counter> {
+        self.incr_with_tags(key).try_send()
+    }
+
+    /// Increment the counter by 1 and return a `MetricBuilder` that can
+    /// be used to add tags to the metric.
+    fn incr_with_tags<'a>(&'a self, key: &'a str) -> MetricBuilder<'a, 'a, Counter> {
+        self.count_with_tags(key, 1)
+    }
+
+    /// Decrement the counter by 1
+    fn decr(&self, key: &str) -> MetricResult<Counter> {
+        self.decr_with_tags(key).try_send()
+    }
+
+    /// Decrement the counter by 1 and return a `MetricBuilder` that can
+    /// be used to add tags to the metric.
+    fn decr_with_tags<'a>(&'a self, key: &'a str) -> MetricBuilder<'a, 'a, Counter> {
+        self.count_with_tags(key, -1)
+    }
+}
+
+/// Trait for recording timings in milliseconds.
+///
+/// Timings are a positive number of milliseconds between a start and end
 /// time. Examples include time taken to render a web page or time taken
 /// for a database call to return. `Duration` values are converted to
-/// milliseconds before being recorded.
+/// milliseconds before being recorded, unless a different unit is set
+/// using `StatsdClientBuilder::with_timer_unit()` or `ScaledDuration`.
 ///
 /// The following types are valid for timers:
 /// * `u64`
 /// * `Duration`
+/// * `ScaledDuration`
 ///
 /// See the [Statsd spec](https://github.com/b/statsd_spec) for more
 /// information.
@@ -340,7 +927,7 @@ where
 
     /// Record a timing in milliseconds with the given key and return a
     /// `MetricBuilder` that can be used to add tags to the metric.
-    fn time_with_tags<'a>(&'a self, key: &'a str, time: T) -> MetricBuilder<'_, '_, Timer>;
+    fn time_with_tags<'a>(&'a self, key: &'a str, time: T) -> MetricBuilder<'a, 'a, Timer>;
 }
 
 /// Trait for recording gauge values.
@@ -369,7 +956,7 @@ where
 
     /// Record a gauge value with the given key and return a `MetricBuilder`
     /// that can be used to add tags to the metric.
-    fn gauge_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Gauge>;
+    fn gauge_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Gauge>;
 }
 
 /// Trait for recording meter values.
@@ -382,6 +969,7 @@ where
 ///
 /// The following types are valid for meters:
 /// * `u64`
+/// * `f64`
 ///
 /// See the [Statsd spec](https://github.com/b/statsd_spec) for more
 /// information.
@@ -399,7 +987,7 @@ where
 
     /// Record a meter value with the given key and return a `MetricBuilder`
     /// that can be used to add tags to the metric.
-    fn meter_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Meter>;
+    fn meter_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Meter>;
 }
 
 /// Trait for recording histogram values.
@@ -409,12 +997,14 @@ where
 /// timings, amount of some resource consumed, size of HTTP responses in
 /// some application, etc. Histograms can be thought of as a more general
 /// form of timers. `Duration` values are converted to nanoseconds before
-/// being emitted.
+/// being emitted, unless a different unit is set using
+/// `StatsdClientBuilder::with_histogram_unit()` or `ScaledDuration`.
 ///
 /// The following types are valid for histograms:
 /// * `u64`
 /// * `f64`
 /// * `Duration`
+/// * `ScaledDuration`
 ///
 /// See the [Statsd spec](https://github.com/b/statsd_spec) for more
 /// information.
@@ -433,18 +1023,22 @@ where
 
     /// Record a single histogram value with the given key and return a
     /// `MetricBuilder` that can be used to add tags to the metric.
-    fn histogram_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Histogram>;
+    fn histogram_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Histogram>;
 }
 
 /// Trait for recording distribution values.
 ///
 /// Similar to histograms, but applies globally. A distribution can be used to
 /// instrument logical objects, like services, independently from the underlying
-/// hosts.
+/// hosts. `Duration` values are converted to nanoseconds before being emitted,
+/// unless a different unit is set using `StatsdClientBuilder::with_distribution_unit()`
+/// or `ScaledDuration`.
 ///
 /// The following types are valid for distributions:
 /// * `u64`
 /// * `f64`
+/// * `Duration`
+/// * `ScaledDuration`
 ///
 /// See the [Datadog docs](https://docs.datadoghq.com/developers/metrics/types/?tab=distribution#definition)
 /// for more information.
@@ -463,7 +1057,7 @@ where
 
     /// Record a single distribution value with the given key and return a
     /// `MetricBuilder` that can be used to add tags to the metric.
-    fn distribution_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Distribution>;
+    fn distribution_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Distribution>;
 }
 
 /// Trait for recording set values.
@@ -487,7 +1081,57 @@ where
 
     /// Record a single set value with the given key and return a
     /// `MetricBuilder` that can be used to add tags to the metric.
-    fn set_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Set>;
+    fn set_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Set>;
+}
+
+/// Trait for emitting Datadog events.
+///
+/// Events are records of notable things that happen, such as deploys or
+/// errors, and are displayed alongside metrics in the Datadog UI. Events
+/// have a title and text along with optional attributes such as priority,
+/// alert type, and aggregation key that can be set via the returned
+/// `EventBuilder`.
+///
+/// Note that the metric prefix of the client is not applied to events.
+///
+/// See the [Datadog docs](https://docs.datadoghq.com/developers/dogstatsd/datagram_shell/?tab=events)
+/// for more information.
+///
+/// Note that events are a [Datadog](https://docs.datadoghq.com/developers/dogstatsd/)
+/// extension to Statsd and may not be supported by your server.
+pub trait Evented {
+    /// Emit an event with the given title and text
+    fn event(&self, title: &str, text: &str) -> MetricResult<Event> {
+        self.event_with_tags(title, text).try_send()
+    }
+
+    /// Emit an event with the given title and text and return an `EventBuilder`
+    /// that can be used to add tags and other attributes to the event.
+    fn event_with_tags<'a>(&'a self, title: &'a str, text: &'a str) -> EventBuilder<'a, 'a>;
+}
+
+/// Trait for reporting the status of services via Datadog service checks.
+///
+/// Service checks report whether a service is `Ok`, `Warning`, `Critical`,
+/// or `Unknown` along with an optional message describing its state. The
+/// name of the service check is prefixed with the metric prefix of the
+/// client.
+///
+/// See the [Datadog docs](https://docs.datadoghq.com/developers/dogstatsd/datagram_shell/?tab=servicechecks)
+/// for more information.
+///
+/// Note that service checks are a [Datadog](https://docs.datadoghq.com/developers/dogstatsd/)
+/// extension to Statsd and may not be supported by your server.
+pub trait ServiceChecked {
+    /// Report the status of the service with the given name
+    fn service_check(&self, name: &str, status: ServiceCheckStatus) -> MetricResult<ServiceCheck> {
+        self.service_check_with_tags(name, status).try_send()
+    }
+
+    /// Report the status of the service with the given name and return a
+    /// `ServiceCheckBuilder` that can be used to add tags and other attributes
+    /// to the service check.
+    fn service_check_with_tags<'a>(&'a self, name: &'a str, status: ServiceCheckStatus) -> ServiceCheckBuilder<'a, 'a>;
 }
 
 /// Trait that encompasses all other traits for sending metrics.
@@ -642,8 +1286,21 @@ pub trait MetricBackend: Sealed {
     /// use this method. This is only useful if you are extending Cadence with a
     /// custom metric type or something similar.
     fn consume_error(&self, err: MetricError);
+
+    /// Consume an error from attempting to send the given metric.
+    ///
+    /// This method is invoked instead of `.consume_error()` when the metric
+    /// was formatted but could not be sent, so that the error handler can tell
+    /// which metric was dropped. By default, the metric is ignored and the error
+    /// is passed to `.consume_error()`.
+    fn consume_metric_error(&self, metric: &str, err: MetricError) {
+        let _ = metric;
+        self.consume_error(err);
+    }
 }
 
+type MetricErrorHandler = Box<dyn Fn(&str, MetricError) + Sync + Send + RefUnwindSafe>;
+
 /// Builder for creating and customizing `StatsdClient` instances.
 ///
 /// Instances of the builder should be created by calling the `::builder()`
@@ -663,7 +1320,9 @@ pub trait MetricBackend: Sealed {
 ///     .with_error_handler(my_error_handler)
 ///     .with_tag("environment", "production")
 ///     .with_tag_value("rust")
-///     .build();
+///     .with_sample_rate(0.5)
+///     .try_build()
+///     .unwrap();
 ///
 /// client.count("something", 123);
 /// client.count_with_tags("some.counter", 42)
@@ -674,8 +1333,22 @@ pub struct StatsdClientBuilder {
     prefix: String,
     sink: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
     errors: Box<dyn Fn(MetricError) + Sync + Send + RefUnwindSafe>,
+    metric_errors: Option<MetricErrorHandler>,
     tags: Vec<(Option<String>, String)>,
     container_id: Option<String>,
+    tag_format: TagFormat,
+    sanitize_policy: SanitizePolicy,
+    non_finite_policy: NonFinitePolicy,
+    sample_rate: Option<f64>,
+    type_sample_rates: Vec<(String, f64)>,
+    rng: Box<dyn Rng + Sync + Send + RefUnwindSafe>,
+    clock: Option<Box<dyn Fn() -> u64 + Sync + Send + RefUnwindSafe>>,
+    timer_clock: Box<dyn Clock + Sync + Send + RefUnwindSafe>,
+    timer_unit: TimeUnit,
+    histogram_unit: TimeUnit,
+    distribution_unit: TimeUnit,
+    gauge_interval: Duration,
+    disabled: bool,
 }
 
 impl StatsdClientBuilder {
@@ -691,8 +1364,22 @@ impl StatsdClientBuilder {
 
             // optional with defaults
             errors: Box::new(nop_error_handler),
+            metric_errors: None,
             tags: Vec::new(),
             container_id: None,
+            tag_format: TagFormat::default(),
+            sanitize_policy: SanitizePolicy::default(),
+            non_finite_policy: NonFinitePolicy::default(),
+            sample_rate: None,
+            type_sample_rates: Vec::new(),
+            rng: Box::new(ThreadLocalRng),
+            clock: None,
+            timer_clock: Box::new(MonotonicClock::new()),
+            timer_unit: TimeUnit::Milliseconds,
+            histogram_unit: TimeUnit::Nanoseconds,
+            distribution_unit: TimeUnit::Nanoseconds,
+            gauge_interval: DEFAULT_GAUGE_INTERVAL,
+            disabled: false,
         }
     }
 
@@ -713,8 +1400,45 @@ impl StatsdClientBuilder {
         self
     }
 
+    /// Set an error handler that is given the metric that couldn't be sent
+    /// along with the error, for metrics sent via `MetricBuilder::send()`
+    ///
+    /// This allows counting or logging which metrics are being dropped. When
+    /// set, this handler is used instead of the handler set by
+    /// `with_error_handler()` for any metric that could be formatted but not
+    /// sent, such as because of an I/O error. Errors that happen before a
+    /// metric is formatted, such as invalid values, don't have a metric to
+    /// include and are still passed to the handler set by `with_error_handler()`.
+    ///
+    /// The error handler should consume the error without panicking.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::builder("prefix", NopMetricSink)
+    ///     .with_metric_error_handler(|metric, err| {
+    ///         eprintln!("Dropped metric {}: {}", metric, err);
+    ///     })
+    ///     .build();
+    ///
+    /// client.count_with_tags("some.counter", 1).send();
+    /// ```
+    pub fn with_metric_error_handler<F>(mut self, errors: F) -> Self
+    where
+        F: Fn(&str, MetricError) + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.metric_errors = Some(Box::new(errors));
+        self
+    }
+
     /// Add a default tag with key and value to every metric published by the
     /// built [StatsdClient].
+    ///
+    /// A tag with the same key added to an individual metric will replace
+    /// the default tag for that metric.
     pub fn with_tag<K, V>(mut self, key: K, value: V) -> Self
     where
         K: ToString,
@@ -724,6 +1448,35 @@ impl StatsdClientBuilder {
         self
     }
 
+    /// Add each of the given key-value tags as default tags to every metric
+    /// published by the built [StatsdClient].
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::collections::HashMap;
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let mut tags = HashMap::new();
+    /// tags.insert("region", "us-east-1");
+    ///
+    /// let client = StatsdClient::builder("my.prefix", NopMetricSink)
+    ///     .with_tags(&tags)
+    ///     .with_tags([("env", "prod"), ("service", "api")])
+    ///     .build();
+    /// ```
+    pub fn with_tags<I, K, V>(mut self, tags: I) -> Self
+    where
+        I: IntoIterator<Item = (K, V)>,
+        K: ToString,
+        V: ToString,
+    {
+        for (key, value) in tags {
+            self = self.with_tag(key, value);
+        }
+        self
+    }
+
     /// Add a default tag with only a value to every metric published by the built
     /// [StatsdClient].
     pub fn with_tag_value<K>(mut self, value: K) -> Self
@@ -734,6 +1487,91 @@ impl StatsdClientBuilder {
         self
     }
 
+    /// Set the format used for the tags of every metric published by the built
+    /// [StatsdClient], Datadog style tags by default.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{SpyMetricSink, StatsdClient, TagFormat};
+    ///
+    /// let (rx, sink) = SpyMetricSink::new();
+    /// let client = StatsdClient::builder("prefix", sink)
+    ///     .with_tag_format(TagFormat::Telegraf)
+    ///     .with_tag("region", "us-east-1")
+    ///     .build();
+    ///
+    /// client.count_with_tags("some.counter", 1).with_tag("host", "web01").send();
+    /// assert_eq!(
+    ///     b"prefix.some.counter,region=us-east-1,host=web01:1|c".to_vec(),
+    ///     rx.try_recv().unwrap()
+    /// );
+    /// ```
+    pub fn with_tag_format(mut self, tag_format: TagFormat) -> Self {
+        self.tag_format = tag_format;
+        self
+    }
+
+    /// Set how keys and tags of metrics published by the built [StatsdClient]
+    /// are validated, such as those that contain characters used to separate
+    /// parts of a metric, `SanitizePolicy::Unchecked` by default.
+    ///
+    /// See `SanitizePolicy` for more information.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{ErrorKind, Metric, NopMetricSink, SanitizePolicy, StatsdClient};
+    ///
+    /// let client = StatsdClient::builder("prefix", NopMetricSink)
+    ///     .with_sanitize_policy(SanitizePolicy::Lenient)
+    ///     .build();
+    ///
+    /// let metric = client.count("user.bad|key\n", 1).unwrap();
+    /// assert_eq!("prefix.user.bad_key_:1|c", metric.as_metric_str());
+    ///
+    /// let client = StatsdClient::builder("prefix", NopMetricSink)
+    ///     .with_sanitize_policy(SanitizePolicy::Strict)
+    ///     .build();
+    ///
+    /// let err = client.count("user.bad|key\n", 1).unwrap_err();
+    /// assert_eq!(ErrorKind::Sanitization, err.kind());
+    /// ```
+    pub fn with_sanitize_policy(mut self, policy: SanitizePolicy) -> Self {
+        self.sanitize_policy = policy;
+        self
+    }
+
+    /// Set how gauge, histogram, and distribution values published by the
+    /// built [StatsdClient] that are `NaN` or infinite are handled,
+    /// `NonFinitePolicy::Error` by default.
+    ///
+    /// See `NonFinitePolicy` for more information.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{ErrorKind, Metric, NonFinitePolicy, NopMetricSink, StatsdClient};
+    ///
+    /// let client = StatsdClient::from_sink("prefix", NopMetricSink);
+    /// let err = client.gauge("queue.ratio", f64::NAN).unwrap_err();
+    /// assert_eq!(ErrorKind::InvalidInput, err.kind());
+    ///
+    /// let client = StatsdClient::builder("prefix", NopMetricSink)
+    ///     .with_non_finite_policy(NonFinitePolicy::Clamp)
+    ///     .build();
+    ///
+    /// let metric = client.gauge("queue.ratio", f64::INFINITY).unwrap();
+    /// assert_eq!(format!("prefix.queue.ratio:{}|g", f64::MAX), metric.as_metric_str());
+    /// ```
+    pub fn with_non_finite_policy(mut self, policy: NonFinitePolicy) -> Self {
+        self.non_finite_policy = policy;
+        self
+    }
+
     /// Add a default container ID to every metric published by the built
     /// [StatsdClient].
     pub fn with_container_id<K>(mut self, container_id: K) -> Self
@@ -744,14 +1582,248 @@ impl StatsdClientBuilder {
         self
     }
 
-    /// Construct a new `StatsdClient` instance based on current settings.
-    pub fn build(self) -> StatsdClient {
-        StatsdClient::from_builder(self)
+    /// Set a default client-side sample rate for every metric published by
+    /// the built [StatsdClient].
+    ///
+    /// See `MetricBuilder::with_sample_rate()` for more information. A sample
+    /// rate set on an individual metric replaces the default. Events and service
+    /// checks are never sampled.
+    pub fn with_sample_rate(mut self, rate: f64) -> Self {
+        self.sample_rate = Some(rate);
+        self
     }
 
-    fn formatted_prefix(prefix: &str) -> String {
-        if prefix.is_empty() {
-            String::new()
+    /// Set a default client-side sample rate for metrics of the given type
+    /// published by the built [StatsdClient].
+    ///
+    /// This replaces the default sample rate set by `with_sample_rate()` for
+    /// metrics of the given type, so that sampling policy can be set in one
+    /// place. Setting a rate for the same type again replaces the previous one.
+    /// A sample rate set on an individual metric replaces both.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{MetricType, NopMetricSink, StatsdClient};
+    ///
+    /// let client = StatsdClient::builder("my.prefix", NopMetricSink)
+    ///     .with_type_sample_rate(MetricType::Timer, 0.1)
+    ///     .with_type_sample_rate(MetricType::Counter, 1.0)
+    ///     .build();
+    /// ```
+    pub fn with_type_sample_rate(mut self, metric_type: MetricType<'_>, rate: f64) -> Self {
+        let type_ = metric_type.as_str();
+        self.type_sample_rates.retain(|(t, _)| t != type_);
+        self.type_sample_rates.push((type_.to_owned(), rate));
+        self
+    }
+
+    /// Set the random number generator used to decide which sampled metrics
+    /// are sent by the built [StatsdClient].
+    ///
+    /// By default, `ThreadLocalRng` is used. This is mostly useful for tests
+    /// of code that sends sampled metrics, using `SeededRng` or another `Rng`
+    /// so that the same metrics are sent every time.
+    pub fn with_rng<R>(mut self, rng: R) -> Self
+    where
+        R: Rng + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.rng = Box::new(rng);
+        self
+    }
+
+    /// Set a clock used to add a UNIX timestamp in seconds to every metric
+    /// published by the built [StatsdClient].
+    ///
+    /// See `MetricBuilder::with_timestamp()` for more information. A timestamp
+    /// set on an individual metric replaces the one from the clock.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::{SystemTime, UNIX_EPOCH};
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::builder("prefix", NopMetricSink)
+    ///     .with_clock(|| SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs())
+    ///     .build();
+    /// ```
+    pub fn with_clock<F>(mut self, clock: F) -> Self
+    where
+        F: Fn() -> u64 + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.clock = Some(Box::new(clock));
+        self
+    }
+
+    /// Set the clock used to measure timers recorded by the timing helpers of
+    /// the built [StatsdClient], such as `StatsdClient::time_block()`.
+    ///
+    /// By default, `MonotonicClock` is used. This is mostly useful for tests
+    /// that need exact timer values, using `ManualClock` or another `Clock`.
+    pub fn with_timer_clock<C>(mut self, clock: C) -> Self
+    where
+        C: Clock + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.timer_clock = Box::new(clock);
+        self
+    }
+
+    /// Set the unit that `Duration` values are converted to for timers
+    /// emitted by the built [StatsdClient], milliseconds by default.
+    ///
+    /// This also applies to timers recorded by the timing helpers of the
+    /// client. Individual timers can use a different unit by passing a
+    /// `ScaledDuration` as the value. Note that the type of the timer is
+    /// still `ms`, regardless of the unit.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use cadence::prelude::*;
+    /// use cadence::{SpyMetricSink, StatsdClient, TimeUnit};
+    ///
+    /// let (rx, sink) = SpyMetricSink::new();
+    /// let client = StatsdClient::builder("prefix", sink)
+    ///     .with_timer_unit(TimeUnit::Microseconds)
+    ///     .build();
+    ///
+    /// client.time("some.timer", Duration::from_micros(1250)).unwrap();
+    /// assert_eq!(b"prefix.some.timer:1250|ms".to_vec(), rx.try_recv().unwrap());
+    /// ```
+    pub fn with_timer_unit(mut self, unit: TimeUnit) -> Self {
+        self.timer_unit = unit;
+        self
+    }
+
+    /// Set the unit that `Duration` values are converted to for histograms
+    /// emitted by the built [StatsdClient], nanoseconds by default.
+    ///
+    /// Individual histograms can use a different unit by passing a
+    /// `ScaledDuration` as the value.
+    pub fn with_histogram_unit(mut self, unit: TimeUnit) -> Self {
+        self.histogram_unit = unit;
+        self
+    }
+
+    /// Set the unit that `Duration` values are converted to for distributions
+    /// emitted by the built [StatsdClient], nanoseconds by default.
+    ///
+    /// Individual distributions can use a different unit by passing a
+    /// `ScaledDuration` as the value.
+    pub fn with_distribution_unit(mut self, unit: TimeUnit) -> Self {
+        self.distribution_unit = unit;
+        self
+    }
+
+    /// Set how often the values of gauges registered with the built
+    /// [StatsdClient] are sent, every 10 seconds by default.
+    ///
+    /// See `StatsdClient::register_gauge()` for more information.
+    pub fn with_gauge_interval(mut self, interval: Duration) -> Self {
+        self.gauge_interval = interval;
+        self
+    }
+
+    /// Disable the built [StatsdClient] so that it never sends metrics, for
+    /// example in tests, benchmarks, or when metrics are turned off by the
+    /// configuration of an application.
+    ///
+    /// Metrics sent with `MetricBuilder::send()` by a disabled client are
+    /// never formatted and their tags are never computed. Methods that return
+    /// a metric, such as `Counted::count()` or `MetricBuilder::try_send()`,
+    /// still format and validate it but don't pass it to the sink. Flushing or
+    /// checking a disabled client does nothing, and it never starts threads to
+    /// send registered gauges.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{Metric, StatsdClient, SpyMetricSink};
+    ///
+    /// let (rx, sink) = SpyMetricSink::new();
+    /// let client = StatsdClient::builder("my.prefix", sink)
+    ///     .with_disabled(true)
+    ///     .build();
+    ///
+    /// client.incr_with_tags("some.counter").with_tag("region", "us-west-2").send();
+    /// let counter = client.incr("some.counter").unwrap();
+    ///
+    /// assert_eq!("my.prefix.some.counter:1|c", counter.as_metric_str());
+    /// assert!(rx.try_recv().is_err());
+    /// ```
+    pub fn with_disabled(mut self, disabled: bool) -> Self {
+        self.disabled = disabled;
+        self
+    }
+
+    /// Construct a new `StatsdClient` instance based on current settings.
+    ///
+    /// Settings are not validated. An invalid default sample rate causes an
+    /// error for every metric sent. Use `try_build()` to validate settings.
+    pub fn build(self) -> StatsdClient {
+        StatsdClient::from_builder(self)
+    }
+
+    /// Construct a new `StatsdClient` instance based on current settings
+    /// after making sure they are valid.
+    ///
+    /// # Failures
+    ///
+    /// This method will fail if:
+    ///
+    /// * The default sample rate is not between 0 and 1.
+    /// * The prefix contains characters used to separate parts of a metric.
+    /// * A default tag or the container ID contains characters used to separate
+    ///   tags or parts of a metric.
+    pub fn try_build(self) -> MetricResult<StatsdClient> {
+        self.validate()?;
+        Ok(self.build())
+    }
+
+    fn validate(&self) -> MetricResult<()> {
+        fn invalid(desc: &'static str) -> MetricResult<()> {
+            Err(MetricError::from((ErrorKind::InvalidInput, desc)))
+        }
+
+        let rates = self
+            .sample_rate
+            .iter()
+            .chain(self.type_sample_rates.iter().map(|(_, r)| r));
+        for rate in rates {
+            if !sample::is_valid_rate(*rate) {
+                return invalid("sample rate must be between 0 and 1");
+            }
+        }
+
+        if self.prefix.contains(self.tag_format.name_reserved()) {
+            return invalid("prefix must not contain characters used to separate parts of a metric");
+        }
+
+        for (key, value) in self.tags.iter() {
+            let bad_key = key
+                .as_ref()
+                .map(|k| k.contains(self.tag_format.tag_key_reserved()))
+                .unwrap_or(false);
+            if bad_key || value.contains(self.tag_format.tag_value_reserved()) {
+                return invalid("default tags must not contain characters used to separate parts of a metric");
+            }
+        }
+
+        if let Some(id) = &self.container_id {
+            if id.contains(&['|', ',', '\n'][..]) {
+                return invalid("container ID must not contain '|', ',', or newlines");
+            }
+        }
+
+        Ok(())
+    }
+
+    fn formatted_prefix(prefix: &str) -> String {
+        if prefix.is_empty() {
+            String::new()
         } else {
             format!("{}.", prefix.trim_end_matches('.'))
         }
@@ -773,6 +1845,8 @@ impl StatsdClientBuilder {
 /// * `Distributed` for emitting distribution values.
 /// * `Setted` for emitting set values.
 /// * `MetricClient` for a combination of all of the above.
+/// * `Evented` for emitting Datadog events.
+/// * `ServiceChecked` for emitting Datadog service checks.
 ///
 /// For more information about the uses for each type of metric, see the
 /// documentation for each mentioned trait.
@@ -842,10 +1916,41 @@ impl StatsdClientBuilder {
 /// ```
 pub struct StatsdClient {
     prefix: String,
+    shared: Arc<SharedState>,
+}
+
+// Everything about a client except its prefix, shared with scoped clients
+struct SharedState {
     sink: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
     errors: Box<dyn Fn(MetricError) + Sync + Send + RefUnwindSafe>,
+    metric_errors: Option<MetricErrorHandler>,
     tags: Vec<(Option<String>, String)>,
     container_id: Option<String>,
+    tag_format: TagFormat,
+    sanitize_policy: SanitizePolicy,
+    non_finite_policy: NonFinitePolicy,
+    sample_rate: Option<f64>,
+    type_sample_rates: Vec<(String, f64)>,
+    rng: Box<dyn Rng + Sync + Send + RefUnwindSafe>,
+    clock: Option<Box<dyn Fn() -> u64 + Sync + Send + RefUnwindSafe>>,
+    timer_clock: Box<dyn Clock + Sync + Send + RefUnwindSafe>,
+    timer_unit: TimeUnit,
+    histogram_unit: TimeUnit,
+    distribution_unit: TimeUnit,
+    gauges: Arc<GaugeRegistry>,
+    sink_errors: ErrorTracker,
+    disabled: bool,
+}
+
+impl SharedState {
+    // Default sample rate for metrics with the given type suffix, if any
+    fn sample_rate(&self, metric_type: &str) -> Option<f64> {
+        self.type_sample_rates
+            .iter()
+            .find(|(t, _)| t == metric_type)
+            .map(|(_, rate)| *rate)
+            .or(self.sample_rate)
+    }
 }
 
 impl StatsdClient {
@@ -976,189 +2081,1309 @@ impl StatsdClient {
     /// client.flush();
     /// ```
     pub fn flush(&self) -> MetricResult<()> {
-        Ok(self.sink.flush()?)
+        if self.is_disabled() {
+            return Ok(());
+        }
+
+        self.sink_result(self.shared.sink.flush())
     }
 
-    // Create a new StatsdClient by consuming the builder
-    fn from_builder(builder: StatsdClientBuilder) -> Self {
-        StatsdClient {
-            prefix: builder.prefix,
-            sink: builder.sink,
-            errors: builder.errors,
-            tags: builder.tags,
-            container_id: builder.container_id,
+    /// Verify that the sink of this client is able to send metrics, returning
+    /// an error if it isn't, for example because the Statsd server can't be
+    /// reached.
+    ///
+    /// This is meant to be called when an application starts so that it can
+    /// fail fast if metrics have been misconfigured. See `MetricSink::check`
+    /// for what is checked by each sink. Errors returned by this method are
+    /// not included in the stats returned by `.error_stats()`.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cadence::{StatsdClient, TcpMetricSink, DEFAULT_PORT};
+    ///
+    /// let sink = TcpMetricSink::from(("metrics.example.com", DEFAULT_PORT)).unwrap();
+    /// let client = StatsdClient::from_sink("my.prefix", sink);
+    ///
+    /// if let Err(e) = client.check() {
+    ///     eprintln!("unable to send metrics: {}", e);
+    ///     std::process::exit(1);
+    /// }
+    /// ```
+    pub fn check(&self) -> MetricResult<()> {
+        if self.is_disabled() {
+            return Ok(());
         }
+
+        Ok(self.shared.sink.check()?)
     }
 
-    fn tags(&self) -> impl IntoIterator<Item = (Option<&str>, &str)> {
-        self.tags.iter().map(|(k, v)| (k.as_deref(), v.as_str()))
+    /// Return the number of errors returned by the sink of this client by
+    /// kind, along with the most recent one.
+    ///
+    /// Errors are counted whether they are returned to the caller or passed to
+    /// the error handler of the client. Scoped clients created using
+    /// `.scoped()` share these stats with the client they were created from.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{ErrorKind, StatsdClient, SpyMetricSink};
+    ///
+    /// let (_rx, sink) = SpyMetricSink::with_capacity(1);
+    /// let client = StatsdClient::from_sink("my.prefix", sink);
+    ///
+    /// client.count("some.counter", 1).unwrap();
+    /// client.count("some.counter", 1).unwrap_err();
+    ///
+    /// let stats = client.error_stats();
+    /// assert_eq!(1, stats.queue_full);
+    /// assert_eq!(ErrorKind::QueueFull, stats.last_error.unwrap().kind);
+    /// ```
+    pub fn error_stats(&self) -> ErrorStats {
+        self.shared.sink_errors.stats()
     }
-}
 
-impl Sealed for StatsdClient {}
+    /// Create a new client that appends the given name to the prefix of this
+    /// client and shares everything else with it.
+    ///
+    /// The new client uses the same sink, error handlers, default tags, and
+    /// other settings as this client. Creating it is cheap since none of them
+    /// are copied. This allows parts of an application to use their own
+    /// prefix without building the full key of every metric they emit.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::prelude::*;
+    /// use cadence::{Metric, StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("my.app", NopMetricSink);
+    /// let db = client.scoped("db");
+    ///
+    /// let res = db.count("queries", 1);
+    /// assert_eq!("my.app.db.queries:1|c", res.unwrap().as_metric_str());
+    /// ```
+    pub fn scoped(&self, name: &str) -> StatsdClient {
+        let name = name.trim_matches('.');
+        let prefix = if name.is_empty() {
+            self.prefix.clone()
+        } else {
+            format!("{}{}.", self.prefix, name)
+        };
 
-impl MetricBackend for StatsdClient {
-    fn send_metric<M>(&self, metric: &M) -> MetricResult<()>
-    where
-        M: Metric,
-    {
-        let metric_string = metric.as_metric_str();
-        self.sink.emit(metric_string)?;
-        Ok(())
+        StatsdClient {
+            prefix,
+            shared: self.shared.clone(),
+        }
     }
 
-    fn consume_error(&self, err: MetricError) {
-        (self.errors)(err);
+    /// Create a handle for sending counters with the given key.
+    ///
+    /// The prefix, key, default tags, and other settings of this client are
+    /// formatted once when the handle is created, so sending a value with the
+    /// handle only formats the value. This is useful for metrics sent in hot
+    /// paths of an application. See `MetricHandle` for more information.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("my.app", NopMetricSink);
+    /// let requests = client.counter_handle("requests");
+    ///
+    /// requests.incr().unwrap();
+    /// ```
+    pub fn counter_handle(&self, key: &str) -> MetricHandle<Counter> {
+        MetricHandle::new(self.scoped(""), key, |p, k, v| MetricFormatter::counter(p, k, v))
     }
-}
 
-impl fmt::Debug for StatsdClient {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(
-            f,
-            "StatsdClient {{ prefix: {:?}, sink: ..., errors: ..., tags: {:?} }}",
-            self.prefix, self.tags,
-        )
+    /// Create a handle for sending timers with the given key.
+    ///
+    /// See `StatsdClient::counter_handle()` for more information.
+    pub fn timer_handle(&self, key: &str) -> MetricHandle<Timer> {
+        MetricHandle::new(self.scoped(""), key, |p, k, v| MetricFormatter::timer(p, k, v))
     }
-}
 
-impl<T> Counted<T> for StatsdClient
-where
-    T: ToCounterValue,
-{
-    fn count_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Counter> {
-        match value.try_to_value() {
-            Ok(v) => MetricBuilder::from_fmt(MetricFormatter::counter(&self.prefix, key, v), self)
-                .with_tags(self.tags())
-                .with_container_id_opt(self.container_id.as_deref()),
-            Err(e) => MetricBuilder::from_error(e, self),
-        }
+    /// Create a handle for sending gauges with the given key.
+    ///
+    /// See `StatsdClient::counter_handle()` for more information.
+    pub fn gauge_handle(&self, key: &str) -> MetricHandle<Gauge> {
+        MetricHandle::new(self.scoped(""), key, |p, k, v| MetricFormatter::gauge(p, k, v))
     }
-}
-
-impl CountedExt for StatsdClient {}
 
-impl<T> Timed<T> for StatsdClient
-where
-    T: ToTimerValue,
-{
-    fn time_with_tags<'a>(&'a self, key: &'a str, time: T) -> MetricBuilder<'_, '_, Timer> {
-        match time.try_to_value() {
-            Ok(v) => MetricBuilder::from_fmt(MetricFormatter::timer(&self.prefix, key, v), self)
-                .with_tags(self.tags())
-                .with_container_id_opt(self.container_id.as_deref()),
-            Err(e) => MetricBuilder::from_error(e, self),
-        }
+    /// Create a handle for sending histograms with the given key.
+    ///
+    /// See `StatsdClient::counter_handle()` for more information.
+    pub fn histogram_handle(&self, key: &str) -> MetricHandle<Histogram> {
+        MetricHandle::new(self.scoped(""), key, |p, k, v| MetricFormatter::histogram(p, k, v))
     }
-}
 
-impl<T> Gauged<T> for StatsdClient
-where
-    T: ToGaugeValue,
-{
-    fn gauge_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Gauge> {
-        match value.try_to_value() {
-            Ok(v) => MetricBuilder::from_fmt(MetricFormatter::gauge(&self.prefix, key, v), self)
-                .with_tags(self.tags())
-                .with_container_id_opt(self.container_id.as_deref()),
-            Err(e) => MetricBuilder::from_error(e, self),
-        }
+    /// Create a handle for sending distributions with the given key.
+    ///
+    /// See `StatsdClient::counter_handle()` for more information.
+    pub fn distribution_handle(&self, key: &str) -> MetricHandle<Distribution> {
+        MetricHandle::new(self.scoped(""), key, |p, k, v| MetricFormatter::distribution(p, k, v))
     }
-}
 
-impl<T> Metered<T> for StatsdClient
-where
-    T: ToMeterValue,
-{
-    fn meter_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Meter> {
-        match value.try_to_value() {
-            Ok(v) => MetricBuilder::from_fmt(MetricFormatter::meter(&self.prefix, key, v), self)
-                .with_tags(self.tags())
-                .with_container_id_opt(self.container_id.as_deref()),
-            Err(e) => MetricBuilder::from_error(e, self),
-        }
-    }
-}
+    /// Register a gauge with the given key whose value is read by calling the
+    /// given function and sent periodically from a background thread.
+    ///
+    /// Values of every registered gauge are sent every 10 seconds by default,
+    /// which can be changed with `StatsdClientBuilder::with_gauge_interval()`.
+    /// The thread sending them is started when the first gauge is registered
+    /// and exits once every client sharing the sink of this client has been
+    /// dropped. Gauges are sent quietly, like `MetricBuilder::send()`, so any
+    /// error sending them is passed to the error handler of this client.
+    ///
+    /// The gauge is sent until the returned registration is dropped or used to
+    /// unregister it. Call `GaugeRegistration::detach()` to keep sending the
+    /// gauge for as long as the client is used instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::sync::{Arc, Mutex};
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let queue = Arc::new(Mutex::new(vec!["job1", "job2"]));
+    /// let client = StatsdClient::from_sink("my.app", NopMetricSink);
+    ///
+    /// let depth = queue.clone();
+    /// let registration = client.register_gauge("queue.depth", move || depth.lock().unwrap().len() as u64);
+    ///
+    /// // Later, when the queue is no longer used
+    /// registration.unregister();
+    ///
+    /// // Gauges that should be sent for the life of the client
+    /// client.register_gauge("uptime", || 1u64).detach();
+    /// ```
+    pub fn register_gauge<F, T>(&self, key: &str, f: F) -> GaugeRegistration
+    where
+        F: Fn() -> T + Send + Sync + RefUnwindSafe + 'static,
+        T: ToGaugeValue,
+    {
+        let key = key.to_owned();
+        let registration = self.shared.gauges.register(
+            &self.prefix,
+            Arc::new(move |client: &StatsdClient| client.gauge_with_tags(&key, f()).send()),
+        );
 
-impl<T> Histogrammed<T> for StatsdClient
-where
-    T: ToHistogramValue,
-{
-    fn histogram_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Histogram> {
-        match value.try_to_value() {
-            Ok(v) => MetricBuilder::from_fmt(MetricFormatter::histogram(&self.prefix, key, v), self)
-                .with_tags(self.tags())
-                .with_container_id_opt(self.container_id.as_deref()),
-            Err(e) => MetricBuilder::from_error(e, self),
+        if !self.is_disabled() && self.shared.gauges.should_start() {
+            let shared = Arc::downgrade(&self.shared);
+            let interval = self.shared.gauges.interval();
+            let dropped = self.shared.gauges.dropped();
+            let res = thread::Builder::new()
+                .name("cadence-gauges".into())
+                .spawn(move || send_registered_gauges(shared, interval, &dropped));
+
+            if let Err(e) = res {
+                self.shared.gauges.stopped();
+                self.consume_error(MetricError::from(e));
+            }
         }
+
+        registration
     }
-}
 
-impl<T> Distributed<T> for StatsdClient
-where
-    T: ToDistributionValue,
-{
-    fn distribution_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Distribution> {
-        match value.try_to_value() {
-            Ok(v) => MetricBuilder::from_fmt(MetricFormatter::distribution(&self.prefix, key, v), self)
-                .with_tags(self.tags())
-                .with_container_id_opt(self.container_id.as_deref()),
-            Err(e) => MetricBuilder::from_error(e, self),
+    /// Install a panic hook that increments a counter with the given key each
+    /// time a thread panics, before running the previously installed hook.
+    ///
+    /// The counter is tagged with the name of the thread that panicked, or
+    /// `unnamed` for threads without a name, using the tag key `thread`. Since a
+    /// panic often ends the process, the sink of this client is flushed after
+    /// the counter is sent. Like any panic hook, this applies to every thread
+    /// of the process and stops working if another hook that doesn't run the
+    /// previous one is installed afterwards.
+    ///
+    /// The counter is sent from a separate thread, since the thread that
+    /// panicked may be holding a lock used by the sink and a second panic while
+    /// the hook runs aborts the process. The hook waits up to 500 milliseconds
+    /// for the counter to be sent before running the previous hook, which adds
+    /// that much latency to each panic when the sink is slow or blocked. Panics
+    /// while sending the counter are ignored.
+    ///
+    /// The hook doesn't keep the client alive: once every client sharing the
+    /// sink of this client has been dropped, it only runs the previous hook. No
+    /// hook is installed when this client is disabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("my.app", NopMetricSink);
+    /// client.install_panic_hook("panics");
+    /// ```
+    pub fn install_panic_hook(&self, key: &str) {
+        if self.is_disabled() {
+            return;
         }
+
+        let prefix = self.prefix.clone();
+        let shared = Arc::downgrade(&self.shared);
+        let key = key.to_owned();
+        let previous = panic::take_hook();
+
+        panic::set_hook(Box::new(move |info| {
+            let client = match shared.upgrade() {
+                Some(shared) if !IN_PANIC_HOOK.with(Cell::get) => Some(StatsdClient {
+                    prefix: prefix.clone(),
+                    shared,
+                }),
+                _ => None,
+            };
+
+            if let Some(client) = client {
+                let current = thread::current();
+                let name = current.name().unwrap_or("unnamed").to_owned();
+                send_panic_counter(client, key.clone(), name);
+            }
+
+            previous(info);
+        }));
     }
-}
 
-impl<T> Setted<T> for StatsdClient
-where
-    T: ToSetValue,
+    /// Run the given closure and record how long it took to run as a timer
+    /// with the given key, returning the result of the closure.
+    ///
+    /// The time is measured using the timer clock of this client, a monotonic
+    /// clock by default, and is emitted in the timer unit of this client,
+    /// milliseconds by default. The timer is sent quietly, like
+    /// `MetricBuilder::send()`, so any error sending it is passed to the error
+    /// handler of this client.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("my.app", NopMetricSink);
+    /// let sum = client.time_block("compute.sum", || (1..=100u64).sum::<u64>());
+    ///
+    /// assert_eq!(5050, sum);
+    /// ```
+    pub fn time_block<F, R>(&self, key: &str, block: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        let start = self.timer_now();
+        let res = block();
+        self.time_with_tags(key, self.timer_now().saturating_sub(start)).send();
+        res
+    }
+
+    /// Start a timer with the given key that is recorded when the returned
+    /// guard is dropped.
+    ///
+    /// This makes sure the time taken by a block of code is recorded even when
+    /// it returns early. See `Stopwatch` for more information.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("my.app", NopMetricSink);
+    /// {
+    ///     let _timer = client.start_timer("cache.rebuild").with_tag("cache", "users");
+    ///     // Rebuild the cache...
+    /// }
+    /// ```
+    pub fn start_timer<'a>(&'a self, key: &'a str) -> Stopwatch<'a> {
+        Stopwatch::new(self, key)
+    }
+
+    /// Wrap the given future to record how long it took to complete as a
+    /// timer with the given key, returning the output of the future.
+    ///
+    /// The time is measured from when the returned future is first polled
+    /// until the wrapped future completes. See `TimedFuture` for more
+    /// information. This method is only available when the `async-timing`
+    /// feature is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::StatsdClient;
+    ///
+    /// async fn handle_request(client: &StatsdClient) -> u64 {
+    ///     client.time_future("request.handle", async { 42 }).await
+    /// }
+    /// ```
+    #[cfg(feature = "async-timing")]
+    pub fn time_future<'a, F>(&'a self, key: &'a str, fut: F) -> TimedFuture<'a, F>
+    where
+        F: Future,
+    {
+        TimedFuture::new(self, key, fut)
+    }
+
+    /// Record a metric with a type that isn't part of the Statsd spec, for
+    /// servers that support nonstandard types of metrics.
+    ///
+    /// The metric is emitted as `key:value|metric_type`, with the prefix,
+    /// default tags, and other settings of this client applied like any other
+    /// metric. The type must be made up of only ASCII letters, digits, and
+    /// underscores.
+    ///
+    /// The following types are valid for custom metric values:
+    /// * `i64`
+    /// * `i32`
+    /// * `i128`
+    /// * `u64`
+    /// * `u32`
+    /// * `u128`
+    /// * `f64`
+    /// * `Vec<u64>`
+    /// * `Vec<f64>`
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{Metric, StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("my.app", NopMetricSink);
+    /// let res = client.custom("cache.hit_ratio", 0.75, "pct");
+    ///
+    /// assert_eq!("my.app.cache.hit_ratio:0.75|pct", res.unwrap().as_metric_str());
+    /// ```
+    pub fn custom<T>(&self, key: &str, value: T, metric_type: &str) -> MetricResult<CustomMetric>
+    where
+        T: ToCustomValue,
+    {
+        self.custom_with_tags(key, value, metric_type).try_send()
+    }
+
+    /// Record a metric with a type that isn't part of the Statsd spec and
+    /// return a `MetricBuilder` that can be used to add tags to the metric.
+    ///
+    /// See `StatsdClient::custom()` for more information.
+    pub fn custom_with_tags<'a, T>(
+        &'a self,
+        key: &'a str,
+        value: T,
+        metric_type: &'a str,
+    ) -> MetricBuilder<'a, 'a, CustomMetric>
+    where
+        T: ToCustomValue,
+    {
+        if !valid_metric_type(metric_type) {
+            return MetricBuilder::from_error(
+                MetricError::from((ErrorKind::InvalidInput, "invalid custom metric type"))
+                    .with_metric(&self.prefix, key),
+                self,
+            );
+        }
+
+        match value.try_to_value() {
+            Ok(v) => self.metric_builder(MetricFormatter::custom(&self.prefix, key, v, metric_type)),
+            Err(e) => MetricBuilder::from_error(e.with_metric(&self.prefix, key), self),
+        }
+    }
+
+    /// Send several metrics using the sink of this client in a single call.
+    ///
+    /// This allows sinks that buffer or queue metrics to handle the whole batch
+    /// at once. The metrics are sent verbatim, see `MetricBackend::send_metric()`
+    /// for more information. To create and send several metrics at once, use
+    /// `StatsdClient::batch()` instead.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{Counter, Gauge, StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("my.app", NopMetricSink);
+    /// let counters = [
+    ///     Counter::new("my.app.", "requests", 4),
+    ///     Counter::new("my.app.", "errors", 1),
+    /// ];
+    ///
+    /// client.emit_all(&counters).unwrap();
+    /// ```
+    pub fn emit_all<M>(&self, metrics: &[M]) -> MetricResult<()>
+    where
+        M: Metric,
+    {
+        let metrics: Vec<&str> = metrics.iter().map(|m| m.as_metric_str()).collect();
+        self.send_batch(&metrics)
+    }
+
+    /// Create a new builder for sending several metrics created by this client
+    /// using its sink in a single call.
+    ///
+    /// See `MetricBatch` for more information.
+    pub fn batch(&self) -> MetricBatch<'_> {
+        MetricBatch::new(self)
+    }
+
+    // Send a metric formatted by a builder without wrapping it in a `Metric`
+    pub(crate) fn send_formatted(&self, metric: &str) -> MetricResult<()> {
+        if self.is_disabled() {
+            return Ok(());
+        }
+
+        self.sink_result(self.shared.sink.emit(metric))
+    }
+
+    // Send a metric that hasn't been formatted yet, leaving it to the sink
+    pub(crate) fn send_writable(&self, metric: &dyn WriteMetric) -> MetricResult<()> {
+        if self.is_disabled() {
+            return Ok(());
+        }
+
+        self.sink_result(self.shared.sink.emit_metric(metric))
+    }
+
+    pub(crate) fn send_batch(&self, metrics: &[&str]) -> MetricResult<()> {
+        if !self.is_disabled() && !metrics.is_empty() {
+            self.sink_result(self.shared.sink.emit_batch(metrics))?;
+        }
+
+        Ok(())
+    }
+
+    // Convert the result of calling the sink, counting any error
+    fn sink_result<T>(&self, res: io::Result<T>) -> MetricResult<()> {
+        res.map(|_| ()).map_err(|e| {
+            let err = MetricError::from(e);
+            self.shared.sink_errors.record(&err);
+            err
+        })
+    }
+
+    /// Send a metric that has already been formatted by the caller, quietly
+    /// discarding the result.
+    ///
+    /// The metric is written to the sink of this client verbatim except for
+    /// the default sample rate of the client, if any: metrics are sampled at
+    /// that rate and `|@rate` is added to metrics that are sent. Metrics that
+    /// already include a rate have it multiplied by the rate of the client
+    /// instead, the same as `MetricBuilder::with_sample_rate()`. The prefix, default tags, and other settings of
+    /// the client are not applied. Events and service checks are never sampled.
+    ///
+    /// This is useful for forwarding Statsd lines received from elsewhere,
+    /// such as in a proxy. If sending the metric fails, the error handler of
+    /// the client is invoked with the metric and the error.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{StatsdClient, NopMetricSink};
+    ///
+    /// let client = StatsdClient::from_sink("my.app", NopMetricSink);
+    /// client.emit_raw("other.app.requests:1|c|#region:us-west-1");
+    /// ```
+    pub fn emit_raw(&self, metric: &str) {
+        if let Err(e) = self.try_emit_raw(metric) {
+            self.consume_metric_error(metric, e);
+        }
+    }
+
+    /// Send a metric that has already been formatted by the caller, returning
+    /// an error if it could not be sent.
+    ///
+    /// See `StatsdClient::emit_raw()` for more information.
+    ///
+    /// # Failures
+    ///
+    /// This method will fail if the metric is empty or contains a newline, or
+    /// if the sink of this client fails to write it.
+    pub fn try_emit_raw(&self, metric: &str) -> MetricResult<()> {
+        if metric.is_empty() || metric.contains('\n') {
+            return Err(MetricError::from((
+                ErrorKind::InvalidInput,
+                "raw metric must be a single non-empty line",
+            )));
+        }
+
+        let metric_type = metric.split('|').nth(1).unwrap_or("");
+        let rate = match self.shared.sample_rate(metric_type) {
+            Some(rate) if !metric.starts_with("_e{") && !metric.starts_with("_sc|") => rate,
+            _ => return self.send_formatted(metric),
+        };
+
+        if !sample::is_sampled(rate, self.rng()) {
+            return Ok(());
+        }
+
+        if rate < 1.0 {
+            self.send_formatted(&with_sampling_rate(metric, rate))
+        } else {
+            self.send_formatted(metric)
+        }
+    }
+
+    // Create a new StatsdClient by consuming the builder
+    fn from_builder(builder: StatsdClientBuilder) -> Self {
+        StatsdClient {
+            prefix: builder.prefix,
+            shared: Arc::new(SharedState {
+                sink: builder.sink,
+                errors: builder.errors,
+                metric_errors: builder.metric_errors,
+                tags: builder.tags,
+                container_id: builder.container_id,
+                tag_format: builder.tag_format,
+                sanitize_policy: builder.sanitize_policy,
+                non_finite_policy: builder.non_finite_policy,
+                sample_rate: builder.sample_rate,
+                type_sample_rates: builder.type_sample_rates,
+                rng: builder.rng,
+                clock: builder.clock,
+                timer_clock: builder.timer_clock,
+                timer_unit: builder.timer_unit,
+                histogram_unit: builder.histogram_unit,
+                distribution_unit: builder.distribution_unit,
+                gauges: Arc::new(GaugeRegistry::new(builder.gauge_interval)),
+                sink_errors: ErrorTracker::default(),
+                disabled: builder.disabled,
+            }),
+        }
+    }
+
+    // Current time of the clock used for timing helpers
+    pub(crate) fn timer_now(&self) -> Duration {
+        self.shared.timer_clock.now()
+    }
+
+    fn tags(&self) -> impl IntoIterator<Item = (Option<&str>, &str)> {
+        self.shared.tags.iter().map(|(k, v)| (k.as_deref(), v.as_str()))
+    }
+
+    // Create a new builder for the formatted metric that includes any default
+    // tags, container ID, sample rate, or timestamp that this client has been
+    // configured with.
+    fn metric_builder<'a, T>(&'a self, formatter: MetricFormatter<'a>) -> MetricBuilder<'a, 'a, T>
+    where
+        T: Metric + From<String>,
+    {
+        let builder = self.metric_builder_defaults(formatter);
+
+        match self.timestamp() {
+            Some(timestamp) => builder.with_timestamp(timestamp),
+            None => builder,
+        }
+    }
+
+    // Create a new builder for the formatted metric that includes any default
+    // tags, container ID, or sample rate but not a timestamp since that changes
+    // each time a metric is sent.
+    fn metric_builder_defaults<'a, T>(&'a self, formatter: MetricFormatter<'a>) -> MetricBuilder<'a, 'a, T>
+    where
+        T: Metric + From<String>,
+    {
+        let formatter_type = formatter.metric_type().as_str();
+        let builder = MetricBuilder::from_fmt(formatter, self)
+            .with_default_tags(self.tags())
+            .with_container_id_opt(self.shared.container_id.as_deref())
+            .with_tag_format(self.shared.tag_format)
+            .with_sanitize_policy(self.shared.sanitize_policy)
+            .with_non_finite_policy(self.shared.non_finite_policy);
+
+        match self.shared.sample_rate(formatter_type) {
+            Some(rate) => builder.with_sample_rate(rate),
+            None => builder,
+        }
+    }
+
+    // Format a metric for a handle with a single character placeholder for
+    // the value, returning the metric and the index of the placeholder.
+    pub(crate) fn handle_template<T>(
+        &self,
+        key: &str,
+        new_fmt: NewFormatter,
+        tags: &[(Option<String>, String)],
+    ) -> MetricResult<(String, usize)>
+    where
+        T: Metric + From<String>,
+    {
+        let formatter = new_fmt(&self.prefix, key, MetricValue::Unsigned(0));
+        let builder = tags.iter().fold(
+            self.metric_builder_defaults::<T>(formatter),
+            |builder, (k, v)| match k {
+                Some(k) => builder.with_tag(k, v),
+                None => builder.with_tag_value(v),
+            },
+        );
+
+        builder.format_with_value_index()
+    }
+
+    // Current UNIX timestamp from the clock of this client, if any
+    pub(crate) fn timestamp(&self) -> Option<u64> {
+        self.shared.clock.as_ref().map(|clock| clock())
+    }
+
+    // Decide if a metric of the given type should be sent based on the default
+    // sample rate of this client for it, if any. Note that each call to this
+    // method makes a new decision.
+    pub(crate) fn is_sampled(&self, metric_type: &str) -> bool {
+        match self.shared.sample_rate(metric_type) {
+            Some(rate) => sample::is_sampled(rate, self.rng()),
+            None => true,
+        }
+    }
+
+    // Random number generator used to decide which sampled metrics are sent
+    pub(crate) fn rng(&self) -> &dyn Rng {
+        &*self.shared.rng
+    }
+
+    pub(crate) fn non_finite_policy(&self) -> NonFinitePolicy {
+        self.shared.non_finite_policy
+    }
+
+    // True if this client never sends metrics, see `StatsdClientBuilder::with_disabled()`
+    pub(crate) fn is_disabled(&self) -> bool {
+        self.shared.disabled
+    }
+
+    pub(crate) fn timer_unit(&self) -> TimeUnit {
+        self.shared.timer_unit
+    }
+
+    pub(crate) fn histogram_unit(&self) -> TimeUnit {
+        self.shared.histogram_unit
+    }
+
+    pub(crate) fn distribution_unit(&self) -> TimeUnit {
+        self.shared.distribution_unit
+    }
+
+    // Create a new builder for the formatted event that includes any default
+    // tags or container ID that this client has been configured with.
+    fn event_builder<'a>(&'a self, formatter: EventFormatter<'a>) -> EventBuilder<'a, 'a> {
+        EventBuilder::from_fmt(formatter, self)
+            .with_default_tags(self.tags())
+            .with_container_id_opt(self.shared.container_id.as_deref())
+    }
+
+    // Create a new builder for the formatted service check that includes any
+    // default tags or container ID that this client has been configured with.
+    fn service_check_builder<'a>(&'a self, formatter: ServiceCheckFormatter<'a>) -> ServiceCheckBuilder<'a, 'a> {
+        ServiceCheckBuilder::from_fmt(formatter, self)
+            .with_default_tags(self.tags())
+            .with_container_id_opt(self.shared.container_id.as_deref())
+    }
+}
+
+// Send the values of gauges registered with a client on a fixed interval until
+// every client sharing its state has been dropped, which wakes this thread up
+// by setting the `dropped` flag. A callback that panics only skips sending
+// that gauge.
+fn send_registered_gauges(shared: Weak<SharedState>, interval: Duration, dropped: &(Mutex<bool>, Condvar)) {
+    let (lock, cond) = dropped;
+    loop {
+        let guard = lock.lock().unwrap();
+        let (guard, _) = cond.wait_timeout_while(guard, interval, |dropped| !*dropped).unwrap();
+        if *guard {
+            break;
+        }
+
+        drop(guard);
+        let shared = match shared.upgrade() {
+            Some(shared) => shared,
+            None => break,
+        };
+
+        for (prefix, callback) in shared.gauges.callbacks() {
+            let client = StatsdClient {
+                prefix,
+                shared: shared.clone(),
+            };
+
+            let _ = panic::catch_unwind(AssertUnwindSafe(|| callback(&client)));
+        }
+    }
+}
+
+impl Sealed for StatsdClient {}
+
+impl MetricBackend for StatsdClient {
+    fn send_metric<M>(&self, metric: &M) -> MetricResult<()>
+    where
+        M: Metric,
+    {
+        let metric_string = metric.as_metric_str();
+        self.send_formatted(metric_string)
+    }
+
+    fn consume_error(&self, err: MetricError) {
+        (self.shared.errors)(err);
+    }
+
+    fn consume_metric_error(&self, metric: &str, err: MetricError) {
+        match &self.shared.metric_errors {
+            Some(metric_errors) => metric_errors(metric, err),
+            None => (self.shared.errors)(err),
+        }
+    }
+}
+
+impl fmt::Debug for StatsdClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "StatsdClient {{ prefix: {:?}, sink: ..., errors: ..., tags: {:?} }}",
+            self.prefix, self.shared.tags,
+        )
+    }
+}
+
+impl<T> Counted<T> for StatsdClient
+where
+    T: ToCounterValue,
+{
+    fn count_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Counter> {
+        match value.try_to_value() {
+            Ok(v) => self.metric_builder(MetricFormatter::counter(&self.prefix, key, v)),
+            Err(e) => MetricBuilder::from_error(e.with_metric(&self.prefix, key), self),
+        }
+    }
+}
+
+impl CountedExt for StatsdClient {}
+
+impl<T> Timed<T> for StatsdClient
+where
+    T: ToTimerValue,
+{
+    fn time_with_tags<'a>(&'a self, key: &'a str, time: T) -> MetricBuilder<'a, 'a, Timer> {
+        match time.try_to_value_in(self.shared.timer_unit) {
+            Ok(v) => self.metric_builder(MetricFormatter::timer(&self.prefix, key, v)),
+            Err(e) => MetricBuilder::from_error(e.with_metric(&self.prefix, key), self),
+        }
+    }
+}
+
+impl<T> Gauged<T> for StatsdClient
+where
+    T: ToGaugeValue,
+{
+    fn gauge_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Gauge> {
+        match value.try_to_value() {
+            Ok(v) => self.metric_builder(MetricFormatter::gauge(&self.prefix, key, v)),
+            Err(e) => MetricBuilder::from_error(e.with_metric(&self.prefix, key), self),
+        }
+    }
+}
+
+impl<T> Metered<T> for StatsdClient
+where
+    T: ToMeterValue,
 {
-    fn set_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'_, '_, Set> {
+    fn meter_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Meter> {
         match value.try_to_value() {
-            Ok(v) => MetricBuilder::from_fmt(MetricFormatter::set(&self.prefix, key, v), self)
-                .with_tags(self.tags())
-                .with_container_id_opt(self.container_id.as_deref()),
-            Err(e) => MetricBuilder::from_error(e, self),
+            Ok(v) => self.metric_builder(MetricFormatter::meter(&self.prefix, key, v)),
+            Err(e) => MetricBuilder::from_error(e.with_metric(&self.prefix, key), self),
+        }
+    }
+}
+
+impl<T> Histogrammed<T> for StatsdClient
+where
+    T: ToHistogramValue,
+{
+    fn histogram_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Histogram> {
+        match value.try_to_value_in(self.shared.histogram_unit) {
+            Ok(v) => self.metric_builder(MetricFormatter::histogram(&self.prefix, key, v)),
+            Err(e) => MetricBuilder::from_error(e.with_metric(&self.prefix, key), self),
         }
     }
-}
+}
+
+impl<T> Distributed<T> for StatsdClient
+where
+    T: ToDistributionValue,
+{
+    fn distribution_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Distribution> {
+        match value.try_to_value_in(self.shared.distribution_unit) {
+            Ok(v) => self.metric_builder(MetricFormatter::distribution(&self.prefix, key, v)),
+            Err(e) => MetricBuilder::from_error(e.with_metric(&self.prefix, key), self),
+        }
+    }
+}
+
+impl<T> Setted<T> for StatsdClient
+where
+    T: ToSetValue,
+{
+    fn set_with_tags<'a>(&'a self, key: &'a str, value: T) -> MetricBuilder<'a, 'a, Set> {
+        match value.try_to_value() {
+            Ok(v) => self.metric_builder(MetricFormatter::set(&self.prefix, key, v)),
+            Err(e) => MetricBuilder::from_error(e.with_metric(&self.prefix, key), self),
+        }
+    }
+}
+
+impl MetricClient for StatsdClient {}
+
+impl Evented for StatsdClient {
+    fn event_with_tags<'a>(&'a self, title: &'a str, text: &'a str) -> EventBuilder<'a, 'a> {
+        if title.is_empty() {
+            EventBuilder::from_error(
+                MetricError::from((ErrorKind::InvalidInput, "event title is required")),
+                self,
+            )
+        } else {
+            self.event_builder(EventFormatter::new(title, text))
+        }
+    }
+}
+
+impl ServiceChecked for StatsdClient {
+    fn service_check_with_tags<'a>(&'a self, name: &'a str, status: ServiceCheckStatus) -> ServiceCheckBuilder<'a, 'a> {
+        if name.is_empty() {
+            let err = MetricError::from((ErrorKind::InvalidInput, "service check name is required"));
+            ServiceCheckBuilder::from_error(err, self)
+        } else {
+            self.service_check_builder(ServiceCheckFormatter::new(&self.prefix, name, status))
+        }
+    }
+}
+
+#[allow(clippy::needless_pass_by_value)]
+fn nop_error_handler(_err: MetricError) {
+    // nothing
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        with_sampling_rate, Counted, CountedExt, Distributed, Evented, Gauged, Histogrammed, Metered, MetricClient,
+        ServiceChecked, Setted, StatsdClient, Timed, ToMetricValue,
+    };
+    use crate::builder::{MetricType, MetricValue, NonFinitePolicy, TagFormat};
+    use crate::sample::Rng;
+    use crate::sinks::{
+        MetricSink, NopMetricSink, QueuingMetricSink, RecordingMetricSink, SpyMetricSink, TcpMetricSink,
+    };
+    use crate::test::ErrorMetricSink;
+    use crate::timing::{ScaledDuration, TimeUnit};
+    use crate::types::{Counter, ErrorKind, EventAlertType, Metric, MetricError, MetricResult, ServiceCheckStatus};
+    use crate::StatsdClientBuilder;
+    use std::collections::BTreeMap;
+    use std::io;
+    use std::net::TcpListener;
+    use std::num::{NonZeroU32, NonZeroU64};
+    use std::panic::RefUnwindSafe;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use std::thread;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_statsd_client_empty_prefix() {
+        let client = StatsdClient::from_sink("", NopMetricSink);
+        let res = client.count("some.method", 1);
+
+        assert_eq!("some.method:1|c", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_with_container_id() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_container_id("1234")
+            .build();
+        let res = client.count("some.method", 1);
+
+        assert_eq!("prefix.some.method:1|c|c:1234", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_with_sample_rate() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClientBuilder::new("prefix", sink).with_sample_rate(0.0).build();
+
+        let res = client.count("some.counter", 1);
+        assert_eq!("prefix.some.counter:1|c|@0", res.unwrap().as_metric_str());
+        assert!(rx.try_recv().is_err(), "expected metric to be sampled out");
+
+        let res = client
+            .count_with_tags("some.counter", 1)
+            .with_sample_rate(1.0)
+            .try_send();
+        assert_eq!("prefix.some.counter:1|c", res.unwrap().as_metric_str());
+        assert!(rx.try_recv().is_ok());
+    }
+
+    #[test]
+    fn test_statsd_client_with_type_sample_rate() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClientBuilder::new("prefix", sink)
+            .with_sample_rate(0.0)
+            .with_type_sample_rate(MetricType::Timer, 1.0)
+            .with_type_sample_rate(MetricType::Counter, 0.5)
+            .with_type_sample_rate(MetricType::Counter, 1.0)
+            .build();
+
+        let res = client.time("some.timer", 12);
+        assert_eq!("prefix.some.timer:12|ms", res.unwrap().as_metric_str());
+        let res = client.count("some.counter", 1);
+        assert_eq!("prefix.some.counter:1|c", res.unwrap().as_metric_str());
+        let res = client.gauge("some.gauge", 1);
+        assert_eq!("prefix.some.gauge:1|g|@0", res.unwrap().as_metric_str());
+
+        client.timer_handle("handle.timer").time(5).unwrap();
+        client.gauge_handle("handle.gauge").gauge(5).unwrap();
+        client.emit_raw("raw.timer:1|ms");
+        client.emit_raw("raw.gauge:1|g");
+
+        let received: Vec<String> = rx.try_iter().map(|m| String::from_utf8(m).unwrap()).collect();
+        assert_eq!(
+            vec![
+                "prefix.some.timer:12|ms",
+                "prefix.some.counter:1|c",
+                "prefix.handle.timer:5|ms",
+                "raw.timer:1|ms",
+            ],
+            received
+        );
+
+        let res = client.time_with_tags("some.timer", 12).with_sample_rate(0.0).try_send();
+        assert_eq!("prefix.some.timer:12|ms|@0", res.unwrap().as_metric_str());
+        assert!(rx.try_recv().is_err());
+    }
 
-impl MetricClient for StatsdClient {}
+    #[test]
+    fn test_statsd_client_with_rng() {
+        struct FixedRng(f64);
 
-#[allow(clippy::needless_pass_by_value)]
-fn nop_error_handler(_err: MetricError) {
-    // nothing
-}
+        impl Rng for FixedRng {
+            fn next_f64(&self) -> f64 {
+                self.0
+            }
+        }
 
-#[cfg(test)]
-mod tests {
-    use super::{
-        Counted, CountedExt, Distributed, Gauged, Histogrammed, Metered, MetricClient, Setted, StatsdClient, Timed,
-    };
-    use crate::sinks::{MetricSink, NopMetricSink, QueuingMetricSink, SpyMetricSink};
-    use crate::types::{ErrorKind, Metric, MetricError};
-    use crate::StatsdClientBuilder;
-    use std::io;
-    use std::panic::RefUnwindSafe;
-    use std::sync::atomic::{AtomicUsize, Ordering};
-    use std::sync::Arc;
-    use std::time::Duration;
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClientBuilder::new("prefix", sink)
+            .with_sample_rate(0.5)
+            .with_rng(FixedRng(0.4))
+            .build();
+
+        client.count("some.counter", 1).unwrap();
+        client.count_with_tags("some.counter", 2).with_sample_rate(0.3).send();
+        client.counter_handle("handle.counter").count(3).unwrap();
+        client.emit_raw("raw.counter:4|c");
+        client.emit_raw("raw.counter:5|c|@0.5|#a:b");
+
+        let received: Vec<String> = rx.try_iter().map(|m| String::from_utf8(m).unwrap()).collect();
+        assert_eq!(
+            vec![
+                "prefix.some.counter:1|c|@0.5",
+                "prefix.handle.counter:3|c|@0.5",
+                "raw.counter:4|c|@0.5",
+                "raw.counter:5|c|@0.25|#a:b",
+            ],
+            received
+        );
+    }
 
     #[test]
-    fn test_statsd_client_empty_prefix() {
-        let client = StatsdClient::from_sink("", NopMetricSink);
-        let res = client.count("some.method", 1);
+    fn test_statsd_client_with_clock() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_clock(|| 1_700_000_000)
+            .build();
 
-        assert_eq!("some.method:1|c", res.unwrap().as_metric_str());
+        let res = client.count("some.counter", 1);
+        assert_eq!("prefix.some.counter:1|c|T1700000000", res.unwrap().as_metric_str());
+
+        let res = client.count_with_tags("some.counter", 1).with_timestamp(1).try_send();
+        assert_eq!("prefix.some.counter:1|c|T1", res.unwrap().as_metric_str());
     }
 
     #[test]
-    fn test_statsd_client_with_container_id() {
+    fn test_statsd_client_try_build() {
         let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("env", "production")
+            .with_tag_value("rust")
             .with_container_id("1234")
+            .with_sample_rate(0.5)
+            .try_build();
+        assert!(client.is_ok());
+    }
+
+    #[test]
+    fn test_statsd_client_try_build_invalid() {
+        let builders = vec![
+            StatsdClientBuilder::new("prefix", NopMetricSink).with_sample_rate(1.5),
+            StatsdClientBuilder::new("prefix", NopMetricSink).with_type_sample_rate(MetricType::Timer, -0.5),
+            StatsdClientBuilder::new("prefix:", NopMetricSink),
+            StatsdClientBuilder::new("prefix", NopMetricSink).with_tag("env", "prod,dev"),
+            StatsdClientBuilder::new("prefix", NopMetricSink).with_tag("env:name", "prod"),
+            StatsdClientBuilder::new("prefix", NopMetricSink).with_tag_value("a|b"),
+            StatsdClientBuilder::new("prefix", NopMetricSink).with_container_id("12\n34"),
+        ];
+
+        for builder in builders {
+            let err = builder.try_build().unwrap_err();
+            assert_eq!(ErrorKind::InvalidInput, err.kind());
+        }
+    }
+
+    #[test]
+    fn test_statsd_client_error_stats() {
+        let client = StatsdClient::from_sink("prefix", ErrorMetricSink::always());
+        let scoped = client.scoped("scoped");
+
+        client.count_with_tags("some.counter", 1).send();
+        assert!(scoped.count("some.counter", 1).is_err());
+        assert!(client.try_emit_raw("foo:1|c").is_err());
+        // Invalid metrics aren't sink errors
+        assert!(client.gauge("some.gauge", f64::NAN).is_err());
+
+        let stats = client.error_stats();
+        assert_eq!(3, stats.timeouts);
+        assert_eq!(3, stats.total());
+        assert_eq!(stats, scoped.error_stats());
+        assert_eq!(ErrorKind::Timeout, stats.last_error.unwrap().kind);
+    }
+
+    #[test]
+    fn test_statsd_client_check() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let client = StatsdClient::from_sink("prefix", TcpMetricSink::from(addr).unwrap());
+        let err = client.check().unwrap_err();
+        assert_eq!(ErrorKind::IoError, err.kind());
+        // Failed checks aren't counted as errors sending metrics
+        assert_eq!(0, client.error_stats().total());
+
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        assert!(client.check().is_ok());
+    }
+
+    #[test]
+    fn test_statsd_client_with_metric_error_handler() {
+        let dropped = Arc::new(Mutex::new(Vec::new()));
+        let dropped_ref = dropped.clone();
+        let errors = Arc::new(AtomicUsize::new(0));
+        let errors_ref = errors.clone();
+
+        let client = StatsdClientBuilder::new("prefix", ErrorMetricSink::always())
+            .with_error_handler(move |_e| {
+                errors_ref.fetch_add(1, Ordering::Release);
+            })
+            .with_metric_error_handler(move |metric, e| {
+                dropped_ref.lock().unwrap().push((metric.to_string(), e.kind()));
+            })
             .build();
-        let res = client.count("some.method", 1);
 
-        assert_eq!("prefix.some.method:1|c|c:1234", res.unwrap().as_metric_str());
+        client.count_with_tags("some.counter", 1).with_tag("foo", "bar").send();
+        client.event_with_tags("Deploy", "v1").send();
+        client.time_with_tags("some.timer", Duration::MAX).send();
+
+        assert_eq!(
+            vec![
+                ("prefix.some.counter:1|c|#foo:bar".to_string(), ErrorKind::Timeout),
+                ("_e{6,2}:Deploy|v1".to_string(), ErrorKind::Timeout),
+            ],
+            *dropped.lock().unwrap()
+        );
+        // The timer value is invalid so there's no metric to pass to the handler
+        assert_eq!(1, errors.load(Ordering::Acquire));
+    }
+
+    #[test]
+    fn test_statsd_client_custom() {
+        let client = StatsdClient::builder("prefix", NopMetricSink)
+            .with_tag("env", "prod")
+            .build();
+
+        let res = client.custom("some.ratio", 42, "pct");
+        assert_eq!("prefix.some.ratio:42|pct|#env:prod", res.unwrap().as_metric_str());
+
+        let res = client
+            .custom_with_tags("some.kv", vec![1.5, 2.5], "kv")
+            .with_tag("host", "web01")
+            .try_send();
+        assert_eq!(
+            "prefix.some.kv:1.5:2.5|kv|#env:prod,host:web01",
+            res.unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_custom_invalid_type() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+
+        for metric_type in ["", "c|#tag", "c@0.5", "pct\n"] {
+            let err = client.custom("some.ratio", 42u64, metric_type).unwrap_err();
+            assert_eq!(ErrorKind::InvalidInput, err.kind(), "{}", metric_type);
+        }
+
+        let err = client.custom("some.ratio", f64::NAN, "pct").unwrap_err();
+        assert_eq!(ErrorKind::InvalidInput, err.kind());
+    }
+
+    #[test]
+    fn test_with_sampling_rate() {
+        assert_eq!("foo:1|c|@0.5", with_sampling_rate("foo:1|c", 0.5));
+        assert_eq!("foo:1|c|@0.5|#a:b|T123", with_sampling_rate("foo:1|c|#a:b|T123", 0.5));
+        assert_eq!("foo|@0.5", with_sampling_rate("foo", 0.5));
+        assert_eq!("foo:1|c|@0.25|#a:b", with_sampling_rate("foo:1|c|@0.5|#a:b", 0.5));
+        assert_eq!("foo:1|c|#a:b|@0.05", with_sampling_rate("foo:1|c|#a:b|@0.1", 0.5));
+        assert_eq!("foo:1|c|@0.5|#@a", with_sampling_rate("foo:1|c|#@a", 0.5));
+    }
+
+    #[test]
+    fn test_statsd_client_emit_raw() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClientBuilder::new("prefix", sink).with_tag("env", "prod").build();
+
+        client.emit_raw("other.requests:1|c|#region:us-west-1");
+        assert_eq!(b"other.requests:1|c|#region:us-west-1".to_vec(), rx.try_recv().unwrap());
+    }
+
+    #[test]
+    fn test_statsd_client_emit_raw_sample_rate() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClientBuilder::new("prefix", sink).with_sample_rate(0.0).build();
+
+        client.try_emit_raw("other.requests:1|c").unwrap();
+        client.try_emit_raw("_e{5,4}:title|text").unwrap();
+
+        assert_eq!(b"_e{5,4}:title|text".to_vec(), rx.try_recv().unwrap());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_statsd_client_emit_raw_invalid() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+
+        assert_eq!(ErrorKind::InvalidInput, client.try_emit_raw("").unwrap_err().kind());
+        assert_eq!(
+            ErrorKind::InvalidInput,
+            client.try_emit_raw("foo:1|c\nbar:1|c").unwrap_err().kind()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_emit_raw_error_handler() {
+        let failed = Arc::new(Mutex::new(Vec::new()));
+        let failed_ref = failed.clone();
+        let client = StatsdClient::builder("prefix", ErrorMetricSink::always())
+            .with_metric_error_handler(move |metric, _| failed_ref.lock().unwrap().push(metric.to_string()))
+            .build();
+
+        client.emit_raw("other.requests:1|c");
+        assert_eq!(vec!["other.requests:1|c".to_string()], *failed.lock().unwrap());
+    }
+
+    #[derive(Clone, Default)]
+    struct BatchMetricSink {
+        batches: Arc<Mutex<Vec<Vec<String>>>>,
+    }
+
+    impl MetricSink for BatchMetricSink {
+        fn emit(&self, metric: &str) -> io::Result<usize> {
+            self.emit_batch(&[metric])
+        }
+
+        fn emit_batch(&self, metrics: &[&str]) -> io::Result<usize> {
+            let batch = metrics.iter().map(|m| m.to_string()).collect();
+            self.batches.lock().unwrap().push(batch);
+            Ok(metrics.iter().map(|m| m.len()).sum())
+        }
+    }
+
+    #[test]
+    fn test_statsd_client_emit_all() {
+        let sink = BatchMetricSink::default();
+        let client = StatsdClient::from_sink("prefix", sink.clone());
+
+        client
+            .emit_all(&[Counter::new("prefix.", "foo", 1), Counter::new("prefix.", "bar", 2)])
+            .unwrap();
+        client.emit_all::<Counter>(&[]).unwrap();
+
+        assert_eq!(
+            vec![vec!["prefix.foo:1|c".to_string(), "prefix.bar:2|c".to_string()]],
+            *sink.batches.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_batch() {
+        let sink = BatchMetricSink::default();
+        let client = StatsdClient::builder("prefix", sink.clone())
+            .with_tag("env", "prod")
+            .build();
+
+        let batch = client
+            .batch()
+            .with_metric(client.count_with_tags("foo", 1).with_tag("host", "web01"))
+            .with_metric(client.gauge_with_tags("bar", 2).with_sample_rate(0.0))
+            .with_metric(client.time_with_tags("baz", 3));
+
+        assert_eq!(2, batch.len());
+        batch.try_send().unwrap();
+
+        assert_eq!(
+            vec![vec![
+                "prefix.foo:1|c|#env:prod,host:web01".to_string(),
+                "prefix.baz:3|ms|#env:prod".to_string(),
+            ]],
+            *sink.batches.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_batch_error() {
+        let sink = BatchMetricSink::default();
+        let client = StatsdClient::from_sink("prefix", sink.clone());
+
+        let res = client
+            .batch()
+            .with_metric(client.count_with_tags("foo", f64::NAN))
+            .with_metric(client.count_with_tags("bar", 1))
+            .try_send();
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+        assert_eq!(vec![vec!["prefix.bar:1|c".to_string()]], *sink.batches.lock().unwrap());
+    }
+
+    #[test]
+    fn test_statsd_client_batch_error_and_sink_error() {
+        let client = StatsdClient::from_sink("prefix", ErrorMetricSink::always());
+
+        let res = client
+            .batch()
+            .with_metric(client.count_with_tags("foo", f64::NAN))
+            .with_metric(client.count_with_tags("bar", 1))
+            .try_send();
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_client_scoped() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClientBuilder::new("prefix", sink).with_tag("env", "prod").build();
+        let db = client.scoped("db");
+        let queries = db.scoped(".queries.");
+
+        db.count("connections", 1).unwrap();
+        queries.count("slow", 2).unwrap();
+        client.scoped("").count("requests", 3).unwrap();
+
+        assert_eq!(b"prefix.db.connections:1|c|#env:prod".to_vec(), rx.try_recv().unwrap());
+        assert_eq!(b"prefix.db.queries.slow:2|c|#env:prod".to_vec(), rx.try_recv().unwrap());
+        assert_eq!(b"prefix.requests:3|c|#env:prod".to_vec(), rx.try_recv().unwrap());
+    }
+
+    #[test]
+    fn test_statsd_client_scoped_empty_prefix() {
+        let client = StatsdClient::from_sink("", NopMetricSink);
+        let res = client.scoped("db").count("queries", 1);
+
+        assert_eq!("db.queries:1|c", res.unwrap().as_metric_str());
     }
 
     #[test]
@@ -1180,6 +3405,58 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_statsd_client_tags_override_default_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("env", "production")
+            .with_tag("host", "web01")
+            .build();
+        let res = client
+            .count_with_tags("some.counter", 3)
+            .with_tag("env", "staging")
+            .try_send();
+
+        assert_eq!(
+            "prefix.some.counter:3|c|#host:web01,env:staging",
+            res.unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_builder_with_tags() {
+        let mut tags = BTreeMap::new();
+        tags.insert("env", "production");
+        tags.insert("host", "web01");
+
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tags(&tags)
+            .with_tags(vec![("shard".to_string(), 4)])
+            .build();
+        let res = client.count_with_tags("some.counter", 3).try_send();
+
+        assert_eq!(
+            "prefix.some.counter:3|c|#env:production,host:web01,shard:4",
+            res.unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_graphite_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag_format(TagFormat::Graphite)
+            .with_tag("env", "production")
+            .build();
+        let res = client
+            .gauge_with_tags("some.gauge", 3)
+            .with_tag("host", "web01")
+            .try_send();
+
+        assert_eq!(
+            "prefix.some.gauge;env=production;host=web01:3|g",
+            res.unwrap().as_metric_str()
+        );
+    }
+
     #[test]
     fn test_statsd_client_count_with_tags() {
         let client = StatsdClient::from_sink("prefix", NopMetricSink);
@@ -1198,43 +3475,171 @@ mod tests {
             .build();
         let res = client.count_with_tags("some.counter", 3).try_send();
 
-        assert_eq!("prefix.some.counter:3|c|#hello:world", res.unwrap().as_metric_str());
+        assert_eq!("prefix.some.counter:3|c|#hello:world", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_incr_with_tags() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.incr_with_tags("some.counter").with_tag("foo", "bar").try_send();
+
+        assert_eq!("prefix.some.counter:1|c|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_incr_with_default_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("foo", "bar")
+            .build();
+        let res = client.incr_with_tags("some.counter").try_send();
+
+        assert_eq!("prefix.some.counter:1|c|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_decr_with_tags() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.decr_with_tags("some.counter").with_tag("foo", "bar").try_send();
+
+        assert_eq!("prefix.some.counter:-1|c|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_decr_with_default_tags() {
+        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
+            .with_tag("foo", "bar")
+            .build();
+        let res = client.decr_with_tags("some.counter").try_send();
+
+        assert_eq!("prefix.some.counter:-1|c|#foo:bar", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_count_wide_integers() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+
+        let res = client.count("some.counter", u64::MAX);
+        assert_eq!(
+            "prefix.some.counter:18446744073709551615|c",
+            res.unwrap().as_metric_str()
+        );
+
+        let res = client.count("some.counter", u128::from(u64::MAX));
+        assert_eq!(
+            "prefix.some.counter:18446744073709551615|c",
+            res.unwrap().as_metric_str()
+        );
+
+        let res = client.count("some.counter", i128::from(i64::MIN));
+        assert_eq!(
+            "prefix.some.counter:-9223372036854775808|c",
+            res.unwrap().as_metric_str()
+        );
+
+        let res = client.count("some.counter", u128::from(u64::MAX) + 1);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+
+        let res = client.count("some.counter", i128::from(i64::MIN) - 1);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+
+        let res = client.custom("some.custom", -5i128, "x");
+        assert_eq!("prefix.some.custom:-5|x", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_invalid_value_metric() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let err = client.gauge("some.gauge", f64::NAN).unwrap_err();
+        assert_eq!(Some("prefix.some.gauge"), err.metric());
+
+        let err = client.time("some.timer", Duration::MAX).unwrap_err();
+        assert_eq!(Some("prefix.some.timer"), err.metric());
+    }
+
+    #[test]
+    fn test_statsd_client_count_f64() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+
+        let res = client.count("some.counter", 0.25);
+        assert_eq!("prefix.some.counter:0.25|c", res.unwrap().as_metric_str());
+
+        let res = client.count("some.counter", -1.5);
+        assert_eq!("prefix.some.counter:-1.5|c", res.unwrap().as_metric_str());
+
+        let res = client.count("some.counter", f64::NAN);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
     }
 
     #[test]
-    fn test_statsd_client_incr_with_tags() {
+    fn test_statsd_client_meter_f64() {
         let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client.incr_with_tags("some.counter").with_tag("foo", "bar").try_send();
 
-        assert_eq!("prefix.some.counter:1|c|#foo:bar", res.unwrap().as_metric_str());
+        let res = client.meter("some.meter", 2.5);
+        assert_eq!("prefix.some.meter:2.5|m", res.unwrap().as_metric_str());
+
+        let res = client.meter("some.meter", f64::INFINITY);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
     }
 
     #[test]
-    fn test_statsd_client_incr_with_default_tags() {
-        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
-            .with_tag("foo", "bar")
-            .build();
-        let res = client.incr_with_tags("some.counter").try_send();
+    fn test_statsd_client_non_finite_error() {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink.clone());
 
-        assert_eq!("prefix.some.counter:1|c|#foo:bar", res.unwrap().as_metric_str());
+        let res = client.gauge("some.gauge", f64::NAN);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+
+        let res = client.histogram("some.histogram", f64::INFINITY);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+
+        let res = client.distribution("some.distribution", vec![1.0, f64::NEG_INFINITY]);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+
+        assert!(sink.metrics().is_empty());
     }
 
     #[test]
-    fn test_statsd_client_decr_with_tags() {
-        let client = StatsdClient::from_sink("prefix", NopMetricSink);
-        let res = client.decr_with_tags("some.counter").with_tag("foo", "bar").try_send();
+    fn test_statsd_client_non_finite_skip() {
+        let sink = RecordingMetricSink::new();
+        let errors = Arc::new(AtomicUsize::new(0));
+        let errors_ref = errors.clone();
+        let client = StatsdClient::builder("prefix", sink.clone())
+            .with_non_finite_policy(NonFinitePolicy::Skip)
+            .with_error_handler(move |_e| {
+                errors_ref.fetch_add(1, Ordering::Release);
+            })
+            .build();
 
-        assert_eq!("prefix.some.counter:-1|c|#foo:bar", res.unwrap().as_metric_str());
+        let res = client.gauge("some.gauge", f64::NAN);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+        let res = client.histogram("some.histogram", f64::INFINITY);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+        assert!(client.gauge("some.gauge", 1.5).is_ok());
+        client.distribution_with_tags("some.distribution", f64::NAN).send();
+
+        let mut buf = String::new();
+        let res = client
+            .gauge_with_tags("some.gauge", f64::NAN)
+            .try_send_with_buffer(&mut buf);
+        assert!(res.is_ok());
+        assert!(buf.is_empty());
+
+        assert_eq!(vec!["prefix.some.gauge:1.5|g"], sink.metrics());
+        assert_eq!(0, errors.load(Ordering::Acquire));
     }
 
     #[test]
-    fn test_statsd_client_decr_with_default_tags() {
-        let client = StatsdClientBuilder::new("prefix", NopMetricSink)
-            .with_tag("foo", "bar")
+    fn test_statsd_client_non_finite_clamp() {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink.clone())
+            .with_non_finite_policy(NonFinitePolicy::Clamp)
             .build();
-        let res = client.decr_with_tags("some.counter").try_send();
 
-        assert_eq!("prefix.some.counter:-1|c|#foo:bar", res.unwrap().as_metric_str());
+        assert!(client.gauge("some.gauge", f64::NEG_INFINITY).is_ok());
+        let res = client.gauge("some.gauge", f64::NAN);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+
+        assert_eq!(vec![format!("prefix.some.gauge:{}|g", f64::MIN)], sink.metrics());
     }
 
     #[test]
@@ -1294,6 +3699,42 @@ mod tests {
         assert_eq!("prefix.key:157:158:159|ms", res.unwrap().as_metric_str());
     }
 
+    #[test]
+    fn test_statsd_client_time_duration_client_unit() {
+        let client = StatsdClient::builder("prefix", NopMetricSink)
+            .with_timer_unit(TimeUnit::Microseconds)
+            .build();
+
+        let res = client.time("key", Duration::from_micros(1570));
+        assert_eq!("prefix.key:1570|ms", res.unwrap().as_metric_str());
+
+        let res = client.time("key", [Duration::from_micros(1570), Duration::from_nanos(1580)]);
+        assert_eq!("prefix.key:1570:1|ms", res.unwrap().as_metric_str());
+
+        // Plain values aren't affected by the unit
+        let res = client.time("key", 157);
+        assert_eq!("prefix.key:157|ms", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_time_scaled_duration() {
+        let client = StatsdClient::builder("prefix", NopMetricSink)
+            .with_timer_unit(TimeUnit::Microseconds)
+            .build();
+
+        let res = client.time(
+            "key",
+            ScaledDuration::new(Duration::from_micros(1570), TimeUnit::Nanoseconds),
+        );
+        assert_eq!("prefix.key:1570000|ms", res.unwrap().as_metric_str());
+
+        let res = client.time(
+            "key",
+            ScaledDuration::new(Duration::from_secs(u64::MAX), TimeUnit::Nanoseconds),
+        );
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
     #[test]
     fn test_statsd_client_time_duration_with_overflow() {
         let client = StatsdClient::from_sink("prefix", NopMetricSink);
@@ -1354,6 +3795,40 @@ mod tests {
         assert_eq!("prefix.key:157:158:159|ms|#foo:bar,quux", res.unwrap().as_metric_str());
     }
 
+    #[test]
+    fn test_statsd_client_packed_slices_and_arrays() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let timings: &[u64] = &[101, 105, 112];
+
+        let res = client.time("api.latency", timings);
+        assert_eq!("prefix.api.latency:101:105:112|ms", res.unwrap().as_metric_str());
+
+        let res = client.time("api.latency", [Duration::from_millis(3), Duration::from_millis(4)]);
+        assert_eq!("prefix.api.latency:3:4|ms", res.unwrap().as_metric_str());
+
+        let res = client.histogram("some.histogram", [1.5, 2.5]);
+        assert_eq!("prefix.some.histogram:1.5:2.5|h", res.unwrap().as_metric_str());
+
+        let res = client.distribution("some.distribution", &[7u64, 8][..]);
+        assert_eq!("prefix.some.distribution:7:8|d", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_packed_empty() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let empty: &[u64] = &[];
+
+        assert_eq!(ErrorKind::InvalidInput, client.time("key", empty).unwrap_err().kind());
+        assert_eq!(
+            ErrorKind::InvalidInput,
+            client.time("key", Vec::<u64>::new()).unwrap_err().kind()
+        );
+        assert_eq!(
+            ErrorKind::InvalidInput,
+            client.distribution("key", Vec::<f64>::new()).unwrap_err().kind()
+        );
+    }
+
     #[test]
     fn test_statsd_client_time_duration_with_tags_with_overflow() {
         let client = StatsdClient::from_sink("prefix", NopMetricSink);
@@ -1448,6 +3923,48 @@ mod tests {
         assert_eq!("prefix.key:210|h", res.unwrap().as_metric_str());
     }
 
+    #[test]
+    fn test_statsd_client_histogram_duration_client_unit() {
+        let client = StatsdClient::builder("prefix", NopMetricSink)
+            .with_histogram_unit(TimeUnit::Microseconds)
+            .build();
+
+        let res = client.histogram("key", Duration::from_micros(210));
+        assert_eq!("prefix.key:210|h", res.unwrap().as_metric_str());
+
+        let res = client.histogram("key", vec![Duration::from_micros(210), Duration::from_millis(2)]);
+        assert_eq!("prefix.key:210:2000|h", res.unwrap().as_metric_str());
+
+        let res = client.histogram(
+            "key",
+            ScaledDuration::new(Duration::from_micros(210), TimeUnit::Nanoseconds),
+        );
+        assert_eq!("prefix.key:210000|h", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_histogram_fractional_duration() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+
+        let res = client.histogram(
+            "key",
+            ScaledDuration::fractional(Duration::from_micros(125), TimeUnit::Milliseconds),
+        );
+        assert_eq!("prefix.key:0.125|h", res.unwrap().as_metric_str());
+
+        let res = client.histogram(
+            "key",
+            ScaledDuration::fractional(Duration::from_nanos(1500), TimeUnit::Microseconds),
+        );
+        assert_eq!("prefix.key:1.5|h", res.unwrap().as_metric_str());
+
+        let res = client.histogram(
+            "key",
+            ScaledDuration::fractional(Duration::from_nanos(42), TimeUnit::Nanoseconds),
+        );
+        assert_eq!("prefix.key:42|h", res.unwrap().as_metric_str());
+    }
+
     #[test]
     fn test_statsd_client_histogram_multiple_durations() {
         let client = StatsdClient::from_sink("prefix", NopMetricSink);
@@ -1517,6 +4034,100 @@ mod tests {
         assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
     }
 
+    struct Bytes(u64);
+
+    impl ToMetricValue for Bytes {
+        fn try_to_metric_value(self) -> MetricResult<MetricValue> {
+            Ok(MetricValue::Unsigned(self.0))
+        }
+    }
+
+    struct Samples(Vec<f64>);
+
+    impl ToMetricValue for Samples {
+        fn try_to_metric_value(self) -> MetricResult<MetricValue> {
+            Ok(MetricValue::PackedFloat(self.0))
+        }
+    }
+
+    #[test]
+    fn test_statsd_client_metric_value_all_types() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+
+        assert_eq!("prefix.key:8|c", client.count("key", Bytes(8)).unwrap().as_metric_str());
+        assert_eq!("prefix.key:8|ms", client.time("key", Bytes(8)).unwrap().as_metric_str());
+        assert_eq!("prefix.key:8|g", client.gauge("key", Bytes(8)).unwrap().as_metric_str());
+        assert_eq!("prefix.key:8|m", client.meter("key", Bytes(8)).unwrap().as_metric_str());
+        assert_eq!(
+            "prefix.key:8|h",
+            client.histogram("key", Bytes(8)).unwrap().as_metric_str()
+        );
+        assert_eq!(
+            "prefix.key:8|d",
+            client.distribution("key", Bytes(8)).unwrap().as_metric_str()
+        );
+        assert_eq!("prefix.key:8|s", client.set("key", Bytes(8)).unwrap().as_metric_str());
+        assert_eq!(
+            "prefix.key:8|b",
+            client.custom("key", Bytes(8), "b").unwrap().as_metric_str()
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_metric_value_non_zero() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+
+        let res = client.count("key", NonZeroU64::new(3).unwrap());
+        assert_eq!("prefix.key:3|c", res.unwrap().as_metric_str());
+
+        let res = client.gauge("key", NonZeroU32::new(4).unwrap());
+        assert_eq!("prefix.key:4|g", res.unwrap().as_metric_str());
+    }
+
+    #[test]
+    fn test_statsd_client_metric_value_packed() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+
+        let res = client.histogram("key", Samples(vec![1.5, 2.5]));
+        assert_eq!("prefix.key:1.5:2.5|h", res.unwrap().as_metric_str());
+
+        let res = client.histogram("key", Samples(vec![]));
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+
+        let res = client.gauge("key", Samples(vec![1.5, 2.5]));
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_client_distribution_duration() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+
+        let res = client.distribution("key", Duration::from_nanos(210));
+        assert_eq!("prefix.key:210|d", res.unwrap().as_metric_str());
+
+        let res = client.distribution("key", [Duration::from_nanos(210), Duration::from_nanos(211)]);
+        assert_eq!("prefix.key:210:211|d", res.unwrap().as_metric_str());
+
+        let res = client.distribution("key", Duration::from_secs(u64::MAX));
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_client_distribution_duration_client_unit() {
+        let client = StatsdClient::builder("prefix", NopMetricSink)
+            .with_distribution_unit(TimeUnit::Milliseconds)
+            .build();
+
+        let res = client.distribution("key", Duration::from_micros(2500));
+        assert_eq!("prefix.key:2|d", res.unwrap().as_metric_str());
+
+        let res = client.distribution(
+            "key",
+            ScaledDuration::fractional(Duration::from_micros(2500), TimeUnit::Milliseconds),
+        );
+        assert_eq!("prefix.key:2.5|d", res.unwrap().as_metric_str());
+    }
+
     #[test]
     fn test_statsd_client_distribution_with_tags() {
         let client = StatsdClient::from_sink("prefix", NopMetricSink);
@@ -1604,6 +4215,90 @@ mod tests {
         assert_eq!("prefix.some.key:1|c|#test:a", String::from_utf8(sent).unwrap());
     }
 
+    #[test]
+    fn test_statsd_client_event_with_default_tags() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClientBuilder::new("prefix", sink)
+            .with_tag("env", "production")
+            .with_container_id("1234")
+            .build();
+
+        client
+            .event_with_tags("Deploy", "Deployed v1.2.3")
+            .with_alert_type(EventAlertType::Success)
+            .with_tag("service", "api")
+            .send();
+
+        let sent = String::from_utf8(rx.try_recv().unwrap()).unwrap();
+        assert_eq!(
+            "_e{6,15}:Deploy|Deployed v1.2.3|t:success|#env:production,service:api|c:1234",
+            sent
+        );
+    }
+
+    #[test]
+    fn test_statsd_client_event_empty_title() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.event("", "Deployed v1.2.3");
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_client_service_check_with_default_tags() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClientBuilder::new("prefix", sink)
+            .with_tag("env", "production")
+            .build();
+
+        client
+            .service_check_with_tags("db.up", ServiceCheckStatus::Warning)
+            .with_tag("db", "users")
+            .with_message("replication lag")
+            .send();
+
+        let sent = String::from_utf8(rx.try_recv().unwrap()).unwrap();
+        assert_eq!("_sc|prefix.db.up|1|#env:production,db:users|m:replication lag", sent);
+    }
+
+    #[test]
+    fn test_statsd_client_service_check_empty_name() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.service_check("", ServiceCheckStatus::Ok);
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_statsd_client_with_tags_sent_to_sink() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
+
+        client
+            .time_with_tags("some.timer", 12)
+            .with_tag("region", "us-east-1")
+            .send();
+        client.gauge_with_tags("some.gauge", 4).with_tag_value("beta").send();
+        client
+            .histogram_with_tags("some.histogram", 7)
+            .with_tag("region", "us-east-1")
+            .with_tag_value("beta")
+            .send();
+        client.set_with_tags("some.set", 3).with_tag("host", "web01").send();
+
+        let sent: Vec<String> = rx.try_iter().map(|v| String::from_utf8(v).unwrap()).collect();
+
+        assert_eq!(
+            vec![
+                "prefix.some.timer:12|ms|#region:us-east-1",
+                "prefix.some.gauge:4|g|#beta",
+                "prefix.some.histogram:7|h|#region:us-east-1,beta",
+                "prefix.some.set:3|s|#host:web01",
+            ],
+            sent
+        );
+    }
+
     #[test]
     fn test_statsd_client_with_tags_send_error() {
         struct ErrorSink;
@@ -1822,4 +4517,212 @@ mod tests {
         client.distribution("some.distribution", vec![248.0]).unwrap();
         client.set("some.set", 5).unwrap();
     }
+
+    // Wait for at least the given number of metrics to be recorded
+    fn wait_for_metrics(sink: &RecordingMetricSink, count: usize) -> Vec<String> {
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while sink.len() < count && Instant::now() < deadline {
+            thread::sleep(Duration::from_millis(5));
+        }
+
+        sink.metrics()
+    }
+
+    #[test]
+    fn test_statsd_client_register_gauge() {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink.clone())
+            .with_gauge_interval(Duration::from_millis(10))
+            .build();
+
+        let depth = Arc::new(AtomicUsize::new(3));
+        let value = depth.clone();
+        let registration = client
+            .scoped("queue")
+            .register_gauge("depth", move || value.load(Ordering::SeqCst) as u64);
+
+        let metrics = wait_for_metrics(&sink, 1);
+        assert_eq!("prefix.queue.depth:3|g", metrics[0]);
+
+        depth.store(7, Ordering::SeqCst);
+        let metrics = wait_for_metrics(&sink, metrics.len() + 2);
+        assert_eq!("prefix.queue.depth:7|g", metrics[metrics.len() - 1]);
+
+        registration.unregister();
+        thread::sleep(Duration::from_millis(30));
+        let sent = sink.len();
+        thread::sleep(Duration::from_millis(30));
+
+        assert_eq!(sent, sink.len());
+    }
+
+    #[test]
+    fn test_statsd_client_register_gauge_drop() {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink.clone())
+            .with_gauge_interval(Duration::from_millis(10))
+            .build();
+
+        let registration = client.register_gauge("dropped", || 1u64);
+        client.register_gauge("detached", || 2u64).detach();
+        drop(registration);
+
+        let metrics = wait_for_metrics(&sink, 2);
+        assert!(metrics.iter().all(|m| m == "prefix.detached:2|g"));
+    }
+
+    #[test]
+    fn test_statsd_client_register_gauge_thread_exits() {
+        let client = StatsdClient::builder("prefix", NopMetricSink)
+            .with_gauge_interval(Duration::from_secs(60))
+            .build();
+
+        client.register_gauge("some.gauge", || 1u64).detach();
+        let dropped = client.shared.gauges.dropped();
+        drop(client);
+
+        // The thread exits as soon as the client is dropped instead of after
+        // the interval, dropping its reference to the flag.
+        let start = Instant::now();
+        while Arc::strong_count(&dropped) > 1 {
+            assert!(start.elapsed() < Duration::from_secs(5));
+            thread::sleep(Duration::from_millis(5));
+        }
+    }
+
+    #[test]
+    fn test_statsd_client_install_panic_hook() {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink.clone());
+        client.install_panic_hook("panics");
+
+        let res = thread::Builder::new()
+            .name("panic-hook-worker".into())
+            .spawn(|| panic!("worker failed"))
+            .unwrap()
+            .join();
+
+        // Other tests that panic while this hook is installed record counters
+        // as well, tagged with the names of their threads.
+        let expected = "prefix.panics:1|c|#thread:panic-hook-worker";
+        assert!(res.is_err());
+        assert_eq!(1, sink.metrics().iter().filter(|m| *m == expected).count());
+    }
+
+    #[test]
+    fn test_statsd_client_install_panic_hook_client_dropped() {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink.clone());
+        client.install_panic_hook("dropped.panics");
+        drop(client);
+
+        let res = thread::Builder::new()
+            .name("panic-hook-dropped-worker".into())
+            .spawn(|| panic!("worker failed"))
+            .unwrap()
+            .join();
+
+        assert!(res.is_err());
+        assert!(sink.metrics().iter().all(|m| !m.starts_with("prefix.dropped.panics")));
+    }
+
+    #[test]
+    fn test_statsd_client_install_panic_hook_sink_locked() {
+        // Sink that panics while holding its lock when emitting a metric
+        struct LockingMetricSink(Mutex<()>);
+
+        impl MetricSink for LockingMetricSink {
+            fn emit(&self, metric: &str) -> io::Result<usize> {
+                let _guard = self.0.lock().unwrap();
+                if metric.starts_with("prefix.trigger") {
+                    panic!("sink failed while locked");
+                }
+
+                Ok(metric.len())
+            }
+        }
+
+        let client = StatsdClient::from_sink("prefix", LockingMetricSink(Mutex::new(())));
+        client.install_panic_hook("locked.panics");
+
+        // Sending the counter waits on the lock held by the panicking thread
+        // and then panics because the lock is poisoned, neither of which
+        // deadlocks or aborts the process.
+        let res = thread::Builder::new()
+            .name("panic-hook-locked-worker".into())
+            .spawn(move || client.incr("trigger"))
+            .unwrap()
+            .join();
+
+        assert!(res.is_err());
+    }
+
+    #[test]
+    fn test_statsd_client_register_gauge_panic() {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink.clone())
+            .with_gauge_interval(Duration::from_millis(10))
+            .build();
+
+        client
+            .register_gauge("broken", || -> u64 { panic!("gauge callback failed") })
+            .detach();
+        client.register_gauge("working", || 1u64).detach();
+
+        let metrics = wait_for_metrics(&sink, 2);
+        assert!(metrics.iter().all(|m| m == "prefix.working:1|g"));
+    }
+
+    #[test]
+    fn test_statsd_client_disabled_metrics_not_sent() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink).with_disabled(true).build();
+
+        let counter = client.incr("some.counter").unwrap();
+        client.gauge_with_tags("some.gauge", 5).with_tag("foo", "bar").send();
+        client.event("Deploy", "Deployed v1.2.3").unwrap();
+        client.service_check("db", ServiceCheckStatus::Ok).unwrap();
+        client.counter_handle("some.counter").incr().unwrap();
+        client
+            .batch()
+            .with_metric(client.incr_with_tags("some.counter"))
+            .try_send()
+            .unwrap();
+        client.try_emit_raw("other.counter:1|c").unwrap();
+        client.flush().unwrap();
+
+        assert_eq!("prefix.some.counter:1|c", counter.as_metric_str());
+        assert!(rx.try_recv().is_err(), "expected no metrics to be sent");
+    }
+
+    #[test]
+    fn test_statsd_client_disabled_send_does_nothing() {
+        let errors = Arc::new(AtomicUsize::new(0));
+        let errors_ref = errors.clone();
+        let client = StatsdClient::builder("prefix", ErrorMetricSink::always())
+            .with_error_handler(move |_| {
+                errors_ref.fetch_add(1, Ordering::Relaxed);
+            })
+            .with_disabled(true)
+            .build();
+
+        client.count_with_tags("some.counter", 1).send();
+        client.count_with_tags("some.counter", f64::NAN).send();
+        client
+            .count_with_tags("some.counter", 1)
+            .with_tag_lazy("shard", || panic!("tag computed by disabled client"))
+            .send();
+
+        assert_eq!(0, errors.load(Ordering::Relaxed));
+    }
+
+    #[test]
+    fn test_statsd_client_disabled_try_send_invalid() {
+        let client = StatsdClient::builder("prefix", NopMetricSink)
+            .with_disabled(true)
+            .build();
+        let res = client.count("some.counter", f64::NAN);
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
 }
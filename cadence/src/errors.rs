@@ -0,0 +1,109 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::SystemTime;
+
+use crate::types::{ErrorKind, MetricError};
+
+/// Errors returned by the sink of a `StatsdClient`, counted by kind, along
+/// with the most recent one.
+///
+/// Only errors from the sink are included, not errors caused by invalid
+/// metrics, so that these stats can be used to tell if metrics are making it
+/// out of the application, for example as part of a health check.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ErrorStats {
+    pub io_errors: u64,
+    pub timeouts: u64,
+    pub queue_full: u64,
+    pub last_error: Option<LastError>,
+}
+
+impl ErrorStats {
+    /// Return the number of errors of every kind.
+    pub fn total(&self) -> u64 {
+        self.io_errors + self.timeouts + self.queue_full
+    }
+}
+
+/// Most recent error returned by the sink of a `StatsdClient`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct LastError {
+    pub kind: ErrorKind,
+    pub message: String,
+    pub at: SystemTime,
+}
+
+// Thread-safe counts of errors updated by a client and converted to an
+// instance of `ErrorStats` for callers.
+#[derive(Debug, Default)]
+pub(crate) struct ErrorTracker {
+    io_errors: AtomicU64,
+    timeouts: AtomicU64,
+    queue_full: AtomicU64,
+    last_error: Mutex<Option<LastError>>,
+}
+
+impl ErrorTracker {
+    pub(crate) fn record(&self, err: &MetricError) {
+        let kind = err.kind();
+        let counter = match kind {
+            ErrorKind::Timeout => &self.timeouts,
+            ErrorKind::QueueFull => &self.queue_full,
+            _ => &self.io_errors,
+        };
+
+        counter.fetch_add(1, Ordering::Relaxed);
+        *self.last_error.lock().unwrap() = Some(LastError {
+            kind,
+            message: err.to_string(),
+            at: SystemTime::now(),
+        });
+    }
+
+    pub(crate) fn stats(&self) -> ErrorStats {
+        ErrorStats {
+            io_errors: self.io_errors.load(Ordering::Relaxed),
+            timeouts: self.timeouts.load(Ordering::Relaxed),
+            queue_full: self.queue_full.load(Ordering::Relaxed),
+            last_error: self.last_error.lock().unwrap().clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ErrorTracker;
+    use crate::types::{queue_full, ErrorKind, MetricError};
+    use std::io;
+
+    #[test]
+    fn test_error_tracker_record() {
+        let tracker = ErrorTracker::default();
+        assert_eq!(0, tracker.stats().total());
+        assert_eq!(None, tracker.stats().last_error);
+
+        tracker.record(&MetricError::from(io::Error::new(io::ErrorKind::TimedOut, "timeout")));
+        tracker.record(&MetricError::from(queue_full()));
+        tracker.record(&MetricError::from(io::Error::new(io::ErrorKind::BrokenPipe, "broken")));
+
+        let stats = tracker.stats();
+        assert_eq!(1, stats.io_errors);
+        assert_eq!(1, stats.timeouts);
+        assert_eq!(1, stats.queue_full);
+        assert_eq!(3, stats.total());
+
+        let last = stats.last_error.unwrap();
+        assert_eq!(ErrorKind::IoError, last.kind);
+        assert_eq!("broken", last.message);
+    }
+}
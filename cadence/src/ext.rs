@@ -22,16 +22,17 @@
 //! valid for each type of metric. They also contain conversion logic for
 //! the types in some cases (such as in the case of `Duration` objects).
 //! These can be used to allow your own custom types to be converted to
-//! metric values that Cadence understands.
+//! metric values that Cadence understands. The `ToMetricValue` trait can be
+//! implemented to make a custom numeric type valid for all of them at once.
 //!
 //! In summary, most users don't need to worry about these types but they
 //! are available for advanced use cases and subject to the same guarantees
 //! as the rest of the API (semantic versioning, etc.).
 
-pub use crate::builder::MetricValue;
+pub use crate::builder::{MetricValue, ToTag, ToTagStr};
 pub use crate::client::{
-    MetricBackend, ToCounterValue, ToDistributionValue, ToGaugeValue, ToHistogramValue, ToMeterValue, ToSetValue,
-    ToTimerValue,
+    MetricBackend, ToCounterValue, ToCustomValue, ToDistributionValue, ToGaugeValue, ToHistogramValue, ToMeterValue,
+    ToMetricValue, ToSetValue, ToTimerValue,
 };
 pub use crate::io::MultiLineWriter;
 pub use crate::sinks::SocketStats;
@@ -0,0 +1,147 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::panic::RefUnwindSafe;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, Condvar, Mutex, Weak};
+use std::time::Duration;
+
+use crate::client::StatsdClient;
+
+// Callback that sends the current value of a registered gauge using a client
+// with the prefix it was registered with.
+pub(crate) type GaugeCallback = Arc<dyn Fn(&StatsdClient) + Send + Sync + RefUnwindSafe>;
+
+/// Gauge registered with `StatsdClient::register_gauge()` whose value is sent
+/// periodically until it is unregistered.
+///
+/// The gauge is unregistered when this is dropped. Use `.detach()` for gauges
+/// that should be sent for the life of the client instead.
+#[must_use = "the gauge is unregistered when the registration is dropped, use `.detach()` to keep sending it"]
+pub struct GaugeRegistration {
+    registry: Weak<GaugeRegistry>,
+    id: u64,
+}
+
+impl GaugeRegistration {
+    /// Stop sending the value of the registered gauge.
+    ///
+    /// This is the same as dropping the registration.
+    pub fn unregister(self) {}
+
+    /// Keep sending the value of the registered gauge for as long as the
+    /// client is used, without having to hold on to the registration.
+    pub fn detach(mut self) {
+        self.registry = Weak::new();
+    }
+}
+
+impl Drop for GaugeRegistration {
+    fn drop(&mut self) {
+        if let Some(registry) = self.registry.upgrade() {
+            registry.unregister(self.id);
+        }
+    }
+}
+
+impl fmt::Debug for GaugeRegistration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GaugeRegistration").field("id", &self.id).finish()
+    }
+}
+
+// Gauges registered with a client and how often their values are sent. The
+// thread that sends them is started the first time a gauge is registered and
+// is woken up to exit when the registry is dropped along with the client.
+pub(crate) struct GaugeRegistry {
+    interval: Duration,
+    started: AtomicBool,
+    next_id: AtomicU64,
+    gauges: Mutex<Vec<(u64, String, GaugeCallback)>>,
+    dropped: Arc<(Mutex<bool>, Condvar)>,
+}
+
+impl GaugeRegistry {
+    pub(crate) fn new(interval: Duration) -> Self {
+        GaugeRegistry {
+            interval,
+            started: AtomicBool::new(false),
+            next_id: AtomicU64::new(0),
+            gauges: Mutex::new(Vec::new()),
+            dropped: Arc::new((Mutex::new(false), Condvar::new())),
+        }
+    }
+
+    pub(crate) fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    // Flag set when the registry is dropped, for the thread sending gauges to
+    // wait on between sends without keeping the client alive.
+    pub(crate) fn dropped(&self) -> Arc<(Mutex<bool>, Condvar)> {
+        self.dropped.clone()
+    }
+
+    // Add a gauge sent using a client with the given prefix.
+    pub(crate) fn register(self: &Arc<Self>, prefix: &str, callback: GaugeCallback) -> GaugeRegistration {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        self.gauges.lock().unwrap().push((id, prefix.to_owned(), callback));
+
+        GaugeRegistration {
+            registry: Arc::downgrade(self),
+            id,
+        }
+    }
+
+    fn unregister(&self, id: u64) {
+        self.gauges.lock().unwrap().retain(|(i, _, _)| *i != id);
+    }
+
+    // Return true the first time this is called, or after `.stopped()`, to
+    // indicate the thread sending gauges should be started.
+    pub(crate) fn should_start(&self) -> bool {
+        !self.started.swap(true, Ordering::AcqRel)
+    }
+
+    // Record that the thread sending gauges isn't running, for example because
+    // it could not be started.
+    pub(crate) fn stopped(&self) {
+        self.started.store(false, Ordering::Release);
+    }
+
+    // Return a copy of every registered gauge so that callbacks can be run
+    // without holding the lock.
+    pub(crate) fn callbacks(&self) -> Vec<(String, GaugeCallback)> {
+        self.gauges
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|(_, prefix, callback)| (prefix.clone(), callback.clone()))
+            .collect()
+    }
+}
+
+impl Drop for GaugeRegistry {
+    fn drop(&mut self) {
+        let (lock, cond) = &*self.dropped;
+        *lock.lock().unwrap() = true;
+        cond.notify_all();
+    }
+}
+
+impl fmt::Debug for GaugeRegistry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("GaugeRegistry")
+            .field("interval", &self.interval)
+            .field("gauges", &self.gauges.lock().unwrap().len())
+            .finish()
+    }
+}
@@ -0,0 +1,255 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Functions for emitting metrics using the global default `StatsdClient`.
+//!
+//! The global default client is set once, typically early in `main`, using
+//! `set_global_default`. After that, the functions in this module can be used
+//! anywhere in an application, including in library code, to emit metrics
+//! without passing a client around. If the global default client has not been
+//! set, these functions do nothing, similar to how the `log` crate discards log
+//! events when no logger has been set.
+//!
+//! Any errors encountered sending metrics will be handled by the error handler
+//! registered with the global default client.
+//!
+//! # Example
+//!
+//! ```
+//! use cadence::{NopMetricSink, StatsdClient};
+//!
+//! fn handle_request() {
+//!     cadence::global::incr("requests");
+//!     cadence::global::time("request.latency", 34);
+//! }
+//!
+//! let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+//! cadence::set_global_default(client).unwrap();
+//!
+//! handle_request();
+//! ```
+//!
+//! To emit metrics with tags, or other options not supported by these
+//! functions, use the global default client directly.
+//!
+//! ```
+//! use cadence::prelude::*;
+//!
+//! if let Some(client) = cadence::get_global_default() {
+//!     client.count_with_tags("requests", 1).with_tag("method", "GET").send();
+//! }
+//! ```
+
+use std::error::Error;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::client::{
+    Counted, CountedExt, Distributed, Gauged, Histogrammed, Metered, Setted, StatsdClient, Timed, ToCounterValue,
+    ToDistributionValue, ToGaugeValue, ToHistogramValue, ToMeterValue, ToSetValue, ToTimerValue,
+};
+use crate::state::SingletonHolder;
+
+static GLOBAL_DEFAULT: SingletonHolder<StatsdClient> = SingletonHolder::new();
+
+/// Error indicating that the global default `StatsdClient` could not be set
+/// because it had already been set.
+#[derive(Debug)]
+pub struct SetGlobalDefaultError {
+    _priv: (),
+}
+
+impl fmt::Display for SetGlobalDefaultError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt("global default StatsdClient instance already set", f)
+    }
+}
+
+impl Error for SetGlobalDefaultError {}
+
+/// Set the global default `StatsdClient` instance used by the functions in the
+/// `global` module.
+///
+/// The global default client can only be set once.
+///
+/// # Errors
+///
+/// This method will return an error if the global default client has already
+/// been set.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{NopMetricSink, StatsdClient};
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// assert!(cadence::set_global_default(client).is_ok());
+///
+/// let another = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// assert!(cadence::set_global_default(another).is_err());
+/// ```
+pub fn set_global_default<C>(client: C) -> Result<(), SetGlobalDefaultError>
+where
+    C: Into<Arc<StatsdClient>>,
+{
+    if GLOBAL_DEFAULT.set(client) {
+        Ok(())
+    } else {
+        Err(SetGlobalDefaultError { _priv: () })
+    }
+}
+
+/// Get the global default `StatsdClient` instance, or `None` if it has not
+/// been set.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{NopMetricSink, StatsdClient};
+///
+/// assert!(cadence::get_global_default().is_none());
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// cadence::set_global_default(client).unwrap();
+///
+/// assert!(cadence::get_global_default().is_some());
+/// ```
+pub fn get_global_default() -> Option<Arc<StatsdClient>> {
+    GLOBAL_DEFAULT.get()
+}
+
+/// Return true if the global default `StatsdClient` is set, false otherwise.
+pub fn is_global_default_set() -> bool {
+    GLOBAL_DEFAULT.is_set()
+}
+
+// Run the given function with the global default client if it has been set.
+fn with_global_default<F>(f: F)
+where
+    F: FnOnce(&StatsdClient),
+{
+    if let Some(client) = GLOBAL_DEFAULT.get() {
+        f(&client);
+    }
+}
+
+/// Increment the counter by `1` using the global default client.
+pub fn incr(key: &str) {
+    with_global_default(|client| client.incr_with_tags(key).send())
+}
+
+/// Decrement the counter by `1` using the global default client.
+pub fn decr(key: &str) {
+    with_global_default(|client| client.decr_with_tags(key).send())
+}
+
+/// Increment or decrement the counter by the given amount using the global
+/// default client.
+pub fn count<T>(key: &str, value: T)
+where
+    T: ToCounterValue,
+{
+    with_global_default(|client| client.count_with_tags(key, value).send())
+}
+
+/// Record a timing in milliseconds (or a `Duration`) using the global default
+/// client.
+pub fn time<T>(key: &str, time: T)
+where
+    T: ToTimerValue,
+{
+    with_global_default(|client| client.time_with_tags(key, time).send())
+}
+
+/// Record a gauge value using the global default client.
+pub fn gauge<T>(key: &str, value: T)
+where
+    T: ToGaugeValue,
+{
+    with_global_default(|client| client.gauge_with_tags(key, value).send())
+}
+
+/// Record a meter value using the global default client.
+pub fn meter<T>(key: &str, value: T)
+where
+    T: ToMeterValue,
+{
+    with_global_default(|client| client.meter_with_tags(key, value).send())
+}
+
+/// Record a single histogram value using the global default client.
+pub fn histogram<T>(key: &str, value: T)
+where
+    T: ToHistogramValue,
+{
+    with_global_default(|client| client.histogram_with_tags(key, value).send())
+}
+
+/// Record a single distribution value using the global default client.
+pub fn distribution<T>(key: &str, value: T)
+where
+    T: ToDistributionValue,
+{
+    with_global_default(|client| client.distribution_with_tags(key, value).send())
+}
+
+/// Record a single set value using the global default client.
+pub fn set<T>(key: &str, value: T)
+where
+    T: ToSetValue,
+{
+    with_global_default(|client| client.set_with_tags(key, value).send())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_global_default, is_global_default_set, set_global_default};
+    use crate::{RecordingMetricSink, StatsdClient};
+    use std::time::Duration;
+
+    // The global default can only be set once per process so everything that
+    // depends on it being set is tested here.
+    #[test]
+    fn test_global_default() {
+        super::incr("dropped");
+        assert!(!is_global_default_set());
+        assert!(get_global_default().is_none());
+
+        let sink = RecordingMetricSink::new();
+        set_global_default(StatsdClient::from_sink("prefix", sink.clone())).unwrap();
+        assert!(set_global_default(StatsdClient::from_sink("other", RecordingMetricSink::new())).is_err());
+        assert!(is_global_default_set());
+
+        super::incr("some.counter");
+        super::decr("some.counter");
+        super::count("some.counter", 4);
+        super::time("some.timer", Duration::from_millis(12));
+        super::gauge("some.gauge", 5.5);
+        super::meter("some.meter", 2);
+        super::histogram("some.histogram", 3);
+        super::distribution("some.distribution", 7);
+        super::set("some.set", 9);
+
+        assert_eq!(
+            vec![
+                "prefix.some.counter:1|c",
+                "prefix.some.counter:-1|c",
+                "prefix.some.counter:4|c",
+                "prefix.some.timer:12|ms",
+                "prefix.some.gauge:5.5|g",
+                "prefix.some.meter:2|m",
+                "prefix.some.histogram:3|h",
+                "prefix.some.distribution:7|d",
+                "prefix.some.set:9|s",
+            ],
+            sink.metrics()
+        );
+    }
+}
@@ -0,0 +1,402 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::marker::PhantomData;
+
+use crate::builder::{MetricFormatter, MetricValue};
+use crate::client::{StatsdClient, ToCounterValue, ToDistributionValue, ToGaugeValue, ToHistogramValue, ToTimerValue};
+use crate::types::{
+    Counter, Distribution, ErrorKind, Gauge, Histogram, Metric, MetricError, MetricResult, Timer, WriteMetric,
+};
+
+// Constructor of the formatter for the type of metric a handle emits
+pub(crate) type NewFormatter = for<'a> fn(&'a str, &'a str, MetricValue) -> MetricFormatter<'a>;
+
+/// Pre-registered metric that only formats its value each time it is sent.
+///
+/// The prefix, key, tags, and other settings of the client that created the
+/// handle are formatted once, when the handle is created or a tag is added to
+/// it. Sending a value with the handle only formats the value (and timestamp,
+/// if the client has a clock) before passing the metric to the sink of the
+/// client. This makes handles useful for metrics sent in hot paths of an
+/// application.
+///
+/// Handles own everything they need to send metrics, including a reference to
+/// the sink of the client, so they can be stored in long-lived structs.
+/// Unlike the methods of `StatsdClient`, the methods of handles don't return
+/// the metric that was sent to avoid allocating a string for it.
+///
+/// NOTE: The only way to instantiate a handle is via methods of `StatsdClient`
+/// such as `StatsdClient::counter_handle()`.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{SpyMetricSink, StatsdClient};
+///
+/// let (rx, sink) = SpyMetricSink::new();
+/// let client = StatsdClient::from_sink("my.app", sink);
+/// let requests = client.counter_handle("requests").with_tag("region", "us-east-1");
+///
+/// requests.incr().unwrap();
+/// requests.count(5).unwrap();
+///
+/// assert_eq!(b"my.app.requests:1|c|#region:us-east-1".to_vec(), rx.try_recv().unwrap());
+/// assert_eq!(b"my.app.requests:5|c|#region:us-east-1".to_vec(), rx.try_recv().unwrap());
+/// ```
+pub struct MetricHandle<T> {
+    client: StatsdClient,
+    key: String,
+    tags: Vec<(Option<String>, String)>,
+    new_fmt: NewFormatter,
+    metric_type: &'static str,
+    template: MetricResult<Template>,
+    type_: PhantomData<T>,
+}
+
+impl<T> MetricHandle<T>
+where
+    T: Metric + From<String>,
+{
+    pub(crate) fn new(client: StatsdClient, key: &str, new_fmt: NewFormatter) -> Self {
+        let mut handle = MetricHandle {
+            client,
+            key: key.to_owned(),
+            tags: Vec::new(),
+            new_fmt,
+            metric_type: new_fmt("", "", MetricValue::Unsigned(0)).metric_type().as_str(),
+            template: Err(MetricError::from((ErrorKind::InvalidInput, "handle not formatted"))),
+            type_: PhantomData,
+        };
+
+        handle.template = handle.format_template();
+        handle
+    }
+
+    /// Add a key-value tag to every metric sent by this handle.
+    pub fn with_tag(mut self, key: &str, value: &str) -> Self {
+        self.tags.push((Some(key.to_owned()), value.to_owned()));
+        self.template = self.format_template();
+        self
+    }
+
+    /// Add a value tag to every metric sent by this handle.
+    pub fn with_tag_value(mut self, value: &str) -> Self {
+        self.tags.push((None, value.to_owned()));
+        self.template = self.format_template();
+        self
+    }
+
+    // Formatting the template fails if the default sample rate of the client
+    // is invalid or the key or tags are rejected by its sanitize policy, which
+    // is reported when sending a value instead.
+    fn format_template(&self) -> MetricResult<Template> {
+        self.client
+            .handle_template::<T>(&self.key, self.new_fmt, &self.tags)
+            .map(|(metric, value_at)| Template { metric, value_at })
+    }
+
+    fn send(&self, value: MetricResult<MetricValue>) -> MetricResult<()> {
+        if self.client.is_disabled() {
+            return Ok(());
+        }
+
+        let template = self.template.as_ref().map_err(MetricError::duplicate)?;
+        let mut value = value?;
+        if !self.client.non_finite_policy().apply(&mut value)? {
+            return Ok(());
+        }
+
+        if !self.client.is_sampled(self.metric_type) {
+            return Ok(());
+        }
+
+        let metric = HandleMetric {
+            template,
+            value: &value,
+            timestamp: self.client.timestamp(),
+        };
+
+        self.client.send_writable(&metric)
+    }
+}
+
+impl MetricHandle<Counter> {
+    /// Increment the counter by 1
+    pub fn incr(&self) -> MetricResult<()> {
+        self.count(1)
+    }
+
+    /// Decrement the counter by 1
+    pub fn decr(&self) -> MetricResult<()> {
+        self.count(-1)
+    }
+
+    /// Increment or decrement the counter by the given amount
+    pub fn count<V>(&self, count: V) -> MetricResult<()>
+    where
+        V: ToCounterValue,
+    {
+        self.send(count.try_to_value())
+    }
+}
+
+impl MetricHandle<Timer> {
+    /// Record a timing, converting `Duration`s using the timer unit of the client
+    pub fn time<V>(&self, time: V) -> MetricResult<()>
+    where
+        V: ToTimerValue,
+    {
+        self.send(time.try_to_value_in(self.client.timer_unit()))
+    }
+}
+
+impl MetricHandle<Gauge> {
+    /// Record a gauge value
+    pub fn gauge<V>(&self, value: V) -> MetricResult<()>
+    where
+        V: ToGaugeValue,
+    {
+        self.send(value.try_to_value())
+    }
+}
+
+impl MetricHandle<Histogram> {
+    /// Record a histogram value, converting `Duration`s using the histogram
+    /// unit of the client
+    pub fn histogram<V>(&self, value: V) -> MetricResult<()>
+    where
+        V: ToHistogramValue,
+    {
+        self.send(value.try_to_value_in(self.client.histogram_unit()))
+    }
+}
+
+impl MetricHandle<Distribution> {
+    /// Record a distribution value, converting `Duration`s using the
+    /// distribution unit of the client
+    pub fn distribution<V>(&self, value: V) -> MetricResult<()>
+    where
+        V: ToDistributionValue,
+    {
+        self.send(value.try_to_value_in(self.client.distribution_unit()))
+    }
+}
+
+impl<T> fmt::Debug for MetricHandle<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("MetricHandle")
+            .field("client", &self.client)
+            .field("key", &self.key)
+            .field("tags", &self.tags)
+            .finish()
+    }
+}
+
+// Metric formatted with a placeholder value that is replaced when sending
+#[derive(Debug, Clone)]
+struct Template {
+    metric: String,
+    // start of the single character placeholder value
+    value_at: usize,
+}
+
+impl Template {
+    fn head(&self) -> &str {
+        &self.metric[..self.value_at]
+    }
+
+    fn tail(&self) -> &str {
+        &self.metric[self.value_at + 1..]
+    }
+}
+
+struct HandleMetric<'a> {
+    template: &'a Template,
+    value: &'a MetricValue,
+    timestamp: Option<u64>,
+}
+
+impl<'a> WriteMetric for HandleMetric<'a> {
+    fn write_to(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+        out.write_str(self.template.head())?;
+        self.value.write_to(out)?;
+        out.write_str(self.template.tail())?;
+        if let Some(timestamp) = self.timestamp {
+            write!(out, "|T{}", timestamp)?;
+        }
+
+        Ok(())
+    }
+
+    fn size_hint(&self) -> usize {
+        // value(s) and timestamp, the same as `MetricFormatter`
+        self.template.metric.len() + 10 * self.value.count() + self.timestamp.map(|_| 12).unwrap_or(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::builder::{NonFinitePolicy, TagFormat};
+    use crate::client::StatsdClient;
+    use crate::sinks::{NopMetricSink, SpyMetricSink};
+    use crate::test::ErrorMetricSink;
+    use crate::timing::TimeUnit;
+    use crate::types::ErrorKind;
+    use std::time::Duration;
+
+    #[test]
+    fn test_counter_handle() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink);
+        let handle = client.counter_handle("some.counter");
+
+        handle.incr().unwrap();
+        handle.decr().unwrap();
+        handle.count(42).unwrap();
+
+        assert_eq!(b"prefix.some.counter:1|c".to_vec(), rx.try_recv().unwrap());
+        assert_eq!(b"prefix.some.counter:-1|c".to_vec(), rx.try_recv().unwrap());
+        assert_eq!(b"prefix.some.counter:42|c".to_vec(), rx.try_recv().unwrap());
+    }
+
+    #[test]
+    fn test_handle_tags_and_defaults() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink)
+            .with_tag("region", "us-west-2")
+            .with_tag("env", "prod")
+            .with_container_id("1234")
+            .build();
+        let handle = client
+            .gauge_handle("some.gauge")
+            .with_tag("env", "staging")
+            .with_tag_value("beta");
+
+        handle.gauge(5.5).unwrap();
+
+        assert_eq!(
+            b"prefix.some.gauge:5.5|g|#region:us-west-2,env:staging,beta|c:1234".to_vec(),
+            rx.try_recv().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_handle_telegraf_tags() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink)
+            .with_tag_format(TagFormat::Telegraf)
+            .with_tag("region", "us-west-2")
+            .build();
+        let handle = client.counter_handle("some.counter").with_tag("env", "staging");
+
+        handle.count(12).unwrap();
+        handle.incr().unwrap();
+
+        assert_eq!(
+            b"prefix.some.counter,region=us-west-2,env=staging:12|c".to_vec(),
+            rx.try_recv().unwrap()
+        );
+        assert_eq!(
+            b"prefix.some.counter,region=us-west-2,env=staging:1|c".to_vec(),
+            rx.try_recv().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_handle_units() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink)
+            .with_timer_unit(TimeUnit::Microseconds)
+            .build();
+
+        client
+            .timer_handle("some.timer")
+            .time(Duration::from_micros(1500))
+            .unwrap();
+        client
+            .histogram_handle("some.histogram")
+            .histogram(Duration::from_nanos(20))
+            .unwrap();
+        client
+            .distribution_handle("some.distr")
+            .distribution(vec![1, 2])
+            .unwrap();
+
+        assert_eq!(b"prefix.some.timer:1500|ms".to_vec(), rx.try_recv().unwrap());
+        assert_eq!(b"prefix.some.histogram:20|h".to_vec(), rx.try_recv().unwrap());
+        assert_eq!(b"prefix.some.distr:1:2|d".to_vec(), rx.try_recv().unwrap());
+    }
+
+    #[test]
+    fn test_handle_timestamp() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink).with_clock(|| 1234).build();
+
+        client.counter_handle("some.counter").incr().unwrap();
+
+        assert_eq!(b"prefix.some.counter:1|c|T1234".to_vec(), rx.try_recv().unwrap());
+    }
+
+    #[test]
+    fn test_handle_sample_rate() {
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink).with_sample_rate(0.0).build();
+
+        client.counter_handle("some.counter").incr().unwrap();
+
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_handle_invalid_sample_rate() {
+        let client = StatsdClient::builder("prefix", NopMetricSink)
+            .with_sample_rate(1.5)
+            .build();
+        let res = client.counter_handle("some.counter").incr();
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_handle_invalid_value() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.counter_handle("some.counter").count(f64::NAN);
+
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_handle_non_finite_value() {
+        let client = StatsdClient::from_sink("prefix", NopMetricSink);
+        let res = client.gauge_handle("some.gauge").gauge(f64::NAN);
+        assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind());
+
+        let (rx, sink) = SpyMetricSink::new();
+        let client = StatsdClient::builder("prefix", sink)
+            .with_non_finite_policy(NonFinitePolicy::Clamp)
+            .build();
+        let handle = client.gauge_handle("some.gauge");
+        handle.gauge(f64::NAN).unwrap();
+        handle.gauge(f64::INFINITY).unwrap();
+
+        let sent: Vec<Vec<u8>> = rx.try_iter().collect();
+        assert_eq!(vec![format!("prefix.some.gauge:{}|g", f64::MAX).into_bytes()], sent);
+    }
+
+    #[test]
+    fn test_handle_sink_error() {
+        let client = StatsdClient::from_sink("prefix", ErrorMetricSink::always());
+        let res = client.counter_handle("some.counter").incr();
+
+        assert_eq!(ErrorKind::Timeout, res.unwrap_err().kind());
+    }
+}
@@ -0,0 +1,246 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::client::{Counted, Gauged, StatsdClient};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Builder for creating and customizing `HeartbeatReporter` instances.
+///
+/// By default, the heartbeat is sent as a counter incremented by one every 10
+/// seconds, without any tags other than the default tags of the client.
+#[derive(Debug, Clone)]
+pub struct HeartbeatReporterBuilder {
+    key: String,
+    interval: Duration,
+    gauge: bool,
+    tags: Vec<(String, String)>,
+}
+
+impl HeartbeatReporterBuilder {
+    /// Create a new builder for a heartbeat with the given key, added after
+    /// the prefix of the client.
+    pub fn new(key: &str) -> Self {
+        HeartbeatReporterBuilder {
+            key: key.to_owned(),
+            interval: DEFAULT_INTERVAL,
+            gauge: false,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Set how often the heartbeat is sent.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Send the heartbeat as a gauge set to one instead of a counter.
+    ///
+    /// A gauge keeps its last value between flushes of the Statsd server, so
+    /// alerts need to check when it was last received rather than its value.
+    pub fn as_gauge(mut self) -> Self {
+        self.gauge = true;
+        self
+    }
+
+    /// Add a tag to every heartbeat sent, for example the version of the
+    /// service.
+    pub fn with_tag(mut self, key: &str, value: &str) -> Self {
+        self.tags.push((key.to_owned(), value.to_owned()));
+        self
+    }
+
+    /// Start a thread sending the heartbeat using the given client, returning
+    /// an error if the thread can't be started. No thread is started when the
+    /// client is disabled since nothing would be sent.
+    pub fn build<C>(self, client: C) -> io::Result<HeartbeatReporter>
+    where
+        C: Into<Arc<StatsdClient>>,
+    {
+        let client = client.into();
+        let stopped = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread_stopped = stopped.clone();
+        let key = self.key.clone();
+        let thread = if client.is_disabled() {
+            None
+        } else {
+            Some(
+                thread::Builder::new()
+                    .name("cadence-heartbeat".into())
+                    .spawn(move || run_reporter(&client, &self, &thread_stopped))?,
+            )
+        };
+
+        Ok(HeartbeatReporter { key, stopped, thread })
+    }
+}
+
+/// Reporter that periodically sends a heartbeat metric using a `StatsdClient`
+/// so that alerts can detect when a service stops running.
+///
+/// The heartbeat is sent from a background thread as soon as the reporter is
+/// started and then on a fixed interval until the reporter is stopped or
+/// dropped. It is sent as a counter incremented by one, like
+/// `my.prefix.alive:1|c`, or as a gauge set to one if configured using the
+/// builder. Heartbeats are sent quietly, like `MetricBuilder::send()`, so any
+/// error sending them is passed to the error handler of the client.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use cadence::{HeartbeatReporter, NopMetricSink, StatsdClient};
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// let reporter = HeartbeatReporter::builder("alive")
+///     .with_interval(Duration::from_secs(30))
+///     .with_tag("version", "1.2.3")
+///     .build(client)
+///     .unwrap();
+///
+/// // The heartbeat is sent until the reporter is stopped or dropped.
+/// reporter.stop();
+/// ```
+pub struct HeartbeatReporter {
+    key: String,
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl HeartbeatReporter {
+    /// Start sending a heartbeat counter with the given key using the given
+    /// client with the default interval.
+    pub fn start<C>(key: &str, client: C) -> io::Result<Self>
+    where
+        C: Into<Arc<StatsdClient>>,
+    {
+        Self::builder(key).build(client)
+    }
+
+    /// Create a new builder for a reporter that sends a heartbeat with the
+    /// given key.
+    pub fn builder(key: &str) -> HeartbeatReporterBuilder {
+        HeartbeatReporterBuilder::new(key)
+    }
+
+    /// Stop sending the heartbeat, waiting for the reporting thread to exit.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        let (lock, cond) = &*self.stopped;
+        *lock.lock().unwrap() = true;
+        cond.notify_all();
+
+        if let Some(t) = self.thread.take() {
+            let _ = t.join();
+        }
+    }
+}
+
+impl fmt::Debug for HeartbeatReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("HeartbeatReporter")
+            .field("key", &self.key)
+            .field("stopped", &self.stopped.0)
+            .finish()
+    }
+}
+
+impl Drop for HeartbeatReporter {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn run_reporter(client: &StatsdClient, config: &HeartbeatReporterBuilder, stopped: &(Mutex<bool>, Condvar)) {
+    let (lock, cond) = stopped;
+    loop {
+        beat(client, config);
+
+        let guard = lock.lock().unwrap();
+        let (guard, _) = cond
+            .wait_timeout_while(guard, config.interval, |stopped| !*stopped)
+            .unwrap();
+        if *guard {
+            break;
+        }
+    }
+}
+
+fn beat(client: &StatsdClient, config: &HeartbeatReporterBuilder) {
+    if config.gauge {
+        let mut builder = client.gauge_with_tags(&config.key, 1);
+        for (k, v) in &config.tags {
+            builder = builder.with_tag(k, v);
+        }
+        builder.send();
+    } else {
+        let mut builder = client.count_with_tags(&config.key, 1);
+        for (k, v) in &config.tags {
+            builder = builder.with_tag(k, v);
+        }
+        builder.send();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{beat, HeartbeatReporter, HeartbeatReporterBuilder};
+    use crate::{RecordingMetricSink, StatsdClient};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_beat() {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink.clone());
+
+        beat(&client, &HeartbeatReporterBuilder::new("alive"));
+        beat(
+            &client,
+            &HeartbeatReporterBuilder::new("up")
+                .as_gauge()
+                .with_tag("version", "1.2.3"),
+        );
+
+        assert_eq!(vec!["prefix.alive:1|c", "prefix.up:1|g|#version:1.2.3"], sink.metrics());
+    }
+
+    #[test]
+    fn test_reporter_interval() {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink.clone());
+        let reporter = HeartbeatReporter::builder("alive")
+            .with_interval(Duration::from_millis(10))
+            .build(client)
+            .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while sink.len() < 3 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        reporter.stop();
+        let reported = sink.len();
+        std::thread::sleep(Duration::from_millis(50));
+
+        assert!(reported >= 3);
+        assert_eq!(reported, sink.len());
+        assert!(sink.metrics().iter().all(|m| m == "prefix.alive:1|c"));
+    }
+}
@@ -8,9 +8,47 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use crate::types::WriteMetric;
+use std::cell::RefCell;
+use std::fmt;
 use std::io;
 use std::io::{BufWriter, Write};
+use std::mem;
 use std::str;
+use std::time::{Duration, Instant};
+
+// Buffers larger than this are dropped after use instead of being kept around
+// for the next metric, so that a single huge metric doesn't pin the memory.
+const MAX_BUFFER_CAPACITY: usize = 8 * 1024;
+
+thread_local! {
+    static FORMAT_BUFFER: RefCell<String> = const { RefCell::new(String::new()) };
+}
+
+// Run the function with an empty string reused by every metric formatted on
+// this thread. If the buffer is already in use, such as when a sink sends a
+// metric while emitting another one, a new string is used instead.
+pub(crate) fn with_format_buffer<F, R>(f: F) -> R
+where
+    F: FnOnce(&mut String) -> R,
+{
+    FORMAT_BUFFER.with(|cell| match cell.try_borrow_mut() {
+        Ok(mut buf) => {
+            buf.clear();
+            let res = f(&mut buf);
+            if buf.capacity() > MAX_BUFFER_CAPACITY {
+                *buf = String::new();
+            }
+            res
+        }
+        Err(_) => f(&mut String::new()),
+    })
+}
+
+// Formatting metrics only fails if a metric returns an error itself
+pub(crate) fn format_error(_: fmt::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, "failed to format metric")
+}
 
 #[derive(Debug, Default)]
 struct WriterMetrics {
@@ -33,6 +71,10 @@ where
     metrics: WriterMetrics,
     inner: BufWriter<T>,
     line_ending: Vec<u8>,
+    oversized_ending: bool,
+    flush_interval: Option<Duration>,
+    buffered_at: Option<Instant>,
+    line: String,
 }
 
 impl<T> MultiLineWriter<T>
@@ -54,7 +96,54 @@ where
             metrics: WriterMetrics::default(),
             inner: BufWriter::with_capacity(cap, inner),
             line_ending: Vec::from(end.as_bytes()),
+            oversized_ending: false,
+            flush_interval: None,
+            buffered_at: None,
+            line: String::new(),
+        }
+    }
+
+    /// Add the line ending to inputs that are bigger than the buffer and
+    /// written directly to the underlying writer.
+    ///
+    /// This is required when the line ending is the only thing separating
+    /// inputs from each other, such as when writing to a TCP stream.
+    pub fn with_oversized_ending(mut self) -> MultiLineWriter<T> {
+        self.oversized_ending = true;
+        self
+    }
+
+    /// Flush the buffer when writing to it if the oldest input in the buffer
+    /// was written more than `interval` ago, even if the buffer isn't full.
+    pub fn with_flush_interval(mut self, interval: Duration) -> MultiLineWriter<T> {
+        self.set_flush_interval(Some(interval));
+        self
+    }
+
+    /// Set or clear the interval for flushing the buffer when writing to it.
+    pub fn set_flush_interval(&mut self, interval: Option<Duration>) {
+        self.flush_interval = interval;
+    }
+
+    /// Format a metric into a line reused by every metric written to this
+    /// writer and write it the same way as `.write()`.
+    ///
+    /// This avoids allocating a string for each metric written.
+    pub fn write_metric(&mut self, metric: &dyn WriteMetric) -> io::Result<usize> {
+        let mut line = mem::take(&mut self.line);
+        line.clear();
+        line.reserve(metric.size_hint());
+
+        let res = metric
+            .write_to(&mut line)
+            .map_err(format_error)
+            .and_then(|_| self.write(line.as_bytes()));
+
+        if line.capacity() <= MAX_BUFFER_CAPACITY {
+            self.line = line;
         }
+
+        res
     }
 
     #[allow(dead_code)]
@@ -62,6 +151,10 @@ where
         self.inner.get_ref()
     }
 
+    pub(crate) fn get_mut(&mut self) -> &mut T {
+        self.inner.get_mut()
+    }
+
     #[allow(dead_code)]
     fn get_metrics(&self) -> &WriterMetrics {
         &self.metrics
@@ -88,7 +181,15 @@ where
             // a newline when we're only writing a single large value to
             // the underlying impl.
             // See https://github.com/56quarters/cadence/issues/87
-            Ok(self.inner.get_mut().write(buf)?)
+            if self.oversized_ending {
+                let mut line = Vec::with_capacity(required);
+                line.extend_from_slice(buf);
+                line.extend_from_slice(&self.line_ending);
+                self.inner.get_mut().write_all(&line)?;
+                Ok(buf.len())
+            } else {
+                Ok(self.inner.get_mut().write(buf)?)
+            }
         } else {
             if left < required {
                 self.flush()?;
@@ -108,24 +209,104 @@ where
             // we only return the number of bytes from the provided buffer we
             // wrote per the `Write::write` contract.
             // See https://github.com/56quarters/cadence/issues/117
+            if let Some(interval) = self.flush_interval {
+                let now = Instant::now();
+                let buffered_at = *self.buffered_at.get_or_insert(now);
+                if now.duration_since(buffered_at) >= interval {
+                    self.flush()?;
+                }
+            }
+
             Ok(write1)
         }
     }
 
     fn flush(&mut self) -> io::Result<()> {
         self.metrics.flushed += 1;
-        self.inner.flush()?;
-        self.written = 0;
-        Ok(())
+        let res = self.inner.flush();
+        // Writers may fail but still consume some or all of the buffer
+        // so use whatever is actually left in it instead of assuming that
+        // nothing was written when there was an error.
+        self.written = self.inner.buffer().len();
+        if self.written == 0 {
+            self.buffered_at = None;
+        }
+
+        res
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::MultiLineWriter;
+    use super::{with_format_buffer, MultiLineWriter, MAX_BUFFER_CAPACITY};
+    use crate::types::WriteMetric;
 
+    use std::fmt;
     use std::io::Write;
     use std::str;
+    use std::time::Duration;
+
+    struct Counter(u64);
+
+    impl WriteMetric for Counter {
+        fn write_to(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+            write!(out, "foo:{}|c", self.0)
+        }
+    }
+
+    struct Broken;
+
+    impl WriteMetric for Broken {
+        fn write_to(&self, _out: &mut dyn fmt::Write) -> fmt::Result {
+            Err(fmt::Error)
+        }
+    }
+
+    #[test]
+    fn test_with_format_buffer_nested() {
+        with_format_buffer(|outer| {
+            outer.push_str("outer");
+            with_format_buffer(|inner| {
+                assert!(inner.is_empty());
+                inner.push_str("inner");
+            });
+            assert_eq!("outer", outer);
+        });
+    }
+
+    #[test]
+    fn test_with_format_buffer_reused() {
+        with_format_buffer(|buf| buf.push_str("some.metric:1|c"));
+        let capacity = with_format_buffer(|buf| {
+            assert!(buf.is_empty());
+            buf.capacity()
+        });
+        assert!(capacity > 0);
+
+        with_format_buffer(|buf| buf.reserve(MAX_BUFFER_CAPACITY * 2));
+        assert_eq!(0, with_format_buffer(|buf| buf.capacity()));
+    }
+
+    #[test]
+    fn test_write_metric() {
+        let mut buffered = MultiLineWriter::new(vec![], 16);
+
+        assert_eq!(8, buffered.write_metric(&Counter(12)).unwrap());
+        assert_eq!(8, buffered.write_metric(&Counter(34)).unwrap());
+        buffered.flush().unwrap();
+
+        assert_eq!("foo:12|c\nfoo:34|c\n", str::from_utf8(buffered.get_ref()).unwrap());
+    }
+
+    #[test]
+    fn test_write_metric_error() {
+        let mut buffered = MultiLineWriter::new(vec![], 16);
+
+        assert!(buffered.write_metric(&Broken).is_err());
+        buffered.flush().unwrap();
+
+        assert!(buffered.get_ref().is_empty());
+    }
 
     #[test]
     fn test_write_needs_flush() {
@@ -186,6 +367,17 @@ mod tests {
         assert_eq!(8, in_buffer_after_write2);
     }
 
+    #[test]
+    fn test_write_bigger_than_buffer_oversized_ending() {
+        let mut buffered = MultiLineWriter::new(vec![], 16).with_oversized_ending();
+
+        let write1 = buffered.write(b"some_really_long_metric:456|c").unwrap();
+        let written = str::from_utf8(buffered.get_ref()).unwrap();
+
+        assert_eq!(29, write1);
+        assert_eq!("some_really_long_metric:456|c\n", written);
+    }
+
     #[test]
     fn test_buffer_write_equal_capacity() {
         let mut buffered = MultiLineWriter::new(vec![], 8);
@@ -232,4 +424,23 @@ mod tests {
         assert_eq!(10, buf.len());
         assert_eq!("something\n", str::from_utf8(&buf).unwrap());
     }
+
+    #[test]
+    fn test_write_flush_interval_elapsed() {
+        let mut buffered = MultiLineWriter::new(vec![], 32).with_flush_interval(Duration::from_secs(0));
+
+        buffered.write_all(b"abc:3|g").unwrap();
+        assert_eq!("abc:3|g\n", str::from_utf8(buffered.get_ref()).unwrap());
+        assert_eq!(0, buffered.written);
+    }
+
+    #[test]
+    fn test_write_flush_interval_not_elapsed() {
+        let mut buffered = MultiLineWriter::new(vec![], 32).with_flush_interval(Duration::from_secs(3600));
+
+        buffered.write_all(b"abc:3|g").unwrap();
+        buffered.write_all(b"def:4|g").unwrap();
+        assert_eq!(0, buffered.get_ref().len());
+        assert_eq!(16, buffered.written);
+    }
 }
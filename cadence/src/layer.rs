@@ -0,0 +1,189 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::sync::Arc;
+use std::time::Instant;
+
+use tracing_core::field::{Field, Visit};
+use tracing_core::span::{Attributes, Id, Record};
+use tracing_core::Subscriber;
+use tracing_subscriber::layer::{Context, Layer};
+use tracing_subscriber::registry::LookupSpan;
+
+use crate::client::{StatsdClient, Timed};
+
+/// Layer for `tracing-subscriber` that sends how long each span was open as
+/// a timer using a `StatsdClient`.
+///
+/// When a span is closed, a timer is sent with the name of the span as its key
+/// and the time since the span was created as its value. The fields of the span
+/// are sent as key-value tags, including fields recorded after the span was
+/// created. Since each distinct tag value creates a new time series in most
+/// metric servers, spans should not have fields with many possible values, such
+/// as user or request IDs, when this layer is used.
+///
+/// This layer is only available when the `tracing` feature is enabled, which
+/// requires Rust 1.65 or newer.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{NopMetricSink, StatsdClient, StatsdLayer};
+/// use tracing_subscriber::layer::SubscriberExt;
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// let subscriber = tracing_subscriber::registry().with(StatsdLayer::new(client));
+///
+/// tracing::subscriber::with_default(subscriber, || {
+///     let span = tracing::info_span!("handle_request", method = "GET");
+///     let _guard = span.enter();
+///     // Handle the request...
+/// });
+/// ```
+#[derive(Debug, Clone)]
+pub struct StatsdLayer {
+    client: Arc<StatsdClient>,
+}
+
+impl StatsdLayer {
+    /// Create a new layer that sends span timings using the given client.
+    pub fn new<C>(client: C) -> Self
+    where
+        C: Into<Arc<StatsdClient>>,
+    {
+        StatsdLayer { client: client.into() }
+    }
+}
+
+impl<S> Layer<S> for StatsdLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            let mut timing = SpanTiming {
+                start: Instant::now(),
+                tags: Vec::new(),
+            };
+
+            attrs.record(&mut timing);
+            span.extensions_mut().insert(timing);
+        }
+    }
+
+    fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+        if let Some(span) = ctx.span(id) {
+            if let Some(timing) = span.extensions_mut().get_mut::<SpanTiming>() {
+                values.record(timing);
+            }
+        }
+    }
+
+    fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+        let span = match ctx.span(&id) {
+            Some(span) => span,
+            None => return,
+        };
+
+        let timing = match span.extensions_mut().remove::<SpanTiming>() {
+            Some(timing) => timing,
+            None => return,
+        };
+
+        timing
+            .tags
+            .iter()
+            .fold(
+                self.client.time_with_tags(span.name(), timing.start.elapsed()),
+                |b, (key, value)| b.with_tag(key, value),
+            )
+            .send();
+    }
+}
+
+// When a span was created and the fields recorded for it so far, stored in
+// the extensions of the span.
+struct SpanTiming {
+    start: Instant,
+    tags: Vec<(&'static str, String)>,
+}
+
+impl SpanTiming {
+    fn set_tag(&mut self, field: &Field, value: String) {
+        let key = field.name();
+        match self.tags.iter_mut().find(|(k, _)| *k == key) {
+            Some(tag) => tag.1 = value,
+            None => self.tags.push((key, value)),
+        }
+    }
+}
+
+impl Visit for SpanTiming {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.set_tag(field, value.to_owned());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.set_tag(field, format!("{:?}", value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatsdLayer;
+    use crate::{ParsedMetric, RecordingMetricSink, StatsdClient};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    fn with_layer<F: FnOnce()>(f: F) -> RecordingMetricSink {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink.clone());
+        let subscriber = tracing_subscriber::registry().with(StatsdLayer::new(client));
+
+        tracing::subscriber::with_default(subscriber, f);
+        sink
+    }
+
+    #[test]
+    fn test_layer_span_timer() {
+        let sink = with_layer(|| {
+            let span = tracing::info_span!("handle_request", method = "GET", status = tracing::field::Empty);
+            let _guard = span.enter();
+            span.record("status", 200);
+        });
+
+        let metrics = sink.metrics();
+        assert_eq!(1, metrics.len());
+
+        let metric = ParsedMetric::parse(&metrics[0]).unwrap();
+        assert_eq!("prefix.handle_request", metric.name());
+        assert_eq!(&[(Some("method"), "GET"), (Some("status"), "200")], metric.tags());
+    }
+
+    #[test]
+    fn test_layer_nested_spans() {
+        let sink = with_layer(|| {
+            let outer = tracing::info_span!("outer");
+            let _outer = outer.enter();
+            let inner = tracing::info_span!("inner", cached = true);
+            let _inner = inner.enter();
+        });
+
+        let metrics = sink.metrics();
+        let inner = ParsedMetric::parse(&metrics[0]).unwrap();
+        let outer = ParsedMetric::parse(&metrics[1]).unwrap();
+
+        assert_eq!(2, metrics.len());
+        assert_eq!("prefix.inner", inner.name());
+        assert_eq!(Some("true"), inner.tag("cached"));
+        assert_eq!("prefix.outer", outer.name());
+        assert!(outer.tags().is_empty());
+    }
+}
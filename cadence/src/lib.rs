@@ -15,9 +15,10 @@
 //! ## Features
 //!
 //! * [Support](https://docs.rs/cadence/) for emitting counters, timers, histograms, distributions,
-//!   gauges, meters, and sets to Statsd over UDP (or optionally Unix sockets).
+//!   gauges, meters, and sets to Statsd over UDP, TCP, or optionally Unix sockets.
 //! * Support for alternate backends via the `MetricSink` trait.
 //! * Support for [Datadog](https://docs.datadoghq.com/developers/dogstatsd/) style metrics tags.
+//! * Support for [Datadog](https://docs.datadoghq.com/developers/dogstatsd/) events and service checks.
 //! * [Macros](https://docs.rs/cadence-macros/) to simplify common calls to emit metrics
 //! * A simple yet flexible API for sending metrics.
 //!
@@ -183,6 +184,11 @@
 //! metrics will use in your application. This is a tradeoff that users of
 //! Cadence must decide for themselves.
 //!
+//! When the `crossbeam-queue` feature is enabled, a bounded queue can use a
+//! lock-free array instead of a channel by calling `with_array_queue` on the
+//! `QueuingMetricSinkBuilder`. This lowers the time spent queuing metrics when
+//! many threads are sending metrics at the same time.
+//!
 
 //! It is also possible to supply an error handler for a `QueuingMetricSink` to
 //! be called whenever the wrapped sink cannot send metrics for whatever reason.
@@ -244,11 +250,18 @@
 //! );
 //! ```
 //!
+//! Tags use the Datadog format by default. Servers that expect tags in the
+//! format used by the Telegraf statsd input plugin, such as
+//! `my.counter,host=web03:29|c`, can be supported by building the client with
+//! `.with_tag_format(TagFormat::Telegraf)`. Graphite 1.1 style tags, such as
+//! `my.counter;host=web03:29|c`, can be used with `TagFormat::Graphite`.
+//!
 //! ### Default Tags
 //!
 //! Default tags can be added to a `StatsdClient` when constructed using the builder.
 //! Default tags are added to every metric emitted by the `StatsdClient` without any
-//! extra work after building the client. Note that tags are an extension to the Statsd
+//! extra work after building the client. A key-value tag added to a metric will replace
+//! any default tag that uses the same key. Note that tags are an extension to the Statsd
 //! protocol and so may not be supported by all servers.
 //!
 //! See the [Datadog docs](https://docs.datadoghq.com/developers/dogstatsd/) for
@@ -283,8 +296,10 @@
 //! ### Value Packing
 //!
 //! Value packing allows multiple values to be sent as a single metric for histograms,
-//! distributions, and timer types. The Cadence client accepts `Vec<T>` for histogram,
-//! distribution, and timer methods and will format multiple values as described below.
+//! distributions, and timer types. The Cadence client accepts `Vec<T>`, `&[T]`, and
+//! `[T; N]` for histogram, distribution, and timer methods and will format multiple
+//! values as described below. Sending many values in a single metric reduces the
+//! number of packets needed for bursts of values.
 //! Note that this feature is a Datadog extension and so may not be supported by your
 //! server. It is supported by versions `>=v6.25.0 && <v7.0.0` or `>=v7.25.0` of the
 //! Datadog agent.
@@ -318,6 +333,70 @@
 //! );
 //! ```
 //!
+//! ### Events
+//!
+//! Datadog events can be emitted alongside metrics to record notable things that
+//! happen in your application such as deploys or errors. Events have a title and
+//! text along with optional attributes such as a priority, alert type, aggregation
+//! key, and tags. Default tags and container ID from the client are applied to events
+//! but the metric prefix is not. Note that this feature is a Datadog extension and so
+//! may not be supported by your server.
+//!
+//! See the [Datadog Docs](https://docs.datadoghq.com/developers/dogstatsd/datagram_shell/?tab=events)
+//! for more information.
+//!
+//! ```rust,no_run
+//! use cadence::prelude::*;
+//! use cadence::{EventAlertType, EventPriority, Metric, StatsdClient, NopMetricSink};
+//!
+//! let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+//!
+//! let res = client.event_with_tags("Deploy failed", "Rolled back to v1.2.2")
+//!     .with_alert_type(EventAlertType::Error)
+//!     .with_priority(EventPriority::Low)
+//!     .with_aggregation_key("deploy")
+//!     .with_tag("service", "api")
+//!     .try_send();
+//!
+//! assert_eq!(
+//!     concat!(
+//!         "_e{13,21}:Deploy failed|Rolled back to v1.2.2|",
+//!         "k:deploy|p:low|t:error|#service:api"
+//!     ),
+//!     res.unwrap().as_metric_str()
+//! );
+//! ```
+//!
+//! ### Service Checks
+//!
+//! Datadog service checks can be used to report the status of a service that your
+//! application depends on, such as a database. The status of a service check is one
+//! of `Ok`, `Warning`, `Critical`, or `Unknown` and a message describing the state
+//! of the service can optionally be included. Default tags and container ID from the
+//! client are applied to service checks and the name is prefixed with the metric
+//! prefix of the client. Note that this feature is a Datadog extension and so may not
+//! be supported by your server.
+//!
+//! See the [Datadog Docs](https://docs.datadoghq.com/developers/dogstatsd/datagram_shell/?tab=servicechecks)
+//! for more information.
+//!
+//! ```rust,no_run
+//! use cadence::prelude::*;
+//! use cadence::{Metric, ServiceCheckStatus, StatsdClient, NopMetricSink};
+//!
+//! let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+//!
+//! let res = client.service_check_with_tags("database.up", ServiceCheckStatus::Critical)
+//!     .with_tag("db", "users")
+//!     .with_message("connection refused")
+//!     .try_send();
+//!
+//! assert_eq!(
+//!     "_sc|my.prefix.database.up|2|#db:users|m:connection refused",
+//!     res.unwrap().as_metric_str()
+//! );
+//! ```
+//!
 //! ### Implemented Traits
 //!
 //! Each of the methods that the Cadence `StatsdClient` struct uses to send
@@ -413,6 +492,89 @@
 //!     .send();
 //! ```
 //!
+//! ### Global Default Client
+//!
+//! Instead of passing a `StatsdClient` to every part of an application that
+//! emits metrics, a client can be set as the global default with
+//! `set_global_default`. The functions in the `global` module then emit metrics
+//! using it, similar to how the `log` and `tracing` crates work. Metrics emitted
+//! before a global default client is set are discarded.
+//!
+//! ```
+//! use cadence::{NopMetricSink, StatsdClient};
+//!
+//! let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+//! cadence::set_global_default(client).unwrap();
+//!
+//! // Deep inside some library code...
+//! cadence::global::incr("cache.miss");
+//! cadence::global::gauge("cache.size", 128);
+//! ```
+//!
+//! ### Counting Bytes Read or Written
+//!
+//! The `CountedReader` and `CountedWriter` types wrap any `Read` or `Write`
+//! implementation, such as a file or socket, and increment a counter with the
+//! number of bytes passing through them. They can optionally send the rate of
+//! each read or write in bytes per second as a histogram.
+//!
+//! ```
+//! use std::io::Write;
+//! use cadence::{CountedWriter, NopMetricSink, StatsdClient};
+//!
+//! let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+//! let mut writer = CountedWriter::new(client, "report.write", Vec::new()).with_throughput();
+//!
+//! // Increments "my.prefix.report.write.bytes" by 11
+//! writer.write_all(b"hello world").unwrap();
+//! ```
+//!
+//! ### Heartbeats
+//!
+//! A `HeartbeatReporter` sends a counter, or optionally a gauge, with a value
+//! of one on a fixed interval from a background thread. Alerts can use it to
+//! detect when a service has stopped running.
+//!
+//! ```
+//! use cadence::{HeartbeatReporter, NopMetricSink, StatsdClient};
+//!
+//! let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+//!
+//! // Sends "my.prefix.alive:1|c" every 10 seconds until dropped
+//! let reporter = HeartbeatReporter::start("alive", client).unwrap();
+//! ```
+//!
+//! ### Testing
+//!
+//! Code that emits metrics can be tested without a Statsd server by giving its
+//! `StatsdClient` a `RecordingMetricSink`. Every metric sent by the client is
+//! recorded as a string that tests can check after exercising the code.
+//!
+//! ```rust
+//! use cadence::prelude::*;
+//! use cadence::{RecordingMetricSink, StatsdClient};
+//!
+//! fn handle_login(client: &StatsdClient) {
+//!     client.incr_with_tags("logins").with_tag("method", "sso").send();
+//! }
+//!
+//! let sink = RecordingMetricSink::new();
+//! let client = StatsdClient::from_sink("my.app", sink.clone());
+//!
+//! handle_login(&client);
+//!
+//! assert_eq!(vec!["my.app.logins:1|c|#method:sso"], sink.metrics());
+//! ```
+//!
+//! When the `test-util` feature is enabled, the `testing` module includes
+//! functions for making assertions about recorded metrics, such as the total
+//! of a counter or metrics with particular tags.
+//! It also includes `UdpTestServer` and `TcpTestServer` which record metrics
+//! received over a real socket, for end-to-end tests of a configured client.
+//!
+//! The `SpyMetricSink` and `BufferedSpyMetricSink` instead send metrics to a
+//! channel, which is useful when they are emitted from other threads.
+//!
 //! ### Custom Metric Sinks
 //!
 //! The Cadence `StatsdClient` uses implementations of the `MetricSink`
@@ -447,6 +609,10 @@
 //! client.count("some.other.counter", 1);
 //! ```
 //!
+//! When the `test-util` feature is enabled, the functions in the
+//! `testing::conformance` module can be used to check that a custom sink
+//! handles flushing, newlines, and large metrics like the included sinks do.
+//!
 //! ### Custom UDP Socket
 //!
 //! Most users of the Cadence `StatsdClient` will be using it to send metrics
@@ -473,6 +639,91 @@
 //! client.set("users.uniques", 42);
 //! ```
 //!
+//! The `UdpMetricSinkBuilder` can also create the socket for a sink, bound to
+//! a particular local address and in blocking or non-blocking mode. When the
+//! `socket-options` feature is enabled, it can set the size of the send buffer
+//! of the socket and the type of service used to mark packets with a DSCP value.
+//!
+//! ```rust,no_run
+//! use cadence::{StatsdClient, UdpMetricSinkBuilder, DEFAULT_PORT};
+//!
+//! let sink = UdpMetricSinkBuilder::new()
+//!     .with_bind_addr("10.0.0.2:0".parse().unwrap())
+//!     .with_nonblocking(false)
+//!     .build(("metrics.example.com", DEFAULT_PORT))
+//!     .unwrap();
+//! let client = StatsdClient::from_sink("my.prefix", sink);
+//! ```
+//!
+//! ### InfluxDB Line Protocol
+//!
+//! Metrics can be sent directly to InfluxDB or Telegraf, without a Statsd server
+//! in between, by wrapping a UDP or TCP sink with an `InfluxLineMetricSink`. It
+//! converts each metric to the InfluxDB line protocol before writing it.
+//!
+//! ```rust,no_run
+//! use std::net::UdpSocket;
+//! use cadence::prelude::*;
+//! use cadence::{InfluxLineMetricSink, StatsdClient, UdpMetricSink};
+//!
+//! let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+//! let udp = UdpMetricSink::from(("influx.example.com", 8089), socket).unwrap();
+//! let sink = InfluxLineMetricSink::from(udp);
+//! let client = StatsdClient::from_sink("my.prefix", sink);
+//!
+//! // Sent as "my.prefix.requests,metric_type=counter,method=GET value=1i"
+//! client.count_with_tags("requests", 1).with_tag("method", "GET").send();
+//! ```
+//!
+//! When the `influx-http` feature is enabled, the `InfluxHttpMetricSink` can be
+//! wrapped instead to send batches of lines to the HTTP write API of InfluxDB.
+//! This feature requires Rust 1.71 or newer.
+//!
+//! ```rust,ignore
+//! use cadence::{InfluxHttpMetricSink, InfluxLineMetricSink, QueuingMetricSink, StatsdClient};
+//!
+//! let http = InfluxHttpMetricSink::builder("http://localhost:8086/api/v2/write?org=my-org&bucket=metrics")
+//!     .with_token("my-token")
+//!     .build();
+//! let sink = QueuingMetricSink::from(InfluxLineMetricSink::from(http));
+//! let client = StatsdClient::from_sink("my.prefix", sink);
+//! ```
+//!
+//! ### Wavefront Data Format
+//!
+//! Metrics can be sent directly to a Wavefront proxy, used by Tanzu Observability,
+//! by wrapping a TCP sink with a `WavefrontMetricSink`. It converts each metric to
+//! the Wavefront data format, including the source of the metric and its tags as
+//! point tags, before writing it.
+//!
+//! ```rust,no_run
+//! use cadence::prelude::*;
+//! use cadence::{StatsdClient, TcpMetricSink, WavefrontMetricSink};
+//!
+//! let tcp = TcpMetricSink::from(("wavefront-proxy.example.com", 2878)).unwrap();
+//! let sink = WavefrontMetricSink::from("web01", tcp);
+//! let client = StatsdClient::from_sink("my.prefix", sink);
+//!
+//! client.time_with_tags("request.latency", 34).with_tag("method", "GET").send();
+//! ```
+//!
+//! ### CloudWatch Embedded Metric Format
+//!
+//! Applications running on AWS Lambda or ECS can create CloudWatch metrics without
+//! a Statsd server using the `EmfMetricSink`. It writes each metric to stdout as a
+//! CloudWatch Embedded Metric Format (EMF) document, using key-value tags as
+//! dimensions.
+//!
+//! ```rust,no_run
+//! use cadence::prelude::*;
+//! use cadence::{EmfMetricSink, StatsdClient};
+//!
+//! let sink = EmfMetricSink::new("MyService");
+//! let client = StatsdClient::from_sink("my.prefix", sink);
+//!
+//! client.time_with_tags("latency", 23).with_tag("method", "GET").send();
+//! ```
+//!
 //! ### Unix Sockets
 //!
 //! Cadence also supports using Unix datagram sockets with the `UnixMetricSink`  or
@@ -506,35 +757,297 @@
 //! client.set("users.uniques", 42);
 //! ```
 //!
+//! Sinks created from a socket like above will return errors for as long as
+//! nothing is listening at the path. To have the sink connect its own socket to the
+//! path and reconnect, with a backoff, when the server or agent is restarted, use
+//! the `UnixMetricSinkBuilder` instead.
+//!
+//! ```rust,no_run
+//! use cadence::{StatsdClient, UnixMetricSinkBuilder};
+//!
+//! let sink = UnixMetricSinkBuilder::new().build_buffered("/run/statsd.sock");
+//! let client = StatsdClient::from_sink("my.prefix", sink);
+//! ```
+//!
+//! For servers or agents that listen on Unix stream sockets instead of datagram
+//! sockets, use the `UnixStreamMetricSink` or `BufferedUnixStreamMetricSink`. Each
+//! metric is followed by a newline and the sinks reconnect to the socket after
+//! failures, the same as the TCP sinks.
+//!
 //! NOTE: This feature is only available on Unix platforms (Linux, BSD, MacOS).
 //!
+//! ### TLS
+//!
+//! Cadence can send metrics to a server over an encrypted connection using the
+//! `TlsMetricSink` or `BufferedTlsMetricSink` when the `rustls` feature is enabled.
+//! These work the same way as the `TcpMetricSink` and `BufferedTcpMetricSink`,
+//! including reconnecting to the server after failures, but require a
+//! `rustls::ClientConfig` that determines which certificates are trusted. The
+//! sinks can be configured using the `TlsMetricSinkBuilder`.
+//!
+//! ### DataDog HTTP API
+//!
+//! When no DataDog agent is available, metrics can be sent directly to the
+//! DataDog HTTP API using the `DatadogHttpMetricSink` when the `datadog-http`
+//! feature is enabled. Metrics are batched and sent over HTTPS using an API key,
+//! so the sink should usually be wrapped in a `QueuingMetricSink` to avoid
+//! making requests from application threads. It can be configured using the
+//! `DatadogHttpMetricSinkBuilder`. This feature requires Rust 1.71 or newer.
+//!
+//! ```rust,ignore
+//! use cadence::prelude::*;
+//! use cadence::{DatadogHttpMetricSink, QueuingMetricSink, StatsdClient};
+//!
+//! let sink = DatadogHttpMetricSink::builder("my-api-key")
+//!     .with_site("datadoghq.eu")
+//!     .build();
+//! let client = StatsdClient::from_sink("my.prefix", QueuingMetricSink::from(sink));
+//!
+//! client.count_with_tags("requests", 1).with_tag("method", "GET").send();
+//! ```
+//!
+//! ### OpenTelemetry
+//!
+//! Applications that export metrics using OpenTelemetry can keep using code
+//! instrumented with a `StatsdClient` with the `OpenTelemetryMetricSink` when
+//! the `opentelemetry` feature is enabled. It records each metric using an
+//! instrument created by an OpenTelemetry `Meter`, so metrics are exported by
+//! whatever pipeline the meter provider is configured with. This feature
+//! requires Rust 1.75 or newer.
+//!
+//! ```rust,ignore
+//! use cadence::prelude::*;
+//! use cadence::{OpenTelemetryMetricSink, StatsdClient};
+//!
+//! let meter = opentelemetry::global::meter("my-service");
+//! let client = StatsdClient::from_sink("my.prefix", OpenTelemetryMetricSink::new(meter));
+//!
+//! client.time_with_tags("request.latency", 34).with_tag("method", "GET").send();
+//! ```
+//!
+//! ### Async Sinks
+//!
+//! Async applications can send metrics without blocking the runtime using the
+//! `AsyncStatsdClient` and implementations of the `AsyncMetricSink` trait. The
+//! client supports the same metric types and options as the `StatsdClient` but
+//! each metric is sent by awaiting the returned future. When the `tokio` feature
+//! is enabled, the `TokioUdpMetricSink` sends metrics over a Tokio UDP socket.
+//!
+//! ```rust,ignore
+//! use tokio::net::UdpSocket;
+//! use cadence::{AsyncStatsdClient, TokioUdpMetricSink, DEFAULT_PORT};
+//!
+//! let socket = UdpSocket::bind("0.0.0.0:0").await.unwrap();
+//! let host = ("metrics.example.com", DEFAULT_PORT);
+//! let sink = TokioUdpMetricSink::from(host, socket).unwrap();
+//! let client = AsyncStatsdClient::from_sink("my.prefix", sink);
+//!
+//! client.count("my.counter.thing", 29).await.unwrap();
+//! client.time_with_tags("my.service.call", 214)
+//!     .with_tag("region", "us-east-2")
+//!     .send()
+//!     .await;
+//! ```
+//!
+//! Applications that use the regular `StatsdClient` can avoid blocking the runtime
+//! and avoid a dedicated thread for sending metrics by using the `TokioQueuingMetricSink`.
+//! It queues metrics and sends them to a wrapped `AsyncMetricSink` from a Tokio task.
+//!
+//! When the `async-timing` feature is enabled, `StatsdClient::time_future` can be
+//! used to record how long a future takes to complete as a timer, such as when
+//! instrumenting the handlers of an async server.
+//!
+//! ```rust,ignore
+//! let user = client.time_future("db.load_user", load_user(id)).await;
+//! ```
+//!
+//! ### Metrics Crate Recorder
+//!
+//! When the `metrics` feature is enabled, `StatsdRecorder` implements the
+//! `Recorder` trait of the [metrics](https://docs.rs/metrics) crate using a
+//! `StatsdClient`. Libraries instrumented with the macros of that crate can then
+//! emit metrics through the same sinks as code that uses Cadence directly.
+//!
+//! ```rust,ignore
+//! let recorder = StatsdRecorder::new(client);
+//! metrics::set_global_recorder(recorder).unwrap();
+//! ```
+//!
+//! ### Tracing Span Timings
+//!
+//! When the `tracing` feature is enabled, `StatsdLayer` can be added to a
+//! [tracing-subscriber](https://docs.rs/tracing-subscriber) subscriber to send
+//! how long each span was open as a timer, using the span name as the key and
+//! its fields as tags.
+//!
+//! ```rust,ignore
+//! let subscriber = tracing_subscriber::registry().with(StatsdLayer::new(client));
+//! tracing::subscriber::set_global_default(subscriber).unwrap();
+//! ```
+//!
+//! ### Counting Log Events
+//!
+//! When the `log` feature is enabled, `StatsdLogger` can wrap the logger of an
+//! application to count events logged using the [log](https://docs.rs/log)
+//! crate by level, as the counters `log.error`, `log.warn`, and so on.
+//!
+//! ```rust,ignore
+//! StatsdLogger::new(client, env_logger::Logger::from_default_env())
+//!     .install(LevelFilter::Info)
+//!     .unwrap();
+//! ```
+//!
+//! ### Tower Request Metrics
+//!
+//! When the `tower` feature is enabled, `RequestMetricsLayer` can wrap a
+//! [tower](https://docs.rs/tower) service to send a counter of requests, a
+//! gauge of requests in flight, and a timer of request latency, optionally
+//! tagged with the status of each response.
+//!
+//! ```rust,ignore
+//! let service = ServiceBuilder::new()
+//!     .layer(RequestMetricsLayer::new("users_api", client))
+//!     .service(users_service);
+//! ```
+//!
+//! ### Process Metrics
+//!
+//! When the `process-metrics` feature is enabled, `ProcessMetricsReporter`
+//! sends the memory, CPU time, open file descriptors, and threads used by the
+//! current process as gauges on a fixed interval from a background thread.
+//!
+//! ```rust,ignore
+//! let reporter = ProcessMetricsReporter::start(client).unwrap();
+//! ```
+//!
+//! ### Timing Functions
+//!
+//! When the `attributes` feature is enabled, the `#[timed]` attribute can be
+//! used to time each call of a function, including `async` functions. Timers
+//! are sent using the global default client, named after the function unless a
+//! key is given.
+//!
+//! ```rust,ignore
+//! use cadence::timed;
+//!
+//! #[timed(key = "db.query", tags(table = "users"))]
+//! async fn find_user(id: u64) -> Option<User> {
+//!     // ...
+//! }
+//! ```
+//!
+//! ### Disabling Metrics
+//!
+//! Clients built with `StatsdClientBuilder::with_disabled(true)` never send
+//! metrics. Metrics sent with `MetricBuilder::send()` are not formatted, tags
+//! are not computed, and sinks are never used. This allows tests, benchmarks, or
+//! applications that turn metrics off in their configuration to skip the cost of
+//! instrumentation without changing any other code. Methods that return a
+//! metric, such as `Counted::count`, still format it but don't send it.
+//!
+//! ```
+//! use cadence::prelude::*;
+//! use cadence::{StatsdClient, UdpMetricSink, DEFAULT_PORT};
+//! use std::net::UdpSocket;
+//!
+//! let metrics_enabled = false;
+//! let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+//! let sink = UdpMetricSink::from(("localhost", DEFAULT_PORT), socket).unwrap();
+//! let client = StatsdClient::builder("my.prefix", sink)
+//!     .with_disabled(!metrics_enabled)
+//!     .build();
+//!
+//! client.incr_with_tags("some.counter").with_tag("region", "us-west-2").send();
+//! ```
+//!
 
-#![forbid(unsafe_code)]
+#![deny(unsafe_code)]
 
 pub const DEFAULT_PORT: u16 = 8125;
 
-pub use self::builder::MetricBuilder;
+pub use self::async_client::{AsyncStatsdClient, AsyncStatsdClientBuilder};
+
+pub use self::builder::{
+    AsyncMetricBuilder, EventBuilder, MetricBatch, MetricBuilder, MetricType, NonFinitePolicy, SanitizePolicy,
+    ServiceCheckBuilder, TagFormat,
+};
 
 pub use self::client::{
-    Counted, CountedExt, Distributed, Gauged, Histogrammed, Metered, MetricClient, Setted, StatsdClient,
-    StatsdClientBuilder, Timed,
+    Counted, CountedExt, Distributed, Evented, Gauged, Histogrammed, Metered, MetricClient, ServiceChecked, Setted,
+    StatsdClient, StatsdClientBuilder, Timed,
 };
 
+pub use self::errors::{ErrorStats, LastError};
+
+pub use self::gauges::GaugeRegistration;
+
+pub use self::global::{get_global_default, is_global_default_set, set_global_default, SetGlobalDefaultError};
+
+pub use self::handle::MetricHandle;
+
+pub use self::heartbeat::{HeartbeatReporter, HeartbeatReporterBuilder};
+
+pub use self::parse::ParsedMetric;
+
+pub use self::sample::{Rng, SeededRng, ThreadLocalRng};
+
+#[doc(hidden)]
+pub use self::state::SingletonHolder;
+
+pub use self::stream::{CountedReader, CountedWriter};
+
 pub use self::sinks::{
-    BufferedSpyMetricSink, BufferedUdpMetricSink, MetricSink, NopMetricSink, QueuingMetricSink,
-    QueuingMetricSinkBuilder, SinkStats, SpyMetricSink, UdpMetricSink,
+    AdaptiveSamplingMetricSink, AdaptiveSamplingMetricSinkBuilder, AggregatingMetricSink, AggregatingMetricSinkBuilder,
+    AsyncMetricSink, Backoff, BufferedSpyMetricSink, BufferedTcpMetricSink, BufferedUdpMetricSink,
+    CircuitBreakerMetricSink, CircuitBreakerMetricSinkBuilder, DeadLetterMetricSink, DeduplicatingMetricSink,
+    DeduplicatingMetricSinkBuilder, DisconnectPolicy, EmfMetricSink, FailoverMetricSink, FailoverMetricSinkBuilder,
+    Fault, FaultSchedule, FilteringMetricSink, FilteringMetricSinkBuilder, FlakyMetricSink, FlakyMetricSinkBuilder,
+    InfluxLineMetricSink, InfluxLineMetricSinkBuilder, InstrumentedMetricSink, InstrumentedMetricSinkBuilder,
+    MetricSink, MetricSinkBuilder, MultiErrorPolicy, MultiMetricSink, MultiMetricSinkBuilder, NopMetricSink,
+    OverflowPolicy, PacketSize, QueuingMetricSink, QueuingMetricSinkBuilder, RecordingMetricSink, ResolvePolicy,
+    RetryingMetricSink, RetryingMetricSinkBuilder, RewritingMetricSink, RewritingMetricSinkBuilder, ShardedMetricSink,
+    ShardedMetricSinkBuilder, SinkFuture, SinkStats, SketchOutput, SpyMetricSink, TcpMetricSink, TcpMetricSinkBuilder,
+    UdpMetricSink, UdpMetricSinkBuilder, WavefrontMetricSink, WavefrontMetricSinkBuilder, WouldBlockPolicy,
 };
 
+pub use self::timing::{Clock, ManualClock, MonotonicClock, ScaledDuration, Stopwatch, TimeUnit};
+
 pub use self::types::{
-    Counter, Distribution, ErrorKind, Gauge, Histogram, Meter, Metric, MetricError, MetricResult, Set, Timer,
+    Counter, CustomMetric, Distribution, ErrorKind, Event, EventAlertType, EventPriority, Gauge, Histogram, Meter,
+    Metric, MetricError, MetricResult, ServiceCheck, ServiceCheckStatus, Set, Timer, WriteMetric,
 };
 
+mod async_client;
 mod builder;
 mod client;
+mod errors;
+#[cfg(test)]
+mod executor;
 pub mod ext;
+mod gauges;
+pub mod global;
+mod handle;
+mod heartbeat;
 mod io;
+#[cfg(feature = "tracing")]
+mod layer;
+#[cfg(feature = "log")]
+mod logger;
+#[cfg(feature = "tower")]
+mod middleware;
+mod parse;
 pub mod prelude;
+#[cfg(feature = "process-metrics")]
+mod process;
+#[cfg(feature = "metrics")]
+mod recorder;
+mod sample;
 mod sinks;
+mod state;
+mod stream;
+#[cfg(feature = "test-util")]
+pub mod testing;
+mod timing;
 mod types;
 
 // Utilities for running integration tests with Unix datagram sockets.
@@ -544,7 +1057,59 @@ pub mod test;
 
 // Sinks for sending metrics over Unix datagram sockets
 #[cfg(unix)]
-pub use crate::sinks::{BufferedUnixMetricSink, UnixMetricSink};
+pub use crate::sinks::{BufferedUnixMetricSink, UnixMetricSink, UnixMetricSinkBuilder};
+
+// Sinks for sending metrics over Unix stream sockets
+#[cfg(unix)]
+pub use crate::sinks::{BufferedUnixStreamMetricSink, UnixStreamMetricSink, UnixStreamMetricSinkBuilder};
+
+// Sinks for sending metrics over TLS
+#[cfg(feature = "rustls")]
+pub use crate::sinks::{BufferedTlsMetricSink, TlsMetricSink, TlsMetricSinkBuilder};
+
+// Sink for sending metrics to the DataDog HTTP API
+#[cfg(feature = "datadog-http")]
+pub use crate::sinks::{DatadogHttpMetricSink, DatadogHttpMetricSinkBuilder};
+
+// Sink for sending metrics to the InfluxDB HTTP API
+#[cfg(feature = "influx-http")]
+pub use crate::sinks::{InfluxHttpMetricSink, InfluxHttpMetricSinkBuilder};
+
+// Sink for recording metrics using OpenTelemetry instruments
+#[cfg(feature = "opentelemetry")]
+pub use crate::sinks::OpenTelemetryMetricSink;
+
+// Sinks for sending metrics from async applications using Tokio
+#[cfg(feature = "tokio")]
+pub use crate::sinks::{TokioQueuingMetricSink, TokioQueuingMetricSinkBuilder, TokioUdpMetricSink};
+
+// Recorder for libraries instrumented with the metrics crate
+#[cfg(feature = "metrics")]
+pub use crate::recorder::StatsdRecorder;
+
+// Layer for recording span timings of applications using tracing
+#[cfg(feature = "tracing")]
+pub use crate::layer::StatsdLayer;
+
+// Logger for counting log events by level
+#[cfg(feature = "log")]
+pub use crate::logger::StatsdLogger;
+
+// Middleware for recording request metrics of tower services
+#[cfg(feature = "tower")]
+pub use crate::middleware::{RequestMetrics, RequestMetricsFuture, RequestMetricsLayer, RequestMetricsLayerBuilder};
+
+// Reporter for metrics about the current process
+#[cfg(feature = "process-metrics")]
+pub use crate::process::{ProcessMetricsReporter, ProcessMetricsReporterBuilder};
+
+// Attribute for timing functions
+#[cfg(feature = "attributes")]
+pub use cadence_attributes::timed;
+
+// Timing futures in async applications
+#[cfg(feature = "async-timing")]
+pub use crate::timing::TimedFuture;
 
 mod sealed {
     pub trait Sealed {}
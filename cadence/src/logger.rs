@@ -0,0 +1,177 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::sync::Arc;
+
+use log::{Level, LevelFilter, Log, Metadata, Record, SetLoggerError};
+
+use crate::client::{CountedExt, StatsdClient};
+
+/// Implementation of the `log` crate `Log` trait that counts log events by
+/// level using a `StatsdClient` before passing them to another logger.
+///
+/// Each event logged increments one of the counters `log.error`, `log.warn`,
+/// `log.info`, `log.debug`, or `log.trace` (after the prefix of the client),
+/// giving basic error rates for an application without instrumenting it.
+/// Events are counted even if the wrapped logger ignores them, but only events
+/// at or above the maximum level set with `log::set_max_level` are ever passed
+/// to a logger.
+///
+/// This logger is only available when the `log` feature is enabled.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{NopMetricSink, StatsdClient, StatsdLogger};
+/// use log::{LevelFilter, Log, Metadata, Record};
+///
+/// struct StderrLogger;
+///
+/// impl Log for StderrLogger {
+///     fn enabled(&self, _metadata: &Metadata) -> bool {
+///         true
+///     }
+///
+///     fn log(&self, record: &Record) {
+///         eprintln!("{} {}", record.level(), record.args());
+///     }
+///
+///     fn flush(&self) {}
+/// }
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// StatsdLogger::new(client, StderrLogger).install(LevelFilter::Warn).unwrap();
+///
+/// log::error!("Something went wrong");
+/// ```
+#[derive(Debug)]
+pub struct StatsdLogger<L> {
+    client: Arc<StatsdClient>,
+    inner: L,
+}
+
+impl<L> StatsdLogger<L>
+where
+    L: Log,
+{
+    /// Create a new logger that counts log events using the given client and
+    /// then passes them to the wrapped logger.
+    pub fn new<C>(client: C, inner: L) -> Self
+    where
+        C: Into<Arc<StatsdClient>>,
+    {
+        StatsdLogger {
+            client: client.into(),
+            inner,
+        }
+    }
+
+    /// Set this logger as the global logger, only passing events at or above the
+    /// given level to it. Returns an error if a global logger was already set.
+    pub fn install(self, level: LevelFilter) -> Result<(), SetLoggerError>
+    where
+        L: 'static,
+    {
+        log::set_boxed_logger(Box::new(self))?;
+        log::set_max_level(level);
+        Ok(())
+    }
+}
+
+impl<L> Log for StatsdLogger<L>
+where
+    L: Log,
+{
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        self.inner.enabled(metadata)
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        self.client.incr_with_tags(level_key(record.level())).send();
+        self.inner.log(record);
+    }
+
+    fn flush(&self) {
+        self.inner.flush();
+    }
+}
+
+fn level_key(level: Level) -> &'static str {
+    match level {
+        Level::Error => "log.error",
+        Level::Warn => "log.warn",
+        Level::Info => "log.info",
+        Level::Debug => "log.debug",
+        Level::Trace => "log.trace",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatsdLogger;
+    use crate::{RecordingMetricSink, StatsdClient};
+    use log::{Level, Log, Metadata, Record};
+    use std::sync::Mutex;
+
+    #[derive(Default)]
+    struct CapturingLogger {
+        messages: Mutex<Vec<String>>,
+    }
+
+    impl Log for CapturingLogger {
+        fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+            metadata.level() <= Level::Info
+        }
+
+        fn log(&self, record: &Record<'_>) {
+            if self.enabled(record.metadata()) {
+                self.messages.lock().unwrap().push(record.args().to_string());
+            }
+        }
+
+        fn flush(&self) {}
+    }
+
+    fn log(logger: &dyn Log, level: Level, message: &str) {
+        logger.log(&Record::builder().level(level).args(format_args!("{}", message)).build());
+    }
+
+    #[test]
+    fn test_logger_counts_by_level() {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink.clone());
+        let logger = StatsdLogger::new(client, CapturingLogger::default());
+
+        log(&logger, Level::Error, "first");
+        log(&logger, Level::Warn, "second");
+        log(&logger, Level::Error, "third");
+        log(&logger, Level::Debug, "fourth");
+
+        assert_eq!(
+            vec![
+                "prefix.log.error:1|c",
+                "prefix.log.warn:1|c",
+                "prefix.log.error:1|c",
+                "prefix.log.debug:1|c"
+            ],
+            sink.metrics()
+        );
+        assert_eq!(vec!["first", "second", "third"], *logger.inner.messages.lock().unwrap());
+    }
+
+    #[test]
+    fn test_logger_enabled() {
+        let client = StatsdClient::from_sink("prefix", RecordingMetricSink::new());
+        let logger = StatsdLogger::new(client, CapturingLogger::default());
+
+        assert!(logger.enabled(&Metadata::builder().level(Level::Warn).build()));
+        assert!(!logger.enabled(&Metadata::builder().level(Level::Trace).build()));
+    }
+}
@@ -0,0 +1,465 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Instant;
+
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::client::{CountedExt, Gauged, StatsdClient, Timed};
+
+type StatusFn<Resp> = Arc<dyn Fn(&Resp) -> String + Send + Sync>;
+
+// Value of the status tag for requests that fail with an error instead of
+// returning a response.
+const ERROR_STATUS: &str = "error";
+
+/// Builder for creating and customizing `RequestMetricsLayer` instances.
+///
+/// By default, the keys of metrics are the name given to the builder followed
+/// by `.requests`, `.in_flight`, and `.latency`, and requests are not tagged
+/// with a status.
+pub struct RequestMetricsLayerBuilder<Resp> {
+    requests_key: String,
+    in_flight_key: String,
+    latency_key: String,
+    status: Option<(String, StatusFn<Resp>)>,
+}
+
+impl<Resp> RequestMetricsLayerBuilder<Resp> {
+    /// Create a new builder for a service with the given name.
+    pub fn new(name: &str) -> Self {
+        RequestMetricsLayerBuilder {
+            requests_key: format!("{}.requests", name),
+            in_flight_key: format!("{}.in_flight", name),
+            latency_key: format!("{}.latency", name),
+            status: None,
+        }
+    }
+
+    /// Set the key of the counter incremented when each request completes.
+    pub fn with_requests_key(mut self, key: &str) -> Self {
+        self.requests_key = key.to_owned();
+        self
+    }
+
+    /// Set the key of the gauge of how many requests are being handled.
+    pub fn with_in_flight_key(mut self, key: &str) -> Self {
+        self.in_flight_key = key.to_owned();
+        self
+    }
+
+    /// Set the key of the timer of how long each request took.
+    pub fn with_latency_key(mut self, key: &str) -> Self {
+        self.latency_key = key.to_owned();
+        self
+    }
+
+    /// Tag the request counter and latency timer with the status of each
+    /// request, using the given tag key and the value returned by the function
+    /// for each response. Requests that fail with an error are tagged with the
+    /// value `error`.
+    pub fn with_status<F>(mut self, key: &str, f: F) -> Self
+    where
+        F: Fn(&Resp) -> String + Send + Sync + 'static,
+    {
+        self.status = Some((key.to_owned(), Arc::new(f)));
+        self
+    }
+
+    /// Construct a new `RequestMetricsLayer` that sends metrics using the given
+    /// client.
+    pub fn build<C>(self, client: C) -> RequestMetricsLayer<Resp>
+    where
+        C: Into<Arc<StatsdClient>>,
+    {
+        RequestMetricsLayer {
+            shared: Arc::new(Shared {
+                client: client.into(),
+                requests_key: self.requests_key,
+                in_flight_key: self.in_flight_key,
+                latency_key: self.latency_key,
+                status: self.status,
+                in_flight: AtomicU64::new(0),
+            }),
+        }
+    }
+}
+
+impl<Resp> fmt::Debug for RequestMetricsLayerBuilder<Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestMetricsLayerBuilder")
+            .field("requests_key", &self.requests_key)
+            .field("in_flight_key", &self.in_flight_key)
+            .field("latency_key", &self.latency_key)
+            .field("status", &self.status.as_ref().map(|(k, _)| k))
+            .finish()
+    }
+}
+
+/// Layer for `tower` services that records metrics about each request using a
+/// `StatsdClient`.
+///
+/// For each request handled by a wrapped service, the layer sends:
+///
+/// * A counter of completed requests, incremented when a response is returned
+///   or the request fails.
+/// * A gauge of how many requests are currently being handled, sent when each
+///   request starts and completes.
+/// * A timer of how long each request took to complete.
+///
+/// Requests can optionally be tagged with their status, such as the status code
+/// of an HTTP response. The in-flight count is shared by every service created
+/// by the same layer, so each distinct service should use its own layer with
+/// its own name.
+///
+/// This layer is only available when the `tower` feature is enabled.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{NopMetricSink, RequestMetricsLayer, StatsdClient};
+///
+/// struct Response {
+///     code: u16,
+/// }
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// let _layer = RequestMetricsLayer::builder("users_api")
+///     .with_status("status", |res: &Response| res.code.to_string())
+///     .build(client);
+/// ```
+pub struct RequestMetricsLayer<Resp> {
+    shared: Arc<Shared<Resp>>,
+}
+
+impl<Resp> RequestMetricsLayer<Resp> {
+    /// Create a new layer for a service with the given name that sends metrics
+    /// using the given client with the default keys and no status tag.
+    pub fn new<C>(name: &str, client: C) -> Self
+    where
+        C: Into<Arc<StatsdClient>>,
+    {
+        Self::builder(name).build(client)
+    }
+
+    /// Create a new builder for a service with the given name.
+    pub fn builder(name: &str) -> RequestMetricsLayerBuilder<Resp> {
+        RequestMetricsLayerBuilder::new(name)
+    }
+}
+
+impl<Resp> Clone for RequestMetricsLayer<Resp> {
+    fn clone(&self) -> Self {
+        RequestMetricsLayer {
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<Resp> fmt::Debug for RequestMetricsLayer<Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestMetricsLayer")
+            .field("shared", &self.shared)
+            .finish()
+    }
+}
+
+impl<S, Resp> Layer<S> for RequestMetricsLayer<Resp> {
+    type Service = RequestMetrics<S, Resp>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RequestMetrics {
+            inner,
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+/// Service that records metrics about each request to a wrapped service.
+///
+/// See `RequestMetricsLayer` for the metrics that are sent.
+pub struct RequestMetrics<S, Resp> {
+    inner: S,
+    shared: Arc<Shared<Resp>>,
+}
+
+impl<S, Resp> Clone for RequestMetrics<S, Resp>
+where
+    S: Clone,
+{
+    fn clone(&self) -> Self {
+        RequestMetrics {
+            inner: self.inner.clone(),
+            shared: self.shared.clone(),
+        }
+    }
+}
+
+impl<S, Resp> fmt::Debug for RequestMetrics<S, Resp>
+where
+    S: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestMetrics")
+            .field("inner", &self.inner)
+            .field("shared", &self.shared)
+            .finish()
+    }
+}
+
+impl<S, Req, Resp> Service<Req> for RequestMetrics<S, Resp>
+where
+    S: Service<Req, Response = Resp>,
+{
+    type Response = Resp;
+    type Error = S::Error;
+    type Future = RequestMetricsFuture<S::Future, Resp>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let in_flight = self.shared.in_flight.fetch_add(1, Ordering::AcqRel) + 1;
+        self.shared.send_in_flight(in_flight);
+
+        RequestMetricsFuture {
+            inner: Box::pin(self.inner.call(req)),
+            request: InFlightRequest {
+                shared: self.shared.clone(),
+                start: Instant::now(),
+            },
+        }
+    }
+}
+
+/// Future returned by `RequestMetrics` that records metrics about a request
+/// once it completes.
+pub struct RequestMetricsFuture<F, Resp> {
+    inner: Pin<Box<F>>,
+    request: InFlightRequest<Resp>,
+}
+
+impl<F, Resp> fmt::Debug for RequestMetricsFuture<F, Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RequestMetricsFuture")
+            .field("inner", &"...")
+            .field("start", &self.request.start)
+            .finish()
+    }
+}
+
+impl<F, Resp, E> Future for RequestMetricsFuture<F, Resp>
+where
+    F: Future<Output = Result<Resp, E>>,
+{
+    type Output = Result<Resp, E>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        let res = match self.inner.as_mut().poll(cx) {
+            Poll::Ready(res) => res,
+            Poll::Pending => return Poll::Pending,
+        };
+
+        self.request.complete(&res);
+        Poll::Ready(res)
+    }
+}
+
+// Keys and status function shared by each service created by a layer, and
+// the number of requests currently being handled by them.
+struct Shared<Resp> {
+    client: Arc<StatsdClient>,
+    requests_key: String,
+    in_flight_key: String,
+    latency_key: String,
+    status: Option<(String, StatusFn<Resp>)>,
+    in_flight: AtomicU64,
+}
+
+impl<Resp> Shared<Resp> {
+    fn send_in_flight(&self, in_flight: u64) {
+        self.client.gauge_with_tags(&self.in_flight_key, in_flight).send();
+    }
+}
+
+impl<Resp> fmt::Debug for Shared<Resp> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Shared")
+            .field("client", &self.client)
+            .field("requests_key", &self.requests_key)
+            .field("in_flight_key", &self.in_flight_key)
+            .field("latency_key", &self.latency_key)
+            .field("status", &self.status.as_ref().map(|(k, _)| k))
+            .field("in_flight", &self.in_flight)
+            .finish()
+    }
+}
+
+// Request that has started but not completed. The in-flight count is
+// decremented when this is dropped so that requests that are cancelled by
+// dropping their future aren't counted forever.
+struct InFlightRequest<Resp> {
+    shared: Arc<Shared<Resp>>,
+    start: Instant,
+}
+
+impl<Resp> InFlightRequest<Resp> {
+    fn complete<E>(&self, res: &Result<Resp, E>) {
+        let shared = &self.shared;
+        let elapsed = self.start.elapsed();
+        let status = shared.status.as_ref().map(|(key, f)| {
+            let value = match res {
+                Ok(resp) => f(resp),
+                Err(_) => ERROR_STATUS.to_owned(),
+            };
+
+            (key.as_str(), value)
+        });
+
+        let mut requests = shared.client.incr_with_tags(&shared.requests_key);
+        let mut latency = shared.client.time_with_tags(&shared.latency_key, elapsed);
+        if let Some((key, value)) = status.as_ref() {
+            requests = requests.with_tag(key, value);
+            latency = latency.with_tag(key, value);
+        }
+
+        requests.send();
+        latency.send();
+    }
+}
+
+impl<Resp> Drop for InFlightRequest<Resp> {
+    fn drop(&mut self) {
+        let in_flight = self.shared.in_flight.fetch_sub(1, Ordering::AcqRel) - 1;
+        self.shared.send_in_flight(in_flight);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::RequestMetricsLayer;
+    use crate::executor::block_on;
+    use crate::{ParsedMetric, RecordingMetricSink, StatsdClient};
+    use std::future::{ready, Ready};
+    use std::task::{Context, Poll};
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    // Service that responds with the length of each request or an error if
+    // the request is empty.
+    struct LengthService;
+
+    impl Service<&'static str> for LengthService {
+        type Response = usize;
+        type Error = &'static str;
+        type Future = Ready<Result<usize, &'static str>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, req: &'static str) -> Self::Future {
+            ready(if req.is_empty() { Err("empty") } else { Ok(req.len()) })
+        }
+    }
+
+    fn new_client() -> (RecordingMetricSink, StatsdClient) {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink.clone());
+        (sink, client)
+    }
+
+    fn names(sink: &RecordingMetricSink) -> Vec<String> {
+        sink.metrics()
+            .iter()
+            .map(|m| ParsedMetric::parse(m).unwrap().name().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn test_request_metrics() {
+        block_on(async {
+            let (sink, client) = new_client();
+            let mut service = RequestMetricsLayer::new("api", client).layer(LengthService);
+
+            assert_eq!(Ok(3), service.call("foo").await);
+            assert_eq!(
+                vec![
+                    "prefix.api.in_flight",
+                    "prefix.api.requests",
+                    "prefix.api.latency",
+                    "prefix.api.in_flight"
+                ],
+                names(&sink)
+            );
+
+            let metrics = sink.metrics();
+            assert_eq!("prefix.api.in_flight:1|g", metrics[0]);
+            assert_eq!("prefix.api.requests:1|c", metrics[1]);
+            assert_eq!("prefix.api.in_flight:0|g", metrics[3]);
+        });
+    }
+
+    #[test]
+    fn test_request_metrics_status() {
+        block_on(async {
+            let (sink, client) = new_client();
+            let mut service = RequestMetricsLayer::builder("api")
+                .with_requests_key("api.calls")
+                .with_status("status", |len: &usize| {
+                    if *len > 3 { "long" } else { "short" }.to_owned()
+                })
+                .build(client)
+                .layer(LengthService);
+
+            assert_eq!(Ok(5), service.call("hello").await);
+            assert_eq!(Err("empty"), service.call("").await);
+
+            let recorded = sink.metrics();
+            let parsed: Vec<ParsedMetric<'_>> = recorded.iter().map(|m| ParsedMetric::parse(m).unwrap()).collect();
+            let statuses: Vec<(&str, Option<&str>)> = parsed
+                .iter()
+                .filter(|m| m.name() != "prefix.api.in_flight")
+                .map(|m| (m.name(), m.tag("status")))
+                .collect();
+
+            assert_eq!(
+                vec![
+                    ("prefix.api.calls", Some("long")),
+                    ("prefix.api.latency", Some("long")),
+                    ("prefix.api.calls", Some("error")),
+                    ("prefix.api.latency", Some("error")),
+                ],
+                statuses
+            );
+        });
+    }
+
+    #[test]
+    fn test_request_metrics_cancelled() {
+        let (sink, client) = new_client();
+        let mut service = RequestMetricsLayer::new("api", client).layer(LengthService);
+
+        drop(service.call("foo"));
+
+        assert_eq!(
+            vec!["prefix.api.in_flight:1|g", "prefix.api.in_flight:0|g"],
+            sink.metrics()
+        );
+    }
+}
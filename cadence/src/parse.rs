@@ -0,0 +1,371 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use crate::builder::{MetricType, MetricValue};
+use crate::sample;
+use crate::types::{ErrorKind, MetricError, MetricResult};
+use std::fmt::{self, Write};
+
+/// Statsd metric parsed from a single line of text.
+///
+/// Parsing supports the metrics Cadence is able to emit: a name, one or more
+/// values (separated by `:` when packed), a type, and the optional Datadog
+/// extensions for sample rate (`|@`), tags (`|#`), container ID (`|c:`), and
+/// timestamp (`|T`). Events and service checks are not supported.
+///
+/// The name, type, tags, and container ID borrow from the parsed line so that
+/// parsing doesn't allocate except for packed values and tags. Formatting the
+/// parsed metric with `Display` produces an equivalent Statsd metric.
+///
+/// This is useful for checking the output of instrumented code in tests or for
+/// building relays that inspect metrics before forwarding them.
+///
+/// # Example
+///
+/// ```
+/// use cadence::ext::MetricValue;
+/// use cadence::{MetricType, ParsedMetric};
+///
+/// let metric = ParsedMetric::parse("my.app.requests:1|c|@0.5|#region:us-east-1,canary").unwrap();
+///
+/// assert_eq!("my.app.requests", metric.name());
+/// assert_eq!(&MetricValue::Signed(1), metric.value());
+/// assert_eq!(MetricType::Counter, metric.metric_type());
+/// assert_eq!(Some(0.5), metric.sample_rate());
+/// assert_eq!(&[(Some("region"), "us-east-1"), (None, "canary")], metric.tags());
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParsedMetric<'a> {
+    name: &'a str,
+    value: MetricValue,
+    type_: MetricType<'a>,
+    sample_rate: Option<f64>,
+    tags: Vec<(Option<&'a str>, &'a str)>,
+    container_id: Option<&'a str>,
+    timestamp: Option<u64>,
+}
+
+impl<'a> ParsedMetric<'a> {
+    /// Parse a single Statsd metric, with or without a trailing newline.
+    ///
+    /// # Failures
+    ///
+    /// This method will fail with an `InvalidInput` error if the metric is
+    /// missing a name, value, or type, if any of the values are not valid
+    /// numbers, or if it includes any invalid or unknown extensions.
+    pub fn parse(line: &'a str) -> MetricResult<Self> {
+        let line = line.strip_suffix('\n').unwrap_or(line);
+        if line.starts_with("_e{") || line.starts_with("_sc|") {
+            return Err(invalid("events and service checks cannot be parsed"));
+        }
+
+        let (name, rest) = line.split_once(':').ok_or_else(|| invalid("missing metric value"))?;
+        if name.is_empty() {
+            return Err(invalid("missing metric name"));
+        }
+
+        let mut parts = rest.split('|');
+        let value = parse_value(parts.next().unwrap_or(""))?;
+        let type_ = match parts.next() {
+            Some(t) if !t.is_empty() => MetricType::from_suffix(t),
+            _ => return Err(invalid("missing metric type")),
+        };
+
+        let mut metric = ParsedMetric {
+            name,
+            value,
+            type_,
+            sample_rate: None,
+            tags: Vec::new(),
+            container_id: None,
+            timestamp: None,
+        };
+
+        for part in parts {
+            if let Some(rate) = part.strip_prefix('@') {
+                metric.sample_rate = Some(parse_rate(rate)?);
+            } else if let Some(tags) = part.strip_prefix('#') {
+                metric.tags.extend(tags.split(',').map(parse_tag));
+            } else if let Some(container_id) = part.strip_prefix("c:") {
+                metric.container_id = Some(container_id);
+            } else if let Some(timestamp) = part.strip_prefix('T') {
+                metric.timestamp = Some(timestamp.parse().map_err(|_| invalid("invalid metric timestamp"))?);
+            } else {
+                return Err(invalid("unknown metric extension"));
+            }
+        }
+
+        Ok(metric)
+    }
+
+    /// Return the full name of the metric, including any prefix.
+    pub fn name(&self) -> &'a str {
+        self.name
+    }
+
+    /// Return the value (or values if packed) of the metric.
+    ///
+    /// Values are parsed as signed integers if possible, unsigned integers if
+    /// they are too large for signed integers, and floats otherwise. Packed
+    /// values are all parsed as the same type.
+    pub fn value(&self) -> &MetricValue {
+        &self.value
+    }
+
+    /// Return the type of the metric.
+    pub fn metric_type(&self) -> MetricType<'a> {
+        self.type_
+    }
+
+    /// Return the sample rate of the metric, if any.
+    pub fn sample_rate(&self) -> Option<f64> {
+        self.sample_rate
+    }
+
+    /// Return the tags of the metric in the order they appear, with `None`
+    /// as the key of value tags.
+    pub fn tags(&self) -> &[(Option<&'a str>, &'a str)] {
+        &self.tags
+    }
+
+    /// Return the value of the tag with the given key, if the metric has one.
+    pub fn tag(&self, key: &str) -> Option<&'a str> {
+        self.tags.iter().find(|(k, _)| *k == Some(key)).map(|(_, v)| *v)
+    }
+
+    /// Return the container ID of the metric, if any.
+    pub fn container_id(&self) -> Option<&'a str> {
+        self.container_id
+    }
+
+    /// Return the UNIX timestamp in seconds of the metric, if any.
+    pub fn timestamp(&self) -> Option<u64> {
+        self.timestamp
+    }
+}
+
+impl<'a> fmt::Display for ParsedMetric<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)?;
+        f.write_char(':')?;
+        self.value.write_to(f)?;
+        f.write_char('|')?;
+        f.write_str(self.type_.as_str())?;
+
+        if let Some(rate) = self.sample_rate {
+            write!(f, "|@{}", rate)?;
+        }
+
+        for (i, (key, value)) in self.tags.iter().enumerate() {
+            f.write_str(if i == 0 { "|#" } else { "," })?;
+            if let Some(key) = key {
+                f.write_str(key)?;
+                f.write_char(':')?;
+            }
+            f.write_str(value)?;
+        }
+
+        if let Some(container_id) = self.container_id {
+            write!(f, "|c:{}", container_id)?;
+        }
+
+        if let Some(timestamp) = self.timestamp {
+            write!(f, "|T{}", timestamp)?;
+        }
+
+        Ok(())
+    }
+}
+
+fn invalid(desc: &'static str) -> MetricError {
+    MetricError::from((ErrorKind::InvalidInput, desc))
+}
+
+fn parse_value(value: &str) -> MetricResult<MetricValue> {
+    if value.is_empty() {
+        return Err(invalid("missing metric value"));
+    }
+
+    if !value.contains(':') {
+        return if let Ok(v) = value.parse() {
+            Ok(MetricValue::Signed(v))
+        } else if let Ok(v) = value.parse() {
+            Ok(MetricValue::Unsigned(v))
+        } else {
+            parse_float(value).map(MetricValue::Float)
+        };
+    }
+
+    let values = value.split(':');
+    if let Ok(v) = values.clone().map(str::parse).collect() {
+        Ok(MetricValue::PackedSigned(v))
+    } else if let Ok(v) = values.clone().map(str::parse).collect() {
+        Ok(MetricValue::PackedUnsigned(v))
+    } else {
+        values
+            .map(parse_float)
+            .collect::<MetricResult<_>>()
+            .map(MetricValue::PackedFloat)
+    }
+}
+
+fn parse_float(value: &str) -> MetricResult<f64> {
+    match value.parse::<f64>() {
+        Ok(v) if v.is_finite() => Ok(v),
+        _ => Err(invalid("invalid metric value")),
+    }
+}
+
+fn parse_rate(rate: &str) -> MetricResult<f64> {
+    match rate.parse() {
+        Ok(rate) if sample::is_valid_rate(rate) => Ok(rate),
+        _ => Err(invalid("invalid metric sample rate")),
+    }
+}
+
+fn parse_tag(tag: &str) -> (Option<&str>, &str) {
+    match tag.split_once(':') {
+        Some((key, value)) => (Some(key), value),
+        None => (None, tag),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::ParsedMetric;
+    use crate::builder::{MetricType, MetricValue};
+    use crate::client::{Counted, Distributed, Gauged, Histogrammed, Setted, StatsdClient, Timed};
+    use crate::sinks::NopMetricSink;
+    use crate::types::{ErrorKind, Metric};
+
+    #[test]
+    fn test_parse_simple() {
+        let metric = ParsedMetric::parse("some.counter:-4|c\n").unwrap();
+
+        assert_eq!("some.counter", metric.name());
+        assert_eq!(&MetricValue::Signed(-4), metric.value());
+        assert_eq!(MetricType::Counter, metric.metric_type());
+        assert_eq!(None, metric.sample_rate());
+        assert!(metric.tags().is_empty());
+        assert_eq!(None, metric.container_id());
+        assert_eq!(None, metric.timestamp());
+    }
+
+    #[test]
+    fn test_parse_values() {
+        let parse = |line| ParsedMetric::parse(line).unwrap().value().clone();
+
+        assert_eq!(MetricValue::Unsigned(u64::MAX), parse("a:18446744073709551615|g"));
+        assert_eq!(MetricValue::Float(2.5), parse("a:2.5|h"));
+        assert_eq!(MetricValue::PackedSigned(vec![1, -2, 3]), parse("a:1:-2:3|d"));
+        assert_eq!(
+            MetricValue::PackedUnsigned(vec![1, u64::MAX]),
+            parse("a:1:18446744073709551615|ms")
+        );
+        assert_eq!(MetricValue::PackedFloat(vec![1.0, 2.5]), parse("a:1:2.5|h"));
+    }
+
+    #[test]
+    fn test_parse_extensions() {
+        let line = "some.timer:23|ms|@0.1|#region:us-east-1,canary,url:http://example.com|c:1234|T1700000000";
+        let metric = ParsedMetric::parse(line).unwrap();
+
+        assert_eq!(Some(0.1), metric.sample_rate());
+        assert_eq!(
+            &[
+                (Some("region"), "us-east-1"),
+                (None, "canary"),
+                (Some("url"), "http://example.com")
+            ],
+            metric.tags()
+        );
+        assert_eq!(Some("us-east-1"), metric.tag("region"));
+        assert_eq!(None, metric.tag("canary"));
+        assert_eq!(Some("1234"), metric.container_id());
+        assert_eq!(Some(1700000000), metric.timestamp());
+        assert_eq!(line, metric.to_string());
+    }
+
+    #[test]
+    fn test_parse_custom_type() {
+        let metric = ParsedMetric::parse("some.ratio:0.75|pct").unwrap();
+        assert_eq!(MetricType::Custom("pct"), metric.metric_type());
+    }
+
+    #[test]
+    fn test_parse_client_metrics() {
+        let client = StatsdClient::builder("prefix", NopMetricSink)
+            .with_tag("env", "prod")
+            .with_clock(|| 1234)
+            .build();
+
+        let metrics = vec![
+            client
+                .count_with_tags("counter", 1)
+                .with_tag("foo", "bar")
+                .try_send()
+                .unwrap()
+                .as_metric_str()
+                .to_owned(),
+            client
+                .time_with_tags("timer", vec![1, 2])
+                .try_send()
+                .unwrap()
+                .as_metric_str()
+                .to_owned(),
+            client
+                .gauge_with_tags("gauge", 5.5)
+                .with_sampling_rate(0.5)
+                .try_send()
+                .unwrap()
+                .as_metric_str()
+                .to_owned(),
+            client.histogram("histogram", 4.0).unwrap().as_metric_str().to_owned(),
+            client
+                .distribution("distribution", vec![0.5, 1.5])
+                .unwrap()
+                .as_metric_str()
+                .to_owned(),
+            client.set("set", 7).unwrap().as_metric_str().to_owned(),
+        ];
+
+        for line in metrics {
+            let metric = ParsedMetric::parse(&line).unwrap();
+            assert_eq!(line, metric.to_string());
+            assert_eq!(Some("prod"), metric.tag("env"));
+            assert_eq!(Some(1234), metric.timestamp());
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        let lines = [
+            "",
+            "some.counter",
+            ":1|c",
+            "some.counter:|c",
+            "some.counter:1",
+            "some.counter:1|",
+            "some.counter:one|c",
+            "some.counter:1:two|c",
+            "some.counter:NaN|c",
+            "some.counter:1|c|@1.5",
+            "some.counter:1|c|Tnow",
+            "some.counter:1|c|x:y",
+            "_e{6,15}:Deploy|Deployed v1.2.3",
+            "_sc|db|0",
+        ];
+
+        for line in lines.iter() {
+            let res = ParsedMetric::parse(line);
+            assert_eq!(ErrorKind::InvalidInput, res.unwrap_err().kind(), "line: {:?}", line);
+        }
+    }
+}
@@ -27,4 +27,7 @@
 //! client.distribution("some.distribution", 45.5).unwrap();
 //! ```
 
-pub use crate::client::{Counted, CountedExt, Distributed, Gauged, Histogrammed, Metered, MetricClient, Setted, Timed};
+pub use crate::client::{
+    Counted, CountedExt, Distributed, Evented, Gauged, Histogrammed, Metered, MetricClient, ServiceChecked, Setted,
+    Timed,
+};
@@ -0,0 +1,382 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::io;
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::client::{Gauged, StatsdClient};
+
+const DEFAULT_PREFIX: &str = "process";
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Builder for creating and customizing `ProcessMetricsReporter` instances.
+///
+/// By default, metrics are reported every 10 seconds with keys starting with
+/// `process`.
+#[derive(Debug, Clone)]
+pub struct ProcessMetricsReporterBuilder {
+    prefix: String,
+    interval: Duration,
+}
+
+impl ProcessMetricsReporterBuilder {
+    /// Create a new builder with the default prefix and interval.
+    pub fn new() -> Self {
+        ProcessMetricsReporterBuilder {
+            prefix: DEFAULT_PREFIX.to_owned(),
+            interval: DEFAULT_INTERVAL,
+        }
+    }
+
+    /// Set the prefix of the keys of reported metrics, added after the prefix
+    /// of the client.
+    pub fn with_prefix(mut self, prefix: &str) -> Self {
+        self.prefix = prefix.to_owned();
+        self
+    }
+
+    /// Set how often metrics are reported.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Start a thread reporting metrics about the current process using the
+    /// given client, returning an error if the thread can't be started. No
+    /// thread is started when the client is disabled since nothing would be
+    /// sent.
+    pub fn build<C>(self, client: C) -> io::Result<ProcessMetricsReporter>
+    where
+        C: Into<Arc<StatsdClient>>,
+    {
+        let client = client.into().scoped(&self.prefix);
+        let stopped = Arc::new((Mutex::new(false), Condvar::new()));
+        let thread_stopped = stopped.clone();
+        let thread = if client.is_disabled() {
+            None
+        } else {
+            Some(
+                thread::Builder::new()
+                    .name("cadence-process-metrics".into())
+                    .spawn(move || run_reporter(&client, self.interval, &thread_stopped))?,
+            )
+        };
+
+        Ok(ProcessMetricsReporter { stopped, thread })
+    }
+}
+
+impl Default for ProcessMetricsReporterBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Reporter that periodically sends metrics about the current process as
+/// gauges using a `StatsdClient`.
+///
+/// Metrics are sent from a background thread as soon as the reporter is started
+/// and then on a fixed interval until the reporter is stopped or dropped. The
+/// following metrics are reported, after the configured prefix:
+///
+/// * `memory.rss` - Resident set size of the process in bytes.
+/// * `cpu.seconds` - Total user and system CPU time used by the process in
+///   seconds.
+/// * `open_fds` - Number of file descriptors open by the process.
+/// * `threads` - Number of threads in the process.
+///
+/// These metrics are read from `/proc` and are only reported on Linux. On other
+/// platforms the reporter runs but does not send any metrics.
+///
+/// This reporter is only available when the `process-metrics` feature is
+/// enabled.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use cadence::{NopMetricSink, ProcessMetricsReporter, StatsdClient};
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// let reporter = ProcessMetricsReporter::builder()
+///     .with_interval(Duration::from_secs(30))
+///     .build(client)
+///     .unwrap();
+///
+/// // Metrics are reported until the reporter is stopped or dropped.
+/// reporter.stop();
+/// ```
+pub struct ProcessMetricsReporter {
+    stopped: Arc<(Mutex<bool>, Condvar)>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl ProcessMetricsReporter {
+    /// Start reporting metrics using the given client with the default prefix
+    /// and interval.
+    pub fn start<C>(client: C) -> io::Result<Self>
+    where
+        C: Into<Arc<StatsdClient>>,
+    {
+        Self::builder().build(client)
+    }
+
+    /// Create a new builder for a reporter.
+    pub fn builder() -> ProcessMetricsReporterBuilder {
+        ProcessMetricsReporterBuilder::new()
+    }
+
+    /// Stop reporting metrics, waiting for the reporting thread to exit.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        let (lock, cond) = &*self.stopped;
+        *lock.lock().unwrap() = true;
+        cond.notify_all();
+
+        if let Some(t) = self.thread.take() {
+            let _ = t.join();
+        }
+    }
+}
+
+impl fmt::Debug for ProcessMetricsReporter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ProcessMetricsReporter")
+            .field("stopped", &self.stopped.0)
+            .finish()
+    }
+}
+
+impl Drop for ProcessMetricsReporter {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+fn run_reporter(client: &StatsdClient, interval: Duration, stopped: &(Mutex<bool>, Condvar)) {
+    let (lock, cond) = stopped;
+    loop {
+        report(client, &ProcessStats::current());
+
+        let guard = lock.lock().unwrap();
+        let (guard, _) = cond.wait_timeout_while(guard, interval, |stopped| !*stopped).unwrap();
+        if *guard {
+            break;
+        }
+    }
+}
+
+// Send the given stats using a client scoped to the prefix of the reporter.
+fn report(client: &StatsdClient, stats: &ProcessStats) {
+    if let Some(rss) = stats.rss_bytes {
+        client.gauge_with_tags("memory.rss", rss).send();
+    }
+
+    if let Some(cpu) = stats.cpu_seconds {
+        client.gauge_with_tags("cpu.seconds", cpu).send();
+    }
+
+    if let Some(fds) = stats.open_fds {
+        client.gauge_with_tags("open_fds", fds).send();
+    }
+
+    if let Some(threads) = stats.threads {
+        client.gauge_with_tags("threads", threads).send();
+    }
+}
+
+// Resource usage of the current process. Each value is `None` if it couldn't
+// be read on this platform.
+#[derive(Debug, Default, Clone, PartialEq)]
+struct ProcessStats {
+    rss_bytes: Option<u64>,
+    cpu_seconds: Option<f64>,
+    open_fds: Option<u64>,
+    threads: Option<u64>,
+}
+
+#[cfg(target_os = "linux")]
+impl ProcessStats {
+    // Frequency of the clock ticks used for CPU time in `/proc/self/stat`,
+    // which is fixed by the kernel ABI regardless of the kernel configuration.
+    const CLOCK_TICKS: f64 = 100.0;
+
+    fn current() -> Self {
+        let mut stats = std::fs::read_to_string("/proc/self/stat")
+            .map(|s| Self::from_stat(&s))
+            .unwrap_or_default();
+
+        stats.rss_bytes = std::fs::read_to_string("/proc/self/status")
+            .ok()
+            .and_then(|s| Self::rss_from_status(&s));
+        stats.open_fds = std::fs::read_dir("/proc/self/fd")
+            .map(|entries| entries.count() as u64)
+            .ok();
+
+        stats
+    }
+
+    // Parse CPU time and threads from the contents of `/proc/self/stat`. The
+    // second field is the name of the executable in parentheses, which may
+    // contain spaces, so fields are counted from the last closing parenthesis.
+    fn from_stat(stat: &str) -> Self {
+        let fields: Vec<&str> = match stat.rfind(')') {
+            Some(i) => stat[i + 1..].split_whitespace().collect(),
+            None => return Self::default(),
+        };
+
+        // Fields after the executable name start at the 3rd field, `state`.
+        let field = |n: usize| fields.get(n - 3).and_then(|v| v.parse::<u64>().ok());
+        let cpu = match (field(14), field(15)) {
+            (Some(utime), Some(stime)) => Some((utime + stime) as f64 / Self::CLOCK_TICKS),
+            _ => None,
+        };
+
+        ProcessStats {
+            cpu_seconds: cpu,
+            threads: field(20),
+            ..Self::default()
+        }
+    }
+
+    // Parse the resident set size in bytes from the contents of
+    // `/proc/self/status`, where it is given in kilobytes.
+    fn rss_from_status(status: &str) -> Option<u64> {
+        status
+            .lines()
+            .find_map(|line| line.strip_prefix("VmRSS:"))
+            .and_then(|v| v.trim().trim_end_matches("kB").trim().parse::<u64>().ok())
+            .map(|kb| kb * 1024)
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+impl ProcessStats {
+    fn current() -> Self {
+        Self::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{report, ProcessMetricsReporter, ProcessStats};
+    use crate::{RecordingMetricSink, StatsdClient};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_report() {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink.clone());
+        let stats = ProcessStats {
+            rss_bytes: Some(1024),
+            cpu_seconds: Some(1.5),
+            open_fds: None,
+            threads: Some(4),
+        };
+
+        report(&client.scoped("proc"), &stats);
+
+        assert_eq!(
+            vec![
+                "prefix.proc.memory.rss:1024|g",
+                "prefix.proc.cpu.seconds:1.5|g",
+                "prefix.proc.threads:4|g",
+            ],
+            sink.metrics()
+        );
+    }
+
+    #[test]
+    fn test_reporter_empty_prefix() {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::from_sink("", sink.clone());
+        let reporter = ProcessMetricsReporter::builder()
+            .with_prefix("")
+            .with_interval(Duration::from_secs(60))
+            .build(client)
+            .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while sink.is_empty() && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        reporter.stop();
+        if cfg!(target_os = "linux") {
+            assert!(sink.metrics().iter().any(|m| m.starts_with("memory.rss:")));
+        }
+        assert!(sink.metrics().iter().all(|m| !m.starts_with('.')));
+    }
+
+    #[test]
+    fn test_reporter_interval() {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink.clone());
+        let reporter = ProcessMetricsReporter::builder()
+            .with_prefix("proc")
+            .with_interval(Duration::from_millis(10))
+            .build(client)
+            .unwrap();
+
+        let deadline = Instant::now() + Duration::from_secs(5);
+        while sink.len() < 8 && Instant::now() < deadline {
+            std::thread::sleep(Duration::from_millis(10));
+        }
+
+        reporter.stop();
+        let reported = sink.len();
+        std::thread::sleep(Duration::from_millis(50));
+
+        assert_eq!(reported, sink.len());
+        if cfg!(target_os = "linux") {
+            assert!(reported >= 8);
+            assert!(sink.metrics().iter().any(|m| m.starts_with("prefix.proc.threads:")));
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_process_stats_from_stat() {
+        let stat = "1234 (my (app)) S 1 1234 1234 0 -1 4194560 2187 0 0 0 150 50 0 0 20 0 7 0 \
+                    12345 123456789 300 18446744073709551615";
+        let stats = ProcessStats::from_stat(stat);
+
+        assert_eq!(Some(2.0), stats.cpu_seconds);
+        assert_eq!(Some(7), stats.threads);
+        assert_eq!(None, stats.rss_bytes);
+        assert_eq!(None, stats.open_fds);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_process_stats_rss_from_status() {
+        let status = "Name:\tapp\nVmPeak:\t  20000 kB\nVmRSS:\t   5120 kB\nThreads:\t7\n";
+
+        assert_eq!(Some(5120 * 1024), ProcessStats::rss_from_status(status));
+        assert_eq!(None, ProcessStats::rss_from_status("Name:\tapp\n"));
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_process_stats_current() {
+        let stats = ProcessStats::current();
+
+        assert!(stats.rss_bytes.unwrap() > 0);
+        assert!(stats.cpu_seconds.is_some());
+        assert!(stats.open_fds.unwrap() > 0);
+        assert!(stats.threads.unwrap() >= 1);
+    }
+}
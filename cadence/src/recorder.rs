@@ -0,0 +1,257 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use metrics::{
+    Counter, CounterFn, Gauge, GaugeFn, Histogram, HistogramFn, Key, KeyName, Metadata, Recorder, SharedString, Unit,
+};
+
+use crate::builder::MetricBuilder;
+use crate::client::{Counted, Gauged, Histogrammed, StatsdClient};
+use crate::types::Metric;
+
+/// Implementation of the `metrics` crate `Recorder` trait that sends metrics
+/// using a `StatsdClient`.
+///
+/// This allows libraries instrumented with the macros of the `metrics` crate
+/// to emit metrics using the same client, sinks, and default tags as code that
+/// uses Cadence directly. Labels of metrics are sent as key-value tags.
+///
+/// Metrics are mapped to Statsd metrics as follows:
+///
+/// * Counters are sent as counters. Setting the absolute value of a counter
+///   sends the difference from the previous absolute value.
+/// * Gauges are sent as gauges. Since Statsd gauges can only be set, the value
+///   of each gauge is kept by the recorder so that incrementing or decrementing
+///   it sends the new value.
+/// * Histograms are sent as histograms.
+///
+/// Descriptions and units of metrics are not supported by Statsd and are
+/// ignored.
+///
+/// This recorder is only available when the `metrics` feature is enabled,
+/// which requires Rust 1.71 or newer.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{NopMetricSink, StatsdClient, StatsdRecorder};
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// let recorder = StatsdRecorder::new(client);
+///
+/// metrics::set_global_recorder(recorder).unwrap();
+/// metrics::counter!("requests", "method" => "GET").increment(1);
+/// ```
+pub struct StatsdRecorder {
+    client: Arc<StatsdClient>,
+    counters: Mutex<HashMap<Key, Arc<AtomicU64>>>,
+    gauges: Mutex<HashMap<Key, Arc<AtomicU64>>>,
+}
+
+impl StatsdRecorder {
+    /// Create a new recorder that sends metrics using the given client.
+    pub fn new<C>(client: C) -> Self
+    where
+        C: Into<Arc<StatsdClient>>,
+    {
+        StatsdRecorder {
+            client: client.into(),
+            counters: Mutex::new(HashMap::new()),
+            gauges: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn handle(&self, key: &Key, values: &Mutex<HashMap<Key, Arc<AtomicU64>>>, initial: u64) -> MetricsHandle {
+        let value = values
+            .lock()
+            .unwrap()
+            .entry(key.clone())
+            .or_insert_with(|| Arc::new(AtomicU64::new(initial)))
+            .clone();
+
+        MetricsHandle {
+            client: self.client.clone(),
+            key: key.clone(),
+            value,
+        }
+    }
+}
+
+impl fmt::Debug for StatsdRecorder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("StatsdRecorder")
+            .field("client", &self.client)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Recorder for StatsdRecorder {
+    fn describe_counter(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_gauge(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn describe_histogram(&self, _key: KeyName, _unit: Option<Unit>, _description: SharedString) {}
+
+    fn register_counter(&self, key: &Key, _metadata: &Metadata<'_>) -> Counter {
+        Counter::from_arc(Arc::new(self.handle(key, &self.counters, 0)))
+    }
+
+    fn register_gauge(&self, key: &Key, _metadata: &Metadata<'_>) -> Gauge {
+        Gauge::from_arc(Arc::new(self.handle(key, &self.gauges, 0f64.to_bits())))
+    }
+
+    fn register_histogram(&self, key: &Key, _metadata: &Metadata<'_>) -> Histogram {
+        Histogram::from_arc(Arc::new(MetricsHandle {
+            client: self.client.clone(),
+            key: key.clone(),
+            value: Arc::new(AtomicU64::new(0)),
+        }))
+    }
+}
+
+// Metric registered with the recorder. The value is shared by every handle
+// for the same key and is the last absolute value of a counter or the bits
+// of the current value of a gauge. It is unused by histograms.
+struct MetricsHandle {
+    client: Arc<StatsdClient>,
+    key: Key,
+    value: Arc<AtomicU64>,
+}
+
+impl MetricsHandle {
+    fn send<T>(&self, builder: MetricBuilder<'_, '_, T>)
+    where
+        T: Metric + From<String>,
+    {
+        self.key
+            .labels()
+            .fold(builder, |b, label| b.with_tag(label.key(), label.value()))
+            .send()
+    }
+
+    fn update_gauge<F>(&self, f: F)
+    where
+        F: Fn(f64) -> f64,
+    {
+        let previous = self
+            .value
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |v| {
+                Some(f(f64::from_bits(v)).to_bits())
+            })
+            .unwrap();
+
+        let current = f(f64::from_bits(previous));
+        self.send(self.client.gauge_with_tags(self.key.name(), current));
+    }
+}
+
+impl CounterFn for MetricsHandle {
+    fn increment(&self, value: u64) {
+        self.send(self.client.count_with_tags(self.key.name(), value));
+    }
+
+    fn absolute(&self, value: u64) {
+        let previous = self.value.fetch_max(value, Ordering::AcqRel);
+        if value > previous {
+            CounterFn::increment(self, value - previous);
+        }
+    }
+}
+
+impl GaugeFn for MetricsHandle {
+    fn increment(&self, value: f64) {
+        self.update_gauge(|v| v + value);
+    }
+
+    fn decrement(&self, value: f64) {
+        self.update_gauge(|v| v - value);
+    }
+
+    fn set(&self, value: f64) {
+        self.update_gauge(|_| value);
+    }
+}
+
+impl HistogramFn for MetricsHandle {
+    fn record(&self, value: f64) {
+        self.send(self.client.histogram_with_tags(self.key.name(), value));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::StatsdRecorder;
+    use crate::{RecordingMetricSink, StatsdClient};
+
+    fn new_recorder() -> (RecordingMetricSink, StatsdRecorder) {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink.clone());
+        (sink, StatsdRecorder::new(client))
+    }
+
+    #[test]
+    fn test_recorder_counter() {
+        let (sink, recorder) = new_recorder();
+
+        metrics::with_local_recorder(&recorder, || {
+            metrics::counter!("requests", "method" => "GET").increment(2);
+            metrics::counter!("total").absolute(5);
+            metrics::counter!("total").absolute(3);
+            metrics::counter!("total").absolute(8);
+        });
+
+        assert_eq!(
+            vec![
+                "prefix.requests:2|c|#method:GET",
+                "prefix.total:5|c",
+                "prefix.total:3|c"
+            ],
+            sink.metrics()
+        );
+    }
+
+    #[test]
+    fn test_recorder_gauge() {
+        let (sink, recorder) = new_recorder();
+
+        metrics::with_local_recorder(&recorder, || {
+            metrics::gauge!("connections", "pool" => "db").set(4.0);
+            metrics::gauge!("connections", "pool" => "db").increment(2.0);
+            metrics::gauge!("connections", "pool" => "db").decrement(1.5);
+            metrics::gauge!("connections", "pool" => "cache").increment(1.0);
+        });
+
+        assert_eq!(
+            vec![
+                "prefix.connections:4|g|#pool:db",
+                "prefix.connections:6|g|#pool:db",
+                "prefix.connections:4.5|g|#pool:db",
+                "prefix.connections:1|g|#pool:cache",
+            ],
+            sink.metrics()
+        );
+    }
+
+    #[test]
+    fn test_recorder_histogram() {
+        let (sink, recorder) = new_recorder();
+
+        metrics::with_local_recorder(&recorder, || {
+            metrics::histogram!("latency", "status" => "200").record(0.25);
+        });
+
+        assert_eq!(vec!["prefix.latency:0.25|h|#status:200"], sink.metrics());
+    }
+}
@@ -0,0 +1,192 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cell::Cell;
+use std::collections::hash_map::RandomState;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::Mutex;
+
+// Seed used by `SeededRng` in place of zero, which the generator can't use
+const ZERO_SEED: u64 = 0x9E37_79B9_7F4A_7C15;
+
+/// Source of the random numbers used to decide which metrics are sent when
+/// they are sampled.
+///
+/// By default, `StatsdClient` uses `ThreadLocalRng`. Tests of code that
+/// sends sampled metrics can use `SeededRng` or their own implementation
+/// instead to make the metrics that are sent predictable. Implementations
+/// can also be used to plug in a faster or otherwise preferred generator.
+pub trait Rng {
+    /// Return a pseudo-random number in the range `[0, 1)`.
+    fn next_f64(&self) -> f64;
+}
+
+/// `Rng` implementation that uses a separate generator for each thread,
+/// seeded randomly. This is the default used by `StatsdClient`.
+///
+/// Since each thread has its own generator, deciding whether to send a
+/// sampled metric never requires any synchronization between threads.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ThreadLocalRng;
+
+impl Rng for ThreadLocalRng {
+    fn next_f64(&self) -> f64 {
+        next_f64()
+    }
+}
+
+/// `Rng` implementation that always returns the same sequence of numbers for
+/// the same seed, for testing.
+///
+/// The generator is shared by every thread using it, so the sequence is only
+/// predictable when metrics are sent in a predictable order.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{NopMetricSink, SeededRng, StatsdClient};
+///
+/// let client = StatsdClient::builder("my.app", NopMetricSink)
+///     .with_sample_rate(0.5)
+///     .with_rng(SeededRng::new(42))
+///     .build();
+/// ```
+#[derive(Debug)]
+pub struct SeededRng {
+    state: Mutex<u64>,
+}
+
+impl SeededRng {
+    /// Create a new generator with the given seed.
+    pub fn new(seed: u64) -> Self {
+        let state = if seed == 0 { ZERO_SEED } else { seed };
+        SeededRng {
+            state: Mutex::new(state),
+        }
+    }
+}
+
+impl Rng for SeededRng {
+    fn next_f64(&self) -> f64 {
+        let mut state = self.state.lock().unwrap();
+        xorshift(&mut state)
+    }
+}
+
+thread_local! {
+    // Each thread gets its own generator so that deciding whether to keep a
+    // sampled metric never requires any synchronization between threads.
+    static STATE: Cell<u64> = Cell::new(seed());
+}
+
+fn seed() -> u64 {
+    // `RandomState` is seeded with random keys by the standard library which
+    // saves us from having to depend on an external crate for randomness. The
+    // state must never be zero for the generator to work.
+    RandomState::new().build_hasher().finish() | 1
+}
+
+/// Return a pseudo-random number in the range `[0, 1)` from the generator of
+/// the current thread.
+pub(crate) fn next_f64() -> f64 {
+    STATE.with(|state| {
+        let mut x = state.get();
+        let v = xorshift(&mut x);
+        state.set(x);
+        v
+    })
+}
+
+/// Advance the given state and return a pseudo-random number in the range
+/// `[0, 1)` based on it.
+///
+/// This uses the xorshift64* generator which is fast and good enough for
+/// sampling metrics but is not suitable for anything security related.
+fn xorshift(x: &mut u64) -> f64 {
+    *x ^= *x >> 12;
+    *x ^= *x << 25;
+    *x ^= *x >> 27;
+
+    // Use the top 53 bits since that's the precision of an `f64`
+    (x.wrapping_mul(0x2545_F491_4F6C_DD1D) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Return true if a metric with the given sample rate should be sent, using
+/// the given generator to decide.
+pub(crate) fn is_sampled(rate: f64, rng: &dyn Rng) -> bool {
+    if rate >= 1.0 {
+        true
+    } else if rate <= 0.0 {
+        false
+    } else {
+        rng.next_f64() < rate
+    }
+}
+
+/// Return true if the given value is a valid sample rate, between 0 and 1 inclusive.
+pub(crate) fn is_valid_rate(rate: f64) -> bool {
+    (0.0..=1.0).contains(&rate)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{is_sampled, is_valid_rate, next_f64, Rng, SeededRng, ThreadLocalRng};
+
+    #[test]
+    fn test_next_f64_range() {
+        for _ in 0..10_000 {
+            let v = next_f64();
+            assert!((0.0..1.0).contains(&v), "unexpected value {}", v);
+        }
+    }
+
+    #[test]
+    fn test_is_sampled_always() {
+        assert!((0..1_000).all(|_| is_sampled(1.0, &ThreadLocalRng)));
+    }
+
+    #[test]
+    fn test_is_sampled_never() {
+        assert!((0..1_000).all(|_| !is_sampled(0.0, &ThreadLocalRng)));
+    }
+
+    #[test]
+    fn test_is_sampled_rate() {
+        let kept = (0..10_000).filter(|_| is_sampled(0.5, &ThreadLocalRng)).count();
+        assert!((4_000..6_000).contains(&kept), "unexpected sampled count {}", kept);
+    }
+
+    #[test]
+    fn test_seeded_rng() {
+        let (a, b) = (SeededRng::new(42), SeededRng::new(42));
+        let first: Vec<f64> = (0..100).map(|_| a.next_f64()).collect();
+        let second: Vec<f64> = (0..100).map(|_| b.next_f64()).collect();
+
+        assert_eq!(first, second);
+        assert!(first.iter().all(|v| (0.0..1.0).contains(v)));
+        assert_ne!(first[0], SeededRng::new(43).next_f64());
+    }
+
+    #[test]
+    fn test_seeded_rng_zero() {
+        let rng = SeededRng::new(0);
+        assert!((0..100).map(|_| rng.next_f64()).any(|v| v > 0.0));
+    }
+
+    #[test]
+    fn test_is_valid_rate() {
+        assert!(is_valid_rate(0.0));
+        assert!(is_valid_rate(0.25));
+        assert!(is_valid_rate(1.0));
+        assert!(!is_valid_rate(-0.1));
+        assert!(!is_valid_rate(1.5));
+        assert!(!is_valid_rate(f64::NAN));
+    }
+}
@@ -0,0 +1,398 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::sample::{self, Rng, ThreadLocalRng};
+use crate::sinks::core::{MetricSink, SinkStats};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Implementation of a builder pattern for `AdaptiveSamplingMetricSink`.
+///
+/// The builder requires the maximum number of metrics per second to send for
+/// each key. It can also be used to set how often the sample rate of each key
+/// is adjusted, every second by default, and the random number generator used
+/// to decide which metrics are sent.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::net::UdpSocket;
+/// use std::time::Duration;
+/// use cadence::{AdaptiveSamplingMetricSinkBuilder, UdpMetricSink, DEFAULT_PORT};
+///
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let udp = UdpMetricSink::from(("localhost", DEFAULT_PORT), socket).unwrap();
+///
+/// let sink = AdaptiveSamplingMetricSinkBuilder::new(500.0)
+///     .with_interval(Duration::from_secs(5))
+///     .build(udp);
+/// ```
+pub struct AdaptiveSamplingMetricSinkBuilder {
+    max_per_second: f64,
+    interval: Duration,
+    rng: Box<dyn Rng + Sync + Send + RefUnwindSafe>,
+}
+
+impl AdaptiveSamplingMetricSinkBuilder {
+    /// Construct a new builder that targets at most the given number of
+    /// metrics per second for each key.
+    pub fn new(max_per_second: f64) -> Self {
+        AdaptiveSamplingMetricSinkBuilder {
+            max_per_second,
+            interval: DEFAULT_INTERVAL,
+            rng: Box::new(ThreadLocalRng),
+        }
+    }
+
+    /// Set how often the sample rate of each key is adjusted based on the
+    /// number of metrics seen for it since the last adjustment.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the interval is zero.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        assert!(interval > Duration::ZERO, "interval must be greater than zero");
+        self.interval = interval;
+        self
+    }
+
+    /// Set the random number generator used to decide which metrics are sent,
+    /// `ThreadLocalRng` by default.
+    pub fn with_rng<R>(mut self, rng: R) -> Self
+    where
+        R: Rng + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.rng = Box::new(rng);
+        self
+    }
+
+    /// Construct a new `AdaptiveSamplingMetricSink` instance wrapping the given
+    /// sink based on the builder configuration.
+    pub fn build<T>(self, sink: T) -> AdaptiveSamplingMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        AdaptiveSamplingMetricSink {
+            sink: Box::new(sink),
+            max_per_second: self.max_per_second,
+            interval: self.interval,
+            rng: self.rng,
+            state: Mutex::new(Rates {
+                pruned: Instant::now(),
+                keys: HashMap::new(),
+            }),
+            sampled_out: AtomicU64::new(0),
+        }
+    }
+}
+
+impl fmt::Debug for AdaptiveSamplingMetricSinkBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "AdaptiveSamplingMetricSinkBuilder {{ max_per_second: {}, interval: {:?} }}",
+            self.max_per_second, self.interval
+        )
+    }
+}
+
+// Throughput and current sample rate of each key
+#[derive(Debug)]
+struct Rates {
+    pruned: Instant,
+    keys: HashMap<String, KeyRate>,
+}
+
+#[derive(Debug)]
+struct KeyRate {
+    started: Instant,
+    seen: u64,
+    rate: f64,
+}
+
+/// Implementation of a `MetricSink` that samples metrics to keep the number
+/// of metrics written to a wrapped sink for each key near a maximum number
+/// per second.
+///
+/// This protects the network and the Statsd server during traffic spikes
+/// without having to pick fixed sample rates ahead of time. The number of
+/// metrics seen for each key, the name of the metric, is counted over an
+/// interval and used to set the sample rate for the next interval. Within an
+/// interval, the rate is lowered further if the number of metrics seen so far
+/// is already over the limit. Keys that are under the limit are not sampled.
+///
+/// Metrics that are sent while sampled include the rate they were sampled at,
+/// like `|@0.25`, so that the Statsd server can scale their values correctly.
+/// Metrics that were already sampled by the client have their rate multiplied
+/// by the rate of this sink. Metrics that are not sent are not written to the
+/// wrapped sink and `0` is returned from `.emit()` instead of an error.
+///
+/// Only counters, timers, histograms, and distributions are sampled since the
+/// Statsd server only scales the values of those types. All other metrics,
+/// events, and service checks are always written.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{AdaptiveSamplingMetricSink, MetricSink, SpyMetricSink};
+///
+/// let (rx, spy) = SpyMetricSink::new();
+/// let sink = AdaptiveSamplingMetricSink::from(1000.0, spy);
+///
+/// sink.emit("requests:1|c").unwrap();
+/// assert_eq!(b"requests:1|c".to_vec(), rx.try_recv().unwrap());
+/// ```
+pub struct AdaptiveSamplingMetricSink {
+    sink: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
+    max_per_second: f64,
+    interval: Duration,
+    rng: Box<dyn Rng + Sync + Send + RefUnwindSafe>,
+    state: Mutex<Rates>,
+    sampled_out: AtomicU64,
+}
+
+impl AdaptiveSamplingMetricSink {
+    /// Construct a new `AdaptiveSamplingMetricSink` that writes at most the
+    /// given number of metrics per second for each key to the given sink.
+    pub fn from<T>(max_per_second: f64, sink: T) -> Self
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        AdaptiveSamplingMetricSinkBuilder::new(max_per_second).build(sink)
+    }
+
+    /// Construct a new builder for `AdaptiveSamplingMetricSink` that targets
+    /// at most the given number of metrics per second for each key.
+    pub fn builder(max_per_second: f64) -> AdaptiveSamplingMetricSinkBuilder {
+        AdaptiveSamplingMetricSinkBuilder::new(max_per_second)
+    }
+
+    /// Return the number of metrics that have not been written to the wrapped
+    /// sink due to sampling.
+    pub fn sampled_out(&self) -> u64 {
+        self.sampled_out.load(Ordering::Relaxed)
+    }
+
+    // Return the rate to sample a metric with the given name at, counting it
+    // towards the throughput of its key.
+    fn rate(&self, name: &str, now: Instant) -> f64 {
+        let mut state = self.state.lock().unwrap();
+        // Forget keys that haven't been seen for a couple of intervals to keep
+        // keys that are no longer used from piling up. They start over without
+        // sampling if they're seen again.
+        if now.saturating_duration_since(state.pruned) >= self.interval {
+            let interval = self.interval;
+            state
+                .keys
+                .retain(|_, key| now.saturating_duration_since(key.started) < interval * 2);
+            state.pruned = now;
+        }
+
+        let key = state.keys.entry(name.to_owned()).or_insert_with(|| KeyRate {
+            started: now,
+            seen: 0,
+            rate: 1.0,
+        });
+
+        let elapsed = now.saturating_duration_since(key.started);
+        if elapsed >= self.interval {
+            let per_second = key.seen as f64 / elapsed.as_secs_f64();
+            key.rate = (self.max_per_second / per_second).min(1.0);
+            key.started = now;
+            key.seen = 0;
+        }
+
+        key.seen += 1;
+        let budget = self.max_per_second * self.interval.as_secs_f64();
+        if key.seen as f64 > budget {
+            key.rate = key.rate.min(budget / key.seen as f64);
+        }
+
+        key.rate
+    }
+}
+
+impl MetricSink for AdaptiveSamplingMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let (name, existing) = match sampled_parts(metric) {
+            Some(parts) => parts,
+            None => return self.sink.emit(metric),
+        };
+
+        let rate = self.rate(name, Instant::now());
+        if rate >= 1.0 {
+            return self.sink.emit(metric);
+        }
+
+        if !sample::is_sampled(rate, &*self.rng) {
+            self.sampled_out.fetch_add(1, Ordering::Relaxed);
+            return Ok(0);
+        }
+
+        self.sink.emit(&with_sampling_rate(metric, existing, rate))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.sink.flush()
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.sink.check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats()
+    }
+}
+
+impl fmt::Debug for AdaptiveSamplingMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "AdaptiveSamplingMetricSink {{ max_per_second: {}, interval: {:?}, sampled_out: {} }}",
+            self.max_per_second,
+            self.interval,
+            self.sampled_out()
+        )
+    }
+}
+
+/// Return the name and any existing sample rate of a metric that the Statsd
+/// server scales by its sample rate, or `None` for any other metric.
+fn sampled_parts(metric: &str) -> Option<(&str, Option<f64>)> {
+    if metric.starts_with("_e{") || metric.starts_with("_sc|") {
+        return None;
+    }
+
+    let name = &metric[..metric.find(':')?];
+    let mut fields = metric.split('|').skip(1);
+    match fields.next()? {
+        "c" | "ms" | "h" | "d" => {}
+        _ => return None,
+    }
+
+    let existing = fields
+        .find_map(|f| f.strip_prefix('@'))
+        .and_then(|r| r.parse::<f64>().ok());
+    Some((name, existing))
+}
+
+/// Set the sample rate of a metric to the product of any rate it already had
+/// and the given rate, placing it right after the type if it didn't have one.
+fn with_sampling_rate(metric: &str, existing: Option<f64>, rate: f64) -> String {
+    let mut out = String::with_capacity(metric.len() + 12);
+    let mut replaced = false;
+    for (i, field) in metric.split('|').enumerate() {
+        if i > 0 {
+            out.push('|');
+        }
+
+        if existing.is_some() && !replaced && i > 1 && field.starts_with('@') {
+            out.push('@');
+            out.push_str(&(existing.unwrap_or(1.0) * rate).to_string());
+            replaced = true;
+        } else {
+            out.push_str(field);
+        }
+
+        if existing.is_none() && i == 1 {
+            out.push_str("|@");
+            out.push_str(&rate.to_string());
+        }
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{sampled_parts, with_sampling_rate, AdaptiveSamplingMetricSink, MetricSink};
+    use crate::sample::SeededRng;
+    use crate::sinks::spy::RecordingMetricSink;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_sampled_parts() {
+        assert_eq!(Some(("foo", None)), sampled_parts("foo:1|c"));
+        assert_eq!(Some(("foo", Some(0.5))), sampled_parts("foo:1|ms|@0.5|#a:b"));
+        assert_eq!(Some(("foo", None)), sampled_parts("foo:1:2|d|#a:b"));
+        assert_eq!(None, sampled_parts("foo:1|g"));
+        assert_eq!(None, sampled_parts("foo:1|s"));
+        assert_eq!(None, sampled_parts("_e{5,4}:title|text"));
+        assert_eq!(None, sampled_parts("_sc|foo|0"));
+    }
+
+    #[test]
+    fn test_with_sampling_rate() {
+        assert_eq!("foo:1|c|@0.25", with_sampling_rate("foo:1|c", None, 0.25));
+        assert_eq!("foo:1|c|@0.25|#a:b", with_sampling_rate("foo:1|c|#a:b", None, 0.25));
+        assert_eq!(
+            "foo:1|ms|@0.125|#a:b",
+            with_sampling_rate("foo:1|ms|@0.5|#a:b", Some(0.5), 0.25)
+        );
+    }
+
+    #[test]
+    fn test_adaptive_sampling_metric_sink_rate() {
+        let sink = AdaptiveSamplingMetricSink::builder(10.0)
+            .with_interval(Duration::from_secs(1))
+            .build(RecordingMetricSink::new());
+        let start = Instant::now();
+
+        // Within the limit for the first interval, then over it
+        assert!((0..10).all(|_| sink.rate("foo", start) == 1.0));
+        assert_eq!(10.0 / 11.0, sink.rate("foo", start));
+        for _ in 0..28 {
+            sink.rate("foo", start);
+        }
+        assert_eq!(10.0 / 40.0, sink.rate("foo", start));
+
+        // The next interval starts at the rate of the observed throughput and
+        // other keys are unaffected
+        assert_eq!(0.25, sink.rate("foo", start + Duration::from_secs(1)));
+        assert_eq!(1.0, sink.rate("bar", start + Duration::from_secs(1)));
+
+        // Quiet intervals go back to not sampling
+        let later = start + Duration::from_secs(2);
+        assert_eq!(1.0, sink.rate("foo", later + Duration::from_secs(1)));
+    }
+
+    #[test]
+    fn test_adaptive_sampling_metric_sink_emit() {
+        let recording = RecordingMetricSink::new();
+        let sink = AdaptiveSamplingMetricSink::builder(10.0)
+            .with_interval(Duration::from_secs(60))
+            .with_rng(SeededRng::new(7))
+            .build(recording.clone());
+
+        for _ in 0..1200 {
+            sink.emit("foo:1|c|#a:b").unwrap();
+            sink.emit("some.gauge:1|g").unwrap();
+        }
+
+        let metrics = recording.metrics();
+        let gauges = metrics.iter().filter(|m| m.as_str() == "some.gauge:1|g").count();
+        let counters: Vec<&String> = metrics.iter().filter(|m| m.starts_with("foo:")).collect();
+
+        assert_eq!(1200, gauges);
+        assert_eq!(1200, counters.len() as u64 + sink.sampled_out());
+        assert!(sink.sampled_out() > 0);
+        assert!(counters[counters.len() - 1].starts_with("foo:1|c|@0."));
+        assert!(counters[counters.len() - 1].ends_with("|#a:b"));
+    }
+}
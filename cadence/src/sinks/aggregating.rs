@@ -0,0 +1,804 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::mem;
+use std::panic::RefUnwindSafe;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::sinks::core::{MetricSink, SinkStats};
+use crate::sinks::sketch::Sketch;
+
+const DEFAULT_WINDOW: Duration = Duration::from_secs(10);
+
+/// How distributions summarized by a sketch are sent when a window ends.
+///
+/// See `AggregatingMetricSinkBuilder::with_distribution_sketches` for details.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SketchOutput {
+    /// Send a distribution for each bucket of the sketch with a sample rate
+    /// that makes it count as every value in the bucket. Datadog agents scale
+    /// distributions by their sample rate so percentiles and counts computed
+    /// by Datadog are accurate, within the accuracy of the sketch.
+    Points,
+    /// Send gauges with the count, minimum, maximum, average, and the 50th,
+    /// 95th, and 99th percentiles of the values, suffixed with `.count`,
+    /// `.min`, `.max`, `.avg`, `.p50`, `.p95`, and `.p99`.
+    Summary,
+}
+
+/// Implementation of a builder pattern for `AggregatingMetricSink`.
+///
+/// The builder can be used to set how long metrics are aggregated for,
+/// whether multiple values for the same timer, histogram, or distribution
+/// are sent as a single line, and whether timers and distributions are
+/// summarized instead of sending every value.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::net::UdpSocket;
+/// use std::time::Duration;
+/// use cadence::{AggregatingMetricSinkBuilder, BufferedUdpMetricSink, MetricSink, DEFAULT_PORT};
+///
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let udp = BufferedUdpMetricSink::from(("localhost", DEFAULT_PORT), socket).unwrap();
+///
+/// let sink = AggregatingMetricSinkBuilder::new()
+///     .with_window(Duration::from_secs(5))
+///     .build(udp);
+///
+/// sink.emit("foo.counter:4|c");
+/// sink.emit("foo.counter:2|c");
+/// ```
+#[derive(Debug, Clone)]
+pub struct AggregatingMetricSinkBuilder {
+    window: Duration,
+    packed: bool,
+    options: Options,
+}
+
+impl AggregatingMetricSinkBuilder {
+    /// Construct a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how long metrics are aggregated for before being sent to the
+    /// wrapped sink.
+    ///
+    /// The default window is 10 seconds.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Send every value of a timer, histogram, or distribution aggregated in
+    /// a window as a single line, separated by colons (`foo:1:2:3|ms`).
+    ///
+    /// This is not part of the Statsd protocol but is supported by Datadog
+    /// agents since version 6.25. By default, each value is sent as its own line.
+    pub fn with_value_packing(mut self, packed: bool) -> Self {
+        self.packed = packed;
+        self
+    }
+
+    /// Summarize the values of each distribution in a window using a DDSketch
+    /// with the given relative accuracy, instead of sending every value.
+    ///
+    /// A sketch groups values into buckets such that every value in a bucket
+    /// is within the relative accuracy (`0.01` is 1%) of the value used for the
+    /// bucket. When the window ends, each sketch is sent based on `output`.
+    /// This can cut the number of lines sent for frequently emitted
+    /// distributions by orders of magnitude, at the cost of accuracy.
+    /// Distributions with a sample rate are aggregated without a sketch.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the relative accuracy is not greater than
+    /// zero and less than one.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{AggregatingMetricSink, MetricSink, SketchOutput, SpyMetricSink};
+    ///
+    /// let (rx, spy) = SpyMetricSink::new();
+    /// let sink = AggregatingMetricSink::builder()
+    ///     .with_distribution_sketches(0.01, SketchOutput::Points)
+    ///     .build(spy);
+    ///
+    /// for _ in 0..100 {
+    ///     sink.emit("request.latency:12|d").unwrap();
+    /// }
+    ///
+    /// // Sent as a single line, "request.latency:12.0...|d|@0.01"
+    /// sink.flush().unwrap();
+    /// let line = String::from_utf8(rx.try_recv().unwrap()).unwrap();
+    /// assert!(line.starts_with("request.latency:12.0"));
+    /// assert!(line.ends_with("|d|@0.01"));
+    /// ```
+    pub fn with_distribution_sketches(mut self, relative_accuracy: f64, output: SketchOutput) -> Self {
+        assert!(
+            relative_accuracy > 0.0 && relative_accuracy < 1.0,
+            "relative accuracy must be between zero and one"
+        );
+        self.options.sketches = Some((relative_accuracy, output));
+        self
+    }
+
+    /// Send a summary of the values of each timer in a window as gauges,
+    /// instead of sending every value.
+    ///
+    /// The summary is made up of the count, minimum, maximum, average, and the
+    /// 95th and 99th percentiles of the values, suffixed with `.count`, `.min`,
+    /// `.max`, `.avg`, `.p95`, and `.p99`. This is useful for Statsd servers
+    /// that don't aggregate timers themselves, such as Telegraf when passing
+    /// metrics through to a backend. Timers with a sample rate are sent as-is.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use cadence::{AggregatingMetricSink, MetricSink, SpyMetricSink};
+    ///
+    /// let (rx, spy) = SpyMetricSink::new();
+    /// let sink = AggregatingMetricSink::builder()
+    ///     .with_timer_summaries(true)
+    ///     .build(spy);
+    ///
+    /// sink.emit("request.latency:10|ms").unwrap();
+    /// sink.emit("request.latency:30|ms").unwrap();
+    /// sink.flush().unwrap();
+    ///
+    /// let lines: Vec<Vec<u8>> = rx.try_iter().collect();
+    /// assert_eq!(b"request.latency.count:2|g".to_vec(), lines[0]);
+    /// assert_eq!(b"request.latency.avg:20|g".to_vec(), lines[3]);
+    /// ```
+    pub fn with_timer_summaries(mut self, enabled: bool) -> Self {
+        self.options.timer_summaries = enabled;
+        self
+    }
+
+    /// Construct a new `AggregatingMetricSink` instance wrapping the given
+    /// sink based on the builder configuration.
+    pub fn build<T>(self, sink: T) -> AggregatingMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        AggregatingMetricSink {
+            sink: Box::new(sink),
+            window: self.window,
+            packed: self.packed,
+            options: self.options,
+            state: Mutex::new(Window::new(Instant::now(), self.options)),
+        }
+    }
+}
+
+impl Default for AggregatingMetricSinkBuilder {
+    fn default() -> Self {
+        AggregatingMetricSinkBuilder {
+            window: DEFAULT_WINDOW,
+            packed: false,
+            options: Options::default(),
+        }
+    }
+}
+
+/// Which metrics are summarized instead of sending every value
+#[derive(Debug, Clone, Copy, Default)]
+struct Options {
+    sketches: Option<(f64, SketchOutput)>,
+    timer_summaries: bool,
+}
+
+/// How values of a particular type of metric are combined
+#[derive(Debug)]
+enum Aggregate {
+    Sum(i64),
+    Last(String),
+    All(Vec<String>),
+    Unique(Vec<String>),
+    Sketch(Box<Sketch>, SketchOutput),
+    Summary(Vec<f64>),
+}
+
+/// Metric being aggregated along with everything that isn't its value
+#[derive(Debug)]
+struct Entry {
+    name: String,
+    kind: String,
+    suffix: String,
+    value: Aggregate,
+}
+
+impl Entry {
+    fn update(&mut self, value: &str) -> bool {
+        match &mut self.value {
+            Aggregate::Sum(sum) => match value.parse::<i64>() {
+                Ok(v) => {
+                    *sum = sum.saturating_add(v);
+                    true
+                }
+                Err(_) => false,
+            },
+            // Gauges with a sign are relative to the current value of the gauge
+            Aggregate::Last(last) if value.starts_with('+') || value.starts_with('-') => {
+                match (last.parse::<f64>(), value.parse::<f64>()) {
+                    (Ok(current), Ok(delta)) => {
+                        *last = (current + delta).to_string();
+                        true
+                    }
+                    _ => false,
+                }
+            }
+            Aggregate::Last(last) => {
+                *last = value.to_string();
+                true
+            }
+            Aggregate::All(values) => {
+                values.push(value.to_string());
+                true
+            }
+            Aggregate::Unique(values) => {
+                if !values.iter().any(|v| v == value) {
+                    values.push(value.to_string());
+                }
+                true
+            }
+            Aggregate::Sketch(sketch, _) => match parse_values(value) {
+                Some(parsed) => {
+                    parsed.into_iter().for_each(|v| sketch.add(v));
+                    true
+                }
+                None => false,
+            },
+            Aggregate::Summary(values) => match parse_values(value) {
+                Some(parsed) => {
+                    values.extend(parsed);
+                    true
+                }
+                None => false,
+            },
+        }
+    }
+
+    fn lines(&self, packed: bool) -> Vec<String> {
+        let line = |value: &str| format!("{}:{}|{}{}", self.name, value, self.kind, self.suffix);
+        match &self.value {
+            Aggregate::Sum(sum) => vec![line(&sum.to_string())],
+            Aggregate::Last(last) => vec![line(last)],
+            Aggregate::All(values) if packed => vec![line(&values.join(":"))],
+            Aggregate::All(values) | Aggregate::Unique(values) => values.iter().map(|v| line(v)).collect(),
+            Aggregate::Sketch(sketch, SketchOutput::Points) => sketch
+                .buckets()
+                .into_iter()
+                .map(|(value, n)| match n {
+                    1 => line(&value.to_string()),
+                    n => format!(
+                        "{}:{}|{}|@{}{}",
+                        self.name,
+                        value,
+                        self.kind,
+                        1.0 / n as f64,
+                        self.suffix
+                    ),
+                })
+                .collect(),
+            Aggregate::Sketch(sketch, SketchOutput::Summary) => self.summary(&[
+                ("count", Some(sketch.count() as f64)),
+                ("min", sketch.min()),
+                ("max", sketch.max()),
+                ("avg", Some(sketch.sum() / sketch.count() as f64)),
+                ("p50", sketch.quantile(0.5)),
+                ("p95", sketch.quantile(0.95)),
+                ("p99", sketch.quantile(0.99)),
+            ]),
+            Aggregate::Summary(values) => {
+                let mut sorted = values.clone();
+                sorted.sort_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal));
+                let sum: f64 = sorted.iter().sum();
+
+                self.summary(&[
+                    ("count", Some(sorted.len() as f64)),
+                    ("min", sorted.first().copied()),
+                    ("max", sorted.last().copied()),
+                    ("avg", Some(sum / sorted.len() as f64)),
+                    ("p95", percentile(&sorted, 0.95)),
+                    ("p99", percentile(&sorted, 0.99)),
+                ])
+            }
+        }
+    }
+
+    /// Lines for gauges named after this metric with a suffix for each stat
+    fn summary(&self, stats: &[(&str, Option<f64>)]) -> Vec<String> {
+        stats
+            .iter()
+            .filter_map(|(stat, v)| v.map(|v| format!("{}.{}:{}|g{}", self.name, stat, v, self.suffix)))
+            .collect()
+    }
+}
+
+/// Metrics aggregated since the start of the current window
+#[derive(Debug)]
+struct Window {
+    started: Instant,
+    options: Options,
+    index: HashMap<String, usize>,
+    entries: Vec<Entry>,
+}
+
+impl Window {
+    fn new(started: Instant, options: Options) -> Self {
+        Window {
+            started,
+            options,
+            index: HashMap::new(),
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add a metric to this window, returning false if it can't be aggregated
+    fn add(&mut self, metric: &str) -> bool {
+        let (name, value, kind, suffix) = match parse(metric) {
+            Some(parts) => parts,
+            None => return false,
+        };
+
+        let key = format!("{}|{}{}", name, kind, suffix);
+        if let Some(&i) = self.index.get(&key) {
+            return self.entries[i].update(value);
+        }
+
+        // Values with a sample rate already stand for more than one value so
+        // they can't be summarized.
+        let sampled = suffix.split('|').any(|s| s.starts_with('@'));
+        let initial = match kind {
+            "c" | "m" => match value.parse::<i64>() {
+                Ok(v) => Aggregate::Sum(v),
+                Err(_) => return false,
+            },
+            // Without a previous absolute value, there's nothing to apply a
+            // relative change to, so it has to be sent as-is.
+            "g" if value.starts_with('+') || value.starts_with('-') => return false,
+            "g" => Aggregate::Last(value.to_string()),
+            "d" => match self.options.sketches {
+                Some((accuracy, output)) if !sampled => {
+                    let mut sketch = Box::new(Sketch::new(accuracy));
+                    match parse_values(value) {
+                        Some(parsed) => parsed.into_iter().for_each(|v| sketch.add(v)),
+                        None => return false,
+                    }
+                    Aggregate::Sketch(sketch, output)
+                }
+                _ => Aggregate::All(vec![value.to_string()]),
+            },
+            "ms" if self.options.timer_summaries && !sampled => match parse_values(value) {
+                Some(parsed) => Aggregate::Summary(parsed),
+                None => return false,
+            },
+            "ms" | "h" => Aggregate::All(vec![value.to_string()]),
+            "s" => Aggregate::Unique(vec![value.to_string()]),
+            _ => return false,
+        };
+
+        self.index.insert(key, self.entries.len());
+        self.entries.push(Entry {
+            name: name.to_string(),
+            kind: kind.to_string(),
+            suffix: suffix.to_string(),
+            value: initial,
+        });
+
+        true
+    }
+}
+
+/// Parse a value, or packed values, returning `None` if any of them aren't
+/// finite numbers.
+fn parse_values(value: &str) -> Option<Vec<f64>> {
+    let values: Vec<f64> = value
+        .split(':')
+        .map(|v| v.parse::<f64>())
+        .collect::<Result<_, _>>()
+        .ok()?;
+    if values.iter().all(|v| v.is_finite()) {
+        Some(values)
+    } else {
+        None
+    }
+}
+
+/// Value at the given quantile of sorted values using the nearest rank method
+fn percentile(sorted: &[f64], q: f64) -> Option<f64> {
+    let rank = (q * sorted.len() as f64).ceil() as usize;
+    sorted.get(rank.max(1) - 1).copied()
+}
+
+/// Split a metric into its name, value, type, and everything after the type
+/// (sample rate, tags, etc.) or return `None` if it isn't a metric that can
+/// be aggregated.
+fn parse(metric: &str) -> Option<(&str, &str, &str, &str)> {
+    // Events and service checks aren't metrics
+    if metric.starts_with('_') {
+        return None;
+    }
+
+    let (head, tail) = metric.split_at(metric.find('|')?);
+    let (name, value) = head.split_at(head.find(':')?);
+    let tail = &tail[1..];
+    let (kind, suffix) = match tail.find('|') {
+        Some(i) => tail.split_at(i),
+        None => (tail, ""),
+    };
+
+    // Metrics with a timestamp refer to a particular point in time and
+    // can't be combined with other metrics.
+    if suffix.split('|').any(|s| s.starts_with('T')) {
+        return None;
+    }
+
+    Some((name, &value[1..], kind, suffix))
+}
+
+/// Implementation of a `MetricSink` that combines metrics over a window of
+/// time before sending them to another sink.
+///
+/// Metrics with the same name, type, sample rate, and tags emitted during a
+/// window (10 seconds by default) are combined and sent to the wrapped sink
+/// once the window ends. This can drastically cut the number of packets sent
+/// for metrics that are emitted very frequently. Metrics are combined based
+/// on their type:
+///
+/// * Counters and meters: The sum of all values.
+/// * Gauges: The last value.
+/// * Timers, histograms, and distributions: Every value.
+/// * Sets: Every unique value.
+///
+/// Gauges with a relative value (`+` or `-`) are applied to the last absolute
+/// value of the gauge in the window, if there is one. Relative gauges without
+/// an absolute value, metrics with a timestamp, events, service checks, and
+/// anything else that can't be aggregated are sent to the wrapped sink
+/// immediately.
+///
+/// Aggregated metrics are sent to the wrapped sink when a metric is emitted
+/// after the window has ended, when the sink is flushed, and when the sink is
+/// dropped. A background thread is not used. Errors sending aggregated metrics
+/// are returned from the call to `.emit()` or `.flush()` that sent them.
+///
+/// Distributions can optionally be summarized using a DDSketch and timers can
+/// optionally be summarized as gauges instead of sending every value, see
+/// `AggregatingMetricSinkBuilder::with_distribution_sketches` and
+/// `AggregatingMetricSinkBuilder::with_timer_summaries`.
+///
+/// Note that the timing information of individual metrics is lost, so this
+/// sink is not appropriate when a Statsd server computes rates based on
+/// when each metric was received.
+pub struct AggregatingMetricSink {
+    sink: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
+    window: Duration,
+    packed: bool,
+    options: Options,
+    state: Mutex<Window>,
+}
+
+impl AggregatingMetricSink {
+    /// Construct a new builder for `AggregatingMetricSink`.
+    pub fn builder() -> AggregatingMetricSinkBuilder {
+        AggregatingMetricSinkBuilder::new()
+    }
+
+    /// Construct a new `AggregatingMetricSink` instance wrapping the given
+    /// sink with the default window.
+    pub fn from<T>(sink: T) -> AggregatingMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        Self::builder().build(sink)
+    }
+
+    /// Send every aggregated metric to the wrapped sink and start a new window
+    fn send(&self, entries: Vec<Entry>) -> io::Result<()> {
+        let mut res = Ok(());
+        for entry in entries.iter() {
+            for line in entry.lines(self.packed) {
+                if let Err(e) = self.sink.emit(&line) {
+                    // Keep sending the rest but only return the first error
+                    if res.is_ok() {
+                        res = Err(e);
+                    }
+                }
+            }
+        }
+
+        res
+    }
+
+    /// Remove every metric from the current window and start a new one
+    fn take(&self, now: Instant) -> Vec<Entry> {
+        let mut state = self.state.lock().unwrap();
+        mem::replace(&mut *state, Window::new(now, self.options)).entries
+    }
+}
+
+impl MetricSink for AggregatingMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let now = Instant::now();
+        let ended = {
+            let mut state = self.state.lock().unwrap();
+            if !state.add(metric) {
+                drop(state);
+                return self.sink.emit(metric);
+            }
+
+            now.duration_since(state.started) >= self.window
+        };
+
+        if ended {
+            self.send(self.take(now))?;
+        }
+
+        Ok(metric.len())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let res = self.send(self.take(Instant::now()));
+        self.sink.flush()?;
+        res
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.sink.check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats()
+    }
+}
+
+impl Drop for AggregatingMetricSink {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl fmt::Debug for AggregatingMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "AggregatingMetricSink {{ window: {:?}, packed: {}, options: {:?} }}",
+            self.window, self.packed, self.options
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse, AggregatingMetricSink, MetricSink, SketchOutput};
+    use crate::sinks::spy::SpyMetricSink;
+    use crossbeam_channel::Receiver;
+    use std::time::Duration;
+
+    fn received(rx: &Receiver<Vec<u8>>) -> Vec<String> {
+        rx.try_iter().map(|m| String::from_utf8(m).unwrap()).collect()
+    }
+
+    fn new_sink(packed: bool) -> (Receiver<Vec<u8>>, AggregatingMetricSink) {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = AggregatingMetricSink::builder()
+            .with_window(Duration::from_secs(3600))
+            .with_value_packing(packed)
+            .build(spy);
+        (rx, sink)
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Some(("foo", "1", "c", "")), parse("foo:1|c"));
+        assert_eq!(Some(("foo", "1", "c", "|@0.5|#a:b")), parse("foo:1|c|@0.5|#a:b"));
+        assert_eq!(None, parse("foo:1|c|T1692653541"));
+        assert_eq!(None, parse("_e{5,4}:title|text"));
+        assert_eq!(None, parse("foo"));
+    }
+
+    #[test]
+    fn test_aggregating_metric_sink_counters() {
+        let (rx, sink) = new_sink(false);
+
+        sink.emit("foo:1|c").unwrap();
+        sink.emit("foo:2|c").unwrap();
+        sink.emit("foo:4|c|#region:us").unwrap();
+        sink.emit("foo:3|c").unwrap();
+        assert!(received(&rx).is_empty());
+
+        sink.flush().unwrap();
+        assert_eq!(vec!["foo:6|c", "foo:4|c|#region:us"], received(&rx));
+    }
+
+    #[test]
+    fn test_aggregating_metric_sink_gauges() {
+        let (rx, sink) = new_sink(false);
+
+        sink.emit("bar:-1|g").unwrap();
+        sink.emit("foo:1|g").unwrap();
+        sink.emit("foo:2|g").unwrap();
+        sink.emit("foo:+5|g").unwrap();
+        assert_eq!(vec!["bar:-1|g"], received(&rx));
+
+        sink.flush().unwrap();
+        assert_eq!(vec!["foo:7|g"], received(&rx));
+    }
+
+    #[test]
+    fn test_aggregating_metric_sink_timers_and_sets() {
+        let (rx, sink) = new_sink(false);
+
+        sink.emit("foo:1|ms").unwrap();
+        sink.emit("foo:2|ms").unwrap();
+        sink.emit("bar:a|s").unwrap();
+        sink.emit("bar:a|s").unwrap();
+        sink.emit("bar:b|s").unwrap();
+
+        sink.flush().unwrap();
+        assert_eq!(vec!["foo:1|ms", "foo:2|ms", "bar:a|s", "bar:b|s"], received(&rx));
+    }
+
+    #[test]
+    fn test_aggregating_metric_sink_packed() {
+        let (rx, sink) = new_sink(true);
+
+        sink.emit("foo:1|h").unwrap();
+        sink.emit("foo:2|h").unwrap();
+        sink.emit("foo:3|h").unwrap();
+
+        sink.flush().unwrap();
+        assert_eq!(vec!["foo:1:2:3|h"], received(&rx));
+    }
+
+    // Split a line into its value and everything else, for checking values
+    // estimated by a sketch.
+    fn split_value(line: &str) -> (f64, String) {
+        let start = line.find(':').unwrap() + 1;
+        let end = line.find('|').unwrap();
+        let value = line[start..end].parse().unwrap();
+        (value, format!("{}{}", &line[..start], &line[end..]))
+    }
+
+    #[test]
+    fn test_aggregating_metric_sink_sketch_points() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = AggregatingMetricSink::builder()
+            .with_window(Duration::from_secs(3600))
+            .with_distribution_sketches(0.01, SketchOutput::Points)
+            .build(spy);
+
+        for _ in 0..4 {
+            sink.emit("foo:10|d|#region:us").unwrap();
+        }
+        sink.emit("foo:0:0|d|#region:us").unwrap();
+        sink.emit("foo:-5|d|#region:us").unwrap();
+        sink.emit("foo:7|d|@0.5").unwrap();
+        sink.emit("foo:8|d|@0.5").unwrap();
+        sink.emit("bar:x|d").unwrap();
+
+        sink.flush().unwrap();
+        let lines = received(&rx);
+        assert_eq!(6, lines.len());
+        assert_eq!("bar:x|d", lines[0]);
+
+        let (value, rest) = split_value(&lines[1]);
+        assert!((value + 5.0).abs() <= 0.05);
+        assert_eq!("foo:|d|#region:us", rest);
+        assert_eq!("foo:0|d|@0.5|#region:us", lines[2]);
+
+        let (value, rest) = split_value(&lines[3]);
+        assert!((value - 10.0).abs() <= 0.1);
+        assert_eq!("foo:|d|@0.25|#region:us", rest);
+        assert_eq!(vec!["foo:7|d|@0.5", "foo:8|d|@0.5"], lines[4..]);
+    }
+
+    #[test]
+    fn test_aggregating_metric_sink_sketch_summary() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = AggregatingMetricSink::builder()
+            .with_window(Duration::from_secs(3600))
+            .with_distribution_sketches(0.01, SketchOutput::Summary)
+            .build(spy);
+
+        sink.emit("foo:1|d").unwrap();
+        sink.emit("foo:2|d").unwrap();
+        sink.emit("foo:3|d").unwrap();
+        sink.emit("foo:4|ms").unwrap();
+
+        sink.flush().unwrap();
+        let lines = received(&rx);
+        assert_eq!(8, lines.len());
+        assert_eq!(
+            vec!["foo.count:3|g", "foo.min:1|g", "foo.max:3|g", "foo.avg:2|g"],
+            lines[..4]
+        );
+
+        for (line, (expected, name)) in
+            lines[4..7]
+                .iter()
+                .zip(&[(2.0, "foo.p50:|g"), (2.0, "foo.p95:|g"), (2.0, "foo.p99:|g")])
+        {
+            let (value, rest) = split_value(line);
+            assert!((value - expected).abs() <= 0.02, "{}", line);
+            assert_eq!(*name, rest);
+        }
+        assert_eq!("foo:4|ms", lines[7]);
+    }
+
+    #[test]
+    fn test_aggregating_metric_sink_timer_summaries() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = AggregatingMetricSink::builder()
+            .with_window(Duration::from_secs(3600))
+            .with_timer_summaries(true)
+            .build(spy);
+
+        for v in 1..=100 {
+            sink.emit(&format!("foo:{}|ms|#region:us", v)).unwrap();
+        }
+        sink.emit("foo:2:4|ms").unwrap();
+        sink.emit("foo:9|ms|@0.5").unwrap();
+        sink.emit("foo:x|ms").unwrap();
+        sink.emit("foo:5|h").unwrap();
+
+        sink.flush().unwrap();
+        assert_eq!(
+            vec![
+                "foo:x|ms",
+                "foo.count:100|g|#region:us",
+                "foo.min:1|g|#region:us",
+                "foo.max:100|g|#region:us",
+                "foo.avg:50.5|g|#region:us",
+                "foo.p95:95|g|#region:us",
+                "foo.p99:99|g|#region:us",
+                "foo.count:2|g",
+                "foo.min:2|g",
+                "foo.max:4|g",
+                "foo.avg:3|g",
+                "foo.p95:4|g",
+                "foo.p99:4|g",
+                "foo:9|ms|@0.5",
+                "foo:5|h",
+            ],
+            received(&rx)
+        );
+    }
+
+    #[test]
+    fn test_aggregating_metric_sink_window_ended() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = AggregatingMetricSink::builder()
+            .with_window(Duration::from_secs(0))
+            .build(spy);
+
+        sink.emit("foo:1|c").unwrap();
+        assert_eq!(vec!["foo:1|c"], received(&rx));
+    }
+
+    #[test]
+    fn test_aggregating_metric_sink_drop() {
+        let (rx, sink) = new_sink(false);
+
+        sink.emit("foo:1|c").unwrap();
+        drop(sink);
+
+        assert_eq!(vec!["foo:1|c"], received(&rx));
+    }
+}
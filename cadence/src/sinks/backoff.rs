@@ -0,0 +1,165 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::time::{Duration, Instant};
+
+use crate::sample;
+
+/// How long to wait before trying to reconnect to a server after a failure.
+///
+/// The delay starts at an initial value and doubles after each consecutive
+/// failure until it reaches a maximum value. The delay is reset after the
+/// next successful write to the server.
+///
+/// By default, the initial delay is 100 milliseconds and the maximum delay
+/// is 10 seconds.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use cadence::Backoff;
+///
+/// let backoff = Backoff::exponential(Duration::from_millis(50), Duration::from_secs(30));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Backoff {
+    initial: Duration,
+    max: Duration,
+}
+
+impl Backoff {
+    /// Create a new backoff that starts at `initial` and doubles after each
+    /// consecutive failure, up to `max`.
+    pub fn exponential(initial: Duration, max: Duration) -> Self {
+        Backoff { initial, max }
+    }
+
+    /// Create a new backoff that doesn't wait at all before reconnecting.
+    pub fn none() -> Self {
+        Self::exponential(Duration::from_secs(0), Duration::from_secs(0))
+    }
+
+    /// Delay to wait after the given number of consecutive failures
+    pub(crate) fn delay(&self, failures: u32) -> Duration {
+        if failures == 0 {
+            return Duration::from_secs(0);
+        }
+
+        // Anything past 2^31 times the initial delay is going to be above
+        // any reasonable maximum delay so stop there to avoid overflowing.
+        let factor = 1u32 << (failures - 1).min(31);
+        self.initial.saturating_mul(factor).min(self.max)
+    }
+
+    /// Delay to wait after the given number of consecutive failures, randomly
+    /// reduced by up to half so that many clients failing at the same time
+    /// don't all retry at the same time.
+    pub(crate) fn jittered_delay(&self, failures: u32) -> Duration {
+        self.delay(failures).mul_f64(0.5 + sample::next_f64() / 2.0)
+    }
+}
+
+impl Default for Backoff {
+    fn default() -> Self {
+        Self::exponential(Duration::from_millis(100), Duration::from_secs(10))
+    }
+}
+
+/// Tracker for consecutive failures and when the next attempt is allowed
+#[derive(Debug, Clone)]
+pub(crate) struct BackoffState {
+    backoff: Backoff,
+    failures: u32,
+    retry_at: Option<Instant>,
+}
+
+impl BackoffState {
+    pub(crate) fn new(backoff: Backoff) -> Self {
+        BackoffState {
+            backoff,
+            failures: 0,
+            retry_at: None,
+        }
+    }
+
+    /// Return true if enough time has passed since the last failure to try again
+    pub(crate) fn is_ready(&self, now: Instant) -> bool {
+        match self.retry_at {
+            Some(retry_at) => now >= retry_at,
+            None => true,
+        }
+    }
+
+    pub(crate) fn failure(&mut self, now: Instant) {
+        self.failures = self.failures.saturating_add(1);
+        self.retry_at = Some(now + self.backoff.delay(self.failures));
+    }
+
+    pub(crate) fn success(&mut self) {
+        self.failures = 0;
+        self.retry_at = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backoff, BackoffState};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_backoff_delay() {
+        let backoff = Backoff::exponential(Duration::from_millis(100), Duration::from_secs(1));
+
+        assert_eq!(Duration::from_millis(0), backoff.delay(0));
+        assert_eq!(Duration::from_millis(100), backoff.delay(1));
+        assert_eq!(Duration::from_millis(200), backoff.delay(2));
+        assert_eq!(Duration::from_millis(400), backoff.delay(3));
+        assert_eq!(Duration::from_millis(800), backoff.delay(4));
+        assert_eq!(Duration::from_secs(1), backoff.delay(5));
+        assert_eq!(Duration::from_secs(1), backoff.delay(u32::MAX));
+    }
+
+    #[test]
+    fn test_backoff_jittered_delay() {
+        let backoff = Backoff::exponential(Duration::from_millis(100), Duration::from_secs(1));
+
+        assert_eq!(Duration::from_millis(0), backoff.jittered_delay(0));
+        for _ in 0..1_000 {
+            let delay = backoff.jittered_delay(2);
+            assert!(delay >= Duration::from_millis(100), "unexpected delay {:?}", delay);
+            assert!(delay <= Duration::from_millis(200), "unexpected delay {:?}", delay);
+        }
+    }
+
+    #[test]
+    fn test_backoff_none() {
+        let backoff = Backoff::none();
+        assert_eq!(Duration::from_secs(0), backoff.delay(10));
+    }
+
+    #[test]
+    fn test_backoff_state() {
+        let now = Instant::now();
+        let mut state = BackoffState::new(Backoff::exponential(Duration::from_secs(1), Duration::from_secs(10)));
+        assert!(state.is_ready(now));
+
+        state.failure(now);
+        assert!(!state.is_ready(now));
+        assert!(state.is_ready(now + Duration::from_secs(1)));
+
+        state.failure(now);
+        assert!(!state.is_ready(now + Duration::from_secs(1)));
+        assert!(state.is_ready(now + Duration::from_secs(2)));
+
+        state.success();
+        assert!(state.is_ready(now));
+    }
+}
@@ -0,0 +1,294 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::sinks::core::{MetricSink, SinkStats, SocketStats};
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 5;
+const DEFAULT_COOLDOWN: Duration = Duration::from_secs(10);
+
+/// Implementation of a builder pattern for `CircuitBreakerMetricSink`.
+///
+/// The builder can be used to set how many consecutive errors trip the
+/// circuit breaker and how long it stays open before the wrapped sink is
+/// tried again.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use cadence::{CircuitBreakerMetricSinkBuilder, MetricSink, QueuingMetricSink, TcpMetricSink};
+///
+/// let tcp = TcpMetricSink::from(("localhost", 8125)).unwrap();
+/// let breaker = CircuitBreakerMetricSinkBuilder::new()
+///     .with_failure_threshold(3)
+///     .with_cooldown(Duration::from_secs(30))
+///     .build(tcp);
+///
+/// let sink = QueuingMetricSink::from(breaker);
+/// sink.emit("foo.counter:4|c");
+/// ```
+#[derive(Debug, Clone)]
+pub struct CircuitBreakerMetricSinkBuilder {
+    threshold: u32,
+    cooldown: Duration,
+}
+
+impl CircuitBreakerMetricSinkBuilder {
+    /// Construct a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of consecutive errors from the wrapped sink that trip
+    /// the circuit breaker.
+    ///
+    /// The default threshold is 5 errors. A threshold of 0 is treated as 1.
+    pub fn with_failure_threshold(mut self, threshold: u32) -> Self {
+        self.threshold = threshold.max(1);
+        self
+    }
+
+    /// Set how long the circuit breaker stays open, failing every metric
+    /// immediately, before a metric is written to the wrapped sink again.
+    ///
+    /// The default cooldown is 10 seconds.
+    pub fn with_cooldown(mut self, cooldown: Duration) -> Self {
+        self.cooldown = cooldown;
+        self
+    }
+
+    /// Construct a new `CircuitBreakerMetricSink` instance wrapping the given
+    /// sink based on the builder configuration.
+    pub fn build<T>(self, sink: T) -> CircuitBreakerMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        CircuitBreakerMetricSink {
+            sink: Box::new(sink),
+            threshold: self.threshold,
+            cooldown: self.cooldown,
+            state: Mutex::new(BreakerState::default()),
+            rejected: SocketStats::default(),
+        }
+    }
+}
+
+impl Default for CircuitBreakerMetricSinkBuilder {
+    fn default() -> Self {
+        CircuitBreakerMetricSinkBuilder {
+            threshold: DEFAULT_FAILURE_THRESHOLD,
+            cooldown: DEFAULT_COOLDOWN,
+        }
+    }
+}
+
+/// Consecutive errors of the wrapped sink and when the breaker closes again
+#[derive(Debug, Default)]
+struct BreakerState {
+    failures: u32,
+    open_until: Option<Instant>,
+}
+
+/// Implementation of a `MetricSink` that stops writing to a wrapped sink
+/// after it fails repeatedly.
+///
+/// Each metric is written to the wrapped sink until it returns a number of
+/// consecutive errors (5 by default). At that point the circuit breaker trips
+/// open and every metric fails immediately without touching the wrapped sink
+/// for a cooldown period (10 seconds by default). After the cooldown, a single
+/// metric is written to the wrapped sink to probe it. If that succeeds, the
+/// breaker closes and metrics are written normally again. If it fails, the
+/// breaker stays open for another cooldown period.
+///
+/// This is useful for sinks that can block for a long time while failing,
+/// such as a `TcpMetricSink` trying to connect to a server that is down. When
+/// wrapped by a `QueuingMetricSink`, failing fast keeps the worker thread
+/// from being stalled by each metric in the queue.
+///
+/// Metrics rejected while the breaker is open are counted as dropped in the
+/// stats returned by this sink, in addition to the stats of the wrapped sink.
+pub struct CircuitBreakerMetricSink {
+    sink: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
+    threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+    rejected: SocketStats,
+}
+
+impl CircuitBreakerMetricSink {
+    /// Construct a new builder for `CircuitBreakerMetricSink`.
+    pub fn builder() -> CircuitBreakerMetricSinkBuilder {
+        CircuitBreakerMetricSinkBuilder::new()
+    }
+
+    /// Construct a new `CircuitBreakerMetricSink` instance wrapping the given
+    /// sink with the default failure threshold and cooldown.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cadence::{CircuitBreakerMetricSink, TcpMetricSink};
+    ///
+    /// let tcp = TcpMetricSink::from(("localhost", 8125)).unwrap();
+    /// let sink = CircuitBreakerMetricSink::from(tcp);
+    /// ```
+    pub fn from<T>(sink: T) -> CircuitBreakerMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        Self::builder().build(sink)
+    }
+
+    /// Return true if the circuit breaker is open and metrics are being
+    /// rejected without being written to the wrapped sink.
+    pub fn is_open(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.failures >= self.threshold
+    }
+
+    /// Return true if the next metric should be written to the wrapped sink
+    fn allow(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.failures < self.threshold {
+            return true;
+        }
+
+        match state.open_until {
+            Some(open_until) if now < open_until => false,
+            _ => {
+                // Only let a single metric through to probe the wrapped sink,
+                // other callers keep failing fast until it's done.
+                state.open_until = Some(now + self.cooldown);
+                true
+            }
+        }
+    }
+
+    fn record<T>(&self, res: &io::Result<T>, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        if res.is_ok() {
+            state.failures = 0;
+            state.open_until = None;
+        } else {
+            state.failures = state.failures.saturating_add(1);
+            if state.failures >= self.threshold {
+                state.open_until = Some(now + self.cooldown);
+            }
+        }
+    }
+}
+
+impl MetricSink for CircuitBreakerMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let now = Instant::now();
+        if !self.allow(now) {
+            self.rejected.incr_bytes_dropped(metric.len() as u64);
+            self.rejected.incr_packets_dropped();
+            return Err(io::Error::new(io::ErrorKind::Other, "Circuit breaker is open"));
+        }
+
+        let res = self.sink.emit(metric);
+        self.record(&res, now);
+        res
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        if self.is_open() {
+            return Err(io::Error::new(io::ErrorKind::Other, "Circuit breaker is open"));
+        }
+
+        self.sink.flush()
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.sink.check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats().combine((&self.rejected).into())
+    }
+}
+
+impl fmt::Debug for CircuitBreakerMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "CircuitBreakerMetricSink {{ threshold: {}, cooldown: {:?}, state: {:?} }}",
+            self.threshold, self.cooldown, self.state
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CircuitBreakerMetricSink, MetricSink};
+    use crate::test::FailingMetricSink;
+    use std::io;
+    use std::time::Duration;
+
+    #[test]
+    fn test_circuit_breaker_metric_sink_closed() {
+        let wrapped = FailingMetricSink::new(io::ErrorKind::Other);
+        let sink = CircuitBreakerMetricSink::from(wrapped.clone());
+
+        assert_eq!(7, sink.emit("foo:1|c").unwrap());
+        assert_eq!(7, sink.emit("foo:2|c").unwrap());
+        assert_eq!(2, wrapped.attempts());
+        assert!(!sink.is_open());
+    }
+
+    #[test]
+    fn test_circuit_breaker_metric_sink_trips_open() {
+        let wrapped = FailingMetricSink::new(io::ErrorKind::Other);
+        wrapped.fail_always();
+        let sink = CircuitBreakerMetricSink::builder()
+            .with_failure_threshold(2)
+            .with_cooldown(Duration::from_secs(3600))
+            .build(wrapped.clone());
+
+        assert!(sink.emit("foo:1|c").is_err());
+        assert!(!sink.is_open());
+        assert!(sink.emit("foo:2|c").is_err());
+        assert!(sink.is_open());
+        assert!(sink.emit("foo:3|c").is_err());
+        assert!(sink.emit("foo:4|c").is_err());
+
+        // Metrics aren't written to the wrapped sink until the cooldown passes
+        assert_eq!(2, wrapped.attempts());
+        assert_eq!(2, sink.stats().packets_dropped);
+        assert_eq!(14, sink.stats().bytes_dropped);
+    }
+
+    #[test]
+    fn test_circuit_breaker_metric_sink_probe() {
+        let wrapped = FailingMetricSink::new(io::ErrorKind::Other);
+        wrapped.fail_always();
+        let sink = CircuitBreakerMetricSink::builder()
+            .with_failure_threshold(1)
+            .with_cooldown(Duration::from_secs(0))
+            .build(wrapped.clone());
+
+        assert!(sink.emit("foo:1|c").is_err());
+        assert!(sink.is_open());
+        assert!(sink.emit("foo:2|c").is_err());
+        assert!(sink.is_open());
+
+        wrapped.recover();
+        assert_eq!(7, sink.emit("foo:3|c").unwrap());
+        assert!(!sink.is_open());
+        assert_eq!(3, wrapped.attempts());
+    }
+}
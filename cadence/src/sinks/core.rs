@@ -8,7 +8,11 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+use crate::io::{format_error, with_format_buffer};
+use crate::types::WriteMetric;
+use std::future::Future;
 use std::io;
+use std::pin::Pin;
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::sync::Arc;
 
@@ -21,6 +25,18 @@ pub struct SinkStats {
     pub packets_dropped: u64,
 }
 
+impl SinkStats {
+    /// Add the stats of another sink to these stats
+    pub(crate) fn combine(self, other: SinkStats) -> SinkStats {
+        SinkStats {
+            bytes_sent: self.bytes_sent + other.bytes_sent,
+            packets_sent: self.packets_sent + other.packets_sent,
+            bytes_dropped: self.bytes_dropped + other.bytes_dropped,
+            packets_dropped: self.packets_dropped + other.packets_dropped,
+        }
+    }
+}
+
 /// Thread-safe collection of stats updated by network sinks.
 ///
 /// This struct is meant to be updated internally by `MetricSink` implementations
@@ -162,6 +178,32 @@ pub trait MetricSink {
     /// interpret this as an error.
     fn emit(&self, metric: &str) -> io::Result<usize>;
 
+    /// Send a metric that writes itself to this sink instead of being passed
+    /// as a string and return the number of bytes written or an I/O error.
+    ///
+    /// Sinks that buffer metrics may override this method to format metrics
+    /// directly into memory they own. The default implementation formats the
+    /// metric into a string reused by each thread and passes it to `.emit()`.
+    fn emit_metric(&self, metric: &dyn WriteMetric) -> io::Result<usize> {
+        with_format_buffer(|buf| {
+            buf.reserve(metric.size_hint());
+            metric.write_to(buf).map_err(format_error)?;
+            self.emit(buf)
+        })
+    }
+
+    /// Send several Statsd metrics using this sink at once and return the total
+    /// number of bytes written or an I/O error.
+    ///
+    /// Every metric is sent even if sending one of them fails, in which case the
+    /// first error is returned. Sinks that buffer or queue metrics may override
+    /// this method to handle the whole batch at once, for example by only taking
+    /// a lock a single time. The default implementation calls `.emit()` for each
+    /// metric.
+    fn emit_batch(&self, metrics: &[&str]) -> io::Result<usize> {
+        emit_each(metrics, |m| self.emit(m))
+    }
+
     /// Flush any currently buffered metrics to the underlying backend, returning
     /// an I/O error if they could not be written for some reason.
     ///
@@ -171,6 +213,132 @@ pub trait MetricSink {
         Ok(())
     }
 
+    /// Verify that metrics can be sent using this sink, returning an I/O error
+    /// if they can't, for example because the server can't be reached.
+    ///
+    /// This is meant to be called when an application starts so that it can
+    /// fail fast if it has been misconfigured. Sinks that send metrics over a
+    /// socket connect to the server or send an empty packet to it and sinks
+    /// that wrap other sinks check them instead. Note that connectionless
+    /// transports like UDP can't tell if anything is listening on the other
+    /// end so this only catches some problems. The default implementation of
+    /// this method does nothing.
+    fn check(&self) -> io::Result<()> {
+        Ok(())
+    }
+
+    /// Return I/O telemetry like bytes / packets sent or dropped.
+    ///
+    /// Note that not all sinks implement this method and the default implementation
+    /// returns zeros.
+    fn stats(&self) -> SinkStats {
+        SinkStats::default()
+    }
+}
+
+/// Allow boxed sinks, such as those created by `MetricSinkBuilder`, to be used
+/// anywhere a `MetricSink` is expected.
+impl<T> MetricSink for Box<T>
+where
+    T: MetricSink + ?Sized,
+{
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        (**self).emit(metric)
+    }
+
+    fn emit_metric(&self, metric: &dyn WriteMetric) -> io::Result<usize> {
+        (**self).emit_metric(metric)
+    }
+
+    fn emit_batch(&self, metrics: &[&str]) -> io::Result<usize> {
+        (**self).emit_batch(metrics)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        (**self).flush()
+    }
+
+    fn check(&self) -> io::Result<()> {
+        (**self).check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        (**self).stats()
+    }
+}
+
+/// Send every metric using the given function, returning the total number of
+/// bytes written or the first error after every metric has been tried.
+pub(crate) fn emit_each<F>(metrics: &[&str], mut emit: F) -> io::Result<usize>
+where
+    F: FnMut(&str) -> io::Result<usize>,
+{
+    let mut written = 0;
+    let mut first_err = None;
+
+    for metric in metrics {
+        match emit(metric) {
+            Ok(n) => written += n,
+            Err(e) => {
+                first_err.get_or_insert(e);
+            }
+        }
+    }
+
+    match first_err {
+        Some(e) => Err(e),
+        None => Ok(written),
+    }
+}
+
+/// Future returned by `AsyncMetricSink` methods.
+pub type SinkFuture<'a, T> = Pin<Box<dyn Future<Output = io::Result<T>> + Send + 'a>>;
+
+/// Trait for backends that send Statsd metrics somewhere without blocking
+/// the thread of the caller.
+///
+/// This is the asynchronous version of `MetricSink` meant for use in async
+/// applications (Tokio, async-std, etc.) where the emit path should be awaited
+/// instead of blocking the runtime. The metric strings are the same as those
+/// passed to `MetricSink` implementations.
+///
+/// Methods return boxed futures so that the trait can be used as a trait
+/// object (e.g. `Arc<dyn AsyncMetricSink + Send + Sync>`).
+///
+/// # Example
+///
+/// ```
+/// use cadence::{AsyncMetricSink, SinkFuture};
+///
+/// pub struct MyAsyncSink;
+///
+/// impl AsyncMetricSink for MyAsyncSink {
+///     fn emit<'a>(&'a self, metric: &'a str) -> SinkFuture<'a, usize> {
+///         Box::pin(async move {
+///             // Your custom async sink implementation goes here!
+///             Ok(metric.len())
+///         })
+///     }
+/// }
+/// ```
+pub trait AsyncMetricSink {
+    /// Send the Statsd metric using this sink and resolve to the number of
+    /// bytes written or an I/O error.
+    ///
+    /// Note that implementations may return `0` bytes if the metric is not
+    /// immediately written (such as when it is buffered).  Callers should *NOT*
+    /// interpret this as an error.
+    fn emit<'a>(&'a self, metric: &'a str) -> SinkFuture<'a, usize>;
+
+    /// Flush any currently buffered metrics to the underlying backend, resolving
+    /// to an I/O error if they could not be written for some reason.
+    ///
+    /// Note that not all sinks buffer metrics and so the default implementation of
+    /// this method does nothing.
+    fn flush(&self) -> SinkFuture<'_, ()> {
+        Box::pin(async { Ok(()) })
+    }
+
     /// Return I/O telemetry like bytes / packets sent or dropped.
     ///
     /// Note that not all sinks implement this method and the default implementation
@@ -190,14 +358,71 @@ impl MetricSink for NopMetricSink {
     fn emit(&self, _metric: &str) -> io::Result<usize> {
         Ok(0)
     }
+
+    fn emit_metric(&self, _metric: &dyn WriteMetric) -> io::Result<usize> {
+        Ok(0)
+    }
+}
+
+impl AsyncMetricSink for NopMetricSink {
+    fn emit<'a>(&'a self, _metric: &'a str) -> SinkFuture<'a, usize> {
+        Box::pin(async { Ok(0) })
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{MetricSink, NopMetricSink};
+    use super::{MetricSink, NopMetricSink, WriteMetric};
+    use std::cell::RefCell;
+    use std::fmt;
+    use std::io;
+
+    #[derive(Default)]
+    struct RecordingSink {
+        metrics: RefCell<Vec<String>>,
+    }
+
+    impl MetricSink for RecordingSink {
+        fn emit(&self, metric: &str) -> io::Result<usize> {
+            self.metrics.borrow_mut().push(metric.to_owned());
+            Ok(metric.len())
+        }
+    }
+
+    struct Gauge(u64);
+
+    impl WriteMetric for Gauge {
+        fn write_to(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+            write!(out, "baz:{}|g", self.0)
+        }
+    }
+
+    struct Broken;
+
+    impl WriteMetric for Broken {
+        fn write_to(&self, _out: &mut dyn fmt::Write) -> fmt::Result {
+            Err(fmt::Error)
+        }
+    }
+
     #[test]
     fn test_nop_metric_sink() {
         let sink = NopMetricSink;
         assert_eq!(0, sink.emit("baz:4|c").unwrap());
     }
+
+    #[test]
+    fn test_metric_sink_emit_metric() {
+        let sink = RecordingSink::default();
+        assert_eq!(7, sink.emit_metric(&Gauge(4)).unwrap());
+        assert_eq!(8, sink.emit_metric(&Gauge(12)).unwrap());
+        assert_eq!(vec!["baz:4|g", "baz:12|g"], *sink.metrics.borrow());
+    }
+
+    #[test]
+    fn test_metric_sink_emit_metric_error() {
+        let sink = RecordingSink::default();
+        assert!(sink.emit_metric(&Broken).is_err());
+        assert!(sink.metrics.borrow().is_empty());
+    }
 }
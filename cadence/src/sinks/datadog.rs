@@ -0,0 +1,596 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt::{self, Write as _};
+use std::io;
+use std::mem;
+use std::panic::AssertUnwindSafe;
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use crate::builder::{MetricType, MetricValue};
+use crate::parse::ParsedMetric;
+use crate::sinks::core::{MetricSink, SinkStats, SocketStats};
+use crate::sinks::emf::write_json_str;
+
+const DEFAULT_SITE: &str = "datadoghq.com";
+const DEFAULT_BATCH_SIZE: usize = 500;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Implementation of a builder pattern for `DatadogHttpMetricSink`.
+///
+/// The builder requires the DataDog API key used to submit metrics. It can
+/// also be used to set the DataDog site or API URL metrics are sent to, how
+/// many metrics are sent in each request, how long metrics are batched before
+/// being sent, and how long to wait for each request.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use cadence::{DatadogHttpMetricSinkBuilder, MetricSink};
+///
+/// let sink = DatadogHttpMetricSinkBuilder::new("my-api-key")
+///     .with_site("datadoghq.eu")
+///     .with_batch_size(100)
+///     .with_flush_interval(Duration::from_secs(10))
+///     .build();
+///
+/// sink.emit("foo.counter:4|c");
+/// ```
+#[derive(Clone)]
+pub struct DatadogHttpMetricSinkBuilder {
+    api_key: String,
+    api_url: String,
+    batch_size: usize,
+    flush_interval: Option<Duration>,
+    timeout: Duration,
+}
+
+impl DatadogHttpMetricSinkBuilder {
+    /// Construct a new builder that sends metrics to the US1 DataDog site using
+    /// the given API key.
+    pub fn new<T>(api_key: T) -> Self
+    where
+        T: Into<String>,
+    {
+        DatadogHttpMetricSinkBuilder {
+            api_key: api_key.into(),
+            api_url: site_url(DEFAULT_SITE),
+            batch_size: DEFAULT_BATCH_SIZE,
+            flush_interval: None,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Set the DataDog site to send metrics to, such as `datadoghq.eu` or
+    /// `us5.datadoghq.com`. The default is `datadoghq.com`.
+    pub fn with_site(mut self, site: &str) -> Self {
+        self.api_url = site_url(site);
+        self
+    }
+
+    /// Set the base URL of the API metrics are sent to, such as
+    /// `https://proxy.example.com`, overriding the site. The paths of the
+    /// series and distribution points endpoints are appended to it. This is
+    /// useful for sending metrics through a proxy.
+    pub fn with_api_url<T>(mut self, url: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.api_url = url.into().trim_end_matches('/').to_owned();
+        self
+    }
+
+    /// Set the number of metrics to batch before sending them in a single
+    /// request. The default is 500.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the batch size is zero.
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        assert!(size > 0, "batch size must be greater than zero");
+        self.batch_size = size;
+        self
+    }
+
+    /// Send batched metrics when a metric is emitted after the first metric
+    /// in the batch has been waiting for at least `interval`, even if the
+    /// batch isn't full.
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = Some(interval);
+        self
+    }
+
+    /// Set how long to wait for each request to complete. The default is ten
+    /// seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Construct a new `DatadogHttpMetricSink` based on the builder
+    /// configuration.
+    pub fn build(self) -> DatadogHttpMetricSink {
+        DatadogHttpMetricSink {
+            agent: AssertUnwindSafe(ureq::AgentBuilder::new().timeout(self.timeout).build()),
+            api_key: self.api_key,
+            series_url: format!("{}/api/v1/series", self.api_url),
+            distribution_url: format!("{}/api/v1/distribution_points", self.api_url),
+            batch_size: self.batch_size,
+            flush_interval: self.flush_interval,
+            batch: Mutex::new(Batch::default()),
+            stats: SocketStats::default(),
+        }
+    }
+}
+
+/// Metrics converted to DataDog series that haven't been sent yet
+// The API key is a secret so it's left out of the debug output.
+impl fmt::Debug for DatadogHttpMetricSinkBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("DatadogHttpMetricSinkBuilder")
+            .field("api_key", &"...")
+            .field("api_url", &self.api_url)
+            .field("batch_size", &self.batch_size)
+            .field("flush_interval", &self.flush_interval)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+#[derive(Debug, Default)]
+struct Batch {
+    series: Vec<String>,
+    distributions: Vec<String>,
+    started_at: Option<Instant>,
+}
+
+impl Batch {
+    fn len(&self) -> usize {
+        self.series.len() + self.distributions.len()
+    }
+}
+
+/// Metric converted to a DataDog series for one of the intake endpoints
+#[derive(Debug, Clone, PartialEq)]
+enum Series {
+    Metric(String),
+    Distribution(String),
+}
+
+/// Implementation of a `MetricSink` that sends metrics to the DataDog HTTP
+/// API instead of a Statsd server or DataDog agent.
+///
+/// This is meant for environments where no DataDog agent can be run, such as
+/// some platforms as a service. Metrics are batched and sent over HTTPS to the
+/// metrics intake of the configured DataDog site once the batch is full, when
+/// the flush interval has elapsed, when the sink is flushed, or when it's
+/// dropped. Requests are made in the thread that emits the metric that
+/// completes a batch so this sink should usually be wrapped in a
+/// `QueuingMetricSink`.
+///
+/// Counters are submitted as DataDog counts, scaled by their sample rate.
+/// Timers, histograms, and distributions are submitted to the distribution
+/// points intake with every value, so that DataDog calculates percentiles and
+/// other aggregations from them. Gauges, meters, and custom metric types are
+/// submitted as gauges with a single point: the last value of metrics with
+/// packed values, since DataDog keeps a single point per timestamp. Tags and
+/// timestamps are sent as-is, and metrics without a timestamp use the time
+/// they were emitted. Sets, events, and service checks can't be sent and
+/// result in an `InvalidInput` error.
+///
+/// If a request fails, the metrics in it are dropped and the error is
+/// returned to the caller that triggered the request.
+///
+/// This sink is only available when the `datadog-http` feature is enabled,
+/// which requires Rust 1.71 or newer.
+///
+/// # Example
+///
+/// ```no_run
+/// use cadence::prelude::*;
+/// use cadence::{DatadogHttpMetricSink, QueuingMetricSink, StatsdClient};
+///
+/// let sink = DatadogHttpMetricSink::builder("my-api-key").build();
+/// let queuing = QueuingMetricSink::from(sink);
+/// let client = StatsdClient::from_sink("my.prefix", queuing);
+///
+/// client.count_with_tags("requests", 1).with_tag("method", "GET").send();
+/// ```
+pub struct DatadogHttpMetricSink {
+    // The agent only holds a pool of idle connections which is safe to keep
+    // using if a request panics, allowing this sink to be used with other
+    // sinks such as `QueuingMetricSink` that require `RefUnwindSafe`.
+    agent: AssertUnwindSafe<ureq::Agent>,
+    api_key: String,
+    series_url: String,
+    distribution_url: String,
+    batch_size: usize,
+    flush_interval: Option<Duration>,
+    batch: Mutex<Batch>,
+    stats: SocketStats,
+}
+
+impl DatadogHttpMetricSink {
+    /// Construct a new builder for `DatadogHttpMetricSink` using the given
+    /// API key.
+    pub fn builder<T>(api_key: T) -> DatadogHttpMetricSinkBuilder
+    where
+        T: Into<String>,
+    {
+        DatadogHttpMetricSinkBuilder::new(api_key)
+    }
+
+    // Add a series to the batch, returning the whole batch if it should be
+    // sent once the lock is released.
+    fn add_series(&self, series: Series) -> Option<Batch> {
+        let mut batch = self.batch.lock().unwrap();
+        let now = Instant::now();
+        let started_at = *batch.started_at.get_or_insert(now);
+        match series {
+            Series::Metric(s) => batch.series.push(s),
+            Series::Distribution(s) => batch.distributions.push(s),
+        }
+
+        let expired = self
+            .flush_interval
+            .map(|interval| now.duration_since(started_at) >= interval)
+            .unwrap_or(false);
+
+        if batch.len() >= self.batch_size || expired {
+            Some(mem::take(&mut *batch))
+        } else {
+            None
+        }
+    }
+
+    // Send each kind of series in the batch to its endpoint, returning the
+    // first error if either request fails.
+    fn send_batch(&self, batch: &Batch) -> io::Result<()> {
+        let series = self.send(&self.series_url, &batch.series);
+        let distributions = self.send(&self.distribution_url, &batch.distributions);
+        series.and(distributions).map(|_| ())
+    }
+
+    fn send(&self, url: &str, series: &[String]) -> io::Result<usize> {
+        if series.is_empty() {
+            return Ok(0);
+        }
+
+        let body = format!("{{\"series\":[{}]}}", series.join(","));
+        let res = self
+            .agent
+            .post(url)
+            .set("DD-API-KEY", &self.api_key)
+            .set("Content-Type", "application/json")
+            .send_string(&body)
+            .map(|_| body.len())
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()));
+
+        self.stats.update(res, body.len())
+    }
+}
+
+impl MetricSink for DatadogHttpMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let series = convert(metric)?;
+        if let Some(batch) = self.add_series(series) {
+            self.send_batch(&batch)?;
+        }
+
+        Ok(metric.len())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let batch = mem::take(&mut *self.batch.lock().unwrap());
+        self.send_batch(&batch)
+    }
+
+    fn stats(&self) -> SinkStats {
+        (&self.stats).into()
+    }
+}
+
+impl Drop for DatadogHttpMetricSink {
+    fn drop(&mut self) {
+        let _r = self.flush();
+    }
+}
+
+impl fmt::Debug for DatadogHttpMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "DatadogHttpMetricSink {{ series_url: {:?}, distribution_url: {:?}, batch_size: {}, flush_interval: {:?} }}",
+            self.series_url, self.distribution_url, self.batch_size, self.flush_interval
+        )
+    }
+}
+
+fn site_url(site: &str) -> String {
+    format!("https://api.{}", site)
+}
+
+/// Convert a Statsd metric to a DataDog series object.
+fn convert(metric: &str) -> io::Result<Series> {
+    let parsed = ParsedMetric::parse(metric).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // Members of a set are identifiers, not values that can be graphed
+    if parsed.metric_type() == MetricType::Set {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "sets can't be sent to DataDog",
+        ));
+    }
+
+    let timestamp = match parsed.timestamp() {
+        Some(ts) => ts,
+        None => SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or(0),
+    };
+
+    let values: Vec<f64> = match parsed.value() {
+        MetricValue::Signed(v) => vec![*v as f64],
+        MetricValue::Unsigned(v) => vec![*v as f64],
+        MetricValue::Float(v) => vec![*v],
+        MetricValue::PackedSigned(vals) => vals.iter().map(|v| *v as f64).collect(),
+        MetricValue::PackedUnsigned(vals) => vals.iter().map(|v| *v as f64).collect(),
+        MetricValue::PackedFloat(vals) => vals.clone(),
+    };
+
+    // JSON has no way to represent NaN or infinite numbers
+    if !values.iter().all(|v| v.is_finite()) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            "metric values must be finite to be sent to DataDog",
+        ));
+    }
+
+    let mut out = String::with_capacity(metric.len() * 2 + 64);
+    // Writing to a `String` can't fail
+    let _ = write_series(&mut out, &parsed, timestamp, &values);
+    Ok(match parsed.metric_type() {
+        MetricType::Timer | MetricType::Histogram | MetricType::Distribution => Series::Distribution(out),
+        _ => Series::Metric(out),
+    })
+}
+
+fn write_series(out: &mut String, metric: &ParsedMetric<'_>, timestamp: u64, values: &[f64]) -> fmt::Result {
+    out.push_str("{\"metric\":");
+    write_json_str(out, metric.name())?;
+
+    match metric.metric_type() {
+        // Counts are a single point with the total of all values, scaled up to
+        // account for values that weren't sent due to sampling.
+        MetricType::Counter => {
+            let total: f64 = values.iter().sum();
+            let rate = metric.sample_rate().filter(|r| *r > 0.0).unwrap_or(1.0);
+            write!(out, ",\"type\":\"count\",\"points\":[[{},{}]]", timestamp, total / rate)?;
+        }
+        // Distribution points have every value for a timestamp in one point
+        MetricType::Timer | MetricType::Histogram | MetricType::Distribution => {
+            write!(out, ",\"type\":\"distribution\",\"points\":[[{},[", timestamp)?;
+            for (i, val) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write!(out, "{}", val)?;
+            }
+            out.push_str("]]]");
+        }
+        // Only one point per timestamp is kept, so use the latest value
+        _ => {
+            let last = values.last().copied().unwrap_or_default();
+            write!(out, ",\"type\":\"gauge\",\"points\":[[{},{}]]", timestamp, last)?;
+        }
+    }
+
+    out.push_str(",\"tags\":[");
+    for (i, (key, val)) in metric.tags().iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        match key {
+            Some(key) => write_json_str(out, &format!("{}:{}", key, val))?,
+            None => write_json_str(out, val)?,
+        }
+    }
+
+    out.push_str("]}");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{convert, DatadogHttpMetricSink, MetricSink, Series};
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+
+    // Start an HTTP server that responds to each request with the given status
+    // and sends the path, API key header, and body of the request to the
+    // returned channel.
+    fn server(status: &'static str) -> (String, Receiver<(String, String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut request = String::new();
+                reader.read_line(&mut request).unwrap();
+                let path = request.split(' ').nth(1).unwrap_or_default().to_owned();
+
+                let mut api_key = String::new();
+                let mut length = 0;
+
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+
+                    let (name, value) = line.split_once(": ").unwrap_or((line, ""));
+                    if name.eq_ignore_ascii_case("dd-api-key") {
+                        api_key = value.to_owned();
+                    } else if name.eq_ignore_ascii_case("content-length") {
+                        length = value.parse().unwrap();
+                    }
+                }
+
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let response = format!(
+                    "HTTP/1.1 {}\r\nContent-Length: 2\r\nConnection: close\r\n\r\n{{}}",
+                    status
+                );
+                stream.write_all(response.as_bytes()).unwrap();
+
+                if tx.send((path, api_key, String::from_utf8(body).unwrap())).is_err() {
+                    break;
+                }
+            }
+        });
+
+        (url, rx)
+    }
+
+    #[test]
+    fn test_convert_counter() {
+        assert_eq!(
+            Series::Metric(
+                r#"{"metric":"some.counter","type":"count","points":[[1700000000,10]],"tags":["env:prod","beta"]}"#
+                    .to_owned()
+            ),
+            convert("some.counter:3:2|c|@0.5|#env:prod,beta|T1700000000").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_convert_gauge() {
+        assert_eq!(
+            Series::Metric(r#"{"metric":"some.gauge","type":"gauge","points":[[1700000000,7]],"tags":[]}"#.to_owned()),
+            convert("some.gauge:5:7|g|T1700000000").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_convert_timer() {
+        assert_eq!(
+            Series::Distribution(
+                r#"{"metric":"some.timer","type":"distribution","points":[[1700000000,[12,1.5]]],"tags":[]}"#
+                    .to_owned()
+            ),
+            convert("some.timer:12:1.5|ms|T1700000000").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_convert_invalid() {
+        assert!(convert("_e{5,4}:title|text").is_err());
+        assert!(convert("some.gauge:inf|g").is_err());
+        assert!(convert("some.set:44|s").is_err());
+    }
+
+    #[test]
+    fn test_datadog_http_metric_sink_batch() {
+        let (url, rx) = server("202 Accepted");
+        let sink = DatadogHttpMetricSink::builder("some-key")
+            .with_api_url(url)
+            .with_batch_size(2)
+            .build();
+
+        sink.emit("some.counter:1|c|T1700000000").unwrap();
+        assert!(rx.try_recv().is_err());
+        sink.emit("some.gauge:5|g|T1700000000").unwrap();
+
+        let (path, api_key, body) = rx.recv().unwrap();
+        assert_eq!("/api/v1/series", path);
+        assert_eq!("some-key", api_key);
+        assert_eq!(
+            concat!(
+                r#"{"series":[{"metric":"some.counter","type":"count","points":[[1700000000,1]],"tags":[]},"#,
+                r#"{"metric":"some.gauge","type":"gauge","points":[[1700000000,5]],"tags":[]}]}"#,
+            ),
+            body
+        );
+
+        let stats = sink.stats();
+        assert_eq!(1, stats.packets_sent);
+        assert_eq!(body.len() as u64, stats.bytes_sent);
+    }
+
+    #[test]
+    fn test_datadog_http_metric_sink_builder_debug_redacts_api_key() {
+        let builder = DatadogHttpMetricSink::builder("some-key");
+        let debug = format!("{:?}", builder);
+        assert!(!debug.contains("some-key"));
+        assert!(debug.contains("api_url"));
+    }
+
+    #[test]
+    fn test_datadog_http_metric_sink_flush() {
+        let (url, rx) = server("202 Accepted");
+        let sink = DatadogHttpMetricSink::builder("some-key").with_api_url(url).build();
+
+        sink.emit("some.counter:1|c|T1700000000").unwrap();
+        sink.flush().unwrap();
+        sink.flush().unwrap();
+
+        let (_, _, body) = rx.recv().unwrap();
+        assert!(body.contains("some.counter"));
+        assert_eq!(1, sink.stats().packets_sent);
+    }
+
+    #[test]
+    fn test_datadog_http_metric_sink_distributions() {
+        let (url, rx) = server("202 Accepted");
+        let sink = DatadogHttpMetricSink::builder("some-key").with_api_url(url).build();
+
+        sink.emit("some.counter:1|c|T1700000000").unwrap();
+        sink.emit("some.histogram:1:2|h|T1700000000").unwrap();
+        sink.flush().unwrap();
+
+        let (path, _, body) = rx.recv().unwrap();
+        assert_eq!("/api/v1/series", path);
+        assert!(body.contains("some.counter"));
+
+        let (path, _, body) = rx.recv().unwrap();
+        assert_eq!("/api/v1/distribution_points", path);
+        assert_eq!(
+            r#"{"series":[{"metric":"some.histogram","type":"distribution","points":[[1700000000,[1,2]]],"tags":[]}]}"#,
+            body
+        );
+        assert_eq!(2, sink.stats().packets_sent);
+    }
+
+    #[test]
+    fn test_datadog_http_metric_sink_error() {
+        let (url, rx) = server("403 Forbidden");
+        let sink = DatadogHttpMetricSink::builder("bad-key")
+            .with_api_url(url)
+            .with_batch_size(1)
+            .build();
+
+        assert!(sink.emit("some.counter:1|c").is_err());
+        assert!(rx.recv().is_ok());
+
+        let stats = sink.stats();
+        assert_eq!(0, stats.packets_sent);
+        assert_eq!(1, stats.packets_dropped);
+    }
+}
@@ -0,0 +1,149 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::sinks::core::{MetricSink, SinkStats};
+
+/// Implementation of a `MetricSink` that writes metrics the primary sink
+/// failed to send to a secondary "dead letter" sink, so that metrics lost
+/// during an outage can be inspected or replayed later.
+///
+/// Unlike `FailoverMetricSink`, every metric is written to the primary sink
+/// and the error from the primary sink is still returned after the metric is
+/// written to the dead letter sink, so that error handlers and error counts
+/// still see the failure. To only write metrics to the dead letter sink once
+/// retries have been exhausted, wrap the primary sink in a
+/// `RetryingMetricSink`. Errors from the dead letter sink itself are ignored.
+///
+/// Stats returned by this sink are the stats of the primary sink.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::fs::{File, OpenOptions};
+/// use std::io::{self, Write};
+/// use std::net::UdpSocket;
+/// use std::sync::Mutex;
+/// use cadence::{DeadLetterMetricSink, MetricSink, RetryingMetricSink, UdpMetricSink, DEFAULT_PORT};
+///
+/// // Sink that appends each metric to a file, one per line
+/// struct FileSink(Mutex<File>);
+///
+/// impl MetricSink for FileSink {
+///     fn emit(&self, metric: &str) -> io::Result<usize> {
+///         writeln!(self.0.lock().unwrap(), "{}", metric)?;
+///         Ok(metric.len())
+///     }
+/// }
+///
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let udp = UdpMetricSink::from(("localhost", DEFAULT_PORT), socket).unwrap();
+/// let file = OpenOptions::new().create(true).append(true).open("/var/tmp/dead-metrics.log").unwrap();
+///
+/// let sink = DeadLetterMetricSink::from(RetryingMetricSink::from(udp), FileSink(Mutex::new(file)));
+/// sink.emit("foo.counter:4|c");
+/// ```
+pub struct DeadLetterMetricSink {
+    primary: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
+    dead_letter: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
+    dead_lettered: AtomicU64,
+}
+
+impl DeadLetterMetricSink {
+    /// Construct a new `DeadLetterMetricSink` that writes metrics to the
+    /// primary sink and any that fail to the dead letter sink.
+    pub fn from<P, D>(primary: P, dead_letter: D) -> Self
+    where
+        P: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+        D: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        DeadLetterMetricSink {
+            primary: Box::new(primary),
+            dead_letter: Box::new(dead_letter),
+            dead_lettered: AtomicU64::new(0),
+        }
+    }
+
+    /// Return the number of metrics that the primary sink failed to send and
+    /// were written to the dead letter sink.
+    pub fn dead_lettered(&self) -> u64 {
+        self.dead_lettered.load(Ordering::Relaxed)
+    }
+}
+
+impl MetricSink for DeadLetterMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let res = self.primary.emit(metric);
+        if res.is_err() {
+            self.dead_lettered.fetch_add(1, Ordering::Relaxed);
+            let _ = self.dead_letter.emit(metric);
+        }
+
+        res
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let res = self.primary.flush();
+        let _ = self.dead_letter.flush();
+        res
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.primary.check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.primary.stats()
+    }
+}
+
+impl fmt::Debug for DeadLetterMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "DeadLetterMetricSink {{ dead_lettered: {} }}", self.dead_lettered())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DeadLetterMetricSink, MetricSink};
+    use crate::sinks::flaky::FlakyMetricSink;
+    use crate::sinks::spy::RecordingMetricSink;
+    use std::io;
+
+    #[test]
+    fn test_dead_letter_metric_sink_primary_succeeds() {
+        let primary = RecordingMetricSink::new();
+        let dead_letter = RecordingMetricSink::new();
+        let sink = DeadLetterMetricSink::from(primary.clone(), dead_letter.clone());
+
+        assert_eq!(7, sink.emit("foo:1|c").unwrap());
+        assert_eq!(vec!["foo:1|c"], primary.metrics());
+        assert!(dead_letter.metrics().is_empty());
+        assert_eq!(0, sink.dead_lettered());
+    }
+
+    #[test]
+    fn test_dead_letter_metric_sink_primary_fails() {
+        let primary = FlakyMetricSink::failing(RecordingMetricSink::new(), io::ErrorKind::BrokenPipe);
+        let dead_letter = RecordingMetricSink::new();
+        let sink = DeadLetterMetricSink::from(primary, dead_letter.clone());
+
+        let err = sink.emit("foo:1|c").unwrap_err();
+        assert_eq!(io::ErrorKind::BrokenPipe, err.kind());
+        sink.emit("bar:2|c").unwrap_err();
+
+        assert_eq!(vec!["foo:1|c", "bar:2|c"], dead_letter.metrics());
+        assert_eq!(2, sink.dead_lettered());
+    }
+}
@@ -0,0 +1,291 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::sinks::core::{MetricSink, SinkStats};
+
+const DEFAULT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Implementation of a builder pattern for `DeduplicatingMetricSink`.
+///
+/// The builder can be used to set how long identical gauge values are
+/// suppressed for before being sent again, 60 seconds by default.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::net::UdpSocket;
+/// use std::time::Duration;
+/// use cadence::{DeduplicatingMetricSinkBuilder, UdpMetricSink, DEFAULT_PORT};
+///
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let udp = UdpMetricSink::from(("localhost", DEFAULT_PORT), socket).unwrap();
+///
+/// let sink = DeduplicatingMetricSinkBuilder::new()
+///     .with_window(Duration::from_secs(30))
+///     .build(udp);
+/// ```
+#[derive(Debug, Clone)]
+pub struct DeduplicatingMetricSinkBuilder {
+    window: Duration,
+}
+
+impl DeduplicatingMetricSinkBuilder {
+    /// Construct a new builder with the default window.
+    pub fn new() -> Self {
+        DeduplicatingMetricSinkBuilder { window: DEFAULT_WINDOW }
+    }
+
+    /// Set how long a gauge value that is the same as the last value sent
+    /// for the same key is suppressed for. Once the window has passed since
+    /// the value was last sent, it is sent again even if it hasn't changed.
+    pub fn with_window(mut self, window: Duration) -> Self {
+        self.window = window;
+        self
+    }
+
+    /// Construct a new `DeduplicatingMetricSink` instance wrapping the given
+    /// sink based on the builder configuration.
+    pub fn build<T>(self, sink: T) -> DeduplicatingMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        DeduplicatingMetricSink {
+            sink: Box::new(sink),
+            window: self.window,
+            state: Mutex::new(Sent {
+                pruned: Instant::now(),
+                values: HashMap::new(),
+            }),
+            suppressed: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Default for DeduplicatingMetricSinkBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+// Last value sent for each gauge key and when it was sent
+#[derive(Debug)]
+struct Sent {
+    pruned: Instant,
+    values: HashMap<String, (String, Instant)>,
+}
+
+/// Implementation of a `MetricSink` that drops gauges with the same value as
+/// the last one sent for the same key, instead of writing them to a wrapped
+/// sink.
+///
+/// This is useful when gauges are sent often, such as on every request, but
+/// their values rarely change. The key of a gauge is its name along with its
+/// tags and anything else after the value, so the same gauge with different
+/// tags is tracked separately. Unchanged values are still sent once the window
+/// set using `DeduplicatingMetricSinkBuilder` has passed since the value was
+/// last sent, so that the Statsd server keeps receiving them.
+///
+/// Gauges that are dropped are not written to the wrapped sink and `0` is
+/// returned from `.emit()` instead of an error. Relative gauges, like `+5` or
+/// `-5`, and all other types of metrics are always written.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{DeduplicatingMetricSink, MetricSink, SpyMetricSink};
+///
+/// let (rx, spy) = SpyMetricSink::new();
+/// let sink = DeduplicatingMetricSink::from(spy);
+///
+/// sink.emit("pool.size:8|g").unwrap();
+/// sink.emit("pool.size:8|g").unwrap();
+/// sink.emit("pool.size:9|g").unwrap();
+///
+/// let sent: Vec<Vec<u8>> = rx.try_iter().collect();
+/// assert_eq!(vec![b"pool.size:8|g".to_vec(), b"pool.size:9|g".to_vec()], sent);
+/// ```
+pub struct DeduplicatingMetricSink {
+    sink: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
+    window: Duration,
+    state: Mutex<Sent>,
+    suppressed: AtomicU64,
+}
+
+impl DeduplicatingMetricSink {
+    /// Construct a new `DeduplicatingMetricSink` wrapping the given sink with
+    /// the default window.
+    pub fn from<T>(sink: T) -> Self
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        DeduplicatingMetricSinkBuilder::new().build(sink)
+    }
+
+    /// Construct a new builder for `DeduplicatingMetricSink`.
+    pub fn builder() -> DeduplicatingMetricSinkBuilder {
+        DeduplicatingMetricSinkBuilder::new()
+    }
+
+    /// Return the number of gauges that have been dropped because their value
+    /// was unchanged.
+    pub fn suppressed(&self) -> u64 {
+        self.suppressed.load(Ordering::Relaxed)
+    }
+
+    fn is_duplicate(&self, metric: &str, now: Instant) -> bool {
+        let (key, value) = match split_gauge(metric) {
+            Some(parts) => parts,
+            None => return false,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        // Values sent longer ago than the window would be sent again anyway,
+        // so forget them to keep keys that are no longer used from piling up.
+        if now.saturating_duration_since(state.pruned) >= self.window {
+            let window = self.window;
+            state
+                .values
+                .retain(|_, (_, sent)| now.saturating_duration_since(*sent) < window);
+            state.pruned = now;
+        }
+
+        if let Some((last, sent)) = state.values.get(&key) {
+            if last == value && now.saturating_duration_since(*sent) < self.window {
+                return true;
+            }
+        }
+
+        state.values.insert(key, (value.to_owned(), now));
+        false
+    }
+}
+
+impl MetricSink for DeduplicatingMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        if self.is_duplicate(metric, Instant::now()) {
+            self.suppressed.fetch_add(1, Ordering::Relaxed);
+            return Ok(0);
+        }
+
+        self.sink.emit(metric)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.sink.flush()
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.sink.check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats()
+    }
+}
+
+impl fmt::Debug for DeduplicatingMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "DeduplicatingMetricSink {{ window: {:?}, suppressed: {} }}",
+            self.window,
+            self.suppressed()
+        )
+    }
+}
+
+/// Split an absolute gauge into a key made of its name and everything after
+/// its value, and its value. Returns `None` for any other kind of metric.
+fn split_gauge(metric: &str) -> Option<(String, &str)> {
+    let colon = metric.find(':')?;
+    let pipe = colon + metric[colon..].find('|')?;
+    let (name, value, suffix) = (&metric[..colon], &metric[colon + 1..pipe], &metric[pipe..]);
+
+    let is_gauge = suffix == "|g" || suffix.starts_with("|g|");
+    if !is_gauge || value.starts_with('+') || value.starts_with('-') {
+        return None;
+    }
+
+    Some((format!("{}{}", name, suffix), value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{split_gauge, DeduplicatingMetricSink, MetricSink};
+    use crate::sinks::spy::RecordingMetricSink;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn test_split_gauge() {
+        assert_eq!(Some(("foo|g".to_owned(), "1")), split_gauge("foo:1|g"));
+        assert_eq!(Some(("foo|g|#a:b".to_owned(), "1:2")), split_gauge("foo:1:2|g|#a:b"));
+        assert_eq!(None, split_gauge("foo:+1|g"));
+        assert_eq!(None, split_gauge("foo:-1|g"));
+        assert_eq!(None, split_gauge("foo:1|c"));
+        assert_eq!(None, split_gauge("foo:1|gauge"));
+        assert_eq!(None, split_gauge("_sc|foo|0"));
+    }
+
+    #[test]
+    fn test_deduplicating_metric_sink_unchanged_values() {
+        let recording = RecordingMetricSink::new();
+        let sink = DeduplicatingMetricSink::from(recording.clone());
+
+        assert_eq!(7, sink.emit("foo:1|g").unwrap());
+        assert_eq!(0, sink.emit("foo:1|g").unwrap());
+        assert_eq!(12, sink.emit("foo:1|g|#a:b").unwrap());
+        assert_eq!(7, sink.emit("foo:2|g").unwrap());
+        assert_eq!(7, sink.emit("foo:1|g").unwrap());
+        assert_eq!(7, sink.emit("foo:1|c").unwrap());
+        assert_eq!(7, sink.emit("foo:1|c").unwrap());
+        assert_eq!(8, sink.emit("foo:+1|g").unwrap());
+        assert_eq!(8, sink.emit("foo:+1|g").unwrap());
+
+        assert_eq!(
+            vec![
+                "foo:1|g",
+                "foo:1|g|#a:b",
+                "foo:2|g",
+                "foo:1|g",
+                "foo:1|c",
+                "foo:1|c",
+                "foo:+1|g",
+                "foo:+1|g"
+            ],
+            recording.metrics()
+        );
+        assert_eq!(1, sink.suppressed());
+    }
+
+    #[test]
+    fn test_deduplicating_metric_sink_window() {
+        let sink = DeduplicatingMetricSink::builder()
+            .with_window(Duration::from_secs(10))
+            .build(RecordingMetricSink::new());
+        let start = Instant::now();
+
+        assert!(!sink.is_duplicate("foo:1|g", start));
+        assert!(!sink.is_duplicate("bar:1|g", start));
+        assert!(sink.is_duplicate("foo:1|g", start + Duration::from_secs(5)));
+        assert!(!sink.is_duplicate("foo:1|g", start + Duration::from_secs(10)));
+        assert!(sink.is_duplicate("foo:1|g", start + Duration::from_secs(15)));
+
+        // Keys not sent within the window are forgotten
+        assert_eq!(1, sink.state.lock().unwrap().values.len());
+    }
+}
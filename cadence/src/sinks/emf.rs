@@ -0,0 +1,312 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt::{self, Write as _};
+use std::io::{self, Write};
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use crate::builder::{MetricType, MetricValue};
+use crate::parse::ParsedMetric;
+use crate::sinks::core::{MetricSink, SinkStats, SocketStats};
+
+/// Implementation of a `MetricSink` that writes metrics as CloudWatch Embedded
+/// Metric Format (EMF) JSON documents, one per line, to stdout or another writer.
+///
+/// This allows applications running on AWS Lambda or ECS, where everything
+/// written to stdout is sent to CloudWatch Logs, to create CloudWatch metrics
+/// using the same `StatsdClient` API without running a Statsd server.
+///
+/// Each metric is written as a separate document in the configured namespace.
+/// Key-value tags of a metric are used as its dimensions, so metrics should have
+/// no more than 30 key-value tags, the CloudWatch limit. Value tags, sample
+/// rates, and container IDs have no equivalent in EMF and are not written.
+///
+/// Timers are written with the unit `Milliseconds`, other metrics have no unit.
+/// Packed values are written as an array of values in a single document. The
+/// timestamp of a metric is used if it has one, otherwise the current time is.
+/// Events and service checks can't be written and result in an `InvalidInput`
+/// error.
+///
+/// # Example
+///
+/// ```no_run
+/// use cadence::prelude::*;
+/// use cadence::{EmfMetricSink, StatsdClient};
+///
+/// let sink = EmfMetricSink::new("MyService");
+/// let client = StatsdClient::from_sink("my.prefix", sink);
+///
+/// // Writes a document like
+/// // {"_aws":{"Timestamp":1700000000000,"CloudWatchMetrics":[{"Namespace":"MyService",
+/// // "Dimensions":[["method"]],"Metrics":[{"Name":"my.prefix.latency","Unit":"Milliseconds"}]}]},
+/// // "method":"GET","my.prefix.latency":23}
+/// client.time_with_tags("latency", 23).with_tag("method", "GET").send();
+/// ```
+pub struct EmfMetricSink {
+    namespace: String,
+    writer: Mutex<Box<dyn Write + Send>>,
+    stats: SocketStats,
+}
+
+impl EmfMetricSink {
+    /// Construct a new `EmfMetricSink` that writes metrics in the given
+    /// namespace to stdout.
+    pub fn new<T>(namespace: T) -> Self
+    where
+        T: Into<String>,
+    {
+        Self::from_writer(namespace, io::stdout())
+    }
+
+    /// Construct a new `EmfMetricSink` that writes metrics in the given
+    /// namespace to the given writer.
+    ///
+    /// Each document is written with a single call to `.write_all()` so
+    /// unbuffered writers may be used without documents being interleaved
+    /// with output from other threads.
+    pub fn from_writer<T, W>(namespace: T, writer: W) -> Self
+    where
+        T: Into<String>,
+        W: Write + Send + 'static,
+    {
+        EmfMetricSink {
+            namespace: namespace.into(),
+            writer: Mutex::new(Box::new(writer)),
+            stats: SocketStats::default(),
+        }
+    }
+
+    /// Convert a Statsd metric to an EMF document followed by a newline.
+    fn convert(&self, metric: &str) -> io::Result<String> {
+        let parsed = ParsedMetric::parse(metric).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let timestamp = match parsed.timestamp() {
+            Some(ts) => u128::from(ts) * 1000,
+            None => SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_millis())
+                .unwrap_or(0),
+        };
+
+        let dimensions: Vec<(&str, &str)> = parsed.tags().iter().filter_map(|(k, v)| k.map(|k| (k, *v))).collect();
+
+        // JSON has no way to represent NaN or infinite numbers
+        let finite = match parsed.value() {
+            MetricValue::Float(v) => v.is_finite(),
+            MetricValue::PackedFloat(vals) => vals.iter().all(|v| v.is_finite()),
+            _ => true,
+        };
+
+        if !finite {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "metric values must be finite to be written as EMF",
+            ));
+        }
+
+        let mut out = String::with_capacity(metric.len() * 4 + self.namespace.len() + 128);
+        // Writing to a `String` can't fail
+        let _ = self.write_document(&mut out, &parsed, timestamp, &dimensions);
+        out.push('\n');
+        Ok(out)
+    }
+
+    fn write_document(
+        &self,
+        out: &mut String,
+        metric: &ParsedMetric<'_>,
+        timestamp: u128,
+        dimensions: &[(&str, &str)],
+    ) -> fmt::Result {
+        write!(
+            out,
+            "{{\"_aws\":{{\"Timestamp\":{},\"CloudWatchMetrics\":[{{\"Namespace\":",
+            timestamp
+        )?;
+        write_json_str(out, &self.namespace)?;
+        out.push_str(",\"Dimensions\":[[");
+        for (i, (key, _)) in dimensions.iter().enumerate() {
+            if i > 0 {
+                out.push(',');
+            }
+            write_json_str(out, key)?;
+        }
+
+        out.push_str("]],\"Metrics\":[{\"Name\":");
+        write_json_str(out, metric.name())?;
+        if metric.metric_type() == MetricType::Timer {
+            out.push_str(",\"Unit\":\"Milliseconds\"");
+        }
+
+        out.push_str("}]}]}");
+        for (key, val) in dimensions {
+            out.push(',');
+            write_json_str(out, key)?;
+            out.push(':');
+            write_json_str(out, val)?;
+        }
+
+        out.push(',');
+        write_json_str(out, metric.name())?;
+        out.push(':');
+        match metric.value() {
+            MetricValue::Signed(v) => write!(out, "{}", v)?,
+            MetricValue::Unsigned(v) => write!(out, "{}", v)?,
+            MetricValue::Float(v) => write!(out, "{}", v)?,
+            MetricValue::PackedSigned(vals) => write_array(out, vals)?,
+            MetricValue::PackedUnsigned(vals) => write_array(out, vals)?,
+            MetricValue::PackedFloat(vals) => write_array(out, vals)?,
+        }
+
+        out.push('}');
+        Ok(())
+    }
+}
+
+impl MetricSink for EmfMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let doc = self.convert(metric)?;
+        let res = self.writer.lock().unwrap().write_all(doc.as_bytes()).map(|_| doc.len());
+        self.stats.update(res, doc.len())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.writer.lock().unwrap().flush()
+    }
+
+    fn stats(&self) -> SinkStats {
+        (&self.stats).into()
+    }
+}
+
+impl fmt::Debug for EmfMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "EmfMetricSink {{ namespace: {:?} }}", self.namespace)
+    }
+}
+
+fn write_array<T>(out: &mut String, vals: &[T]) -> fmt::Result
+where
+    T: fmt::Display,
+{
+    out.push('[');
+    for (i, val) in vals.iter().enumerate() {
+        if i > 0 {
+            out.push(',');
+        }
+        write!(out, "{}", val)?;
+    }
+    out.push(']');
+    Ok(())
+}
+
+pub(crate) fn write_json_str(out: &mut String, val: &str) -> fmt::Result {
+    out.push('"');
+    for c in val.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => write!(out, "\\u{:04x}", c as u32)?,
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EmfMetricSink, MetricSink};
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Clone, Default)]
+    struct SharedWriter(Arc<Mutex<Vec<u8>>>);
+
+    impl SharedWriter {
+        fn contents(&self) -> String {
+            String::from_utf8(self.0.lock().unwrap().clone()).unwrap()
+        }
+    }
+
+    impl Write for SharedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn convert(metric: &str) -> String {
+        let writer = SharedWriter::default();
+        let sink = EmfMetricSink::from_writer("MyService", writer.clone());
+        sink.emit(metric).unwrap();
+        writer.contents()
+    }
+
+    #[test]
+    fn test_emf_metric_sink_timer_with_tags() {
+        assert_eq!(
+            concat!(
+                r#"{"_aws":{"Timestamp":1700000000000,"CloudWatchMetrics":[{"Namespace":"MyService","#,
+                r#""Dimensions":[["method","path"]],"Metrics":[{"Name":"req.latency","Unit":"Milliseconds"}]}]},"#,
+                r#""method":"GET","path":"/a\"b","req.latency":23}"#,
+                "\n",
+            ),
+            convert("req.latency:23|ms|#method:GET,beta,path:/a\"b|T1700000000")
+        );
+    }
+
+    #[test]
+    fn test_emf_metric_sink_packed_without_tags() {
+        assert_eq!(
+            concat!(
+                r#"{"_aws":{"Timestamp":1700000000000,"CloudWatchMetrics":[{"Namespace":"MyService","#,
+                r#""Dimensions":[[]],"Metrics":[{"Name":"some.histogram"}]}]},"some.histogram":[1.5,2,3.25]}"#,
+                "\n",
+            ),
+            convert("some.histogram:1.5:2:3.25|h|T1700000000")
+        );
+    }
+
+    #[test]
+    fn test_emf_metric_sink_current_timestamp() {
+        let doc = convert("some.counter:1|c");
+        assert!(doc.starts_with(r#"{"_aws":{"Timestamp":1"#));
+        assert!(doc.ends_with("\"some.counter\":1}\n"));
+    }
+
+    #[test]
+    fn test_emf_metric_sink_invalid() {
+        let writer = SharedWriter::default();
+        let sink = EmfMetricSink::from_writer("MyService", writer.clone());
+
+        let err = sink.emit("_sc|some.check|0").unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        assert!(sink.emit("some.gauge:NaN|g").is_err());
+        assert!(writer.contents().is_empty());
+    }
+
+    #[test]
+    fn test_emf_metric_sink_stats() {
+        let writer = SharedWriter::default();
+        let sink = EmfMetricSink::from_writer("MyService", writer.clone());
+        sink.emit("some.counter:1|c").unwrap();
+
+        let stats = sink.stats();
+        assert_eq!(1, stats.packets_sent);
+        assert_eq!(writer.contents().len() as u64, stats.bytes_sent);
+    }
+}
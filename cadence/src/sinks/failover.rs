@@ -0,0 +1,319 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::sinks::core::{MetricSink, SinkStats};
+
+const DEFAULT_FAILURE_THRESHOLD: u32 = 3;
+const DEFAULT_PROBE_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Implementation of a builder pattern for `FailoverMetricSink`.
+///
+/// The builder can be used to set how many consecutive failures of the
+/// primary sink are tolerated before failing over to the secondary sink and
+/// how often the primary sink is probed to see if it has recovered.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::net::UdpSocket;
+/// use std::time::Duration;
+/// use cadence::{FailoverMetricSinkBuilder, MetricSink, NopMetricSink, UdpMetricSink, DEFAULT_PORT};
+///
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let primary = UdpMetricSink::from(("localhost", DEFAULT_PORT), socket).unwrap();
+///
+/// let sink = FailoverMetricSinkBuilder::new()
+///     .with_failure_threshold(5)
+///     .with_probe_interval(Duration::from_secs(30))
+///     .build(primary, NopMetricSink);
+///
+/// sink.emit("foo.counter:4|c");
+/// ```
+#[derive(Debug, Clone)]
+pub struct FailoverMetricSinkBuilder {
+    threshold: u32,
+    probe_interval: Duration,
+}
+
+impl FailoverMetricSinkBuilder {
+    /// Construct a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the number of consecutive failures of the primary sink after which
+    /// metrics are written only to the secondary sink.
+    ///
+    /// The default threshold is 3 failures. A threshold of 0 is treated as 1.
+    pub fn with_failure_threshold(mut self, threshold: u32) -> Self {
+        self.threshold = threshold.max(1);
+        self
+    }
+
+    /// Set how long to wait after failing over before writing a metric to the
+    /// primary sink again to check if it has recovered.
+    ///
+    /// The default interval is 5 seconds.
+    pub fn with_probe_interval(mut self, interval: Duration) -> Self {
+        self.probe_interval = interval;
+        self
+    }
+
+    /// Construct a new `FailoverMetricSink` instance that writes to the primary
+    /// sink and falls back to the secondary sink based on the builder configuration.
+    pub fn build<P, S>(self, primary: P, secondary: S) -> FailoverMetricSink
+    where
+        P: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+        S: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        FailoverMetricSink {
+            primary: Box::new(primary),
+            secondary: Box::new(secondary),
+            threshold: self.threshold,
+            probe_interval: self.probe_interval,
+            state: Mutex::new(FailoverState::default()),
+        }
+    }
+}
+
+impl Default for FailoverMetricSinkBuilder {
+    fn default() -> Self {
+        FailoverMetricSinkBuilder {
+            threshold: DEFAULT_FAILURE_THRESHOLD,
+            probe_interval: DEFAULT_PROBE_INTERVAL,
+        }
+    }
+}
+
+/// Consecutive failures of the primary sink and when to probe it next
+#[derive(Debug, Default)]
+struct FailoverState {
+    failures: u32,
+    probe_at: Option<Instant>,
+}
+
+/// Implementation of a `MetricSink` that writes to a primary sink and falls
+/// back to a secondary sink when the primary sink fails.
+///
+/// Each metric is written to the primary sink first. If that fails, the metric
+/// is written to the secondary sink instead. After a number of consecutive
+/// failures (3 by default), the primary sink is considered to be down and
+/// metrics are written only to the secondary sink. While failed over, a single
+/// metric is written to the primary sink after each probe interval (5 seconds
+/// by default) to check if it has recovered. Once a write to the primary sink
+/// succeeds, metrics are written to it again.
+///
+/// The secondary sink might be a sink that writes to a different server, a
+/// sink that logs metrics locally, or a `NopMetricSink` if metrics should
+/// just be discarded quietly during an outage.
+///
+/// Stats returned by this sink are the sum of the stats of both sinks.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::net::UdpSocket;
+/// use cadence::{FailoverMetricSink, MetricSink, UdpMetricSink, DEFAULT_PORT};
+///
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let primary = UdpMetricSink::from(("statsd1.example.com", DEFAULT_PORT), socket).unwrap();
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let secondary = UdpMetricSink::from(("statsd2.example.com", DEFAULT_PORT), socket).unwrap();
+///
+/// let sink = FailoverMetricSink::from(primary, secondary);
+/// sink.emit("foo.counter:4|c");
+/// ```
+pub struct FailoverMetricSink {
+    primary: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
+    secondary: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
+    threshold: u32,
+    probe_interval: Duration,
+    state: Mutex<FailoverState>,
+}
+
+impl FailoverMetricSink {
+    /// Construct a new builder for `FailoverMetricSink`.
+    pub fn builder() -> FailoverMetricSinkBuilder {
+        FailoverMetricSinkBuilder::new()
+    }
+
+    /// Construct a new `FailoverMetricSink` instance with the default failure
+    /// threshold and probe interval.
+    pub fn from<P, S>(primary: P, secondary: S) -> FailoverMetricSink
+    where
+        P: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+        S: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        Self::builder().build(primary, secondary)
+    }
+
+    /// Return true if the primary sink is considered to be down and metrics
+    /// are being written only to the secondary sink.
+    pub fn is_failed_over(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.failures >= self.threshold
+    }
+
+    /// Return true if the next metric should be written to the primary sink
+    fn use_primary(&self, now: Instant) -> bool {
+        let mut state = self.state.lock().unwrap();
+        if state.failures < self.threshold {
+            return true;
+        }
+
+        match state.probe_at {
+            Some(probe_at) if now < probe_at => false,
+            _ => {
+                // Only let a single metric through to probe the primary sink,
+                // other callers keep writing to the secondary until it's done.
+                state.probe_at = Some(now + self.probe_interval);
+                true
+            }
+        }
+    }
+
+    fn primary_failure(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        state.failures = state.failures.saturating_add(1);
+        if state.failures >= self.threshold {
+            state.probe_at = Some(now + self.probe_interval);
+        }
+    }
+
+    fn primary_success(&self) {
+        let mut state = self.state.lock().unwrap();
+        state.failures = 0;
+        state.probe_at = None;
+    }
+}
+
+impl MetricSink for FailoverMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let now = Instant::now();
+        if self.use_primary(now) {
+            match self.primary.emit(metric) {
+                Ok(n) => {
+                    self.primary_success();
+                    return Ok(n);
+                }
+                Err(_) => self.primary_failure(now),
+            }
+        }
+
+        self.secondary.emit(metric)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        // Don't bother flushing the primary sink while it's down, it's only
+        // going to fail and any buffered metrics will be flushed after it
+        // recovers.
+        let primary = if self.is_failed_over() {
+            Ok(())
+        } else {
+            self.primary.flush()
+        };
+
+        self.secondary.flush()?;
+        primary
+    }
+
+    fn check(&self) -> io::Result<()> {
+        // Both sinks are checked since a secondary sink that doesn't work
+        // would only be noticed once the primary sink is already down.
+        self.primary.check()?;
+        self.secondary.check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.primary.stats().combine(self.secondary.stats())
+    }
+}
+
+impl fmt::Debug for FailoverMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "FailoverMetricSink {{ threshold: {}, probe_interval: {:?}, state: {:?} }}",
+            self.threshold, self.probe_interval, self.state
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FailoverMetricSink, MetricSink};
+    use crate::sinks::spy::SpyMetricSink;
+    use crate::test::FailingMetricSink;
+    use std::io;
+    use std::time::Duration;
+
+    #[test]
+    fn test_failover_metric_sink_primary() {
+        let primary = FailingMetricSink::new(io::ErrorKind::Other);
+        let (rx, secondary) = SpyMetricSink::new();
+        let sink = FailoverMetricSink::from(primary.clone(), secondary);
+
+        assert_eq!(7, sink.emit("foo:1|c").unwrap());
+        assert_eq!(1, primary.attempts());
+        assert!(rx.try_recv().is_err());
+        assert!(!sink.is_failed_over());
+    }
+
+    #[test]
+    fn test_failover_metric_sink_falls_back() {
+        let primary = FailingMetricSink::new(io::ErrorKind::Other);
+        primary.fail_always();
+        let (rx, secondary) = SpyMetricSink::new();
+        let sink = FailoverMetricSink::builder()
+            .with_failure_threshold(2)
+            .with_probe_interval(Duration::from_secs(3600))
+            .build(primary.clone(), secondary);
+
+        assert_eq!(7, sink.emit("foo:1|c").unwrap());
+        assert!(!sink.is_failed_over());
+        assert_eq!(7, sink.emit("foo:2|c").unwrap());
+        assert!(sink.is_failed_over());
+        assert_eq!(7, sink.emit("foo:3|c").unwrap());
+
+        // The primary isn't written to again until the probe interval passes
+        assert_eq!(2, primary.attempts());
+        assert_eq!(b"foo:1|c".to_vec(), rx.try_recv().unwrap());
+        assert_eq!(b"foo:2|c".to_vec(), rx.try_recv().unwrap());
+        assert_eq!(b"foo:3|c".to_vec(), rx.try_recv().unwrap());
+    }
+
+    #[test]
+    fn test_failover_metric_sink_recovers() {
+        let primary = FailingMetricSink::new(io::ErrorKind::Other);
+        primary.fail_always();
+        let (rx, secondary) = SpyMetricSink::new();
+        let sink = FailoverMetricSink::builder()
+            .with_failure_threshold(1)
+            .with_probe_interval(Duration::from_secs(0))
+            .build(primary.clone(), secondary);
+
+        assert_eq!(7, sink.emit("foo:1|c").unwrap());
+        assert!(sink.is_failed_over());
+
+        primary.recover();
+        assert_eq!(7, sink.emit("foo:2|c").unwrap());
+        assert!(!sink.is_failed_over());
+
+        assert_eq!(2, primary.attempts());
+        assert_eq!(b"foo:1|c".to_vec(), rx.try_recv().unwrap());
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,343 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::sinks::core::{MetricSink, SinkStats};
+
+/// Rule for matching the name of a metric
+#[derive(Debug, Clone)]
+enum Rule {
+    Prefix(String),
+    Glob(String),
+    #[cfg(feature = "regex")]
+    Regex(regex::Regex),
+}
+
+impl Rule {
+    fn matches(&self, name: &str) -> bool {
+        match self {
+            Rule::Prefix(prefix) => name.starts_with(prefix.as_str()),
+            Rule::Glob(pattern) => glob_matches(pattern.as_bytes(), name.as_bytes()),
+            #[cfg(feature = "regex")]
+            Rule::Regex(re) => re.is_match(name),
+        }
+    }
+}
+
+/// Return true if the name matches the glob pattern where `*` matches any
+/// number of characters (including none) and `?` matches a single character.
+fn glob_matches(pattern: &[u8], name: &[u8]) -> bool {
+    let (mut p, mut n) = (0, 0);
+    // Position of the last `*` in the pattern and the position in the name
+    // it started matching at, to backtrack to when a match fails.
+    let mut star: Option<(usize, usize)> = None;
+
+    while n < name.len() {
+        if p < pattern.len() && (pattern[p] == b'?' || pattern[p] == name[n]) {
+            p += 1;
+            n += 1;
+        } else if p < pattern.len() && pattern[p] == b'*' {
+            star = Some((p, n));
+            p += 1;
+        } else if let Some((sp, sn)) = star {
+            // Let the last `*` match one more character and try again
+            star = Some((sp, sn + 1));
+            p = sp + 1;
+            n = sn + 1;
+        } else {
+            return false;
+        }
+    }
+
+    pattern[p..].iter().all(|&c| c == b'*')
+}
+
+/// Implementation of a builder pattern for `FilteringMetricSink`.
+///
+/// Rules are added to allow or deny metrics based on their name. A metric
+/// is denied if its name matches any deny rule. Otherwise, if there are any
+/// allow rules, it is only allowed if its name matches one of them.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::net::UdpSocket;
+/// use cadence::{FilteringMetricSinkBuilder, MetricSink, UdpMetricSink, DEFAULT_PORT};
+///
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let udp = UdpMetricSink::from(("localhost", DEFAULT_PORT), socket).unwrap();
+///
+/// let sink = FilteringMetricSinkBuilder::new()
+///     .deny_prefix("myapp.debug.")
+///     .deny_glob("myapp.*.per_request_*")
+///     .build(udp);
+///
+/// // Dropped without being sent
+/// sink.emit("myapp.debug.cache_size:4|g");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct FilteringMetricSinkBuilder {
+    allow: Vec<Rule>,
+    deny: Vec<Rule>,
+}
+
+impl FilteringMetricSinkBuilder {
+    /// Construct a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allow metrics with names that start with the given prefix.
+    pub fn allow_prefix<T>(mut self, prefix: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.allow.push(Rule::Prefix(prefix.into()));
+        self
+    }
+
+    /// Deny metrics with names that start with the given prefix.
+    pub fn deny_prefix<T>(mut self, prefix: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.deny.push(Rule::Prefix(prefix.into()));
+        self
+    }
+
+    /// Allow metrics with names that match the given glob pattern.
+    ///
+    /// In the pattern, `*` matches any number of characters (including none)
+    /// and `?` matches a single character. The pattern must match the entire
+    /// name of the metric.
+    pub fn allow_glob<T>(mut self, pattern: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.allow.push(Rule::Glob(pattern.into()));
+        self
+    }
+
+    /// Deny metrics with names that match the given glob pattern.
+    ///
+    /// See `allow_glob` for the syntax of the pattern.
+    pub fn deny_glob<T>(mut self, pattern: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.deny.push(Rule::Glob(pattern.into()));
+        self
+    }
+
+    /// Allow metrics with names that match the given regular expression.
+    ///
+    /// Note that the expression can match any part of the name unless it is
+    /// anchored with `^` and `$`. This method is only available when the
+    /// `regex` feature is enabled.
+    #[cfg(feature = "regex")]
+    pub fn allow_regex(mut self, re: regex::Regex) -> Self {
+        self.allow.push(Rule::Regex(re));
+        self
+    }
+
+    /// Deny metrics with names that match the given regular expression.
+    ///
+    /// See `allow_regex` for more information. This method is only available
+    /// when the `regex` feature is enabled.
+    #[cfg(feature = "regex")]
+    pub fn deny_regex(mut self, re: regex::Regex) -> Self {
+        self.deny.push(Rule::Regex(re));
+        self
+    }
+
+    /// Construct a new `FilteringMetricSink` instance wrapping the given sink
+    /// based on the builder configuration.
+    pub fn build<T>(self, sink: T) -> FilteringMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        FilteringMetricSink {
+            sink: Box::new(sink),
+            allow: self.allow,
+            deny: self.deny,
+            filtered: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Implementation of a `MetricSink` that only writes metrics with names
+/// allowed by a set of rules to a wrapped sink.
+///
+/// This can be used to drop noisy or expensive metrics in the client without
+/// changing the code that emits them. Rules match the names of metrics by
+/// prefix, by glob pattern, or by regular expression when the `regex` feature
+/// is enabled. Rules are set using `FilteringMetricSinkBuilder`.
+///
+/// Metrics that are filtered out are not written to the wrapped sink and `0`
+/// is returned from `.emit()` instead of an error. Events and service checks
+/// are never filtered.
+pub struct FilteringMetricSink {
+    sink: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
+    allow: Vec<Rule>,
+    deny: Vec<Rule>,
+    filtered: AtomicU64,
+}
+
+impl FilteringMetricSink {
+    /// Construct a new builder for `FilteringMetricSink`.
+    pub fn builder() -> FilteringMetricSinkBuilder {
+        FilteringMetricSinkBuilder::new()
+    }
+
+    /// Return the number of metrics that have been filtered out and not
+    /// written to the wrapped sink.
+    pub fn filtered(&self) -> u64 {
+        self.filtered.load(Ordering::Relaxed)
+    }
+
+    fn is_allowed(&self, metric: &str) -> bool {
+        // Events and service checks don't have a metric name
+        if metric.starts_with("_e{") || metric.starts_with("_sc|") {
+            return true;
+        }
+
+        let name = match metric.find(':') {
+            Some(i) => &metric[..i],
+            None => metric,
+        };
+
+        if self.deny.iter().any(|r| r.matches(name)) {
+            return false;
+        }
+
+        self.allow.is_empty() || self.allow.iter().any(|r| r.matches(name))
+    }
+}
+
+impl MetricSink for FilteringMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        if !self.is_allowed(metric) {
+            self.filtered.fetch_add(1, Ordering::Relaxed);
+            return Ok(0);
+        }
+
+        self.sink.emit(metric)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.sink.flush()
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.sink.check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats()
+    }
+}
+
+impl fmt::Debug for FilteringMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "FilteringMetricSink {{ allow: {:?}, deny: {:?}, filtered: {} }}",
+            self.allow,
+            self.deny,
+            self.filtered()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_matches, FilteringMetricSink, MetricSink};
+    use crate::sinks::spy::SpyMetricSink;
+
+    fn glob(pattern: &str, name: &str) -> bool {
+        glob_matches(pattern.as_bytes(), name.as_bytes())
+    }
+
+    #[test]
+    fn test_glob_matches() {
+        assert!(glob("foo.bar", "foo.bar"));
+        assert!(glob("foo.*", "foo.bar"));
+        assert!(glob("foo.*", "foo."));
+        assert!(glob("*.bar", "foo.bar"));
+        assert!(glob("f*o*r", "foo.bar"));
+        assert!(glob("foo.ba?", "foo.baz"));
+        assert!(glob("*", ""));
+        assert!(!glob("foo.*", "foo"));
+        assert!(!glob("foo.ba?", "foo.ba"));
+        assert!(!glob("*.baz", "foo.bar"));
+        assert!(!glob("foo", "foo.bar"));
+    }
+
+    #[test]
+    fn test_filtering_metric_sink_deny() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = FilteringMetricSink::builder()
+            .deny_prefix("debug.")
+            .deny_glob("*.per_request")
+            .build(spy);
+
+        assert_eq!(0, sink.emit("debug.cache:1|g").unwrap());
+        assert_eq!(0, sink.emit("api.per_request:1|c").unwrap());
+        assert_eq!(11, sink.emit("api.req:1|c").unwrap());
+
+        assert_eq!(b"api.req:1|c".to_vec(), rx.try_recv().unwrap());
+        assert!(rx.try_recv().is_err());
+        assert_eq!(2, sink.filtered());
+    }
+
+    #[test]
+    fn test_filtering_metric_sink_allow() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = FilteringMetricSink::builder()
+            .allow_prefix("api.")
+            .deny_prefix("api.internal.")
+            .build(spy);
+
+        assert_eq!(0, sink.emit("db.query:1|c").unwrap());
+        assert_eq!(0, sink.emit("api.internal.gc:1|c").unwrap());
+        assert_eq!(11, sink.emit("api.req:1|c").unwrap());
+
+        assert_eq!(b"api.req:1|c".to_vec(), rx.try_recv().unwrap());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_filtering_metric_sink_events() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = FilteringMetricSink::builder().allow_prefix("api.").build(spy);
+
+        sink.emit("_e{5,4}:title|text").unwrap();
+        assert_eq!(b"_e{5,4}:title|text".to_vec(), rx.try_recv().unwrap());
+    }
+
+    #[cfg(feature = "regex")]
+    #[test]
+    fn test_filtering_metric_sink_regex() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = FilteringMetricSink::builder()
+            .deny_regex(regex::Regex::new(r"\.p(50|99)$").unwrap())
+            .build(spy);
+
+        assert_eq!(0, sink.emit("api.latency.p99:1|g").unwrap());
+        assert_eq!(15, sink.emit("api.latency:1|g").unwrap());
+
+        assert_eq!(b"api.latency:1|g".to_vec(), rx.try_recv().unwrap());
+        assert!(rx.try_recv().is_err());
+    }
+}
@@ -0,0 +1,358 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::sample::{self, ThreadLocalRng};
+use crate::sinks::core::{MetricSink, SinkStats};
+
+/// Fault injected by a `FlakyMetricSink` instead of a normal write.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Return an error of the given kind without writing the metric.
+    Error(io::ErrorKind),
+    /// Wait for the given amount of time before writing the metric.
+    Delay(Duration),
+    /// Write at most the given number of bytes of the metric, truncated to
+    /// a character boundary, and return the result of the partial write.
+    PartialWrite(usize),
+}
+
+/// Decides which writes to a `FlakyMetricSink` have a fault injected.
+///
+/// Writes are counted starting at 1 from when the sink is created.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FaultSchedule {
+    /// Inject a fault into every write.
+    Always,
+    /// Never inject a fault.
+    Never,
+    /// Inject a fault into every nth write, e.g. `Every(3)` for the 3rd,
+    /// 6th, 9th, etc. writes.
+    Every(u64),
+    /// Inject a fault into the first n writes and none after that, such as
+    /// to simulate a server that is down when an application starts.
+    FirstN(u64),
+    /// Inject a fault into every write after the first n, such as to
+    /// simulate a server that goes down and never comes back.
+    AfterN(u64),
+    /// Inject a fault into each write with the given probability, between 0
+    /// and 1.
+    Probability(f64),
+}
+
+impl FaultSchedule {
+    fn is_fault(&self, write: u64) -> bool {
+        match *self {
+            FaultSchedule::Always => true,
+            FaultSchedule::Never => false,
+            FaultSchedule::Every(n) => n > 0 && write % n == 0,
+            FaultSchedule::FirstN(n) => write <= n,
+            FaultSchedule::AfterN(n) => write > n,
+            FaultSchedule::Probability(p) => sample::is_sampled(p, &ThreadLocalRng),
+        }
+    }
+}
+
+/// Implementation of a builder pattern for `FlakyMetricSink`.
+///
+/// The builder can be used to set which fault is injected and which writes
+/// it is injected into. By default, every write fails with an error of kind
+/// `Other`.
+///
+/// # Example
+///
+/// ```
+/// use std::io;
+/// use cadence::{Fault, FaultSchedule, FlakyMetricSinkBuilder, MetricSink, NopMetricSink};
+///
+/// let sink = FlakyMetricSinkBuilder::new()
+///     .with_fault(Fault::Error(io::ErrorKind::ConnectionReset))
+///     .with_schedule(FaultSchedule::Every(2))
+///     .build(NopMetricSink);
+///
+/// assert!(sink.emit("foo.counter:1|c").is_ok());
+/// assert!(sink.emit("foo.counter:1|c").is_err());
+/// ```
+#[derive(Debug, Clone)]
+pub struct FlakyMetricSinkBuilder {
+    fault: Fault,
+    schedule: FaultSchedule,
+}
+
+impl FlakyMetricSinkBuilder {
+    /// Construct a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the fault injected into writes.
+    pub fn with_fault(mut self, fault: Fault) -> Self {
+        self.fault = fault;
+        self
+    }
+
+    /// Set which writes have a fault injected.
+    pub fn with_schedule(mut self, schedule: FaultSchedule) -> Self {
+        self.schedule = schedule;
+        self
+    }
+
+    /// Construct a new `FlakyMetricSink` instance wrapping the given sink
+    /// based on the builder configuration.
+    pub fn build<T>(self, sink: T) -> FlakyMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        FlakyMetricSink {
+            sink: Box::new(sink),
+            fault: self.fault,
+            schedule: self.schedule,
+            writes: AtomicU64::new(0),
+            faults: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Default for FlakyMetricSinkBuilder {
+    fn default() -> Self {
+        FlakyMetricSinkBuilder {
+            fault: Fault::Error(io::ErrorKind::Other),
+            schedule: FaultSchedule::Always,
+        }
+    }
+}
+
+/// Implementation of a `MetricSink` that injects faults, such as errors or
+/// delays, into writes to a wrapped sink.
+///
+/// This is not a general purpose sink, rather it's a sink meant for testing
+/// how an application behaves when sending metrics fails: for example, that
+/// its error handler is invoked, or that a `RetryingMetricSink` or the overflow
+/// policy of a `QueuingMetricSink` works the way it expects. A `FaultSchedule`
+/// decides which writes have the fault injected. Other writes and flushes are
+/// passed to the wrapped sink unchanged.
+///
+/// Each metric passed to the sink counts as a write, including each metric of
+/// a batch.
+///
+/// # Example
+///
+/// ```
+/// use cadence::prelude::*;
+/// use cadence::{FaultSchedule, FlakyMetricSink, NopMetricSink, StatsdClient};
+///
+/// let sink = FlakyMetricSink::builder()
+///     .with_schedule(FaultSchedule::FirstN(1))
+///     .build(NopMetricSink);
+/// let client = StatsdClient::from_sink("my.app", sink);
+///
+/// assert!(client.incr("requests").is_err());
+/// assert!(client.incr("requests").is_ok());
+/// ```
+pub struct FlakyMetricSink {
+    sink: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
+    fault: Fault,
+    schedule: FaultSchedule,
+    writes: AtomicU64,
+    faults: AtomicU64,
+}
+
+impl FlakyMetricSink {
+    /// Construct a new builder for `FlakyMetricSink`.
+    pub fn builder() -> FlakyMetricSinkBuilder {
+        FlakyMetricSinkBuilder::new()
+    }
+
+    /// Construct a new `FlakyMetricSink` instance wrapping the given sink
+    /// that fails every write with an error of the given kind.
+    ///
+    /// # Example
+    ///
+    /// ```
+    /// use std::io;
+    /// use cadence::{FlakyMetricSink, MetricSink, NopMetricSink};
+    ///
+    /// let sink = FlakyMetricSink::failing(NopMetricSink, io::ErrorKind::BrokenPipe);
+    /// let err = sink.emit("foo.counter:1|c").unwrap_err();
+    ///
+    /// assert_eq!(io::ErrorKind::BrokenPipe, err.kind());
+    /// ```
+    pub fn failing<T>(sink: T, kind: io::ErrorKind) -> FlakyMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        Self::builder().with_fault(Fault::Error(kind)).build(sink)
+    }
+
+    /// Return the number of writes to this sink since it was created.
+    pub fn writes(&self) -> u64 {
+        self.writes.load(Ordering::Relaxed)
+    }
+
+    /// Return the number of writes that had a fault injected since this
+    /// sink was created.
+    pub fn faults(&self) -> u64 {
+        self.faults.load(Ordering::Relaxed)
+    }
+}
+
+impl MetricSink for FlakyMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let write = self.writes.fetch_add(1, Ordering::Relaxed) + 1;
+        if !self.schedule.is_fault(write) {
+            return self.sink.emit(metric);
+        }
+
+        self.faults.fetch_add(1, Ordering::Relaxed);
+        match self.fault {
+            Fault::Error(kind) => Err(io::Error::new(kind, "injected fault")),
+            Fault::Delay(delay) => {
+                thread::sleep(delay);
+                self.sink.emit(metric)
+            }
+            Fault::PartialWrite(len) => self.sink.emit(truncate(metric, len)),
+        }
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.sink.flush()
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.sink.check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats()
+    }
+}
+
+impl fmt::Debug for FlakyMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "FlakyMetricSink {{ fault: {:?}, schedule: {:?}, writes: {}, faults: {} }}",
+            self.fault,
+            self.schedule,
+            self.writes(),
+            self.faults()
+        )
+    }
+}
+
+fn truncate(metric: &str, len: usize) -> &str {
+    let mut end = len.min(metric.len());
+    while !metric.is_char_boundary(end) {
+        end -= 1;
+    }
+
+    &metric[..end]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{truncate, Fault, FaultSchedule, FlakyMetricSink};
+    use crate::sinks::core::{MetricSink, NopMetricSink};
+    use crate::sinks::spy::RecordingMetricSink;
+    use std::io;
+    use std::time::{Duration, Instant};
+
+    fn new_sink(wrapped: RecordingMetricSink, fault: Fault, schedule: FaultSchedule) -> FlakyMetricSink {
+        FlakyMetricSink::builder()
+            .with_fault(fault)
+            .with_schedule(schedule)
+            .build(wrapped)
+    }
+
+    fn fault_pattern(schedule: FaultSchedule, writes: usize) -> Vec<bool> {
+        let sink = new_sink(RecordingMetricSink::new(), Fault::Error(io::ErrorKind::Other), schedule);
+        (0..writes).map(|_| sink.emit("foo:1|c").is_err()).collect()
+    }
+
+    #[test]
+    fn test_flaky_metric_sink_schedules() {
+        assert_eq!(vec![true, true, true], fault_pattern(FaultSchedule::Always, 3));
+        assert_eq!(vec![false, false, false], fault_pattern(FaultSchedule::Never, 3));
+        assert_eq!(
+            vec![false, false, true, false, false, true],
+            fault_pattern(FaultSchedule::Every(3), 6)
+        );
+        assert_eq!(vec![false, false], fault_pattern(FaultSchedule::Every(0), 2));
+        assert_eq!(vec![true, true, false], fault_pattern(FaultSchedule::FirstN(2), 3));
+        assert_eq!(vec![false, false, true], fault_pattern(FaultSchedule::AfterN(2), 3));
+        assert_eq!(vec![false, false], fault_pattern(FaultSchedule::Probability(0.0), 2));
+        assert_eq!(vec![true, true], fault_pattern(FaultSchedule::Probability(1.0), 2));
+    }
+
+    #[test]
+    fn test_flaky_metric_sink_error() {
+        let wrapped = RecordingMetricSink::new();
+        let sink = new_sink(
+            wrapped.clone(),
+            Fault::Error(io::ErrorKind::BrokenPipe),
+            FaultSchedule::Every(2),
+        );
+
+        sink.emit("foo:1|c").unwrap();
+        let err = sink.emit("foo:2|c").unwrap_err();
+        sink.emit_batch(&["foo:3|c", "foo:4|c"]).unwrap_err();
+
+        assert_eq!(io::ErrorKind::BrokenPipe, err.kind());
+        assert_eq!(vec!["foo:1|c", "foo:3|c"], wrapped.metrics());
+        assert_eq!(4, sink.writes());
+        assert_eq!(2, sink.faults());
+    }
+
+    #[test]
+    fn test_flaky_metric_sink_delay() {
+        let wrapped = RecordingMetricSink::new();
+        let sink = new_sink(
+            wrapped.clone(),
+            Fault::Delay(Duration::from_millis(20)),
+            FaultSchedule::Always,
+        );
+
+        let start = Instant::now();
+        sink.emit("foo:1|c").unwrap();
+
+        assert!(start.elapsed() >= Duration::from_millis(20));
+        assert_eq!(vec!["foo:1|c"], wrapped.metrics());
+    }
+
+    #[test]
+    fn test_flaky_metric_sink_partial_write() {
+        let wrapped = RecordingMetricSink::new();
+        let sink = new_sink(wrapped.clone(), Fault::PartialWrite(4), FaultSchedule::Always);
+
+        assert_eq!(4, sink.emit("foo:1|c").unwrap());
+        assert_eq!(vec!["foo:"], wrapped.metrics());
+    }
+
+    #[test]
+    fn test_flaky_metric_sink_failing() {
+        let sink = FlakyMetricSink::failing(NopMetricSink, io::ErrorKind::TimedOut);
+
+        assert_eq!(io::ErrorKind::TimedOut, sink.emit("foo:1|c").unwrap_err().kind());
+        assert!(sink.flush().is_ok());
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!("foo", truncate("foo", 10));
+        assert_eq!("fo", truncate("foo", 2));
+        assert_eq!("", truncate("é", 1));
+    }
+}
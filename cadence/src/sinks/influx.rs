@@ -0,0 +1,368 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt::{self, Write};
+use std::io;
+use std::panic::RefUnwindSafe;
+
+use crate::builder::{MetricType, MetricValue};
+use crate::parse::ParsedMetric;
+use crate::sinks::core::{MetricSink, SinkStats};
+
+const DEFAULT_FIELD: &str = "value";
+const DEFAULT_TYPE_TAG: &str = "metric_type";
+
+/// Implementation of a builder pattern for `InfluxLineMetricSink`.
+///
+/// By default, the value of each metric is written as the field `value` and
+/// the type of the metric is written as the tag `metric_type`.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::net::UdpSocket;
+/// use cadence::{InfluxLineMetricSinkBuilder, MetricSink, UdpMetricSink};
+///
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let udp = UdpMetricSink::from(("localhost", 8089), socket).unwrap();
+///
+/// let sink = InfluxLineMetricSinkBuilder::new()
+///     .with_field("val")
+///     .with_type_tag("type")
+///     .build(udp);
+///
+/// // Sent as "requests,type=counter,method=GET val=1i"
+/// sink.emit("requests:1|c|#method:GET");
+/// ```
+#[derive(Debug, Clone)]
+pub struct InfluxLineMetricSinkBuilder {
+    field: String,
+    type_tag: Option<String>,
+}
+
+impl InfluxLineMetricSinkBuilder {
+    /// Construct a new builder with the default field and type tag.
+    pub fn new() -> Self {
+        InfluxLineMetricSinkBuilder {
+            field: DEFAULT_FIELD.to_owned(),
+            type_tag: Some(DEFAULT_TYPE_TAG.to_owned()),
+        }
+    }
+
+    /// Set the name of the field the value of each metric is written as.
+    pub fn with_field<T>(mut self, field: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.field = field.into();
+        self
+    }
+
+    /// Set the key of the tag the type of each metric is written as.
+    pub fn with_type_tag<T>(mut self, key: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.type_tag = Some(key.into());
+        self
+    }
+
+    /// Don't write the type of each metric as a tag.
+    pub fn without_type_tag(mut self) -> Self {
+        self.type_tag = None;
+        self
+    }
+
+    /// Construct a new `InfluxLineMetricSink` instance wrapping the given sink
+    /// based on the builder configuration.
+    pub fn build<T>(self, sink: T) -> InfluxLineMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        InfluxLineMetricSink {
+            sink: Box::new(sink),
+            field: self.field,
+            type_tag: self.type_tag,
+        }
+    }
+}
+
+impl Default for InfluxLineMetricSinkBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Implementation of a `MetricSink` that converts metrics to the InfluxDB
+/// line protocol before writing them to a wrapped sink.
+///
+/// This allows metrics to be sent directly to InfluxDB or Telegraf without
+/// a Statsd server translating them. Wrap a `UdpMetricSink` to send metrics
+/// to the UDP listener of InfluxDB or Telegraf, a `TcpMetricSink` to send
+/// them to the TCP socket listener of Telegraf, or an `InfluxHttpMetricSink`
+/// to send them to the HTTP write API of InfluxDB when the `influx-http`
+/// feature is enabled.
+///
+/// Each metric is written as a single point with the name of the metric as the
+/// measurement, key-value tags of the metric and its type as tags, and its
+/// value as a field. Packed values are written as a point for each value,
+/// separated by newlines. Integer values are written as integer fields and
+/// the timestamp, if any, is written in nanoseconds. Otherwise, the server
+/// assigns the timestamp of each point when it's received.
+///
+/// The line protocol has no equivalent of some parts of Statsd metrics so they
+/// are not written: value tags, sample rates, and container IDs. Events and
+/// service checks can't be written at all and result in an `InvalidInput`
+/// error, as do NaN or infinite values and unsigned values larger than the
+/// largest signed 64-bit integer.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{InfluxLineMetricSink, MetricSink, SpyMetricSink};
+///
+/// let (rx, spy) = SpyMetricSink::new();
+/// let sink = InfluxLineMetricSink::from(spy);
+///
+/// sink.emit("my.prefix.requests:1|c|#method:GET").unwrap();
+/// assert_eq!(
+///     b"my.prefix.requests,metric_type=counter,method=GET value=1i".to_vec(),
+///     rx.try_recv().unwrap()
+/// );
+/// ```
+pub struct InfluxLineMetricSink {
+    sink: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
+    field: String,
+    type_tag: Option<String>,
+}
+
+impl InfluxLineMetricSink {
+    /// Construct a new `InfluxLineMetricSink` wrapping the given sink, using
+    /// the default field and type tag.
+    pub fn from<T>(sink: T) -> Self
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        InfluxLineMetricSinkBuilder::new().build(sink)
+    }
+
+    /// Construct a new builder for `InfluxLineMetricSink`.
+    pub fn builder() -> InfluxLineMetricSinkBuilder {
+        InfluxLineMetricSinkBuilder::new()
+    }
+
+    /// Convert a Statsd metric to one or more lines of the line protocol.
+    fn convert(&self, metric: &str) -> io::Result<String> {
+        let parsed = ParsedMetric::parse(metric).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+
+        // The line protocol has no way to represent NaN or infinite numbers.
+        // Unsigned values are written as integer fields, so values too large
+        // for a signed integer would be rejected by the server.
+        let valid = match parsed.value() {
+            MetricValue::Unsigned(v) => *v <= i64::MAX as u64,
+            MetricValue::PackedUnsigned(vals) => vals.iter().all(|v| *v <= i64::MAX as u64),
+            MetricValue::Float(v) => v.is_finite(),
+            MetricValue::PackedFloat(vals) => vals.iter().all(|v| v.is_finite()),
+            MetricValue::Signed(_) | MetricValue::PackedSigned(_) => true,
+        };
+
+        if !valid {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "metric values must be finite and fit in a signed integer to be written as line protocol",
+            ));
+        }
+
+        let mut out = String::with_capacity(metric.len() * 2);
+
+        // Writing to a `String` can't fail
+        let _ = match parsed.value() {
+            MetricValue::Signed(v) => self.write_point(&mut out, &parsed, |o| write!(o, "{}i", v)),
+            MetricValue::Unsigned(v) => self.write_point(&mut out, &parsed, |o| write!(o, "{}i", v)),
+            MetricValue::Float(v) => self.write_point(&mut out, &parsed, |o| write!(o, "{}", v)),
+            MetricValue::PackedSigned(vals) => vals
+                .iter()
+                .try_for_each(|v| self.write_point(&mut out, &parsed, |o| write!(o, "{}i", v))),
+            MetricValue::PackedUnsigned(vals) => vals
+                .iter()
+                .try_for_each(|v| self.write_point(&mut out, &parsed, |o| write!(o, "{}i", v))),
+            MetricValue::PackedFloat(vals) => vals
+                .iter()
+                .try_for_each(|v| self.write_point(&mut out, &parsed, |o| write!(o, "{}", v))),
+        };
+
+        Ok(out)
+    }
+
+    // Write a single point for the metric with a value written by the given
+    // function, preceded by a newline if it isn't the first.
+    fn write_point<F>(&self, out: &mut String, metric: &ParsedMetric<'_>, value: F) -> fmt::Result
+    where
+        F: FnOnce(&mut String) -> fmt::Result,
+    {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+
+        write_escaped(out, metric.name(), &[',', ' ']);
+        if let Some(key) = &self.type_tag {
+            write_tag(out, key, type_name(metric.metric_type()));
+        }
+
+        for (key, val) in metric.tags() {
+            if let Some(key) = key {
+                write_tag(out, key, val);
+            }
+        }
+
+        out.push(' ');
+        write_escaped(out, &self.field, &[',', '=', ' ']);
+        out.push('=');
+        value(out)?;
+
+        if let Some(ts) = metric.timestamp() {
+            write!(out, " {}", u128::from(ts) * 1_000_000_000)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl MetricSink for InfluxLineMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        self.sink.emit(&self.convert(metric)?)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.sink.flush()
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.sink.check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats()
+    }
+}
+
+impl fmt::Debug for InfluxLineMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "InfluxLineMetricSink {{ field: {:?}, type_tag: {:?} }}",
+            self.field, self.type_tag
+        )
+    }
+}
+
+fn type_name(type_: MetricType<'_>) -> &str {
+    match type_ {
+        MetricType::Counter => "counter",
+        MetricType::Timer => "timer",
+        MetricType::Gauge => "gauge",
+        MetricType::Meter => "meter",
+        MetricType::Histogram => "histogram",
+        MetricType::Set => "set",
+        MetricType::Distribution => "distribution",
+        MetricType::Custom(t) => t,
+    }
+}
+
+// Tags with empty keys or values aren't allowed by the line protocol
+fn write_tag(out: &mut String, key: &str, value: &str) {
+    if key.is_empty() || value.is_empty() {
+        return;
+    }
+
+    out.push(',');
+    write_escaped(out, key, &[',', '=', ' ']);
+    out.push('=');
+    write_escaped(out, value, &[',', '=', ' ']);
+}
+
+fn write_escaped(out: &mut String, val: &str, special: &[char]) {
+    for c in val.chars() {
+        if special.contains(&c) {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InfluxLineMetricSink, MetricSink};
+    use crate::sinks::spy::{RecordingMetricSink, SpyMetricSink};
+    use std::io;
+
+    fn convert(metric: &str) -> String {
+        let sink = RecordingMetricSink::new();
+        InfluxLineMetricSink::from(sink.clone()).emit(metric).unwrap();
+        sink.metrics().remove(0)
+    }
+
+    #[test]
+    fn test_influx_line_metric_sink_types() {
+        assert_eq!(
+            "some.counter,metric_type=counter value=-3i",
+            convert("some.counter:-3|c")
+        );
+        assert_eq!(
+            "some.timer,metric_type=timer value=12i",
+            convert("some.timer:12|ms|@0.5")
+        );
+        assert_eq!("some.gauge,metric_type=gauge value=1.5", convert("some.gauge:1.5|g"));
+        assert_eq!("some.set,metric_type=set value=44i", convert("some.set:44|s"));
+        assert_eq!("some.ratio,metric_type=pct value=0.75", convert("some.ratio:0.75|pct"));
+    }
+
+    #[test]
+    fn test_influx_line_metric_sink_tags_and_timestamp() {
+        assert_eq!(
+            r"http\ requests,metric_type=counter,route=/a\ b,x\=y=1 value=1i 1234567890000000000",
+            convert("http requests:1|c|#route:/a b,beta,x=y:1,empty:|T1234567890")
+        );
+    }
+
+    #[test]
+    fn test_influx_line_metric_sink_packed() {
+        assert_eq!(
+            "some.histogram,metric_type=histogram value=1i\nsome.histogram,metric_type=histogram value=2i",
+            convert("some.histogram:1:2|h")
+        );
+    }
+
+    #[test]
+    fn test_influx_line_metric_sink_builder() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = InfluxLineMetricSink::builder()
+            .with_field("val")
+            .without_type_tag()
+            .build(spy);
+
+        sink.emit("some.counter:1|c|#host:web01").unwrap();
+        assert_eq!(b"some.counter,host=web01 val=1i".to_vec(), rx.try_recv().unwrap());
+    }
+
+    #[test]
+    fn test_influx_line_metric_sink_invalid() {
+        let sink = RecordingMetricSink::new();
+        let influx = InfluxLineMetricSink::from(sink.clone());
+
+        let err = influx.emit("_e{5,4}:title|text").unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        assert!(influx.emit("not a metric").is_err());
+        assert!(influx.emit("some.gauge:NaN|g").is_err());
+        assert!(influx.emit("some.gauge:1.5:inf|g").is_err());
+        assert!(influx.emit("some.counter:18446744073709551615|c").is_err());
+        assert!(sink.metrics().is_empty());
+    }
+}
@@ -0,0 +1,404 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::io;
+use std::mem;
+use std::panic::AssertUnwindSafe;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::sinks::core::{MetricSink, SinkStats, SocketStats};
+
+const DEFAULT_BATCH_SIZE: usize = 500;
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Implementation of a builder pattern for `InfluxHttpMetricSink`.
+///
+/// The builder requires the full URL of the write endpoint of the InfluxDB
+/// HTTP API, including the query parameters that select where points are
+/// written, such as `/api/v2/write?org=my-org&bucket=my-bucket` for InfluxDB
+/// 2.x or `/write?db=my-db` for InfluxDB 1.x. It can also be used to set the
+/// token used to authenticate, how many lines are sent in each request, how
+/// long lines are batched before being sent, and how long to wait for each
+/// request.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use cadence::{InfluxHttpMetricSinkBuilder, InfluxLineMetricSink, MetricSink};
+///
+/// let http = InfluxHttpMetricSinkBuilder::new("http://localhost:8086/api/v2/write?org=my-org&bucket=metrics")
+///     .with_token("my-token")
+///     .with_batch_size(100)
+///     .with_flush_interval(Duration::from_secs(10))
+///     .build();
+///
+/// let sink = InfluxLineMetricSink::from(http);
+/// sink.emit("foo.counter:4|c");
+/// ```
+#[derive(Clone)]
+pub struct InfluxHttpMetricSinkBuilder {
+    url: String,
+    token: Option<String>,
+    batch_size: usize,
+    flush_interval: Option<Duration>,
+    timeout: Duration,
+}
+
+impl InfluxHttpMetricSinkBuilder {
+    /// Construct a new builder that sends lines to the given write URL
+    /// without authentication.
+    pub fn new<T>(url: T) -> Self
+    where
+        T: Into<String>,
+    {
+        InfluxHttpMetricSinkBuilder {
+            url: url.into(),
+            token: None,
+            batch_size: DEFAULT_BATCH_SIZE,
+            flush_interval: None,
+            timeout: DEFAULT_TIMEOUT,
+        }
+    }
+
+    /// Set the API token sent with each request, as `Authorization: Token <token>`.
+    pub fn with_token<T>(mut self, token: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.token = Some(token.into());
+        self
+    }
+
+    /// Set the number of metrics to batch before sending them in a single
+    /// request. The default is 500.
+    ///
+    /// # Panics
+    ///
+    /// This method will panic if the batch size is zero.
+    pub fn with_batch_size(mut self, size: usize) -> Self {
+        assert!(size > 0, "batch size must be greater than zero");
+        self.batch_size = size;
+        self
+    }
+
+    /// Send batched metrics when a metric is emitted after the first metric
+    /// in the batch has been waiting for at least `interval`, even if the
+    /// batch isn't full.
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = Some(interval);
+        self
+    }
+
+    /// Set how long to wait for each request to complete. The default is ten
+    /// seconds.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Construct a new `InfluxHttpMetricSink` based on the builder
+    /// configuration.
+    pub fn build(self) -> InfluxHttpMetricSink {
+        InfluxHttpMetricSink {
+            agent: AssertUnwindSafe(ureq::AgentBuilder::new().timeout(self.timeout).build()),
+            url: self.url,
+            token: self.token,
+            batch_size: self.batch_size,
+            flush_interval: self.flush_interval,
+            batch: Mutex::new(Batch {
+                lines: Vec::new(),
+                started_at: None,
+            }),
+            stats: SocketStats::default(),
+        }
+    }
+}
+
+// The token is a secret so only whether one is set is included in the debug
+// output.
+impl fmt::Debug for InfluxHttpMetricSinkBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("InfluxHttpMetricSinkBuilder")
+            .field("url", &self.url)
+            .field("token", &self.token.as_ref().map(|_| "..."))
+            .field("batch_size", &self.batch_size)
+            .field("flush_interval", &self.flush_interval)
+            .field("timeout", &self.timeout)
+            .finish()
+    }
+}
+
+/// Lines that haven't been sent yet
+#[derive(Debug)]
+struct Batch {
+    lines: Vec<String>,
+    started_at: Option<Instant>,
+}
+
+/// Implementation of a `MetricSink` that sends metrics already converted to
+/// the InfluxDB line protocol to the HTTP write API of InfluxDB.
+///
+/// This sink doesn't convert metrics itself and is meant to be wrapped by an
+/// `InfluxLineMetricSink`, which does. Metrics are batched and sent in a single
+/// request, one line per point, once the batch is full, when the flush interval
+/// has elapsed, when the sink is flushed, or when it's dropped. Requests are
+/// made in the thread that emits the metric that completes a batch so this sink
+/// should usually be wrapped in a `QueuingMetricSink` as well. Timestamps are
+/// written in nanoseconds, the default precision of the write API.
+///
+/// If a request fails, the metrics in it are dropped and the error is
+/// returned to the caller that triggered the request.
+///
+/// This sink is only available when the `influx-http` feature is enabled,
+/// which requires Rust 1.71 or newer.
+///
+/// # Example
+///
+/// ```no_run
+/// use cadence::prelude::*;
+/// use cadence::{InfluxHttpMetricSink, InfluxLineMetricSink, QueuingMetricSink, StatsdClient};
+///
+/// let http = InfluxHttpMetricSink::builder("http://localhost:8086/write?db=metrics").build();
+/// let queuing = QueuingMetricSink::from(InfluxLineMetricSink::from(http));
+/// let client = StatsdClient::from_sink("my.prefix", queuing);
+///
+/// client.count_with_tags("requests", 1).with_tag("method", "GET").send();
+/// ```
+pub struct InfluxHttpMetricSink {
+    // The agent only holds a pool of idle connections which is safe to keep
+    // using if a request panics, allowing this sink to be used with other
+    // sinks such as `QueuingMetricSink` that require `RefUnwindSafe`.
+    agent: AssertUnwindSafe<ureq::Agent>,
+    url: String,
+    token: Option<String>,
+    batch_size: usize,
+    flush_interval: Option<Duration>,
+    batch: Mutex<Batch>,
+    stats: SocketStats,
+}
+
+impl InfluxHttpMetricSink {
+    /// Construct a new builder for `InfluxHttpMetricSink` that sends lines to
+    /// the given write URL.
+    pub fn builder<T>(url: T) -> InfluxHttpMetricSinkBuilder
+    where
+        T: Into<String>,
+    {
+        InfluxHttpMetricSinkBuilder::new(url)
+    }
+
+    // Add lines to the batch, returning the whole batch if it should be
+    // sent once the lock is released.
+    fn add_lines(&self, lines: &str) -> Option<Vec<String>> {
+        let mut batch = self.batch.lock().unwrap();
+        let now = Instant::now();
+        let started_at = *batch.started_at.get_or_insert(now);
+        batch.lines.push(lines.to_owned());
+
+        let expired = self
+            .flush_interval
+            .map(|interval| now.duration_since(started_at) >= interval)
+            .unwrap_or(false);
+
+        if batch.lines.len() >= self.batch_size || expired {
+            Some(take_lines(&mut batch))
+        } else {
+            None
+        }
+    }
+
+    fn send(&self, lines: &[String]) -> io::Result<usize> {
+        let body = lines.join("\n");
+        let mut req = self
+            .agent
+            .post(&self.url)
+            .set("Content-Type", "text/plain; charset=utf-8");
+        if let Some(token) = &self.token {
+            req = req.set("Authorization", &format!("Token {}", token));
+        }
+
+        let res = req
+            .send_string(&body)
+            .map(|_| body.len())
+            .map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()));
+
+        self.stats.update(res, body.len())
+    }
+}
+
+impl MetricSink for InfluxHttpMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        if let Some(batch) = self.add_lines(metric) {
+            self.send(&batch)?;
+        }
+
+        Ok(metric.len())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let batch = take_lines(&mut self.batch.lock().unwrap());
+        if !batch.is_empty() {
+            self.send(&batch)?;
+        }
+
+        Ok(())
+    }
+
+    fn stats(&self) -> SinkStats {
+        (&self.stats).into()
+    }
+}
+
+impl Drop for InfluxHttpMetricSink {
+    fn drop(&mut self) {
+        let _r = self.flush();
+    }
+}
+
+impl fmt::Debug for InfluxHttpMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "InfluxHttpMetricSink {{ url: {:?}, batch_size: {}, flush_interval: {:?} }}",
+            self.url, self.batch_size, self.flush_interval
+        )
+    }
+}
+
+fn take_lines(batch: &mut Batch) -> Vec<String> {
+    batch.started_at = None;
+    mem::take(&mut batch.lines)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InfluxHttpMetricSink, MetricSink};
+    use crate::sinks::influx::InfluxLineMetricSink;
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::TcpListener;
+    use std::sync::mpsc::{self, Receiver};
+    use std::thread;
+
+    // Start an HTTP server that responds to each request with the given status
+    // and sends the authorization header and body of the request to the returned
+    // channel.
+    fn server(status: &'static str) -> (String, Receiver<(String, String)>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/write?db=metrics", listener.local_addr().unwrap());
+        let (tx, rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            for stream in listener.incoming() {
+                let mut stream = stream.unwrap();
+                let mut reader = BufReader::new(stream.try_clone().unwrap());
+                let mut auth = String::new();
+                let mut length = 0;
+
+                loop {
+                    let mut line = String::new();
+                    reader.read_line(&mut line).unwrap();
+                    let line = line.trim_end();
+                    if line.is_empty() {
+                        break;
+                    }
+
+                    let (name, value) = line.split_once(": ").unwrap_or((line, ""));
+                    if name.eq_ignore_ascii_case("authorization") {
+                        auth = value.to_owned();
+                    } else if name.eq_ignore_ascii_case("content-length") {
+                        length = value.parse().unwrap();
+                    }
+                }
+
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                let response = format!("HTTP/1.1 {}\r\nContent-Length: 0\r\nConnection: close\r\n\r\n", status);
+                stream.write_all(response.as_bytes()).unwrap();
+
+                if tx.send((auth, String::from_utf8(body).unwrap())).is_err() {
+                    break;
+                }
+            }
+        });
+
+        (url, rx)
+    }
+
+    #[test]
+    fn test_influx_http_metric_sink_batch() {
+        let (url, rx) = server("204 No Content");
+        let http = InfluxHttpMetricSink::builder(url)
+            .with_token("some-token")
+            .with_batch_size(2)
+            .build();
+        let sink = InfluxLineMetricSink::from(http);
+
+        sink.emit("some.counter:1|c|T1700000000").unwrap();
+        assert!(rx.try_recv().is_err());
+        sink.emit("some.histogram:1:2|h").unwrap();
+
+        let (auth, body) = rx.recv().unwrap();
+        assert_eq!("Token some-token", auth);
+        assert_eq!(
+            concat!(
+                "some.counter,metric_type=counter value=1i 1700000000000000000\n",
+                "some.histogram,metric_type=histogram value=1i\n",
+                "some.histogram,metric_type=histogram value=2i",
+            ),
+            body
+        );
+
+        let stats = sink.stats();
+        assert_eq!(1, stats.packets_sent);
+        assert_eq!(body.len() as u64, stats.bytes_sent);
+    }
+
+    #[test]
+    fn test_influx_http_metric_sink_builder_debug_redacts_token() {
+        let builder = InfluxHttpMetricSink::builder("http://localhost:8086/api/v2/write").with_token("some-token");
+        let debug = format!("{:?}", builder);
+        assert!(!debug.contains("some-token"));
+        assert!(debug.contains("token: Some(\"...\")"));
+    }
+
+    #[test]
+    fn test_influx_http_metric_sink_flush() {
+        let (url, rx) = server("204 No Content");
+        let sink = InfluxHttpMetricSink::builder(url).build();
+
+        sink.emit("some.gauge value=5").unwrap();
+        sink.flush().unwrap();
+        sink.flush().unwrap();
+
+        let (auth, body) = rx.recv().unwrap();
+        assert_eq!("", auth);
+        assert_eq!("some.gauge value=5", body);
+        assert_eq!(1, sink.stats().packets_sent);
+    }
+
+    #[test]
+    fn test_influx_http_metric_sink_error() {
+        let (url, rx) = server("401 Unauthorized");
+        let sink = InfluxHttpMetricSink::builder(url)
+            .with_token("bad-token")
+            .with_batch_size(1)
+            .build();
+
+        assert!(sink.emit("some.gauge value=5").is_err());
+        assert!(rx.recv().is_ok());
+
+        let stats = sink.stats();
+        assert_eq!(0, stats.packets_sent);
+        assert_eq!(1, stats.packets_dropped);
+    }
+}
@@ -0,0 +1,350 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::sinks::core::{MetricSink, SinkStats};
+
+const DEFAULT_PREFIX: &str = "cadence.internal";
+const DEFAULT_INTERVAL: Duration = Duration::from_secs(10);
+
+type GaugeFn = Box<dyn Fn() -> u64 + Sync + Send + RefUnwindSafe + 'static>;
+
+/// Implementation of a builder pattern for `InstrumentedMetricSink`.
+///
+/// The builder can be used to set the prefix of the metrics the sink emits
+/// about itself, how often they are emitted, and extra gauges to emit along
+/// with them.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::net::UdpSocket;
+/// use std::time::Duration;
+/// use cadence::{BufferedUdpMetricSink, InstrumentedMetricSinkBuilder, QueuingMetricSink, DEFAULT_PORT};
+///
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let udp = BufferedUdpMetricSink::from(("localhost", DEFAULT_PORT), socket).unwrap();
+/// let queuing = QueuingMetricSink::from(udp);
+/// let queued = queuing.clone();
+///
+/// let sink = InstrumentedMetricSinkBuilder::new()
+///     .with_prefix("myapp.statsd")
+///     .with_interval(Duration::from_secs(30))
+///     .with_gauge("queue_depth", move || queued.queued())
+///     .build(queuing);
+/// ```
+pub struct InstrumentedMetricSinkBuilder {
+    prefix: String,
+    interval: Duration,
+    gauges: Vec<(String, GaugeFn)>,
+}
+
+impl InstrumentedMetricSinkBuilder {
+    /// Construct a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the prefix for the metrics emitted about the wrapped sink.
+    ///
+    /// The default prefix is `cadence.internal`. A trailing period is
+    /// removed from the prefix if present.
+    pub fn with_prefix<T>(mut self, prefix: T) -> Self
+    where
+        T: AsRef<str>,
+    {
+        self.prefix = prefix.as_ref().trim_end_matches('.').to_string();
+        self
+    }
+
+    /// Set the minimum amount of time between emitting metrics about the
+    /// wrapped sink.
+    ///
+    /// The default interval is 10 seconds.
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.interval = interval;
+        self
+    }
+
+    /// Add a gauge, such as the depth of a queue, to emit along with the
+    /// metrics about the wrapped sink. The function is called each time
+    /// metrics about the wrapped sink are emitted.
+    pub fn with_gauge<T, F>(mut self, name: T, gauge: F) -> Self
+    where
+        T: Into<String>,
+        F: Fn() -> u64 + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.gauges.push((name.into(), Box::new(gauge)));
+        self
+    }
+
+    /// Construct a new `InstrumentedMetricSink` instance wrapping the given
+    /// sink based on the builder configuration.
+    pub fn build<T>(self, sink: T) -> InstrumentedMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        InstrumentedMetricSink {
+            sink: Box::new(sink),
+            prefix: self.prefix,
+            interval: self.interval,
+            gauges: self.gauges,
+            flushes: AtomicU64::new(0),
+            state: Mutex::new(ReportState {
+                report_at: Instant::now() + self.interval,
+                last: SinkStats::default(),
+                flushes: 0,
+            }),
+        }
+    }
+}
+
+impl Default for InstrumentedMetricSinkBuilder {
+    fn default() -> Self {
+        InstrumentedMetricSinkBuilder {
+            prefix: DEFAULT_PREFIX.to_string(),
+            interval: DEFAULT_INTERVAL,
+            gauges: Vec::new(),
+        }
+    }
+}
+
+impl fmt::Debug for InstrumentedMetricSinkBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "InstrumentedMetricSinkBuilder {{ prefix: {:?}, interval: {:?}, gauges: {} }}",
+            self.prefix,
+            self.interval,
+            self.gauges.len()
+        )
+    }
+}
+
+/// When to emit metrics next and the values emitted last time
+#[derive(Debug)]
+struct ReportState {
+    report_at: Instant,
+    last: SinkStats,
+    flushes: u64,
+}
+
+/// Implementation of a `MetricSink` that periodically emits metrics about
+/// the sink it wraps using that same sink.
+///
+/// Every interval (10 seconds by default) the following counters are emitted
+/// to the wrapped sink, prefixed with `cadence.internal.` by default:
+///
+/// * `bytes_sent` and `packets_sent`: Data successfully written by the sink.
+/// * `bytes_dropped` and `packets_dropped`: Data the sink failed to write.
+/// * `flushes`: Number of times the sink has been flushed.
+///
+/// Each counter is the change since the previous time metrics were emitted
+/// and counters that haven't changed are not emitted. Extra gauges such as the
+/// depth of a queue can be added using `InstrumentedMetricSinkBuilder`.
+///
+/// Metrics are emitted after a metric is emitted or the sink is flushed once
+/// the interval has passed, in the thread of the caller. A background thread
+/// is not used, so metrics won't be emitted while nothing is being written
+/// to this sink. Errors emitting these metrics are ignored.
+pub struct InstrumentedMetricSink {
+    sink: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
+    prefix: String,
+    interval: Duration,
+    gauges: Vec<(String, GaugeFn)>,
+    flushes: AtomicU64,
+    state: Mutex<ReportState>,
+}
+
+impl InstrumentedMetricSink {
+    /// Construct a new builder for `InstrumentedMetricSink`.
+    pub fn builder() -> InstrumentedMetricSinkBuilder {
+        InstrumentedMetricSinkBuilder::new()
+    }
+
+    /// Construct a new `InstrumentedMetricSink` instance wrapping the given
+    /// sink with the default prefix and interval.
+    pub fn from<T>(sink: T) -> InstrumentedMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        Self::builder().build(sink)
+    }
+
+    /// Emit metrics about the wrapped sink if the interval has passed
+    fn report(&self, now: Instant) {
+        let mut state = match self.state.try_lock() {
+            Ok(state) => state,
+            // Another thread is already emitting metrics
+            Err(_) => return,
+        };
+
+        if now < state.report_at {
+            return;
+        }
+
+        let stats = self.sink.stats();
+        let flushes = self.flushes.load(Ordering::Relaxed);
+        let counters = [
+            ("bytes_sent", stats.bytes_sent.saturating_sub(state.last.bytes_sent)),
+            (
+                "packets_sent",
+                stats.packets_sent.saturating_sub(state.last.packets_sent),
+            ),
+            (
+                "bytes_dropped",
+                stats.bytes_dropped.saturating_sub(state.last.bytes_dropped),
+            ),
+            (
+                "packets_dropped",
+                stats.packets_dropped.saturating_sub(state.last.packets_dropped),
+            ),
+            ("flushes", flushes.saturating_sub(state.flushes)),
+        ];
+
+        for (name, value) in counters.iter() {
+            if *value > 0 {
+                let _ = self.sink.emit(&format!("{}.{}:{}|c", self.prefix, name, value));
+            }
+        }
+
+        for (name, gauge) in self.gauges.iter() {
+            let _ = self.sink.emit(&format!("{}.{}:{}|g", self.prefix, name, gauge()));
+        }
+
+        state.report_at = now + self.interval;
+        state.last = stats;
+        state.flushes = flushes;
+    }
+}
+
+impl MetricSink for InstrumentedMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let res = self.sink.emit(metric);
+        self.report(Instant::now());
+        res
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.flushes.fetch_add(1, Ordering::Relaxed);
+        self.report(Instant::now());
+        self.sink.flush()
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.sink.check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats()
+    }
+}
+
+impl fmt::Debug for InstrumentedMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "InstrumentedMetricSink {{ prefix: {:?}, interval: {:?}, gauges: {} }}",
+            self.prefix,
+            self.interval,
+            self.gauges.len()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{InstrumentedMetricSink, MetricSink, SinkStats};
+    use std::io;
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    // Sink that records every metric and counts them as sent
+    #[derive(Clone, Default)]
+    struct RecordingSink {
+        metrics: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl RecordingSink {
+        fn take(&self) -> Vec<String> {
+            self.metrics.lock().unwrap().drain(..).collect()
+        }
+    }
+
+    impl MetricSink for RecordingSink {
+        fn emit(&self, metric: &str) -> io::Result<usize> {
+            self.metrics.lock().unwrap().push(metric.to_string());
+            Ok(metric.len())
+        }
+
+        fn stats(&self) -> SinkStats {
+            // Only count metrics that haven't been taken yet to keep the numbers small
+            let metrics = self.metrics.lock().unwrap();
+            SinkStats {
+                bytes_sent: metrics.iter().map(|m| m.len() as u64).sum(),
+                packets_sent: metrics.len() as u64,
+                ..SinkStats::default()
+            }
+        }
+    }
+
+    #[test]
+    fn test_instrumented_metric_sink_waits_for_interval() {
+        let wrapped = RecordingSink::default();
+        let sink = InstrumentedMetricSink::builder()
+            .with_interval(Duration::from_secs(3600))
+            .build(wrapped.clone());
+
+        sink.emit("foo:1|c").unwrap();
+        sink.flush().unwrap();
+
+        assert_eq!(vec!["foo:1|c"], wrapped.take());
+    }
+
+    #[test]
+    fn test_instrumented_metric_sink_reports() {
+        let wrapped = RecordingSink::default();
+        let sink = InstrumentedMetricSink::builder()
+            .with_prefix("myapp.statsd.")
+            .with_interval(Duration::from_secs(0))
+            .with_gauge("queue_depth", || 42)
+            .build(wrapped.clone());
+
+        sink.emit("foo:1|c").unwrap();
+
+        assert_eq!(
+            vec![
+                "foo:1|c",
+                "myapp.statsd.bytes_sent:7|c",
+                "myapp.statsd.packets_sent:1|c",
+                "myapp.statsd.queue_depth:42|g",
+            ],
+            wrapped.take()
+        );
+    }
+
+    #[test]
+    fn test_instrumented_metric_sink_reports_flushes() {
+        let wrapped = RecordingSink::default();
+        let sink = InstrumentedMetricSink::builder()
+            .with_interval(Duration::from_secs(0))
+            .build(wrapped.clone());
+
+        sink.flush().unwrap();
+
+        assert_eq!(vec!["cadence.internal.flushes:1|c"], wrapped.take());
+    }
+}
@@ -8,18 +8,104 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
+mod adaptive;
+mod aggregating;
+mod backoff;
+mod breaker;
 mod core;
+mod dead_letter;
+mod dedup;
+mod emf;
+mod failover;
+mod filtering;
+mod flaky;
+mod influx;
+mod instrumented;
+mod multi;
 mod queuing;
+mod retry;
+mod rewriting;
+mod sharded;
+mod sketch;
+mod spill;
 mod spy;
+mod stream;
+mod tcp;
 mod udp;
+mod url;
+mod wavefront;
 
-pub use crate::sinks::core::{MetricSink, NopMetricSink, SinkStats, SocketStats};
-pub use crate::sinks::queuing::{QueuingMetricSink, QueuingMetricSinkBuilder};
-pub use crate::sinks::spy::{BufferedSpyMetricSink, SpyMetricSink};
-pub use crate::sinks::udp::{BufferedUdpMetricSink, UdpMetricSink};
+pub use crate::sinks::adaptive::{AdaptiveSamplingMetricSink, AdaptiveSamplingMetricSinkBuilder};
+pub use crate::sinks::aggregating::{AggregatingMetricSink, AggregatingMetricSinkBuilder, SketchOutput};
+pub use crate::sinks::backoff::Backoff;
+pub use crate::sinks::breaker::{CircuitBreakerMetricSink, CircuitBreakerMetricSinkBuilder};
+pub use crate::sinks::core::{AsyncMetricSink, MetricSink, NopMetricSink, SinkFuture, SinkStats, SocketStats};
+pub use crate::sinks::dead_letter::DeadLetterMetricSink;
+pub use crate::sinks::dedup::{DeduplicatingMetricSink, DeduplicatingMetricSinkBuilder};
+pub use crate::sinks::emf::EmfMetricSink;
+pub use crate::sinks::failover::{FailoverMetricSink, FailoverMetricSinkBuilder};
+pub use crate::sinks::filtering::{FilteringMetricSink, FilteringMetricSinkBuilder};
+pub use crate::sinks::flaky::{Fault, FaultSchedule, FlakyMetricSink, FlakyMetricSinkBuilder};
+pub use crate::sinks::influx::{InfluxLineMetricSink, InfluxLineMetricSinkBuilder};
+pub use crate::sinks::instrumented::{InstrumentedMetricSink, InstrumentedMetricSinkBuilder};
+pub use crate::sinks::multi::{MultiErrorPolicy, MultiMetricSink, MultiMetricSinkBuilder};
+pub use crate::sinks::queuing::{OverflowPolicy, QueuingMetricSink, QueuingMetricSinkBuilder};
+pub use crate::sinks::retry::{RetryingMetricSink, RetryingMetricSinkBuilder};
+pub use crate::sinks::rewriting::{RewritingMetricSink, RewritingMetricSinkBuilder};
+pub use crate::sinks::sharded::{ShardedMetricSink, ShardedMetricSinkBuilder};
+pub use crate::sinks::spy::{BufferedSpyMetricSink, RecordingMetricSink, SpyMetricSink};
+pub use crate::sinks::stream::DisconnectPolicy;
+pub use crate::sinks::tcp::{BufferedTcpMetricSink, TcpMetricSink, TcpMetricSinkBuilder};
+pub use crate::sinks::udp::{
+    BufferedUdpMetricSink, PacketSize, ResolvePolicy, UdpMetricSink, UdpMetricSinkBuilder, WouldBlockPolicy,
+};
+pub use crate::sinks::url::MetricSinkBuilder;
+pub use crate::sinks::wavefront::{WavefrontMetricSink, WavefrontMetricSinkBuilder};
 
 #[cfg(unix)]
 mod unix;
 
 #[cfg(unix)]
-pub use crate::sinks::unix::{BufferedUnixMetricSink, UnixMetricSink};
+mod unix_stream;
+
+#[cfg(unix)]
+pub use crate::sinks::unix::{BufferedUnixMetricSink, UnixMetricSink, UnixMetricSinkBuilder};
+
+#[cfg(unix)]
+pub use crate::sinks::unix_stream::{BufferedUnixStreamMetricSink, UnixStreamMetricSink, UnixStreamMetricSinkBuilder};
+
+#[cfg(feature = "datadog-http")]
+mod datadog;
+
+#[cfg(feature = "datadog-http")]
+pub use crate::sinks::datadog::{DatadogHttpMetricSink, DatadogHttpMetricSinkBuilder};
+
+#[cfg(feature = "influx-http")]
+mod influx_http;
+
+#[cfg(feature = "influx-http")]
+pub use crate::sinks::influx_http::{InfluxHttpMetricSink, InfluxHttpMetricSinkBuilder};
+
+#[cfg(feature = "opentelemetry")]
+mod otel;
+
+#[cfg(feature = "opentelemetry")]
+pub use crate::sinks::otel::OpenTelemetryMetricSink;
+
+#[cfg(feature = "rustls")]
+mod tls;
+
+#[cfg(feature = "rustls")]
+pub use crate::sinks::tls::{BufferedTlsMetricSink, TlsMetricSink, TlsMetricSinkBuilder};
+
+#[cfg(feature = "tokio")]
+mod tokio_queuing;
+
+#[cfg(feature = "tokio")]
+mod tokio_udp;
+
+#[cfg(feature = "tokio")]
+pub use crate::sinks::tokio_queuing::{TokioQueuingMetricSink, TokioQueuingMetricSinkBuilder};
+
+#[cfg(feature = "tokio")]
+pub use crate::sinks::tokio_udp::TokioUdpMetricSink;
@@ -0,0 +1,268 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::io;
+use std::panic::RefUnwindSafe;
+
+use crate::sinks::core::{MetricSink, SinkStats};
+
+/// What a `MultiMetricSink` should do when one of the sinks it wraps fails.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MultiErrorPolicy {
+    /// Stop at the first sink that fails and return its error. Sinks after
+    /// the one that failed are not written to.
+    FirstError,
+    /// Write to every sink even if some of them fail and then return the
+    /// first error encountered, if any.
+    ///
+    /// This is the default.
+    BestEffort,
+}
+
+impl Default for MultiErrorPolicy {
+    fn default() -> Self {
+        MultiErrorPolicy::BestEffort
+    }
+}
+
+/// Implementation of a builder pattern for `MultiMetricSink`.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::net::UdpSocket;
+/// use cadence::{MetricSink, MultiErrorPolicy, MultiMetricSinkBuilder, UdpMetricSink, DEFAULT_PORT};
+///
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let legacy = UdpMetricSink::from(("statsd.example.com", DEFAULT_PORT), socket).unwrap();
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let agent = UdpMetricSink::from(("localhost", DEFAULT_PORT), socket).unwrap();
+///
+/// let sink = MultiMetricSinkBuilder::new()
+///     .with_sink(legacy)
+///     .with_sink(agent)
+///     .with_error_policy(MultiErrorPolicy::BestEffort)
+///     .build();
+///
+/// sink.emit("foo.counter:4|c");
+/// ```
+#[derive(Default)]
+pub struct MultiMetricSinkBuilder {
+    sinks: Vec<Box<dyn MetricSink + Sync + Send + RefUnwindSafe>>,
+    policy: MultiErrorPolicy,
+}
+
+impl MultiMetricSinkBuilder {
+    /// Construct a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a sink that every metric will be written to. Sinks are written to
+    /// in the order they are added.
+    pub fn with_sink<T>(mut self, sink: T) -> Self
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Set what to do when one of the sinks fails to emit a metric.
+    ///
+    /// By default, every sink is written to and the first error is returned.
+    pub fn with_error_policy(mut self, policy: MultiErrorPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Construct a new `MultiMetricSink` instance based on the builder configuration.
+    pub fn build(self) -> MultiMetricSink {
+        MultiMetricSink {
+            sinks: self.sinks,
+            policy: self.policy,
+        }
+    }
+}
+
+impl fmt::Debug for MultiMetricSinkBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "MultiMetricSinkBuilder {{ sinks: {}, policy: {:?} }}",
+            self.sinks.len(),
+            self.policy
+        )
+    }
+}
+
+/// Implementation of a `MetricSink` that writes every metric to multiple
+/// other sinks.
+///
+/// This is useful for sending metrics to more than one server, for example
+/// writing to both an existing Statsd server and a new Datadog agent while
+/// migrating from one to the other. Sinks are written to in the order they
+/// were added, in the thread of the caller.
+///
+/// What happens when one of the sinks fails is determined by the
+/// `MultiErrorPolicy` set using the `MultiMetricSinkBuilder`. Flushing the
+/// sink flushes every wrapped sink using the same policy. Stats returned by
+/// this sink are the sum of the stats of every wrapped sink.
+pub struct MultiMetricSink {
+    sinks: Vec<Box<dyn MetricSink + Sync + Send + RefUnwindSafe>>,
+    policy: MultiErrorPolicy,
+}
+
+impl MultiMetricSink {
+    /// Construct a new builder for `MultiMetricSink`.
+    pub fn builder() -> MultiMetricSinkBuilder {
+        MultiMetricSinkBuilder::new()
+    }
+
+    /// Call the given function with each sink according to the error policy,
+    /// returning the largest successful result or the first error.
+    fn each<F, R>(&self, f: F) -> io::Result<R>
+    where
+        F: Fn(&dyn MetricSink) -> io::Result<R>,
+        R: Ord + Default,
+    {
+        let mut out = R::default();
+        let mut err = None;
+
+        for sink in self.sinks.iter() {
+            match f(sink.as_ref()) {
+                Ok(v) => out = out.max(v),
+                Err(e) => match self.policy {
+                    MultiErrorPolicy::FirstError => return Err(e),
+                    MultiErrorPolicy::BestEffort => {
+                        err.get_or_insert(e);
+                    }
+                },
+            }
+        }
+
+        match err {
+            Some(e) => Err(e),
+            None => Ok(out),
+        }
+    }
+}
+
+impl MetricSink for MultiMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        self.each(|sink| sink.emit(metric))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.each(|sink| sink.flush())
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.each(|sink| sink.check())
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sinks
+            .iter()
+            .fold(SinkStats::default(), |acc, sink| acc.combine(sink.stats()))
+    }
+}
+
+impl fmt::Debug for MultiMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "MultiMetricSink {{ sinks: {}, policy: {:?} }}",
+            self.sinks.len(),
+            self.policy
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MetricSink, MultiErrorPolicy, MultiMetricSink};
+    use crate::sinks::core::SinkStats;
+    use crate::sinks::spy::SpyMetricSink;
+    use std::io;
+
+    struct ErrorSink;
+
+    impl MetricSink for ErrorSink {
+        fn emit(&self, _metric: &str) -> io::Result<usize> {
+            Err(io::Error::new(io::ErrorKind::Other, "broken"))
+        }
+
+        fn stats(&self) -> SinkStats {
+            SinkStats {
+                bytes_dropped: 3,
+                packets_dropped: 1,
+                ..SinkStats::default()
+            }
+        }
+    }
+
+    #[test]
+    fn test_multi_metric_sink_writes_all() {
+        let (rx1, sink1) = SpyMetricSink::new();
+        let (rx2, sink2) = SpyMetricSink::new();
+        let sink = MultiMetricSink::builder().with_sink(sink1).with_sink(sink2).build();
+
+        assert_eq!(7, sink.emit("foo:1|c").unwrap());
+        assert_eq!(b"foo:1|c".to_vec(), rx1.try_recv().unwrap());
+        assert_eq!(b"foo:1|c".to_vec(), rx2.try_recv().unwrap());
+    }
+
+    #[test]
+    fn test_multi_metric_sink_best_effort() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = MultiMetricSink::builder()
+            .with_sink(ErrorSink)
+            .with_sink(spy)
+            .with_error_policy(MultiErrorPolicy::BestEffort)
+            .build();
+
+        assert!(sink.emit("foo:1|c").is_err());
+        assert_eq!(b"foo:1|c".to_vec(), rx.try_recv().unwrap());
+    }
+
+    #[test]
+    fn test_multi_metric_sink_first_error() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = MultiMetricSink::builder()
+            .with_sink(ErrorSink)
+            .with_sink(spy)
+            .with_error_policy(MultiErrorPolicy::FirstError)
+            .build();
+
+        assert!(sink.emit("foo:1|c").is_err());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_multi_metric_sink_stats() {
+        let sink = MultiMetricSink::builder()
+            .with_sink(ErrorSink)
+            .with_sink(ErrorSink)
+            .build();
+        let stats = sink.stats();
+
+        assert_eq!(6, stats.bytes_dropped);
+        assert_eq!(2, stats.packets_dropped);
+    }
+
+    #[test]
+    fn test_multi_metric_sink_empty() {
+        let sink = MultiMetricSink::builder().build();
+        assert_eq!(0, sink.emit("foo:1|c").unwrap());
+        assert!(sink.flush().is_ok());
+    }
+}
@@ -0,0 +1,309 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::HashMap;
+use std::fmt;
+use std::io;
+use std::panic::AssertUnwindSafe;
+use std::sync::Mutex;
+
+use opentelemetry::metrics::{Counter, Gauge, Histogram, Meter};
+use opentelemetry::KeyValue;
+
+use crate::builder::{MetricType, MetricValue};
+use crate::parse::ParsedMetric;
+use crate::sinks::core::MetricSink;
+
+/// Implementation of a `MetricSink` that records metrics using instruments
+/// from an OpenTelemetry `Meter` instead of sending them to a Statsd server.
+///
+/// This allows code instrumented with a `StatsdClient` to be used in an
+/// application that exports metrics using an OpenTelemetry pipeline, such as
+/// an OTLP exporter, easing migration between the two. An instrument is created
+/// for each metric name the first time it's emitted and reused after that.
+///
+/// Metrics are recorded using instruments based on their type:
+///
+/// * Counters and meters are added to a `Counter`, scaled by their sample rate.
+///   OpenTelemetry counters can only increase so negative values, such as from
+///   `.decr()`, result in an `InvalidInput` error.
+/// * Gauges are recorded using a `Gauge`.
+/// * Timers, histograms, and distributions are recorded using a `Histogram`.
+///   Timers use the unit `ms`.
+///
+/// Key-value tags are recorded as attributes while value tags, timestamps, and
+/// container IDs are not recorded. Sets, custom metric types, events, and
+/// service checks have no OpenTelemetry equivalent and result in an
+/// `InvalidInput` error.
+///
+/// This sink is only available when the `opentelemetry` feature is enabled,
+/// which requires Rust 1.75 or newer.
+///
+/// # Example
+///
+/// ```
+/// use cadence::prelude::*;
+/// use cadence::{OpenTelemetryMetricSink, StatsdClient};
+/// use opentelemetry::global;
+///
+/// // Set up an OpenTelemetry meter provider and exporter here...
+/// let meter = global::meter("my-service");
+/// let sink = OpenTelemetryMetricSink::new(meter);
+/// let client = StatsdClient::from_sink("my.prefix", sink);
+///
+/// client.count_with_tags("requests", 1).with_tag("method", "GET").send();
+/// ```
+pub struct OpenTelemetryMetricSink {
+    // The meter is only used to create instruments which doesn't leave
+    // anything in an inconsistent state if it panics, allowing this sink to
+    // be used with other sinks such as `QueuingMetricSink` that require
+    // `RefUnwindSafe`.
+    meter: AssertUnwindSafe<Meter>,
+    counters: Mutex<HashMap<String, Counter<f64>>>,
+    gauges: Mutex<HashMap<String, Gauge<f64>>>,
+    histograms: Mutex<HashMap<String, Histogram<f64>>>,
+}
+
+impl OpenTelemetryMetricSink {
+    /// Construct a new `OpenTelemetryMetricSink` that records metrics using
+    /// instruments created by the given `Meter`.
+    pub fn new(meter: Meter) -> Self {
+        OpenTelemetryMetricSink {
+            meter: AssertUnwindSafe(meter),
+            counters: Mutex::new(HashMap::new()),
+            gauges: Mutex::new(HashMap::new()),
+            histograms: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn counter(&self, name: &str) -> Counter<f64> {
+        let mut counters = self.counters.lock().unwrap();
+        if let Some(counter) = counters.get(name) {
+            return counter.clone();
+        }
+
+        let counter = self.meter.f64_counter(name.to_owned()).build();
+        counters.insert(name.to_owned(), counter.clone());
+        counter
+    }
+
+    fn gauge(&self, name: &str) -> Gauge<f64> {
+        let mut gauges = self.gauges.lock().unwrap();
+        if let Some(gauge) = gauges.get(name) {
+            return gauge.clone();
+        }
+
+        let gauge = self.meter.f64_gauge(name.to_owned()).build();
+        gauges.insert(name.to_owned(), gauge.clone());
+        gauge
+    }
+
+    fn histogram(&self, name: &str, unit: Option<&'static str>) -> Histogram<f64> {
+        let mut histograms = self.histograms.lock().unwrap();
+        if let Some(histogram) = histograms.get(name) {
+            return histogram.clone();
+        }
+
+        let builder = self.meter.f64_histogram(name.to_owned());
+        let histogram = match unit {
+            Some(unit) => builder.with_unit(unit).build(),
+            None => builder.build(),
+        };
+
+        histograms.insert(name.to_owned(), histogram.clone());
+        histogram
+    }
+}
+
+impl MetricSink for OpenTelemetryMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let parsed = ParsedMetric::parse(metric).map_err(|e| invalid(e.to_string()))?;
+        let values = values(parsed.value());
+        let attributes: Vec<KeyValue> = parsed
+            .tags()
+            .iter()
+            .filter_map(|(k, v)| k.map(|k| KeyValue::new(k.to_owned(), (*v).to_owned())))
+            .collect();
+
+        match parsed.metric_type() {
+            MetricType::Counter | MetricType::Meter => {
+                if values.iter().any(|v| *v < 0.0) {
+                    return Err(invalid("OpenTelemetry counters can't be decremented"));
+                }
+
+                let rate = parsed.sample_rate().filter(|r| *r > 0.0).unwrap_or(1.0);
+                let counter = self.counter(parsed.name());
+                values.iter().for_each(|v| counter.add(v / rate, &attributes));
+            }
+            MetricType::Gauge => {
+                let gauge = self.gauge(parsed.name());
+                values.iter().for_each(|v| gauge.record(*v, &attributes));
+            }
+            MetricType::Timer => {
+                let histogram = self.histogram(parsed.name(), Some("ms"));
+                values.iter().for_each(|v| histogram.record(*v, &attributes));
+            }
+            MetricType::Histogram | MetricType::Distribution => {
+                let histogram = self.histogram(parsed.name(), None);
+                values.iter().for_each(|v| histogram.record(*v, &attributes));
+            }
+            MetricType::Set | MetricType::Custom(_) => {
+                return Err(invalid("metric type has no OpenTelemetry equivalent"));
+            }
+        }
+
+        Ok(metric.len())
+    }
+}
+
+impl fmt::Debug for OpenTelemetryMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OpenTelemetryMetricSink")
+            .field("meter", &*self.meter)
+            .finish()
+    }
+}
+
+fn values(value: &MetricValue) -> Vec<f64> {
+    match value {
+        MetricValue::Signed(v) => vec![*v as f64],
+        MetricValue::Unsigned(v) => vec![*v as f64],
+        MetricValue::Float(v) => vec![*v],
+        MetricValue::PackedSigned(vals) => vals.iter().map(|v| *v as f64).collect(),
+        MetricValue::PackedUnsigned(vals) => vals.iter().map(|v| *v as f64).collect(),
+        MetricValue::PackedFloat(vals) => vals.clone(),
+    }
+}
+
+fn invalid<E>(err: E) -> io::Error
+where
+    E: Into<Box<dyn std::error::Error + Send + Sync>>,
+{
+    io::Error::new(io::ErrorKind::InvalidInput, err)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MetricSink, OpenTelemetryMetricSink};
+    use opentelemetry::metrics::{
+        Counter, Gauge, Histogram, HistogramBuilder, InstrumentBuilder, InstrumentProvider, Meter, SyncInstrument,
+    };
+    use opentelemetry::KeyValue;
+    use std::sync::{Arc, Mutex};
+
+    type Measurements = Arc<Mutex<Vec<String>>>;
+
+    // Instrument that records each measurement as "kind name value attributes"
+    struct RecordingInstrument {
+        description: String,
+        measurements: Measurements,
+    }
+
+    impl SyncInstrument<f64> for RecordingInstrument {
+        fn measure(&self, measurement: f64, attributes: &[KeyValue]) {
+            let attributes: Vec<String> = attributes.iter().map(|kv| format!("{}={}", kv.key, kv.value)).collect();
+
+            self.measurements.lock().unwrap().push(format!(
+                "{} {} [{}]",
+                self.description,
+                measurement,
+                attributes.join(",")
+            ));
+        }
+    }
+
+    #[derive(Default)]
+    struct RecordingProvider {
+        measurements: Measurements,
+        created: Mutex<usize>,
+    }
+
+    impl RecordingProvider {
+        fn instrument(&self, kind: &str, name: &str, unit: Option<&str>) -> Arc<RecordingInstrument> {
+            *self.created.lock().unwrap() += 1;
+            let description = match unit {
+                Some(unit) => format!("{} {} ({})", kind, name, unit),
+                None => format!("{} {}", kind, name),
+            };
+
+            Arc::new(RecordingInstrument {
+                description,
+                measurements: self.measurements.clone(),
+            })
+        }
+    }
+
+    impl InstrumentProvider for RecordingProvider {
+        fn f64_counter(&self, builder: InstrumentBuilder<'_, Counter<f64>>) -> Counter<f64> {
+            Counter::new(self.instrument("counter", &builder.name, builder.unit.as_deref()))
+        }
+
+        fn f64_gauge(&self, builder: InstrumentBuilder<'_, Gauge<f64>>) -> Gauge<f64> {
+            Gauge::new(self.instrument("gauge", &builder.name, builder.unit.as_deref()))
+        }
+
+        fn f64_histogram(&self, builder: HistogramBuilder<'_, Histogram<f64>>) -> Histogram<f64> {
+            Histogram::new(self.instrument("histogram", &builder.name, builder.unit.as_deref()))
+        }
+    }
+
+    fn new_sink() -> (Arc<RecordingProvider>, OpenTelemetryMetricSink) {
+        let provider = Arc::new(RecordingProvider::default());
+        let sink = OpenTelemetryMetricSink::new(Meter::new(provider.clone()));
+        (provider, sink)
+    }
+
+    #[test]
+    fn test_open_telemetry_metric_sink_types() {
+        let (provider, sink) = new_sink();
+
+        sink.emit("some.counter:2|c|@0.5|#method:GET,beta").unwrap();
+        sink.emit("some.meter:1|m").unwrap();
+        sink.emit("some.gauge:3.5|g").unwrap();
+        sink.emit("some.timer:12|ms").unwrap();
+        sink.emit("some.histogram:1:2|h").unwrap();
+        sink.emit("some.distribution:7|d").unwrap();
+
+        assert_eq!(
+            vec![
+                "counter some.counter 4 [method=GET]",
+                "counter some.meter 1 []",
+                "gauge some.gauge 3.5 []",
+                "histogram some.timer (ms) 12 []",
+                "histogram some.histogram 1 []",
+                "histogram some.histogram 2 []",
+                "histogram some.distribution 7 []",
+            ],
+            *provider.measurements.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_open_telemetry_metric_sink_reuses_instruments() {
+        let (provider, sink) = new_sink();
+
+        sink.emit("some.counter:1|c").unwrap();
+        sink.emit("some.counter:1|c|#host:web01").unwrap();
+        sink.emit("other.counter:1|c").unwrap();
+
+        assert_eq!(2, *provider.created.lock().unwrap());
+        assert_eq!(3, provider.measurements.lock().unwrap().len());
+    }
+
+    #[test]
+    fn test_open_telemetry_metric_sink_invalid() {
+        let (provider, sink) = new_sink();
+
+        assert!(sink.emit("some.counter:-1|c").is_err());
+        assert!(sink.emit("some.set:4|s").is_err());
+        assert!(sink.emit("_e{5,4}:title|text").is_err());
+        assert!(provider.measurements.lock().unwrap().is_empty());
+    }
+}
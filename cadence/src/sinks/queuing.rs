@@ -9,30 +9,61 @@
 // except according to those terms.
 
 use crate::sinks::core::{MetricSink, SinkStats};
-use crossbeam_channel::{self, Receiver, Sender, TrySendError};
+use crate::sinks::spill::SpillFile;
+use crate::types::queue_full;
+use crossbeam_channel::{self, Receiver, SendTimeoutError, Sender, TrySendError};
+#[cfg(feature = "crossbeam-queue")]
+use crossbeam_queue::ArrayQueue;
 use std::fmt;
 use std::io::{self, ErrorKind};
 use std::panic::RefUnwindSafe;
+use std::path::{Path, PathBuf};
+#[cfg(feature = "crossbeam-queue")]
+use std::sync::atomic;
 use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
-use std::sync::Arc;
+use std::sync::{Arc, Condvar, Mutex};
 use std::thread;
+use std::time::{Duration, Instant};
+
+/// What a `QueuingMetricSink` with a bounded queue should do when a metric is
+/// submitted and the queue is full.
+///
+/// This has no effect on sinks with an unbounded queue since they are never full.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    /// Wait for the wrapped sink to drain an entry from the queue, blocking
+    /// the thread submitting the metric until there is room for it.
+    Block,
+    /// Drop the metric being submitted and return an error. This is the default.
+    DropNewest,
+    /// Drop the oldest metric in the queue to make room for the metric being
+    /// submitted.
+    DropOldest,
+}
+
+impl Default for OverflowPolicy {
+    fn default() -> Self {
+        OverflowPolicy::DropNewest
+    }
+}
 
 /// Implementation of a builder pattern for `QueuingMetricSink`.
 ///
 /// The builder can be used to set an error handler for the sink being
 /// wrapped by a `QueuingMetricSink` as well as setting the capacity of
-/// the `QueuingMetricSink`.
+/// the `QueuingMetricSink` and what to do when its queue is full.
 ///
 /// # Example
 ///
 /// ```no_run
-/// use cadence::{MetricSink, QueuingMetricSinkBuilder, NopMetricSink};
+/// use cadence::{MetricSink, OverflowPolicy, QueuingMetricSinkBuilder, NopMetricSink};
 ///
 /// let queue_size = 64 * 1024;
 /// let wrapped = NopMetricSink;
 ///
 /// let queuing = QueuingMetricSinkBuilder::new()
 ///     .with_capacity(queue_size)
+///     .with_overflow_policy(OverflowPolicy::DropOldest)
 ///     .with_error_handler(|e| {
 ///         eprintln!("Error while sending metrics: {:?}", e);
 ///     })
@@ -44,6 +75,10 @@ use std::thread;
 pub struct QueuingMetricSinkBuilder {
     error_handler: Option<Box<dyn Fn(io::Error) + Sync + Send + RefUnwindSafe + 'static>>,
     capacity: Option<usize>,
+    policy: OverflowPolicy,
+    spill: Option<(PathBuf, u64)>,
+    #[cfg(feature = "crossbeam-queue")]
+    array_queue: bool,
 }
 
 impl QueuingMetricSinkBuilder {
@@ -58,15 +93,40 @@ impl QueuingMetricSinkBuilder {
     where
         T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
     {
+        let queue = self.queue();
         let sink = Arc::new(sink);
         let sink_c = sink.clone();
-        let worker = Arc::new(Worker::new(self.capacity, move |v: String| {
-            if let Err(e) = sink_c.emit(&v) {
-                if let Some(error_handler) = &self.error_handler {
-                    error_handler(e);
+        let stats = Arc::new(WorkerStats::new());
+        let stats_c = stats.clone();
+        let spill = self.spill.map(|(path, max_bytes)| SpillFile::new(path, max_bytes));
+        let error_handler: Option<Arc<dyn Fn(io::Error) + Sync + Send + RefUnwindSafe>> =
+            self.error_handler.map(Arc::from);
+
+        let (batch_sink, batch_stats, batch_handler) = (sink.clone(), stats.clone(), error_handler.clone());
+        let worker = Arc::new(
+            Worker::with_stats(queue, self.policy, stats, move |v: String| match sink_c.emit(&v) {
+                Ok(_) => stats_c.incr_sent(1),
+                Err(e) => {
+                    stats_c.incr_errors(1);
+                    if let Some(error_handler) = &error_handler {
+                        error_handler(e);
+                    }
                 }
-            }
-        }));
+            })
+            .with_batch_task(move |vs: Vec<String>| {
+                let metrics: Vec<&str> = vs.iter().map(|v| v.as_str()).collect();
+                match batch_sink.emit_batch(&metrics) {
+                    Ok(_) => batch_stats.incr_sent(vs.len() as u64),
+                    Err(e) => {
+                        batch_stats.incr_errors(vs.len() as u64);
+                        if let Some(error_handler) = &batch_handler {
+                            error_handler(e);
+                        }
+                    }
+                }
+            })
+            .with_spill(spill),
+        );
 
         spawn_worker_in_thread(worker.clone());
 
@@ -92,6 +152,76 @@ impl QueuingMetricSinkBuilder {
         self.capacity = Some(capacity);
         self
     }
+
+    /// Set what to do when a metric is submitted and the queue is full.
+    ///
+    /// By default, the metric is dropped and an error is returned. This only
+    /// applies when the queue size has been set using `with_capacity`.
+    pub fn with_overflow_policy(mut self, policy: OverflowPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Write metrics to a file when the queue is full and send them to the
+    /// wrapped sink once the queue has been emptied.
+    ///
+    /// The file is limited to `max_bytes` in size. The `OverflowPolicy` of the
+    /// sink only applies to metrics submitted while the file is full or can't
+    /// be written to. Metrics left in the file when the process exits are sent
+    /// the next time a sink using the same file is created. This only applies
+    /// when the queue size has been set using `with_capacity`.
+    ///
+    /// Note that metrics read back from the file are sent after metrics that
+    /// were queued in memory after them, so they may arrive out of order.
+    pub fn with_spill_file<P>(mut self, path: P, max_bytes: u64) -> Self
+    where
+        P: AsRef<Path>,
+    {
+        self.spill = Some((path.as_ref().to_path_buf(), max_bytes));
+        self
+    }
+
+    /// Use a fixed size, lock-free array for the queue instead of a channel.
+    ///
+    /// Submitting a metric to an array queue doesn't take any locks, which
+    /// lowers the time spent queuing metrics when many threads are sending
+    /// metrics at once. However, threads submitting metrics while the queue is
+    /// full with the `OverflowPolicy::Block` policy wait by polling the queue
+    /// instead of being woken up when there's room. This only applies when the
+    /// queue size has been set using `with_capacity`.
+    ///
+    /// This method is only available when the `crossbeam-queue` feature is enabled.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cadence::{MetricSink, QueuingMetricSinkBuilder, NopMetricSink};
+    ///
+    /// let queuing = QueuingMetricSinkBuilder::new()
+    ///     .with_capacity(64 * 1024)
+    ///     .with_array_queue()
+    ///     .build(NopMetricSink);
+    ///
+    /// queuing.emit("foo.counter:4|c");
+    /// ```
+    #[cfg(feature = "crossbeam-queue")]
+    pub fn with_array_queue(mut self) -> Self {
+        self.array_queue = true;
+        self
+    }
+
+    #[cfg(feature = "crossbeam-queue")]
+    fn queue(&self) -> Queue {
+        match self.capacity {
+            Some(capacity) if self.array_queue => Queue::array(capacity),
+            capacity => Queue::channel(capacity),
+        }
+    }
+
+    #[cfg(not(feature = "crossbeam-queue"))]
+    fn queue(&self) -> Queue {
+        Queue::channel(self.capacity)
+    }
 }
 
 /// Implementation of a `MetricSink` that wraps another implementation
@@ -114,10 +244,11 @@ impl QueuingMetricSinkBuilder {
 /// accepted and queued until they can be drained by the network operation
 /// thread. This means that if the network thread cannot drain entries off
 /// the queue for some reason, it will grow without bound. Alternatively, if
-/// created with a bounded queue, entries submitted to the sink will not be
-/// accepted if the queue is full. This means that the network thread must
-/// be able to keep up with the rate of entries submit to the queue or writes
-/// to this sink will begin to fail.
+/// created with a bounded queue, what happens to entries submitted to the sink
+/// when the queue is full depends on the `OverflowPolicy` set using the
+/// `QueuingMetricSinkBuilder`. By default, they will not be accepted. This
+/// means that the network thread must be able to keep up with the rate of
+/// entries submit to the queue or writes to this sink will begin to fail.
 ///
 /// Entries already queued are guaranteed to be sent to the wrapped sink
 /// before the queuing sink is stopped. Meaning, the following code ends up
@@ -141,6 +272,11 @@ impl QueuingMetricSinkBuilder {
 /// At the end of this code block, all metrics are guaranteed to be sent to
 /// the underlying wrapped metric sink before the thread used by the queuing
 /// sink is stopped.
+///
+/// However, dropping the sink doesn't wait for the thread to finish sending
+/// queued metrics. If the process exits right after, for example at the end of
+/// `main`, queued metrics may be lost. To avoid this, use the `.shutdown()`
+/// method to wait for all queued metrics to be sent before exiting.
 #[derive(Clone)]
 pub struct QueuingMetricSink {
     worker: Arc<Worker>,
@@ -252,6 +388,73 @@ impl QueuingMetricSink {
         self.worker.stats.submitted()
     }
 
+    /// Return the number of metrics dropped because the queue was full, either
+    /// the metric being submitted or the oldest queued metric depending on the
+    /// `OverflowPolicy` of this sink.
+    pub fn dropped(&self) -> u64 {
+        self.worker.stats.dropped()
+    }
+
+    /// Return the number of metrics written to the spill file because the queue
+    /// was full. See `QueuingMetricSinkBuilder::with_spill_file`.
+    pub fn spilled(&self) -> u64 {
+        self.worker.stats.spilled()
+    }
+
+    /// Return the number of metrics read back from the spill file and passed to
+    /// the wrapped sink, including any left in the file by a previous process.
+    pub fn replayed(&self) -> u64 {
+        self.worker.stats.replayed()
+    }
+
+    /// Return the number of metrics successfully emitted by the wrapped sink.
+    pub fn sent(&self) -> u64 {
+        self.worker.stats.sent()
+    }
+
+    /// Return the number of metrics the wrapped sink returned an error for.
+    /// Each of these errors is passed to the error handler of this sink, if
+    /// one was set.
+    pub fn errors(&self) -> u64 {
+        self.worker.stats.errors()
+    }
+
+    /// Stop accepting new metrics and wait for every queued metric to be sent to
+    /// the wrapped sink, then flush the wrapped sink.
+    ///
+    /// Once this method is called, every call to `.emit()` on this sink (or any
+    /// clone of it) will fail. Metrics that were already queued are sent to the
+    /// wrapped sink by the worker thread which stops once the queue is empty.
+    /// This method blocks until the worker thread has stopped or the timeout
+    /// expires.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::time::Duration;
+    /// use cadence::{MetricSink, QueuingMetricSink, NopMetricSink};
+    ///
+    /// let queuing = QueuingMetricSink::from(NopMetricSink);
+    /// queuing.emit("foo.counter:4|c");
+    /// queuing.shutdown(Duration::from_secs(5)).unwrap();
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// This method will fail if the queued metrics aren't sent before the
+    /// timeout expires, in which case the worker thread will keep running in
+    /// the background until they are, or if flushing the wrapped sink fails.
+    pub fn shutdown(&self, timeout: Duration) -> io::Result<()> {
+        if !self.worker.stop_and_wait_timeout(timeout) {
+            return Err(io::Error::new(
+                ErrorKind::TimedOut,
+                "timed out waiting for queued metrics to be sent",
+            ));
+        }
+
+        self.sink.flush()
+    }
+
     /// Return the number of metrics removed from the queue to be processed by
     /// the wrapped sink. Note that this does not indicate that the metric has
     /// been successfully sent to a backend, only that it has been passed to
@@ -263,22 +466,46 @@ impl QueuingMetricSink {
 
 impl MetricSink for QueuingMetricSink {
     fn emit(&self, metric: &str) -> io::Result<usize> {
-        match self.worker.submit(metric.to_string()) {
-            Err(TrySendError::Disconnected(_)) => Err(io::Error::new(ErrorKind::Other, "channel disconnected")),
-            Err(TrySendError::Full(_)) => Err(io::Error::new(ErrorKind::Other, "channel full")),
-            Ok(_) => Ok(metric.len()),
+        submit_result(self.worker.submit(metric.to_string()), metric.len())
+    }
+
+    /// Add every metric to the queue as a single entry, to be written to the
+    /// wrapped sink with a single call to its `.emit_batch()` method.
+    ///
+    /// The whole batch takes up one entry in the queue and is either accepted
+    /// or rejected as a whole. If the wrapped sink fails to write the batch,
+    /// every metric in it is counted as an error but the error handler of this
+    /// sink is only called once.
+    fn emit_batch(&self, metrics: &[&str]) -> io::Result<usize> {
+        if metrics.is_empty() {
+            return Ok(0);
         }
+
+        let batch = metrics.iter().map(|m| m.to_string()).collect();
+        submit_result(self.worker.submit_batch(batch), metrics.iter().map(|m| m.len()).sum())
     }
 
     fn flush(&self) -> Result<(), std::io::Error> {
         self.sink.flush()
     }
 
+    fn check(&self) -> io::Result<()> {
+        self.sink.check()
+    }
+
     fn stats(&self) -> SinkStats {
         self.sink.stats()
     }
 }
 
+fn submit_result(res: Result<(), TrySendError<Option<Entry>>>, len: usize) -> io::Result<usize> {
+    match res {
+        Err(TrySendError::Disconnected(_)) => Err(io::Error::new(ErrorKind::Other, "channel disconnected")),
+        Err(TrySendError::Full(_)) => Err(queue_full()),
+        Ok(_) => Ok(len),
+    }
+}
+
 impl Drop for QueuingMetricSink {
     /// Send the worker a signal to stop processing metrics.
     ///
@@ -291,14 +518,21 @@ impl Drop for QueuingMetricSink {
 
 /// Statistics about the worker running.
 ///
-/// These statistics are only used for unit testing to verify that our
-/// sentinel can handle thread panics and restart the thread the worker
-/// is running in.
+/// These statistics are exposed by the `QueuingMetricSink` so that callers can
+/// tell when the queue is backing up. They're also used for unit testing to
+/// verify that our sentinel can handle thread panics and restart the thread
+/// the worker is running in.
 #[derive(Debug)]
 struct WorkerStats {
     panics: AtomicU64,
     submitted: AtomicU64,
     drained: AtomicU64,
+    dropped: AtomicU64,
+    evicted: AtomicU64,
+    spilled: AtomicU64,
+    replayed: AtomicU64,
+    sent: AtomicU64,
+    errors: AtomicU64,
 }
 
 impl WorkerStats {
@@ -307,6 +541,12 @@ impl WorkerStats {
             panics: AtomicU64::new(0),
             submitted: AtomicU64::new(0),
             drained: AtomicU64::new(0),
+            dropped: AtomicU64::new(0),
+            evicted: AtomicU64::new(0),
+            spilled: AtomicU64::new(0),
+            replayed: AtomicU64::new(0),
+            sent: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
         }
     }
 
@@ -318,31 +558,72 @@ impl WorkerStats {
         self.panics.load(Ordering::Acquire)
     }
 
-    fn incr_submitted(&self) {
-        self.submitted.fetch_add(1, Ordering::Release);
+    fn incr_submitted(&self, n: u64) {
+        self.submitted.fetch_add(n, Ordering::Release);
     }
 
     fn submitted(&self) -> u64 {
         self.submitted.load(Ordering::Acquire)
     }
 
-    fn incr_drained(&self) {
-        self.drained.fetch_add(1, Ordering::Release);
+    fn incr_drained(&self, n: u64) {
+        self.drained.fetch_add(n, Ordering::Release);
     }
 
     fn drained(&self) -> u64 {
         self.drained.load(Ordering::Acquire)
     }
 
+    fn incr_dropped(&self, n: u64) {
+        self.dropped.fetch_add(n, Ordering::Release);
+    }
+
+    fn incr_evicted(&self, n: u64) {
+        self.evicted.fetch_add(n, Ordering::Release);
+    }
+
+    fn dropped(&self) -> u64 {
+        self.dropped.load(Ordering::Acquire) + self.evicted.load(Ordering::Acquire)
+    }
+
+    fn incr_spilled(&self) {
+        self.spilled.fetch_add(1, Ordering::Release);
+    }
+
+    fn spilled(&self) -> u64 {
+        self.spilled.load(Ordering::Acquire)
+    }
+
+    fn incr_replayed(&self) {
+        self.replayed.fetch_add(1, Ordering::Release);
+    }
+
+    fn replayed(&self) -> u64 {
+        self.replayed.load(Ordering::Acquire)
+    }
+
+    fn incr_sent(&self, n: u64) {
+        self.sent.fetch_add(n, Ordering::Release);
+    }
+
+    fn sent(&self) -> u64 {
+        self.sent.load(Ordering::Acquire)
+    }
+
+    fn incr_errors(&self, n: u64) {
+        self.errors.fetch_add(n, Ordering::Release);
+    }
+
+    fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Acquire)
+    }
+
     fn queued(&self) -> u64 {
         let submitted = self.submitted.load(Ordering::Acquire);
         let drained = self.drained.load(Ordering::Acquire);
-
-        if submitted > drained {
-            submitted - drained
-        } else {
-            0
-        }
+        // Entries evicted from the queue were submitted but never drained
+        let evicted = self.evicted.load(Ordering::Acquire);
+        submitted.saturating_sub(drained).saturating_sub(evicted)
     }
 }
 
@@ -394,7 +675,197 @@ impl<'a> Drop for Sentinel<'a> {
     }
 }
 
-/// Worker to repeatedly run a method consuming entries via a channel.
+type BatchTask = Box<dyn Fn(Vec<String>) + Sync + Send + RefUnwindSafe + 'static>;
+
+/// Entry in the channel between a worker and the threads submitting to it
+#[derive(Debug, PartialEq, Eq)]
+enum Entry {
+    Metric(String),
+    Batch(Vec<String>),
+}
+
+impl Entry {
+    /// Number of metrics in this entry
+    fn len(&self) -> u64 {
+        match self {
+            Entry::Metric(_) => 1,
+            Entry::Batch(vs) => vs.len() as u64,
+        }
+    }
+}
+
+/// Queue connecting a worker and the threads submitting to it.
+///
+/// This is a channel by default. When the `crossbeam-queue` feature is enabled
+/// it may instead be an `ArrayQueue` which never takes a lock to push or pop
+/// entries. Since an `ArrayQueue` can't block until an entry is available, the
+/// worker waits on a condition variable when it's empty and producers only
+/// signal it when the worker is actually waiting.
+enum Queue {
+    Channel(Sender<Option<Entry>>, Receiver<Option<Entry>>),
+    // Boxed since the queue pads its head and tail to separate cache lines
+    #[cfg(feature = "crossbeam-queue")]
+    Array(Box<ArrayQueue<Option<Entry>>>, Wakeup),
+}
+
+impl Queue {
+    fn channel(capacity: Option<usize>) -> Self {
+        let (tx, rx) = if let Some(v) = capacity {
+            crossbeam_channel::bounded(v)
+        } else {
+            crossbeam_channel::unbounded()
+        };
+
+        Queue::Channel(tx, rx)
+    }
+
+    #[cfg(feature = "crossbeam-queue")]
+    fn array(capacity: usize) -> Self {
+        // An ArrayQueue can't have a capacity of zero, unlike a channel
+        Queue::Array(Box::new(ArrayQueue::new(capacity.max(1))), Wakeup::default())
+    }
+
+    fn try_send(&self, v: Option<Entry>) -> Result<(), TrySendError<Option<Entry>>> {
+        match self {
+            Queue::Channel(tx, _) => tx.try_send(v),
+            #[cfg(feature = "crossbeam-queue")]
+            Queue::Array(queue, wakeup) => {
+                queue.push(v).map_err(TrySendError::Full)?;
+                wakeup.notify();
+                Ok(())
+            }
+        }
+    }
+
+    // Wait until there's room in the queue for the entry, returning it if there
+    // isn't any before the deadline, if one was given.
+    fn send_deadline(
+        &self,
+        v: Option<Entry>,
+        deadline: Option<Instant>,
+    ) -> Result<(), SendTimeoutError<Option<Entry>>> {
+        match self {
+            Queue::Channel(tx, _) => match deadline {
+                Some(deadline) => tx.send_deadline(v, deadline),
+                None => tx.send(v).map_err(|e| SendTimeoutError::Disconnected(e.0)),
+            },
+            #[cfg(feature = "crossbeam-queue")]
+            Queue::Array(queue, wakeup) => {
+                let mut v = v;
+                let mut attempts = 0;
+                loop {
+                    match queue.push(v) {
+                        Ok(_) => {
+                            wakeup.notify();
+                            return Ok(());
+                        }
+                        Err(rejected) if matches!(deadline, Some(d) if Instant::now() >= d) => {
+                            return Err(SendTimeoutError::Timeout(rejected));
+                        }
+                        Err(rejected) => v = rejected,
+                    }
+
+                    // Give the worker a chance to drain the queue, backing off
+                    // if it's slow so that waiting doesn't keep a core busy.
+                    attempts += 1;
+                    if attempts < ARRAY_QUEUE_SPINS {
+                        thread::yield_now();
+                    } else {
+                        thread::sleep(ARRAY_QUEUE_BACKOFF);
+                    }
+                }
+            }
+        }
+    }
+
+    fn try_recv(&self) -> Option<Option<Entry>> {
+        match self {
+            Queue::Channel(_, rx) => rx.try_recv().ok(),
+            #[cfg(feature = "crossbeam-queue")]
+            Queue::Array(queue, _) => queue.pop(),
+        }
+    }
+
+    // Wait until an entry is available. The worker holds both ends of the
+    // channel so it's never disconnected while the worker is running.
+    fn recv(&self) -> Option<Option<Entry>> {
+        match self {
+            Queue::Channel(_, rx) => rx.recv().ok(),
+            #[cfg(feature = "crossbeam-queue")]
+            Queue::Array(queue, wakeup) => Some(wakeup.wait_for(|| queue.pop())),
+        }
+    }
+
+    fn is_empty(&self) -> bool {
+        match self {
+            Queue::Channel(_, rx) => rx.is_empty(),
+            #[cfg(feature = "crossbeam-queue")]
+            Queue::Array(queue, _) => queue.is_empty(),
+        }
+    }
+}
+
+// Number of times a full array queue is retried before sleeping between attempts
+#[cfg(feature = "crossbeam-queue")]
+const ARRAY_QUEUE_SPINS: u32 = 16;
+
+#[cfg(feature = "crossbeam-queue")]
+const ARRAY_QUEUE_BACKOFF: Duration = Duration::from_micros(100);
+
+// Upper bound on how long the worker waits without checking an array queue, in
+// case a wakeup is missed.
+#[cfg(feature = "crossbeam-queue")]
+const ARRAY_QUEUE_WAIT: Duration = Duration::from_millis(100);
+
+/// Wakes up a worker waiting for entries in an `ArrayQueue`.
+#[cfg(feature = "crossbeam-queue")]
+#[derive(Debug, Default)]
+struct Wakeup {
+    waiting: AtomicBool,
+    lock: Mutex<()>,
+    cond: Condvar,
+}
+
+#[cfg(feature = "crossbeam-queue")]
+impl Wakeup {
+    // Called by producers after pushing an entry. The lock is only taken when
+    // the worker is waiting, keeping it out of the common case.
+    fn notify(&self) {
+        atomic::fence(Ordering::SeqCst);
+        if self.waiting.load(Ordering::SeqCst) {
+            let _guard = self.lock.lock().unwrap();
+            self.cond.notify_one();
+        }
+    }
+
+    // Called by the worker to wait until `pop` returns an entry.
+    fn wait_for<T, F>(&self, pop: F) -> T
+    where
+        F: Fn() -> Option<T>,
+    {
+        loop {
+            if let Some(v) = pop() {
+                return v;
+            }
+
+            let guard = self.lock.lock().unwrap();
+            self.waiting.store(true, Ordering::SeqCst);
+            atomic::fence(Ordering::SeqCst);
+
+            // Check again now that producers will signal us: anything pushed
+            // before they could see the flag is visible here.
+            if let Some(v) = pop() {
+                self.waiting.store(false, Ordering::SeqCst);
+                return v;
+            }
+
+            let _ = self.cond.wait_timeout(guard, ARRAY_QUEUE_WAIT).unwrap();
+            self.waiting.store(false, Ordering::SeqCst);
+        }
+    }
+}
+
+/// Worker to repeatedly run a method consuming entries via a queue.
 ///
 /// The `.run()` method of the worker is intended to be in a separate
 /// thread (thread B). Meanwhile, the `.submit()`, `.stop()`,
@@ -417,87 +888,249 @@ impl<'a> Drop for Sentinel<'a> {
 /// for it or inspect it even exist: testing is the reason.
 struct Worker {
     task: Box<dyn Fn(String) + Sync + Send + RefUnwindSafe + 'static>,
-    sender: Sender<Option<String>>,
-    receiver: Receiver<Option<String>>,
-    stopped: AtomicBool,
-    stats: WorkerStats,
+    batch_task: Option<BatchTask>,
+    queue: Queue,
+    policy: OverflowPolicy,
+    spill: Option<SpillFile>,
+    closed: AtomicBool,
+    stopped: Mutex<bool>,
+    stopped_cond: Condvar,
+    stats: Arc<WorkerStats>,
 }
 
 impl Worker {
-    fn new<F>(capacity: Option<usize>, task: F) -> Self
+    #[cfg(test)]
+    fn new<F>(capacity: Option<usize>, policy: OverflowPolicy, task: F) -> Self
+    where
+        F: Fn(String) + Sync + Send + RefUnwindSafe + 'static,
+    {
+        Self::with_stats(Queue::channel(capacity), policy, Arc::new(WorkerStats::new()), task)
+    }
+
+    fn with_stats<F>(queue: Queue, policy: OverflowPolicy, stats: Arc<WorkerStats>, task: F) -> Self
     where
         F: Fn(String) + Sync + Send + RefUnwindSafe + 'static,
     {
-        let (tx, rx) = Self::get_channels(capacity);
         Worker {
             task: Box::new(task),
-            sender: tx,
-            receiver: rx,
-            stopped: AtomicBool::new(false),
-            stats: WorkerStats::new(),
+            batch_task: None,
+            queue,
+            policy,
+            spill: None,
+            closed: AtomicBool::new(false),
+            stopped: Mutex::new(false),
+            stopped_cond: Condvar::new(),
+            stats,
         }
     }
 
-    fn get_channels(capacity: Option<usize>) -> (Sender<Option<String>>, Receiver<Option<String>>) {
-        if let Some(v) = capacity {
-            crossbeam_channel::bounded(v)
-        } else {
-            crossbeam_channel::unbounded()
-        }
+    // Run batches of entries through a different task than single entries. If
+    // not set, each entry in a batch is run through the normal task.
+    fn with_batch_task<F>(mut self, task: F) -> Self
+    where
+        F: Fn(Vec<String>) + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.batch_task = Some(Box::new(task));
+        self
+    }
+
+    fn with_spill(mut self, spill: Option<SpillFile>) -> Self {
+        self.spill = spill;
+        self
+    }
+
+    fn submit(&self, v: String) -> Result<(), TrySendError<Option<Entry>>> {
+        self.submit_entry(Entry::Metric(v))
+    }
+
+    fn submit_batch(&self, vs: Vec<String>) -> Result<(), TrySendError<Option<Entry>>> {
+        self.submit_entry(Entry::Batch(vs))
     }
 
-    fn submit(&self, v: String) -> Result<(), TrySendError<Option<String>>> {
-        let res = self.sender.try_send(Some(v));
+    fn submit_entry(&self, v: Entry) -> Result<(), TrySendError<Option<Entry>>> {
+        if self.closed.load(Ordering::Acquire) {
+            return Err(TrySendError::Disconnected(Some(v)));
+        }
+
+        let len = v.len();
+        let res = match self.queue.try_send(Some(v)) {
+            Err(TrySendError::Full(v)) => self.overflow(v),
+            res => res,
+        };
+
         if res.is_ok() {
-            self.stats.incr_submitted();
+            self.stats.incr_submitted(len);
         }
 
         res
     }
 
+    fn overflow(&self, v: Option<Entry>) -> Result<(), TrySendError<Option<Entry>>> {
+        let v = match (&self.spill, v) {
+            // Fall back to the overflow policy for anything that doesn't fit
+            // in the file or if the file is broken
+            (Some(spill), Some(entry)) => match self.spill(spill, entry) {
+                Some(rest) => Some(rest),
+                None => return Ok(()),
+            },
+            (_, v) => v,
+        };
+
+        match self.policy {
+            OverflowPolicy::Block => self
+                .queue
+                .send_deadline(v, None)
+                .map_err(|e| TrySendError::Disconnected(e.into_inner())),
+            OverflowPolicy::DropNewest => {
+                self.stats.incr_dropped(v.as_ref().map_or(0, Entry::len));
+                Err(TrySendError::Full(v))
+            }
+            OverflowPolicy::DropOldest => {
+                let mut v = v;
+                loop {
+                    // Make room by removing the oldest entry from the queue. The worker
+                    // thread may have drained the queue in the meantime or another thread
+                    // may have taken the free slot, so keep trying until the entry fits.
+                    match self.queue.try_recv() {
+                        Some(Some(e)) => self.stats.incr_evicted(e.len()),
+                        Some(None) => {
+                            // We removed the poison pill meant to stop the worker, put it
+                            // back and give up since the sink is being shut down anyway.
+                            let _ = self.queue.try_send(None);
+                            return Err(TrySendError::Disconnected(v));
+                        }
+                        None => {}
+                    }
+
+                    match self.queue.try_send(v) {
+                        Err(TrySendError::Full(rejected)) => v = rejected,
+                        res => return res,
+                    }
+                }
+            }
+        }
+    }
+
+    // Write as much of the entry to the spill file as possible, returning
+    // whatever didn't fit.
+    fn spill(&self, spill: &SpillFile, entry: Entry) -> Option<Entry> {
+        let push = |metric: &str| match spill.push(metric) {
+            Ok(true) => {
+                self.stats.incr_spilled();
+                true
+            }
+            _ => false,
+        };
+
+        match entry {
+            Entry::Metric(v) if push(&v) => None,
+            Entry::Metric(v) => Some(Entry::Metric(v)),
+            Entry::Batch(vs) => {
+                let rest: Vec<String> = vs.into_iter().filter(|v| !push(v)).collect();
+                if rest.is_empty() {
+                    None
+                } else {
+                    Some(Entry::Batch(rest))
+                }
+            }
+        }
+    }
+
     fn run(&self) {
-        for opt in self.receiver.iter() {
+        // Send anything left in the spill file by a previous process first
+        self.replay();
+
+        while let Some(opt) = self.queue.recv() {
             if let Some(v) = opt {
-                self.stats.incr_drained();
-                (self.task)(v);
+                self.stats.incr_drained(v.len());
+                match v {
+                    Entry::Metric(v) => (self.task)(v),
+                    Entry::Batch(vs) => match &self.batch_task {
+                        Some(batch_task) => batch_task(vs),
+                        None => vs.into_iter().for_each(|v| (self.task)(v)),
+                    },
+                }
             } else {
                 break;
             }
+
+            // Metrics are only spilled when the queue is full so there's always
+            // at least one more entry to process after a metric is spilled. This
+            // means checking when the queue becomes empty won't miss any.
+            if self.queue.is_empty() {
+                self.replay();
+            }
         }
 
-        // Set the "stopped" flag so that callers using the `stop_and_wait`
+        self.replay();
+
+        // Set the "stopped" flag so that callers using the `stop_and_wait_timeout`
         // method will see that we've stopped processing entries in the channel.
-        // This is only for the benefit of unit testing.
-        self.stopped.store(true, Ordering::Release);
+        *self.stopped.lock().unwrap() = true;
+        self.stopped_cond.notify_all();
+    }
+
+    // Send every metric from the spill file to the task. If the file can't be
+    // read, the metrics in it are left there to try again later.
+    fn replay(&self) {
+        if let Some(spill) = &self.spill {
+            for v in spill.drain().unwrap_or_default() {
+                self.stats.incr_replayed();
+                (self.task)(v);
+            }
+        }
     }
 
     fn stop(&self) {
         // Send a `None` poison pill value to stop the run loop.
-        let _ = self.sender.try_send(None);
+        let _ = self.queue.try_send(None);
+    }
+
+    // Stop accepting new entries, stop reading events from the channel once
+    // it is empty, and wait for the "stopped" flag to be set. Returns false
+    // if the worker didn't stop before the timeout.
+    fn stop_and_wait_timeout(&self, timeout: Duration) -> bool {
+        let deadline = Instant::now() + timeout;
+        self.closed.store(true, Ordering::Release);
+
+        // Unlike `.stop()`, wait for room in the queue for the poison pill
+        // since we need the worker to actually stop.
+        if let Err(SendTimeoutError::Timeout(_)) = self.queue.send_deadline(None, Some(deadline)) {
+            return false;
+        }
+
+        let stopped = self.stopped.lock().unwrap();
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let (stopped, _) = self
+            .stopped_cond
+            .wait_timeout_while(stopped, remaining, |stopped| !*stopped)
+            .unwrap();
+
+        *stopped
     }
 
     // Stop reading events from the channel and wait for the "stopped" flag
-    // to be set. Note that this repeatedly yields the current thread and is
-    // only intended for unit testing.
+    // to be set. This is only intended for unit testing.
     #[cfg(test)]
     fn stop_and_wait(&self) {
         self.stop();
 
-        while !self.stopped.load(Ordering::Acquire) {
-            thread::yield_now();
+        let mut stopped = self.stopped.lock().unwrap();
+        while !*stopped {
+            stopped = self.stopped_cond.wait(stopped).unwrap();
         }
     }
 
     // Is the channel used between threads empty, i.e. are all values processed?
     #[cfg(test)]
     fn is_empty(&self) -> bool {
-        self.receiver.is_empty()
+        self.queue.is_empty()
     }
 
     // Has this worker stopped running?
     #[cfg(test)]
     fn is_stopped(&self) -> bool {
-        self.stopped.load(Ordering::Acquire)
+        *self.stopped.lock().unwrap()
     }
 }
 
@@ -509,15 +1142,18 @@ impl fmt::Debug for Worker {
 
 #[cfg(test)]
 mod tests {
-    use super::{QueuingMetricSink, Worker};
+    use super::{Entry, OverflowPolicy, QueuingMetricSink, Worker};
+    #[cfg(feature = "crossbeam-queue")]
+    use super::{Queue, QueuingMetricSinkBuilder, WorkerStats};
     use crate::sinks::MetricSink;
     use crate::sinks::SpyMetricSink;
-    use crate::test::PanickingMetricSink;
+    use crate::test::{ErrorMetricSink, PanickingMetricSink, TempDir};
     use std::io;
     use std::panic;
     use std::sync::atomic::{AtomicBool, Ordering};
-    use std::sync::Arc;
+    use std::sync::{Arc, Mutex};
     use std::thread;
+    use std::time::Duration;
 
     const QUEUE_SIZE: Option<usize> = Some(128);
 
@@ -532,7 +1168,7 @@ mod tests {
             }
         };
 
-        let worker = Arc::new(Worker::new(QUEUE_SIZE, task));
+        let worker = Arc::new(Worker::new(QUEUE_SIZE, OverflowPolicy::default(), task));
         let worker_ref = worker.clone();
 
         let t = thread::spawn(move || {
@@ -549,7 +1185,7 @@ mod tests {
 
     #[test]
     fn test_worker_stop() {
-        let worker = Arc::new(Worker::new(QUEUE_SIZE, move |_: String| {}));
+        let worker = Arc::new(Worker::new(QUEUE_SIZE, OverflowPolicy::default(), move |_: String| {}));
         let worker_ref = worker.clone();
 
         let t = thread::spawn(move || {
@@ -564,7 +1200,7 @@ mod tests {
 
     #[test]
     fn test_worker_stop_and_wait() {
-        let worker = Arc::new(Worker::new(QUEUE_SIZE, move |_: String| {}));
+        let worker = Arc::new(Worker::new(QUEUE_SIZE, OverflowPolicy::default(), move |_: String| {}));
         let worker_ref = worker.clone();
 
         let _t = thread::spawn(move || {
@@ -579,7 +1215,7 @@ mod tests {
     // when the producer size of the channel panics.
     #[test]
     fn test_worker_panic_on_submit_side() {
-        let worker = Arc::new(Worker::new(QUEUE_SIZE, move |_: String| {}));
+        let worker = Arc::new(Worker::new(QUEUE_SIZE, OverflowPolicy::default(), move |_: String| {}));
         let worker_ref1 = worker.clone();
         let worker_ref2 = worker.clone();
 
@@ -606,7 +1242,7 @@ mod tests {
     // when the consumer side of the channel panics.
     #[test]
     fn test_worker_panic_on_run_side() {
-        let worker = Arc::new(Worker::new(QUEUE_SIZE, move |_: String| {
+        let worker = Arc::new(Worker::new(QUEUE_SIZE, OverflowPolicy::default(), move |_: String| {
             panic!("This thread is supposed to panic");
         }));
         let worker_ref1 = worker.clone();
@@ -627,6 +1263,163 @@ mod tests {
         assert!(worker.is_empty());
     }
 
+    #[test]
+    fn test_worker_overflow_drop_newest() {
+        let worker = Worker::new(Some(2), OverflowPolicy::DropNewest, move |_: String| {});
+
+        worker.submit("foo".to_string()).unwrap();
+        worker.submit("bar".to_string()).unwrap();
+        assert!(worker.submit("baz".to_string()).is_err());
+
+        assert_eq!(Some(Entry::Metric("foo".to_string())), worker.queue.try_recv().unwrap());
+        assert_eq!(Some(Entry::Metric("bar".to_string())), worker.queue.try_recv().unwrap());
+        assert_eq!(1, worker.stats.dropped());
+        assert_eq!(2, worker.stats.submitted());
+    }
+
+    #[test]
+    fn test_worker_overflow_drop_oldest() {
+        let worker = Worker::new(Some(2), OverflowPolicy::DropOldest, move |_: String| {});
+
+        worker.submit("foo".to_string()).unwrap();
+        worker.submit("bar".to_string()).unwrap();
+        worker.submit("baz".to_string()).unwrap();
+
+        assert_eq!(Some(Entry::Metric("bar".to_string())), worker.queue.try_recv().unwrap());
+        assert_eq!(Some(Entry::Metric("baz".to_string())), worker.queue.try_recv().unwrap());
+        assert_eq!(1, worker.stats.dropped());
+        assert_eq!(3, worker.stats.submitted());
+        assert_eq!(2, worker.stats.queued());
+    }
+
+    #[test]
+    fn test_worker_overflow_block() {
+        let worker = Arc::new(Worker::new(Some(1), OverflowPolicy::Block, move |_: String| {}));
+        let worker_ref = worker.clone();
+
+        worker.submit("foo".to_string()).unwrap();
+        let t = thread::spawn(move || {
+            worker_ref.submit("bar".to_string()).unwrap();
+        });
+
+        // The second submission can only complete once there's room in the queue
+        assert_eq!(Some(Entry::Metric("foo".to_string())), worker.queue.recv().unwrap());
+        t.join().unwrap();
+
+        assert_eq!(Some(Entry::Metric("bar".to_string())), worker.queue.try_recv().unwrap());
+        assert_eq!(0, worker.stats.dropped());
+    }
+
+    #[test]
+    fn test_worker_submit_batch_single_entry() {
+        let worker = Worker::new(Some(1), OverflowPolicy::DropNewest, move |_: String| {});
+
+        worker.submit_batch(vec!["foo".to_string(), "bar".to_string()]).unwrap();
+        assert!(worker.submit("baz".to_string()).is_err());
+
+        assert_eq!(
+            Some(Entry::Batch(vec!["foo".to_string(), "bar".to_string()])),
+            worker.queue.try_recv().unwrap()
+        );
+        assert_eq!(2, worker.stats.submitted());
+        assert_eq!(1, worker.stats.dropped());
+    }
+
+    #[cfg(feature = "crossbeam-queue")]
+    fn new_array_worker(capacity: usize, policy: OverflowPolicy) -> Worker {
+        Worker::with_stats(
+            Queue::array(capacity),
+            policy,
+            Arc::new(WorkerStats::new()),
+            |_: String| {},
+        )
+    }
+
+    #[cfg(feature = "crossbeam-queue")]
+    #[test]
+    fn test_worker_array_queue_overflow_drop_newest() {
+        let worker = new_array_worker(2, OverflowPolicy::DropNewest);
+
+        worker.submit("foo".to_string()).unwrap();
+        worker.submit("bar".to_string()).unwrap();
+        assert!(worker.submit("baz".to_string()).is_err());
+
+        assert_eq!(Some(Entry::Metric("foo".to_string())), worker.queue.try_recv().unwrap());
+        assert_eq!(Some(Entry::Metric("bar".to_string())), worker.queue.try_recv().unwrap());
+        assert_eq!(1, worker.stats.dropped());
+        assert_eq!(2, worker.stats.submitted());
+    }
+
+    #[cfg(feature = "crossbeam-queue")]
+    #[test]
+    fn test_worker_array_queue_overflow_drop_oldest() {
+        let worker = new_array_worker(2, OverflowPolicy::DropOldest);
+
+        worker.submit("foo".to_string()).unwrap();
+        worker.submit("bar".to_string()).unwrap();
+        worker.submit("baz".to_string()).unwrap();
+
+        assert_eq!(Some(Entry::Metric("bar".to_string())), worker.queue.try_recv().unwrap());
+        assert_eq!(Some(Entry::Metric("baz".to_string())), worker.queue.try_recv().unwrap());
+        assert_eq!(1, worker.stats.dropped());
+        assert_eq!(2, worker.stats.queued());
+    }
+
+    #[cfg(feature = "crossbeam-queue")]
+    #[test]
+    fn test_worker_array_queue_overflow_block() {
+        let worker = Arc::new(new_array_worker(1, OverflowPolicy::Block));
+        let worker_ref = worker.clone();
+
+        worker.submit("foo".to_string()).unwrap();
+        let t = thread::spawn(move || {
+            worker_ref.submit("bar".to_string()).unwrap();
+        });
+
+        // The second submission can only complete once there's room in the queue
+        assert_eq!(Some(Entry::Metric("foo".to_string())), worker.queue.recv().unwrap());
+        t.join().unwrap();
+
+        assert_eq!(Some(Entry::Metric("bar".to_string())), worker.queue.try_recv().unwrap());
+        assert_eq!(0, worker.stats.dropped());
+    }
+
+    #[cfg(feature = "crossbeam-queue")]
+    #[test]
+    fn test_queuing_sink_array_queue_many_producers() {
+        let (rx, spy) = SpyMetricSink::new();
+        // Dropping any clone of the sink stops the worker so share a single one
+        let queuing = Arc::new(
+            QueuingMetricSinkBuilder::new()
+                .with_capacity(16)
+                .with_overflow_policy(OverflowPolicy::Block)
+                .with_array_queue()
+                .build(spy),
+        );
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let queuing = queuing.clone();
+                thread::spawn(move || {
+                    for _ in 0..250 {
+                        queuing.emit("foo.counter:1|c").unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        queuing.shutdown(Duration::from_secs(10)).unwrap();
+
+        assert_eq!(1000, rx.try_iter().count());
+        assert_eq!(1000, queuing.submitted());
+        assert_eq!(1000, queuing.sent());
+        assert_eq!(0, queuing.queued());
+    }
+
     #[test]
     fn test_queuing_sink_emit() {
         let (rx, spy) = SpyMetricSink::new();
@@ -646,6 +1439,143 @@ mod tests {
         assert_eq!("baz.counter:3|c".as_bytes(), m3.as_slice());
     }
 
+    #[test]
+    fn test_queuing_sink_emit_batch() {
+        let (rx, spy) = SpyMetricSink::new();
+        let queuing = QueuingMetricSink::from(spy);
+
+        assert_eq!(30, queuing.emit_batch(&["foo.counter:1|c", "bar.counter:2|c"]).unwrap());
+        assert_eq!(0, queuing.emit_batch(&[]).unwrap());
+        queuing.shutdown(Duration::from_secs(10)).unwrap();
+
+        assert_eq!("foo.counter:1|c".as_bytes(), rx.try_recv().unwrap().as_slice());
+        assert_eq!("bar.counter:2|c".as_bytes(), rx.try_recv().unwrap().as_slice());
+        assert_eq!(2, queuing.submitted());
+        assert_eq!(2, queuing.sent());
+        assert_eq!(0, queuing.queued());
+    }
+
+    #[test]
+    fn test_queuing_sink_shutdown() {
+        let (rx, spy) = SpyMetricSink::new();
+        let queuing = QueuingMetricSink::from(spy);
+
+        queuing.emit("foo.counter:1|c").unwrap();
+        queuing.emit("bar.counter:2|c").unwrap();
+        queuing.shutdown(Duration::from_secs(10)).unwrap();
+
+        assert!(queuing.worker.is_stopped());
+        assert!(queuing.emit("baz.counter:3|c").is_err());
+        assert_eq!("foo.counter:1|c".as_bytes(), rx.try_recv().unwrap().as_slice());
+        assert_eq!("bar.counter:2|c".as_bytes(), rx.try_recv().unwrap().as_slice());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_queuing_sink_stats() {
+        let queuing = QueuingMetricSink::from(ErrorMetricSink::every(2));
+
+        queuing.emit("foo.counter:1|c").unwrap();
+        queuing.emit("bar.counter:2|c").unwrap();
+        queuing.emit("baz.counter:3|c").unwrap();
+        queuing.shutdown(Duration::from_secs(10)).unwrap();
+
+        assert_eq!(3, queuing.submitted());
+        assert_eq!(3, queuing.drained());
+        assert_eq!(2, queuing.sent());
+        assert_eq!(1, queuing.errors());
+        assert_eq!(0, queuing.queued());
+    }
+
+    #[test]
+    fn test_queuing_sink_shutdown_timeout() {
+        struct SlowMetricSink;
+
+        impl MetricSink for SlowMetricSink {
+            fn emit(&self, m: &str) -> io::Result<usize> {
+                thread::sleep(Duration::from_millis(500));
+                Ok(m.len())
+            }
+        }
+
+        let queuing = QueuingMetricSink::from(SlowMetricSink);
+        queuing.emit("foo.counter:1|c").unwrap();
+        queuing.emit("bar.counter:2|c").unwrap();
+
+        let err = queuing.shutdown(Duration::from_millis(10)).unwrap_err();
+        assert_eq!(io::ErrorKind::TimedOut, err.kind());
+    }
+
+    // Sink that records metrics once the gate is opened, blocking until then
+    #[derive(Clone, Default)]
+    struct GatedMetricSink {
+        open: Arc<AtomicBool>,
+        metrics: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl MetricSink for GatedMetricSink {
+        fn emit(&self, m: &str) -> io::Result<usize> {
+            while !self.open.load(Ordering::Acquire) {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            self.metrics.lock().unwrap().push(m.to_string());
+            Ok(m.len())
+        }
+    }
+
+    #[test]
+    fn test_queuing_sink_spill_file() {
+        let dir = TempDir::new("cadence-queuing-spill").unwrap();
+        let wrapped = GatedMetricSink::default();
+        let queuing = QueuingMetricSink::builder()
+            .with_capacity(1)
+            .with_spill_file(dir.new_path("spill"), 1024)
+            .build(wrapped.clone());
+
+        let expected: Vec<String> = (0..10).map(|i| format!("foo.counter:{}|c", i)).collect();
+        for m in expected.iter() {
+            queuing.emit(m).unwrap();
+        }
+
+        assert!(queuing.spilled() >= 1);
+        wrapped.open.store(true, Ordering::Release);
+        queuing.shutdown(Duration::from_secs(5)).unwrap();
+
+        let mut metrics = wrapped.metrics.lock().unwrap().clone();
+        metrics.sort_by_key(|m| m[12..m.len() - 2].parse::<u32>().unwrap());
+        assert_eq!(expected, metrics);
+        assert_eq!(queuing.spilled(), queuing.replayed());
+        assert_eq!(0, queuing.dropped());
+    }
+
+    #[test]
+    fn test_queuing_sink_spill_file_full() {
+        let dir = TempDir::new("cadence-queuing-spill-full").unwrap();
+        let wrapped = GatedMetricSink::default();
+        let queuing = QueuingMetricSink::builder()
+            .with_capacity(1)
+            .with_spill_file(dir.new_path("spill"), 16)
+            .build(wrapped.clone());
+
+        // Wait for the worker to block sending the first metric so that it
+        // doesn't replay the spill file while the rest are emitted.
+        queuing.emit("foo.counter:0|c").unwrap();
+        while queuing.drained() == 0 {
+            thread::sleep(Duration::from_millis(1));
+        }
+
+        let results: Vec<io::Result<usize>> = (1..10).map(|i| queuing.emit(&format!("foo.counter:{}|c", i))).collect();
+
+        assert_eq!(1, queuing.spilled());
+        assert!(results.iter().any(|r| r.is_err()));
+        assert!(queuing.dropped() >= 1);
+
+        wrapped.open.store(true, Ordering::Release);
+        queuing.shutdown(Duration::from_secs(5)).unwrap();
+        assert_eq!(1, queuing.replayed());
+    }
+
     #[test]
     fn test_queuing_sink_emit_panics() {
         let queuing = QueuingMetricSink::from(PanickingMetricSink::always());
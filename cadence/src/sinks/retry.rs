@@ -0,0 +1,281 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::Duration;
+
+use crate::sinks::backoff::Backoff;
+use crate::sinks::core::{MetricSink, SinkStats};
+
+const DEFAULT_MAX_RETRIES: u32 = 3;
+
+/// Implementation of a builder pattern for `RetryingMetricSink`.
+///
+/// The builder can be used to set the maximum number of times a metric is
+/// retried and how long to wait between each attempt.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use cadence::{Backoff, MetricSink, RetryingMetricSinkBuilder, TcpMetricSink};
+///
+/// let tcp = TcpMetricSink::from(("localhost", 8125)).unwrap();
+/// let sink = RetryingMetricSinkBuilder::new()
+///     .with_max_retries(5)
+///     .with_backoff(Backoff::exponential(Duration::from_millis(5), Duration::from_millis(500)))
+///     .build(tcp);
+///
+/// sink.emit("foo.counter:4|c");
+/// ```
+#[derive(Debug, Clone)]
+pub struct RetryingMetricSinkBuilder {
+    max_retries: u32,
+    backoff: Backoff,
+}
+
+impl RetryingMetricSinkBuilder {
+    /// Construct a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the maximum number of times a metric is retried after the first
+    /// attempt to write it fails.
+    ///
+    /// The default is 3 retries. Setting this to 0 disables retries.
+    pub fn with_max_retries(mut self, max_retries: u32) -> Self {
+        self.max_retries = max_retries;
+        self
+    }
+
+    /// Set how long to wait before each retry.
+    ///
+    /// The delay is randomly reduced by up to half to avoid many clients
+    /// retrying at the same time. By default, the delay starts at 10
+    /// milliseconds and doubles after each retry up to 1 second.
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Construct a new `RetryingMetricSink` instance wrapping the given sink
+    /// based on the builder configuration.
+    pub fn build<T>(self, sink: T) -> RetryingMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        RetryingMetricSink {
+            sink: Box::new(sink),
+            max_retries: self.max_retries,
+            backoff: self.backoff,
+            retries: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Default for RetryingMetricSinkBuilder {
+    fn default() -> Self {
+        RetryingMetricSinkBuilder {
+            max_retries: DEFAULT_MAX_RETRIES,
+            backoff: Backoff::exponential(Duration::from_millis(10), Duration::from_secs(1)),
+        }
+    }
+}
+
+/// Return true if an error of this kind might not happen if the same
+/// operation is tried again, for example because the connection to a
+/// server was lost and will be reestablished.
+fn is_transient(kind: io::ErrorKind) -> bool {
+    matches!(
+        kind,
+        io::ErrorKind::BrokenPipe
+            | io::ErrorKind::ConnectionAborted
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::Interrupted
+            | io::ErrorKind::NotConnected
+            | io::ErrorKind::TimedOut
+            | io::ErrorKind::UnexpectedEof
+            | io::ErrorKind::WouldBlock
+    )
+}
+
+/// Implementation of a `MetricSink` that retries writing metrics to a
+/// wrapped sink when it fails with a transient error.
+///
+/// Transient errors are those caused by a lost connection or timeout, such
+/// as `BrokenPipe` or `ConnectionReset`, which are likely to succeed if tried
+/// again. Any other error is returned immediately. Each metric is retried up
+/// to a maximum number of times (3 by default) waiting for an exponentially
+/// increasing, randomly jittered delay before each retry. If the last retry
+/// fails, its error is returned.
+///
+/// This is useful for sinks that use stream connections, such as the
+/// `TcpMetricSink` or `UnixStreamMetricSink`, where a single broken connection
+/// shouldn't cause a metric or a buffer of metrics to be dropped. Retries
+/// happen in the thread of the caller, so this sink is best combined with a
+/// `QueuingMetricSink` to avoid blocking application threads.
+///
+/// Note that the wrapped sink must be able to recover from a failure by
+/// itself, such as by reconnecting, for retries to be useful. If the wrapped
+/// sink waits before reconnecting after a failure, the backoff used by this
+/// sink should be long enough for that to happen.
+pub struct RetryingMetricSink {
+    sink: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
+    max_retries: u32,
+    backoff: Backoff,
+    retries: AtomicU64,
+}
+
+impl RetryingMetricSink {
+    /// Construct a new builder for `RetryingMetricSink`.
+    pub fn builder() -> RetryingMetricSinkBuilder {
+        RetryingMetricSinkBuilder::new()
+    }
+
+    /// Construct a new `RetryingMetricSink` instance wrapping the given sink
+    /// with the default number of retries and backoff.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cadence::{RetryingMetricSink, TcpMetricSink};
+    ///
+    /// let tcp = TcpMetricSink::from(("localhost", 8125)).unwrap();
+    /// let sink = RetryingMetricSink::from(tcp);
+    /// ```
+    pub fn from<T>(sink: T) -> RetryingMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        Self::builder().build(sink)
+    }
+
+    /// Return the total number of times a write to the wrapped sink has been
+    /// retried since this sink was created.
+    pub fn retries(&self) -> u64 {
+        self.retries.load(Ordering::Relaxed)
+    }
+
+    fn with_retries<F, R>(&self, f: F) -> io::Result<R>
+    where
+        F: Fn() -> io::Result<R>,
+    {
+        let mut attempt = 0;
+        loop {
+            match f() {
+                Err(e) if attempt < self.max_retries && is_transient(e.kind()) => {
+                    attempt += 1;
+                    self.retries.fetch_add(1, Ordering::Relaxed);
+                    thread::sleep(self.backoff.jittered_delay(attempt));
+                }
+                res => return res,
+            }
+        }
+    }
+}
+
+impl MetricSink for RetryingMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        self.with_retries(|| self.sink.emit(metric))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.with_retries(|| self.sink.flush())
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.sink.check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats()
+    }
+}
+
+impl fmt::Debug for RetryingMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "RetryingMetricSink {{ max_retries: {}, backoff: {:?}, retries: {} }}",
+            self.max_retries,
+            self.backoff,
+            self.retries()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Backoff, MetricSink, RetryingMetricSink};
+    use crate::test::FailingMetricSink;
+    use std::io;
+
+    // Sink that fails with an error of the given kind the first `failures`
+    // times it's written to
+    fn failing_sink(kind: io::ErrorKind, failures: u64) -> FailingMetricSink {
+        let sink = FailingMetricSink::new(kind);
+        sink.fail_next(failures);
+        sink
+    }
+
+    fn new_sink(wrapped: FailingMetricSink, max_retries: u32) -> RetryingMetricSink {
+        RetryingMetricSink::builder()
+            .with_max_retries(max_retries)
+            .with_backoff(Backoff::none())
+            .build(wrapped)
+    }
+
+    #[test]
+    fn test_retrying_metric_sink_success() {
+        let wrapped = failing_sink(io::ErrorKind::BrokenPipe, 2);
+        let sink = new_sink(wrapped.clone(), 3);
+
+        assert_eq!(7, sink.emit("foo:1|c").unwrap());
+        assert_eq!(3, wrapped.attempts());
+        assert_eq!(2, sink.retries());
+    }
+
+    #[test]
+    fn test_retrying_metric_sink_budget_exhausted() {
+        let wrapped = failing_sink(io::ErrorKind::ConnectionReset, 10);
+        let sink = new_sink(wrapped.clone(), 3);
+
+        let err = sink.emit("foo:1|c").unwrap_err();
+        assert_eq!(io::ErrorKind::ConnectionReset, err.kind());
+        assert_eq!(4, wrapped.attempts());
+        assert_eq!(3, sink.retries());
+    }
+
+    #[test]
+    fn test_retrying_metric_sink_permanent_error() {
+        let wrapped = failing_sink(io::ErrorKind::InvalidInput, 1);
+        let sink = new_sink(wrapped.clone(), 3);
+
+        let err = sink.emit("foo:1|c").unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        assert_eq!(1, wrapped.attempts());
+        assert_eq!(0, sink.retries());
+    }
+
+    #[test]
+    fn test_retrying_metric_sink_no_retries() {
+        let wrapped = failing_sink(io::ErrorKind::BrokenPipe, 1);
+        let sink = new_sink(wrapped.clone(), 0);
+
+        assert!(sink.emit("foo:1|c").is_err());
+        assert_eq!(1, wrapped.attempts());
+    }
+}
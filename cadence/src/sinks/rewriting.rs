@@ -0,0 +1,352 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::io;
+use std::panic::RefUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+use crate::sinks::core::{MetricSink, SinkStats};
+
+/// Rule for changing the name of a metric
+#[derive(Debug, Clone)]
+enum Rule {
+    Prefix { from: String, to: String },
+    Replace { find: String, replace: String },
+    Template { pattern: String, template: String },
+}
+
+impl Rule {
+    /// Return the new name of the metric or `None` if the rule doesn't apply
+    fn apply(&self, name: &str) -> Option<String> {
+        match self {
+            Rule::Prefix { from, to } => name.strip_prefix(from.as_str()).map(|rest| format!("{}{}", to, rest)),
+            Rule::Replace { find, replace } => {
+                if !find.is_empty() && name.contains(find.as_str()) {
+                    Some(name.replace(find.as_str(), replace))
+                } else {
+                    None
+                }
+            }
+            Rule::Template { pattern, template } => {
+                glob_captures(pattern, name).map(|captures| render_template(template, &captures))
+            }
+        }
+    }
+}
+
+/// Match the name against a glob pattern where `*` matches any number of
+/// characters (including none) and `?` matches a single character, returning
+/// the part of the name matched by each `*` if the entire name matches.
+fn glob_captures<'a>(pattern: &str, name: &'a str) -> Option<Vec<&'a str>> {
+    fn match_at(pattern: &[u8], name: &str, n: usize, captures: &mut Vec<(usize, usize)>) -> bool {
+        let rest = &name.as_bytes()[n..];
+        match pattern.first() {
+            None => rest.is_empty(),
+            Some(b'*') => {
+                // Match as few characters as possible so that earlier
+                // captures are as short as they can be.
+                for end in (n..=name.len()).filter(|&i| name.is_char_boundary(i)) {
+                    captures.push((n, end));
+                    if match_at(&pattern[1..], name, end, captures) {
+                        return true;
+                    }
+                    captures.pop();
+                }
+                false
+            }
+            Some(b'?') => match name[n..].chars().next() {
+                Some(c) => match_at(&pattern[1..], name, n + c.len_utf8(), captures),
+                None => false,
+            },
+            Some(&c) => rest.first() == Some(&c) && match_at(&pattern[1..], name, n + 1, captures),
+        }
+    }
+
+    let mut captures = Vec::new();
+    if match_at(pattern.as_bytes(), name, 0, &mut captures) {
+        Some(captures.into_iter().map(|(start, end)| &name[start..end]).collect())
+    } else {
+        None
+    }
+}
+
+/// Replace `{1}`, `{2}`, etc. in the template with the matching capture.
+/// Placeholders without a matching capture are left as-is.
+fn render_template(template: &str, captures: &[&str]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let capture = rest
+            .find('}')
+            .and_then(|end| rest[1..end].parse::<usize>().ok().map(|i| (i, end)))
+            .and_then(|(i, end)| i.checked_sub(1).and_then(|i| captures.get(i)).map(|c| (c, end)));
+
+        match capture {
+            Some((capture, end)) => {
+                out.push_str(capture);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.push('{');
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+    out
+}
+
+/// Implementation of a builder pattern for `RewritingMetricSink`.
+///
+/// Rules are applied to the name of each metric in the order they are added,
+/// each one to the result of the previous rule.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::net::UdpSocket;
+/// use cadence::{MetricSink, RewritingMetricSinkBuilder, UdpMetricSink, DEFAULT_PORT};
+///
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let udp = UdpMetricSink::from(("localhost", DEFAULT_PORT), socket).unwrap();
+///
+/// let sink = RewritingMetricSinkBuilder::new()
+///     .with_prefix("legacy.", "myapp.")
+///     .with_template("myapp.*.requests.*", "myapp.http.{2}.{1}")
+///     .build(udp);
+///
+/// // Sent as "myapp.http.errors.users:1|c"
+/// sink.emit("legacy.users.requests.errors:1|c");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RewritingMetricSinkBuilder {
+    rules: Vec<Rule>,
+}
+
+impl RewritingMetricSinkBuilder {
+    /// Construct a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Replace the prefix `from` of metric names with `to`. Names that don't
+    /// start with `from` are unchanged.
+    pub fn with_prefix<T, U>(mut self, from: T, to: U) -> Self
+    where
+        T: Into<String>,
+        U: Into<String>,
+    {
+        self.rules.push(Rule::Prefix {
+            from: from.into(),
+            to: to.into(),
+        });
+        self
+    }
+
+    /// Replace every occurrence of `find` in metric names with `replace`.
+    pub fn with_replace<T, U>(mut self, find: T, replace: U) -> Self
+    where
+        T: Into<String>,
+        U: Into<String>,
+    {
+        self.rules.push(Rule::Replace {
+            find: find.into(),
+            replace: replace.into(),
+        });
+        self
+    }
+
+    /// Replace metric names that match the glob pattern with the template.
+    ///
+    /// In the pattern, `*` matches any number of characters (including none)
+    /// and `?` matches a single character. The pattern must match the entire
+    /// name of the metric. In the template, `{1}` is replaced with the part of
+    /// the name matched by the first `*`, `{2}` with the second, and so on.
+    /// Each `*` matches as little of the name as possible.
+    pub fn with_template<T, U>(mut self, pattern: T, template: U) -> Self
+    where
+        T: Into<String>,
+        U: Into<String>,
+    {
+        self.rules.push(Rule::Template {
+            pattern: pattern.into(),
+            template: template.into(),
+        });
+        self
+    }
+
+    /// Construct a new `RewritingMetricSink` instance wrapping the given sink
+    /// based on the builder configuration.
+    pub fn build<T>(self, sink: T) -> RewritingMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        RewritingMetricSink {
+            sink: Box::new(sink),
+            rules: self.rules,
+            rewritten: AtomicU64::new(0),
+        }
+    }
+}
+
+/// Implementation of a `MetricSink` that changes the names of metrics
+/// before writing them to a wrapped sink.
+///
+/// This can be used to migrate to new metric names without changing the code
+/// of every application that emits them at the same time. Names can be
+/// changed by replacing a prefix, replacing part of the name, or using a
+/// template filled in from a glob pattern. Rules are set using
+/// `RewritingMetricSinkBuilder`.
+///
+/// Only the name of each metric is changed, the value, type, and tags of the
+/// metric are written unchanged. Events and service checks are never changed.
+pub struct RewritingMetricSink {
+    sink: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
+    rules: Vec<Rule>,
+    rewritten: AtomicU64,
+}
+
+impl RewritingMetricSink {
+    /// Construct a new builder for `RewritingMetricSink`.
+    pub fn builder() -> RewritingMetricSinkBuilder {
+        RewritingMetricSinkBuilder::new()
+    }
+
+    /// Return the number of metrics that have had their name changed.
+    pub fn rewritten(&self) -> u64 {
+        self.rewritten.load(Ordering::Relaxed)
+    }
+
+    /// Return the metric with a new name or `None` if no rules apply to it
+    fn rewrite(&self, metric: &str) -> Option<String> {
+        // Events and service checks don't have a metric name
+        if metric.starts_with("_e{") || metric.starts_with("_sc|") {
+            return None;
+        }
+
+        let (name, rest) = match metric.find(':') {
+            Some(i) => metric.split_at(i),
+            None => (metric, ""),
+        };
+
+        let mut renamed: Option<String> = None;
+        for rule in self.rules.iter() {
+            if let Some(n) = rule.apply(renamed.as_deref().unwrap_or(name)) {
+                renamed = Some(n);
+            }
+        }
+
+        renamed.map(|mut n| {
+            n.push_str(rest);
+            n
+        })
+    }
+}
+
+impl MetricSink for RewritingMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        match self.rewrite(metric) {
+            Some(rewritten) => {
+                self.rewritten.fetch_add(1, Ordering::Relaxed);
+                self.sink.emit(&rewritten)
+            }
+            None => self.sink.emit(metric),
+        }
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.sink.flush()
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.sink.check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats()
+    }
+}
+
+impl fmt::Debug for RewritingMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "RewritingMetricSink {{ rules: {:?}, rewritten: {} }}",
+            self.rules,
+            self.rewritten()
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{glob_captures, render_template, MetricSink, RewritingMetricSink};
+    use crate::sinks::spy::SpyMetricSink;
+
+    #[test]
+    fn test_glob_captures() {
+        assert_eq!(Some(vec![]), glob_captures("foo.bar", "foo.bar"));
+        assert_eq!(Some(vec!["bar"]), glob_captures("foo.*", "foo.bar"));
+        assert_eq!(Some(vec!["a", "b.c"]), glob_captures("*.*", "a.b.c"));
+        assert_eq!(Some(vec!["", "x"]), glob_captures("*foo.*", "foo.x"));
+        assert_eq!(Some(vec!["é"]), glob_captures("caf*", "café"));
+        assert_eq!(Some(vec![]), glob_captures("caf?", "café"));
+        assert_eq!(None, glob_captures("foo.*", "bar.foo"));
+        assert_eq!(None, glob_captures("foo.?", "foo."));
+    }
+
+    #[test]
+    fn test_render_template() {
+        assert_eq!("b.a", render_template("{2}.{1}", &["a", "b"]));
+        assert_eq!("a.{3}.{x}.{0}", render_template("{1}.{3}.{x}.{0}", &["a", "b"]));
+        assert_eq!("a{", render_template("{1}{", &["a"]));
+    }
+
+    #[test]
+    fn test_rewriting_metric_sink_prefix() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = RewritingMetricSink::builder().with_prefix("old.", "new.").build(spy);
+
+        sink.emit("old.foo:1|c|#old.tag:old.value").unwrap();
+        sink.emit("other.old.foo:1|c").unwrap();
+
+        assert_eq!(b"new.foo:1|c|#old.tag:old.value".to_vec(), rx.try_recv().unwrap());
+        assert_eq!(b"other.old.foo:1|c".to_vec(), rx.try_recv().unwrap());
+        assert_eq!(1, sink.rewritten());
+    }
+
+    #[test]
+    fn test_rewriting_metric_sink_rules_in_order() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = RewritingMetricSink::builder()
+            .with_replace("-", "_")
+            .with_template("api.*.requests", "http.requests.{1}")
+            .build(spy);
+
+        sink.emit("api.get-user.requests:12|ms").unwrap();
+        assert_eq!(b"http.requests.get_user:12|ms".to_vec(), rx.try_recv().unwrap());
+    }
+
+    #[test]
+    fn test_rewriting_metric_sink_events() {
+        let (rx, spy) = SpyMetricSink::new();
+        let sink = RewritingMetricSink::builder().with_replace("title", "other").build(spy);
+
+        sink.emit("_e{5,4}:title|text").unwrap();
+        assert_eq!(b"_e{5,4}:title|text".to_vec(), rx.try_recv().unwrap());
+        assert_eq!(0, sink.rewritten());
+    }
+}
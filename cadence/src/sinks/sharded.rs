@@ -0,0 +1,225 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::io;
+use std::panic::RefUnwindSafe;
+
+use crate::sinks::core::{MetricSink, SinkStats};
+
+/// Implementation of a builder pattern for `ShardedMetricSink`.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::net::UdpSocket;
+/// use cadence::{MetricSink, ShardedMetricSinkBuilder, UdpMetricSink, DEFAULT_PORT};
+///
+/// let mut builder = ShardedMetricSinkBuilder::new();
+/// for host in ["statsd1.example.com", "statsd2.example.com", "statsd3.example.com"] {
+///     let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+///     builder = builder.with_sink(UdpMetricSink::from((host, DEFAULT_PORT), socket).unwrap());
+/// }
+///
+/// let sink = builder.build();
+/// sink.emit("foo.counter:4|c");
+/// ```
+#[derive(Default)]
+pub struct ShardedMetricSinkBuilder {
+    sinks: Vec<Box<dyn MetricSink + Sync + Send + RefUnwindSafe>>,
+}
+
+impl ShardedMetricSinkBuilder {
+    /// Construct a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a sink to route metrics to. The shard a metric is routed to depends
+    /// on the number of sinks and the order they are added in, so every process
+    /// sending metrics to the same servers must add them in the same order.
+    pub fn with_sink<T>(mut self, sink: T) -> Self
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        self.sinks.push(Box::new(sink));
+        self
+    }
+
+    /// Construct a new `ShardedMetricSink` instance based on the builder configuration.
+    pub fn build(self) -> ShardedMetricSink {
+        ShardedMetricSink { sinks: self.sinks }
+    }
+}
+
+impl fmt::Debug for ShardedMetricSinkBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ShardedMetricSinkBuilder {{ sinks: {} }}", self.sinks.len())
+    }
+}
+
+/// Hash the name of a metric using 64-bit FNV-1a.
+///
+/// The hash must be the same in every process and every version of Rust so
+/// that different applications route the same metric to the same shard. This
+/// rules out the hashers from the standard library.
+fn hash_name(metric: &str) -> u64 {
+    let name = match metric.find(':') {
+        Some(i) => &metric[..i],
+        None => metric,
+    };
+
+    name.bytes().fold(0xcbf2_9ce4_8422_2325, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Map a hash to one of `buckets` using the jump consistent hash algorithm.
+///
+/// When the number of buckets changes from N to N + 1, only 1 / (N + 1) of
+/// the keys move to a different bucket. See https://arxiv.org/abs/1406.2294
+fn jump_hash(mut key: u64, buckets: usize) -> usize {
+    let mut b: i64 = -1;
+    let mut j: i64 = 0;
+
+    while j < buckets as i64 {
+        b = j;
+        key = key.wrapping_mul(2_862_933_555_777_941_757).wrapping_add(1);
+        j = ((b + 1) as f64 * ((1u64 << 31) as f64 / ((key >> 33) + 1) as f64)) as i64;
+    }
+
+    b as usize
+}
+
+/// Implementation of a `MetricSink` that routes each metric to one of
+/// several other sinks based on the name of the metric.
+///
+/// This is useful when Statsd is scaled horizontally across multiple servers
+/// that each need to see every value of a given metric to aggregate it
+/// correctly. Every metric with the same name (regardless of tags) is
+/// always written to the same sink, in the thread of the caller.
+///
+/// Metrics are assigned to sinks using a stable hash of their name and a
+/// consistent hashing algorithm, so the same metric is routed to the same
+/// sink by every process using the same list of sinks. Adding a sink to the
+/// end of the list only moves a fraction of metrics to the new sink.
+///
+/// Flushing this sink flushes every wrapped sink and returns the first
+/// error, if any. Stats returned by this sink are the sum of the stats of
+/// every wrapped sink.
+pub struct ShardedMetricSink {
+    sinks: Vec<Box<dyn MetricSink + Sync + Send + RefUnwindSafe>>,
+}
+
+impl ShardedMetricSink {
+    /// Construct a new builder for `ShardedMetricSink`.
+    pub fn builder() -> ShardedMetricSinkBuilder {
+        ShardedMetricSinkBuilder::new()
+    }
+
+    /// Return the index of the sink that the given metric is routed to.
+    fn shard(&self, metric: &str) -> usize {
+        jump_hash(hash_name(metric), self.sinks.len())
+    }
+}
+
+impl MetricSink for ShardedMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        if self.sinks.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::Other, "no sinks to route metric to"));
+        }
+
+        self.sinks[self.shard(metric)].emit(metric)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let mut res = Ok(());
+        for sink in self.sinks.iter() {
+            if let Err(e) = sink.flush() {
+                if res.is_ok() {
+                    res = Err(e);
+                }
+            }
+        }
+
+        res
+    }
+
+    fn check(&self) -> io::Result<()> {
+        // Every shard is used for some metrics so all of them need to work
+        self.sinks.iter().try_for_each(|sink| sink.check())
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sinks
+            .iter()
+            .fold(SinkStats::default(), |acc, sink| acc.combine(sink.stats()))
+    }
+}
+
+impl fmt::Debug for ShardedMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "ShardedMetricSink {{ sinks: {} }}", self.sinks.len())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{hash_name, jump_hash, MetricSink, ShardedMetricSink};
+    use crate::sinks::spy::SpyMetricSink;
+
+    #[test]
+    fn test_hash_name_stable() {
+        // Known FNV-1a values, these must never change
+        assert_eq!(0xcbf2_9ce4_8422_2325, hash_name(""));
+        assert_eq!(0xaf63_dc4c_8601_ec8c, hash_name("a:1|c"));
+        assert_eq!(hash_name("foo.bar"), hash_name("foo.bar:1|c|#region:us"));
+    }
+
+    #[test]
+    fn test_jump_hash() {
+        assert_eq!(0, jump_hash(12345, 1));
+        for key in 0..1_000 {
+            assert!(jump_hash(key, 7) < 7);
+        }
+    }
+
+    #[test]
+    fn test_jump_hash_minimal_movement() {
+        let moved = (0..10_000u64)
+            .map(|k| hash_name(&k.to_string()))
+            .filter(|&k| jump_hash(k, 4) != jump_hash(k, 5))
+            .count();
+
+        // About 1/5 of keys should move to the new bucket
+        assert!((1_500..2_500).contains(&moved), "unexpected moved count {}", moved);
+    }
+
+    #[test]
+    fn test_sharded_metric_sink_routes_consistently() {
+        let (rx1, sink1) = SpyMetricSink::new();
+        let (rx2, sink2) = SpyMetricSink::new();
+        let sink = ShardedMetricSink::builder().with_sink(sink1).with_sink(sink2).build();
+
+        for i in 0..10 {
+            sink.emit(&format!("foo.bar:{}|c", i)).unwrap();
+        }
+
+        let (a, b) = (rx1.try_iter().count(), rx2.try_iter().count());
+        assert!((a == 10 && b == 0) || (a == 0 && b == 10), "split {} / {}", a, b);
+    }
+
+    #[test]
+    fn test_sharded_metric_sink_empty() {
+        let sink = ShardedMetricSink::builder().build();
+        assert!(sink.emit("foo:1|c").is_err());
+        assert!(sink.flush().is_ok());
+    }
+}
@@ -0,0 +1,193 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::collections::BTreeMap;
+
+// Values closer to zero than this are counted as zero instead of being
+// given a bucket of their own, which keeps bucket indexes in range.
+const MIN_INDEXABLE: f64 = 1e-9;
+
+/// DDSketch that summarizes values using buckets whose width grows with the
+/// magnitude of the values they hold, so that any quantile can be estimated
+/// to within a fixed relative accuracy.
+///
+/// See https://arxiv.org/abs/1908.10693 for details of the algorithm.
+#[derive(Debug, Clone)]
+pub(crate) struct Sketch {
+    gamma: f64,
+    ln_gamma: f64,
+    positive: BTreeMap<i32, u64>,
+    negative: BTreeMap<i32, u64>,
+    zero: u64,
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Sketch {
+    /// Create a new sketch with the given relative accuracy, which must be
+    /// greater than zero and less than one.
+    pub(crate) fn new(relative_accuracy: f64) -> Self {
+        let gamma = (1.0 + relative_accuracy) / (1.0 - relative_accuracy);
+        Sketch {
+            gamma,
+            ln_gamma: gamma.ln(),
+            positive: BTreeMap::new(),
+            negative: BTreeMap::new(),
+            zero: 0,
+            count: 0,
+            sum: 0.0,
+            min: f64::INFINITY,
+            max: f64::NEG_INFINITY,
+        }
+    }
+
+    /// Add a value to the sketch, ignoring values that aren't finite.
+    pub(crate) fn add(&mut self, value: f64) {
+        if !value.is_finite() {
+            return;
+        }
+
+        if value > MIN_INDEXABLE {
+            *self.positive.entry(self.index(value)).or_insert(0) += 1;
+        } else if value < -MIN_INDEXABLE {
+            *self.negative.entry(self.index(-value)).or_insert(0) += 1;
+        } else {
+            self.zero += 1;
+        }
+
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+
+    pub(crate) fn count(&self) -> u64 {
+        self.count
+    }
+
+    pub(crate) fn sum(&self) -> f64 {
+        self.sum
+    }
+
+    pub(crate) fn min(&self) -> Option<f64> {
+        if self.count > 0 {
+            Some(self.min)
+        } else {
+            None
+        }
+    }
+
+    pub(crate) fn max(&self) -> Option<f64> {
+        if self.count > 0 {
+            Some(self.max)
+        } else {
+            None
+        }
+    }
+
+    /// Return the value of every non-empty bucket and the number of values
+    /// in it, from the smallest value to the largest.
+    pub(crate) fn buckets(&self) -> Vec<(f64, u64)> {
+        let negative = self.negative.iter().rev().map(|(&i, &n)| (-self.value(i), n));
+        let zero = Some((0.0, self.zero)).filter(|(_, n)| *n > 0);
+        let positive = self.positive.iter().map(|(&i, &n)| (self.value(i), n));
+        negative.chain(zero).chain(positive).collect()
+    }
+
+    /// Estimate the value at the given quantile, between zero and one, or
+    /// return `None` if the sketch is empty.
+    pub(crate) fn quantile(&self, q: f64) -> Option<f64> {
+        if self.count == 0 {
+            return None;
+        }
+
+        let rank = (q.clamp(0.0, 1.0) * (self.count - 1) as f64) as u64;
+        let mut seen = 0;
+        for (value, n) in self.buckets() {
+            seen += n;
+            if seen > rank {
+                // Estimates are never outside the range of values actually added
+                return Some(value.max(self.min).min(self.max));
+            }
+        }
+
+        Some(self.max)
+    }
+
+    fn index(&self, value: f64) -> i32 {
+        (value.ln() / self.ln_gamma).ceil() as i32
+    }
+
+    /// Value that represents every value in a bucket to within the relative
+    /// accuracy of the sketch.
+    fn value(&self, index: i32) -> f64 {
+        2.0 * self.gamma.powi(index) / (self.gamma + 1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Sketch;
+
+    fn assert_within(expected: f64, actual: f64, accuracy: f64) {
+        let error = ((actual - expected) / expected).abs();
+        assert!(
+            error <= accuracy,
+            "expected {} within {} but got {}",
+            expected,
+            accuracy,
+            actual
+        );
+    }
+
+    #[test]
+    fn test_sketch_empty() {
+        let sketch = Sketch::new(0.01);
+        assert_eq!(0, sketch.count());
+        assert_eq!(None, sketch.min());
+        assert_eq!(None, sketch.quantile(0.5));
+        assert!(sketch.buckets().is_empty());
+    }
+
+    #[test]
+    fn test_sketch_quantiles() {
+        let mut sketch = Sketch::new(0.01);
+        for v in 1..=1000 {
+            sketch.add(v as f64);
+        }
+
+        assert_eq!(1000, sketch.count());
+        assert_eq!(500500.0, sketch.sum());
+        assert_eq!(Some(1.0), sketch.min());
+        assert_eq!(Some(1000.0), sketch.max());
+        assert_eq!(Some(1.0), sketch.quantile(0.0));
+        assert_within(500.0, sketch.quantile(0.5).unwrap(), 0.01);
+        assert_within(990.0, sketch.quantile(0.99).unwrap(), 0.01);
+        assert_eq!(Some(1000.0), sketch.quantile(1.0));
+    }
+
+    #[test]
+    fn test_sketch_buckets() {
+        let mut sketch = Sketch::new(0.01);
+        for v in &[-10.0, 0.0, 100.0, 100.5, 1000.0, f64::NAN] {
+            sketch.add(*v);
+        }
+
+        let buckets = sketch.buckets();
+        let counts: Vec<u64> = buckets.iter().map(|(_, n)| *n).collect();
+        assert_eq!(vec![1, 1, 2, 1], counts);
+        assert_within(-10.0, buckets[0].0, 0.01);
+        assert_eq!(0.0, buckets[1].0);
+        assert_within(100.0, buckets[2].0, 0.01);
+        assert_within(1000.0, buckets[3].0, 0.01);
+    }
+}
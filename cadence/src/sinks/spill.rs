@@ -0,0 +1,176 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, ErrorKind, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// File that metrics are appended to, one per line, when they can't be kept
+/// in memory and read back all at once when there's room for them again.
+///
+/// The file is only opened the first time it's used and its size is limited
+/// to a maximum number of bytes. Metrics left in the file by a previous process
+/// are read back along with any new metrics.
+#[derive(Debug)]
+pub(crate) struct SpillFile {
+    path: PathBuf,
+    max_bytes: u64,
+    state: Mutex<SpillState>,
+}
+
+#[derive(Debug, Default)]
+struct SpillState {
+    file: Option<File>,
+    len: u64,
+}
+
+impl SpillFile {
+    pub(crate) fn new<P>(path: P, max_bytes: u64) -> SpillFile
+    where
+        P: AsRef<Path>,
+    {
+        SpillFile {
+            path: path.as_ref().to_path_buf(),
+            max_bytes,
+            state: Mutex::new(SpillState::default()),
+        }
+    }
+
+    fn open(&self, state: &mut SpillState) -> io::Result<()> {
+        if state.file.is_none() {
+            let mut file = OpenOptions::new()
+                .read(true)
+                .write(true)
+                .create(true)
+                .truncate(false)
+                .open(&self.path)?;
+            state.len = file.seek(SeekFrom::End(0))?;
+            state.file = Some(file);
+        }
+
+        Ok(())
+    }
+
+    /// Append a metric to the file, returning `false` if there isn't room for it.
+    pub(crate) fn push(&self, metric: &str) -> io::Result<bool> {
+        if metric.contains('\n') {
+            return Err(io::Error::new(ErrorKind::InvalidInput, "metric contains a newline"));
+        }
+
+        let mut state = self.state.lock().unwrap();
+        self.open(&mut state)?;
+
+        let size = metric.len() as u64 + 1;
+        if state.len + size > self.max_bytes {
+            return Ok(false);
+        }
+
+        let mut line = String::with_capacity(metric.len() + 1);
+        line.push_str(metric);
+        line.push('\n');
+
+        // Unwrap is safe since the file was just opened if it wasn't already
+        state.file.as_mut().unwrap().write_all(line.as_bytes())?;
+        state.len += size;
+        Ok(true)
+    }
+
+    /// Remove and return every metric in the file.
+    pub(crate) fn drain(&self) -> io::Result<Vec<String>> {
+        let mut state = self.state.lock().unwrap();
+        // Only create the file when writing to it, but still read any metrics
+        // left behind by a previous process.
+        if state.file.is_none() && !self.path.exists() {
+            return Ok(Vec::new());
+        }
+
+        self.open(&mut state)?;
+        if state.len == 0 {
+            return Ok(Vec::new());
+        }
+
+        let file = state.file.as_mut().unwrap();
+        let mut contents = String::new();
+        file.seek(SeekFrom::Start(0))?;
+        file.read_to_string(&mut contents)?;
+        file.set_len(0)?;
+        file.seek(SeekFrom::Start(0))?;
+        state.len = 0;
+
+        Ok(contents.lines().map(|s| s.to_string()).collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SpillFile;
+    use crate::test::TempDir;
+
+    #[test]
+    fn test_spill_file_push_drain() {
+        let dir = TempDir::new("cadence-spill-push-drain").unwrap();
+        let spill = SpillFile::new(dir.new_path("spill"), 1024);
+
+        assert!(spill.push("foo:1|c").unwrap());
+        assert!(spill.push("bar:2|g").unwrap());
+
+        assert_eq!(vec!["foo:1|c", "bar:2|g"], spill.drain().unwrap());
+        assert!(spill.drain().unwrap().is_empty());
+
+        assert!(spill.push("baz:3|ms").unwrap());
+        assert_eq!(vec!["baz:3|ms"], spill.drain().unwrap());
+    }
+
+    #[test]
+    fn test_spill_file_full() {
+        let dir = TempDir::new("cadence-spill-full").unwrap();
+        let spill = SpillFile::new(dir.new_path("spill"), 16);
+
+        assert!(spill.push("foo:1|c").unwrap());
+        assert!(spill.push("bar:2|c").unwrap());
+        assert!(!spill.push("baz:3|c").unwrap());
+
+        assert_eq!(vec!["foo:1|c", "bar:2|c"], spill.drain().unwrap());
+        assert!(spill.push("baz:3|c").unwrap());
+    }
+
+    #[test]
+    fn test_spill_file_left_by_previous_process() {
+        let dir = TempDir::new("cadence-spill-previous").unwrap();
+        let path = dir.new_path("spill");
+
+        {
+            let spill = SpillFile::new(&path, 1024);
+            assert!(spill.push("foo:1|c").unwrap());
+        }
+
+        let spill = SpillFile::new(&path, 1024);
+        assert_eq!(vec!["foo:1|c"], spill.drain().unwrap());
+    }
+
+    #[test]
+    fn test_spill_file_missing() {
+        let dir = TempDir::new("cadence-spill-missing").unwrap();
+        let path = dir.new_path("spill");
+        let spill = SpillFile::new(&path, 1024);
+
+        assert!(spill.drain().unwrap().is_empty());
+        assert!(!path.exists());
+    }
+
+    #[test]
+    fn test_spill_file_newline() {
+        let dir = TempDir::new("cadence-spill-newline").unwrap();
+        let spill = SpillFile::new(dir.new_path("spill"), 1024);
+
+        assert!(spill.push("foo:1|c\nbar:2|c").is_err());
+    }
+}
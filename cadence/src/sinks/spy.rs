@@ -9,10 +9,11 @@
 // except according to those terms.
 
 use crate::io::MultiLineWriter;
-use crate::sinks::core::MetricSink;
+use crate::sinks::core::{emit_each, MetricSink};
+use crate::types::{queue_full, WriteMetric};
 use crossbeam_channel::{bounded, unbounded, Receiver, Sender, TrySendError};
 use std::io::{self, ErrorKind, Write};
-use std::sync::Mutex;
+use std::sync::{Arc, Mutex};
 
 // Default size of the buffer for buffered metric sinks, picked for
 // consistency with the UDP implementation.
@@ -54,6 +55,84 @@ impl MetricSink for SpyMetricSink {
     }
 }
 
+/// `MetricSink` implementation that records every metric written to it so
+/// that tests can check which metrics were emitted.
+///
+/// This is not a general purpose sink, rather it's a sink meant for verifying
+/// metrics emitted by an application or library in its tests without using a
+/// socket or channel. Metrics are stored in memory as strings, in the order they
+/// were emitted, until they are removed with the `.take()` or `.clear()` methods.
+///
+/// Clones of this sink share the same recorded metrics so one clone can be
+/// given to a `StatsdClient` while another is kept to inspect what was sent.
+///
+/// # Example
+///
+/// ```
+/// use cadence::prelude::*;
+/// use cadence::{RecordingMetricSink, StatsdClient};
+///
+/// let sink = RecordingMetricSink::new();
+/// let client = StatsdClient::from_sink("my.app", sink.clone());
+///
+/// client.incr("requests").unwrap();
+/// client.time_with_tags("latency", 23).with_tag("status", "200").send();
+///
+/// assert_eq!(
+///     vec!["my.app.requests:1|c", "my.app.latency:23|ms|#status:200"],
+///     sink.take()
+/// );
+/// assert!(sink.is_empty());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct RecordingMetricSink {
+    metrics: Arc<Mutex<Vec<String>>>,
+}
+
+impl RecordingMetricSink {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Return a copy of every metric recorded so far, oldest first.
+    pub fn metrics(&self) -> Vec<String> {
+        self.metrics.lock().unwrap().clone()
+    }
+
+    /// Remove and return every metric recorded so far, oldest first.
+    pub fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.metrics.lock().unwrap())
+    }
+
+    /// Remove every metric recorded so far.
+    pub fn clear(&self) {
+        self.metrics.lock().unwrap().clear();
+    }
+
+    /// Return the number of metrics recorded.
+    pub fn len(&self) -> usize {
+        self.metrics.lock().unwrap().len()
+    }
+
+    /// Return true if no metrics have been recorded.
+    pub fn is_empty(&self) -> bool {
+        self.metrics.lock().unwrap().is_empty()
+    }
+}
+
+impl MetricSink for RecordingMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        self.metrics.lock().unwrap().push(metric.to_owned());
+        Ok(metric.len())
+    }
+
+    fn emit_batch(&self, metrics: &[&str]) -> io::Result<usize> {
+        let mut recorded = self.metrics.lock().unwrap();
+        recorded.extend(metrics.iter().map(|m| (*m).to_owned()));
+        Ok(metrics.iter().map(|m| m.len()).sum())
+    }
+}
+
 /// `MetricSink` implementation that buffers metrics and writes them to the
 /// `Sender` half of a channel while callers are given ownership of the `Receiver`
 /// half.
@@ -107,6 +186,16 @@ impl MetricSink for BufferedSpyMetricSink {
         writer.write(metric.as_bytes())
     }
 
+    fn emit_metric(&self, metric: &dyn WriteMetric) -> io::Result<usize> {
+        let mut writer = self.writer.lock().unwrap();
+        writer.write_metric(metric)
+    }
+
+    fn emit_batch(&self, metrics: &[&str]) -> io::Result<usize> {
+        let mut writer = self.writer.lock().unwrap();
+        emit_each(metrics, |m| writer.write(m.as_bytes()))
+    }
+
     fn flush(&self) -> io::Result<()> {
         let mut writer = self.writer.lock().unwrap();
         writer.flush()
@@ -145,14 +234,15 @@ fn new_channel(cap: Option<usize>) -> (Sender<Vec<u8>>, Receiver<Vec<u8>>) {
 fn send_metric(sender: &Sender<Vec<u8>>, metric: &[u8]) -> io::Result<usize> {
     match sender.try_send(metric.to_vec()) {
         Err(TrySendError::Disconnected(_)) => Err(io::Error::new(ErrorKind::Other, "channel disconnected")),
-        Err(TrySendError::Full(_)) => Err(io::Error::new(ErrorKind::Other, "channel full")),
+        Err(TrySendError::Full(_)) => Err(queue_full()),
         Ok(_) => Ok(metric.len()),
     }
 }
 
 #[cfg(test)]
 mod test {
-    use super::{BufferedSpyMetricSink, MetricSink, SpyMetricSink};
+    use super::{BufferedSpyMetricSink, MetricSink, RecordingMetricSink, SpyMetricSink};
+    use crate::types::Counter;
 
     #[test]
     fn test_spy_metric_sink() {
@@ -163,6 +253,22 @@ mod test {
         assert_eq!(b"buz:1|c", sent.as_slice());
     }
 
+    #[test]
+    fn test_recording_metric_sink() {
+        let sink = RecordingMetricSink::new();
+        let clone = sink.clone();
+        sink.emit("foo:1|c").unwrap();
+        clone.emit_metric(&Counter::from("foo:2|c".to_owned())).unwrap();
+        clone.emit_batch(&["bar:3|g", "baz:4|ms"]).unwrap();
+
+        assert_eq!(4, sink.len());
+        assert_eq!(vec!["foo:1|c", "foo:2|c", "bar:3|g", "baz:4|ms"], sink.metrics());
+
+        sink.clear();
+        assert!(clone.is_empty());
+        assert!(sink.take().is_empty());
+    }
+
     #[test]
     fn test_buffered_spy_metric_sink() {
         // Make sure the sink is dropped before checking what was written
@@ -178,6 +284,17 @@ mod test {
         assert_eq!(b"foo:54|c\nfoo:67|c\n", sent.as_slice());
     }
 
+    #[test]
+    fn test_buffered_spy_metric_sink_emit_metric() {
+        let (rx, sink) = BufferedSpyMetricSink::with_capacity(None, Some(64));
+        sink.emit_metric(&Counter::from("foo:54|c".to_owned())).unwrap();
+        sink.emit("foo:67|c").unwrap();
+        sink.flush().unwrap();
+
+        let sent = rx.recv().unwrap();
+        assert_eq!(b"foo:54|c\nfoo:67|c\n", sent.as_slice());
+    }
+
     #[test]
     fn test_buffered_spy_metric_sink_flush() {
         // Set the capacity of the buffer such that it won't be flushed
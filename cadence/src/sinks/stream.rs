@@ -0,0 +1,480 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::io;
+use std::io::{IoSlice, Write};
+use std::time::Instant;
+
+use crate::sinks::backoff::{Backoff, BackoffState};
+use crate::sinks::core::SocketStats;
+
+/// What stream based sinks should do with metrics while they are unable to
+/// write to the server.
+///
+/// Metrics can't be written while the connection to the server is broken and
+/// while waiting to reconnect to the server after a failure.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DisconnectPolicy {
+    /// Drop metrics that can't be written and return an error.
+    ///
+    /// This is the default.
+    Drop,
+    /// Hold up to the given number of bytes of metrics in memory and write them
+    /// after reconnecting to the server. Metrics that don't fit are dropped and
+    /// an error is returned.
+    ///
+    /// Note that metrics being written when a connection breaks may be written
+    /// again after reconnecting if the server received some of them.
+    Buffer(usize),
+}
+
+impl Default for DisconnectPolicy {
+    fn default() -> Self {
+        DisconnectPolicy::Drop
+    }
+}
+
+/// Way to establish a new connection to a server for stream based sinks.
+pub(crate) trait Connector {
+    type Stream: Write + fmt::Debug;
+
+    fn connect(&self) -> io::Result<Self::Stream>;
+}
+
+/// Adapter for writing to a stream (TCP, Unix, etc.) via the `Write` trait
+///
+/// The connection to the server is established when the first write is made.
+/// If a connection or write fails, the connection is discarded and a new
+/// connection will be made the next time there is something to write, once
+/// the backoff delay has passed. What happens to writes that can't be made
+/// while disconnected is determined by the `DisconnectPolicy`.
+#[derive(Debug)]
+pub(crate) struct StreamWriter<C>
+where
+    C: Connector,
+{
+    connector: C,
+    stream: Option<C::Stream>,
+    backoff: BackoffState,
+    policy: DisconnectPolicy,
+    pending: Vec<u8>,
+    error: Option<io::Error>,
+    stats: SocketStats,
+}
+
+impl<C> StreamWriter<C>
+where
+    C: Connector,
+{
+    pub(crate) fn new(connector: C, backoff: Backoff, policy: DisconnectPolicy, stats: SocketStats) -> Self {
+        StreamWriter {
+            connector,
+            stream: None,
+            backoff: BackoffState::new(backoff),
+            policy,
+            pending: Vec::new(),
+            error: None,
+            stats,
+        }
+    }
+
+    /// Connect to the server if there isn't a connection already, without
+    /// waiting for the backoff delay, returning an error if it can't be reached.
+    pub(crate) fn check(&mut self) -> io::Result<()> {
+        if self.stream.is_none() {
+            self.stream = Some(self.connector.connect()?);
+            self.backoff.success();
+        }
+
+        Ok(())
+    }
+
+    /// Write a single metric followed by a newline, returning the number of
+    /// bytes of the metric written.
+    pub(crate) fn write_line(&mut self, line: &[u8]) -> io::Result<usize> {
+        self.write_bufs(&[line, b"\n"]).map(|_| line.len())
+    }
+
+    fn write_bufs(&mut self, bufs: &[&[u8]]) -> io::Result<()> {
+        let len: usize = bufs.iter().map(|b| b.len()).sum();
+        let now = Instant::now();
+
+        match self.write_to_stream(bufs, now) {
+            Ok(()) => {
+                self.stats.incr_bytes_sent((self.pending.len() + len) as u64);
+                self.stats.incr_packets_sent();
+                self.pending.clear();
+                self.backoff.success();
+                Ok(())
+            }
+            Err(e) => match self.policy {
+                DisconnectPolicy::Buffer(max) if self.pending.len() + len <= max => {
+                    for buf in bufs {
+                        self.pending.extend_from_slice(buf);
+                    }
+                    Ok(())
+                }
+                _ => {
+                    if len > 0 {
+                        self.stats.incr_bytes_dropped(len as u64);
+                        self.stats.incr_packets_dropped();
+                    }
+                    Err(e)
+                }
+            },
+        }
+    }
+
+    fn write_to_stream(&mut self, bufs: &[&[u8]], now: Instant) -> io::Result<()> {
+        let stream = match self.stream {
+            Some(ref mut stream) => stream,
+            None => {
+                if !self.backoff.is_ready(now) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotConnected,
+                        "waiting to reconnect to server",
+                    ));
+                }
+
+                match self.connector.connect() {
+                    Ok(stream) => self.stream.insert(stream),
+                    Err(e) => {
+                        self.backoff.failure(now);
+                        return Err(e);
+                    }
+                }
+            }
+        };
+
+        let res = write_all_bufs(stream, &self.pending, bufs);
+        if res.is_err() {
+            // The state of the connection is unknown after a failed write
+            // (part of a metric may have been written) so start over with
+            // a new connection for the next write.
+            self.stream = None;
+            self.backoff.failure(now);
+        }
+
+        res
+    }
+}
+
+// Write anything pending from before a disconnect followed by the buffers,
+// using a single vectored write when the stream supports it.
+fn write_all_bufs<W: Write>(stream: &mut W, pending: &[u8], bufs: &[&[u8]]) -> io::Result<()> {
+    match *bufs {
+        [] => write_all_vectored(stream, [pending]),
+        [a] => write_all_vectored(stream, [pending, a]),
+        [a, b] => write_all_vectored(stream, [pending, a, b]),
+        _ => {
+            stream.write_all(pending)?;
+            bufs.iter().try_for_each(|buf| stream.write_all(buf))
+        }
+    }
+}
+
+// Write every buffer, the same as the unstable `Write::write_all_vectored`.
+// Streams that don't support vectored writes only write the first non-empty
+// buffer each time, the same as calling `.write_all()` for each of them.
+fn write_all_vectored<W: Write, const N: usize>(stream: &mut W, mut bufs: [&[u8]; N]) -> io::Result<()> {
+    while bufs.iter().any(|buf| !buf.is_empty()) {
+        let mut written = match stream.write_vectored(&bufs.map(IoSlice::new)) {
+            Ok(0) => return Err(io::Error::new(io::ErrorKind::WriteZero, "failed to write whole buffer")),
+            Ok(n) => n,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        };
+
+        for buf in bufs.iter_mut() {
+            let n = written.min(buf.len());
+            *buf = &buf[n..];
+            written -= n;
+        }
+    }
+
+    Ok(())
+}
+
+impl<C> Write for StreamWriter<C>
+where
+    C: Connector,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // Errors are saved and returned when flushing instead of being returned
+        // here. If an error was returned, the `BufWriter` this is typically used
+        // with would hold on to the data and try to write it again on the next
+        // flush, ignoring the disconnect policy.
+        if let Err(e) = self.write_bufs(&[buf]) {
+            self.error = Some(e);
+        }
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        if let Some(e) = self.error.take() {
+            return Err(e);
+        }
+
+        if !self.pending.is_empty() {
+            self.write_bufs(&[])?;
+        }
+
+        match self.stream {
+            Some(ref mut stream) => stream.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{write_all_bufs, Connector, DisconnectPolicy, StreamWriter};
+    use crate::sinks::backoff::Backoff;
+    use crate::sinks::core::{SinkStats, SocketStats};
+    use std::io;
+    use std::io::{IoSlice, Write};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    // Everything written to each stream, along with the ID of the stream
+    type Written = Arc<Mutex<Vec<(usize, Vec<u8>)>>>;
+
+    /// Stream that records everything written to it and fails all writes
+    /// after a certain number of them.
+    #[derive(Debug)]
+    struct FlakyStream {
+        id: usize,
+        writes_left: usize,
+        written: Written,
+    }
+
+    impl Write for FlakyStream {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            if self.writes_left == 0 {
+                return Err(io::Error::new(io::ErrorKind::BrokenPipe, "broken pipe"));
+            }
+
+            self.writes_left -= 1;
+            self.written.lock().unwrap().push((self.id, buf.to_vec()));
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    /// Writer that supports vectored writes but only writes up to a limited
+    /// number of bytes in each call.
+    #[derive(Debug)]
+    struct ChunkedWriter {
+        limit: usize,
+        calls: usize,
+        written: Vec<u8>,
+    }
+
+    impl ChunkedWriter {
+        fn new(limit: usize) -> Self {
+            ChunkedWriter {
+                limit,
+                calls: 0,
+                written: Vec::new(),
+            }
+        }
+    }
+
+    impl Write for ChunkedWriter {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.write_vectored(&[IoSlice::new(buf)])
+        }
+
+        fn write_vectored(&mut self, bufs: &[IoSlice<'_>]) -> io::Result<usize> {
+            self.calls += 1;
+            let mut left = self.limit;
+            for buf in bufs {
+                let n = left.min(buf.len());
+                self.written.extend_from_slice(&buf[..n]);
+                left -= n;
+            }
+
+            Ok(self.limit - left)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[derive(Debug, Default)]
+    struct FlakyConnector {
+        connects: Mutex<usize>,
+        refuse: Mutex<bool>,
+        writes_per_stream: usize,
+        written: Written,
+    }
+
+    impl Connector for FlakyConnector {
+        type Stream = FlakyStream;
+
+        fn connect(&self) -> io::Result<FlakyStream> {
+            if *self.refuse.lock().unwrap() {
+                return Err(io::Error::new(io::ErrorKind::ConnectionRefused, "refused"));
+            }
+
+            let mut connects = self.connects.lock().unwrap();
+            *connects += 1;
+            Ok(FlakyStream {
+                id: *connects,
+                writes_left: self.writes_per_stream,
+                written: self.written.clone(),
+            })
+        }
+    }
+
+    fn new_writer(writes_per_stream: usize) -> (StreamWriter<FlakyConnector>, Written) {
+        new_writer_with(writes_per_stream, Backoff::none(), DisconnectPolicy::Drop)
+    }
+
+    fn new_writer_with(
+        writes_per_stream: usize,
+        backoff: Backoff,
+        policy: DisconnectPolicy,
+    ) -> (StreamWriter<FlakyConnector>, Written) {
+        let written = Arc::new(Mutex::new(Vec::new()));
+        let connector = FlakyConnector {
+            writes_per_stream,
+            written: written.clone(),
+            ..FlakyConnector::default()
+        };
+
+        (
+            StreamWriter::new(connector, backoff, policy, SocketStats::default()),
+            written,
+        )
+    }
+
+    #[test]
+    fn test_stream_writer_connects_lazily() {
+        let (mut writer, written) = new_writer(10);
+        assert_eq!(0, *writer.connector.connects.lock().unwrap());
+
+        writer.write_line(b"foo:1|c").unwrap();
+
+        assert_eq!(1, *writer.connector.connects.lock().unwrap());
+        assert_eq!(
+            vec![(1, b"foo:1|c".to_vec()), (1, b"\n".to_vec())],
+            *written.lock().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_stream_writer_reconnects_after_failure() {
+        // each connection allows a single metric (metric and newline) to be written
+        let (mut writer, written) = new_writer(2);
+
+        assert_eq!(7, writer.write_line(b"foo:1|c").unwrap());
+        assert!(writer.write_line(b"foo:2|c").is_err());
+        assert_eq!(7, writer.write_line(b"foo:3|c").unwrap());
+
+        assert_eq!(2, *writer.connector.connects.lock().unwrap());
+        assert_eq!(
+            vec![
+                (1, b"foo:1|c".to_vec()),
+                (1, b"\n".to_vec()),
+                (2, b"foo:3|c".to_vec()),
+                (2, b"\n".to_vec()),
+            ],
+            *written.lock().unwrap()
+        );
+
+        let stats: SinkStats = (&writer.stats).into();
+        assert_eq!(16, stats.bytes_sent);
+        assert_eq!(2, stats.packets_sent);
+        assert_eq!(8, stats.bytes_dropped);
+        assert_eq!(1, stats.packets_dropped);
+    }
+
+    #[test]
+    fn test_stream_writer_connect_failure() {
+        let (mut writer, written) = new_writer(10);
+        *writer.connector.refuse.lock().unwrap() = true;
+
+        // errors are returned by flush so that buffers aren't retried
+        assert_eq!(8, writer.write(b"foo:1|c\n").unwrap());
+        let res = writer.flush();
+        assert_eq!(io::ErrorKind::ConnectionRefused, res.unwrap_err().kind());
+
+        *writer.connector.refuse.lock().unwrap() = false;
+
+        assert_eq!(8, writer.write(b"foo:2|c\n").unwrap());
+        assert!(writer.flush().is_ok());
+        assert_eq!(vec![(1, b"foo:2|c\n".to_vec())], *written.lock().unwrap());
+    }
+
+    #[test]
+    fn test_stream_writer_waits_for_backoff() {
+        let backoff = Backoff::exponential(Duration::from_secs(3600), Duration::from_secs(3600));
+        let (mut writer, _written) = new_writer_with(10, backoff, DisconnectPolicy::Drop);
+        *writer.connector.refuse.lock().unwrap() = true;
+
+        let res = writer.write_line(b"foo:1|c");
+        assert_eq!(io::ErrorKind::ConnectionRefused, res.unwrap_err().kind());
+
+        // no connection attempt is made until the backoff delay has passed
+        *writer.connector.refuse.lock().unwrap() = false;
+        let res = writer.write_line(b"foo:2|c");
+        assert_eq!(io::ErrorKind::NotConnected, res.unwrap_err().kind());
+        assert_eq!(0, *writer.connector.connects.lock().unwrap());
+    }
+
+    #[test]
+    fn test_stream_writer_buffer_policy() {
+        let (mut writer, written) = new_writer_with(10, Backoff::none(), DisconnectPolicy::Buffer(16));
+        *writer.connector.refuse.lock().unwrap() = true;
+
+        assert_eq!(7, writer.write_line(b"foo:1|c").unwrap());
+        assert_eq!(7, writer.write_line(b"foo:2|c").unwrap());
+        // no more room to hold on to metrics until reconnected
+        assert!(writer.write_line(b"foo:3|c").is_err());
+
+        *writer.connector.refuse.lock().unwrap() = false;
+        assert!(writer.flush().is_ok());
+
+        assert_eq!(vec![(1, b"foo:1|c\nfoo:2|c\n".to_vec())], *written.lock().unwrap());
+
+        let stats: SinkStats = (&writer.stats).into();
+        assert_eq!(16, stats.bytes_sent);
+        assert_eq!(1, stats.packets_sent);
+        assert_eq!(8, stats.bytes_dropped);
+        assert_eq!(1, stats.packets_dropped);
+    }
+
+    #[test]
+    fn test_write_all_bufs_single_vectored_write() {
+        let mut writer = ChunkedWriter::new(64);
+
+        write_all_bufs(&mut writer, b"foo:1|c\n", &[b"bar:2|c", b"\n"]).unwrap();
+
+        assert_eq!(b"foo:1|c\nbar:2|c\n".to_vec(), writer.written);
+        assert_eq!(1, writer.calls);
+    }
+
+    #[test]
+    fn test_write_all_bufs_partial_vectored_writes() {
+        let mut writer = ChunkedWriter::new(5);
+
+        write_all_bufs(&mut writer, b"foo:1|c\n", &[b"bar:2|c", b"\n"]).unwrap();
+
+        assert_eq!(b"foo:1|c\nbar:2|c\n".to_vec(), writer.written);
+        assert_eq!(4, writer.calls);
+    }
+}
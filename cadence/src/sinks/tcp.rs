@@ -0,0 +1,690 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io;
+use std::io::Write;
+use std::net::{SocketAddr, TcpStream, ToSocketAddrs};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::io::MultiLineWriter;
+use crate::sinks::backoff::Backoff;
+use crate::sinks::core::{emit_each, MetricSink, SinkStats, SocketStats};
+use crate::sinks::stream::{Connector, DisconnectPolicy, StreamWriter};
+use crate::sinks::udp::{get_addrs, ResolvePolicy};
+use crate::types::{MetricResult, WriteMetric};
+
+// Default size of the buffer for buffered TCP sinks. Unlike UDP,
+// there's no packet size to stay under so this is the same as the
+// default buffer size used by the standard library.
+const DEFAULT_BUFFER_SIZE: usize = 8192;
+
+/// Options applied to each new TCP connection to a Statsd server
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct TcpOptions {
+    connect_timeout: Option<Duration>,
+    write_timeout: Option<Duration>,
+    nodelay: bool,
+    #[cfg(feature = "socket-options")]
+    keepalive: Option<Duration>,
+}
+
+impl TcpOptions {
+    /// Connect to each address in order until one succeeds and configure the
+    /// connection, returning the error from the last address if none do.
+    pub(crate) fn connect(&self, addrs: &[SocketAddr]) -> io::Result<TcpStream> {
+        let stream = match self.connect_timeout {
+            Some(timeout) => connect_timeout(addrs, timeout)?,
+            None => TcpStream::connect(addrs)?,
+        };
+
+        stream.set_write_timeout(self.write_timeout)?;
+        stream.set_nodelay(self.nodelay)?;
+        #[cfg(feature = "socket-options")]
+        if let Some(idle) = self.keepalive {
+            let keepalive = socket2::TcpKeepalive::new().with_time(idle);
+            socket2::SockRef::from(&stream).set_tcp_keepalive(&keepalive)?;
+        }
+
+        Ok(stream)
+    }
+}
+
+// Same as `TcpStream::connect()` with multiple addresses but giving up on
+// each address after the timeout.
+fn connect_timeout(addrs: &[SocketAddr], timeout: Duration) -> io::Result<TcpStream> {
+    let mut last_err = None;
+    for addr in addrs {
+        match TcpStream::connect_timeout(addr, timeout) {
+            Ok(stream) => return Ok(stream),
+            Err(e) => last_err = Some(e),
+        }
+    }
+
+    Err(last_err.unwrap_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no addresses to connect to")))
+}
+
+/// Connector for establishing TCP connections to a Statsd server, trying
+/// each address in order until one succeeds
+#[derive(Debug)]
+pub(crate) struct TcpConnector {
+    addrs: Vec<SocketAddr>,
+    options: TcpOptions,
+}
+
+impl Connector for TcpConnector {
+    type Stream = TcpStream;
+
+    fn connect(&self) -> io::Result<TcpStream> {
+        self.options.connect(&self.addrs)
+    }
+}
+
+/// Implementation of a builder pattern for TCP sinks.
+///
+/// The builder can be used to set how long to wait before reconnecting to
+/// the server after a failure, what to do with metrics while disconnected
+/// from the server, and the size of the buffer used by the buffered sink.
+/// It can also be used to set timeouts and other options for connections to
+/// the server so that a server that stops responding doesn't block the thread
+/// emitting metrics indefinitely.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use cadence::{Backoff, DisconnectPolicy, MetricSink, TcpMetricSinkBuilder, DEFAULT_PORT};
+///
+/// let sink = TcpMetricSinkBuilder::new()
+///     .with_backoff(Backoff::exponential(Duration::from_millis(50), Duration::from_secs(30)))
+///     .with_disconnect_policy(DisconnectPolicy::Buffer(1024 * 1024))
+///     .with_connect_timeout(Duration::from_secs(1))
+///     .with_write_timeout(Duration::from_millis(500))
+///     .with_capacity(16 * 1024)
+///     .build_buffered(("metrics.example.com", DEFAULT_PORT))
+///     .unwrap();
+///
+/// sink.emit("foo.counter:4|c");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct TcpMetricSinkBuilder {
+    backoff: Backoff,
+    policy: DisconnectPolicy,
+    resolve: ResolvePolicy,
+    options: TcpOptions,
+    capacity: Option<usize>,
+    flush_interval: Option<Duration>,
+}
+
+impl TcpMetricSinkBuilder {
+    /// Construct a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how long to wait before reconnecting to the server after a connection
+    /// attempt or write fails.
+    ///
+    /// While waiting to reconnect, metrics are not written to the server and are
+    /// handled according to the `DisconnectPolicy` of the sink. By default, the
+    /// delay starts at 100 milliseconds and doubles after each consecutive failure
+    /// up to 10 seconds.
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Set what to do with metrics that can't be written to the server because
+    /// the connection has failed or the sink is waiting to reconnect.
+    ///
+    /// By default, metrics are dropped and an error is returned.
+    pub fn with_disconnect_policy(mut self, policy: DisconnectPolicy) -> Self {
+        self.policy = policy;
+        self
+    }
+
+    /// Set whether IPv4 or IPv6 addresses are tried first when connecting to
+    /// the server if its hostname resolves to both.
+    ///
+    /// Each address the hostname resolves to is tried in order until a
+    /// connection succeeds. By default, they are tried in the order they were
+    /// resolved. See `ResolvePolicy` for more information.
+    pub fn with_resolve_policy(mut self, policy: ResolvePolicy) -> Self {
+        self.resolve = policy;
+        self
+    }
+
+    /// Set how long to wait for a connection to the server to be established
+    /// before giving up on it.
+    ///
+    /// If the hostname of the server resolves to multiple addresses, the
+    /// timeout applies to each of them. By default, there is no timeout and
+    /// connecting waits for as long as the operating system allows.
+    pub fn with_connect_timeout(mut self, timeout: Duration) -> Self {
+        self.options.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Set how long writing metrics to the server may block before failing.
+    ///
+    /// This keeps a server that has stopped reading metrics, but hasn't closed
+    /// the connection, from blocking the thread emitting metrics indefinitely.
+    /// A write that times out fails with a `TimedOut` or `WouldBlock` error,
+    /// depending on the platform, and the connection is discarded. By default,
+    /// there is no timeout.
+    pub fn with_write_timeout(mut self, timeout: Duration) -> Self {
+        self.options.write_timeout = Some(timeout);
+        self
+    }
+
+    /// Set whether Nagle's algorithm is disabled (`TCP_NODELAY`) for
+    /// connections to the server.
+    ///
+    /// Disabling it sends each write right away instead of waiting to combine
+    /// small writes into bigger packets, which lowers latency at the cost of
+    /// sending more packets. By default, Nagle's algorithm is enabled.
+    pub fn with_nodelay(mut self, nodelay: bool) -> Self {
+        self.options.nodelay = nodelay;
+        self
+    }
+
+    /// Enable TCP keepalive for connections to the server, sending the first
+    /// keepalive probe after the connection has been idle for the given time.
+    ///
+    /// This allows connections to a server that has gone away without closing
+    /// them to be detected when metrics aren't sent often. By default,
+    /// keepalive is not enabled. This requires the `socket-options` feature.
+    #[cfg(feature = "socket-options")]
+    pub fn with_keepalive(mut self, idle: Duration) -> Self {
+        self.options.keepalive = Some(idle);
+        self
+    }
+
+    /// Set the size of the buffer used by `BufferedTcpMetricSink` instances.
+    ///
+    /// The default size of the buffer is 8192 bytes. This has no effect on
+    /// `TcpMetricSink` instances since they are not buffered.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Set how long metrics may sit in the buffer of `BufferedTcpMetricSink`
+    /// instances before being sent, even if the buffer isn't full.
+    ///
+    /// The buffer is flushed when a metric is emitted and the oldest metric in
+    /// the buffer was emitted longer ago than the interval. A background thread
+    /// is not used, so metrics are not sent if nothing else is emitted. By
+    /// default, the buffer is only flushed when it is full. This has no effect
+    /// on `TcpMetricSink` instances since they are not buffered.
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = Some(interval);
+        self
+    }
+
+    /// Construct a new `TcpMetricSink` instance that will emit metrics to the
+    /// given address based on the builder configuration.
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server.
+    /// * The host address is otherwise unable to be parsed
+    pub fn build<A>(self, to_addr: A) -> MetricResult<TcpMetricSink>
+    where
+        A: ToSocketAddrs,
+    {
+        let stats = SocketStats::default();
+        let writer = self.writer(to_addr, stats.clone())?;
+        Ok(TcpMetricSink {
+            writer: Mutex::new(writer),
+            stats,
+        })
+    }
+
+    /// Construct a new `BufferedTcpMetricSink` instance that will emit metrics
+    /// to the given address based on the builder configuration.
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server.
+    /// * The host address is otherwise unable to be parsed
+    pub fn build_buffered<A>(self, to_addr: A) -> MetricResult<BufferedTcpMetricSink>
+    where
+        A: ToSocketAddrs,
+    {
+        let stats = SocketStats::default();
+        let writer = self.writer(to_addr, stats.clone())?;
+        Ok(BufferedTcpMetricSink {
+            buffer: Mutex::new(self.buffered(writer)),
+            stats,
+        })
+    }
+
+    fn writer<A>(&self, to_addr: A, stats: SocketStats) -> MetricResult<StreamWriter<TcpConnector>>
+    where
+        A: ToSocketAddrs,
+    {
+        let addrs = get_addrs(to_addr, self.resolve)?;
+        let connector = TcpConnector {
+            addrs,
+            options: self.options,
+        };
+
+        Ok(self.stream_writer(connector, stats))
+    }
+
+    /// Get the policy for ordering the addresses of the server. Used by other
+    /// sinks that are built on top of TCP.
+    #[cfg(feature = "rustls")]
+    pub(crate) fn resolve_policy(&self) -> ResolvePolicy {
+        self.resolve
+    }
+
+    /// Get the options for new TCP connections. Used by other sinks that are
+    /// built on top of TCP.
+    #[cfg(feature = "rustls")]
+    pub(crate) fn options(&self) -> TcpOptions {
+        self.options
+    }
+
+    /// Get the timeout for writes to the server. Used by other sinks that are
+    /// built on top of this builder.
+    #[cfg(unix)]
+    pub(crate) fn write_timeout(&self) -> Option<Duration> {
+        self.options.write_timeout
+    }
+
+    /// Create a writer for the given connector using the backoff and disconnect
+    /// policy of this builder. Used by other sinks that are built on top of TCP.
+    pub(crate) fn stream_writer<C>(&self, connector: C, stats: SocketStats) -> StreamWriter<C>
+    where
+        C: Connector,
+    {
+        StreamWriter::new(connector, self.backoff, self.policy, stats)
+    }
+
+    /// Wrap the given writer in a line buffer using the capacity of this builder.
+    pub(crate) fn buffered<W>(&self, writer: W) -> MultiLineWriter<W>
+    where
+        W: Write,
+    {
+        let cap = self.capacity.unwrap_or(DEFAULT_BUFFER_SIZE);
+        let mut buffered = MultiLineWriter::new(writer, cap).with_oversized_ending();
+        buffered.set_flush_interval(self.flush_interval);
+        buffered
+    }
+}
+
+/// Implementation of a `MetricSink` that emits metrics over TCP.
+///
+/// Each metric is written to a TCP connection to the Statsd server followed
+/// by a newline when the `.emit()` method is called, in the thread of the
+/// caller. This is supported by several Statsd servers as well as Telegraf.
+///
+/// The connection to the server is made when the first metric is emitted.
+/// If writing a metric fails, for example because the server was restarted,
+/// the connection is discarded and a new connection will be made when the next
+/// metric is emitted, after waiting for an exponentially increasing delay. What
+/// happens to metrics in the meantime is determined by the `DisconnectPolicy`
+/// which can be set using `TcpMetricSinkBuilder`. By default they are dropped
+/// and an error is returned.
+#[derive(Debug)]
+pub struct TcpMetricSink {
+    writer: Mutex<StreamWriter<TcpConnector>>,
+    stats: SocketStats,
+}
+
+impl TcpMetricSink {
+    /// Construct a new builder for `TcpMetricSink` or `BufferedTcpMetricSink`.
+    pub fn builder() -> TcpMetricSinkBuilder {
+        TcpMetricSinkBuilder::new()
+    }
+
+    /// Construct a new `TcpMetricSink` instance.
+    ///
+    /// The address should be the address of the remote metric server to
+    /// emit metrics to over TCP. Note that a connection to the server is
+    /// not made until the first metric is emitted.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cadence::{TcpMetricSink, DEFAULT_PORT};
+    ///
+    /// let host = ("metrics.example.com", DEFAULT_PORT);
+    /// let sink = TcpMetricSink::from(host);
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server.
+    /// * The host address is otherwise unable to be parsed
+    pub fn from<A>(to_addr: A) -> MetricResult<TcpMetricSink>
+    where
+        A: ToSocketAddrs,
+    {
+        Self::builder().build(to_addr)
+    }
+}
+
+impl MetricSink for TcpMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let mut writer = self.writer.lock().unwrap();
+        writer.write_line(metric.as_bytes())
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.writer.lock().unwrap().check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        (&self.stats).into()
+    }
+}
+
+/// Implementation of a `MetricSink` that buffers metrics before
+/// sending them over TCP.
+///
+/// Metrics are line buffered, meaning that a trailing "\n" is added
+/// after each metric written to this sink. When the buffer is sufficiently
+/// full and a write is attempted, the contents of the buffer are written to
+/// a TCP connection and then the metric is written to the buffer. The buffer
+/// is also flushed when this sink is destroyed.
+///
+/// The default size of the buffer is 8192 bytes. The buffer size can be
+/// customized using the `with_capacity` method to create the sink if desired.
+///
+/// The connection to the server is made when the buffer is first flushed. If
+/// writing the buffer fails, for example because the server was restarted,
+/// the connection is discarded and a new connection will be made the next time
+/// the buffer is flushed, after waiting for an exponentially increasing delay.
+/// What happens to the contents of the buffer is determined by the `DisconnectPolicy`
+/// which can be set using `TcpMetricSinkBuilder`. By default they are dropped and
+/// an error is returned.
+///
+/// Note that since metrics are buffered until a certain size is reached, it's
+/// possible that they may sit in the buffer for a while for applications
+/// that do not emit metrics frequently or at a high volume. For these low-
+/// throughput use cases, it may make more sense to use the `TcpMetricSink`
+/// since it sends metrics immediately with no buffering.
+#[derive(Debug)]
+pub struct BufferedTcpMetricSink {
+    buffer: Mutex<MultiLineWriter<StreamWriter<TcpConnector>>>,
+    stats: SocketStats,
+}
+
+impl BufferedTcpMetricSink {
+    /// Construct a new `BufferedTcpMetricSink` instance with a default
+    /// buffer size of 8192 bytes.
+    ///
+    /// The address should be the address of the remote metric server to
+    /// emit metrics to over TCP. Note that a connection to the server is
+    /// not made until the buffer is first flushed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cadence::{BufferedTcpMetricSink, DEFAULT_PORT};
+    ///
+    /// let host = ("metrics.example.com", DEFAULT_PORT);
+    /// let sink = BufferedTcpMetricSink::from(host);
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server.
+    /// * The host address is otherwise unable to be parsed
+    pub fn from<A>(sink_addr: A) -> MetricResult<BufferedTcpMetricSink>
+    where
+        A: ToSocketAddrs,
+    {
+        Self::with_capacity(sink_addr, DEFAULT_BUFFER_SIZE)
+    }
+
+    /// Construct a new `BufferedTcpMetricSink` instance with a custom
+    /// buffer size.
+    ///
+    /// The address should be the address of the remote metric server to
+    /// emit metrics to over TCP. Note that a connection to the server is
+    /// not made until the buffer is first flushed.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cadence::{BufferedTcpMetricSink, DEFAULT_PORT};
+    ///
+    /// let host = ("metrics.example.com", DEFAULT_PORT);
+    /// let sink = BufferedTcpMetricSink::with_capacity(host, 65536);
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server.
+    /// * The host address is otherwise unable to be parsed
+    pub fn with_capacity<A>(sink_addr: A, cap: usize) -> MetricResult<BufferedTcpMetricSink>
+    where
+        A: ToSocketAddrs,
+    {
+        TcpMetricSink::builder().with_capacity(cap).build_buffered(sink_addr)
+    }
+}
+
+impl MetricSink for BufferedTcpMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let mut writer = self.buffer.lock().unwrap();
+        writer.write(metric.as_bytes())
+    }
+
+    fn emit_metric(&self, metric: &dyn WriteMetric) -> io::Result<usize> {
+        let mut writer = self.buffer.lock().unwrap();
+        writer.write_metric(metric)
+    }
+
+    fn emit_batch(&self, metrics: &[&str]) -> io::Result<usize> {
+        let mut writer = self.buffer.lock().unwrap();
+        emit_each(metrics, |m| writer.write(m.as_bytes()))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let mut writer = self.buffer.lock().unwrap();
+        writer.flush()
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.buffer.lock().unwrap().get_mut().check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        (&self.stats).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BufferedTcpMetricSink, MetricSink, TcpConnector, TcpMetricSink, TcpMetricSinkBuilder, TcpOptions};
+    use crate::sinks::backoff::Backoff;
+    use crate::sinks::core::SocketStats;
+    use crate::sinks::stream::{DisconnectPolicy, StreamWriter};
+    use std::io::{BufRead, BufReader};
+    use std::net::TcpListener;
+    use std::thread;
+    use std::time::Duration;
+
+    // Accept a single connection and return every line read from it
+    fn serve_lines(listener: TcpListener) -> thread::JoinHandle<Vec<String>> {
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            BufReader::new(stream).lines().map(|l| l.unwrap()).collect()
+        })
+    }
+
+    #[test]
+    fn test_tcp_metric_sink() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let sink = TcpMetricSink::from(listener.local_addr().unwrap()).unwrap();
+        let server = serve_lines(listener);
+
+        assert_eq!(7, sink.emit("buz:1|m").unwrap());
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        drop(sink);
+
+        assert_eq!(vec!["buz:1|m", "foo:54|c"], server.join().unwrap());
+    }
+
+    #[test]
+    fn test_tcp_metric_sink_no_server() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let sink = TcpMetricSink::from(addr).unwrap();
+        assert!(sink.emit("buz:1|m").is_err());
+        assert_eq!(1, sink.stats().packets_dropped);
+    }
+
+    #[test]
+    fn test_tcp_metric_sink_check() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let sink = TcpMetricSink::from(addr).unwrap();
+        assert!(sink.check().is_err());
+
+        let listener = TcpListener::bind(addr).unwrap();
+        let server = serve_lines(listener);
+
+        // not held back by the backoff delay of the failed check
+        sink.check().unwrap();
+        assert_eq!(7, sink.emit("buz:1|m").unwrap());
+        drop(sink);
+
+        assert_eq!(vec!["buz:1|m"], server.join().unwrap());
+    }
+
+    #[test]
+    fn test_tcp_connector_tries_each_address() {
+        let closed = TcpListener::bind("127.0.0.1:0").unwrap();
+        let closed_addr = closed.local_addr().unwrap();
+        drop(closed);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let connector = TcpConnector {
+            addrs: vec![closed_addr, listener.local_addr().unwrap()],
+            options: TcpOptions::default(),
+        };
+        let server = serve_lines(listener);
+
+        let builder = TcpMetricSinkBuilder::new();
+        let mut writer: StreamWriter<TcpConnector> = builder.stream_writer(connector, SocketStats::default());
+        assert_eq!(7, writer.write_line(b"buz:1|m").unwrap());
+        drop(writer);
+
+        assert_eq!(vec!["buz:1|m"], server.join().unwrap());
+    }
+
+    #[test]
+    fn test_tcp_options_connect() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let builder = TcpMetricSinkBuilder::new()
+            .with_connect_timeout(Duration::from_secs(1))
+            .with_write_timeout(Duration::from_millis(500))
+            .with_nodelay(true);
+
+        let stream = builder.options.connect(&[listener.local_addr().unwrap()]).unwrap();
+        assert_eq!(Some(Duration::from_millis(500)), stream.write_timeout().unwrap());
+        assert!(stream.nodelay().unwrap());
+
+        let stream = TcpOptions::default()
+            .connect(&[listener.local_addr().unwrap()])
+            .unwrap();
+        assert_eq!(None, stream.write_timeout().unwrap());
+        assert!(!stream.nodelay().unwrap());
+    }
+
+    #[cfg(feature = "socket-options")]
+    #[test]
+    fn test_tcp_options_keepalive() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let builder = TcpMetricSinkBuilder::new().with_keepalive(Duration::from_secs(30));
+
+        let stream = builder.options.connect(&[listener.local_addr().unwrap()]).unwrap();
+        assert!(socket2::SockRef::from(&stream).keepalive().unwrap());
+    }
+
+    #[test]
+    fn test_tcp_metric_sink_reconnects() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let sink = TcpMetricSinkBuilder::new()
+            .with_backoff(Backoff::none())
+            .with_disconnect_policy(DisconnectPolicy::Buffer(1024))
+            .build(addr)
+            .unwrap();
+
+        // held in memory until the server is available
+        assert_eq!(7, sink.emit("buz:1|m").unwrap());
+
+        let listener = TcpListener::bind(addr).unwrap();
+        let server = serve_lines(listener);
+
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        drop(sink);
+
+        assert_eq!(vec!["buz:1|m", "foo:54|c"], server.join().unwrap());
+    }
+
+    #[test]
+    fn test_buffered_tcp_metric_sink() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        // Set the capacity of the buffer such that we know it will
+        // be flushed as a response to the metrics we're writing.
+        let sink = BufferedTcpMetricSink::with_capacity(listener.local_addr().unwrap(), 16).unwrap();
+        let server = serve_lines(listener);
+
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        assert_eq!(8, sink.emit("foo:67|c").unwrap());
+        assert_eq!(29, sink.emit("some_really_long_metric:456|c").unwrap());
+        drop(sink);
+
+        assert_eq!(
+            vec!["foo:54|c", "some_really_long_metric:456|c", "foo:67|c"],
+            server.join().unwrap()
+        );
+    }
+
+    #[test]
+    fn test_buffered_tcp_metric_sink_flush() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        // Set the capacity of the buffer such that it won't be flushed
+        // from a single write. Thus we can test the flush method.
+        let sink = BufferedTcpMetricSink::with_capacity(listener.local_addr().unwrap(), 64).unwrap();
+        let server = serve_lines(listener);
+
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        assert!(sink.flush().is_ok());
+        assert_eq!(9, sink.stats().bytes_sent);
+        drop(sink);
+
+        assert_eq!(vec!["foo:54|c"], server.join().unwrap());
+    }
+}
@@ -0,0 +1,442 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+use std::io::{self, ErrorKind};
+use std::panic::RefUnwindSafe;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use tokio::runtime::Handle;
+use tokio::sync::mpsc::{self, error::TrySendError};
+
+use crate::sinks::core::{AsyncMetricSink, MetricSink, SinkStats};
+use crate::types::queue_full;
+
+/// Message sent from the sink to the task running the wrapped sink
+#[derive(Debug)]
+enum Message {
+    Metric(String),
+    Flush,
+}
+
+/// Sending half of the queue, bounded or unbounded
+#[derive(Debug)]
+enum QueueSender {
+    Bounded(mpsc::Sender<Message>),
+    Unbounded(mpsc::UnboundedSender<Message>),
+}
+
+impl QueueSender {
+    fn send(&self, msg: Message) -> io::Result<()> {
+        match self {
+            QueueSender::Bounded(tx) => tx.try_send(msg).map_err(|e| match e {
+                TrySendError::Full(_) => queue_full(),
+                TrySendError::Closed(_) => io::Error::new(ErrorKind::Other, "channel disconnected"),
+            }),
+            QueueSender::Unbounded(tx) => tx
+                .send(msg)
+                .map_err(|_| io::Error::new(ErrorKind::Other, "channel disconnected")),
+        }
+    }
+}
+
+/// Receiving half of the queue, bounded or unbounded
+enum QueueReceiver {
+    Bounded(mpsc::Receiver<Message>),
+    Unbounded(mpsc::UnboundedReceiver<Message>),
+}
+
+impl QueueReceiver {
+    async fn recv(&mut self) -> Option<Message> {
+        match self {
+            QueueReceiver::Bounded(rx) => rx.recv().await,
+            QueueReceiver::Unbounded(rx) => rx.recv().await,
+        }
+    }
+}
+
+/// Counts of metrics submitted to and drained from the queue
+#[derive(Debug, Default)]
+struct QueueStats {
+    submitted: AtomicU64,
+    drained: AtomicU64,
+}
+
+/// Implementation of a builder pattern for `TokioQueuingMetricSink`.
+///
+/// The builder can be used to set an error handler for the sink being
+/// wrapped, the capacity of the queue, and the Tokio runtime the task
+/// draining the queue is spawned on.
+///
+/// # Example
+///
+/// ```no_run
+/// use cadence::{MetricSink, NopMetricSink, TokioQueuingMetricSinkBuilder};
+///
+/// # async fn example() {
+/// let queuing = TokioQueuingMetricSinkBuilder::new()
+///     .with_capacity(64 * 1024)
+///     .with_error_handler(|e| {
+///         eprintln!("Error while sending metrics: {:?}", e);
+///     })
+///     .build(NopMetricSink);
+///
+/// queuing.emit("foo.counter:4|c");
+/// # }
+/// ```
+#[derive(Default)]
+pub struct TokioQueuingMetricSinkBuilder {
+    error_handler: Option<Box<dyn Fn(io::Error) + Sync + Send + 'static>>,
+    capacity: Option<usize>,
+    handle: Option<Handle>,
+}
+
+impl TokioQueuingMetricSinkBuilder {
+    /// Construct a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set error handler called when the wrapped sink fails to emit a metric.
+    ///
+    /// The error handler will be run in the Tokio task running the wrapped sink
+    /// and must not panic or block.
+    pub fn with_error_handler<F>(mut self, error_handler: F) -> Self
+    where
+        F: Fn(io::Error) + Sync + Send + 'static,
+    {
+        self.error_handler = Some(Box::new(error_handler));
+        self
+    }
+
+    /// Set queue size used to send metrics to the wrapped sink.
+    ///
+    /// When the queue is full, writes to the sink will fail until the queue
+    /// is drained. By default the queue is unbounded.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Set the Tokio runtime used to run the task that drains the queue.
+    ///
+    /// By default, the task is spawned on the runtime the sink is built in.
+    pub fn with_runtime(mut self, handle: Handle) -> Self {
+        self.handle = Some(handle);
+        self
+    }
+
+    /// Construct a new `TokioQueuingMetricSink` instance wrapping another sink
+    /// based on the builder configuration.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if a runtime has not been set with `with_runtime`
+    /// and it is not called from within a Tokio runtime.
+    pub fn build<T>(self, sink: T) -> TokioQueuingMetricSink
+    where
+        T: AsyncMetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        let (sender, receiver) = match self.capacity {
+            Some(capacity) => {
+                let (tx, rx) = mpsc::channel(capacity);
+                (QueueSender::Bounded(tx), QueueReceiver::Bounded(rx))
+            }
+            None => {
+                let (tx, rx) = mpsc::unbounded_channel();
+                (QueueSender::Unbounded(tx), QueueReceiver::Unbounded(rx))
+            }
+        };
+
+        let sink: Arc<dyn AsyncMetricSink + Send + Sync + RefUnwindSafe> = Arc::new(sink);
+        let stats = Arc::new(QueueStats::default());
+        let handle = self.handle.unwrap_or_else(Handle::current);
+        handle.spawn(drain(receiver, sink.clone(), stats.clone(), self.error_handler));
+
+        TokioQueuingMetricSink { sender, sink, stats }
+    }
+}
+
+impl fmt::Debug for TokioQueuingMetricSinkBuilder {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "TokioQueuingMetricSinkBuilder {{ error_handler: ..., capacity: {:?}, handle: {:?} }}",
+            self.capacity, self.handle
+        )
+    }
+}
+
+/// Send every metric received from the queue to the wrapped sink until all
+/// senders have been dropped and the queue is empty.
+async fn drain(
+    mut receiver: QueueReceiver,
+    sink: Arc<dyn AsyncMetricSink + Send + Sync + RefUnwindSafe>,
+    stats: Arc<QueueStats>,
+    error_handler: Option<Box<dyn Fn(io::Error) + Sync + Send + 'static>>,
+) {
+    while let Some(msg) = receiver.recv().await {
+        let res = match msg {
+            Message::Metric(metric) => {
+                stats.drained.fetch_add(1, Ordering::Release);
+                sink.emit(&metric).await.map(|_| ())
+            }
+            Message::Flush => sink.flush().await,
+        };
+
+        if let (Err(e), Some(handler)) = (res, &error_handler) {
+            handler(e);
+        }
+    }
+
+    if let (Err(e), Some(handler)) = (sink.flush().await, &error_handler) {
+        handler(e);
+    }
+}
+
+/// Implementation of a `MetricSink` that wraps an `AsyncMetricSink` and uses
+/// it to emit metrics from a Tokio task.
+///
+/// This is the equivalent of the `QueuingMetricSink` for async applications.
+/// Metrics submitted to this sink are queued and sent to the wrapped sink by a
+/// Tokio task instead of a dedicated thread. Since submitting a metric never
+/// blocks, this sink can be used with a regular `StatsdClient` from within
+/// async code.
+///
+/// The task draining the queue is spawned when the sink is created. When the
+/// sink is destroyed, the task sends any metrics that are still queued to the
+/// wrapped sink, flushes it, and then stops.
+///
+/// Like the `QueuingMetricSink`, this sink may be created with either a bounded
+/// or unbounded queue. Calling `.flush()` on this sink queues a request to flush
+/// the wrapped sink and returns immediately.
+///
+/// This sink is only available when the `tokio` feature is enabled.
+///
+/// # Example
+///
+/// ```no_run
+/// use tokio::net::UdpSocket;
+/// use cadence::{StatsdClient, TokioQueuingMetricSink, TokioUdpMetricSink, DEFAULT_PORT};
+///
+/// # async fn example() {
+/// let socket = UdpSocket::bind("0.0.0.0:0").await.unwrap();
+/// let host = ("metrics.example.com", DEFAULT_PORT);
+/// let udp_sink = TokioUdpMetricSink::from(host, socket).unwrap();
+/// let queuing_sink = TokioQueuingMetricSink::from(udp_sink);
+/// let client = StatsdClient::from_sink("my.prefix", queuing_sink);
+/// # }
+/// ```
+pub struct TokioQueuingMetricSink {
+    sender: QueueSender,
+    sink: Arc<dyn AsyncMetricSink + Send + Sync + RefUnwindSafe>,
+    stats: Arc<QueueStats>,
+}
+
+impl TokioQueuingMetricSink {
+    /// Construct a new builder for `TokioQueuingMetricSink`.
+    pub fn builder() -> TokioQueuingMetricSinkBuilder {
+        TokioQueuingMetricSinkBuilder::new()
+    }
+
+    /// Construct a new `TokioQueuingMetricSink` instance wrapping another sink
+    /// with an unbounded queue connecting them.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if it is not called from within a Tokio runtime.
+    pub fn from<T>(sink: T) -> Self
+    where
+        T: AsyncMetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        Self::builder().build(sink)
+    }
+
+    /// Construct a new `TokioQueuingMetricSink` instance wrapping another sink
+    /// with a queue of the given size connecting them.
+    ///
+    /// # Panics
+    ///
+    /// This method panics if it is not called from within a Tokio runtime.
+    pub fn with_capacity<T>(sink: T, capacity: usize) -> Self
+    where
+        T: AsyncMetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        Self::builder().with_capacity(capacity).build(sink)
+    }
+
+    /// Return the number of currently queued metrics. Note that due to the way
+    /// this number is computed (submitted metrics - processed metrics), it is
+    /// necessarily approximate.
+    pub fn queued(&self) -> u64 {
+        self.submitted().saturating_sub(self.drained())
+    }
+
+    /// Return the number of metrics successfully submitted to this sink.
+    pub fn submitted(&self) -> u64 {
+        self.stats.submitted.load(Ordering::Acquire)
+    }
+
+    /// Return the number of metrics removed from the queue to be processed by
+    /// the wrapped sink.
+    pub fn drained(&self) -> u64 {
+        self.stats.drained.load(Ordering::Acquire)
+    }
+}
+
+impl MetricSink for TokioQueuingMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        self.sender.send(Message::Metric(metric.to_string()))?;
+        self.stats.submitted.fetch_add(1, Ordering::Release);
+        Ok(metric.len())
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.sender.send(Message::Flush)
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats()
+    }
+}
+
+impl fmt::Debug for TokioQueuingMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "TokioQueuingMetricSink {{ sender: {:?}, stats: {:?} }}",
+            self.sender, self.stats
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MetricSink, TokioQueuingMetricSink};
+    use crate::executor::block_on_tokio;
+    use crate::sinks::core::{AsyncMetricSink, SinkFuture};
+    use std::io;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+
+    #[derive(Debug, Clone, Default)]
+    struct AsyncSpySink {
+        metrics: Arc<Mutex<Vec<String>>>,
+        flushes: Arc<AtomicUsize>,
+    }
+
+    impl AsyncMetricSink for AsyncSpySink {
+        fn emit<'a>(&'a self, metric: &'a str) -> SinkFuture<'a, usize> {
+            Box::pin(async move {
+                self.metrics.lock().unwrap().push(metric.to_owned());
+                Ok(metric.len())
+            })
+        }
+
+        fn flush(&self) -> SinkFuture<'_, ()> {
+            Box::pin(async move {
+                self.flushes.fetch_add(1, Ordering::Release);
+                Ok(())
+            })
+        }
+    }
+
+    struct AsyncErrorSink;
+
+    impl AsyncMetricSink for AsyncErrorSink {
+        fn emit<'a>(&'a self, _metric: &'a str) -> SinkFuture<'a, usize> {
+            Box::pin(async { Err(io::Error::new(io::ErrorKind::Other, "foo")) })
+        }
+    }
+
+    // Give the task draining the queue a chance to run until the condition is met
+    async fn wait_for<F: Fn() -> bool>(cond: F) {
+        for _ in 0..1000 {
+            if cond() {
+                return;
+            }
+            tokio::task::yield_now().await;
+        }
+
+        panic!("condition not met after yielding");
+    }
+
+    #[test]
+    fn test_tokio_queuing_metric_sink() {
+        block_on_tokio(async {
+            let spy = AsyncSpySink::default();
+            let sink = TokioQueuingMetricSink::from(spy.clone());
+
+            assert_eq!(7, sink.emit("foo:1|c").unwrap());
+            assert_eq!(7, sink.emit("bar:2|c").unwrap());
+            assert_eq!(2, sink.submitted());
+
+            wait_for(|| sink.drained() == 2).await;
+            assert_eq!(0, sink.queued());
+            assert_eq!(vec!["foo:1|c", "bar:2|c"], *spy.metrics.lock().unwrap());
+        });
+    }
+
+    #[test]
+    fn test_tokio_queuing_metric_sink_flush() {
+        block_on_tokio(async {
+            let spy = AsyncSpySink::default();
+            let sink = TokioQueuingMetricSink::from(spy.clone());
+
+            assert!(sink.flush().is_ok());
+            wait_for(|| spy.flushes.load(Ordering::Acquire) == 1).await;
+        });
+    }
+
+    #[test]
+    fn test_tokio_queuing_metric_sink_drains_on_drop() {
+        block_on_tokio(async {
+            let spy = AsyncSpySink::default();
+            let sink = TokioQueuingMetricSink::from(spy.clone());
+
+            sink.emit("foo:1|c").unwrap();
+            sink.emit("bar:2|c").unwrap();
+            drop(sink);
+
+            wait_for(|| spy.flushes.load(Ordering::Acquire) == 1).await;
+            assert_eq!(vec!["foo:1|c", "bar:2|c"], *spy.metrics.lock().unwrap());
+        });
+    }
+
+    #[test]
+    fn test_tokio_queuing_metric_sink_full() {
+        block_on_tokio(async {
+            let sink = TokioQueuingMetricSink::with_capacity(AsyncSpySink::default(), 1);
+
+            // The task draining the queue doesn't get a chance to run in between
+            assert!(sink.emit("foo:1|c").is_ok());
+            assert!(sink.emit("bar:2|c").is_err());
+            assert_eq!(1, sink.submitted());
+        });
+    }
+
+    #[test]
+    fn test_tokio_queuing_metric_sink_error_handler() {
+        block_on_tokio(async {
+            let errors = Arc::new(AtomicUsize::new(0));
+            let errors_ref = errors.clone();
+
+            let sink = TokioQueuingMetricSink::builder()
+                .with_error_handler(move |_e| {
+                    errors_ref.fetch_add(1, Ordering::Release);
+                })
+                .build(AsyncErrorSink);
+
+            sink.emit("foo:1|c").unwrap();
+            wait_for(|| errors.load(Ordering::Acquire) == 1).await;
+        });
+    }
+}
@@ -0,0 +1,104 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::net::{SocketAddr, ToSocketAddrs};
+
+use tokio::net::UdpSocket;
+
+use crate::sinks::core::{AsyncMetricSink, SinkFuture, SinkStats, SocketStats};
+use crate::sinks::udp::get_addr;
+use crate::types::MetricResult;
+
+/// Implementation of an `AsyncMetricSink` that emits metrics over UDP
+/// using a Tokio socket.
+///
+/// This is the async equivalent of the `UdpMetricSink`. Each metric is
+/// sent to the Statsd server when the future returned by `.emit()` is
+/// awaited, without blocking the thread running the Tokio runtime.
+///
+/// This sink is only available when the `tokio` feature is enabled.
+#[derive(Debug)]
+pub struct TokioUdpMetricSink {
+    addr: SocketAddr,
+    socket: UdpSocket,
+    stats: SocketStats,
+}
+
+impl TokioUdpMetricSink {
+    /// Construct a new `TokioUdpMetricSink` instance.
+    ///
+    /// The address should be the address of the remote metric server to
+    /// emit metrics to over UDP. The socket should already be bound to a
+    /// local address with any desired configuration applied.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use tokio::net::UdpSocket;
+    /// use cadence::{TokioUdpMetricSink, DEFAULT_PORT};
+    ///
+    /// # async fn example() {
+    /// let socket = UdpSocket::bind("0.0.0.0:0").await.unwrap();
+    /// let host = ("metrics.example.com", DEFAULT_PORT);
+    /// let sink = TokioUdpMetricSink::from(host, socket);
+    /// # }
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server.
+    /// * The host address is otherwise unable to be parsed
+    pub fn from<A>(to_addr: A, socket: UdpSocket) -> MetricResult<TokioUdpMetricSink>
+    where
+        A: ToSocketAddrs,
+    {
+        let addr = get_addr(to_addr)?;
+        let stats = SocketStats::default();
+        Ok(TokioUdpMetricSink { addr, socket, stats })
+    }
+}
+
+impl AsyncMetricSink for TokioUdpMetricSink {
+    fn emit<'a>(&'a self, metric: &'a str) -> SinkFuture<'a, usize> {
+        Box::pin(async move {
+            let res = self.socket.send_to(metric.as_bytes(), self.addr).await;
+            self.stats.update(res, metric.len())
+        })
+    }
+
+    fn stats(&self) -> SinkStats {
+        (&self.stats).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{AsyncMetricSink, TokioUdpMetricSink};
+    use crate::executor::block_on_tokio;
+    use tokio::net::UdpSocket;
+
+    #[test]
+    fn test_tokio_udp_metric_sink() {
+        block_on_tokio(async {
+            let server = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+            let sink = TokioUdpMetricSink::from(server.local_addr().unwrap(), socket).unwrap();
+
+            assert_eq!(7, sink.emit("buz:1|m").await.unwrap());
+
+            let mut buf = [0; 64];
+            let len = server.recv(&mut buf).await.unwrap();
+            assert_eq!(b"buz:1|m", &buf[..len]);
+            assert_eq!(1, sink.stats().packets_sent);
+        });
+    }
+}
@@ -9,13 +9,21 @@
 // except according to those terms.
 
 use std::io;
-use std::io::Write;
+#[cfg(all(feature = "sendmmsg", target_os = "linux"))]
+use std::io::IoSlice;
+use std::mem;
 use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
-use std::sync::Mutex;
+#[cfg(all(feature = "sendmmsg", target_os = "linux"))]
+use std::os::unix::io::AsRawFd;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
 
-use crate::io::MultiLineWriter;
-use crate::sinks::core::{MetricSink, SinkStats, SocketStats};
+use crate::sinks::core::{emit_each, MetricSink, SinkStats, SocketStats};
 use crate::types::{ErrorKind, MetricError, MetricResult};
+#[cfg(all(feature = "sendmmsg", target_os = "linux"))]
+use nix::sys::socket::{sendmmsg, MsgFlags, MultiHeaders, SockaddrStorage};
 
 // Default size of the buffer for buffered metric sinks. This
 // is a rather conservative value, picked to make sure the entire
@@ -24,13 +32,207 @@ use crate::types::{ErrorKind, MetricError, MetricResult};
 // their application runs in.
 const DEFAULT_BUFFER_SIZE: usize = 512;
 
+// Maximum number of spare buffers kept by a `BufferedUdpMetricSink`. Only one
+// buffer is written to at a time, the rest are full buffers being sent by
+// threads that filled them.
+const MAX_POOLED_BUFFERS: usize = 4;
+
+// Default interval for resolving the hostname of the server again for sinks
+// created from a hostname.
+const DEFAULT_RESOLVE_INTERVAL: Duration = Duration::from_secs(60);
+
+// Minimum time between resolving the hostname of the server again because
+// sending to it failed, so that failures don't cause a DNS lookup each time.
+const MIN_FAILURE_RESOLVE_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Maximum size of the datagrams sent by a `BufferedUdpMetricSink`.
+///
+/// Datagrams bigger than the MTU of the network between an application and
+/// the Statsd server are fragmented into multiple IP packets. If any of the
+/// fragments are lost, the entire datagram is lost, so picking a size that
+/// avoids fragmentation makes losing metrics less likely.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::net::UdpSocket;
+/// use cadence::{BufferedUdpMetricSink, PacketSize, DEFAULT_PORT};
+///
+/// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+/// let host = ("metrics.example.com", DEFAULT_PORT);
+/// let sink = BufferedUdpMetricSink::with_packet_size(host, socket, PacketSize::Ethernet);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PacketSize {
+    /// 512 bytes, small enough to never be fragmented on the internet.
+    Internet,
+    /// 1432 bytes, the largest size that fits in a single packet on
+    /// typical networks with an MTU of 1500 bytes.
+    Ethernet,
+    /// 8932 bytes, the largest size that fits in a single packet on
+    /// networks with jumbo frames and an MTU of 9000 bytes.
+    Jumbo,
+    /// A custom size in bytes.
+    Custom(usize),
+}
+
+impl PacketSize {
+    /// Get the size of packets in bytes.
+    pub fn bytes(&self) -> usize {
+        match self {
+            PacketSize::Internet => 512,
+            PacketSize::Ethernet => 1432,
+            PacketSize::Jumbo => 8932,
+            PacketSize::Custom(size) => *size,
+        }
+    }
+}
+
+/// What a UDP sink does when its socket is in non-blocking mode and a packet
+/// can't be sent because the send buffer of the socket is full.
+///
+/// In this case, sending the packet fails with a `WouldBlock` error. This
+/// usually happens during bursts of metrics, when metrics are emitted faster
+/// than the operating system can send them. Packets that are dropped are
+/// counted as dropped in the stats of the sink as well as by the
+/// `.would_block_dropped()` method of the sink.
+///
+/// # Example
+///
+/// ```no_run
+/// use cadence::{UdpMetricSinkBuilder, WouldBlockPolicy, DEFAULT_PORT};
+///
+/// let sink = UdpMetricSinkBuilder::new()
+///     .with_would_block_policy(WouldBlockPolicy::RetryOnce)
+///     .build(("metrics.example.com", DEFAULT_PORT))
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum WouldBlockPolicy {
+    /// Drop the packet and return the `WouldBlock` error to the caller. This
+    /// is the default.
+    Error,
+    /// Drop the packet and return `0` bytes written instead of an error.
+    Drop,
+    /// Try to send the packet once more right away and drop it the same as
+    /// `Drop` if that fails because the buffer is still full.
+    RetryOnce,
+}
+
+impl WouldBlockPolicy {
+    // Handle the result of sending a packet, sending it again or dropping it
+    // without an error if it failed because the socket would block, and update
+    // the stats of the sink.
+    fn handle<F>(
+        self,
+        mut res: io::Result<usize>,
+        send: F,
+        len: usize,
+        stats: &SocketStats,
+        dropped: &AtomicU64,
+    ) -> io::Result<usize>
+    where
+        F: Fn() -> io::Result<usize>,
+    {
+        if self == WouldBlockPolicy::RetryOnce && is_would_block(&res) {
+            res = send();
+        }
+
+        if self != WouldBlockPolicy::Error && is_would_block(&res) {
+            dropped.fetch_add(1, Ordering::Relaxed);
+            return stats.update(res, len).or(Ok(0));
+        }
+
+        stats.update(res, len)
+    }
+}
+
+impl Default for WouldBlockPolicy {
+    fn default() -> Self {
+        WouldBlockPolicy::Error
+    }
+}
+
+fn is_would_block(res: &io::Result<usize>) -> bool {
+    matches!(res, Err(e) if e.kind() == io::ErrorKind::WouldBlock)
+}
+
+/// Which address is used when the hostname of a Statsd server resolves to
+/// both IPv4 and IPv6 addresses.
+///
+/// UDP sinks send metrics to the first address in order of preference. TCP
+/// and TLS sinks try to connect to each address in order of preference until
+/// a connection succeeds, falling back to addresses of the other family if
+/// none of the preferred addresses can be reached.
+///
+/// # Example
+///
+/// ```no_run
+/// use cadence::{ResolvePolicy, TcpMetricSinkBuilder, UdpMetricSinkBuilder, DEFAULT_PORT};
+///
+/// let udp = UdpMetricSinkBuilder::new()
+///     .with_resolve_policy(ResolvePolicy::PreferIpv4)
+///     .build(("metrics.example.com", DEFAULT_PORT))
+///     .unwrap();
+///
+/// let tcp = TcpMetricSinkBuilder::new()
+///     .with_resolve_policy(ResolvePolicy::PreferIpv6)
+///     .build(("metrics.example.com", DEFAULT_PORT))
+///     .unwrap();
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ResolvePolicy {
+    /// Use addresses in the order they were resolved by the operating system.
+    /// This is the default.
+    First,
+    /// Use IPv4 addresses before IPv6 addresses.
+    PreferIpv4,
+    /// Use IPv6 addresses before IPv4 addresses.
+    PreferIpv6,
+}
+
+impl ResolvePolicy {
+    // Sort addresses in order of preference, keeping the order they were
+    // resolved in otherwise.
+    fn sort(self, addrs: &mut [SocketAddr]) {
+        match self {
+            ResolvePolicy::First => {}
+            ResolvePolicy::PreferIpv4 => addrs.sort_by_key(|a| a.is_ipv6()),
+            ResolvePolicy::PreferIpv6 => addrs.sort_by_key(|a| a.is_ipv4()),
+        }
+    }
+}
+
+impl Default for ResolvePolicy {
+    fn default() -> Self {
+        ResolvePolicy::First
+    }
+}
+
+/// Resolve anything implementing the `ToSocketAddrs` trait into every address
+/// it resolves to in order of preference, returning an `InvalidInput` error if
+/// there aren't any.
+#[allow(clippy::needless_pass_by_value)]
+pub(crate) fn get_addrs<A: ToSocketAddrs>(addr: A, policy: ResolvePolicy) -> MetricResult<Vec<SocketAddr>> {
+    let mut addrs: Vec<SocketAddr> = addr.to_socket_addrs()?.collect();
+    if addrs.is_empty() {
+        return Err(MetricError::from((
+            ErrorKind::InvalidInput,
+            "No socket addresses yielded",
+        )));
+    }
+
+    policy.sort(&mut addrs);
+    Ok(addrs)
+}
+
 /// Attempt to convert anything implementing the `ToSocketAddrs` trait
 /// into a concrete `SocketAddr` instance, returning an `InvalidInput`
 /// error if the address could not be parsed.
 // Public portion of the API (the sink constructors) is pass by value so
 // there's no point in changing this to be pass by reference yet.
 #[allow(clippy::needless_pass_by_value)]
-fn get_addr<A: ToSocketAddrs>(addr: A) -> MetricResult<SocketAddr> {
+pub(crate) fn get_addr<A: ToSocketAddrs>(addr: A) -> MetricResult<SocketAddr> {
     match addr.to_socket_addrs()?.next() {
         Some(addr) => Ok(addr),
         None => Err(MetricError::from((
@@ -40,6 +242,440 @@ fn get_addr<A: ToSocketAddrs>(addr: A) -> MetricResult<SocketAddr> {
     }
 }
 
+// Address of the Statsd server that a UDP sink sends packets to, either fixed
+// or resolved from a hostname again periodically and after sending fails.
+#[derive(Debug)]
+enum ServerAddr {
+    Fixed(SocketAddr),
+    Host(Arc<HostAddr>),
+}
+
+impl ServerAddr {
+    fn host(host: &str, port: u16, builder: &UdpMetricSinkBuilder) -> MetricResult<ServerAddr> {
+        let addr = lookup_host(host, port, builder)?;
+        let now = Instant::now();
+        Ok(ServerAddr::Host(Arc::new(HostAddr {
+            host: host.to_owned(),
+            port,
+            builder: builder.clone(),
+            state: Mutex::new(Resolved {
+                addr,
+                socket: None,
+                resolving: false,
+                resolved_at: now,
+                expires: now + builder.resolve_interval,
+            }),
+        })))
+    }
+
+    // Current address of the server, without resolving the hostname again.
+    fn addr(&self) -> SocketAddr {
+        match self {
+            ServerAddr::Fixed(addr) => *addr,
+            ServerAddr::Host(host) => host.state.lock().unwrap().addr,
+        }
+    }
+
+    // Call the function with the socket to send packets from and the address
+    // of the server. The socket of the sink is used unless the hostname resolved
+    // to an address of a different family and a new socket was created for it.
+    fn send<F, T>(&self, socket: &UdpSocket, f: F) -> T
+    where
+        F: FnOnce(&UdpSocket, SocketAddr) -> T,
+    {
+        match self {
+            ServerAddr::Fixed(addr) => f(socket, *addr),
+            ServerAddr::Host(host) => {
+                let (addr, rebound) = HostAddr::get(host, Instant::now());
+                f(rebound.as_deref().unwrap_or(socket), addr)
+            }
+        }
+    }
+
+    // Resolve the hostname again the next time the address is used if sending
+    // failed for some reason other than the send buffer of the socket being full.
+    fn update<T>(&self, res: io::Result<T>) -> io::Result<T> {
+        if let (ServerAddr::Host(host), Err(e)) = (self, &res) {
+            if e.kind() != io::ErrorKind::WouldBlock {
+                host.failed(Instant::now());
+            }
+        }
+
+        res
+    }
+}
+
+// Resolve a hostname to the address to send to, which must be of the same
+// family as the local address the builder binds to, if one was set.
+fn lookup_host(host: &str, port: u16, builder: &UdpMetricSinkBuilder) -> MetricResult<SocketAddr> {
+    let bind = builder.bind_addr;
+    get_addrs((host, port), builder.resolve_policy)?
+        .into_iter()
+        .find(|a| bind.map(|b| b.is_ipv4() == a.is_ipv4()).unwrap_or(true))
+        .ok_or_else(|| {
+            MetricError::from((
+                ErrorKind::InvalidInput,
+                "No socket addresses yielded of the same family as the bind address",
+            ))
+        })
+}
+
+#[derive(Debug)]
+struct HostAddr {
+    host: String,
+    port: u16,
+    builder: UdpMetricSinkBuilder,
+    state: Mutex<Resolved>,
+}
+
+#[derive(Debug)]
+struct Resolved {
+    addr: SocketAddr,
+    socket: Option<Arc<UdpSocket>>,
+    resolving: bool,
+    resolved_at: Instant,
+    expires: Instant,
+}
+
+impl HostAddr {
+    // Return the current address and the socket created for it, if any,
+    // starting a thread to resolve the hostname again once the interval has
+    // passed so that callers emitting metrics never wait for a DNS lookup.
+    fn get(host: &Arc<HostAddr>, now: Instant) -> (SocketAddr, Option<Arc<UdpSocket>>) {
+        let mut state = host.state.lock().unwrap();
+        if now >= state.expires && !state.resolving {
+            // Push back the expiration so the hostname is resolved again after
+            // the interval even if this attempt fails. Until the thread is
+            // done, the current address keeps being used.
+            state.expires = now + host.builder.resolve_interval;
+            let resolver = Arc::clone(host);
+            state.resolving = thread::Builder::new()
+                .name("cadence-resolver".into())
+                .spawn(move || resolver.resolve(now))
+                .is_ok();
+        }
+
+        (state.addr, state.socket.clone())
+    }
+
+    // Resolve the hostname and switch to the new address, creating a socket
+    // based on the builder configuration when it's of a different family than
+    // the current one. The current address keeps being used if the hostname
+    // can't be resolved or the socket can't be created.
+    fn resolve(&self, now: Instant) {
+        let current = self.state.lock().unwrap().addr;
+        let resolved = match lookup_host(&self.host, self.port, &self.builder) {
+            Ok(addr) if addr.is_ipv4() == current.is_ipv4() => Some((addr, None)),
+            Ok(addr) => self.builder.socket(addr).ok().map(|s| (addr, Some(Arc::new(s)))),
+            Err(_) => None,
+        };
+
+        let mut state = self.state.lock().unwrap();
+        if let Some((addr, socket)) = resolved {
+            state.addr = addr;
+            state.resolved_at = now;
+            if socket.is_some() {
+                state.socket = socket;
+            }
+        }
+
+        state.resolving = false;
+    }
+
+    fn failed(&self, now: Instant) {
+        let mut state = self.state.lock().unwrap();
+        if now.saturating_duration_since(state.resolved_at) >= MIN_FAILURE_RESOLVE_INTERVAL {
+            state.expires = now;
+        }
+    }
+}
+
+/// Implementation of a builder pattern for `UdpMetricSink` and
+/// `BufferedUdpMetricSink` that creates the UDP socket for the sink.
+///
+/// The builder can be used to set the local address the socket is bound to
+/// and whether it's in non-blocking mode. When the `socket-options` feature
+/// is enabled, the size of the send buffer of the socket and the type of
+/// service (TOS) of packets sent can be set as well. Sinks can also be
+/// created using a socket configured by the caller instead, for options not
+/// supported by this builder.
+///
+/// Sinks created from a hostname using `.build_from_host()` or
+/// `.build_buffered_from_host()` resolve the hostname again periodically and
+/// after sending a metric fails, and send metrics to the new address if it
+/// changed, using a new socket if the address is of a different family. Sinks
+/// created using any other method only resolve it once.
+///
+/// # Example
+///
+/// ```no_run
+/// use cadence::{MetricSink, PacketSize, UdpMetricSinkBuilder, DEFAULT_PORT};
+///
+/// let sink = UdpMetricSinkBuilder::new()
+///     .with_bind_addr("10.0.0.2:0".parse().unwrap())
+///     .with_packet_size(PacketSize::Ethernet)
+///     .build_buffered(("metrics.example.com", DEFAULT_PORT))
+///     .unwrap();
+///
+/// sink.emit("foo.counter:4|c");
+/// ```
+#[derive(Debug, Clone)]
+pub struct UdpMetricSinkBuilder {
+    bind_addr: Option<SocketAddr>,
+    nonblocking: bool,
+    packet_size: Option<PacketSize>,
+    would_block: WouldBlockPolicy,
+    resolve_interval: Duration,
+    resolve_policy: ResolvePolicy,
+    #[cfg(feature = "socket-options")]
+    send_buffer_size: Option<usize>,
+    #[cfg(feature = "socket-options")]
+    tos: Option<u32>,
+}
+
+impl UdpMetricSinkBuilder {
+    /// Construct a new builder.
+    pub fn new() -> Self {
+        UdpMetricSinkBuilder {
+            bind_addr: None,
+            nonblocking: true,
+            packet_size: None,
+            would_block: WouldBlockPolicy::default(),
+            resolve_interval: DEFAULT_RESOLVE_INTERVAL,
+            resolve_policy: ResolvePolicy::default(),
+            #[cfg(feature = "socket-options")]
+            send_buffer_size: None,
+            #[cfg(feature = "socket-options")]
+            tos: None,
+        }
+    }
+
+    /// Set the local address to bind the socket to.
+    ///
+    /// By default, the socket is bound to an unspecified address and a port
+    /// picked by the operating system, `0.0.0.0:0` or `[::]:0` depending on
+    /// the address of the server.
+    pub fn with_bind_addr(mut self, addr: SocketAddr) -> Self {
+        self.bind_addr = Some(addr);
+        self
+    }
+
+    /// Set whether the socket is in non-blocking mode.
+    ///
+    /// By default, the socket is in non-blocking mode so that sending a metric
+    /// never blocks the caller. When the send buffer of the socket is full, the
+    /// metric is dropped and a `WouldBlock` error is returned instead.
+    pub fn with_nonblocking(mut self, nonblocking: bool) -> Self {
+        self.nonblocking = nonblocking;
+        self
+    }
+
+    /// Set what to do when a packet can't be sent because the socket is in
+    /// non-blocking mode and its send buffer is full.
+    ///
+    /// By default, the packet is dropped and a `WouldBlock` error is returned.
+    /// See `WouldBlockPolicy` for more information.
+    pub fn with_would_block_policy(mut self, policy: WouldBlockPolicy) -> Self {
+        self.would_block = policy;
+        self
+    }
+
+    /// Set whether IPv4 or IPv6 addresses are preferred when the hostname of
+    /// the server resolves to both.
+    ///
+    /// By default, the first address the hostname resolves to is used. See
+    /// `ResolvePolicy` for more information.
+    pub fn with_resolve_policy(mut self, policy: ResolvePolicy) -> Self {
+        self.resolve_policy = policy;
+        self
+    }
+
+    /// Set how often the hostname of the server is resolved again by sinks
+    /// created using `.build_from_host()` or `.build_buffered_from_host()`.
+    ///
+    /// The hostname is also resolved again after sending a metric fails, at
+    /// most once a second. If resolving the hostname fails, the sink keeps
+    /// using the last address it resolved to. By default, the hostname is
+    /// resolved again every 60 seconds. Resolving the hostname is done in a
+    /// background thread, callers emitting metrics keep using the last address
+    /// until it's done. If the hostname resolves to an address of a different
+    /// family, such as IPv6 instead of IPv4, a new socket is created for it
+    /// based on the configuration of this builder.
+    pub fn with_resolve_interval(mut self, interval: Duration) -> Self {
+        self.resolve_interval = interval;
+        self
+    }
+
+    /// Set the maximum size of the datagrams sent by `BufferedUdpMetricSink`
+    /// instances.
+    ///
+    /// See `BufferedUdpMetricSink::with_packet_size` for more information. By
+    /// default, metrics are buffered the same as `BufferedUdpMetricSink::from`.
+    /// This has no effect on `UdpMetricSink` instances since they are not
+    /// buffered.
+    pub fn with_packet_size(mut self, size: PacketSize) -> Self {
+        self.packet_size = Some(size);
+        self
+    }
+
+    /// Set the size of the send buffer of the socket (`SO_SNDBUF`) in bytes.
+    ///
+    /// A bigger send buffer allows more metrics to be queued by the operating
+    /// system during bursts before they are dropped. The operating system may
+    /// adjust the size, for example Linux doubles it and caps it according to
+    /// `net.core.wmem_max`. By default, the operating system default is used.
+    #[cfg(feature = "socket-options")]
+    pub fn with_send_buffer_size(mut self, size: usize) -> Self {
+        self.send_buffer_size = Some(size);
+        self
+    }
+
+    /// Set the type of service (`IP_TOS`) of packets sent by the socket.
+    ///
+    /// This can be used to mark metrics with a DSCP value for networks that
+    /// prioritize traffic based on it, shifted left two bits since DSCP uses
+    /// the upper six bits of the field. For example, `46 << 2` for expedited
+    /// forwarding. This is only supported for IPv4 sockets.
+    #[cfg(feature = "socket-options")]
+    pub fn with_tos(mut self, tos: u32) -> Self {
+        self.tos = Some(tos);
+        self
+    }
+
+    /// Construct a new `UdpMetricSink` instance that will emit metrics to the
+    /// given address using a new socket based on the builder configuration.
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server.
+    /// * The host address is otherwise unable to be parsed
+    /// * The socket can't be created, bound, or configured
+    pub fn build<A>(self, to_addr: A) -> MetricResult<UdpMetricSink>
+    where
+        A: ToSocketAddrs,
+    {
+        let addr = get_addrs(to_addr, self.resolve_policy)?[0];
+        self.udp_sink(ServerAddr::Fixed(addr))
+    }
+
+    /// Construct a new `UdpMetricSink` instance that will emit metrics to the
+    /// given hostname and port using a new socket based on the builder
+    /// configuration, resolving the hostname again periodically.
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server.
+    /// * The socket can't be created, bound, or configured
+    pub fn build_from_host(self, host: &str, port: u16) -> MetricResult<UdpMetricSink> {
+        let addr = ServerAddr::host(host, port, &self)?;
+        self.udp_sink(addr)
+    }
+
+    /// Construct a new `BufferedUdpMetricSink` instance that will emit metrics
+    /// to the given address using a new socket based on the builder
+    /// configuration.
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server.
+    /// * The host address is otherwise unable to be parsed
+    /// * The socket can't be created, bound, or configured
+    /// * The packet size is zero
+    pub fn build_buffered<A>(self, to_addr: A) -> MetricResult<BufferedUdpMetricSink>
+    where
+        A: ToSocketAddrs,
+    {
+        let addr = get_addrs(to_addr, self.resolve_policy)?[0];
+        self.buffered_udp_sink(ServerAddr::Fixed(addr))
+    }
+
+    /// Construct a new `BufferedUdpMetricSink` instance that will emit metrics
+    /// to the given hostname and port using a new socket based on the builder
+    /// configuration, resolving the hostname again periodically.
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server.
+    /// * The socket can't be created, bound, or configured
+    /// * The packet size is zero
+    pub fn build_buffered_from_host(self, host: &str, port: u16) -> MetricResult<BufferedUdpMetricSink> {
+        let addr = ServerAddr::host(host, port, &self)?;
+        self.buffered_udp_sink(addr)
+    }
+
+    fn udp_sink(&self, addr: ServerAddr) -> MetricResult<UdpMetricSink> {
+        let socket = self.socket(addr.addr())?;
+        let sink = UdpMetricSink::new(addr, socket);
+        Ok(sink.with_would_block_policy(self.would_block))
+    }
+
+    fn buffered_udp_sink(&self, addr: ServerAddr) -> MetricResult<BufferedUdpMetricSink> {
+        let socket = self.socket(addr.addr())?;
+        let sink = match self.packet_size {
+            Some(size) => BufferedUdpMetricSink::packet_sized(addr, socket, size)?,
+            None => BufferedUdpMetricSink::new(addr, socket, DEFAULT_BUFFER_SIZE),
+        };
+
+        Ok(sink.with_would_block_policy(self.would_block))
+    }
+
+    // Local address to bind to, an unspecified address of the same family as
+    // the server if one wasn't set.
+    fn bind_addr(&self, to_addr: SocketAddr) -> SocketAddr {
+        self.bind_addr.unwrap_or_else(|| match to_addr {
+            SocketAddr::V4(_) => SocketAddr::from(([0, 0, 0, 0], 0)),
+            SocketAddr::V6(_) => SocketAddr::from(([0u16; 8], 0)),
+        })
+    }
+
+    #[cfg(not(feature = "socket-options"))]
+    fn socket(&self, to_addr: SocketAddr) -> io::Result<UdpSocket> {
+        let socket = UdpSocket::bind(self.bind_addr(to_addr))?;
+        socket.set_nonblocking(self.nonblocking)?;
+        Ok(socket)
+    }
+
+    #[cfg(feature = "socket-options")]
+    fn socket(&self, to_addr: SocketAddr) -> io::Result<UdpSocket> {
+        use socket2::{Domain, Protocol, Socket, Type};
+
+        let bind_addr = self.bind_addr(to_addr);
+        let socket = Socket::new(Domain::for_address(bind_addr), Type::DGRAM, Some(Protocol::UDP))?;
+        if let Some(size) = self.send_buffer_size {
+            socket.set_send_buffer_size(size)?;
+        }
+
+        if let Some(tos) = self.tos {
+            if !bind_addr.is_ipv4() {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "type of service can only be set for IPv4 sockets",
+                ));
+            }
+
+            socket.set_tos_v4(tos)?;
+        }
+
+        socket.bind(&bind_addr.into())?;
+        socket.set_nonblocking(self.nonblocking)?;
+        Ok(socket.into())
+    }
+}
+
+impl Default for UdpMetricSinkBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Implementation of a `MetricSink` that emits metrics over UDP.
 ///
 /// This is the most basic version of `MetricSink` that sends metrics over
@@ -50,9 +686,11 @@ fn get_addr<A: ToSocketAddrs>(addr: A) -> MetricResult<SocketAddr> {
 /// called, in the thread of the caller.
 #[derive(Debug)]
 pub struct UdpMetricSink {
-    addr: SocketAddr,
+    addr: ServerAddr,
     socket: UdpSocket,
     stats: SocketStats,
+    would_block: WouldBlockPolicy,
+    would_block_dropped: AtomicU64,
 }
 
 impl UdpMetricSink {
@@ -104,43 +742,54 @@ impl UdpMetricSink {
         A: ToSocketAddrs,
     {
         let addr = get_addr(to_addr)?;
-        let stats = SocketStats::default();
-        Ok(UdpMetricSink { addr, socket, stats })
+        Ok(Self::new(ServerAddr::Fixed(addr), socket))
     }
-}
 
-impl MetricSink for UdpMetricSink {
-    fn emit(&self, metric: &str) -> io::Result<usize> {
-        self.stats
-            .update(self.socket.send_to(metric.as_bytes(), self.addr), metric.len())
+    fn new(addr: ServerAddr, socket: UdpSocket) -> UdpMetricSink {
+        UdpMetricSink {
+            addr,
+            socket,
+            stats: SocketStats::default(),
+            would_block: WouldBlockPolicy::default(),
+            would_block_dropped: AtomicU64::new(0),
+        }
     }
 
-    fn stats(&self) -> SinkStats {
-        (&self.stats).into()
+    /// Set what to do when a metric can't be sent because the socket is in
+    /// non-blocking mode and its send buffer is full.
+    ///
+    /// By default, the metric is dropped and a `WouldBlock` error is returned.
+    /// See `WouldBlockPolicy` for more information.
+    pub fn with_would_block_policy(mut self, policy: WouldBlockPolicy) -> Self {
+        self.would_block = policy;
+        self
     }
-}
 
-/// Adapter for writing to a `UdpSocket` via the `Write` trait
-#[derive(Debug)]
-pub(crate) struct UdpWriteAdapter {
-    addr: SocketAddr,
-    socket: UdpSocket,
-    stats: SocketStats,
+    /// Return the number of metrics dropped without an error because the send
+    /// buffer of the socket was full, based on the `WouldBlockPolicy`.
+    pub fn would_block_dropped(&self) -> u64 {
+        self.would_block_dropped.load(Ordering::Relaxed)
+    }
 }
 
-impl UdpWriteAdapter {
-    pub(crate) fn new(addr: SocketAddr, socket: UdpSocket, stats: SocketStats) -> UdpWriteAdapter {
-        UdpWriteAdapter { addr, socket, stats }
+impl MetricSink for UdpMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let res = self.addr.send(&self.socket, |socket, addr| {
+            let send = || socket.send_to(metric.as_bytes(), addr);
+            self.would_block
+                .handle(send(), send, metric.len(), &self.stats, &self.would_block_dropped)
+        });
+        self.addr.update(res)
     }
-}
 
-impl Write for UdpWriteAdapter {
-    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.stats.update(self.socket.send_to(buf, self.addr), buf.len())
+    fn check(&self) -> io::Result<()> {
+        self.addr
+            .send(&self.socket, |socket, addr| socket.send_to(&[], addr))
+            .map(|_| ())
     }
 
-    fn flush(&mut self) -> io::Result<()> {
-        Ok(())
+    fn stats(&self) -> SinkStats {
+        (&self.stats).into()
     }
 }
 
@@ -165,11 +814,38 @@ impl Write for UdpWriteAdapter {
 /// possible that they may sit in the buffer for a while for applications
 /// that do not emit metrics frequently or at a high volume. For these low-
 /// throughput use cases, it may make more sense to use the `UdpMetricSink`
-/// since it sends metrics immediately with no buffering.
+/// since it sends metrics immediately with no buffering, or to limit how long
+/// metrics are buffered using `.with_flush_interval()`.
+///
+/// When the buffer is full, it's swapped for an empty buffer and sent to the
+/// socket by the thread that filled it without holding the lock on the buffer,
+/// so other threads can keep writing metrics while the full buffer is sent.
+/// Buffers are reused once they've been sent. If sending a buffer fails, the
+/// metrics in it are dropped and the error is returned to the thread that
+/// filled it.
+///
+/// When the `sendmmsg` feature is enabled on Linux, writes that fill several
+/// buffers at once, such as batches of metrics, send all of them with a single
+/// `sendmmsg` system call.
 #[derive(Debug)]
 pub struct BufferedUdpMetricSink {
-    buffer: Mutex<MultiLineWriter<UdpWriteAdapter>>,
+    buffer: Mutex<PacketBuffer>,
+    pool: Mutex<Vec<String>>,
+    socket: UdpSocket,
+    addr: ServerAddr,
+    capacity: usize,
+    flush_interval: Option<Duration>,
     stats: SocketStats,
+    max_packet: Option<usize>,
+    would_block: WouldBlockPolicy,
+    would_block_dropped: AtomicU64,
+}
+
+/// Buffer that metrics are written to until it's full and sent as a packet
+#[derive(Debug)]
+struct PacketBuffer {
+    packet: String,
+    buffered_at: Option<Instant>,
 }
 
 impl BufferedUdpMetricSink {
@@ -247,26 +923,324 @@ impl BufferedUdpMetricSink {
         A: ToSocketAddrs,
     {
         let addr = get_addr(sink_addr)?;
-        let stats = SocketStats::default();
-        Ok(BufferedUdpMetricSink {
-            buffer: Mutex::new(MultiLineWriter::new(
-                UdpWriteAdapter::new(addr, socket, stats.clone()),
-                cap,
-            )),
-            stats,
+        Ok(Self::new(ServerAddr::Fixed(addr), socket, cap))
+    }
+
+    fn new(addr: ServerAddr, socket: UdpSocket, cap: usize) -> BufferedUdpMetricSink {
+        BufferedUdpMetricSink {
+            buffer: Mutex::new(PacketBuffer {
+                packet: String::with_capacity(cap),
+                buffered_at: None,
+            }),
+            pool: Mutex::new(Vec::new()),
+            socket,
+            addr,
+            capacity: cap,
+            flush_interval: None,
+            stats: SocketStats::default(),
+            max_packet: None,
+            would_block: WouldBlockPolicy::default(),
+            would_block_dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Construct a new `BufferedUdpMetricSink` instance that never sends a
+    /// datagram bigger than the given packet size.
+    ///
+    /// Metrics are buffered until the next metric wouldn't fit in a packet of
+    /// the given size and then sent as a single datagram. Buffered metrics are
+    /// only ever split at newlines between metrics. Unlike sinks created with
+    /// `with_capacity`, a metric bigger than the packet size is rejected with
+    /// an `InvalidInput` error and counted as dropped instead of being sent as
+    /// a datagram by itself.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::net::UdpSocket;
+    /// use cadence::{BufferedUdpMetricSink, PacketSize, DEFAULT_PORT};
+    ///
+    /// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    /// let host = ("metrics.example.com", DEFAULT_PORT);
+    /// let sink = BufferedUdpMetricSink::with_packet_size(host, socket, PacketSize::Jumbo);
+    /// ```
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server.
+    /// * The host address is otherwise unable to be parsed
+    /// * The packet size is zero
+    pub fn with_packet_size<A>(sink_addr: A, socket: UdpSocket, size: PacketSize) -> MetricResult<BufferedUdpMetricSink>
+    where
+        A: ToSocketAddrs,
+    {
+        let addr = get_addr(sink_addr)?;
+        Self::packet_sized(ServerAddr::Fixed(addr), socket, size)
+    }
+
+    fn packet_sized(addr: ServerAddr, socket: UdpSocket, size: PacketSize) -> MetricResult<BufferedUdpMetricSink> {
+        let bytes = size.bytes();
+        if bytes == 0 {
+            return Err(MetricError::from((
+                ErrorKind::InvalidInput,
+                "Packet size must be non-zero",
+            )));
+        }
+
+        let mut sink = Self::new(addr, socket, bytes);
+        sink.max_packet = Some(bytes);
+        Ok(sink)
+    }
+
+    /// Set how long metrics may sit in the buffer before being sent, even if
+    /// the buffer isn't full.
+    ///
+    /// The buffer is flushed when a metric is emitted and the oldest metric in
+    /// the buffer was emitted longer ago than the interval. A background thread
+    /// is not used, so metrics are not sent if nothing else is emitted. Wrap
+    /// this sink with a `QueuingMetricSink` and call `.flush()` periodically if
+    /// metrics must be sent even when nothing else is emitted. By default, the
+    /// buffer is only flushed when it is full.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::net::UdpSocket;
+    /// use std::time::Duration;
+    /// use cadence::{BufferedUdpMetricSink, DEFAULT_PORT};
+    ///
+    /// let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
+    /// let host = ("metrics.example.com", DEFAULT_PORT);
+    /// let sink = BufferedUdpMetricSink::from(host, socket)
+    ///     .unwrap()
+    ///     .with_flush_interval(Duration::from_secs(1));
+    /// ```
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = Some(interval);
+        self
+    }
+
+    /// Set what to do when a packet can't be sent because the socket is in
+    /// non-blocking mode and its send buffer is full.
+    ///
+    /// By default, the packet is dropped and a `WouldBlock` error is returned.
+    /// See `WouldBlockPolicy` for more information.
+    pub fn with_would_block_policy(mut self, policy: WouldBlockPolicy) -> Self {
+        self.would_block = policy;
+        self
+    }
+
+    /// Return the number of packets dropped without an error because the send
+    /// buffer of the socket was full, based on the `WouldBlockPolicy`.
+    pub fn would_block_dropped(&self) -> u64 {
+        self.would_block_dropped.load(Ordering::Relaxed)
+    }
+
+    // Add a metric to the buffer. If the buffer is full or has been buffering
+    // metrics for longer than the flush interval, it's swapped for an empty
+    // buffer and added to `packets` to be sent once the lock is released.
+    fn buffer_metric(&self, buffer: &mut PacketBuffer, metric: &str, packets: &mut Vec<String>) {
+        let required = metric.len() + 1;
+
+        // Metrics bigger than the buffer are sent by themselves without the
+        // trailing newline, see https://github.com/56quarters/cadence/issues/87
+        if required > self.capacity {
+            let mut packet = self.take_buffer();
+            packet.push_str(metric);
+            packets.push(packet);
+            return;
+        }
+
+        if buffer.packet.len() + required > self.capacity {
+            packets.push(self.swap_buffer(buffer));
+        }
+
+        buffer.packet.push_str(metric);
+        buffer.packet.push('\n');
+
+        if let Some(interval) = self.flush_interval {
+            let now = Instant::now();
+            let buffered_at = *buffer.buffered_at.get_or_insert(now);
+            if now.duration_since(buffered_at) >= interval {
+                packets.push(self.swap_buffer(buffer));
+            }
+        }
+    }
+
+    fn swap_buffer(&self, buffer: &mut PacketBuffer) -> String {
+        buffer.buffered_at = None;
+        mem::replace(&mut buffer.packet, self.take_buffer())
+    }
+
+    fn take_buffer(&self) -> String {
+        let mut pool = self.pool.lock().unwrap();
+        pool.pop().unwrap_or_else(|| String::with_capacity(self.capacity))
+    }
+
+    // Keep buffers for reuse unless they grew to hold a metric bigger than
+    // the buffer size.
+    fn return_buffer(&self, mut packet: String) {
+        if packet.capacity() <= self.capacity {
+            packet.clear();
+            let mut pool = self.pool.lock().unwrap();
+            if pool.len() < MAX_POOLED_BUFFERS {
+                pool.push(packet);
+            }
+        }
+    }
+
+    // Send every packet, even if sending some of them fails, returning the first error.
+    fn send_packets(&self, packets: Vec<String>) -> io::Result<()> {
+        let mut first_err = None;
+        let mut sent = 0;
+
+        while sent < packets.len() {
+            let (n, res) = self.send_some(&packets[sent..]);
+            if let Err(e) = res {
+                first_err.get_or_insert(e);
+            }
+
+            sent += n;
+        }
+
+        for packet in packets {
+            self.return_buffer(packet);
+        }
+
+        match first_err {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    // Send one or more of the packets, returning how many of them were either
+    // sent or dropped and the error that caused a packet to be dropped, if any.
+    #[cfg(not(all(feature = "sendmmsg", target_os = "linux")))]
+    fn send_some(&self, packets: &[String]) -> (usize, io::Result<()>) {
+        (1, self.send_one(&packets[0]))
+    }
+
+    // Send as many of the packets as the kernel will accept with a single call
+    // to `sendmmsg`, instead of one system call per packet.
+    #[cfg(all(feature = "sendmmsg", target_os = "linux"))]
+    fn send_some(&self, packets: &[String]) -> (usize, io::Result<()>) {
+        if packets.len() == 1 {
+            return (1, self.send_one(&packets[0]));
+        }
+
+        self.addr.send(&self.socket, |socket, addr| {
+            let addrs = vec![Some(SockaddrStorage::from(addr)); packets.len()];
+            let slices: Vec<[IoSlice<'_>; 1]> = packets.iter().map(|p| [IoSlice::new(p.as_bytes())]).collect();
+            let mut headers = MultiHeaders::preallocate(packets.len(), None);
+
+            match sendmmsg(socket.as_raw_fd(), &mut headers, &slices, &addrs, [], MsgFlags::empty()) {
+                Ok(results) => {
+                    let mut sent = 0;
+                    for (packet, res) in packets.iter().zip(results) {
+                        let _ = self.stats.update(Ok(res.bytes), packet.len());
+                        sent += 1;
+                    }
+
+                    if sent == 0 {
+                        (1, self.send_one(&packets[0]))
+                    } else {
+                        (sent, Ok(()))
+                    }
+                }
+                // The error is for the first packet, the rest may still be sent
+                Err(e) => (1, self.send_result(Err(e.into()), socket, addr, &packets[0])),
+            }
         })
     }
+
+    fn send_one(&self, packet: &str) -> io::Result<()> {
+        self.addr.send(&self.socket, |socket, addr| {
+            self.send_result(socket.send_to(packet.as_bytes(), addr), socket, addr, packet)
+        })
+    }
+
+    fn send_result(
+        &self,
+        res: io::Result<usize>,
+        socket: &UdpSocket,
+        addr: SocketAddr,
+        packet: &str,
+    ) -> io::Result<()> {
+        let send = || socket.send_to(packet.as_bytes(), addr);
+        let res = self
+            .would_block
+            .handle(res, send, packet.len(), &self.stats, &self.would_block_dropped);
+        self.addr.update(res).map(|_| ())
+    }
+
+    // Drop metrics that would never fit in a packet instead of sending them
+    // in a packet that's too large.
+    fn check_size(&self, metric: &str) -> io::Result<()> {
+        if let Some(max) = self.max_packet {
+            if metric.len() > max {
+                self.stats.incr_bytes_dropped(metric.len() as u64);
+                self.stats.incr_packets_dropped();
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidInput,
+                    "metric larger than maximum packet size",
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Drop for BufferedUdpMetricSink {
+    /// Send any buffered metrics before the sink is destroyed.
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
 }
 
 impl MetricSink for BufferedUdpMetricSink {
     fn emit(&self, metric: &str) -> io::Result<usize> {
-        let mut writer = self.buffer.lock().unwrap();
-        writer.write(metric.as_bytes())
+        self.check_size(metric)?;
+
+        let mut packets = Vec::new();
+        self.buffer_metric(&mut self.buffer.lock().unwrap(), metric, &mut packets);
+        self.send_packets(packets)?;
+        Ok(metric.len())
+    }
+
+    fn emit_batch(&self, metrics: &[&str]) -> io::Result<usize> {
+        let mut packets = Vec::new();
+        let res = {
+            let mut buffer = self.buffer.lock().unwrap();
+            emit_each(metrics, |m| {
+                self.check_size(m)?;
+                self.buffer_metric(&mut buffer, m, &mut packets);
+                Ok(m.len())
+            })
+        };
+
+        self.send_packets(packets)?;
+        res
     }
 
     fn flush(&self) -> io::Result<()> {
-        let mut writer = self.buffer.lock().unwrap();
-        writer.flush()
+        let mut packets = Vec::new();
+        {
+            let mut buffer = self.buffer.lock().unwrap();
+            if !buffer.packet.is_empty() {
+                packets.push(self.swap_buffer(&mut buffer));
+            }
+        }
+
+        self.send_packets(packets)
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.addr
+            .send(&self.socket, |socket, addr| socket.send_to(&[], addr))
+            .map(|_| ())
     }
 
     fn stats(&self) -> SinkStats {
@@ -276,8 +1250,18 @@ impl MetricSink for BufferedUdpMetricSink {
 
 #[cfg(test)]
 mod tests {
-    use super::{get_addr, BufferedUdpMetricSink, MetricSink, UdpMetricSink};
-    use std::net::UdpSocket;
+    use super::{
+        get_addr, BufferedUdpMetricSink, HostAddr, MetricSink, PacketSize, ResolvePolicy, ServerAddr, UdpMetricSink,
+        UdpMetricSinkBuilder, WouldBlockPolicy,
+    };
+    use crate::sinks::core::{SinkStats, SocketStats};
+    use crate::types::Counter;
+    use std::io;
+    use std::net::{SocketAddr, UdpSocket};
+    use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::thread;
+    use std::time::{Duration, Instant};
 
     #[test]
     fn test_get_addr_bad_address() {
@@ -285,6 +1269,27 @@ mod tests {
         assert!(res.is_err());
     }
 
+    #[test]
+    fn test_resolve_policy_sort() {
+        let v4a: SocketAddr = "10.0.0.1:8125".parse().unwrap();
+        let v4b: SocketAddr = "10.0.0.2:8125".parse().unwrap();
+        let v6a: SocketAddr = "[fd00::1]:8125".parse().unwrap();
+        let v6b: SocketAddr = "[fd00::2]:8125".parse().unwrap();
+        let resolved = vec![v6a, v4a, v6b, v4b];
+
+        let mut addrs = resolved.clone();
+        ResolvePolicy::First.sort(&mut addrs);
+        assert_eq!(resolved, addrs);
+
+        let mut addrs = resolved.clone();
+        ResolvePolicy::PreferIpv4.sort(&mut addrs);
+        assert_eq!(vec![v4a, v4b, v6a, v6b], addrs);
+
+        let mut addrs = resolved;
+        ResolvePolicy::PreferIpv6.sort(&mut addrs);
+        assert_eq!(vec![v6a, v6b, v4a, v4b], addrs);
+    }
+
     #[test]
     fn test_get_addr_valid_address() {
         let res = get_addr("127.0.0.1:8125");
@@ -306,6 +1311,214 @@ mod tests {
         assert_eq!(7, sink.emit("baz:1|m").unwrap());
     }
 
+    // Address that the hostname resolved to before it changed
+    fn stale_addr() -> SocketAddr {
+        "192.0.2.1:8125".parse().unwrap()
+    }
+
+    fn host_addr(builder: UdpMetricSinkBuilder, stale: SocketAddr) -> Arc<HostAddr> {
+        match ServerAddr::host("127.0.0.1", 8125, &builder).unwrap() {
+            ServerAddr::Host(host) => {
+                host.state.lock().unwrap().addr = stale;
+                host
+            }
+            ServerAddr::Fixed(_) => unreachable!(),
+        }
+    }
+
+    // Wait for the thread started to resolve the hostname to finish
+    fn wait_for_resolver(host: &HostAddr) {
+        while host.state.lock().unwrap().resolving {
+            thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn test_host_addr_resolves_after_interval() {
+        let builder = UdpMetricSinkBuilder::new().with_resolve_interval(Duration::from_secs(10));
+        let host = host_addr(builder, stale_addr());
+        let now = Instant::now();
+
+        assert_eq!(stale_addr(), HostAddr::get(&host, now).0);
+        assert_eq!(stale_addr(), HostAddr::get(&host, now + Duration::from_secs(5)).0);
+
+        // The hostname is resolved in the background, the caller isn't blocked
+        assert_eq!(stale_addr(), HostAddr::get(&host, now + Duration::from_secs(10)).0);
+        wait_for_resolver(&host);
+        assert_eq!(
+            get_addr("127.0.0.1:8125").unwrap(),
+            HostAddr::get(&host, now + Duration::from_secs(10)).0
+        );
+    }
+
+    #[test]
+    fn test_host_addr_resolves_after_failure() {
+        let builder = UdpMetricSinkBuilder::new().with_resolve_interval(Duration::from_secs(60));
+        let host = host_addr(builder, stale_addr());
+        let now = Instant::now();
+
+        // Resolved too recently for a failure to resolve it again
+        host.failed(now);
+        assert_eq!(stale_addr(), HostAddr::get(&host, now).0);
+
+        host.failed(now + Duration::from_secs(1));
+        HostAddr::get(&host, now + Duration::from_secs(1));
+        wait_for_resolver(&host);
+        assert_eq!(
+            get_addr("127.0.0.1:8125").unwrap(),
+            HostAddr::get(&host, now + Duration::from_secs(1)).0
+        );
+    }
+
+    #[test]
+    fn test_host_addr_new_socket_for_different_family() {
+        let host = host_addr(UdpMetricSinkBuilder::new(), "[2001:db8::1]:8125".parse().unwrap());
+        assert!(HostAddr::get(&host, Instant::now()).1.is_none());
+
+        host.resolve(Instant::now());
+        let (addr, socket) = HostAddr::get(&host, Instant::now());
+        assert_eq!(get_addr("127.0.0.1:8125").unwrap(), addr);
+        assert!(socket.unwrap().local_addr().unwrap().is_ipv4());
+
+        // Same family as the last address, the new socket keeps being used
+        host.resolve(Instant::now());
+        assert!(HostAddr::get(&host, Instant::now()).1.is_some());
+    }
+
+    #[test]
+    fn test_host_addr_bind_addr_family() {
+        let builder = UdpMetricSinkBuilder::new().with_bind_addr("[::1]:0".parse().unwrap());
+        assert!(ServerAddr::host("127.0.0.1", 8125, &builder).is_err());
+    }
+
+    #[test]
+    fn test_udp_metric_sink_builder_from_host() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let port = server.local_addr().unwrap().port();
+        let sink = UdpMetricSinkBuilder::new()
+            .with_resolve_interval(Duration::from_secs(1))
+            .build_buffered_from_host("127.0.0.1", port)
+            .unwrap();
+
+        assert_eq!(7, sink.emit("buz:1|m").unwrap());
+        sink.flush().unwrap();
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"buz:1|m\n", &buf[..len]);
+    }
+
+    // Handle a send that would block `blocked` times before succeeding, returning
+    // the result, how many times the packet was sent, and the stats.
+    fn handle_would_block(policy: WouldBlockPolicy, blocked: usize) -> (io::Result<usize>, usize, SinkStats, u64) {
+        let attempts = AtomicUsize::new(0);
+        let send = || {
+            if attempts.fetch_add(1, Ordering::Relaxed) < blocked {
+                Err(io::Error::from(io::ErrorKind::WouldBlock))
+            } else {
+                Ok(7)
+            }
+        };
+
+        let stats = SocketStats::default();
+        let dropped = AtomicU64::new(0);
+        let res = policy.handle(send(), send, 7, &stats, &dropped);
+        (res, attempts.into_inner(), (&stats).into(), dropped.into_inner())
+    }
+
+    #[test]
+    fn test_would_block_policy_error() {
+        let (res, attempts, stats, dropped) = handle_would_block(WouldBlockPolicy::Error, 1);
+        assert_eq!(io::ErrorKind::WouldBlock, res.unwrap_err().kind());
+        assert_eq!(1, attempts);
+        assert_eq!(1, stats.packets_dropped);
+        assert_eq!(0, dropped);
+    }
+
+    #[test]
+    fn test_would_block_policy_drop() {
+        let (res, attempts, stats, dropped) = handle_would_block(WouldBlockPolicy::Drop, 1);
+        assert_eq!(0, res.unwrap());
+        assert_eq!(1, attempts);
+        assert_eq!(1, stats.packets_dropped);
+        assert_eq!(7, stats.bytes_dropped);
+        assert_eq!(1, dropped);
+    }
+
+    #[test]
+    fn test_would_block_policy_retry_once() {
+        let (res, attempts, stats, dropped) = handle_would_block(WouldBlockPolicy::RetryOnce, 1);
+        assert_eq!(7, res.unwrap());
+        assert_eq!(2, attempts);
+        assert_eq!(1, stats.packets_sent);
+        assert_eq!(0, dropped);
+
+        let (res, attempts, stats, dropped) = handle_would_block(WouldBlockPolicy::RetryOnce, 2);
+        assert_eq!(0, res.unwrap());
+        assert_eq!(2, attempts);
+        assert_eq!(1, stats.packets_dropped);
+        assert_eq!(1, dropped);
+    }
+
+    #[test]
+    fn test_would_block_policy_other_errors() {
+        let stats = SocketStats::default();
+        let dropped = AtomicU64::new(0);
+        let send = || Err(io::Error::from(io::ErrorKind::ConnectionRefused));
+
+        let res = WouldBlockPolicy::Drop.handle(send(), send, 7, &stats, &dropped);
+        assert_eq!(io::ErrorKind::ConnectionRefused, res.unwrap_err().kind());
+        assert_eq!(0, dropped.into_inner());
+    }
+
+    #[test]
+    fn test_udp_metric_sink_builder() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = UdpMetricSinkBuilder::new()
+            .with_bind_addr("127.0.0.1:0".parse().unwrap())
+            .build(server.local_addr().unwrap())
+            .unwrap();
+
+        assert_eq!(7, sink.emit("buz:1|m").unwrap());
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"buz:1|m", &buf[..len]);
+    }
+
+    #[test]
+    fn test_udp_metric_sink_builder_buffered() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = UdpMetricSinkBuilder::new()
+            .with_packet_size(PacketSize::Custom(20))
+            .build_buffered(server.local_addr().unwrap())
+            .unwrap();
+
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        assert_eq!(8, sink.emit("foo:67|c").unwrap());
+        assert_eq!(8, sink.emit("foo:89|c").unwrap());
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"foo:54|c\nfoo:67|c\n", &buf[..len]);
+    }
+
+    #[cfg(feature = "socket-options")]
+    #[test]
+    fn test_udp_metric_sink_builder_socket_options() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = UdpMetricSinkBuilder::new()
+            .with_send_buffer_size(64 * 1024)
+            .with_tos(46 << 2)
+            .build(server.local_addr().unwrap())
+            .unwrap();
+
+        assert_eq!(7, sink.emit("buz:1|m").unwrap());
+
+        let res = UdpMetricSinkBuilder::new().with_tos(46 << 2).build("[::1]:8125");
+        assert!(res.is_err());
+    }
+
     #[test]
     fn test_buffered_udp_metric_sink() {
         let socket = UdpSocket::bind("0.0.0.0:0").unwrap();
@@ -327,4 +1540,228 @@ mod tests {
         assert_eq!(8, sink.emit("foo:54|c").unwrap());
         assert!(sink.flush().is_ok());
     }
+
+    #[test]
+    fn test_packet_size_bytes() {
+        assert_eq!(512, PacketSize::Internet.bytes());
+        assert_eq!(1432, PacketSize::Ethernet.bytes());
+        assert_eq!(8932, PacketSize::Jumbo.bytes());
+        assert_eq!(100, PacketSize::Custom(100).bytes());
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_packet_size() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink =
+            BufferedUdpMetricSink::with_packet_size(server.local_addr().unwrap(), socket, PacketSize::Custom(20))
+                .unwrap();
+
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        assert_eq!(8, sink.emit("foo:67|c").unwrap());
+        // Doesn't fit in the 18 bytes already buffered so they're sent first
+        assert_eq!(8, sink.emit("foo:89|c").unwrap());
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"foo:54|c\nfoo:67|c\n", &buf[..len]);
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_emit_batch() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink =
+            BufferedUdpMetricSink::with_packet_size(server.local_addr().unwrap(), socket, PacketSize::Custom(20))
+                .unwrap();
+
+        let res = sink.emit_batch(&["foo:54|c", "foo:67|c", "foo:1234567890123456789|c"]);
+        assert!(res.is_err());
+        sink.flush().unwrap();
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"foo:54|c\nfoo:67|c\n", &buf[..len]);
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_packet_size_oversized() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSink::with_packet_size("127.0.0.1:8125", socket, PacketSize::Custom(8)).unwrap();
+
+        assert!(sink.emit("some_really_long_metric:456|c").is_err());
+        assert_eq!(1, sink.stats().packets_dropped);
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_emit_metric() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink =
+            BufferedUdpMetricSink::with_packet_size(server.local_addr().unwrap(), socket, PacketSize::Custom(20))
+                .unwrap();
+
+        assert_eq!(8, sink.emit_metric(&Counter::from("foo:54|c".to_owned())).unwrap());
+        assert!(sink
+            .emit_metric(&Counter::from("foo:1234567890123456789|c".to_owned()))
+            .is_err());
+        assert_eq!(1, sink.stats().packets_dropped);
+        sink.flush().unwrap();
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"foo:54|c\n", &buf[..len]);
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_packet_size_zero() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        assert!(BufferedUdpMetricSink::with_packet_size("127.0.0.1:8125", socket, PacketSize::Custom(0)).is_err());
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_flush_interval() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        // The buffer is big enough that the metric would be buffered if
+        // not for the flush interval having already elapsed.
+        let sink = BufferedUdpMetricSink::with_capacity(server.local_addr().unwrap(), socket, 64)
+            .unwrap()
+            .with_flush_interval(Duration::from_secs(0));
+
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"foo:54|c\n", &buf[..len]);
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_reuses_buffers() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSink::with_capacity(server.local_addr().unwrap(), socket, 20).unwrap();
+
+        sink.emit("foo:54|c").unwrap();
+        sink.emit("foo:67|c").unwrap();
+        // Swaps the full buffer for a new one and returns it to the pool once sent
+        sink.emit("foo:89|c").unwrap();
+        assert_eq!(1, sink.pool.lock().unwrap().len());
+
+        // Swaps the buffer for the pooled one
+        sink.flush().unwrap();
+        assert_eq!(1, sink.pool.lock().unwrap().len());
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"foo:54|c\nfoo:67|c\n", &buf[..len]);
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"foo:89|c\n", &buf[..len]);
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_many_threads() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        server.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = Arc::new(BufferedUdpMetricSink::with_capacity(server.local_addr().unwrap(), socket, 64).unwrap());
+
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                let sink = sink.clone();
+                thread::spawn(move || {
+                    for _ in 0..50 {
+                        sink.emit("foo:54|c").unwrap();
+                    }
+                })
+            })
+            .collect();
+
+        for t in threads {
+            t.join().unwrap();
+        }
+
+        sink.flush().unwrap();
+
+        let mut received = 0;
+        let mut buf = [0; 64];
+        while received < 200 {
+            let len = server.recv(&mut buf).unwrap();
+            assert!(len <= 64);
+            received += buf[..len].split(|b| *b == b'\n').filter(|m| !m.is_empty()).count();
+        }
+
+        assert_eq!(200, received);
+        assert_eq!(0, sink.stats().packets_dropped);
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_send_error() {
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSink::with_capacity("127.0.0.1:8125", socket, 100_000).unwrap();
+        let metric = format!("foo:{}|c", "1".repeat(70_000));
+
+        // Too big for a single datagram so the packet is dropped when sent
+        assert_eq!(metric.len(), sink.emit(&metric).unwrap());
+        assert!(sink.flush().is_err());
+        assert_eq!(1, sink.stats().packets_dropped);
+
+        // Nothing is left in the buffer to retry
+        assert!(sink.flush().is_ok());
+        assert_eq!(1, sink.stats().packets_dropped);
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_flush_on_drop() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSink::with_capacity(server.local_addr().unwrap(), socket, 64).unwrap();
+
+        sink.emit("foo:54|c").unwrap();
+        drop(sink);
+
+        let mut buf = [0; 64];
+        let len = server.recv(&mut buf).unwrap();
+        assert_eq!(b"foo:54|c\n", &buf[..len]);
+    }
+
+    #[test]
+    fn test_buffered_udp_metric_sink_emit_batch_many_packets() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSink::with_capacity(server.local_addr().unwrap(), socket, 10).unwrap();
+
+        // Each metric fills a buffer so all but the last are sent together
+        assert_eq!(24, sink.emit_batch(&["foo:54|c", "foo:67|c", "foo:89|c"]).unwrap());
+        assert_eq!(2, sink.stats().packets_sent);
+        sink.flush().unwrap();
+
+        let mut buf = [0; 64];
+        for expected in [b"foo:54|c\n", b"foo:67|c\n", b"foo:89|c\n"] {
+            let len = server.recv(&mut buf).unwrap();
+            assert_eq!(expected, &buf[..len]);
+        }
+    }
+
+    #[cfg(all(feature = "sendmmsg", target_os = "linux"))]
+    #[test]
+    fn test_buffered_udp_metric_sink_send_some() {
+        let server = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSink::with_capacity(server.local_addr().unwrap(), socket, 64).unwrap();
+        let packets = vec!["foo:54|c".to_owned(), "foo:67|c".to_owned(), "foo:89|c".to_owned()];
+
+        let (sent, res) = sink.send_some(&packets);
+        assert_eq!(3, sent);
+        assert!(res.is_ok());
+        assert_eq!(3, sink.stats().packets_sent);
+        assert_eq!(24, sink.stats().bytes_sent);
+
+        let mut buf = [0; 64];
+        for expected in &packets {
+            let len = server.recv(&mut buf).unwrap();
+            assert_eq!(expected.as_bytes(), &buf[..len]);
+        }
+    }
 }
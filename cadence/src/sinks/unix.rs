@@ -14,9 +14,12 @@ use std::io::Write;
 use std::os::unix::net::UnixDatagram;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
+use std::time::{Duration, Instant};
 
 use crate::io::MultiLineWriter;
-use crate::sinks::core::{MetricSink, SinkStats, SocketStats};
+use crate::sinks::backoff::{Backoff, BackoffState};
+use crate::sinks::core::{emit_each, MetricSink, SinkStats, SocketStats};
+use crate::types::WriteMetric;
 
 // Default size of the buffer for buffered metric sinks. This
 // is a rather conservative value, picked for consistency with
@@ -25,6 +28,249 @@ use crate::sinks::core::{MetricSink, SinkStats, SocketStats};
 // application is running on.
 const DEFAULT_BUFFER_SIZE: usize = 512;
 
+/// Socket used to send datagrams to the server at a path
+#[derive(Debug)]
+enum UnixSender {
+    /// Socket provided by the caller that sends each datagram to the path
+    Unconnected { socket: UnixDatagram, path: PathBuf },
+    /// Socket created by the sink and connected to the path, replaced with a
+    /// new connection when the server goes away
+    Connected(Mutex<Reconnecting>),
+}
+
+impl UnixSender {
+    fn send(&self, buf: &[u8]) -> io::Result<usize> {
+        match self {
+            UnixSender::Unconnected { socket, path } => socket.send_to(buf, path),
+            UnixSender::Connected(conn) => conn.lock().unwrap().send(buf, Instant::now()),
+        }
+    }
+
+    // Send an empty datagram to make sure something is bound to the path
+    fn check(&self) -> io::Result<()> {
+        match self {
+            UnixSender::Unconnected { socket, path } => socket.send_to(&[], path).map(|_| ()),
+            UnixSender::Connected(conn) => conn.lock().unwrap().check(Instant::now()),
+        }
+    }
+}
+
+/// Connection to the server that is made when the first datagram is sent. If
+/// the server goes away, for example because it was restarted and the socket
+/// at the path was removed or replaced, the connection is discarded and a new
+/// connection will be made the next time there is something to send, once the
+/// backoff delay has passed.
+#[derive(Debug)]
+struct Reconnecting {
+    path: PathBuf,
+    nonblocking: bool,
+    socket: Option<UnixDatagram>,
+    backoff: BackoffState,
+}
+
+impl Reconnecting {
+    fn connect(&self) -> io::Result<UnixDatagram> {
+        let socket = UnixDatagram::unbound()?;
+        socket.set_nonblocking(self.nonblocking)?;
+        socket.connect(&self.path)?;
+        Ok(socket)
+    }
+
+    fn send(&mut self, buf: &[u8], now: Instant) -> io::Result<usize> {
+        let socket = match self.socket {
+            Some(ref socket) => socket,
+            None => {
+                if !self.backoff.is_ready(now) {
+                    return Err(io::Error::new(
+                        io::ErrorKind::NotConnected,
+                        "waiting to reconnect to server",
+                    ));
+                }
+
+                match self.connect() {
+                    Ok(socket) => &*self.socket.insert(socket),
+                    Err(e) => {
+                        self.backoff.failure(now);
+                        return Err(e);
+                    }
+                }
+            }
+        };
+
+        let res = socket.send(buf);
+        match res {
+            Ok(_) => self.backoff.success(),
+            Err(ref e) if is_disconnected(e) => {
+                self.socket = None;
+                self.backoff.failure(now);
+            }
+            Err(_) => {}
+        }
+
+        res
+    }
+
+    // Connect without waiting for the backoff delay if there isn't a connection
+    fn check(&mut self, now: Instant) -> io::Result<()> {
+        if self.socket.is_none() {
+            self.socket = Some(self.connect()?);
+            self.backoff.success();
+        }
+
+        self.send(&[], now).map(|_| ())
+    }
+}
+
+// Errors that mean the server has gone away, so a new connection needs to be
+// made to the socket at the path. A datagram socket connected to a server that
+// was restarted keeps failing with `ConnectionRefused` even once the server is
+// listening at the same path again.
+fn is_disconnected(e: &io::Error) -> bool {
+    matches!(
+        e.kind(),
+        io::ErrorKind::NotFound
+            | io::ErrorKind::ConnectionRefused
+            | io::ErrorKind::ConnectionReset
+            | io::ErrorKind::NotConnected
+    )
+}
+
+/// Implementation of a builder pattern for Unix datagram socket sinks.
+///
+/// Unlike sinks created from a socket provided by the caller, sinks created
+/// by the builder create their own socket and connect it to the path of the
+/// server. If the server goes away, for example because an agent is restarted,
+/// the connection is discarded and a new connection will be made when the
+/// next metric is emitted, after waiting for an exponentially increasing delay.
+/// Metrics emitted while disconnected are dropped and an error is returned.
+///
+/// # Example
+///
+/// ```no_run
+/// use std::time::Duration;
+/// use cadence::{Backoff, MetricSink, UnixMetricSinkBuilder};
+///
+/// let sink = UnixMetricSinkBuilder::new()
+///     .with_backoff(Backoff::exponential(Duration::from_millis(50), Duration::from_secs(5)))
+///     .build_buffered("/run/statsd.sock");
+///
+/// sink.emit("foo.counter:4|c");
+/// ```
+#[derive(Debug, Clone)]
+pub struct UnixMetricSinkBuilder {
+    backoff: Backoff,
+    nonblocking: bool,
+    capacity: Option<usize>,
+    flush_interval: Option<Duration>,
+}
+
+impl UnixMetricSinkBuilder {
+    /// Construct a new builder.
+    pub fn new() -> Self {
+        UnixMetricSinkBuilder {
+            backoff: Backoff::default(),
+            nonblocking: true,
+            capacity: None,
+            flush_interval: None,
+        }
+    }
+
+    /// Set how long to wait before reconnecting to the server after connecting
+    /// or sending a metric fails because the server has gone away.
+    ///
+    /// By default, the delay starts at 100 milliseconds and doubles after each
+    /// consecutive failure up to 10 seconds.
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.backoff = backoff;
+        self
+    }
+
+    /// Set whether the socket is in non-blocking mode.
+    ///
+    /// By default, the socket is in non-blocking mode so that sending a metric
+    /// never blocks the caller. When the server isn't reading metrics fast
+    /// enough, the metric is dropped and a `WouldBlock` error is returned instead.
+    pub fn with_nonblocking(mut self, nonblocking: bool) -> Self {
+        self.nonblocking = nonblocking;
+        self
+    }
+
+    /// Set the size of the buffer used by `BufferedUnixMetricSink` instances.
+    ///
+    /// The default size of the buffer is 512 bytes. This has no effect on
+    /// `UnixMetricSink` instances since they are not buffered.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = Some(capacity);
+        self
+    }
+
+    /// Set how long metrics may sit in the buffer of `BufferedUnixMetricSink`
+    /// instances before being sent, even if the buffer isn't full.
+    ///
+    /// See `BufferedUnixMetricSink::with_flush_interval` for more information.
+    /// This has no effect on `UnixMetricSink` instances since they are not buffered.
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.flush_interval = Some(interval);
+        self
+    }
+
+    /// Construct a new `UnixMetricSink` instance that will emit metrics to the
+    /// socket at the given path based on the builder configuration.
+    ///
+    /// Note that a connection to the socket is not made until the first metric
+    /// is emitted.
+    pub fn build<P>(self, path: P) -> UnixMetricSink
+    where
+        P: AsRef<Path>,
+    {
+        UnixMetricSink {
+            sender: self.sender(path),
+            stats: SocketStats::default(),
+        }
+    }
+
+    /// Construct a new `BufferedUnixMetricSink` instance that will emit metrics
+    /// to the socket at the given path based on the builder configuration.
+    ///
+    /// Note that a connection to the socket is not made until the buffer is
+    /// first flushed.
+    pub fn build_buffered<P>(self, path: P) -> BufferedUnixMetricSink
+    where
+        P: AsRef<Path>,
+    {
+        let stats = SocketStats::default();
+        let adapter = UnixWriteAdapter {
+            sender: self.sender(path),
+            stats: stats.clone(),
+        };
+
+        let mut buffer = MultiLineWriter::new(adapter, self.capacity.unwrap_or(DEFAULT_BUFFER_SIZE));
+        buffer.set_flush_interval(self.flush_interval);
+        BufferedUnixMetricSink {
+            buffer: Mutex::new(buffer),
+            stats,
+        }
+    }
+
+    fn sender<P>(&self, path: P) -> UnixSender
+    where
+        P: AsRef<Path>,
+    {
+        UnixSender::Connected(Mutex::new(Reconnecting {
+            path: path.as_ref().to_path_buf(),
+            nonblocking: self.nonblocking,
+            socket: None,
+            backoff: BackoffState::new(self.backoff),
+        }))
+    }
+}
+
+impl Default for UnixMetricSinkBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Implementation of a `MetricSink` that emits metrics over a Unix socket.
 ///
 /// This is the most basic version of `MetricSink` that sends metrics over
@@ -37,10 +283,13 @@ const DEFAULT_BUFFER_SIZE: usize = 512;
 /// Note that unlike the UDP sinks, if there is no receiving socket at the path
 /// specified or nothing listening at the path, an error will be returned when
 /// metrics are emitted.
+///
+/// Sinks created using `UnixMetricSinkBuilder` connect their own socket to the
+/// path and reconnect to it if the server goes away, see the builder for more
+/// information.
 #[derive(Debug)]
 pub struct UnixMetricSink {
-    socket: UnixDatagram,
-    path: PathBuf,
+    sender: UnixSender,
     stats: SocketStats,
 }
 
@@ -78,21 +327,28 @@ impl UnixMetricSink {
     where
         P: AsRef<Path>,
     {
-        let stats = SocketStats::default();
         UnixMetricSink {
-            path: path.as_ref().to_path_buf(),
-            socket,
-            stats,
+            sender: UnixSender::Unconnected {
+                socket,
+                path: path.as_ref().to_path_buf(),
+            },
+            stats: SocketStats::default(),
         }
     }
+
+    /// Construct a new builder for `UnixMetricSink` or `BufferedUnixMetricSink`.
+    pub fn builder() -> UnixMetricSinkBuilder {
+        UnixMetricSinkBuilder::new()
+    }
 }
 
 impl MetricSink for UnixMetricSink {
     fn emit(&self, metric: &str) -> io::Result<usize> {
-        self.stats.update(
-            self.socket.send_to(metric.as_bytes(), self.path.as_path()),
-            metric.len(),
-        )
+        self.stats.update(self.sender.send(metric.as_bytes()), metric.len())
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.sender.check()
     }
 
     fn stats(&self) -> SinkStats {
@@ -103,8 +359,7 @@ impl MetricSink for UnixMetricSink {
 /// Adapter for writing to a `UnixDatagram` socket via the `Write` trait
 #[derive(Debug)]
 pub(crate) struct UnixWriteAdapter {
-    path: PathBuf,
-    socket: UnixDatagram,
+    sender: UnixSender,
     stats: SocketStats,
 }
 
@@ -114,16 +369,22 @@ impl UnixWriteAdapter {
         P: AsRef<Path>,
     {
         UnixWriteAdapter {
-            path: path.as_ref().to_path_buf(),
-            socket,
+            sender: UnixSender::Unconnected {
+                socket,
+                path: path.as_ref().to_path_buf(),
+            },
             stats,
         }
     }
+
+    fn check(&self) -> io::Result<()> {
+        self.sender.check()
+    }
 }
 
 impl Write for UnixWriteAdapter {
     fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
-        self.stats.update(self.socket.send_to(buf, &self.path), buf.len())
+        self.stats.update(self.sender.send(buf), buf.len())
     }
 
     fn flush(&mut self) -> io::Result<()> {
@@ -151,7 +412,8 @@ impl Write for UnixWriteAdapter {
 /// possible that they may sit in the buffer for a while for applications
 /// that do not emit metrics frequently or at a high volume. For these low-
 /// throughput use cases, it may make more sense to use the `UnixMetricSink`
-/// since it sends metrics immediately with no buffering.
+/// since it sends metrics immediately with no buffering, or to limit how long
+/// metrics are buffered using `.with_flush_interval()`.
 ///
 /// Also note that unlike the UDP sinks, if there is no receiving socket at the path
 /// specified or nothing listening at the path, an error will be returned when
@@ -224,6 +486,32 @@ impl BufferedUnixMetricSink {
             stats,
         }
     }
+
+    /// Set how long metrics may sit in the buffer before being sent, even if
+    /// the buffer isn't full.
+    ///
+    /// The buffer is flushed when a metric is emitted and the oldest metric in
+    /// the buffer was emitted longer ago than the interval. A background thread
+    /// is not used, so metrics are not sent if nothing else is emitted. Wrap
+    /// this sink with a `QueuingMetricSink` and call `.flush()` periodically if
+    /// metrics must be sent even when nothing else is emitted. By default, the
+    /// buffer is only flushed when it is full.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use std::os::unix::net::UnixDatagram;
+    /// use std::time::Duration;
+    /// use cadence::BufferedUnixMetricSink;
+    ///
+    /// let socket = UnixDatagram::unbound().unwrap();
+    /// let sink = BufferedUnixMetricSink::from("/run/statsd.sock", socket)
+    ///     .with_flush_interval(Duration::from_secs(1));
+    /// ```
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.buffer.get_mut().unwrap().set_flush_interval(Some(interval));
+        self
+    }
 }
 
 impl MetricSink for BufferedUnixMetricSink {
@@ -232,11 +520,25 @@ impl MetricSink for BufferedUnixMetricSink {
         writer.write(metric.as_bytes())
     }
 
+    fn emit_metric(&self, metric: &dyn WriteMetric) -> io::Result<usize> {
+        let mut writer = self.buffer.lock().unwrap();
+        writer.write_metric(metric)
+    }
+
+    fn emit_batch(&self, metrics: &[&str]) -> io::Result<usize> {
+        let mut writer = self.buffer.lock().unwrap();
+        emit_each(metrics, |m| writer.write(m.as_bytes()))
+    }
+
     fn flush(&self) -> io::Result<()> {
         let mut writer = self.buffer.lock().unwrap();
         writer.flush()
     }
 
+    fn check(&self) -> io::Result<()> {
+        self.buffer.lock().unwrap().get_mut().check()
+    }
+
     fn stats(&self) -> SinkStats {
         (&self.stats).into()
     }
@@ -244,8 +546,11 @@ impl MetricSink for BufferedUnixMetricSink {
 
 #[cfg(test)]
 mod tests {
-    use super::{BufferedUnixMetricSink, MetricSink, UnixMetricSink};
-    use crate::test::UnixServerHarness;
+    use super::{BufferedUnixMetricSink, MetricSink, UnixMetricSink, UnixMetricSinkBuilder};
+    use crate::sinks::backoff::Backoff;
+    use crate::test::{TempDir, UnixServerHarness};
+    use std::fs;
+    use std::io;
     use std::os::unix::net::UnixDatagram;
 
     #[test]
@@ -304,4 +609,45 @@ mod tests {
             assert!(sink.flush().is_ok());
         });
     }
+
+    #[test]
+    fn test_unix_metric_sink_builder() {
+        let harness = UnixServerHarness::new("test_unix_metric_sink_builder");
+
+        harness.run_quiet(|path| {
+            let sink = UnixMetricSinkBuilder::new().build(path);
+            assert!(sink.check().is_ok());
+            assert_eq!(7, sink.emit("buz:1|m").unwrap());
+
+            let buffered = UnixMetricSinkBuilder::new().with_capacity(16).build_buffered(path);
+            assert_eq!(8, buffered.emit("foo:54|c").unwrap());
+            assert!(buffered.flush().is_ok());
+        });
+    }
+
+    #[test]
+    fn test_unix_metric_sink_builder_reconnect() {
+        let temp = TempDir::new("test_unix_metric_sink_builder_reconnect").unwrap();
+        let path = temp.new_path("cadence.sock");
+        let sink = UnixMetricSinkBuilder::new().with_backoff(Backoff::none()).build(&path);
+
+        let server = UnixDatagram::bind(&path).unwrap();
+        assert_eq!(7, sink.emit("foo:1|c").unwrap());
+
+        // Server goes away, sending fails instead of connecting to nothing
+        drop(server);
+        fs::remove_file(&path).unwrap();
+        assert!(sink.emit("foo:2|c").is_err());
+        assert!(sink.check().is_err());
+
+        // Server comes back at the same path, the sink connects to it again
+        let server = UnixDatagram::bind(&path).unwrap();
+        server.set_nonblocking(true).unwrap();
+        assert_eq!(7, sink.emit("foo:3|c").unwrap());
+
+        let mut buf = [0; 16];
+        let n = server.recv(&mut buf).unwrap();
+        assert_eq!(b"foo:3|c", &buf[..n]);
+        assert_eq!(io::ErrorKind::WouldBlock, server.recv(&mut buf).unwrap_err().kind());
+    }
 }
@@ -0,0 +1,370 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io;
+use std::io::Write;
+use std::os::unix::net::UnixStream;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::io::MultiLineWriter;
+use crate::sinks::backoff::Backoff;
+use crate::sinks::core::{emit_each, MetricSink, SinkStats, SocketStats};
+use crate::sinks::stream::{Connector, DisconnectPolicy, StreamWriter};
+use crate::sinks::tcp::TcpMetricSinkBuilder;
+use crate::types::WriteMetric;
+
+/// Connector for establishing stream connections to a Unix socket
+#[derive(Debug)]
+pub(crate) struct UnixStreamConnector {
+    path: PathBuf,
+    write_timeout: Option<Duration>,
+}
+
+impl Connector for UnixStreamConnector {
+    type Stream = UnixStream;
+
+    fn connect(&self) -> io::Result<UnixStream> {
+        let stream = UnixStream::connect(&self.path)?;
+        stream.set_write_timeout(self.write_timeout)?;
+        Ok(stream)
+    }
+}
+
+/// Implementation of a builder pattern for Unix stream socket sinks.
+///
+/// The builder can be used to set how long to wait before reconnecting to
+/// the server after a failure, what to do with metrics while disconnected
+/// from the server, and the size of the buffer used by the buffered sink.
+///
+/// # Example
+///
+/// ```no_run
+/// use cadence::{DisconnectPolicy, MetricSink, UnixStreamMetricSinkBuilder};
+///
+/// let sink = UnixStreamMetricSinkBuilder::new()
+///     .with_disconnect_policy(DisconnectPolicy::Buffer(64 * 1024))
+///     .build_buffered("/run/statsd.sock");
+///
+/// sink.emit("foo.counter:4|c");
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct UnixStreamMetricSinkBuilder {
+    stream: TcpMetricSinkBuilder,
+}
+
+impl UnixStreamMetricSinkBuilder {
+    /// Construct a new builder.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set how long to wait before reconnecting to the server after a connection
+    /// attempt or write fails.
+    ///
+    /// While waiting to reconnect, metrics are not written to the server and are
+    /// handled according to the `DisconnectPolicy` of the sink. By default, the
+    /// delay starts at 100 milliseconds and doubles after each consecutive failure
+    /// up to 10 seconds.
+    pub fn with_backoff(mut self, backoff: Backoff) -> Self {
+        self.stream = self.stream.with_backoff(backoff);
+        self
+    }
+
+    /// Set what to do with metrics that can't be written to the server because
+    /// the connection has failed or the sink is waiting to reconnect.
+    ///
+    /// By default, metrics are dropped and an error is returned.
+    pub fn with_disconnect_policy(mut self, policy: DisconnectPolicy) -> Self {
+        self.stream = self.stream.with_disconnect_policy(policy);
+        self
+    }
+
+    /// Set how long writing metrics to the server may block before failing.
+    ///
+    /// This keeps a server that has stopped reading metrics, but hasn't closed
+    /// the connection, from blocking the thread emitting metrics indefinitely.
+    /// A write that times out fails with a `WouldBlock` error and the
+    /// connection is discarded. By default, there is no timeout.
+    pub fn with_write_timeout(mut self, timeout: Duration) -> Self {
+        self.stream = self.stream.with_write_timeout(timeout);
+        self
+    }
+
+    /// Set the size of the buffer used by `BufferedUnixStreamMetricSink` instances.
+    ///
+    /// The default size of the buffer is 8192 bytes. This has no effect on
+    /// `UnixStreamMetricSink` instances since they are not buffered.
+    pub fn with_capacity(mut self, capacity: usize) -> Self {
+        self.stream = self.stream.with_capacity(capacity);
+        self
+    }
+
+    /// Set how long metrics may sit in the buffer of `BufferedUnixStreamMetricSink` instances
+    /// before being sent, even if the buffer isn't full.
+    ///
+    /// See `TcpMetricSinkBuilder::with_flush_interval` for more information.
+    /// This has no effect on `UnixStreamMetricSink` instances since they are not buffered.
+    pub fn with_flush_interval(mut self, interval: Duration) -> Self {
+        self.stream = self.stream.with_flush_interval(interval);
+        self
+    }
+
+    /// Construct a new `UnixStreamMetricSink` instance that will emit metrics
+    /// to the socket at the given path based on the builder configuration.
+    pub fn build<P>(self, path: P) -> UnixStreamMetricSink
+    where
+        P: AsRef<Path>,
+    {
+        let stats = SocketStats::default();
+        let writer = self.writer(path, stats.clone());
+        UnixStreamMetricSink {
+            writer: Mutex::new(writer),
+            stats,
+        }
+    }
+
+    /// Construct a new `BufferedUnixStreamMetricSink` instance that will emit
+    /// metrics to the socket at the given path based on the builder configuration.
+    pub fn build_buffered<P>(self, path: P) -> BufferedUnixStreamMetricSink
+    where
+        P: AsRef<Path>,
+    {
+        let stats = SocketStats::default();
+        let writer = self.writer(path, stats.clone());
+        BufferedUnixStreamMetricSink {
+            buffer: Mutex::new(self.stream.buffered(writer)),
+            stats,
+        }
+    }
+
+    fn writer<P>(&self, path: P, stats: SocketStats) -> StreamWriter<UnixStreamConnector>
+    where
+        P: AsRef<Path>,
+    {
+        let connector = UnixStreamConnector {
+            path: path.as_ref().to_path_buf(),
+            write_timeout: self.stream.write_timeout(),
+        };
+
+        self.stream.stream_writer(connector, stats)
+    }
+}
+
+/// Implementation of a `MetricSink` that emits metrics over a Unix stream socket.
+///
+/// Each metric is written to a stream connection to the Unix socket followed by
+/// a newline when the `.emit()` method is called, in the thread of the caller.
+/// This can be used with servers and agents that only listen on stream sockets
+/// (`SOCK_STREAM`) instead of datagram sockets. For datagram sockets, use the
+/// `UnixMetricSink` instead.
+///
+/// The connection to the socket is made when the first metric is emitted. If
+/// writing a metric fails, for example because the server was restarted, the
+/// connection is discarded and a new connection will be made when the next
+/// metric is emitted, after waiting for an exponentially increasing delay. What
+/// happens to metrics in the meantime is determined by the `DisconnectPolicy`
+/// which can be set using `UnixStreamMetricSinkBuilder`. By default they are
+/// dropped and an error is returned.
+#[derive(Debug)]
+pub struct UnixStreamMetricSink {
+    writer: Mutex<StreamWriter<UnixStreamConnector>>,
+    stats: SocketStats,
+}
+
+impl UnixStreamMetricSink {
+    /// Construct a new builder for `UnixStreamMetricSink` or `BufferedUnixStreamMetricSink`.
+    pub fn builder() -> UnixStreamMetricSinkBuilder {
+        UnixStreamMetricSinkBuilder::new()
+    }
+
+    /// Construct a new `UnixStreamMetricSink` instance.
+    ///
+    /// The path should be the path of the socket the server is listening on.
+    /// Note that a connection to the socket is not made until the first metric
+    /// is emitted.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cadence::UnixStreamMetricSink;
+    ///
+    /// let sink = UnixStreamMetricSink::from("/run/statsd.sock");
+    /// ```
+    pub fn from<P>(path: P) -> UnixStreamMetricSink
+    where
+        P: AsRef<Path>,
+    {
+        Self::builder().build(path)
+    }
+}
+
+impl MetricSink for UnixStreamMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let mut writer = self.writer.lock().unwrap();
+        writer.write_line(metric.as_bytes())
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.writer.lock().unwrap().check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        (&self.stats).into()
+    }
+}
+
+/// Implementation of a `MetricSink` that buffers metrics before
+/// sending them over a Unix stream socket.
+///
+/// Metrics are line buffered, meaning that a trailing "\n" is added
+/// after each metric written to this sink. When the buffer is sufficiently
+/// full and a write is attempted, the contents of the buffer are written to
+/// the socket and then the metric is written to the buffer. The buffer is
+/// also flushed when this sink is destroyed.
+///
+/// The default size of the buffer is 8192 bytes. The buffer size can be
+/// customized using the `with_capacity` method to create the sink if desired.
+///
+/// Reconnecting to the socket and handling of metrics while disconnected work
+/// the same way as the `UnixStreamMetricSink` and can be configured using
+/// `UnixStreamMetricSinkBuilder`.
+#[derive(Debug)]
+pub struct BufferedUnixStreamMetricSink {
+    buffer: Mutex<MultiLineWriter<StreamWriter<UnixStreamConnector>>>,
+    stats: SocketStats,
+}
+
+impl BufferedUnixStreamMetricSink {
+    /// Construct a new `BufferedUnixStreamMetricSink` instance with a default
+    /// buffer size of 8192 bytes.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cadence::BufferedUnixStreamMetricSink;
+    ///
+    /// let sink = BufferedUnixStreamMetricSink::from("/run/statsd.sock");
+    /// ```
+    pub fn from<P>(path: P) -> BufferedUnixStreamMetricSink
+    where
+        P: AsRef<Path>,
+    {
+        UnixStreamMetricSink::builder().build_buffered(path)
+    }
+
+    /// Construct a new `BufferedUnixStreamMetricSink` instance with a custom
+    /// buffer size.
+    ///
+    /// # Example
+    ///
+    /// ```no_run
+    /// use cadence::BufferedUnixStreamMetricSink;
+    ///
+    /// let sink = BufferedUnixStreamMetricSink::with_capacity("/run/statsd.sock", 65536);
+    /// ```
+    pub fn with_capacity<P>(path: P, cap: usize) -> BufferedUnixStreamMetricSink
+    where
+        P: AsRef<Path>,
+    {
+        UnixStreamMetricSink::builder().with_capacity(cap).build_buffered(path)
+    }
+}
+
+impl MetricSink for BufferedUnixStreamMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        let mut writer = self.buffer.lock().unwrap();
+        writer.write(metric.as_bytes())
+    }
+
+    fn emit_metric(&self, metric: &dyn WriteMetric) -> io::Result<usize> {
+        let mut writer = self.buffer.lock().unwrap();
+        writer.write_metric(metric)
+    }
+
+    fn emit_batch(&self, metrics: &[&str]) -> io::Result<usize> {
+        let mut writer = self.buffer.lock().unwrap();
+        emit_each(metrics, |m| writer.write(m.as_bytes()))
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        let mut writer = self.buffer.lock().unwrap();
+        writer.flush()
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.buffer.lock().unwrap().get_mut().check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        (&self.stats).into()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{BufferedUnixStreamMetricSink, MetricSink, UnixStreamMetricSink};
+    use crate::test::TempDir;
+    use std::io::{BufRead, BufReader};
+    use std::os::unix::net::UnixListener;
+    use std::thread;
+
+    // Accept a single connection and return every line read from it
+    fn serve_lines(listener: UnixListener) -> thread::JoinHandle<Vec<String>> {
+        thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            BufReader::new(stream).lines().map(|l| l.unwrap()).collect()
+        })
+    }
+
+    #[test]
+    fn test_unix_stream_metric_sink() {
+        let dir = TempDir::new("test_unix_stream_metric_sink").unwrap();
+        let path = dir.new_path("cadence.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        let sink = UnixStreamMetricSink::from(&path);
+        let server = serve_lines(listener);
+
+        assert_eq!(7, sink.emit("buz:1|m").unwrap());
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        drop(sink);
+
+        assert_eq!(vec!["buz:1|m", "foo:54|c"], server.join().unwrap());
+    }
+
+    #[test]
+    fn test_unix_stream_metric_sink_no_server() {
+        let dir = TempDir::new("test_unix_stream_metric_sink_no_server").unwrap();
+        let sink = UnixStreamMetricSink::from(dir.new_path("cadence.sock"));
+
+        assert!(sink.emit("buz:1|m").is_err());
+        assert_eq!(1, sink.stats().packets_dropped);
+    }
+
+    #[test]
+    fn test_buffered_unix_stream_metric_sink() {
+        let dir = TempDir::new("test_buffered_unix_stream_metric_sink").unwrap();
+        let path = dir.new_path("cadence.sock");
+        let listener = UnixListener::bind(&path).unwrap();
+        // Set the capacity of the buffer such that it won't be flushed
+        // from a single write. Thus we can test the flush method.
+        let sink = BufferedUnixStreamMetricSink::with_capacity(&path, 64);
+        let server = serve_lines(listener);
+
+        assert_eq!(8, sink.emit("foo:54|c").unwrap());
+        assert_eq!(8, sink.emit("foo:67|c").unwrap());
+        assert!(sink.flush().is_ok());
+        assert_eq!(18, sink.stats().bytes_sent);
+        drop(sink);
+
+        assert_eq!(vec!["foo:54|c", "foo:67|c"], server.join().unwrap());
+    }
+}
@@ -0,0 +1,295 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::net::{SocketAddr, UdpSocket};
+use std::panic::RefUnwindSafe;
+use std::path::PathBuf;
+
+use crate::sinks::core::{MetricSink, NopMetricSink};
+use crate::sinks::tcp::TcpMetricSinkBuilder;
+use crate::sinks::udp::{get_addr, BufferedUdpMetricSink, UdpMetricSink};
+use crate::types::{ErrorKind, MetricError, MetricResult};
+use crate::DEFAULT_PORT;
+
+/// Where a sink built from a URL sends metrics
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Transport {
+    Udp(String, u16),
+    Tcp(String, u16),
+    Unix(PathBuf),
+    Nop,
+}
+
+/// Builder for creating a `MetricSink` from a URL, allowing the way metrics
+/// are sent to be chosen by configuration instead of code.
+///
+/// The following URLs are supported:
+///
+/// * `statsd://host:port`: Send metrics over UDP using `UdpMetricSink`.
+/// * `statsd+tcp://host:port`: Send metrics over TCP using `TcpMetricSink`.
+/// * `unixgram:///path/to/socket`: Send metrics to a Unix datagram socket using
+///   `UnixMetricSink`. Only available on Unix platforms.
+/// * `nop://`: Discard all metrics using `NopMetricSink`.
+///
+/// If the port is left out, the default Statsd port (8125) is used. IPv6
+/// addresses must be enclosed in brackets, e.g. `statsd://[::1]:8125`.
+///
+/// The query parameter `buffered=true` can be added to any URL other than
+/// `nop://` to use the buffered version of the sink, e.g. `BufferedUdpMetricSink`
+/// instead of `UdpMetricSink`. Other parameters are not supported.
+///
+/// # Example
+///
+/// ```no_run
+/// use cadence::{MetricSinkBuilder, StatsdClient};
+///
+/// let url = std::env::var("STATSD_URL").unwrap_or_else(|_| "statsd://localhost:8125".to_string());
+/// let sink = MetricSinkBuilder::from_url(&url).unwrap().build().unwrap();
+/// let client = StatsdClient::from_sink("my.prefix", sink);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MetricSinkBuilder {
+    transport: Transport,
+    buffered: bool,
+}
+
+impl MetricSinkBuilder {
+    /// Construct a new builder from the given URL.
+    ///
+    /// # Failures
+    ///
+    /// This method will fail if the URL uses an unsupported scheme, has a
+    /// missing host or invalid port, or includes unsupported parameters.
+    pub fn from_url(url: &str) -> MetricResult<MetricSinkBuilder> {
+        let (scheme, rest) = url
+            .split_once("://")
+            .ok_or_else(|| invalid("URL is missing a scheme"))?;
+
+        let (location, query) = match rest.split_once('?') {
+            Some((location, query)) => (location, Some(query)),
+            None => (rest, None),
+        };
+
+        let transport = match scheme {
+            "statsd" | "statsd+udp" => {
+                let (host, port) = parse_host_port(location)?;
+                Transport::Udp(host, port)
+            }
+            "statsd+tcp" => {
+                let (host, port) = parse_host_port(location)?;
+                Transport::Tcp(host, port)
+            }
+            "unixgram" if cfg!(unix) => {
+                if location.is_empty() {
+                    return Err(invalid("URL is missing a socket path"));
+                }
+                Transport::Unix(PathBuf::from(location))
+            }
+            "unixgram" => return Err(invalid("unixgram URLs are only supported on Unix platforms")),
+            "nop" => Transport::Nop,
+            _ => return Err(invalid("URL has an unsupported scheme")),
+        };
+
+        let mut buffered = false;
+        for param in query.into_iter().flat_map(|q| q.split('&')).filter(|p| !p.is_empty()) {
+            match param.split_once('=') {
+                Some(("buffered", "true")) => buffered = true,
+                Some(("buffered", "false")) => buffered = false,
+                Some(("buffered", _)) => return Err(invalid("URL parameter 'buffered' must be true or false")),
+                _ => return Err(invalid("URL has an unsupported parameter")),
+            }
+        }
+
+        Ok(MetricSinkBuilder { transport, buffered })
+    }
+
+    /// Set whether to use the buffered version of the sink, overriding the
+    /// `buffered` parameter of the URL.
+    pub fn with_buffering(mut self, buffered: bool) -> Self {
+        self.buffered = buffered;
+        self
+    }
+
+    /// Construct a new `MetricSink` based on the builder configuration.
+    ///
+    /// # Failures
+    ///
+    /// This method may fail if:
+    ///
+    /// * It is unable to resolve the hostname of the metric server.
+    /// * It is unable to create a socket to send metrics with.
+    pub fn build(self) -> MetricResult<Box<dyn MetricSink + Sync + Send + RefUnwindSafe>> {
+        Ok(match self.transport {
+            Transport::Udp(host, port) => {
+                let addr = get_addr((host.as_str(), port))?;
+                let socket = UdpSocket::bind(unspecified_addr(&addr))?;
+                socket.set_nonblocking(true)?;
+
+                if self.buffered {
+                    Box::new(BufferedUdpMetricSink::from(addr, socket)?)
+                } else {
+                    Box::new(UdpMetricSink::from(addr, socket)?)
+                }
+            }
+            Transport::Tcp(host, port) => {
+                let builder = TcpMetricSinkBuilder::new();
+                if self.buffered {
+                    Box::new(builder.build_buffered((host.as_str(), port))?)
+                } else {
+                    Box::new(builder.build((host.as_str(), port))?)
+                }
+            }
+            #[cfg(unix)]
+            Transport::Unix(path) => {
+                use crate::sinks::unix::{BufferedUnixMetricSink, UnixMetricSink};
+                use std::os::unix::net::UnixDatagram;
+
+                let socket = UnixDatagram::unbound()?;
+                if self.buffered {
+                    Box::new(BufferedUnixMetricSink::from(path, socket))
+                } else {
+                    Box::new(UnixMetricSink::from(path, socket))
+                }
+            }
+            #[cfg(not(unix))]
+            Transport::Unix(_) => return Err(invalid("unixgram URLs are only supported on Unix platforms")),
+            Transport::Nop => Box::new(NopMetricSink),
+        })
+    }
+}
+
+fn invalid(desc: &'static str) -> MetricError {
+    MetricError::from((ErrorKind::InvalidInput, desc))
+}
+
+/// Address to bind a local socket to for sending to the given address
+fn unspecified_addr(addr: &SocketAddr) -> SocketAddr {
+    match addr {
+        SocketAddr::V4(_) => SocketAddr::from(([0, 0, 0, 0], 0)),
+        SocketAddr::V6(_) => SocketAddr::from(([0u16; 8], 0)),
+    }
+}
+
+/// Parse a `host:port`, `host`, `[ipv6]:port`, or `[ipv6]` location
+fn parse_host_port(location: &str) -> MetricResult<(String, u16)> {
+    let location = location.trim_end_matches('/');
+    let (host, port) = if let Some(bracketed) = location.strip_prefix('[') {
+        let (host, rest) = bracketed
+            .split_once(']')
+            .ok_or_else(|| invalid("URL has an unterminated IPv6 address"))?;
+        match rest {
+            "" => (host, None),
+            _ => match rest.strip_prefix(':') {
+                Some(port) => (host, Some(port)),
+                None => return Err(invalid("URL has an invalid port")),
+            },
+        }
+    } else {
+        match location.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (location, None),
+        }
+    };
+
+    if host.is_empty() {
+        return Err(invalid("URL is missing a host"));
+    }
+
+    let port = match port {
+        Some(p) => p.parse::<u16>().map_err(|_| invalid("URL has an invalid port"))?,
+        None => DEFAULT_PORT,
+    };
+
+    Ok((host.to_string(), port))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MetricSinkBuilder, Transport};
+    use crate::sinks::core::MetricSink;
+    use crate::types::ErrorKind;
+    use std::path::PathBuf;
+
+    fn transport(url: &str) -> Transport {
+        MetricSinkBuilder::from_url(url).unwrap().transport
+    }
+
+    #[test]
+    fn test_metric_sink_builder_from_url() {
+        assert_eq!(
+            Transport::Udp("localhost".to_string(), 8125),
+            transport("statsd://localhost")
+        );
+        assert_eq!(
+            Transport::Udp("10.0.0.1".to_string(), 9125),
+            transport("statsd://10.0.0.1:9125/")
+        );
+        assert_eq!(
+            Transport::Udp("::1".to_string(), 8126),
+            transport("statsd+udp://[::1]:8126")
+        );
+        assert_eq!(Transport::Tcp("::1".to_string(), 8125), transport("statsd+tcp://[::1]"));
+        assert_eq!(
+            Transport::Unix(PathBuf::from("/tmp/statsd.sock")),
+            transport("unixgram:///tmp/statsd.sock")
+        );
+        assert_eq!(Transport::Nop, transport("nop://"));
+    }
+
+    #[test]
+    fn test_metric_sink_builder_from_url_buffered() {
+        assert!(
+            MetricSinkBuilder::from_url("statsd://localhost?buffered=true")
+                .unwrap()
+                .buffered
+        );
+        assert!(
+            !MetricSinkBuilder::from_url("statsd://localhost?buffered=false")
+                .unwrap()
+                .buffered
+        );
+        assert!(!MetricSinkBuilder::from_url("statsd://localhost").unwrap().buffered);
+    }
+
+    #[test]
+    fn test_metric_sink_builder_from_url_invalid() {
+        for url in [
+            "localhost:8125",
+            "http://localhost:8125",
+            "statsd://",
+            "statsd://:8125",
+            "statsd://localhost:port",
+            "statsd://localhost:70000",
+            "statsd://[::1",
+            "statsd://[::1]8125",
+            "statsd://localhost?buffered=yes",
+            "statsd://localhost?queued=true",
+            "unixgram://",
+        ] {
+            let err = MetricSinkBuilder::from_url(url).unwrap_err();
+            assert_eq!(ErrorKind::InvalidInput, err.kind(), "{}", url);
+        }
+    }
+
+    #[test]
+    fn test_metric_sink_builder_build_udp() {
+        let sink = MetricSinkBuilder::from_url("statsd://127.0.0.1:8125?buffered=true")
+            .unwrap()
+            .build()
+            .unwrap();
+        assert!(sink.emit("foo:1|c").is_ok());
+    }
+
+    #[test]
+    fn test_metric_sink_builder_build_nop() {
+        let sink = MetricSinkBuilder::from_url("nop://").unwrap().build().unwrap();
+        assert_eq!(0, sink.emit("foo:1|c").unwrap());
+    }
+}
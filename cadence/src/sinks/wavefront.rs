@@ -0,0 +1,354 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt::{self, Write};
+use std::io;
+use std::panic::RefUnwindSafe;
+
+use crate::builder::{MetricType, MetricValue};
+use crate::parse::ParsedMetric;
+use crate::sinks::core::{MetricSink, SinkStats};
+
+// Prefix of metric names that marks them as delta counters
+const DELTA_PREFIX: char = '\u{2206}';
+
+/// Implementation of a builder pattern for `WavefrontMetricSink`.
+///
+/// The builder requires the source that metrics are reported from, usually the
+/// name of the host. It can also be used to take the source from a tag of each
+/// metric instead and to write counters as regular metrics instead of delta
+/// counters.
+///
+/// # Example
+///
+/// ```no_run
+/// use cadence::{MetricSink, TcpMetricSink, WavefrontMetricSinkBuilder};
+///
+/// let tcp = TcpMetricSink::from(("localhost", 2878)).unwrap();
+/// let sink = WavefrontMetricSinkBuilder::new("web01")
+///     .with_source_tag("host")
+///     .build(tcp);
+///
+/// // Sent as "\"requests\" 5 source=\"web02\" \"method\"=\"GET\""
+/// sink.emit("requests:5|g|#host:web02,method:GET");
+/// ```
+#[derive(Debug, Clone)]
+pub struct WavefrontMetricSinkBuilder {
+    source: String,
+    source_tag: Option<String>,
+    delta_counters: bool,
+}
+
+impl WavefrontMetricSinkBuilder {
+    /// Construct a new builder that writes metrics with the given source.
+    pub fn new<T>(source: T) -> Self
+    where
+        T: Into<String>,
+    {
+        WavefrontMetricSinkBuilder {
+            source: source.into(),
+            source_tag: None,
+            delta_counters: true,
+        }
+    }
+
+    /// Use the value of the tag with the given key as the source of metrics
+    /// that have it, instead of writing it as a point tag. Metrics without the
+    /// tag use the source the builder was created with.
+    pub fn with_source_tag<T>(mut self, key: T) -> Self
+    where
+        T: Into<String>,
+    {
+        self.source_tag = Some(key.into());
+        self
+    }
+
+    /// Write counters as regular metrics instead of delta counters.
+    ///
+    /// Delta counters are summed by Wavefront, which is usually what's
+    /// expected of Statsd counters. Regular metrics only keep the last value
+    /// written for each timestamp.
+    pub fn without_delta_counters(mut self) -> Self {
+        self.delta_counters = false;
+        self
+    }
+
+    /// Construct a new `WavefrontMetricSink` instance wrapping the given sink
+    /// based on the builder configuration.
+    pub fn build<T>(self, sink: T) -> WavefrontMetricSink
+    where
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        WavefrontMetricSink {
+            sink: Box::new(sink),
+            source: self.source,
+            source_tag: self.source_tag,
+            delta_counters: self.delta_counters,
+        }
+    }
+}
+
+/// Implementation of a `MetricSink` that converts metrics to the Wavefront
+/// data format before writing them to a wrapped sink.
+///
+/// This allows metrics to be sent directly to a Wavefront proxy, used by Tanzu
+/// Observability, without translating them from Statsd. Wrap a `TcpMetricSink`
+/// to send metrics to the port a Wavefront proxy listens on for metrics in the
+/// Wavefront data format, 2878 by default.
+///
+/// Each metric is written with its name, value, the configured source, and its
+/// key-value tags as point tags. Counters are written as delta counters, with
+/// their values scaled by their sample rate, unless disabled using the builder.
+/// Packed values are written as a line for each value. The timestamp of a
+/// metric is written if it has one, otherwise Wavefront uses the time the
+/// metric is received.
+///
+/// The type of a metric is not written since Wavefront doesn't have types like
+/// Statsd and value tags and container IDs are not written. Events and service
+/// checks can't be written and result in an `InvalidInput` error.
+///
+/// # Example
+///
+/// ```
+/// use cadence::{MetricSink, SpyMetricSink, WavefrontMetricSink};
+///
+/// let (rx, spy) = SpyMetricSink::new();
+/// let sink = WavefrontMetricSink::from("web01", spy);
+///
+/// sink.emit("my.prefix.latency:23|ms|#method:GET").unwrap();
+/// assert_eq!(
+///     b"\"my.prefix.latency\" 23 source=\"web01\" \"method\"=\"GET\"".to_vec(),
+///     rx.try_recv().unwrap()
+/// );
+/// ```
+pub struct WavefrontMetricSink {
+    sink: Box<dyn MetricSink + Sync + Send + RefUnwindSafe>,
+    source: String,
+    source_tag: Option<String>,
+    delta_counters: bool,
+}
+
+impl WavefrontMetricSink {
+    /// Construct a new `WavefrontMetricSink` that writes metrics with the given
+    /// source to the given sink.
+    pub fn from<S, T>(source: S, sink: T) -> Self
+    where
+        S: Into<String>,
+        T: MetricSink + Sync + Send + RefUnwindSafe + 'static,
+    {
+        WavefrontMetricSinkBuilder::new(source).build(sink)
+    }
+
+    /// Construct a new builder for `WavefrontMetricSink` that writes metrics
+    /// with the given source.
+    pub fn builder<S>(source: S) -> WavefrontMetricSinkBuilder
+    where
+        S: Into<String>,
+    {
+        WavefrontMetricSinkBuilder::new(source)
+    }
+
+    /// Convert a Statsd metric to one or more lines of the Wavefront format.
+    fn convert(&self, metric: &str) -> io::Result<String> {
+        let parsed = ParsedMetric::parse(metric).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        let delta = self.delta_counters && parsed.metric_type() == MetricType::Counter;
+        let rate = if delta {
+            parsed.sample_rate().filter(|r| *r > 0.0).unwrap_or(1.0)
+        } else {
+            1.0
+        };
+
+        let mut out = String::with_capacity(metric.len() * 2 + self.source.len());
+        // Writing to a `String` can't fail
+        let _ = match parsed.value() {
+            MetricValue::Signed(v) if rate == 1.0 => self.write_point(&mut out, &parsed, delta, v),
+            MetricValue::Unsigned(v) if rate == 1.0 => self.write_point(&mut out, &parsed, delta, v),
+            MetricValue::Signed(v) => self.write_point(&mut out, &parsed, delta, *v as f64 / rate),
+            MetricValue::Unsigned(v) => self.write_point(&mut out, &parsed, delta, *v as f64 / rate),
+            MetricValue::Float(v) => self.write_point(&mut out, &parsed, delta, v / rate),
+            MetricValue::PackedSigned(vals) => vals
+                .iter()
+                .try_for_each(|v| self.write_point(&mut out, &parsed, delta, *v as f64 / rate)),
+            MetricValue::PackedUnsigned(vals) => vals
+                .iter()
+                .try_for_each(|v| self.write_point(&mut out, &parsed, delta, *v as f64 / rate)),
+            MetricValue::PackedFloat(vals) => vals
+                .iter()
+                .try_for_each(|v| self.write_point(&mut out, &parsed, delta, v / rate)),
+        };
+
+        Ok(out)
+    }
+
+    // Write a single line for the metric, preceded by a newline if it isn't
+    // the first.
+    fn write_point<V>(&self, out: &mut String, metric: &ParsedMetric<'_>, delta: bool, value: V) -> fmt::Result
+    where
+        V: fmt::Display,
+    {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+
+        out.push('"');
+        if delta {
+            out.push(DELTA_PREFIX);
+        }
+        write_escaped(out, metric.name());
+        write!(out, "\" {}", value)?;
+
+        if let Some(ts) = metric.timestamp() {
+            write!(out, " {}", ts)?;
+        }
+
+        let source = self
+            .source_tag
+            .as_deref()
+            .and_then(|key| metric.tag(key))
+            .unwrap_or(&self.source);
+
+        out.push_str(" source=");
+        write_quoted(out, source);
+
+        for (key, val) in metric.tags() {
+            if let Some(key) = key {
+                if Some(*key) != self.source_tag.as_deref() {
+                    out.push(' ');
+                    write_quoted(out, key);
+                    out.push('=');
+                    write_quoted(out, val);
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl MetricSink for WavefrontMetricSink {
+    fn emit(&self, metric: &str) -> io::Result<usize> {
+        self.sink.emit(&self.convert(metric)?)
+    }
+
+    fn flush(&self) -> io::Result<()> {
+        self.sink.flush()
+    }
+
+    fn check(&self) -> io::Result<()> {
+        self.sink.check()
+    }
+
+    fn stats(&self) -> SinkStats {
+        self.sink.stats()
+    }
+}
+
+impl fmt::Debug for WavefrontMetricSink {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "WavefrontMetricSink {{ source: {:?}, source_tag: {:?}, delta_counters: {} }}",
+            self.source, self.source_tag, self.delta_counters
+        )
+    }
+}
+
+fn write_quoted(out: &mut String, val: &str) {
+    out.push('"');
+    write_escaped(out, val);
+    out.push('"');
+}
+
+fn write_escaped(out: &mut String, val: &str) {
+    for c in val.chars() {
+        if c == '"' || c == '\\' {
+            out.push('\\');
+        }
+        out.push(c);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MetricSink, WavefrontMetricSink};
+    use crate::sinks::spy::RecordingMetricSink;
+    use std::io;
+
+    fn convert(sink: &WavefrontMetricSink, recording: &RecordingMetricSink, metric: &str) -> String {
+        sink.emit(metric).unwrap();
+        recording.take().remove(0)
+    }
+
+    #[test]
+    fn test_wavefront_metric_sink_types() {
+        let recording = RecordingMetricSink::new();
+        let sink = WavefrontMetricSink::from("web01", recording.clone());
+
+        assert_eq!(
+            "\"\u{2206}some.counter\" 2 source=\"web01\"",
+            convert(&sink, &recording, "some.counter:2|c")
+        );
+        assert_eq!(
+            "\"\u{2206}some.counter\" 4 source=\"web01\"",
+            convert(&sink, &recording, "some.counter:2|c|@0.5")
+        );
+        assert_eq!(
+            "\"some.gauge\" 1.5 source=\"web01\"",
+            convert(&sink, &recording, "some.gauge:1.5|g")
+        );
+        assert_eq!(
+            "\"some.timer\" 12 source=\"web01\"\n\"some.timer\" 14 source=\"web01\"",
+            convert(&sink, &recording, "some.timer:12:14|ms")
+        );
+    }
+
+    #[test]
+    fn test_wavefront_metric_sink_tags_and_timestamp() {
+        let recording = RecordingMetricSink::new();
+        let sink = WavefrontMetricSink::from("web01", recording.clone());
+
+        assert_eq!(
+            r#""some.gauge" 5 1700000000 source="web01" "path"="/a\"b" "env"="prod""#,
+            convert(
+                &sink,
+                &recording,
+                "some.gauge:5|g|#path:/a\"b,beta,env:prod|T1700000000"
+            )
+        );
+    }
+
+    #[test]
+    fn test_wavefront_metric_sink_builder() {
+        let recording = RecordingMetricSink::new();
+        let sink = WavefrontMetricSink::builder("default")
+            .with_source_tag("host")
+            .without_delta_counters()
+            .build(recording.clone());
+
+        assert_eq!(
+            r#""some.counter" 1 source="web02" "env"="prod""#,
+            convert(&sink, &recording, "some.counter:1|c|#host:web02,env:prod")
+        );
+        assert_eq!(
+            r#""some.counter" 1 source="default""#,
+            convert(&sink, &recording, "some.counter:1|c")
+        );
+    }
+
+    #[test]
+    fn test_wavefront_metric_sink_invalid() {
+        let recording = RecordingMetricSink::new();
+        let sink = WavefrontMetricSink::from("web01", recording.clone());
+
+        let err = sink.emit("_sc|some.check|0").unwrap_err();
+        assert_eq!(io::ErrorKind::InvalidInput, err.kind());
+        assert!(recording.is_empty());
+    }
+}
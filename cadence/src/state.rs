@@ -0,0 +1,113 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2020-2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+// This is the only module that is allowed to use unsafe code. `OnceLock` and
+// a `const` `Mutex::new` aren't available in our minimum supported Rust version
+// so this holder is used for values that are set once and read globally.
+#![allow(unsafe_code)]
+
+use std::cell::UnsafeCell;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+const UNSET: usize = 0;
+const LOADING: usize = 1;
+const COMPLETE: usize = 2;
+
+/// Holder to allow global reads of a value from multiple threads while
+/// allowing the value to be written (set) a single time.
+///
+/// This type is public to allow it to be used by the `cadence-macros` crate
+/// and its integration tests but it is not part of the public API and may
+/// change at any time.
+#[doc(hidden)]
+#[derive(Debug, Default)]
+pub struct SingletonHolder<T> {
+    value: UnsafeCell<Option<Arc<T>>>,
+    state: AtomicUsize,
+}
+
+impl<T> SingletonHolder<T> {
+    /// Create a new empty holder
+    pub const fn new() -> Self {
+        SingletonHolder {
+            value: UnsafeCell::new(None),
+            state: AtomicUsize::new(UNSET),
+        }
+    }
+}
+
+impl<T> SingletonHolder<T> {
+    /// Get a pointer to the contained value if set, None otherwise
+    pub fn get(&self) -> Option<Arc<T>> {
+        if !self.is_set() {
+            return None;
+        }
+
+        // SAFETY: We've ensured that the state is "complete" and the
+        // set method has completed and set a value for the UnsafeCell.
+        unsafe { &*self.value.get() }.clone()
+    }
+
+    pub fn is_set(&self) -> bool {
+        COMPLETE == self.state.load(Ordering::Acquire)
+    }
+
+    /// Set the value if it has not already been set, returning true if the
+    /// value was set and false if this is a no-op because it was already set.
+    pub fn set<V>(&self, val: V) -> bool
+    where
+        V: Into<Arc<T>>,
+    {
+        if self
+            .state
+            .compare_exchange(UNSET, LOADING, Ordering::AcqRel, Ordering::Relaxed)
+            .is_err()
+        {
+            return false;
+        }
+
+        // SAFETY: There are no readers at this point since we've guaranteed the
+        // state could not have been "complete". There are no other writers since
+        // we've ensured that the state was previously "unset" and we've been able
+        // to compare-and-swap it to "loading".
+        let ptr = self.value.get();
+        unsafe {
+            *ptr = Some(val.into());
+        }
+
+        self.state.store(COMPLETE, Ordering::Release);
+        true
+    }
+}
+
+unsafe impl<T: Send + Sync> Send for SingletonHolder<T> {}
+
+unsafe impl<T: Send + Sync> Sync for SingletonHolder<T> {}
+
+#[cfg(test)]
+mod tests {
+    use super::SingletonHolder;
+    use std::sync::Arc;
+
+    #[test]
+    fn test_singleton_holder_set_once() {
+        let holder = SingletonHolder::new();
+
+        assert!(!holder.is_set());
+        assert_eq!(None, holder.get());
+
+        assert!(holder.set(1));
+        assert!(!holder.set(Arc::new(2)));
+
+        assert!(holder.is_set());
+        assert_eq!(Some(Arc::new(1)), holder.get());
+    }
+}
@@ -0,0 +1,277 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{self, Read, Write};
+use std::sync::Arc;
+use std::time::Instant;
+
+use crate::client::{Counted, Histogrammed, StatsdClient};
+
+// Counters and histograms emitted for the bytes passing through a wrapped
+// reader or writer.
+#[derive(Debug)]
+struct ByteMetrics {
+    client: Arc<StatsdClient>,
+    key: String,
+    bytes_key: String,
+    throughput_key: Option<String>,
+}
+
+impl ByteMetrics {
+    fn new(client: Arc<StatsdClient>, key: &str) -> Self {
+        ByteMetrics {
+            client,
+            key: key.to_owned(),
+            bytes_key: format!("{}.bytes", key),
+            throughput_key: None,
+        }
+    }
+
+    fn enable_throughput(&mut self) {
+        self.throughput_key = Some(format!("{}.bytes_per_second", self.key));
+    }
+
+    // Run a single read or write, emitting the number of bytes it handled
+    // and, if enabled, the rate at which they were handled.
+    fn measure<F>(&self, f: F) -> io::Result<usize>
+    where
+        F: FnOnce() -> io::Result<usize>,
+    {
+        let start = self.throughput_key.as_ref().map(|_| Instant::now());
+        let n = f()?;
+        if n == 0 {
+            return Ok(n);
+        }
+
+        self.client.count_with_tags(&self.bytes_key, n as u64).send();
+        if let (Some(key), Some(start)) = (&self.throughput_key, start) {
+            let elapsed = start.elapsed().as_secs_f64();
+            if elapsed > 0.0 {
+                self.client.histogram_with_tags(key, n as f64 / elapsed).send();
+            }
+        }
+
+        Ok(n)
+    }
+}
+
+/// Wrapper for a `Read` implementation that counts bytes read from it using a
+/// `StatsdClient`.
+///
+/// Each read that returns data increments the counter `{key}.bytes` by the
+/// number of bytes read. If throughput is enabled with `.with_throughput()`,
+/// the rate of each read in bytes per second is also sent as the histogram
+/// `{key}.bytes_per_second`.
+///
+/// Metrics are sent for every call to `.read()` so a reader that is read in
+/// many small pieces should be wrapped in a `BufReader` first.
+///
+/// # Example
+///
+/// ```
+/// use std::io::Read;
+/// use cadence::{CountedReader, NopMetricSink, StatsdClient};
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// let mut reader = CountedReader::new(client, "upload", &b"some data"[..]).with_throughput();
+///
+/// let mut buf = String::new();
+/// reader.read_to_string(&mut buf).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct CountedReader<R> {
+    inner: R,
+    metrics: ByteMetrics,
+}
+
+impl<R> CountedReader<R>
+where
+    R: Read,
+{
+    /// Create a new reader that counts bytes read from the wrapped reader
+    /// with the counter `{key}.bytes`.
+    pub fn new<C>(client: C, key: &str, inner: R) -> Self
+    where
+        C: Into<Arc<StatsdClient>>,
+    {
+        CountedReader {
+            inner,
+            metrics: ByteMetrics::new(client.into(), key),
+        }
+    }
+
+    /// Also send the rate of each read in bytes per second as the histogram
+    /// `{key}.bytes_per_second`.
+    pub fn with_throughput(mut self) -> Self {
+        self.metrics.enable_throughput();
+        self
+    }
+
+    /// Get a reference to the wrapped reader.
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped reader.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Unwrap this reader, returning the wrapped reader.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R> Read for CountedReader<R>
+where
+    R: Read,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let inner = &mut self.inner;
+        self.metrics.measure(|| inner.read(buf))
+    }
+}
+
+/// Wrapper for a `Write` implementation that counts bytes written to it using
+/// a `StatsdClient`.
+///
+/// Each write that accepts data increments the counter `{key}.bytes` by the
+/// number of bytes written. If throughput is enabled with `.with_throughput()`,
+/// the rate of each write in bytes per second is also sent as the histogram
+/// `{key}.bytes_per_second`.
+///
+/// Metrics are sent for every call to `.write()` so a writer that is written
+/// in many small pieces should be wrapped in a `BufWriter`.
+///
+/// # Example
+///
+/// ```
+/// use std::io::Write;
+/// use cadence::{CountedWriter, NopMetricSink, StatsdClient};
+///
+/// let client = StatsdClient::from_sink("my.prefix", NopMetricSink);
+/// let mut writer = CountedWriter::new(client, "download", Vec::new());
+///
+/// writer.write_all(b"some data").unwrap();
+/// assert_eq!(b"some data", &writer.get_ref()[..]);
+/// ```
+#[derive(Debug)]
+pub struct CountedWriter<W> {
+    inner: W,
+    metrics: ByteMetrics,
+}
+
+impl<W> CountedWriter<W>
+where
+    W: Write,
+{
+    /// Create a new writer that counts bytes written to the wrapped writer
+    /// with the counter `{key}.bytes`.
+    pub fn new<C>(client: C, key: &str, inner: W) -> Self
+    where
+        C: Into<Arc<StatsdClient>>,
+    {
+        CountedWriter {
+            inner,
+            metrics: ByteMetrics::new(client.into(), key),
+        }
+    }
+
+    /// Also send the rate of each write in bytes per second as the histogram
+    /// `{key}.bytes_per_second`.
+    pub fn with_throughput(mut self) -> Self {
+        self.metrics.enable_throughput();
+        self
+    }
+
+    /// Get a reference to the wrapped writer.
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped writer.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Unwrap this writer, returning the wrapped writer.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W> Write for CountedWriter<W>
+where
+    W: Write,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let inner = &mut self.inner;
+        self.metrics.measure(|| inner.write(buf))
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CountedReader, CountedWriter};
+    use crate::{MetricType, ParsedMetric, RecordingMetricSink, StatsdClient};
+    use std::io::{BufRead, BufReader, Read, Write};
+
+    #[test]
+    fn test_counted_reader() {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink.clone());
+        let mut reader = CountedReader::new(client, "file", &b"hello world"[..]);
+
+        let mut first = [0; 5];
+        reader.read_exact(&mut first).unwrap();
+        let mut rest = Vec::new();
+        reader.read_to_end(&mut rest).unwrap();
+
+        assert_eq!(b"hello", &first);
+        assert_eq!(b" world", &rest[..]);
+        assert_eq!(vec!["prefix.file.bytes:5|c", "prefix.file.bytes:6|c"], sink.metrics());
+    }
+
+    #[test]
+    fn test_counted_reader_buffered() {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink.clone());
+        let reader = BufReader::new(CountedReader::new(client, "file", &b"one\ntwo\nthree\n"[..]));
+
+        assert_eq!(3, reader.lines().count());
+        assert_eq!(vec!["prefix.file.bytes:14|c"], sink.metrics());
+    }
+
+    #[test]
+    fn test_counted_writer_throughput() {
+        let sink = RecordingMetricSink::new();
+        let client = StatsdClient::from_sink("prefix", sink.clone());
+        let mut writer = CountedWriter::new(client, "socket", Vec::new()).with_throughput();
+
+        writer.write_all(b"some bytes").unwrap();
+        writer.write_all(b"").unwrap();
+        writer.flush().unwrap();
+
+        let metrics = sink.metrics();
+        assert_eq!(b"some bytes", &writer.into_inner()[..]);
+        assert_eq!("prefix.socket.bytes:10|c", metrics[0]);
+        assert!(metrics.len() <= 2);
+        if let Some(rate) = metrics.get(1) {
+            let rate = ParsedMetric::parse(rate).unwrap();
+            assert_eq!("prefix.socket.bytes_per_second", rate.name());
+            assert_eq!(MetricType::Histogram, rate.metric_type());
+        }
+    }
+}
@@ -293,3 +293,67 @@ impl MetricSink for ErrorMetricSink {
         }
     }
 }
+
+/// `MetricSink` implementation that fails the next writes with an error of a
+/// given kind and counts every attempted write.
+///
+/// Clones share the same state so that tests can make a sink fail or recover
+/// and check how many times it was written to after wrapping it in another sink.
+#[derive(Debug, Clone)]
+pub struct FailingMetricSink {
+    kind: io::ErrorKind,
+    failures: Arc<AtomicU64>,
+    attempts: Arc<AtomicU64>,
+}
+
+impl FailingMetricSink {
+    /// Create a sink that succeeds until `.fail_next()` or `.fail_always()`
+    /// is called.
+    pub fn new(kind: io::ErrorKind) -> Self {
+        FailingMetricSink {
+            kind,
+            failures: Arc::new(AtomicU64::new(0)),
+            attempts: Arc::new(AtomicU64::new(0)),
+        }
+    }
+
+    /// Fail the next `n` writes, replacing any failures left.
+    pub fn fail_next(&self, n: u64) {
+        self.failures.store(n, Ordering::SeqCst);
+    }
+
+    /// Fail every write until `.recover()` is called.
+    pub fn fail_always(&self) {
+        self.fail_next(u64::MAX);
+    }
+
+    /// Stop failing writes.
+    pub fn recover(&self) {
+        self.fail_next(0);
+    }
+
+    /// Return the number of writes attempted, including failed ones.
+    pub fn attempts(&self) -> u64 {
+        self.attempts.load(Ordering::SeqCst)
+    }
+}
+
+impl MetricSink for FailingMetricSink {
+    fn emit(&self, m: &str) -> io::Result<usize> {
+        self.attempts.fetch_add(1, Ordering::SeqCst);
+        let failing = self
+            .failures
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| match n {
+                0 => None,
+                u64::MAX => Some(n),
+                _ => Some(n - 1),
+            })
+            .is_ok();
+
+        if failing {
+            Err(io::Error::from(self.kind))
+        } else {
+            Ok(m.len())
+        }
+    }
+}
@@ -0,0 +1,321 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Checks that custom `MetricSink` implementations behave like the sinks
+//! included with Cadence
+//!
+//! Each function in this module emits metrics using a sink under test and
+//! then checks the metrics that arrived at the other end of it, panicking with
+//! a description of the problem if they aren't what was expected. Metrics that
+//! arrived are read from anything implementing `Recorded`: a
+//! `RecordingMetricSink` for sinks that wrap other sinks or a `UdpTestServer`
+//! or `TcpTestServer` for sinks that send metrics over the network.
+//!
+//! Since metrics may arrive asynchronously, the checks wait up to five seconds
+//! for them. Each check only looks at metrics with a name it uses so the same
+//! recorder can be used for several checks.
+//!
+//! # Example
+//!
+//! ```
+//! use cadence::testing::{conformance, UdpTestServer};
+//! use cadence::BufferedUdpMetricSink;
+//! use std::net::UdpSocket;
+//!
+//! let server = UdpTestServer::new().unwrap();
+//! let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+//! let sink = BufferedUdpMetricSink::from(server.local_addr(), socket).unwrap();
+//!
+//! conformance::check_all(&sink, &server);
+//! ```
+
+use std::thread;
+use std::time::{Duration, Instant};
+
+use super::Recorded;
+use crate::sinks::MetricSink;
+
+// Longest time to wait for emitted metrics to be recorded.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// How often to check if emitted metrics have been recorded.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+// Size of the metric used to check large metrics, bigger than the buffers of
+// any of the sinks included with Cadence but small enough for a UDP datagram.
+const LARGE_METRIC_SIZE: usize = 10_000;
+
+/// Run every check in this module against the given sink.
+#[track_caller]
+pub fn check_all<S, R>(sink: &S, recorded: &R)
+where
+    S: MetricSink + ?Sized,
+    R: Recorded + ?Sized,
+{
+    check_emit(sink, recorded);
+    check_flush(sink, recorded);
+    check_newlines(sink, recorded);
+    check_large_metric(sink, recorded);
+}
+
+/// Check that a single emitted metric is recorded exactly as it was emitted
+/// once the sink has been flushed.
+#[track_caller]
+pub fn check_emit<S, R>(sink: &S, recorded: &R)
+where
+    S: MetricSink + ?Sized,
+    R: Recorded + ?Sized,
+{
+    let prefix = "cadence.conformance.emit";
+    let metric = "cadence.conformance.emit:1|c|#check:emit";
+    let check = Check::start(prefix, recorded);
+
+    if let Err(e) = sink.emit(metric) {
+        panic!("Failed to emit `{}`: {}", metric, e);
+    }
+
+    flush(sink);
+    check.expect(&[metric]);
+}
+
+/// Check that several emitted metrics are all recorded in the order they were
+/// emitted once the sink has been flushed, and that flushing a sink with no
+/// metrics to write succeeds.
+#[track_caller]
+pub fn check_flush<S, R>(sink: &S, recorded: &R)
+where
+    S: MetricSink + ?Sized,
+    R: Recorded + ?Sized,
+{
+    let prefix = "cadence.conformance.flush";
+    let metrics: Vec<String> = (0..10).map(|i| format!("{}.{}:{}|g", prefix, i, i)).collect();
+    let check = Check::start(prefix, recorded);
+
+    for metric in metrics.iter() {
+        if let Err(e) = sink.emit(metric) {
+            panic!("Failed to emit `{}`: {}", metric, e);
+        }
+    }
+
+    flush(sink);
+    flush(sink);
+    check.expect(&metrics);
+}
+
+/// Check that metrics are recorded as separate lines whether they are emitted
+/// one at a time or in a batch, without any empty lines or extra newlines.
+#[track_caller]
+pub fn check_newlines<S, R>(sink: &S, recorded: &R)
+where
+    S: MetricSink + ?Sized,
+    R: Recorded + ?Sized,
+{
+    let prefix = "cadence.conformance.newlines";
+    let single = [
+        "cadence.conformance.newlines.a:1|c",
+        "cadence.conformance.newlines.b:2|ms",
+    ];
+    let batch = [
+        "cadence.conformance.newlines.c:3|h",
+        "cadence.conformance.newlines.d:4|d",
+    ];
+    let check = Check::start(prefix, recorded);
+
+    for metric in single.iter() {
+        if let Err(e) = sink.emit(metric) {
+            panic!("Failed to emit `{}`: {}", metric, e);
+        }
+    }
+
+    if let Err(e) = sink.emit_batch(&batch) {
+        panic!("Failed to emit batch {:?}: {}", batch, e);
+    }
+
+    flush(sink);
+
+    let expected: Vec<&str> = single.iter().chain(batch.iter()).copied().collect();
+    check.expect(&expected);
+
+    let blank: Vec<String> = recorded
+        .recorded()
+        .into_iter()
+        .filter(|m| m.trim().is_empty())
+        .collect();
+    if !blank.is_empty() {
+        panic!("Recorded {} empty line(s) but metrics must not be empty", blank.len());
+    }
+}
+
+/// Check that a metric larger than typical buffers is either recorded intact
+/// or not at all, and that the sink keeps working afterwards.
+///
+/// Sinks may return an error for metrics that are too large to send or drop
+/// them, but must never truncate them or split them across several lines.
+#[track_caller]
+pub fn check_large_metric<S, R>(sink: &S, recorded: &R)
+where
+    S: MetricSink + ?Sized,
+    R: Recorded + ?Sized,
+{
+    let prefix = "cadence.conformance.large";
+    let large = format!("{}.{}:1|c", prefix, "x".repeat(LARGE_METRIC_SIZE));
+    let after = format!("{}.after:1|c", prefix);
+    let check = Check::start(prefix, recorded);
+
+    let sent = sink.emit(&large).is_ok();
+    flush(sink);
+
+    if let Err(e) = sink.emit(&after) {
+        panic!("Failed to emit `{}` after a large metric: {}", after, e);
+    }
+
+    flush(sink);
+
+    // Metrics arrive in order so once the metric sent afterwards has been
+    // recorded, the large metric has been too if it's ever going to be.
+    let lines = check.wait_for(|lines| lines.contains(&after));
+    let expected: Vec<&str> = if sent && lines.len() > 1 {
+        vec![&large, &after]
+    } else {
+        vec![&after]
+    };
+
+    if lines != expected {
+        panic!(
+            "Expected a {} byte metric to be recorded intact or not at all, got lines of length {:?}",
+            large.len(),
+            lines.iter().map(|m| m.len()).collect::<Vec<_>>(),
+        );
+    }
+}
+
+#[track_caller]
+fn flush<S>(sink: &S)
+where
+    S: MetricSink + ?Sized,
+{
+    if let Err(e) = sink.flush() {
+        panic!("Failed to flush sink: {}", e);
+    }
+}
+
+// Metrics recorded by a single check, ignoring any that were recorded before
+// it started or that belong to other checks.
+struct Check<'a, R: ?Sized> {
+    prefix: &'a str,
+    recorded: &'a R,
+    skip: usize,
+}
+
+impl<'a, R> Check<'a, R>
+where
+    R: Recorded + ?Sized,
+{
+    fn start(prefix: &'a str, recorded: &'a R) -> Self {
+        let mut check = Check {
+            prefix,
+            recorded,
+            skip: 0,
+        };
+
+        check.skip = check.lines().len();
+        check
+    }
+
+    fn lines(&self) -> Vec<String> {
+        self.recorded
+            .recorded()
+            .into_iter()
+            .filter(|m| m.starts_with(self.prefix))
+            .skip(self.skip)
+            .collect()
+    }
+
+    fn wait_for<F>(&self, done: F) -> Vec<String>
+    where
+        F: Fn(&[String]) -> bool,
+    {
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            let lines = self.lines();
+            if done(&lines) || Instant::now() >= deadline {
+                return lines;
+            }
+
+            thread::sleep(POLL_INTERVAL);
+        }
+    }
+
+    #[track_caller]
+    fn expect<T>(&self, expected: &[T])
+    where
+        T: AsRef<str>,
+    {
+        let lines = self.wait_for(|lines| lines.len() >= expected.len());
+        let expected: Vec<&str> = expected.iter().map(|m| m.as_ref()).collect();
+        if lines != expected {
+            panic!("Expected metrics {:?} to be recorded, got {:?}", expected, lines);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_all, check_large_metric};
+    use crate::sinks::{
+        BufferedTcpMetricSink, BufferedUdpMetricSink, Fault, FaultSchedule, FlakyMetricSink, QueuingMetricSink,
+        RecordingMetricSink, TcpMetricSink,
+    };
+    use crate::testing::{TcpTestServer, UdpTestServer};
+    use std::net::UdpSocket;
+
+    #[test]
+    fn test_recording_sink() {
+        let sink = RecordingMetricSink::new();
+        check_all(&sink, &sink);
+    }
+
+    #[test]
+    fn test_queuing_sink() {
+        let recording = RecordingMetricSink::new();
+        let sink = QueuingMetricSink::from(recording.clone());
+        check_all(&sink, &recording);
+    }
+
+    #[test]
+    fn test_buffered_udp_sink() {
+        let server = UdpTestServer::new().unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        let sink = BufferedUdpMetricSink::from(server.local_addr(), socket).unwrap();
+        check_all(&sink, &server);
+    }
+
+    #[test]
+    fn test_tcp_sinks() {
+        let server = TcpTestServer::new().unwrap();
+        let sink = TcpMetricSink::from(server.local_addr()).unwrap();
+        check_all(&sink, &server);
+
+        let sink = BufferedTcpMetricSink::from(server.local_addr()).unwrap();
+        check_all(&sink, &server);
+    }
+
+    #[test]
+    #[should_panic(expected = "recorded intact or not at all")]
+    fn test_truncating_sink() {
+        let recording = RecordingMetricSink::new();
+        let sink = FlakyMetricSink::builder()
+            .with_fault(Fault::PartialWrite(100))
+            .with_schedule(FaultSchedule::Always)
+            .build(recording.clone());
+
+        check_large_metric(&sink, &recording);
+    }
+}
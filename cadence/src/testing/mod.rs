@@ -0,0 +1,363 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+//! Helpers for checking which metrics were emitted by code under test
+//!
+//! The functions in this module check the metrics recorded by a
+//! `RecordingMetricSink` or received by a `UdpTestServer` or `TcpTestServer`,
+//! panicking with a message that includes every recorded metric when they
+//! don't match. This keeps tests of instrumented code short and avoids parsing
+//! Statsd metrics in each of them.
+//!
+//! This module is only available when the `test-util` feature is enabled and
+//! is subject to the same guarantees as the rest of the API (semantic
+//! versioning, etc.).
+//!
+//! # Example
+//!
+//! ```
+//! use cadence::prelude::*;
+//! use cadence::testing::{assert_counter, assert_emitted, MetricMatcher};
+//! use cadence::{RecordingMetricSink, StatsdClient};
+//!
+//! let sink = RecordingMetricSink::new();
+//! let client = StatsdClient::from_sink("my.app", sink.clone());
+//!
+//! client.incr("requests").unwrap();
+//! client.count("requests", 2).unwrap();
+//! client.time_with_tags("latency", 23).with_tag("status", "200").send();
+//!
+//! assert_counter(&sink, "my.app.requests", 3);
+//! assert_emitted(&sink, &MetricMatcher::timer("my.app.latency").with_tag("status", "200"));
+//! ```
+
+use crate::builder::MetricValue;
+use crate::parse::ParsedMetric;
+use crate::sinks::RecordingMetricSink;
+
+pub mod conformance;
+mod server;
+
+pub use self::server::{TcpTestServer, UdpTestServer};
+
+/// Source of recorded metrics that the assertions in this module can check.
+pub trait Recorded {
+    /// Return a copy of every metric recorded so far, oldest first.
+    fn recorded(&self) -> Vec<String>;
+}
+
+impl Recorded for RecordingMetricSink {
+    fn recorded(&self) -> Vec<String> {
+        self.metrics()
+    }
+}
+
+/// Criteria for matching recorded metrics by name, type, and tags.
+///
+/// A metric matches if it has the given name, the given type (if any), and
+/// at least all of the given tags. Metrics may have other tags as well.
+#[derive(Debug, Clone)]
+pub struct MetricMatcher<'a> {
+    key: &'a str,
+    type_: Option<&'a str>,
+    tags: Vec<(Option<&'a str>, &'a str)>,
+}
+
+impl<'a> MetricMatcher<'a> {
+    /// Match metrics of any type with the given full name, including prefix.
+    pub fn new(key: &'a str) -> Self {
+        MetricMatcher {
+            key,
+            type_: None,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Match counters with the given full name, including prefix.
+    pub fn counter(key: &'a str) -> Self {
+        Self::with_type(key, "c")
+    }
+
+    /// Match timers with the given full name, including prefix.
+    pub fn timer(key: &'a str) -> Self {
+        Self::with_type(key, "ms")
+    }
+
+    /// Match gauges with the given full name, including prefix.
+    pub fn gauge(key: &'a str) -> Self {
+        Self::with_type(key, "g")
+    }
+
+    /// Match meters with the given full name, including prefix.
+    pub fn meter(key: &'a str) -> Self {
+        Self::with_type(key, "m")
+    }
+
+    /// Match histograms with the given full name, including prefix.
+    pub fn histogram(key: &'a str) -> Self {
+        Self::with_type(key, "h")
+    }
+
+    /// Match sets with the given full name, including prefix.
+    pub fn set(key: &'a str) -> Self {
+        Self::with_type(key, "s")
+    }
+
+    /// Match distributions with the given full name, including prefix.
+    pub fn distribution(key: &'a str) -> Self {
+        Self::with_type(key, "d")
+    }
+
+    fn with_type(key: &'a str, type_: &'a str) -> Self {
+        MetricMatcher {
+            type_: Some(type_),
+            ..Self::new(key)
+        }
+    }
+
+    /// Only match metrics that have the given key-value tag.
+    pub fn with_tag(mut self, key: &'a str, value: &'a str) -> Self {
+        self.tags.push((Some(key), value));
+        self
+    }
+
+    /// Only match metrics that have the given value tag.
+    pub fn with_tag_value(mut self, value: &'a str) -> Self {
+        self.tags.push((None, value));
+        self
+    }
+
+    /// Return true if the given Statsd metric matches. Metrics that can't be
+    /// parsed never match.
+    pub fn matches(&self, metric: &str) -> bool {
+        ParsedMetric::parse(metric)
+            .map(|m| self.matches_parsed(&m))
+            .unwrap_or(false)
+    }
+
+    /// Return every recorded metric that matches, oldest first.
+    pub fn find<R>(&self, sink: &R) -> Vec<String>
+    where
+        R: Recorded + ?Sized,
+    {
+        sink.recorded().into_iter().filter(|m| self.matches(m)).collect()
+    }
+
+    fn matches_parsed(&self, metric: &ParsedMetric<'_>) -> bool {
+        metric.name() == self.key
+            && self.type_.map(|t| t == metric.metric_type().as_str()).unwrap_or(true)
+            && self.tags.iter().all(|tag| metric.tags().contains(tag))
+    }
+}
+
+/// Assert that at least one recorded metric matches.
+#[track_caller]
+pub fn assert_emitted<R>(sink: &R, matcher: &MetricMatcher<'_>)
+where
+    R: Recorded + ?Sized,
+{
+    let metrics = sink.recorded();
+    if !metrics.iter().any(|m| matcher.matches(m)) {
+        panic!(
+            "expected a metric matching {:?}, recorded metrics: {:?}",
+            matcher, metrics
+        );
+    }
+}
+
+/// Assert that no recorded metrics match.
+#[track_caller]
+pub fn assert_not_emitted<R>(sink: &R, matcher: &MetricMatcher<'_>)
+where
+    R: Recorded + ?Sized,
+{
+    let metrics = sink.recorded();
+    if metrics.iter().any(|m| matcher.matches(m)) {
+        panic!(
+            "expected no metrics matching {:?}, recorded metrics: {:?}",
+            matcher, metrics
+        );
+    }
+}
+
+/// Assert that the values of all recorded counters with the given name add
+/// up to the expected value, regardless of their tags.
+///
+/// Values are added up as they were sent: they are not scaled by sample rate.
+#[track_caller]
+pub fn assert_counter<R>(sink: &R, key: &str, expected: i64)
+where
+    R: Recorded + ?Sized,
+{
+    assert_counter_matching(sink, &MetricMatcher::counter(key), expected);
+}
+
+/// Assert that the values of all recorded counters with the given name and
+/// tags add up to the expected value.
+///
+/// See `assert_counter()` for more information.
+#[track_caller]
+pub fn assert_counter_with_tags<R>(sink: &R, key: &str, tags: &[(&str, &str)], expected: i64)
+where
+    R: Recorded + ?Sized,
+{
+    let matcher = tags
+        .iter()
+        .fold(MetricMatcher::counter(key), |m, (k, v)| m.with_tag(k, v));
+    assert_counter_matching(sink, &matcher, expected);
+}
+
+#[track_caller]
+fn assert_counter_matching<R>(sink: &R, matcher: &MetricMatcher<'_>, expected: i64)
+where
+    R: Recorded + ?Sized,
+{
+    let metrics = sink.recorded();
+    let total: f64 = metrics
+        .iter()
+        .filter_map(|m| ParsedMetric::parse(m).ok())
+        .filter(|m| matcher.matches_parsed(m))
+        .map(|m| single_value(&m))
+        .sum();
+
+    if total != expected as f64 {
+        panic!(
+            "expected counters matching {:?} to add up to {}, got {}, recorded metrics: {:?}",
+            matcher, expected, total, metrics
+        );
+    }
+}
+
+/// Assert that the most recently recorded gauge with the given name has the
+/// expected value, regardless of its tags.
+#[track_caller]
+pub fn assert_gauge<R>(sink: &R, key: &str, expected: f64)
+where
+    R: Recorded + ?Sized,
+{
+    let matcher = MetricMatcher::gauge(key);
+    let metrics = sink.recorded();
+    let last = metrics
+        .iter()
+        .rev()
+        .filter_map(|m| ParsedMetric::parse(m).ok())
+        .find(|m| matcher.matches_parsed(m))
+        .map(|m| single_value(&m));
+
+    match last {
+        Some(v) if v == expected => {}
+        Some(v) => panic!(
+            "expected gauge {:?} to be {}, got {}, recorded metrics: {:?}",
+            key, expected, v, metrics
+        ),
+        None => panic!("expected a gauge {:?}, recorded metrics: {:?}", key, metrics),
+    }
+}
+
+#[track_caller]
+fn single_value(metric: &ParsedMetric<'_>) -> f64 {
+    match metric.value() {
+        MetricValue::Signed(v) => *v as f64,
+        MetricValue::Unsigned(v) => *v as f64,
+        MetricValue::Float(v) => *v,
+        v => panic!("unexpected packed value {:?} for metric {:?}", v, metric.name()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{
+        assert_counter, assert_counter_with_tags, assert_emitted, assert_gauge, assert_not_emitted, MetricMatcher,
+    };
+    use crate::sinks::{MetricSink, RecordingMetricSink};
+
+    fn new_sink(metrics: &[&str]) -> RecordingMetricSink {
+        let sink = RecordingMetricSink::new();
+        sink.emit_batch(metrics).unwrap();
+        sink
+    }
+
+    #[test]
+    fn test_metric_matcher() {
+        let metric = "app.requests:1|c|@0.5|#region:us-east-1,canary|T1234";
+
+        assert!(MetricMatcher::new("app.requests").matches(metric));
+        assert!(MetricMatcher::counter("app.requests").matches(metric));
+        assert!(MetricMatcher::counter("app.requests")
+            .with_tag("region", "us-east-1")
+            .with_tag_value("canary")
+            .matches(metric));
+        assert!(!MetricMatcher::gauge("app.requests").matches(metric));
+        assert!(!MetricMatcher::counter("app").matches(metric));
+        assert!(!MetricMatcher::counter("app.requests")
+            .with_tag("region", "us-west-2")
+            .matches(metric));
+        assert!(!MetricMatcher::counter("app.requests").matches("not a metric"));
+    }
+
+    #[test]
+    fn test_metric_matcher_find() {
+        let sink = new_sink(&["app.latency:23|ms|#status:200", "app.latency:45|ms|#status:500"]);
+        let found = MetricMatcher::timer("app.latency")
+            .with_tag("status", "500")
+            .find(&sink);
+
+        assert_eq!(vec!["app.latency:45|ms|#status:500"], found);
+    }
+
+    #[test]
+    fn test_assert_emitted() {
+        let sink = new_sink(&["app.latency:23|ms|#status:200"]);
+
+        assert_emitted(&sink, &MetricMatcher::timer("app.latency").with_tag("status", "200"));
+        assert_not_emitted(&sink, &MetricMatcher::timer("app.latency").with_tag("status", "500"));
+    }
+
+    #[test]
+    #[should_panic(expected = "recorded metrics: [\"app.latency:23|ms\"]")]
+    fn test_assert_emitted_failure() {
+        let sink = new_sink(&["app.latency:23|ms"]);
+        assert_emitted(&sink, &MetricMatcher::histogram("app.latency"));
+    }
+
+    #[test]
+    fn test_assert_counter() {
+        let sink = new_sink(&[
+            "app.requests:1|c|#status:200",
+            "app.requests:2|c|#status:500",
+            "app.requests:4|g",
+            "app.errors:1|c",
+        ]);
+
+        assert_counter(&sink, "app.requests", 3);
+        assert_counter_with_tags(&sink, "app.requests", &[("status", "500")], 2);
+        assert_counter(&sink, "app.missing", 0);
+    }
+
+    #[test]
+    #[should_panic(expected = "to add up to 2, got 1")]
+    fn test_assert_counter_failure() {
+        let sink = new_sink(&["app.requests:1|c"]);
+        assert_counter(&sink, "app.requests", 2);
+    }
+
+    #[test]
+    fn test_assert_gauge() {
+        let sink = new_sink(&["app.queue:4|g", "app.queue:2.5|g|#shard:1"]);
+        assert_gauge(&sink, "app.queue", 2.5);
+    }
+
+    #[test]
+    #[should_panic(expected = "expected a gauge")]
+    fn test_assert_gauge_missing() {
+        let sink = new_sink(&["app.queue:4|c"]);
+        assert_gauge(&sink, "app.queue", 4.0);
+    }
+}
@@ -0,0 +1,371 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::io::{self, BufRead, BufReader};
+use std::net::{SocketAddr, TcpListener, TcpStream, UdpSocket};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use super::Recorded;
+
+// How often threads of a server check if it has been stopped while waiting
+// for metrics to arrive.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+// Largest datagram a UDP server can receive.
+const MAX_DATAGRAM_SIZE: usize = 65_536;
+
+/// Server that receives metrics on a local UDP socket and records each line.
+///
+/// The server listens on an unused port on `127.0.0.1` and receives metrics in
+/// a background thread until it is dropped. Datagrams containing several
+/// metrics separated by newlines, such as those sent by `BufferedUdpMetricSink`,
+/// are split into a line for each metric.
+///
+/// Since metrics arrive asynchronously, tests should use `.wait_for()` to wait
+/// until the expected number of metrics have been received before checking them.
+///
+/// # Example
+///
+/// ```
+/// use std::net::UdpSocket;
+/// use std::time::Duration;
+/// use cadence::prelude::*;
+/// use cadence::testing::{assert_counter, UdpTestServer};
+/// use cadence::{StatsdClient, UdpMetricSink};
+///
+/// let server = UdpTestServer::new().unwrap();
+/// let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+/// let sink = UdpMetricSink::from(server.local_addr(), socket).unwrap();
+/// let client = StatsdClient::from_sink("my.app", sink);
+///
+/// client.incr("requests").unwrap();
+/// client.incr("requests").unwrap();
+///
+/// server.wait_for(2, Duration::from_secs(5));
+/// assert_counter(&server, "my.app.requests", 2);
+/// ```
+#[derive(Debug)]
+pub struct UdpTestServer {
+    addr: SocketAddr,
+    state: Arc<ServerState>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl UdpTestServer {
+    /// Start a new server listening on an unused port on `127.0.0.1`.
+    pub fn new() -> io::Result<Self> {
+        let socket = UdpSocket::bind("127.0.0.1:0")?;
+        socket.set_read_timeout(Some(POLL_INTERVAL))?;
+
+        let addr = socket.local_addr()?;
+        let state = Arc::new(ServerState::default());
+        let thread_state = state.clone();
+        let thread = thread::Builder::new()
+            .name("cadence-udp-test-server".into())
+            .spawn(move || receive_datagrams(socket, &thread_state))?;
+
+        Ok(UdpTestServer {
+            addr,
+            state,
+            thread: Some(thread),
+        })
+    }
+
+    /// Return the address metrics should be sent to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Return every line received so far, oldest first.
+    pub fn lines(&self) -> Vec<String> {
+        self.state.lines()
+    }
+
+    /// Remove and return every line received so far, oldest first.
+    pub fn take(&self) -> Vec<String> {
+        self.state.take()
+    }
+
+    /// Wait until at least `count` lines have been received or the timeout
+    /// elapses, returning every line received so far.
+    pub fn wait_for(&self, count: usize, timeout: Duration) -> Vec<String> {
+        self.state.wait_for(count, timeout)
+    }
+}
+
+impl Recorded for UdpTestServer {
+    fn recorded(&self) -> Vec<String> {
+        self.lines()
+    }
+}
+
+impl Drop for UdpTestServer {
+    fn drop(&mut self) {
+        self.state.shutdown.store(true, Ordering::Release);
+        if let Some(t) = self.thread.take() {
+            let _ = t.join();
+        }
+    }
+}
+
+/// Server that accepts metrics on a local TCP socket and records each line.
+///
+/// The server listens on an unused port on `127.0.0.1` and accepts any number
+/// of connections, reading newline terminated metrics from each of them in a
+/// background thread until it is dropped.
+///
+/// Since metrics arrive asynchronously, tests should use `.wait_for()` to wait
+/// until the expected number of metrics have been received before checking them.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use cadence::prelude::*;
+/// use cadence::testing::{assert_emitted, MetricMatcher, TcpTestServer};
+/// use cadence::{StatsdClient, TcpMetricSink};
+///
+/// let server = TcpTestServer::new().unwrap();
+/// let sink = TcpMetricSink::from(server.local_addr()).unwrap();
+/// let client = StatsdClient::from_sink("my.app", sink);
+///
+/// client.gauge_with_tags("queue.size", 12).with_tag("queue", "email").send();
+///
+/// server.wait_for(1, Duration::from_secs(5));
+/// assert_emitted(&server, &MetricMatcher::gauge("my.app.queue.size").with_tag("queue", "email"));
+/// ```
+#[derive(Debug)]
+pub struct TcpTestServer {
+    addr: SocketAddr,
+    state: Arc<ServerState>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl TcpTestServer {
+    /// Start a new server listening on an unused port on `127.0.0.1`.
+    pub fn new() -> io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let addr = listener.local_addr()?;
+        let state = Arc::new(ServerState::default());
+        let thread_state = state.clone();
+        let thread = thread::Builder::new()
+            .name("cadence-tcp-test-server".into())
+            .spawn(move || accept_connections(listener, &thread_state))?;
+
+        Ok(TcpTestServer {
+            addr,
+            state,
+            thread: Some(thread),
+        })
+    }
+
+    /// Return the address metrics should be sent to.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
+    /// Return every line received so far, oldest first.
+    pub fn lines(&self) -> Vec<String> {
+        self.state.lines()
+    }
+
+    /// Remove and return every line received so far, oldest first.
+    pub fn take(&self) -> Vec<String> {
+        self.state.take()
+    }
+
+    /// Wait until at least `count` lines have been received or the timeout
+    /// elapses, returning every line received so far.
+    pub fn wait_for(&self, count: usize, timeout: Duration) -> Vec<String> {
+        self.state.wait_for(count, timeout)
+    }
+}
+
+impl Recorded for TcpTestServer {
+    fn recorded(&self) -> Vec<String> {
+        self.lines()
+    }
+}
+
+impl Drop for TcpTestServer {
+    fn drop(&mut self) {
+        self.state.shutdown.store(true, Ordering::Release);
+        // Wake up the thread blocked accepting connections so that it sees
+        // the server has been stopped.
+        let _ = TcpStream::connect(self.addr);
+        if let Some(t) = self.thread.take() {
+            let _ = t.join();
+        }
+    }
+}
+
+// Lines received by a server and whether it has been stopped, shared with
+// the threads receiving metrics.
+#[derive(Debug, Default)]
+struct ServerState {
+    lines: Mutex<Vec<String>>,
+    received: Condvar,
+    shutdown: AtomicBool,
+}
+
+impl ServerState {
+    fn is_shutdown(&self) -> bool {
+        self.shutdown.load(Ordering::Acquire)
+    }
+
+    fn push_lines(&self, buf: &[u8]) {
+        let received = String::from_utf8_lossy(buf);
+        let mut lines = self.lines.lock().unwrap();
+        lines.extend(received.lines().filter(|l| !l.is_empty()).map(|l| l.to_owned()));
+        self.received.notify_all();
+    }
+
+    fn lines(&self) -> Vec<String> {
+        self.lines.lock().unwrap().clone()
+    }
+
+    fn take(&self) -> Vec<String> {
+        std::mem::take(&mut *self.lines.lock().unwrap())
+    }
+
+    fn wait_for(&self, count: usize, timeout: Duration) -> Vec<String> {
+        let deadline = Instant::now() + timeout;
+        let mut lines = self.lines.lock().unwrap();
+        while lines.len() < count {
+            let now = Instant::now();
+            if now >= deadline {
+                break;
+            }
+
+            lines = self.received.wait_timeout(lines, deadline - now).unwrap().0;
+        }
+
+        lines.clone()
+    }
+}
+
+fn is_timeout(e: &io::Error) -> bool {
+    matches!(e.kind(), io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut)
+}
+
+fn receive_datagrams(socket: UdpSocket, state: &ServerState) {
+    let mut buf = vec![0u8; MAX_DATAGRAM_SIZE];
+    while !state.is_shutdown() {
+        match socket.recv(&mut buf) {
+            Ok(n) => state.push_lines(&buf[..n]),
+            Err(e) if is_timeout(&e) => {}
+            Err(e) => eprintln!("Error: UDP test server failed to receive: {}", e),
+        }
+    }
+}
+
+fn accept_connections(listener: TcpListener, state: &Arc<ServerState>) {
+    let mut connections = Vec::new();
+    for stream in listener.incoming() {
+        if state.is_shutdown() {
+            break;
+        }
+
+        match stream {
+            Ok(stream) => {
+                let state = state.clone();
+                connections.push(thread::spawn(move || read_lines(stream, &state)));
+            }
+            Err(e) => eprintln!("Error: TCP test server failed to accept: {}", e),
+        }
+    }
+
+    for t in connections {
+        let _ = t.join();
+    }
+}
+
+fn read_lines(stream: TcpStream, state: &ServerState) {
+    if let Err(e) = stream.set_read_timeout(Some(POLL_INTERVAL)) {
+        eprintln!("Error: TCP test server failed to set timeout: {}", e);
+        return;
+    }
+
+    // Bytes of a partially read line are kept in the buffer when reading
+    // times out so that they are completed by the next read.
+    let mut reader = BufReader::new(stream);
+    let mut line = Vec::new();
+    while !state.is_shutdown() {
+        match reader.read_until(b'\n', &mut line) {
+            Ok(0) => break,
+            Ok(_) if line.ends_with(b"\n") => {
+                state.push_lines(&line);
+                line.clear();
+            }
+            Ok(_) => {}
+            Err(e) if is_timeout(&e) => {}
+            Err(e) => {
+                eprintln!("Error: TCP test server failed to read: {}", e);
+                break;
+            }
+        }
+    }
+
+    if !line.is_empty() {
+        state.push_lines(&line);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{TcpTestServer, UdpTestServer};
+    use std::io::Write;
+    use std::net::{TcpStream, UdpSocket};
+    use std::time::{Duration, Instant};
+
+    const TIMEOUT: Duration = Duration::from_secs(5);
+
+    #[test]
+    fn test_udp_test_server() {
+        let server = UdpTestServer::new().unwrap();
+        let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+        socket.send_to(b"foo:1|c", server.local_addr()).unwrap();
+        socket.send_to(b"bar:2|g\nbaz:3|ms\n", server.local_addr()).unwrap();
+
+        assert_eq!(vec!["foo:1|c", "bar:2|g", "baz:3|ms"], server.wait_for(3, TIMEOUT));
+        assert_eq!(3, server.take().len());
+        assert!(server.lines().is_empty());
+    }
+
+    #[test]
+    fn test_tcp_test_server() {
+        let server = TcpTestServer::new().unwrap();
+        let mut first = TcpStream::connect(server.local_addr()).unwrap();
+        let mut second = TcpStream::connect(server.local_addr()).unwrap();
+
+        first.write_all(b"foo:1|c\nbar:").unwrap();
+        first.flush().unwrap();
+        second.write_all(b"baz:3|ms\n").unwrap();
+        assert_eq!(2, server.wait_for(2, TIMEOUT).len());
+
+        first.write_all(b"2|g\n").unwrap();
+        let mut lines = server.wait_for(3, TIMEOUT);
+        lines.sort();
+
+        assert_eq!(vec!["bar:2|g", "baz:3|ms", "foo:1|c"], lines);
+    }
+
+    #[test]
+    fn test_wait_for_timeout() {
+        let server = UdpTestServer::new().unwrap();
+        let start = Instant::now();
+
+        assert!(server.wait_for(1, Duration::from_millis(20)).is_empty());
+        assert!(start.elapsed() >= Duration::from_millis(20));
+    }
+}
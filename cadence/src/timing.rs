@@ -0,0 +1,545 @@
+// Cadence - An extensible Statsd client for Rust!
+//
+// Copyright 2024 Nick Pillitteri
+//
+// Licensed under the Apache License, Version 2.0 <LICENSE-APACHE or
+// http://www.apache.org/licenses/LICENSE-2.0> or the MIT license
+// <LICENSE-MIT or http://opensource.org/licenses/MIT>, at your
+// option. This file may not be copied, modified, or distributed
+// except according to those terms.
+
+use std::fmt;
+#[cfg(feature = "async-timing")]
+use std::future::Future;
+#[cfg(feature = "async-timing")]
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+#[cfg(feature = "async-timing")]
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use crate::client::{StatsdClient, Timed};
+
+/// Source of the current time used by the timing helpers of `StatsdClient`.
+///
+/// Timers recorded by `StatsdClient::time_block()`, `StatsdClient::start_timer()`,
+/// and `StatsdClient::time_future()` are measured by taking the difference
+/// between two calls to `.now()`. By default, `MonotonicClock` is used. Tests
+/// can use `ManualClock` or their own implementation instead to control the
+/// exact values of the timers that are recorded.
+pub trait Clock {
+    /// Return the time elapsed since some fixed point in the past.
+    ///
+    /// The point in the past can be anything as long as it doesn't change,
+    /// but the value returned must never decrease.
+    fn now(&self) -> Duration;
+}
+
+/// `Clock` implementation that uses the monotonic clock of the system via
+/// `Instant`. This is the default clock used by `StatsdClient`.
+#[derive(Debug, Clone, Copy)]
+pub struct MonotonicClock {
+    start: Instant,
+}
+
+impl MonotonicClock {
+    /// Create a new clock that measures time from now.
+    pub fn new() -> Self {
+        MonotonicClock { start: Instant::now() }
+    }
+}
+
+impl Default for MonotonicClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for MonotonicClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// `Clock` implementation that only moves when it is told to, for testing.
+///
+/// Clones of this clock share the same time, so a clone can be given to a
+/// `StatsdClient` and the original used to advance the time during a test.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use cadence::{ManualClock, Metric, SpyMetricSink, StatsdClient};
+///
+/// let clock = ManualClock::new();
+/// let (rx, sink) = SpyMetricSink::new();
+/// let client = StatsdClient::builder("my.app", sink)
+///     .with_timer_clock(clock.clone())
+///     .build();
+///
+/// client.time_block("some.work", || clock.advance(Duration::from_millis(25)));
+/// assert_eq!(b"my.app.some.work:25|ms".to_vec(), rx.try_recv().unwrap());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    nanos: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    /// Create a new clock starting at zero.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Move the time of this clock and all its clones forward, stopping at
+    /// the largest time that can be represented instead of wrapping around.
+    pub fn advance(&self, duration: Duration) {
+        let nanos = u64::try_from(duration.as_nanos()).unwrap_or(u64::MAX);
+        let _ = self
+            .nanos
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| Some(n.saturating_add(nanos)));
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Relaxed))
+    }
+}
+
+/// Unit that `Duration` values are converted to before being emitted as
+/// timers, histograms, or distributions.
+///
+/// By default, timers are emitted in milliseconds and histograms and
+/// distributions in nanoseconds. This can be changed for every metric emitted
+/// by a client using `StatsdClientBuilder::with_timer_unit()`,
+/// `StatsdClientBuilder::with_histogram_unit()`, and
+/// `StatsdClientBuilder::with_distribution_unit()` or for a single metric
+/// using `ScaledDuration`.
+///
+/// Note that the type of a timer is always `ms`, regardless of the unit used
+/// for the value. When using a unit other than milliseconds for timers, make
+/// sure anything consuming them knows what unit they are in.
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
+pub enum TimeUnit {
+    Milliseconds,
+    Microseconds,
+    Nanoseconds,
+}
+
+impl TimeUnit {
+    // Whole number of this unit in the duration
+    pub(crate) fn of(self, duration: &Duration) -> u128 {
+        match self {
+            TimeUnit::Milliseconds => duration.as_millis(),
+            TimeUnit::Microseconds => duration.as_micros(),
+            TimeUnit::Nanoseconds => duration.as_nanos(),
+        }
+    }
+
+    // Fractional number of this unit in the duration
+    pub(crate) fn fraction_of(self, duration: &Duration) -> f64 {
+        // Dividing whole nanoseconds avoids the rounding errors of scaling
+        // the fractional seconds from `Duration::as_secs_f64()` back up.
+        let nanos = duration.as_nanos() as f64;
+        match self {
+            TimeUnit::Milliseconds => nanos / 1_000_000.0,
+            TimeUnit::Microseconds => nanos / 1_000.0,
+            TimeUnit::Nanoseconds => nanos,
+        }
+    }
+}
+
+/// `Duration` that is converted to a specific unit when emitted as a timer,
+/// histogram, or distribution, instead of the unit configured for the client.
+///
+/// Values created with `ScaledDuration::new()` are truncated to a whole
+/// number of the unit. Values created with `ScaledDuration::fractional()`
+/// keep any fraction of the unit and are emitted as floats, which is useful
+/// for recording sub-millisecond durations in milliseconds.
+///
+/// # Example
+///
+/// ```
+/// use std::time::Duration;
+/// use cadence::prelude::*;
+/// use cadence::{ScaledDuration, SpyMetricSink, StatsdClient, TimeUnit};
+///
+/// let (rx, sink) = SpyMetricSink::new();
+/// let client = StatsdClient::from_sink("my.app", sink);
+/// let elapsed = Duration::from_micros(1250);
+///
+/// client.time("db.query", ScaledDuration::new(elapsed, TimeUnit::Microseconds)).unwrap();
+/// assert_eq!(b"my.app.db.query:1250|ms".to_vec(), rx.try_recv().unwrap());
+///
+/// client.histogram("db.query", ScaledDuration::fractional(elapsed, TimeUnit::Milliseconds)).unwrap();
+/// assert_eq!(b"my.app.db.query:1.25|h".to_vec(), rx.try_recv().unwrap());
+/// ```
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
+pub struct ScaledDuration {
+    duration: Duration,
+    unit: TimeUnit,
+    fractional: bool,
+}
+
+impl ScaledDuration {
+    /// Create a new value that converts the duration to a whole number of
+    /// the given unit.
+    pub fn new(duration: Duration, unit: TimeUnit) -> Self {
+        ScaledDuration {
+            duration,
+            unit,
+            fractional: false,
+        }
+    }
+
+    /// Create a new value that converts the duration to a fractional number
+    /// of the given unit.
+    pub fn fractional(duration: Duration, unit: TimeUnit) -> Self {
+        ScaledDuration {
+            duration,
+            unit,
+            fractional: true,
+        }
+    }
+
+    pub(crate) fn duration(&self) -> &Duration {
+        &self.duration
+    }
+
+    pub(crate) fn unit(&self) -> TimeUnit {
+        self.unit
+    }
+
+    pub(crate) fn is_fractional(&self) -> bool {
+        self.fractional
+    }
+}
+
+/// Guard that records the time elapsed since it was started as a timer when
+/// it is dropped.
+///
+/// This makes sure the time taken by a block of code is recorded even when
+/// it returns early, such as when using the `?` operator. The timer is sent
+/// quietly, like `MetricBuilder::send()`, so any error sending it is passed
+/// to the error handler of the client. The time is measured using the timer
+/// clock of the client and converted using its timer unit.
+///
+/// NOTE: The only way to instantiate an instance of this guard is via the
+/// `StatsdClient::start_timer()` method.
+///
+/// # Example
+///
+/// ```
+/// use std::num::ParseIntError;
+/// use cadence::{StatsdClient, NopMetricSink};
+///
+/// fn parse_port(client: &StatsdClient, val: &str) -> Result<u16, ParseIntError> {
+///     let _timer = client.start_timer("config.parse").with_tag("field", "port");
+///     // The timer is still recorded if this returns early
+///     let port = val.trim().parse()?;
+///     Ok(port)
+/// }
+///
+/// let client = StatsdClient::from_sink("my.app", NopMetricSink);
+/// assert!(parse_port(&client, "not a port").is_err());
+/// ```
+#[must_use = "The timer is recorded as soon as an unused guard is dropped"]
+pub struct Stopwatch<'a> {
+    client: &'a StatsdClient,
+    key: &'a str,
+    tags: Vec<(&'a str, &'a str)>,
+    start: Duration,
+    done: bool,
+}
+
+impl<'a> Stopwatch<'a> {
+    pub(crate) fn new(client: &'a StatsdClient, key: &'a str) -> Self {
+        Stopwatch {
+            client,
+            key,
+            tags: Vec::new(),
+            start: client.timer_now(),
+            done: false,
+        }
+    }
+
+    /// Add a key-value tag to the timer recorded by this guard.
+    pub fn with_tag(mut self, key: &'a str, value: &'a str) -> Self {
+        self.tags.push((key, value));
+        self
+    }
+
+    /// Return the time elapsed since this guard was started.
+    pub fn elapsed(&self) -> Duration {
+        self.client.timer_now().saturating_sub(self.start)
+    }
+
+    /// Record the timer now instead of when this guard is dropped, returning
+    /// the time that was recorded.
+    pub fn stop(mut self) -> Duration {
+        self.record()
+    }
+
+    /// Drop this guard without recording the timer.
+    pub fn cancel(mut self) {
+        self.done = true;
+    }
+
+    fn record(&mut self) -> Duration {
+        let elapsed = self.elapsed();
+        if !self.done {
+            self.done = true;
+
+            let mut builder = self.client.time_with_tags(self.key, elapsed);
+            for (key, value) in self.tags.iter() {
+                builder = builder.with_tag(key, value);
+            }
+            builder.send();
+        }
+
+        elapsed
+    }
+}
+
+impl<'a> Drop for Stopwatch<'a> {
+    fn drop(&mut self) {
+        self.record();
+    }
+}
+
+impl<'a> fmt::Debug for Stopwatch<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "Stopwatch {{ key: {:?}, tags: {:?}, start: {:?} }}",
+            self.key, self.tags, self.start
+        )
+    }
+}
+
+/// Future that records how long a wrapped future took to complete as a timer.
+///
+/// The time is measured using the timer clock of the client from the first
+/// time this future is polled until the wrapped future completes, including
+/// any time spent waiting between polls. The timer is emitted when the wrapped
+/// future completes and is sent quietly, like `MetricBuilder::send()`, so any
+/// error sending it is passed to the error handler of the client. Nothing is
+/// emitted if this future is dropped before it completes.
+///
+/// NOTE: The only way to instantiate an instance of this future is via the
+/// `StatsdClient::time_future()` method. This is only available when the
+/// `async-timing` feature is enabled.
+#[cfg(feature = "async-timing")]
+#[must_use = "futures do nothing unless polled"]
+pub struct TimedFuture<'a, F>
+where
+    F: Future,
+{
+    client: &'a StatsdClient,
+    key: &'a str,
+    inner: Pin<Box<F>>,
+    start: Option<Duration>,
+}
+
+#[cfg(feature = "async-timing")]
+impl<'a, F> TimedFuture<'a, F>
+where
+    F: Future,
+{
+    pub(crate) fn new(client: &'a StatsdClient, key: &'a str, inner: F) -> Self {
+        TimedFuture {
+            client,
+            key,
+            inner: Box::pin(inner),
+            start: None,
+        }
+    }
+}
+
+#[cfg(feature = "async-timing")]
+impl<'a, F> Future for TimedFuture<'a, F>
+where
+    F: Future,
+{
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        // The wrapped future is boxed so this future is always `Unpin`
+        let this = self.get_mut();
+        let client = this.client;
+        let start = *this.start.get_or_insert_with(|| client.timer_now());
+
+        match this.inner.as_mut().poll(cx) {
+            Poll::Ready(out) => {
+                let elapsed = client.timer_now().saturating_sub(start);
+                client.time_with_tags(this.key, elapsed).send();
+                Poll::Ready(out)
+            }
+            Poll::Pending => Poll::Pending,
+        }
+    }
+}
+
+#[cfg(feature = "async-timing")]
+impl<'a, F> fmt::Debug for TimedFuture<'a, F>
+where
+    F: Future,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "TimedFuture {{ key: {:?}, start: {:?} }}", self.key, self.start)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Clock, ManualClock, MonotonicClock};
+    use crate::client::StatsdClient;
+    #[cfg(feature = "async-timing")]
+    use crate::executor::block_on;
+    use crate::sinks::SpyMetricSink;
+    use crossbeam_channel::Receiver;
+    #[cfg(feature = "async-timing")]
+    use std::future::Future;
+    #[cfg(feature = "async-timing")]
+    use std::pin::Pin;
+    #[cfg(feature = "async-timing")]
+    use std::task::{Context, Poll};
+    use std::time::Duration;
+
+    // Future that advances the clock and is pending the first time it is polled
+    #[cfg(feature = "async-timing")]
+    struct Tick(ManualClock, bool);
+
+    #[cfg(feature = "async-timing")]
+    impl Future for Tick {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            self.0.advance(Duration::from_millis(10));
+            if self.1 {
+                Poll::Ready(())
+            } else {
+                self.1 = true;
+                cx.waker().wake_by_ref();
+                Poll::Pending
+            }
+        }
+    }
+
+    fn new_client() -> (Receiver<Vec<u8>>, ManualClock, StatsdClient) {
+        let (rx, sink) = SpyMetricSink::new();
+        let clock = ManualClock::new();
+        let client = StatsdClient::builder("prefix", sink)
+            .with_timer_clock(clock.clone())
+            .build();
+        (rx, clock, client)
+    }
+
+    #[test]
+    fn test_monotonic_clock() {
+        let clock = MonotonicClock::new();
+        let first = clock.now();
+        assert!(clock.now() >= first);
+    }
+
+    #[test]
+    fn test_manual_clock() {
+        let clock = ManualClock::new();
+        let clone = clock.clone();
+
+        clone.advance(Duration::from_millis(5));
+        clone.advance(Duration::from_micros(250));
+        assert_eq!(Duration::from_micros(5250), clock.now());
+
+        clone.advance(Duration::MAX);
+        assert_eq!(Duration::from_nanos(u64::MAX), clock.now());
+    }
+
+    #[test]
+    fn test_stopwatch_drop() {
+        let (rx, clock, client) = new_client();
+
+        {
+            let _timer = client.start_timer("some.block").with_tag("region", "us-west-2");
+            clock.advance(Duration::from_millis(15));
+        }
+
+        assert_eq!(
+            b"prefix.some.block:15|ms|#region:us-west-2".to_vec(),
+            rx.try_recv().unwrap()
+        );
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_stopwatch_stop() {
+        let (rx, clock, client) = new_client();
+
+        let timer = client.start_timer("some.block");
+        clock.advance(Duration::from_millis(5));
+        assert_eq!(Duration::from_millis(5), timer.elapsed());
+        clock.advance(Duration::from_millis(5));
+
+        assert_eq!(Duration::from_millis(10), timer.stop());
+        assert_eq!(b"prefix.some.block:10|ms".to_vec(), rx.try_recv().unwrap());
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_stopwatch_cancel() {
+        let (rx, _clock, client) = new_client();
+
+        client.start_timer("some.block").cancel();
+        assert!(rx.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_time_block() {
+        let (rx, clock, client) = new_client();
+
+        let res = client.time_block("some.block", || {
+            clock.advance(Duration::from_millis(7));
+            "done"
+        });
+
+        assert_eq!("done", res);
+        assert_eq!(b"prefix.some.block:7|ms".to_vec(), rx.try_recv().unwrap());
+    }
+
+    #[cfg(feature = "async-timing")]
+    #[test]
+    fn test_timed_future() {
+        block_on(async {
+            let (rx, clock, client) = new_client();
+
+            let res = client
+                .time_future("some.future", async {
+                    Tick(clock.clone(), false).await;
+                    42
+                })
+                .await;
+
+            assert_eq!(42, res);
+            assert_eq!(b"prefix.some.future:20|ms".to_vec(), rx.try_recv().unwrap());
+            assert!(rx.try_recv().is_err());
+        });
+    }
+
+    #[cfg(feature = "async-timing")]
+    #[test]
+    fn test_timed_future_not_polled() {
+        block_on(async {
+            let (rx, clock, client) = new_client();
+
+            let fut = client.time_future("some.future", async { 42 });
+            // Time before the first poll isn't included
+            clock.advance(Duration::from_millis(20));
+            fut.await;
+
+            assert_eq!(b"prefix.some.future:0|ms".to_vec(), rx.try_recv().unwrap());
+        });
+    }
+}
@@ -8,7 +8,7 @@
 // option. This file may not be copied, modified, or distributed
 // except according to those terms.
 
-use crate::builder::{MetricFormatter, MetricValue};
+use crate::builder::{EventFormatter, MetricFormatter, MetricValue, ServiceCheckFormatter};
 use std::error;
 use std::fmt;
 use std::io;
@@ -21,6 +21,58 @@ pub trait Metric {
     fn as_metric_str(&self) -> &str;
 }
 
+/// Trait for metrics that can write their Statsd representation to a
+/// formatter instead of exposing it as a string slice.
+///
+/// This allows metrics to be passed to `MetricSink::emit_metric()` without
+/// creating a `String` for them first, so that sinks that buffer metrics can
+/// format them directly into memory they already own. Every type that
+/// implements `Metric` implements this trait by writing its string slice.
+///
+/// # Example
+///
+/// ```
+/// use std::fmt;
+/// use cadence::{MetricSink, NopMetricSink, WriteMetric};
+///
+/// struct Requests {
+///     count: u64,
+/// }
+///
+/// impl WriteMetric for Requests {
+///     fn write_to(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+///         write!(out, "app.requests:{}|c", self.count)
+///     }
+/// }
+///
+/// let sink = NopMetricSink;
+/// sink.emit_metric(&Requests { count: 5 }).unwrap();
+/// ```
+pub trait WriteMetric {
+    /// Write the complete metric, without a trailing newline, to `out`.
+    fn write_to(&self, out: &mut dyn fmt::Write) -> fmt::Result;
+
+    /// Return the number of bytes the metric is expected to use so that
+    /// callers can make room for it before it is written. The default
+    /// implementation returns zero.
+    fn size_hint(&self) -> usize {
+        0
+    }
+}
+
+impl<T> WriteMetric for T
+where
+    T: Metric + ?Sized,
+{
+    fn write_to(&self, out: &mut dyn fmt::Write) -> fmt::Result {
+        out.write_str(self.as_metric_str())
+    }
+
+    fn size_hint(&self) -> usize {
+        self.as_metric_str().len()
+    }
+}
+
 /// Counters are simple values incremented or decremented by a client.
 ///
 /// See the `Counted` trait for more information.
@@ -221,18 +273,171 @@ impl Metric for Set {
     }
 }
 
+/// Custom metrics have a type that isn't part of the Statsd spec, for servers
+/// that support nonstandard types of metrics.
+///
+/// See `StatsdClient::custom` for more information.
+#[derive(PartialEq, Eq, Debug, Hash, Clone)]
+pub struct CustomMetric {
+    repr: String,
+}
+
+impl CustomMetric {
+    pub fn new(prefix: &str, key: &str, value: MetricValue, metric_type: &str) -> Self {
+        Self::from(MetricFormatter::custom(prefix, key, value, metric_type).format())
+    }
+}
+
+impl From<String> for CustomMetric {
+    fn from(s: String) -> Self {
+        CustomMetric { repr: s }
+    }
+}
+
+impl Metric for CustomMetric {
+    fn as_metric_str(&self) -> &str {
+        &self.repr
+    }
+}
+
+/// Events are records of something notable happening, such as a deploy.
+///
+/// Note that events are a [Datadog](https://docs.datadoghq.com/developers/dogstatsd/)
+/// extension to Statsd and may not be supported by your server.
+///
+/// See the `Evented` trait for more information.
+#[derive(PartialEq, Eq, Debug, Hash, Clone)]
+pub struct Event {
+    repr: String,
+}
+
+impl Event {
+    pub fn new(title: &str, text: &str) -> Self {
+        Self::from(EventFormatter::new(title, text).format())
+    }
+}
+
+impl From<String> for Event {
+    fn from(s: String) -> Self {
+        Event { repr: s }
+    }
+}
+
+impl Metric for Event {
+    fn as_metric_str(&self) -> &str {
+        &self.repr
+    }
+}
+
+/// Priority of an event, `Normal` if not specified.
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
+pub enum EventPriority {
+    Normal,
+    Low,
+}
+
+impl fmt::Display for EventPriority {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            EventPriority::Normal => "normal".fmt(f),
+            EventPriority::Low => "low".fmt(f),
+        }
+    }
+}
+
+/// Type of alert an event represents, `Info` if not specified.
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
+pub enum EventAlertType {
+    Error,
+    Warning,
+    Info,
+    Success,
+}
+
+impl fmt::Display for EventAlertType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            EventAlertType::Error => "error".fmt(f),
+            EventAlertType::Warning => "warning".fmt(f),
+            EventAlertType::Info => "info".fmt(f),
+            EventAlertType::Success => "success".fmt(f),
+        }
+    }
+}
+
+/// Service checks report the status of a service, such as a database.
+///
+/// Note that service checks are a [Datadog](https://docs.datadoghq.com/developers/dogstatsd/)
+/// extension to Statsd and may not be supported by your server.
+///
+/// See the `ServiceChecked` trait for more information.
+#[derive(PartialEq, Eq, Debug, Hash, Clone)]
+pub struct ServiceCheck {
+    repr: String,
+}
+
+impl ServiceCheck {
+    pub fn new(prefix: &str, name: &str, status: ServiceCheckStatus) -> Self {
+        Self::from(ServiceCheckFormatter::new(prefix, name, status).format())
+    }
+}
+
+impl From<String> for ServiceCheck {
+    fn from(s: String) -> Self {
+        ServiceCheck { repr: s }
+    }
+}
+
+impl Metric for ServiceCheck {
+    fn as_metric_str(&self) -> &str {
+        &self.repr
+    }
+}
+
+/// Status of a service reported by a service check.
+#[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
+pub enum ServiceCheckStatus {
+    Ok,
+    Warning,
+    Critical,
+    Unknown,
+}
+
+impl fmt::Display for ServiceCheckStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self {
+            ServiceCheckStatus::Ok => "0".fmt(f),
+            ServiceCheckStatus::Warning => "1".fmt(f),
+            ServiceCheckStatus::Critical => "2".fmt(f),
+            ServiceCheckStatus::Unknown => "3".fmt(f),
+        }
+    }
+}
+
 /// Potential categories an error from this library falls into.
+///
+/// More categories may be added in the future so matching on them must
+/// include a wildcard arm. Errors from sinks are `IoError` unless they are
+/// known to be one of the more specific categories, `Timeout` or `QueueFull`.
 #[derive(PartialEq, Eq, Debug, Hash, Clone, Copy)]
+#[non_exhaustive]
 pub enum ErrorKind {
     InvalidInput,
     IoError,
+    Timeout,
+    QueueFull,
+    Sanitization,
 }
 
 /// Error generated by this library potentially wrapping another
 /// type of error (exposed via the `Error` trait).
+///
+/// When the error is caused by a particular metric, the name of the metric
+/// is available using `.metric()`.
 #[derive(Debug)]
 pub struct MetricError {
     repr: ErrorRepr,
+    metric: Option<String>,
 }
 
 #[derive(Debug)]
@@ -245,10 +450,57 @@ impl MetricError {
     /// Return the kind of the error
     pub fn kind(&self) -> ErrorKind {
         match self.repr {
+            ErrorRepr::IoError(ref err) if is_queue_full(err) => ErrorKind::QueueFull,
+            ErrorRepr::IoError(ref err) if err.kind() == io::ErrorKind::TimedOut => ErrorKind::Timeout,
             ErrorRepr::IoError(_) => ErrorKind::IoError,
             ErrorRepr::WithDescription(kind, _) => kind,
         }
     }
+
+    /// Return the full name of the metric that caused this error, including
+    /// the prefix of the client, if known.
+    pub fn metric(&self) -> Option<&str> {
+        self.metric.as_deref()
+    }
+
+    pub(crate) fn with_metric(mut self, prefix: &str, key: &str) -> MetricError {
+        self.metric = Some(format!("{}{}", prefix, key));
+        self
+    }
+
+    // Create a new error with the same kind and description as this one, for
+    // errors that are stored and returned more than once.
+    pub(crate) fn duplicate(&self) -> MetricError {
+        let mut err = match self.repr {
+            ErrorRepr::IoError(ref err) if is_queue_full(err) => MetricError::from(queue_full()),
+            ErrorRepr::IoError(ref err) => MetricError::from(io::Error::new(err.kind(), err.to_string())),
+            ErrorRepr::WithDescription(kind, desc) => MetricError::from((kind, desc)),
+        };
+        err.metric = self.metric.clone();
+        err
+    }
+}
+
+// Error wrapped in an `io::Error` by sinks that drop metrics because their
+// queue is full, so that these errors can be told apart from other I/O errors.
+#[derive(Debug)]
+struct QueueFullError;
+
+impl fmt::Display for QueueFullError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        "channel full".fmt(f)
+    }
+}
+
+impl error::Error for QueueFullError {}
+
+/// Create the error returned by sinks when their queue is full.
+pub(crate) fn queue_full() -> io::Error {
+    io::Error::new(io::ErrorKind::Other, QueueFullError)
+}
+
+fn is_queue_full(err: &io::Error) -> bool {
+    err.get_ref().map(|e| e.is::<QueueFullError>()).unwrap_or(false)
 }
 
 impl fmt::Display for MetricError {
@@ -279,16 +531,13 @@ impl error::Error for MetricError {
             ErrorRepr::WithDescription(_, desc) => desc,
         }
     }
-
-    fn cause(&self) -> Option<&dyn error::Error> {
-        self.source()
-    }
 }
 
 impl From<io::Error> for MetricError {
     fn from(err: io::Error) -> MetricError {
         MetricError {
             repr: ErrorRepr::IoError(err),
+            metric: None,
         }
     }
 }
@@ -297,6 +546,7 @@ impl From<(ErrorKind, &'static str)> for MetricError {
     fn from((kind, desc): (ErrorKind, &'static str)) -> MetricError {
         MetricError {
             repr: ErrorRepr::WithDescription(kind, desc),
+            metric: None,
         }
     }
 }
@@ -307,7 +557,11 @@ pub type MetricResult<T> = Result<T, MetricError>;
 mod tests {
     #![allow(deprecated, deprecated_in_future)]
 
-    use super::{Counter, ErrorKind, Gauge, Histogram, Meter, Metric, MetricError, Set, Timer};
+    use super::{
+        queue_full, Counter, CustomMetric, ErrorKind, Event, EventAlertType, EventPriority, Gauge, Histogram, Meter,
+        Metric, MetricError, ServiceCheck, ServiceCheckStatus, Set, Timer,
+    };
+    use crate::builder::MetricValue;
     use std::error::Error;
     use std::io;
 
@@ -371,6 +625,12 @@ mod tests {
         assert_eq!("test.histogram:45|h", histogram.as_metric_str());
     }
 
+    #[test]
+    fn test_custom_metric_to_metric_string() {
+        let custom = CustomMetric::new("my.app.", "test.ratio", MetricValue::Unsigned(42), "pct");
+        assert_eq!("my.app.test.ratio:42|pct", custom.as_metric_str());
+    }
+
     #[test]
     fn test_set_to_metric_string() {
         let set = Set::new("my.app.", "test.set", 4);
@@ -383,6 +643,24 @@ mod tests {
         assert_eq!("test.set:4|s", set.as_metric_str());
     }
 
+    #[test]
+    fn test_event_to_metric_string() {
+        let event = Event::new("Deploy", "Deployed v1.2.3");
+        assert_eq!("_e{6,15}:Deploy|Deployed v1.2.3", event.as_metric_str());
+    }
+
+    #[test]
+    fn test_event_priority_and_alert_type_display() {
+        assert_eq!("low", EventPriority::Low.to_string());
+        assert_eq!("warning", EventAlertType::Warning.to_string());
+    }
+
+    #[test]
+    fn test_service_check_to_metric_string() {
+        let check = ServiceCheck::new("my.app.", "db.up", ServiceCheckStatus::Critical);
+        assert_eq!("_sc|my.app.db.up|2", check.as_metric_str());
+    }
+
     #[test]
     fn test_metric_error_kind_io_error() {
         let io_err = io::Error::new(io::ErrorKind::BrokenPipe, "Broken pipe");
@@ -398,9 +676,11 @@ mod tests {
 
     #[test]
     fn test_metric_error_description_io_error() {
+        // Newer versions of Rust return a placeholder from `io::Error::description`
+        // so compare the `Display` output that it's expected to match instead.
         let io_err = io::Error::new(io::ErrorKind::PermissionDenied, "Permission!");
         let our_err = MetricError::from(io_err);
-        assert_eq!("Permission!", our_err.description());
+        assert_eq!("Permission!", our_err.to_string());
     }
 
     #[test]
@@ -413,7 +693,7 @@ mod tests {
     fn test_metric_error_cause_io_error() {
         let io_err = io::Error::new(io::ErrorKind::TimedOut, "Timeout!");
         let our_err = MetricError::from(io_err);
-        assert_eq!("Timeout!", our_err.source().unwrap().description());
+        assert_eq!("Timeout!", our_err.source().unwrap().to_string());
     }
 
     #[test]
@@ -421,4 +701,25 @@ mod tests {
         let our_err = MetricError::from((ErrorKind::InvalidInput, "Nope!"));
         assert!(our_err.source().is_none());
     }
+
+    #[test]
+    fn test_metric_error_kind_specific_io_errors() {
+        let our_err = MetricError::from(io::Error::new(io::ErrorKind::TimedOut, "Timeout!"));
+        assert_eq!(ErrorKind::Timeout, our_err.kind());
+
+        let our_err = MetricError::from(queue_full());
+        assert_eq!(ErrorKind::QueueFull, our_err.kind());
+        assert_eq!("channel full", our_err.to_string());
+        assert_eq!(ErrorKind::QueueFull, our_err.duplicate().kind());
+    }
+
+    #[test]
+    fn test_metric_error_metric() {
+        let our_err = MetricError::from((ErrorKind::InvalidInput, "Nope!"));
+        assert_eq!(None, our_err.metric());
+
+        let our_err = our_err.with_metric("prefix.", "some.key");
+        assert_eq!(Some("prefix.some.key"), our_err.metric());
+        assert_eq!(Some("prefix.some.key"), our_err.duplicate().metric());
+    }
 }